@@ -0,0 +1,60 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mm_streamer::rtcp::{CompoundPacket, RtcpPacket};
+
+fuzz_target!(|data: &[u8]| {
+    let compound = CompoundPacket::new(data.to_vec());
+    let _ = compound.is_reduced_size();
+    for packet in compound.iter().flatten() {
+        let blocks = match &packet {
+            RtcpPacket::SenderReport(sr) => {
+                let _ = (
+                    sr.ssrc(),
+                    sr.ntp_timestamp(),
+                    sr.rtp_ts(),
+                    sr.packets_sent(),
+                    sr.octets_sent(),
+                );
+                sr.report_blocks()
+            }
+            RtcpPacket::ReceiverReport(rr) => {
+                let _ = rr.ssrc();
+                rr.report_blocks()
+            }
+            RtcpPacket::Sdes(sdes) => {
+                for chunk in sdes.chunks() {
+                    for item in &chunk.items {
+                        let _ = (item.item_type(), item.str());
+                    }
+                }
+                Vec::new()
+            }
+            RtcpPacket::Bye(bye) => {
+                let _ = (bye.sources(), bye.reason());
+                Vec::new()
+            }
+            RtcpPacket::App(app) => {
+                let _ = (app.subtype(), app.ssrc(), app.name(), app.data());
+                Vec::new()
+            }
+            RtcpPacket::Feedback(fb) => {
+                let _ = (fb.sender_ssrc(), fb.media_ssrc(), fb.nacks(), fb.is_pli());
+                Vec::new()
+            }
+            RtcpPacket::Other(other) => {
+                let _ = other.header().length();
+                Vec::new()
+            }
+        };
+        for block in blocks {
+            let _ = (
+                block.ssrc(),
+                block.fraction_lost(),
+                block.packets_lost(),
+                block.highest_sequence(),
+            );
+            let _ = (block.jitter(), block.lsr(), block.dlsr());
+        }
+    }
+});
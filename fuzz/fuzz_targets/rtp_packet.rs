@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mm_streamer::rtp::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = Packet::new(data.to_vec()) {
+        let _ = (packet.marker(), packet.payload_type(), packet.sequence_number());
+        let _ = (packet.timestamp(), packet.ssrc(), packet.csrc());
+        let _ = packet.data_bytes();
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mm_streamer::rtsp::ResponseParser;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = ResponseParser::new();
+    while let Ok(Some(_)) = parser.parse_next(data) {}
+    let _ = (parser.missing_bytes(), parser.response_bytes(), parser.parsed_bytes());
+});
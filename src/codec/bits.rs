@@ -0,0 +1,91 @@
+/// Reads bits of a raw byte sequence payload, most significant first
+pub(crate) struct BitReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.buf.len() * 8 - self.pos
+    }
+
+    pub(crate) fn read(&mut self, bits: u8) -> Option<u32> {
+        if bits > 32 || bits as usize > self.remaining() {
+            return None;
+        }
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let bit = (self.buf[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.pos += 1;
+        }
+        Some(value as u32)
+    }
+
+    pub(crate) fn read_flag(&mut self) -> Option<bool> {
+        self.read(1).map(|b| b == 1)
+    }
+
+    pub(crate) fn skip(&mut self, bits: usize) -> Option<()> {
+        if bits > self.remaining() {
+            return None;
+        }
+        self.pos += bits;
+        Some(())
+    }
+
+    /// Unsigned Exp-Golomb code
+    pub(crate) fn read_ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while !self.read_flag()? {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        let suffix = self.read(zeros)?;
+        Some(((1u64 << zeros) - 1 + suffix as u64) as u32)
+    }
+
+    /// Signed Exp-Golomb code
+    pub(crate) fn read_se(&mut self) -> Option<i32> {
+        let value = self.read_ue()? as i64;
+        Some(if value % 2 == 1 { (value + 1) / 2 } else { -value / 2 } as i32)
+    }
+}
+
+/// Removes the emulation prevention bytes of a NAL unit
+pub(crate) fn to_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    let mut zeros = 0;
+    for &byte in nal {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exp_golomb() {
+        // 1, 010, 011, 00100, 00101
+        let mut reader = BitReader::new(&[0b1010_0110, 0b0100_0010, 0b1000_0000]);
+        assert_eq!(reader.read_ue(), Some(0));
+        assert_eq!(reader.read_ue(), Some(1));
+        assert_eq!(reader.read_se(), Some(-1));
+        assert_eq!(reader.read_ue(), Some(3));
+        assert_eq!(reader.read_se(), Some(-2));
+        assert_eq!(to_rbsp(&[0, 0, 3, 1, 0, 0, 3]), [0, 0, 1, 0, 0]);
+    }
+}
@@ -0,0 +1,12 @@
+mod bits;
+mod nal;
+mod sei;
+
+pub use nal::is_random_access;
+pub use nal::is_sei;
+pub use nal::nal_type;
+pub use nal::to_annex_b;
+pub use nal::NalUnits;
+pub use sei::ClockTimestamp;
+pub use sei::SeiMessage;
+pub use sei::SeiParser;
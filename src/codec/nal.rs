@@ -0,0 +1,105 @@
+use crate::types::FrameType;
+
+/// NAL units of H.264/H.265 data in Annex B format, without start codes
+pub struct NalUnits<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> NalUnits<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let start = find_start_code(data).map_or(data.len(), |(pos, len)| pos + len);
+        Self { data: &data[start..] }
+    }
+}
+
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        while !self.data.is_empty() {
+            let (nal, rest) = match find_start_code(self.data) {
+                Some((pos, len)) => (&self.data[..pos], &self.data[pos + len..]),
+                None => (self.data, &[][..]),
+            };
+            self.data = rest;
+            if !nal.is_empty() {
+                return Some(nal);
+            }
+        }
+        None
+    }
+}
+
+/// Position and length of the first 3 or 4 byte start code
+fn find_start_code(data: &[u8]) -> Option<(usize, usize)> {
+    let pos = data.windows(3).position(|w| w == [0, 0, 1])?;
+    if pos > 0 && data[pos - 1] == 0 {
+        Some((pos - 1, 4))
+    } else {
+        Some((pos, 3))
+    }
+}
+
+/// Type of a NAL unit, None for frame types that are not made of NAL units
+pub fn nal_type(frame_type: FrameType, nal: &[u8]) -> Option<u8> {
+    let header = *nal.first()?;
+    match frame_type {
+        FrameType::H264 => Some(header & 0x1f),
+        FrameType::H265 => Some((header >> 1) & 0x3f),
+        _ => None,
+    }
+}
+
+/// Supplemental enhancement information, which decoders may skip
+pub fn is_sei(frame_type: FrameType, nal: &[u8]) -> bool {
+    match frame_type {
+        FrameType::H264 => nal_type(frame_type, nal) == Some(6),
+        // Prefix and suffix SEI
+        FrameType::H265 => matches!(nal_type(frame_type, nal), Some(39 | 40)),
+        _ => false,
+    }
+}
+
+/// IDR slice of H.264 or IRAP picture of H.265, decoding can start here
+pub fn is_random_access(frame_type: FrameType, nal: &[u8]) -> bool {
+    match frame_type {
+        FrameType::H264 => nal_type(frame_type, nal) == Some(5),
+        FrameType::H265 => matches!(nal_type(frame_type, nal), Some(16..=21)),
+        _ => false,
+    }
+}
+
+/// Writes the NAL units in Annex B format with 4 byte start codes
+pub fn to_annex_b<'a>(nals: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut data = Vec::new();
+    for nal in nals {
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(nal);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nal_units() {
+        let data = [0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 1, 0x65, 4, 5];
+        let nals: Vec<_> = NalUnits::new(&data).collect();
+        assert_eq!(nals, [&[0x67, 1, 2][..], &[0x68, 3], &[0x65, 4, 5]]);
+        assert!(is_random_access(FrameType::H264, nals[2]));
+        assert!(!is_random_access(FrameType::H264, nals[0]));
+        assert_eq!(to_annex_b(nals)[..9], [0, 0, 0, 1, 0x67, 1, 2, 0, 0]);
+        assert_eq!(NalUnits::new(&[1, 2, 3]).count(), 0);
+    }
+
+    #[test]
+    fn test_nal_types() {
+        // H.265 headers are two bytes, the type is in bits 1..7 of the first
+        assert!(is_sei(FrameType::H265, &[39 << 1, 1]));
+        assert!(is_random_access(FrameType::H265, &[19 << 1, 1]));
+        assert!(is_sei(FrameType::H264, &[0x06]));
+        assert_eq!(nal_type(FrameType::AAC, &[0x06]), None);
+    }
+}
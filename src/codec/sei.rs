@@ -0,0 +1,472 @@
+use super::bits::{to_rbsp, BitReader};
+use super::nal::{nal_type, NalUnits};
+use crate::types::FrameType;
+
+const H264_SPS: u8 = 7;
+const H264_SEI: u8 = 6;
+const H265_PREFIX_SEI: u8 = 39;
+const H265_SUFFIX_SEI: u8 = 40;
+
+const PIC_TIMING: u32 = 1;
+const USER_DATA_UNREGISTERED: u32 = 5;
+const TIME_CODE: u32 = 136;
+
+/// A time code of a picture, e.g. the capture time a camera also burns into the image
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    /// Frames since the start of the second
+    pub n_frames: u16,
+    /// Offset in units of the clock tick, 0 if not present
+    pub time_offset: i32,
+    pub discontinuity: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeiMessage {
+    /// H.264 picture timing or H.265 time code
+    PictureTiming(Vec<ClockTimestamp>),
+    /// Vendor data identified by a UUID
+    UserDataUnregistered { uuid: [u8; 16], data: Vec<u8> },
+}
+
+/// Fields of the H.264 SPS the picture timing syntax depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PicTimingParams {
+    // Lengths of cpb_removal_delay and dpb_output_delay if HRD parameters are present
+    delays: Option<(u8, u8)>,
+    pic_struct_present: bool,
+    time_offset_length: u8,
+}
+
+/// Parses SEI messages of H.264 and H.265 access units in Annex B format.
+///
+/// H.264 picture timing depends on the SPS, which is picked up from the
+/// frames passed in. It is usually sent with every keyframe.
+#[derive(Debug, Default, Clone)]
+pub struct SeiParser {
+    params: Option<PicTimingParams>,
+}
+
+impl SeiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, frame_type: FrameType, data: &[u8]) -> Vec<SeiMessage> {
+        let mut messages = Vec::new();
+        for nal in NalUnits::new(data) {
+            let header_len = match frame_type {
+                FrameType::H264 => 1,
+                FrameType::H265 => 2,
+                _ => return messages,
+            };
+            let Some(rbsp) = nal.get(header_len..).map(to_rbsp) else {
+                continue;
+            };
+            match (frame_type, nal_type(frame_type, nal)) {
+                (FrameType::H264, Some(H264_SPS)) => {
+                    if let Some(params) = parse_sps(&rbsp) {
+                        self.params = Some(params);
+                    }
+                }
+                (FrameType::H264, Some(H264_SEI)) | (FrameType::H265, Some(H265_PREFIX_SEI | H265_SUFFIX_SEI)) => {
+                    self.parse_sei(frame_type, &rbsp, &mut messages);
+                }
+                _ => {}
+            }
+        }
+        messages
+    }
+
+    fn parse_sei(&self, frame_type: FrameType, mut rbsp: &[u8], messages: &mut Vec<SeiMessage>) {
+        // Stop at the trailing bits
+        while rbsp.len() > 1 || rbsp.first().is_some_and(|b| *b != 0x80) {
+            let (Some(payload_type), Some(size)) = (read_ff_coded(&mut rbsp), read_ff_coded(&mut rbsp)) else {
+                return;
+            };
+            let Some(payload) = rbsp.get(..size as usize) else {
+                return;
+            };
+            rbsp = &rbsp[size as usize..];
+            let message = match (frame_type, payload_type) {
+                (_, USER_DATA_UNREGISTERED) if payload.len() >= 16 => Some(SeiMessage::UserDataUnregistered {
+                    uuid: payload[..16].try_into().unwrap(),
+                    data: payload[16..].to_vec(),
+                }),
+                (FrameType::H264, PIC_TIMING) => self
+                    .params
+                    .and_then(|params| parse_pic_timing(payload, params))
+                    .map(SeiMessage::PictureTiming),
+                (FrameType::H265, TIME_CODE) => parse_time_code(payload).map(SeiMessage::PictureTiming),
+                _ => None,
+            };
+            messages.extend(message);
+        }
+    }
+}
+
+/// Payload type and size are coded as a sum of 0xff bytes and a final byte
+fn read_ff_coded(buf: &mut &[u8]) -> Option<u32> {
+    let mut value = 0u32;
+    loop {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value = value.checked_add(byte as u32)?;
+        if byte != 0xff {
+            return Some(value);
+        }
+    }
+}
+
+fn parse_pic_timing(payload: &[u8], params: PicTimingParams) -> Option<Vec<ClockTimestamp>> {
+    let mut reader = BitReader::new(payload);
+    if let Some((cpb_removal, dpb_output)) = params.delays {
+        reader.skip(cpb_removal as usize + dpb_output as usize)?;
+    }
+    if !params.pic_struct_present {
+        return None;
+    }
+    let num_clock_ts = match reader.read(4)? {
+        0..=2 => 1,
+        3 | 4 | 7 => 2,
+        5 | 6 | 8 => 3,
+        _ => return None,
+    };
+    let mut timestamps = Vec::new();
+    for _ in 0..num_clock_ts {
+        if !reader.read_flag()? {
+            continue;
+        }
+        // ct_type, nuit_field_based_flag, counting_type
+        reader.skip(2 + 1 + 5)?;
+        timestamps.push(read_clock_timestamp(&mut reader, 8, Some(params.time_offset_length))?);
+    }
+    (!timestamps.is_empty()).then_some(timestamps)
+}
+
+fn parse_time_code(payload: &[u8]) -> Option<Vec<ClockTimestamp>> {
+    let mut reader = BitReader::new(payload);
+    let num_clock_ts = reader.read(2)?;
+    let mut timestamps = Vec::new();
+    for _ in 0..num_clock_ts {
+        if !reader.read_flag()? {
+            continue;
+        }
+        // units_field_based_flag, counting_type
+        reader.skip(1 + 5)?;
+        timestamps.push(read_clock_timestamp(&mut reader, 9, None)?);
+    }
+    (!timestamps.is_empty()).then_some(timestamps)
+}
+
+/// The common part of the H.264 and H.265 syntax starting at
+/// full_timestamp_flag. H.265 codes the time offset length inline.
+fn read_clock_timestamp(
+    reader: &mut BitReader,
+    n_frames_bits: u8,
+    time_offset_length: Option<u8>,
+) -> Option<ClockTimestamp> {
+    let full = reader.read_flag()?;
+    let discontinuity = reader.read_flag()?;
+    // cnt_dropped_flag
+    reader.skip(1)?;
+    let mut timestamp = ClockTimestamp {
+        n_frames: reader.read(n_frames_bits)? as u16,
+        discontinuity,
+        ..Default::default()
+    };
+    if full {
+        timestamp.seconds = reader.read(6)? as u8;
+        timestamp.minutes = reader.read(6)? as u8;
+        timestamp.hours = reader.read(5)? as u8;
+    } else if reader.read_flag()? {
+        timestamp.seconds = reader.read(6)? as u8;
+        if reader.read_flag()? {
+            timestamp.minutes = reader.read(6)? as u8;
+            if reader.read_flag()? {
+                timestamp.hours = reader.read(5)? as u8;
+            }
+        }
+    }
+    let time_offset_length = match time_offset_length {
+        Some(length) => length,
+        None => reader.read(5)? as u8,
+    };
+    if time_offset_length > 0 {
+        let value = reader.read(time_offset_length)?;
+        // Sign extension of the two's complement value
+        let shift = 32 - time_offset_length as u32;
+        timestamp.time_offset = ((value << shift) as i32) >> shift;
+    }
+    Some(timestamp)
+}
+
+/// Reads the SPS up to pic_struct_present_flag of the VUI
+fn parse_sps(rbsp: &[u8]) -> Option<PicTimingParams> {
+    let mut r = BitReader::new(rbsp);
+    let profile_idc = r.read(8)?;
+    // Constraint flags and level
+    r.skip(16)?;
+    r.read_ue()?;
+    if matches!(
+        profile_idc,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        let chroma_format_idc = r.read_ue()?;
+        if chroma_format_idc == 3 {
+            r.skip(1)?;
+        }
+        // Bit depths
+        r.read_ue()?;
+        r.read_ue()?;
+        r.skip(1)?;
+        if r.read_flag()? {
+            let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+            for i in 0..lists {
+                if r.read_flag()? {
+                    skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+    r.read_ue()?;
+    match r.read_ue()? {
+        0 => {
+            r.read_ue()?;
+        }
+        1 => {
+            r.skip(1)?;
+            r.read_se()?;
+            r.read_se()?;
+            for _ in 0..r.read_ue()? {
+                r.read_se()?;
+            }
+        }
+        _ => {}
+    }
+    // max_num_ref_frames, gaps_in_frame_num_value_allowed_flag, size in macroblocks
+    r.read_ue()?;
+    r.skip(1)?;
+    r.read_ue()?;
+    r.read_ue()?;
+    if !r.read_flag()? {
+        r.skip(1)?;
+    }
+    r.skip(1)?;
+    if r.read_flag()? {
+        for _ in 0..4 {
+            r.read_ue()?;
+        }
+    }
+    if !r.read_flag()? {
+        return Some(PicTimingParams {
+            delays: None,
+            pic_struct_present: false,
+            time_offset_length: 0,
+        });
+    }
+    parse_vui(&mut r)
+}
+
+fn parse_vui(r: &mut BitReader) -> Option<PicTimingParams> {
+    if r.read_flag()? && r.read(8)? == 255 {
+        // Extended sample aspect ratio
+        r.skip(32)?;
+    }
+    if r.read_flag()? {
+        r.skip(1)?;
+    }
+    if r.read_flag()? {
+        r.skip(4)?;
+        if r.read_flag()? {
+            r.skip(24)?;
+        }
+    }
+    if r.read_flag()? {
+        r.read_ue()?;
+        r.read_ue()?;
+    }
+    if r.read_flag()? {
+        r.skip(65)?;
+    }
+    let nal_hrd = if r.read_flag()? { Some(parse_hrd(r)?) } else { None };
+    let vcl_hrd = if r.read_flag()? { Some(parse_hrd(r)?) } else { None };
+    let hrd = nal_hrd.or(vcl_hrd);
+    if hrd.is_some() {
+        r.skip(1)?;
+    }
+    Some(PicTimingParams {
+        delays: hrd.map(|(cpb, dpb, _)| (cpb, dpb)),
+        pic_struct_present: r.read_flag()?,
+        time_offset_length: hrd.map_or(24, |(_, _, offset)| offset),
+    })
+}
+
+/// Returns the lengths of cpb_removal_delay, dpb_output_delay and time_offset
+fn parse_hrd(r: &mut BitReader) -> Option<(u8, u8, u8)> {
+    let cpb_cnt = r.read_ue()? + 1;
+    r.skip(8)?;
+    for _ in 0..cpb_cnt {
+        r.read_ue()?;
+        r.read_ue()?;
+        r.skip(1)?;
+    }
+    r.skip(5)?;
+    let cpb_removal = r.read(5)? as u8 + 1;
+    let dpb_output = r.read(5)? as u8 + 1;
+    let time_offset = r.read(5)? as u8;
+    Some((cpb_removal, dpb_output, time_offset))
+}
+
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Option<()> {
+    let mut last = 8;
+    let mut next = 8;
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.read_se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::to_annex_b;
+
+    /// Writes bits most significant first
+    #[derive(Default)]
+    struct BitWriter {
+        bits: Vec<bool>,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u32, bits: u8) -> &mut Self {
+            for i in (0..bits).rev() {
+                self.bits.push((value >> i) & 1 == 1);
+            }
+            self
+        }
+
+        fn ue(&mut self, value: u32) -> &mut Self {
+            let bits = 32 - (value + 1).leading_zeros() as u8;
+            self.write(0, bits - 1).write(value + 1, bits)
+        }
+
+        /// Pads with zero bits to whole bytes
+        fn finish(&mut self) -> Vec<u8> {
+            while !self.bits.len().is_multiple_of(8) {
+                self.bits.push(false);
+            }
+            self.bits
+                .chunks(8)
+                .map(|c| c.iter().fold(0, |b, bit| (b << 1) | *bit as u8))
+                .collect()
+        }
+    }
+
+    /// Baseline SPS with a VUI that has pic_struct_present_flag set and no HRD
+    fn sps() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.write(66, 8).write(0, 8).write(30, 8).ue(0);
+        // log2_max_frame_num, pic_order_cnt_type 2, refs, gaps, 40x30 macroblocks
+        w.ue(0).ue(2).ue(1).write(0, 1).ue(39).ue(29);
+        // frame_mbs_only, direct_8x8, no cropping, VUI present
+        w.write(1, 1).write(1, 1).write(0, 1).write(1, 1);
+        // No aspect ratio, overscan, signal type, chroma location, timing and HRD, then
+        // pic_struct_present_flag and the trailing bit
+        w.write(0, 7).write(1, 1).write(1, 1);
+        let mut nal = vec![0x67];
+        nal.extend(w.finish());
+        nal
+    }
+
+    fn sei(payload_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut nal = vec![0x06, payload_type, payload.len() as u8];
+        nal.extend_from_slice(payload);
+        nal.push(0x80);
+        nal
+    }
+
+    #[test]
+    fn test_sei_user_data() {
+        let mut payload = vec![0xaa; 16];
+        payload.extend_from_slice(b"cam-01");
+        let data = to_annex_b([&sei(5, &payload)[..], &[0x65, 1]]);
+        let messages = SeiParser::new().parse(FrameType::H264, &data);
+        assert_eq!(
+            messages,
+            [SeiMessage::UserDataUnregistered {
+                uuid: [0xaa; 16],
+                data: b"cam-01".to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sei_h264_pic_timing() {
+        let mut w = BitWriter::default();
+        // pic_struct 0, clock_timestamp_flag, ct_type, nuit, counting_type
+        w.write(0, 4).write(1, 1).write(0, 2).write(0, 1).write(0, 5);
+        // full_timestamp, discontinuity, cnt_dropped, n_frames, 12:34:56
+        w.write(1, 1)
+            .write(0, 1)
+            .write(0, 1)
+            .write(7, 8)
+            .write(56, 6)
+            .write(34, 6)
+            .write(12, 5);
+        // 24 bit time offset of -2
+        w.write(0xff_fffe, 24);
+        let payload = &w.finish();
+
+        let mut parser = SeiParser::new();
+        // Without an SPS the syntax is unknown
+        assert!(parser
+            .parse(FrameType::H264, &to_annex_b([&sei(1, payload)[..]]))
+            .is_empty());
+        let data = to_annex_b([&sps()[..], &sei(1, payload), &[0x65, 1]]);
+        let messages = parser.parse(FrameType::H264, &data);
+        assert_eq!(
+            messages,
+            [SeiMessage::PictureTiming(vec![ClockTimestamp {
+                hours: 12,
+                minutes: 34,
+                seconds: 56,
+                n_frames: 7,
+                time_offset: -2,
+                discontinuity: false,
+            }])]
+        );
+    }
+
+    #[test]
+    fn test_sei_h265_time_code() {
+        let mut w = BitWriter::default();
+        // One clock timestamp, units_field_based, counting_type
+        w.write(1, 2).write(1, 1).write(0, 1).write(0, 5);
+        // Not full, discontinuity, n_frames 300, only seconds 5, no time offset
+        w.write(0, 1).write(1, 1).write(0, 1).write(300, 9);
+        w.write(1, 1).write(5, 6).write(0, 1).write(0, 5);
+        let payload = w.finish();
+        let mut nal = vec![39 << 1, 1, 136, payload.len() as u8];
+        nal.extend_from_slice(&payload);
+        nal.push(0x80);
+        let messages = SeiParser::new().parse(FrameType::H265, &to_annex_b([&nal[..]]));
+        assert_eq!(
+            messages,
+            [SeiMessage::PictureTiming(vec![ClockTimestamp {
+                seconds: 5,
+                n_frames: 300,
+                discontinuity: true,
+                ..Default::default()
+            }])]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Delivery of values to any number of receivers that read at their own
+//! pace, shared by [`FrameBus`](crate::rtp::FrameBus) and
+//! [`TimeShift`](crate::recorder::TimeShift).
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Sends every value to all receivers. A receiver that falls behind by
+/// more than the capacity loses the oldest values without slowing the
+/// others down, the losses are counted per receiver.
+#[derive(Debug, Clone)]
+pub(crate) struct Fanout<T> {
+    tx: broadcast::Sender<T>,
+}
+
+impl<T: Clone> Fanout<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            tx: broadcast::channel(capacity.max(1)).0,
+        }
+    }
+
+    /// Sends `value` to every receiver and returns how many there are,
+    /// values nobody receives are dropped
+    pub fn send(&self, value: T) -> usize {
+        self.tx.send(value).unwrap_or(0)
+    }
+
+    /// Receives the values sent from now on
+    pub fn subscribe(&self) -> FanoutReceiver<T> {
+        FanoutReceiver {
+            rx: self.tx.subscribe(),
+            lost: 0,
+            lags: 0,
+        }
+    }
+
+    pub fn receivers(&self) -> usize {
+        self.tx.receiver_count()
+    }
+}
+
+/// What [`FanoutReceiver::recv`] returned
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Received<T> {
+    Value(T),
+    /// The receiver fell behind, this many values were overwritten
+    Lagged(u64),
+    /// Every [`Fanout`] is dropped and the buffered values are read
+    Closed,
+}
+
+#[derive(Debug)]
+pub(crate) struct FanoutReceiver<T> {
+    rx: broadcast::Receiver<T>,
+    lost: u64,
+    lags: u64,
+}
+
+impl<T: Clone> FanoutReceiver<T> {
+    /// Waits for the next value, cancel safe
+    pub async fn recv(&mut self) -> Received<T> {
+        match self.rx.recv().await {
+            Ok(value) => Received::Value(value),
+            Err(RecvError::Lagged(n)) => {
+                self.lost += n;
+                self.lags += 1;
+                Received::Lagged(n)
+            }
+            Err(RecvError::Closed) => Received::Closed,
+        }
+    }
+
+    /// Values overwritten before the receiver read them
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Times the receiver fell behind by the capacity
+    pub fn lags(&self) -> u64 {
+        self.lags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fanout() {
+        let fanout = Fanout::new(2);
+        assert_eq!(fanout.send(0), 0);
+        let mut fast = fanout.subscribe();
+        let mut slow = fanout.subscribe();
+        assert_eq!(fanout.receivers(), 2);
+        for value in 1..5 {
+            assert_eq!(fanout.send(value), 2);
+            assert_eq!(fast.recv().await, Received::Value(value));
+        }
+        assert_eq!(slow.recv().await, Received::Lagged(2));
+        assert_eq!(slow.recv().await, Received::Value(3));
+        assert_eq!((slow.lost(), slow.lags()), (2, 1));
+        assert_eq!((fast.lost(), fast.lags()), (0, 0));
+
+        drop(fanout);
+        assert_eq!(slow.recv().await, Received::Value(4));
+        assert_eq!(slow.recv().await, Received::Closed);
+    }
+}
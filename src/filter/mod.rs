@@ -0,0 +1,13 @@
+mod pipeline;
+mod stages;
+mod watermark;
+
+pub use pipeline::FilteredDepacketizer;
+pub use pipeline::FrameFilter;
+pub use pipeline::Pipeline;
+pub use stages::Downsample;
+pub use stages::KeyframesOnly;
+pub use stages::ParseSei;
+pub use stages::StripSei;
+pub use watermark::FrameIdentity;
+pub use watermark::Watermark;
@@ -0,0 +1,117 @@
+use crate::rtp::{Depacketizer, DepacketizerError, Packet};
+use crate::types::Frame;
+
+/// A post-processing stage between frame assembly and delivery.
+///
+/// Returns the frame, possibly modified, or None to drop it.
+pub trait FrameFilter: Send {
+    fn filter(&mut self, frame: Frame) -> Option<Frame>;
+}
+
+impl<F: FnMut(Frame) -> Option<Frame> + Send> FrameFilter for F {
+    fn filter(&mut self, frame: Frame) -> Option<Frame> {
+        self(frame)
+    }
+}
+
+/// Runs frames through its stages in the order they were added
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn FrameFilter>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, stage: impl FrameFilter + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Runs the pipeline on the frames of `depacketizer`
+    pub fn wrap(self, depacketizer: Box<dyn Depacketizer>) -> FilteredDepacketizer {
+        FilteredDepacketizer {
+            inner: depacketizer,
+            pipeline: self,
+        }
+    }
+}
+
+impl FrameFilter for Pipeline {
+    fn filter(&mut self, frame: Frame) -> Option<Frame> {
+        self.stages
+            .iter_mut()
+            .try_fold(frame, |frame, stage| stage.filter(frame))
+    }
+}
+
+/// A depacketizer whose frames pass a [`Pipeline`] before they are popped
+pub struct FilteredDepacketizer {
+    inner: Box<dyn Depacketizer>,
+    pipeline: Pipeline,
+}
+
+impl FilteredDepacketizer {
+    pub fn into_inner(self) -> Box<dyn Depacketizer> {
+        self.inner
+    }
+}
+
+impl Depacketizer for FilteredDepacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<(), DepacketizerError> {
+        self.inner.push(packet)
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        while let Some(frame) = self.inner.pop() {
+            if let Some(frame) = self.pipeline.filter(frame) {
+                return Some(frame);
+            }
+        }
+        None
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::G711Depacketizer;
+    use crate::testing::rtp_packet;
+    use crate::types::FrameType;
+
+    fn packet(seq: u16, payload: u8) -> Packet {
+        rtp_packet(8, 0, seq, 0, false, &[payload])
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let pipeline = Pipeline::new()
+            .stage(|frame: Frame| frame.data[0].is_multiple_of(2).then_some(frame))
+            .stage(|mut frame: Frame| {
+                frame.data[0] *= 10;
+                Some(frame)
+            });
+        assert_eq!(pipeline.len(), 2);
+        let mut depacketizer = pipeline.wrap(Box::new(G711Depacketizer::new(FrameType::PCMU)));
+        for (seq, payload) in [1, 2, 3, 4].into_iter().enumerate() {
+            depacketizer.push(&packet(seq as u16, payload)).unwrap();
+        }
+        assert_eq!(depacketizer.pop().unwrap().data, [20]);
+        assert_eq!(depacketizer.pop().unwrap().data, [40]);
+        assert!(depacketizer.pop().is_none());
+    }
+}
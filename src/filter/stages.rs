@@ -0,0 +1,152 @@
+use super::FrameFilter;
+use crate::codec::{is_sei, to_annex_b, NalUnits, SeiParser};
+use crate::types::{Frame, FrameType, MediaType};
+
+/// Drops video frames until the next keyframe, e.g. for thumbnails or
+/// motion detection on a fraction of the decoding cost. Audio passes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KeyframesOnly;
+
+impl FrameFilter for KeyframesOnly {
+    fn filter(&mut self, frame: Frame) -> Option<Frame> {
+        (frame.media_type != MediaType::Video || frame.is_keyframe()).then_some(frame)
+    }
+}
+
+/// Limits the frame rate by dropping frames that follow the previous one
+/// too closely.
+///
+/// The remaining frames of inter predicted codecs can't be decoded, use it
+/// after [`KeyframesOnly`] or for codecs like JPEG.
+#[derive(Debug, Clone, Copy)]
+pub struct Downsample {
+    // Minimum RTP timestamp distance of two frames
+    interval: u32,
+    last: Option<u32>,
+}
+
+impl Downsample {
+    pub fn new(fps: u32, clock_rate: u32) -> Self {
+        Self {
+            interval: clock_rate / fps.max(1),
+            last: None,
+        }
+    }
+}
+
+impl FrameFilter for Downsample {
+    fn filter(&mut self, frame: Frame) -> Option<Frame> {
+        if let Some(last) = self.last {
+            let elapsed = frame.timestamp.wrapping_sub(last) as i32;
+            // Tolerates timestamps that are slightly early, restarts when they jump back
+            if elapsed >= 0 && (elapsed as u32) < self.interval - self.interval / 8 {
+                return None;
+            }
+        }
+        self.last = Some(frame.timestamp);
+        Some(frame)
+    }
+}
+
+/// Removes SEI NAL units from H.264 and H.265 frames
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StripSei;
+
+impl FrameFilter for StripSei {
+    fn filter(&mut self, mut frame: Frame) -> Option<Frame> {
+        let frame_type = frame.frame_type;
+        if !matches!(frame_type, FrameType::H264 | FrameType::H265)
+            || !NalUnits::new(&frame.data).any(|nal| is_sei(frame_type, nal))
+        {
+            return Some(frame);
+        }
+        frame.data = to_annex_b(NalUnits::new(&frame.data).filter(|nal| !is_sei(frame_type, nal)));
+        (!frame.data.is_empty()).then_some(frame)
+    }
+}
+
+/// Parses the SEI messages of H.264 and H.265 frames into their
+/// [`FrameMetadata::sei`](crate::types::FrameMetadata::sei), e.g. to cross-check the time a camera burns
+/// into the image. Put it before [`StripSei`].
+#[derive(Debug, Default, Clone)]
+pub struct ParseSei {
+    parser: SeiParser,
+}
+
+impl FrameFilter for ParseSei {
+    fn filter(&mut self, mut frame: Frame) -> Option<Frame> {
+        if matches!(frame.frame_type, FrameType::H264 | FrameType::H265) {
+            frame.metadata.sei = self.parser.parse(frame.frame_type, &frame.data);
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::SeiMessage;
+    use crate::types::FrameMetadata;
+
+    fn h264(timestamp: u32, nals: &[&[u8]]) -> Frame {
+        Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp,
+            data: to_annex_b(nals.iter().copied()),
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_keyframes_only() {
+        let mut filter = KeyframesOnly;
+        assert!(filter.filter(h264(0, &[&[0x67, 1], &[0x65, 2]])).is_some());
+        assert!(filter.filter(h264(0, &[&[0x41, 2]])).is_none());
+        let audio = Frame {
+            media_type: MediaType::Audio,
+            frame_type: FrameType::AAC,
+            timestamp: 0,
+            data: vec![1],
+            metadata: FrameMetadata::default(),
+        };
+        assert!(filter.filter(audio).is_some());
+    }
+
+    #[test]
+    fn test_downsample() {
+        // 30 fps to 10 fps, timestamps are a bit early now and then
+        let mut filter = Downsample::new(10, 90_000);
+        let kept: Vec<_> = [0, 3000, 6000, 8990, 12_000, 15_000, 18_000]
+            .into_iter()
+            .filter_map(|ts| filter.filter(h264(ts, &[&[0x65]])))
+            .map(|f| f.timestamp)
+            .collect();
+        assert_eq!(kept, [0, 8990, 18_000]);
+        // A timestamp jump back restarts the interval
+        assert!(filter.filter(h264(100, &[&[0x65]])).is_some());
+    }
+
+    #[test]
+    fn test_strip_sei() {
+        let mut filter = StripSei;
+        let frame = filter.filter(h264(0, &[&[0x06, 5, 1], &[0x65, 2]])).unwrap();
+        assert_eq!(frame.data, [0, 0, 0, 1, 0x65, 2]);
+        assert!(filter.filter(h264(0, &[&[0x06, 5, 1]])).is_none());
+    }
+
+    #[test]
+    fn test_parse_sei() {
+        let mut sei = vec![0x06, 5, 17];
+        sei.extend_from_slice(&[0x11; 16]);
+        sei.extend_from_slice(&[42, 0x80]);
+        let frame = ParseSei::default().filter(h264(0, &[&sei, &[0x65, 2]])).unwrap();
+        assert_eq!(
+            frame.metadata.sei,
+            [SeiMessage::UserDataUnregistered {
+                uuid: [0x11; 16],
+                data: vec![42]
+            }]
+        );
+    }
+}
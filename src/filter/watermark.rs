@@ -0,0 +1,127 @@
+use super::FrameFilter;
+use crate::sync::{NtpTimestamp, Synchronizer};
+use crate::types::{Frame, FrameOrigin};
+use std::sync::{Arc, Mutex};
+
+/// Stable identifiers of a delivered frame, e.g. for evidentiary
+/// recordings that hash every frame together with where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameIdentity {
+    /// RTSP session the frame was received in
+    pub session: Option<String>,
+    /// Source and sequence numbers, None for frames not assembled from RTP
+    pub origin: Option<FrameOrigin>,
+    /// RTP timestamp of the frame
+    pub timestamp: u32,
+    /// Capture time on the sender's clock, None until a sender report of
+    /// the source arrived
+    pub ntp: Option<NtpTimestamp>,
+    /// Position among the frames that passed the stage, counted from 0, so
+    /// gaps in a record of the hashes show
+    pub index: u64,
+}
+
+/// Calls a hook with the [`FrameIdentity`] of every frame that reaches the
+/// stage, the frames pass unchanged. Added last to a [`Pipeline`](super::Pipeline)
+/// the hook sees exactly the delivered frames.
+pub struct Watermark<F> {
+    hook: F,
+    session: Option<String>,
+    synchronizer: Option<Arc<Mutex<Synchronizer>>>,
+    index: u64,
+}
+
+impl<F: FnMut(&FrameIdentity, &Frame) + Send> Watermark<F> {
+    pub fn new(hook: F) -> Self {
+        Self {
+            hook,
+            session: None,
+            synchronizer: None,
+            index: 0,
+        }
+    }
+
+    pub fn session(mut self, session: impl Into<String>) -> Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Maps RTP timestamps to NTP time with the sender reports given to
+    /// `synchronizer`
+    pub fn synchronizer(mut self, synchronizer: Arc<Mutex<Synchronizer>>) -> Self {
+        self.synchronizer = Some(synchronizer);
+        self
+    }
+}
+
+impl<F: FnMut(&FrameIdentity, &Frame) + Send> FrameFilter for Watermark<F> {
+    fn filter(&mut self, frame: Frame) -> Option<Frame> {
+        let origin = frame.metadata.origin;
+        let ntp = match (&self.synchronizer, origin) {
+            (Some(sync), Some(origin)) => sync
+                .lock()
+                .unwrap()
+                .capture_time(origin.ssrc, frame.timestamp)
+                .map(NtpTimestamp::from_system_time),
+            _ => None,
+        };
+        let identity = FrameIdentity {
+            session: self.session.clone(),
+            origin,
+            timestamp: frame.timestamp,
+            ntp,
+            index: self.index,
+        };
+        self.index += 1;
+        (self.hook)(&identity, &frame);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Pipeline;
+    use crate::rtp::{Depacketizer, G711Depacketizer, Packet};
+    use crate::testing::rtp_packet;
+    use crate::types::FrameType;
+    use std::time::{Duration, SystemTime};
+
+    fn packet(seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(8, 9, seq, timestamp, false, &[0xd5])
+    }
+
+    #[test]
+    fn test_watermark() {
+        let epoch = NtpTimestamp::from_system_time(SystemTime::now()).to_system_time();
+        let sync = Arc::new(Mutex::new(Synchronizer::new()));
+        sync.lock()
+            .unwrap()
+            .update(9, NtpTimestamp::from_system_time(epoch), 0, 8000);
+        let identities = Arc::new(Mutex::new(Vec::new()));
+        let recorded = identities.clone();
+        let watermark = Watermark::new(move |identity: &FrameIdentity, frame: &Frame| {
+            assert_eq!(frame.data, [0xd5]);
+            recorded.lock().unwrap().push(identity.clone());
+        })
+        .session("12345678")
+        .synchronizer(sync);
+        let mut depacketizer = Pipeline::new()
+            .stage(watermark)
+            .wrap(Box::new(G711Depacketizer::new(FrameType::PCMA)));
+        depacketizer.push(&packet(7, 0)).unwrap();
+        depacketizer.push(&packet(8, 8000)).unwrap();
+        while depacketizer.pop().is_some() {}
+
+        let identities = identities.lock().unwrap();
+        assert_eq!(identities.len(), 2);
+        let second = &identities[1];
+        assert_eq!(second.session.as_deref(), Some("12345678"));
+        assert_eq!(second.origin, Some(FrameOrigin::new(9, 8)));
+        assert_eq!(second.index, 1);
+        assert_eq!(
+            second.ntp,
+            Some(NtpTimestamp::from_system_time(epoch + Duration::from_secs(1)))
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use super::response::{DEFAULT_MAX_BODY_SIZE, DEFAULT_MAX_HEADER_SIZE};
+use super::{ParseResponseError, Request, Response, ResponseParser, DEFAULT_HTTP_PORT};
+use base64::prelude::*;
+use percent_encoding::percent_decode_str;
+use std::io;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::Url;
+
+pub const DEFAULT_USER_AGENT: &str = "rs-streamer";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Parse(#[from] ParseResponseError),
+    #[error("Url has no host")]
+    MissingHost,
+    #[error("Unsupported scheme {0}")]
+    UnsupportedScheme(String),
+    #[error("Request timed out")]
+    Timeout,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Minimal HTTP/1.1 client, one request per connection, e.g. to fetch the
+/// JPEG snapshots of cameras
+#[derive(Debug, Clone)]
+pub struct Client {
+    user_agent: String,
+    timeout: Option<Duration>,
+    max_header_size: usize,
+    max_body_size: usize,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: None,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Limits connecting, sending and receiving the response
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_header_size(mut self, size: usize) -> Self {
+        self.max_header_size = size;
+        self
+    }
+
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// GET of the `http://` url, credentials of the url are sent with
+    /// basic authentication
+    pub async fn get(&self, url: &Url) -> Result<Response> {
+        if url.scheme() != "http" {
+            return Err(Error::UnsupportedScheme(url.scheme().to_string()));
+        }
+        let host = url.host_str().ok_or(Error::MissingHost)?;
+        let port = url.port().unwrap_or(DEFAULT_HTTP_PORT);
+        let mut request = Request::get(url);
+        if !url.username().is_empty() {
+            // The user info of a url is percent-encoded
+            let user = percent_decode_str(url.username()).decode_utf8_lossy();
+            let pass = percent_decode_str(url.password().unwrap_or_default()).decode_utf8_lossy();
+            let credentials = format!("{}:{}", user, pass);
+            request = request.header(
+                "Authorization",
+                format!("Basic {}", BASE64_STANDARD.encode(credentials)),
+            );
+        }
+        self.with_timeout(async {
+            let mut stream = TcpStream::connect((host, port)).await?;
+            self.send(&mut stream, request).await
+        })
+        .await
+    }
+
+    /// Sends the request on the connection and reads the response until it
+    /// is complete or the connection is closed
+    pub async fn send<S>(&self, stream: &mut S, request: Request) -> Result<Response>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut request = request;
+        if request.get_header("User-Agent").is_none() {
+            request = request.header("User-Agent", &self.user_agent);
+        }
+        if request.get_header("Connection").is_none() {
+            request = request.header("Connection", "close");
+        }
+        self.with_timeout(async {
+            stream.write_all(&request.serialize()).await?;
+            let mut parser = ResponseParser::new()
+                .max_header_size(self.max_header_size)
+                .max_body_size(self.max_body_size);
+            let mut buf = [0u8; 4096];
+            while !parser.is_done() {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                parser.push(&buf[..n])?;
+            }
+            Ok(parser.finish()?)
+        })
+        .await
+    }
+
+    async fn with_timeout<T>(&self, future: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, future)
+                .await
+                .map_err(|_| Error::Timeout)?,
+            None => future.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_client_get() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://admin:s%40cret@{}/snapshot.jpg",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with("GET /snapshot.jpg HTTP/1.1\r\n"));
+            assert!(request.contains("Authorization: Basic YWRtaW46c0BjcmV0\r\n"));
+            assert!(request.contains("User-Agent: rs-streamer\r\n"));
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n\xff\xd8\r\n0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        let response = Client::new().get(&url).await.unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, b"\xff\xd8");
+    }
+
+    #[tokio::test]
+    async fn test_client_timeout() {
+        let (mut client, _server) = tokio::io::duplex(1024);
+        let request = Request::new("GET", "/");
+        let result = Client::new()
+            .timeout(Duration::from_millis(10))
+            .send(&mut client, request)
+            .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+        let result = Client::new().get(&Url::parse("https://camera/").unwrap()).await;
+        assert!(matches!(result, Err(Error::UnsupportedScheme(_))));
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Days since the unix epoch of a date of the proleptic Gregorian calendar
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Year, month and day of the days since the unix epoch, the inverse of
+/// [`days_from_civil`]
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn parse_time(s: &str) -> Option<u64> {
+    let mut parts = s.split(':').map(|p| p.parse::<u64>().ok());
+    let (h, m, s) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    Some(h * 3600 + m * 60 + s)
+}
+
+/// Parses the value of a `Date` header in any of the formats of RFC 7231
+/// 7.1.1.1 or the RTSP form without weekday, e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`, `Sunday, 06-Nov-94 08:49:37 GMT`,
+/// `Sun Nov  6 08:49:37 1994` or `06 Nov 1994 08:49:37 GMT`.
+/// The time is taken as UTC.
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    let (mut day, mut month, mut year, mut time) = (None, None, None, None);
+    for token in s.split([' ', ',', '-']).filter(|t| !t.is_empty()) {
+        if token.contains(':') {
+            time = Some(parse_time(token)?);
+        } else if token.bytes().all(|b| b.is_ascii_digit()) {
+            let value: u32 = token.parse().ok()?;
+            match (day, token.len()) {
+                (None, 1 | 2) => day = Some(value),
+                (Some(_), 2) if year.is_none() => year = Some(if value < 70 { 2000 + value } else { 1900 + value }),
+                (Some(_), 4) if year.is_none() => year = Some(value),
+                _ => return None,
+            }
+        } else {
+            let name = token.to_ascii_lowercase();
+            if let Some(i) = MONTHS.iter().position(|m| name.starts_with(m)) {
+                month = Some(i as u32 + 1);
+            } else if !WEEKDAYS.iter().any(|d| name.starts_with(d)) && name != "gmt" && name != "utc" {
+                return None;
+            }
+        }
+    }
+    let (day, month, year, time) = (day?, month?, year?, time?);
+    if !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day);
+    let secs = u64::try_from(days).ok()? * 86_400 + time;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_date() {
+        let expected = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(expected));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), Some(expected));
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(expected));
+        assert_eq!(parse_http_date("06 Nov 1994 08:49:37 GMT"), Some(expected));
+        assert_eq!(
+            parse_http_date("Thu, 29 Feb 2024 23:59:59 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(1_709_251_199))
+        );
+    }
+
+    #[test]
+    fn test_parse_http_date_invalid() {
+        assert_eq!(parse_http_date(""), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 +0800"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 -0800"), None);
+    }
+}
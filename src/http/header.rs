@@ -12,6 +12,16 @@ impl<'a> Header<'a> {
     pub fn new(name: &'a str, value: &'a str) -> Self {
         Self { name, value }
     }
+
+    /// Like the strict parsing of [`Header::try_from`], but allows spaces
+    /// around the name, e.g. `CSeq : 2` as sent by some cameras
+    pub fn parse_lenient(value: &'a str) -> Result<Self> {
+        let (name, value) = value.split_once(':').ok_or(ParseHeaderError::InvalidFormat)?;
+        let name = name.trim();
+        verify_header_name(name)?;
+        verify_header_value(value)?;
+        Ok(Header::new(name, value.trim()))
+    }
 }
 
 impl<'a> fmt::Display for Header<'a> {
@@ -31,7 +41,7 @@ pub enum ParseHeaderError {
 type Result<T> = std::result::Result<T, ParseHeaderError>;
 
 fn verify_header_name(name: &str) -> Result<()> {
-    if name.len() > 0 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
         Ok(())
     } else {
         Err(ParseHeaderError::InvalidName)
@@ -82,6 +92,13 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_header_lenient() {
+        let header = Header::parse_lenient(" Content-Length : 123").unwrap();
+        assert_eq!(header, Header::new("Content-Length", "123"));
+        assert!(Header::parse_lenient("Content Length: 123").is_err());
+    }
+
     #[test]
     fn test_parse_header_empty_value() {
         let header = Header::try_from("Content-Length:").unwrap();
@@ -1,7 +1,29 @@
+mod client;
+mod date;
 mod header;
+mod request;
+mod response;
+mod text;
+mod tunnel;
 mod version;
 
 pub use header::Header;
 pub use header::ParseHeaderError;
 pub use version::Version;
 pub use version::ParseVersionError;
+pub use tunnel::Error as TunnelError;
+pub use tunnel::Tunnel;
+pub use tunnel::DEFAULT_HTTP_PORT;
+pub use request::request_target;
+pub use request::Request;
+pub use response::ParseResponseError;
+pub use response::Response;
+pub use response::ResponseParser;
+pub use client::Client;
+pub use client::Error as ClientError;
+pub use text::parse_protocol_version;
+pub use text::ParseTextError;
+pub use text::TextParser;
+pub use date::parse_http_date;
+pub use text::MessageFramer;
+pub(crate) use date::{civil_from_days, days_from_civil};
@@ -0,0 +1,101 @@
+use std::fmt;
+use url::Url;
+
+/// A HTTP/1.1 request, serialized with [`Request::serialize`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Path and query of the url, the request target of origin servers
+pub fn request_target(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+impl Request {
+    pub fn new(method: &str, target: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            target: target.to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// GET of the url's path with its Host header, e.g. of a snapshot url
+    pub fn get(url: &Url) -> Self {
+        let request = Self::new("GET", &request_target(url));
+        match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => request.header("Host", format!("{}:{}", host, port)),
+            (Some(host), None) => request.header("Host", host),
+            (None, _) => request,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: impl fmt::Display) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the body, its Content-Length is added when serializing
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the value of the first header with the given name
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, self.target);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if !self.body.is_empty() {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        let mut buf = head.into_bytes();
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_request() {
+        let url = Url::parse("http://camera:8080/snapshot.jpg?channel=1").unwrap();
+        let request = Request::get(&url).header("Connection", "close");
+        assert_eq!(
+            request.serialize(),
+            b"GET /snapshot.jpg?channel=1 HTTP/1.1\r\nHost: camera:8080\r\nConnection: close\r\n\r\n"
+        );
+        let request = Request::new("POST", "/api").body("{}");
+        assert_eq!(
+            request.serialize(),
+            b"POST /api HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}"
+        );
+    }
+}
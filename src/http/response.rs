@@ -0,0 +1,384 @@
+use super::{ParseTextError, TextParser, Version};
+use thiserror::Error;
+
+/// Longest accepted status line and headers
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 8192;
+/// Largest accepted body, enough for the JPEG snapshots of cameras
+pub const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum ParseResponseError {
+    #[error("Invalid status line")]
+    InvalidStatusLine,
+    #[error(transparent)]
+    Parse(#[from] ParseTextError),
+    #[error("Invalid chunk")]
+    InvalidChunk,
+    #[error("Response header too long")]
+    HeaderTooLong,
+    #[error("Response body too long")]
+    BodyTooLong,
+    #[error("Connection closed before the response was complete")]
+    UnexpectedEof,
+}
+
+type Result<T> = std::result::Result<T, ParseResponseError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub version: Version,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Returns the value of the first header with the given name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Head,
+    Length(usize),
+    ChunkSize,
+    ChunkData(usize),
+    // CRLF after the data of a chunk
+    ChunkEnd,
+    Trailer,
+    UntilClose,
+    Done,
+}
+
+/// Parses a HTTP/1.x response from the bytes of a connection as they
+/// arrive. Bodies are delimited by Content-Length, chunked transfer coding
+/// or the end of the connection, see [`ResponseParser::finish`].
+#[derive(Debug)]
+pub struct ResponseParser {
+    state: State,
+    // Header bytes, or the current chunk size or trailer line
+    line: Vec<u8>,
+    response: Response,
+    max_header_size: usize,
+    max_body_size: usize,
+    head_only: bool,
+}
+
+impl Default for ResponseParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Head,
+            line: Vec::new(),
+            response: Response {
+                version: Version::new(1, 1),
+                status: 0,
+                reason: String::new(),
+                headers: Vec::new(),
+                body: Vec::new(),
+            },
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            head_only: false,
+        }
+    }
+
+    pub fn max_header_size(mut self, size: usize) -> Self {
+        self.max_header_size = size;
+        self
+    }
+
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Stops after the header, e.g. when the body is a stream of another
+    /// protocol as with RTSP tunneled over HTTP
+    pub fn head_only(mut self) -> Self {
+        self.head_only = true;
+        self
+    }
+
+    /// Parses the next bytes and returns how many belong to the response,
+    /// the rest follows it on the connection
+    pub fn push(&mut self, data: &[u8]) -> Result<usize> {
+        let mut pos = 0;
+        while pos < data.len() && self.state != State::Done {
+            let rest = &data[pos..];
+            pos += match self.state {
+                State::Head => self.push_head(rest)?,
+                State::Length(remaining) => {
+                    let n = remaining.min(rest.len());
+                    self.push_body(&rest[..n])?;
+                    self.state = match remaining - n {
+                        0 => State::Done,
+                        remaining => State::Length(remaining),
+                    };
+                    n
+                }
+                State::ChunkSize => {
+                    let (n, line) = self.push_line(rest)?;
+                    if let Some(line) = line {
+                        // Chunk extensions follow a semicolon
+                        let size = line.split(';').next().unwrap_or_default().trim();
+                        let size = usize::from_str_radix(size, 16).map_err(|_| ParseResponseError::InvalidChunk)?;
+                        self.state = match size {
+                            0 => State::Trailer,
+                            size => State::ChunkData(size),
+                        };
+                    }
+                    n
+                }
+                State::ChunkData(remaining) => {
+                    let n = remaining.min(rest.len());
+                    self.push_body(&rest[..n])?;
+                    self.state = match remaining - n {
+                        0 => State::ChunkEnd,
+                        remaining => State::ChunkData(remaining),
+                    };
+                    n
+                }
+                State::ChunkEnd => {
+                    let (n, line) = self.push_line(rest)?;
+                    match line {
+                        Some(line) if !line.is_empty() => return Err(ParseResponseError::InvalidChunk),
+                        Some(_) => self.state = State::ChunkSize,
+                        None => {}
+                    }
+                    n
+                }
+                State::Trailer => {
+                    let (n, line) = self.push_line(rest)?;
+                    if line.is_some_and(|l| l.is_empty()) {
+                        self.state = State::Done;
+                    }
+                    n
+                }
+                State::UntilClose => {
+                    self.push_body(rest)?;
+                    rest.len()
+                }
+                State::Done => 0,
+            };
+        }
+        Ok(pos)
+    }
+
+    /// The status and headers once they were parsed, the body may still be incomplete
+    pub fn head(&self) -> Option<&Response> {
+        (self.state != State::Head).then_some(&self.response)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Returns the response once the connection was closed, which ends
+    /// bodies without length
+    pub fn finish(self) -> Result<Response> {
+        match self.state {
+            State::Done | State::UntilClose => Ok(self.response),
+            _ => Err(ParseResponseError::UnexpectedEof),
+        }
+    }
+
+    /// Returns the response if it is complete
+    pub fn into_response(self) -> Option<Response> {
+        self.is_done().then_some(self.response)
+    }
+
+    fn push_body(&mut self, data: &[u8]) -> Result<()> {
+        if self.response.body.len() + data.len() > self.max_body_size {
+            return Err(ParseResponseError::BodyTooLong);
+        }
+        self.response.body.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Collects a line, returns the consumed bytes and the line once it is complete
+    fn push_line(&mut self, data: &[u8]) -> Result<(usize, Option<String>)> {
+        let Some(end) = data.iter().position(|b| *b == b'\n') else {
+            if self.line.len() + data.len() > self.max_header_size {
+                return Err(ParseResponseError::HeaderTooLong);
+            }
+            self.line.extend_from_slice(data);
+            return Ok((data.len(), None));
+        };
+        self.line.extend_from_slice(&data[..end]);
+        let line = String::from_utf8_lossy(&self.line).trim_end_matches('\r').to_string();
+        self.line.clear();
+        Ok((end + 1, Some(line)))
+    }
+
+    fn push_head(&mut self, data: &[u8]) -> Result<usize> {
+        let start = self.line.len();
+        self.line.extend_from_slice(data);
+        // The end may span the previous and the new bytes
+        let search_from = start.saturating_sub(3);
+        let Some(end) = self.line[search_from..]
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map(|p| search_from + p + 4)
+        else {
+            if self.line.len() > self.max_header_size {
+                return Err(ParseResponseError::HeaderTooLong);
+            }
+            return Ok(data.len());
+        };
+        if end > self.max_header_size {
+            return Err(ParseResponseError::HeaderTooLong);
+        }
+        let head = std::mem::take(&mut self.line);
+        self.parse_head(&head[..end])?;
+        Ok(end - start)
+    }
+
+    fn parse_head(&mut self, head: &[u8]) -> Result<()> {
+        let mut text = TextParser::new("HTTP");
+        let token = text.next_token(head)?;
+        let version = text.parse_version(token)?;
+        // The reason phrase may be missing
+        let line = text.next_line(head)?;
+        let (status, reason) = line.split_once(' ').unwrap_or((line, ""));
+        let status: u16 = status
+            .parse()
+            .ok()
+            .filter(|s| (100..600).contains(s))
+            .ok_or(ParseResponseError::InvalidStatusLine)?;
+        // Interim responses such as 100 Continue precede the final one
+        if (100..200).contains(&status) && status != 101 {
+            return Ok(());
+        }
+        self.response.version = version;
+        self.response.status = status;
+        self.response.reason = reason.to_string();
+        while let Some(header) = text.next_header(head)? {
+            self.response
+                .headers
+                .push((header.name.to_string(), header.value.to_string()));
+        }
+        let chunked = self.response.header("Transfer-Encoding").is_some_and(|v| {
+            v.rsplit(',')
+                .next()
+                .is_some_and(|c| c.trim().eq_ignore_ascii_case("chunked"))
+        });
+        let length = text.content_length();
+        self.state = if self.head_only || status == 204 || status == 304 || status < 200 {
+            State::Done
+        } else if chunked {
+            State::ChunkSize
+        } else {
+            match length {
+                Some(0) => State::Done,
+                Some(length) if length > self.max_body_size => return Err(ParseResponseError::BodyTooLong),
+                Some(length) => State::Length(length),
+                None => State::UntilClose,
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds the response in pieces of `step` bytes
+    fn parse(data: &[u8], step: usize) -> (Response, usize) {
+        let mut parser = ResponseParser::new();
+        let mut consumed = 0;
+        for chunk in data.chunks(step) {
+            consumed += parser.push(chunk).unwrap();
+            if parser.is_done() {
+                break;
+            }
+        }
+        (parser.finish().unwrap(), consumed)
+    }
+
+    #[test]
+    fn test_parse_content_length() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\n\r\n\xff\xd8\xff\xd9next";
+        for step in [1, 3, data.len()] {
+            let (response, consumed) = parse(data, step);
+            assert_eq!(response.status, 200);
+            assert_eq!(response.reason, "OK");
+            assert_eq!(response.header("content-type"), Some("image/jpeg"));
+            assert_eq!(response.body, b"\xff\xd8\xff\xd9");
+            assert_eq!(consumed, data.len() - 4);
+        }
+    }
+
+    #[test]
+    fn test_parse_chunked() {
+        let data = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: never\r\n\r\n";
+        for step in [1, 2, data.len()] {
+            let (response, consumed) = parse(data, step);
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, b"Wikipedia");
+            assert_eq!(consumed, data.len());
+        }
+        let mut parser = ResponseParser::new();
+        let invalid = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(matches!(parser.push(invalid), Err(ParseResponseError::InvalidChunk)));
+    }
+
+    #[test]
+    fn test_parse_until_close() {
+        let mut parser = ResponseParser::new();
+        parser.push(b"HTTP/1.0 200 OK\r\n\r\nbody").unwrap();
+        assert_eq!(parser.head().unwrap().version, Version::new(1, 0));
+        assert!(!parser.is_done());
+        assert_eq!(parser.finish().unwrap().body, b"body");
+
+        let mut parser = ResponseParser::new();
+        parser
+            .push(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nbody")
+            .unwrap();
+        assert!(matches!(parser.finish(), Err(ParseResponseError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn test_parse_without_reason() {
+        let mut parser = ResponseParser::new();
+        parser.push(b"HTTP/1.0 200\r\n\r\nbody").unwrap();
+        let head = parser.head().unwrap();
+        assert_eq!(head.status, 200);
+        assert_eq!(head.reason, "");
+        assert_eq!(parser.finish().unwrap().body, b"body");
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let mut parser = ResponseParser::new().max_header_size(16);
+        assert!(matches!(
+            parser.push(b"HTTP/1.1 200 OK\r\nServer: camera\r\n"),
+            Err(ParseResponseError::HeaderTooLong)
+        ));
+        let mut parser = ResponseParser::new().max_body_size(2);
+        assert!(matches!(
+            parser.push(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n"),
+            Err(ParseResponseError::BodyTooLong)
+        ));
+        let mut parser = ResponseParser::new().head_only();
+        assert_eq!(parser.push(b"HTTP/1.0 200 OK\r\n\r\nRTSP/1.0").unwrap(), 19);
+        assert!(parser.is_done());
+    }
+}
@@ -0,0 +1,395 @@
+use super::{Header, ParseHeaderError, ParseVersionError, Version};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ParseTextError {
+    #[error("Expected end of line")]
+    ExpectedEndOfLine,
+    #[error("Expected space")]
+    ExpectedSpace,
+    #[error(transparent)]
+    ParseHeader(#[from] ParseHeaderError),
+    #[error("Failed to parse content length")]
+    ParseContentLength(#[from] std::num::ParseIntError),
+    #[error("Content length too large")]
+    ContentLengthTooLarge,
+    #[error(transparent)]
+    Encoding(#[from] std::str::Utf8Error),
+    #[error("Unexpected protocol {0}")]
+    UnexpectedProtocol(String),
+    #[error("Failed to parse version {0}")]
+    ParseVersion(#[from] ParseVersionError),
+}
+
+impl ParseTextError {
+    /// The line being parsed isn't complete yet, parsing can continue once
+    /// more data arrived
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, ParseTextError::ExpectedEndOfLine | ParseTextError::ExpectedSpace)
+    }
+}
+
+type Result<T> = std::result::Result<T, ParseTextError>;
+
+/// Parses the `NAME/major.minor` token of the start line
+pub fn parse_protocol_version(protocol: &str, s: &str) -> Result<Version> {
+    let version = s
+        .split_once('/')
+        .filter(|(name, _)| *name == protocol)
+        .map(|(_, version)| version)
+        .ok_or_else(|| ParseTextError::UnexpectedProtocol(s.to_string()))?;
+    Ok(version.parse()?)
+}
+
+/// Line based core of the HTTP and RTSP parsers: start line tokens,
+/// headers and the body framed by Content-Length. The start line itself is
+/// interpreted by the protocol parsers.
+///
+/// Every call gets the data of the message so far, which must start with
+/// the message and may grow between calls. Incomplete lines fail with an
+/// error for which [`ParseTextError::is_incomplete`] is true, they are
+/// parsed again by the next call.
+#[derive(Debug)]
+pub struct TextParser {
+    protocol: &'static str,
+    pos: usize,
+    header_length: Option<usize>,
+    content_length: Option<usize>,
+    lenient_headers: bool,
+}
+
+impl TextParser {
+    /// `protocol` is the name of the protocol in the start line, e.g. `HTTP`
+    pub fn new(protocol: &'static str) -> Self {
+        Self {
+            protocol,
+            pos: 0,
+            header_length: None,
+            content_length: None,
+            lenient_headers: false,
+        }
+    }
+
+    /// Parses headers with [`Header::parse_lenient`]
+    pub fn lenient_headers(mut self, lenient: bool) -> Self {
+        self.lenient_headers = lenient;
+        self
+    }
+
+    /// Body length of a message without a Content-Length header, e.g. the
+    /// rest of a message ended by closing the connection
+    pub fn body_length(mut self, length: usize) -> Self {
+        self.content_length = Some(length);
+        self
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        self.protocol
+    }
+
+    pub fn parse_version(&self, token: &str) -> Result<Version> {
+        parse_protocol_version(self.protocol, token)
+    }
+
+    pub fn next_line<'a>(&mut self, data: &'a [u8]) -> Result<&'a str> {
+        let data = &data[self.pos..];
+        for (i, w) in data.windows(2).enumerate() {
+            if w == b"\r\n" {
+                let line = std::str::from_utf8(&data[..i])?;
+                self.pos += i + 2;
+                return Ok(line);
+            }
+        }
+        Err(ParseTextError::ExpectedEndOfLine)
+    }
+
+    /// Token terminated by a space, e.g. the method of a request line
+    pub fn next_token<'a>(&mut self, data: &'a [u8]) -> Result<&'a str> {
+        let data = &data[self.pos..];
+        for (i, w) in data.windows(2).enumerate() {
+            if w[0] == b' ' {
+                let token = std::str::from_utf8(&data[..i])?;
+                self.pos += i + 1;
+                return Ok(token);
+            } else if w == b"\r\n" {
+                return Err(ParseTextError::ExpectedSpace);
+            }
+        }
+        Err(ParseTextError::ExpectedSpace)
+    }
+
+    /// Skips the rest of the line, which needn't be UTF-8, e.g. a reason phrase
+    pub fn discard_line(&mut self, data: &[u8]) -> Result<()> {
+        let data = &data[self.pos..];
+        let end = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or(ParseTextError::ExpectedEndOfLine)?;
+        self.pos += end + 2;
+        Ok(())
+    }
+
+    /// Returns the next header, `None` once the empty line ending the
+    /// header was parsed. A Content-Length header sets the body length.
+    pub fn next_header<'a>(&mut self, data: &'a [u8]) -> Result<Option<Header<'a>>> {
+        let line = self.next_line(data)?;
+        if line.is_empty() {
+            self.header_length = Some(self.pos);
+            return match self.message_bytes() {
+                Some(_) => Ok(None),
+                None => Err(ParseTextError::ContentLengthTooLarge),
+            };
+        }
+        let header = match self.lenient_headers {
+            true => Header::parse_lenient(line)?,
+            false => Header::try_from(line)?,
+        };
+        if header.name.eq_ignore_ascii_case("content-length") {
+            self.content_length = Some(header.value.parse()?);
+        }
+        Ok(Some(header))
+    }
+
+    /// Returns the body once it is complete, its length is the value of the
+    /// Content-Length header or 0
+    pub fn body<'a>(&mut self, data: &'a [u8]) -> Option<&'a [u8]> {
+        let length = self.content_length.unwrap_or(0);
+        let data = &data[self.pos..];
+        if data.len() < length {
+            return None;
+        }
+        self.pos += length;
+        Some(&data[..length])
+    }
+
+    /// Bytes parsed so far
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Length of the start line and headers including the empty line,
+    /// once they were parsed
+    pub fn header_length(&self) -> Option<usize> {
+        self.header_length
+    }
+
+    pub fn content_length(&self) -> Option<usize> {
+        self.content_length
+    }
+
+    /// Length of the message once the header was parsed
+    pub fn message_bytes(&self) -> Option<usize> {
+        self.header_length
+            .and_then(|length| length.checked_add(self.content_length.unwrap_or(0)))
+    }
+
+    /// Bytes of the body still missing once the header was parsed
+    pub fn missing_bytes(&self) -> Option<usize> {
+        self.message_bytes().map(|length| length - self.pos)
+    }
+}
+
+/// Finds the end of a message whose data arrives in pieces. Every call gets
+/// the data of the message so far, like [`TextParser`], but continues where
+/// the previous call stopped, so a large message costs one pass over its
+/// bytes however often it is fed. Only the Content-Length and Content-Type
+/// headers are decoded, the message is parsed once it is complete.
+#[derive(Debug, Default)]
+pub struct MessageFramer {
+    // Start of the line being scanned and how far it was scanned
+    line_start: usize,
+    scanned: usize,
+    header_length: Option<usize>,
+    content_length: Option<usize>,
+    content_type: bool,
+    lenient_headers: bool,
+    until_close: bool,
+    closed: bool,
+}
+
+impl MessageFramer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes headers with [`Header::parse_lenient`]
+    pub fn lenient_headers(mut self, lenient: bool) -> Self {
+        self.lenient_headers = lenient;
+        self
+    }
+
+    /// A message with a Content-Type but without a Content-Length has a
+    /// body that ends when the connection is closed, see [`MessageFramer::close`]
+    pub fn until_close(mut self, until_close: bool) -> Self {
+        self.until_close = until_close;
+        self
+    }
+
+    /// Length of the message once `data` holds all of it
+    pub fn feed(&mut self, data: &[u8]) -> Result<Option<usize>> {
+        while self.header_length.is_none() {
+            let Some(i) = data[self.scanned..].windows(2).position(|w| w == b"\r\n") else {
+                // A trailing CR may be the start of the line end
+                self.scanned = data.len().saturating_sub(1).max(self.line_start);
+                return Ok(None);
+            };
+            let end = self.scanned + i;
+            let line = &data[self.line_start..end];
+            if line.is_empty() {
+                self.header_length = Some(end + 2);
+                if self.message_bytes().is_none() && !self.ends_with_close() {
+                    return Err(ParseTextError::ContentLengthTooLarge);
+                }
+            } else if let Some(header) = self.header(line, "content-length")? {
+                self.content_length = Some(header.value.parse()?);
+            } else if self.header(line, "content-type")?.is_some() {
+                self.content_type = true;
+            }
+            self.line_start = end + 2;
+            self.scanned = end + 2;
+        }
+        if self.ends_with_close() {
+            return Ok(self.closed.then_some(data.len()));
+        }
+        Ok(self.message_bytes().filter(|length| data.len() >= *length))
+    }
+
+    /// Marks the end of the connection. True if the header of a message
+    /// whose body ends with it was scanned, feeding the data again then
+    /// returns the length of the message.
+    pub fn close(&mut self) -> bool {
+        self.closed = true;
+        self.header_length.is_some() && self.ends_with_close()
+    }
+
+    /// Length of the start line and headers including the empty line,
+    /// once they were scanned
+    pub fn header_length(&self) -> Option<usize> {
+        self.header_length
+    }
+
+    /// Length of the message once the header was scanned, unknown for a
+    /// body that ends with the connection
+    pub fn message_bytes(&self) -> Option<usize> {
+        if self.ends_with_close() {
+            return None;
+        }
+        self.header_length
+            .and_then(|length| length.checked_add(self.content_length.unwrap_or(0)))
+    }
+
+    fn ends_with_close(&self) -> bool {
+        self.until_close && self.content_type && self.content_length.is_none()
+    }
+
+    /// The header of `line` if it is named `name`
+    fn header<'a>(&self, line: &'a [u8], name: &str) -> Result<Option<Header<'a>>> {
+        if line.len() <= name.len() || !line[..name.len()].eq_ignore_ascii_case(name.as_bytes()) {
+            return Ok(None);
+        }
+        let line = std::str::from_utf8(line)?;
+        let header = match self.lenient_headers {
+            true => Header::parse_lenient(line)?,
+            false => Header::try_from(line)?,
+        };
+        Ok(header.name.eq_ignore_ascii_case(name).then_some(header))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_parser() {
+        let message = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let mut parser = TextParser::new("HTTP");
+        let version = parser.next_token(message).unwrap();
+        assert_eq!(parser.parse_version(version).unwrap(), Version::new(1, 1));
+        assert_eq!(parser.next_token(message).unwrap(), "200");
+        assert!(parser.next_token(message).unwrap_err().is_incomplete());
+        assert_eq!(parser.next_line(message).unwrap(), "OK");
+        assert_eq!(parser.missing_bytes(), None);
+        assert_eq!(
+            parser.next_header(message).unwrap(),
+            Some(Header::new("Content-Length", "5"))
+        );
+        assert_eq!(parser.next_header(message).unwrap(), None);
+        assert_eq!(parser.missing_bytes(), Some(5));
+        assert_eq!(parser.body(&message[..40]), None);
+        assert_eq!(parser.body(message), Some(&b"hello"[..]));
+        assert_eq!(parser.message_bytes(), Some(message.len()));
+    }
+
+    #[test]
+    fn test_parse_protocol_version() {
+        assert_eq!(parse_protocol_version("RTSP", "RTSP/2.0").unwrap(), Version::new(2, 0));
+        assert!(matches!(
+            parse_protocol_version("RTSP", "HTTP/1.1"),
+            Err(ParseTextError::UnexpectedProtocol(_))
+        ));
+        assert!(matches!(
+            parse_protocol_version("HTTP", "HTTP/1"),
+            Err(ParseTextError::ParseVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_message_framer() {
+        let message = b"RTSP/1.0 200 OK\r\nCSeq: 2\r\ncontent-length: 5\r\n\r\nhello$\x00";
+        let length = message.len() - 2;
+        let mut framer = MessageFramer::new();
+        for len in 0..length {
+            assert_eq!(framer.feed(&message[..len]).unwrap(), None);
+        }
+        assert_eq!(framer.header_length(), Some(length - 5));
+        assert_eq!(framer.message_bytes(), Some(length));
+        assert_eq!(framer.feed(message).unwrap(), Some(length));
+
+        let mut framer = MessageFramer::new();
+        let message = b"RTSP/1.0 200 OK\r\nContent-Length: x\r\n\r\n";
+        assert!(matches!(
+            framer.feed(message),
+            Err(ParseTextError::ParseContentLength(_))
+        ));
+    }
+
+    #[test]
+    fn test_content_length_too_large() {
+        let message = b"RTSP/1.0 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n";
+        let mut framer = MessageFramer::new();
+        assert!(matches!(
+            framer.feed(message),
+            Err(ParseTextError::ContentLengthTooLarge)
+        ));
+
+        let mut parser = TextParser::new("RTSP");
+        parser.next_line(message).unwrap();
+        parser.next_header(message).unwrap();
+        assert!(matches!(
+            parser.next_header(message),
+            Err(ParseTextError::ContentLengthTooLarge)
+        ));
+        assert_eq!(parser.message_bytes(), None);
+    }
+
+    #[test]
+    fn test_message_framer_until_close() {
+        let message = b"RTSP/1.0 200 OK\r\nCSeq: 2\r\nContent-Type : application/sdp\r\n\r\nv=0\r\n";
+        let mut framer = MessageFramer::new().until_close(true);
+        assert!(framer.feed(message).is_err());
+
+        let mut framer = MessageFramer::new().lenient_headers(true).until_close(true);
+        assert_eq!(framer.feed(message).unwrap(), None);
+        assert_eq!(framer.header_length(), Some(message.len() - 5));
+        assert_eq!(framer.message_bytes(), None);
+        assert!(framer.close());
+        assert_eq!(framer.feed(message).unwrap(), Some(message.len()));
+
+        // Without a Content-Type there is no body
+        let message = b"RTSP/1.0 200 OK\r\nCSeq: 2\r\n\r\n";
+        let mut framer = MessageFramer::new().until_close(true);
+        assert_eq!(framer.feed(message).unwrap(), Some(message.len()));
+        assert!(!framer.close());
+    }
+}
@@ -0,0 +1,219 @@
+use super::{request_target, ResponseParser};
+use base64::prelude::*;
+use rand::distr::{Alphanumeric, SampleString};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::TcpStream;
+use url::Url;
+
+pub const DEFAULT_HTTP_PORT: u16 = 80;
+/// Longest accepted response header of the GET request
+const MAX_HEADER_SIZE: usize = 8192;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Url has no host")]
+    MissingHost,
+    #[error("Invalid HTTP response")]
+    InvalidResponse,
+    #[error("Tunnel rejected with status {0}")]
+    Rejected(u16),
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// RTSP tunneled over HTTP, as introduced by QuickTime.
+///
+/// The server to client direction is the body of a GET request, the client
+/// to server direction is the base64 encoded body of a POST request. Both
+/// requests carry the same `x-sessioncookie` so the server can pair them.
+pub struct Tunnel {
+    get: TcpStream,
+    post: TcpStream,
+    // RTSP bytes received together with the GET response header
+    leftover: Vec<u8>,
+    // Encoded bytes of the current write not sent yet
+    pending: Vec<u8>,
+    pending_len: usize,
+}
+
+impl Tunnel {
+    /// Opens the GET and POST connections for the path of the `http://` url
+    pub async fn connect(url: &Url) -> Result<Self> {
+        let host = url.host_str().ok_or(Error::MissingHost)?;
+        let port = url.port().unwrap_or(DEFAULT_HTTP_PORT);
+        let get = TcpStream::connect((host, port)).await?;
+        let post = TcpStream::connect((host, port)).await?;
+        Self::open(get, post, url).await
+    }
+
+    /// Sets the tunnel up on two connections to the server of the `http://`
+    /// url, e.g. bound to a local address
+    pub async fn open(mut get: TcpStream, mut post: TcpStream, url: &Url) -> Result<Self> {
+        let cookie = Alphanumeric.sample_string(&mut rand::rng(), 22);
+        let path = request_target(url);
+
+        let request = format!(
+            "GET {} HTTP/1.0\r\nx-sessioncookie: {}\r\nAccept: application/x-rtsp-tunnelled\r\n\
+            Pragma: no-cache\r\nCache-Control: no-cache\r\n\r\n",
+            path, cookie
+        );
+        get.write_all(request.as_bytes()).await?;
+        let leftover = read_response(&mut get).await?;
+
+        // The body never ends, servers ignore the length
+        let request = format!(
+            "POST {} HTTP/1.0\r\nx-sessioncookie: {}\r\nContent-Type: application/x-rtsp-tunnelled\r\n\
+            Pragma: no-cache\r\nCache-Control: no-cache\r\nContent-Length: 32767\r\n\
+            Expires: Sun, 9 Jan 1972 00:00:00 GMT\r\n\r\n",
+            path, cookie
+        );
+        post.write_all(request.as_bytes()).await?;
+        Ok(Self {
+            get,
+            post,
+            leftover,
+            pending: Vec::new(),
+            pending_len: 0,
+        })
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.post).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads the response header of the GET request and returns the bytes following it
+async fn read_response(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut parser = ResponseParser::new().max_header_size(MAX_HEADER_SIZE).head_only();
+    let mut chunk = [0u8; 1024];
+    let leftover = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(Error::InvalidResponse);
+        }
+        let consumed = parser.push(&chunk[..n]).map_err(|_| Error::InvalidResponse)?;
+        if parser.is_done() {
+            break chunk[consumed..n].to_vec();
+        }
+    };
+    let status = parser.head().map_or(0, |r| r.status);
+    if status != 200 {
+        return Err(Error::Rejected(status));
+    }
+    Ok(leftover)
+}
+
+impl AsyncRead for Tunnel {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.leftover.is_empty() {
+            let n = this.leftover.len().min(buf.remaining());
+            buf.put_slice(&this.leftover[..n]);
+            this.leftover.drain(..n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.get).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Tunnel {
+    // Every write is encoded on its own. The encoded bytes are kept until
+    // they are sent, so a pending write must be retried with the same data.
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.pending.is_empty() {
+            this.pending = BASE64_STANDARD.encode(buf).into_bytes();
+            this.pending_len = buf.len();
+        }
+        ready!(this.poll_pending(cx))?;
+        Poll::Ready(Ok(this.pending_len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.post).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        ready!(Pin::new(&mut this.post).poll_shutdown(cx))?;
+        Pin::new(&mut this.get).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn read_header(stream: &mut TcpStream) -> String {
+        let mut header = Vec::new();
+        while !header.ends_with(b"\r\n\r\n") {
+            header.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(header).unwrap()
+    }
+
+    fn cookie(header: &str) -> &str {
+        header
+            .lines()
+            .find_map(|l| l.strip_prefix("x-sessioncookie: "))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tunnel() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/live?x=1", listener.local_addr().unwrap())).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut get, _) = listener.accept().await.unwrap();
+            let get_header = read_header(&mut get).await;
+            assert!(get_header.starts_with("GET /live?x=1 HTTP/1.0\r\n"));
+            get.write_all(b"HTTP/1.0 200 OK\r\nContent-Type: application/x-rtsp-tunnelled\r\n\r\nRTSP")
+                .await
+                .unwrap();
+            let (mut post, _) = listener.accept().await.unwrap();
+            let post_header = read_header(&mut post).await;
+            assert!(post_header.starts_with("POST /live?x=1 HTTP/1.0\r\n"));
+            assert_eq!(cookie(&get_header), cookie(&post_header));
+            let mut body = vec![0u8; 12];
+            post.read_exact(&mut body).await.unwrap();
+            assert_eq!(BASE64_STANDARD.decode(&body).unwrap(), b"OPTIONS");
+            get.write_all(b"/1.0").await.unwrap();
+        });
+
+        let mut tunnel = Tunnel::connect(&url).await.unwrap();
+        tunnel.write_all(b"OPTIONS").await.unwrap();
+        let mut buf = [0u8; 8];
+        tunnel.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"RTSP/1.0");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/live", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            let (mut get, _) = listener.accept().await.unwrap();
+            read_header(&mut get).await;
+            get.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await.unwrap();
+        });
+        assert!(matches!(Tunnel::connect(&url).await, Err(Error::Rejected(403))));
+    }
+}
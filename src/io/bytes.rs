@@ -1,8 +1,12 @@
 use std::io::{Cursor, Read, Result};
+
+/// Bounds-checked big-endian reads, running out of data is an
+/// `UnexpectedEof` error instead of a panic
 pub trait ReadBytes {
     fn read_u8(&mut self) -> Result<u8>;
     fn read_u16(&mut self) -> Result<u16>;
     fn read_u32(&mut self) -> Result<u32>;
+    fn read_u64(&mut self) -> Result<u64>;
 }
 
 impl ReadBytes for Cursor<&[u8]> {
@@ -23,4 +27,10 @@ impl ReadBytes for Cursor<&[u8]> {
         self.read_exact(&mut buf)?;
         Ok(u32::from_be_bytes(buf))
     }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
 }
@@ -0,0 +1,23 @@
+pub mod codec;
+pub mod filter;
+pub mod http;
+pub mod metrics;
+pub mod recorder;
+pub mod rtcp;
+pub mod rtp;
+pub mod rtsp;
+pub mod sdp;
+pub mod segment;
+pub mod srtp;
+pub mod stats;
+pub mod sync;
+pub mod ts;
+pub mod types;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+mod fanout;
+mod io;
+mod task;
+mod trace;
@@ -0,0 +1,224 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Where memory charged to a [`MemoryBudget`] is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Component {
+    ReceiveBuffer,
+    SendBuffer,
+    Jitter,
+    /// Partially assembled and not yet consumed frames
+    Assembly,
+}
+
+impl Component {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Memory budget exceeded by {component:?}: requested {requested} bytes, {available} available")]
+pub struct BudgetError {
+    pub component: Component,
+    pub requested: usize,
+    pub available: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetUsage {
+    /// `None` for an unlimited budget
+    pub limit: Option<usize>,
+    pub used: usize,
+    /// Highest usage so far
+    pub peak: usize,
+    /// Requests that were rejected because they exceeded the limit
+    pub rejected: u64,
+    pub receive_buffer: usize,
+    pub send_buffer: usize,
+    pub jitter: usize,
+    pub assembly: usize,
+}
+
+impl BudgetUsage {
+    pub fn component(&self, component: Component) -> usize {
+        match component {
+            Component::ReceiveBuffer => self.receive_buffer,
+            Component::SendBuffer => self.send_buffer,
+            Component::Jitter => self.jitter,
+            Component::Assembly => self.assembly,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    limit: Option<usize>,
+    used: AtomicUsize,
+    peak: AtomicUsize,
+    rejected: AtomicU64,
+    components: [AtomicUsize; 4],
+}
+
+/// Upper bound of the memory the buffers of one client may use.
+///
+/// The budget is shared by cloning it into the channel, jitter buffers and
+/// depacketizers of a client, so with many sessions in one process the
+/// memory per session stays predictable. Growth beyond the limit fails
+/// with a [`BudgetError`] instead of allocating.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                limit: Some(limit),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Only accounts the memory without limiting it
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn try_reserve(&self, component: Component, n: usize) -> Result<(), BudgetError> {
+        let inner = &self.inner;
+        let result = inner.used.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+            let new = used.checked_add(n)?;
+            inner.limit.is_none_or(|limit| new <= limit).then_some(new)
+        });
+        match result {
+            Ok(used) => {
+                inner.peak.fetch_max(used + n, Ordering::AcqRel);
+                inner.components[component.index()].fetch_add(n, Ordering::AcqRel);
+                Ok(())
+            }
+            Err(used) => {
+                inner.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(BudgetError {
+                    component,
+                    requested: n,
+                    available: inner.limit.unwrap_or(usize::MAX).saturating_sub(used),
+                })
+            }
+        }
+    }
+
+    pub fn release(&self, component: Component, n: usize) {
+        self.inner.components[component.index()].fetch_sub(n, Ordering::AcqRel);
+        self.inner.used.fetch_sub(n, Ordering::AcqRel);
+    }
+
+    pub fn usage(&self) -> BudgetUsage {
+        let inner = &self.inner;
+        let component = |c: Component| inner.components[c.index()].load(Ordering::Acquire);
+        BudgetUsage {
+            limit: inner.limit,
+            used: inner.used.load(Ordering::Acquire),
+            peak: inner.peak.load(Ordering::Acquire),
+            rejected: inner.rejected.load(Ordering::Relaxed),
+            receive_buffer: component(Component::ReceiveBuffer),
+            send_buffer: component(Component::SendBuffer),
+            jitter: component(Component::Jitter),
+            assembly: component(Component::Assembly),
+        }
+    }
+}
+
+/// Memory of one buffer charged to a budget, released when dropped
+#[derive(Debug)]
+pub struct Charge {
+    budget: MemoryBudget,
+    component: Component,
+    charged: usize,
+}
+
+impl Charge {
+    pub fn new(budget: MemoryBudget, component: Component) -> Self {
+        Self {
+            budget,
+            component,
+            charged: 0,
+        }
+    }
+
+    pub fn charged(&self) -> usize {
+        self.charged
+    }
+
+    /// Charges or releases the difference to `size`, growth fails if it exceeds the budget
+    pub fn resize(&mut self, size: usize) -> Result<(), BudgetError> {
+        if size > self.charged {
+            self.budget.try_reserve(self.component, size - self.charged)?;
+        } else {
+            self.budget.release(self.component, self.charged - size);
+        }
+        self.charged = size;
+        Ok(())
+    }
+
+    /// Moves `size` of the charged memory to a new charge, e.g. for data
+    /// split off a buffer that still holds on to the memory
+    pub fn split(&mut self, size: usize) -> Charge {
+        let size = size.min(self.charged);
+        self.charged -= size;
+        Self {
+            budget: self.budget.clone(),
+            component: self.component,
+            charged: size,
+        }
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.budget.release(self.component, self.charged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(100);
+        budget.try_reserve(Component::Jitter, 60).unwrap();
+        let err = budget.try_reserve(Component::ReceiveBuffer, 50).unwrap_err();
+        assert_eq!(err.available, 40);
+        budget.try_reserve(Component::ReceiveBuffer, 40).unwrap();
+        budget.release(Component::Jitter, 60);
+        let usage = budget.usage();
+        assert_eq!(usage.used, 40);
+        assert_eq!(usage.peak, 100);
+        assert_eq!(usage.rejected, 1);
+        assert_eq!(usage.component(Component::ReceiveBuffer), 40);
+        assert_eq!(usage.jitter, 0);
+    }
+
+    #[test]
+    fn test_charge() {
+        let budget = MemoryBudget::new(100);
+        let mut charge = Charge::new(budget.clone(), Component::Assembly);
+        charge.resize(80).unwrap();
+        assert!(charge.resize(120).is_err());
+        assert_eq!(charge.charged(), 80);
+        charge.resize(30).unwrap();
+        assert_eq!(budget.usage().assembly, 30);
+        let split = charge.split(20);
+        assert_eq!((charge.charged(), split.charged()), (10, 20));
+        drop(split);
+        assert_eq!(budget.usage().assembly, 10);
+        drop(charge);
+        assert_eq!(budget.usage().used, 0);
+        assert!(MemoryBudget::unlimited()
+            .try_reserve(Component::Jitter, usize::MAX)
+            .is_ok());
+    }
+}
@@ -0,0 +1,143 @@
+/// Histogram with exponentially growing buckets.
+///
+/// The first bucket counts values up to `first_bound`, every further bucket
+/// doubles the bound. Values beyond the last bound are counted in an
+/// overflow bucket, so memory stays constant however many values are recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    first_bound: u64,
+    // One count per bound plus the overflow bucket
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn new(first_bound: u64, buckets: usize) -> Self {
+        Self {
+            first_bound: first_bound.max(1),
+            counts: vec![0; buckets + 1],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    fn bound(&self, index: usize) -> Option<u64> {
+        (index + 1 < self.counts.len()).then(|| self.first_bound.saturating_mul(1 << index.min(63)))
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let index = (0..self.counts.len())
+            .find(|&i| self.bound(i).is_none_or(|bound| value <= bound))
+            .unwrap_or(self.counts.len() - 1);
+        self.counts[index] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    pub fn mean(&self) -> Option<u64> {
+        (self.count > 0).then(|| self.sum / self.count)
+    }
+
+    /// Upper bound of the bucket containing the `q` quantile, the maximum
+    /// for values in the overflow bucket
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(self.bound(i).unwrap_or(self.max).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Upper bound and count of every bucket, `None` for the overflow bucket
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.counts.iter().enumerate().map(|(i, count)| (self.bound(i), *count))
+    }
+
+    /// Adds the values of a histogram with the same buckets
+    pub fn merge(&mut self, other: &Histogram) {
+        debug_assert_eq!(self.first_bound, other.first_bound);
+        debug_assert_eq!(self.counts.len(), other.counts.len());
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets() {
+        let mut histogram = Histogram::new(10, 3);
+        for value in [0, 10, 11, 20, 35, 1000] {
+            histogram.record(value);
+        }
+        let buckets: Vec<_> = histogram.buckets().collect();
+        assert_eq!(buckets, [(Some(10), 2), (Some(20), 2), (Some(40), 1), (None, 1)]);
+        assert_eq!(histogram.count(), 6);
+        assert_eq!(histogram.min(), Some(0));
+        assert_eq!(histogram.max(), Some(1000));
+        assert_eq!(histogram.mean(), Some(1076 / 6));
+    }
+
+    #[test]
+    fn test_histogram_quantile() {
+        let mut histogram = Histogram::new(1, 8);
+        assert_eq!(histogram.quantile(0.5), None);
+        for value in 1..=100 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.quantile(0.5), Some(64));
+        assert_eq!(histogram.quantile(0.99), Some(100));
+        assert_eq!(histogram.quantile(0.0), Some(1));
+    }
+
+    #[test]
+    fn test_histogram_merge() {
+        let mut a = Histogram::new(10, 2);
+        let mut b = Histogram::new(10, 2);
+        a.record(5);
+        b.record(15);
+        b.record(100);
+        a.merge(&b);
+        assert_eq!(a.count(), 3);
+        assert_eq!(a.buckets().map(|(_, c)| c).collect::<Vec<_>>(), [1, 1, 1]);
+        assert_eq!(a.max(), Some(100));
+    }
+}
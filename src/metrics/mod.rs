@@ -0,0 +1,9 @@
+mod budget;
+mod histogram;
+
+pub use budget::BudgetError;
+pub use budget::BudgetUsage;
+pub use budget::Charge;
+pub use budget::Component;
+pub use budget::MemoryBudget;
+pub use histogram::Histogram;
@@ -0,0 +1,11 @@
+mod ring;
+mod timeshift;
+
+pub use ring::Clip;
+pub use ring::FrameRing;
+pub use ring::PreEventRecorder;
+pub use ring::RecordedFrame;
+pub use timeshift::Pacing;
+pub use timeshift::TimeShift;
+pub use timeshift::TimeShiftReader;
+pub use timeshift::DEFAULT_LIVE_CAPACITY;
@@ -0,0 +1,322 @@
+use crate::types::Frame;
+use crate::types::MediaType;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+    pub track: usize,
+    /// When the frame was pushed, usually its arrival time
+    pub time: Instant,
+    pub frame: Frame,
+}
+
+/// Circular buffer of the frames of one track that arrived within the
+/// last `window`.
+#[derive(Debug)]
+pub struct FrameRing {
+    window: Duration,
+    frames: VecDeque<(Instant, Frame)>,
+    bytes: usize,
+}
+
+impl FrameRing {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            frames: VecDeque::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn push(&mut self, time: Instant, frame: Frame) {
+        self.bytes += frame.data.len();
+        self.frames.push_back((time, frame));
+        while let Some((oldest, _)) = self.frames.front() {
+            if time.saturating_duration_since(*oldest) <= self.window {
+                break;
+            }
+            if let Some((_, frame)) = self.frames.pop_front() {
+                self.bytes -= frame.data.len();
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Payload bytes currently held
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Time between the oldest and the newest frame
+    pub fn span(&self) -> Duration {
+        match (self.frames.front(), self.frames.back()) {
+            (Some((first, _)), Some((last, _))) => last.saturating_duration_since(*first),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Frames from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &(Instant, Frame)> {
+        self.frames.iter()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = (Instant, Frame)> + '_ {
+        self.bytes = 0;
+        self.frames.drain(..)
+    }
+}
+
+/// Frames of all tracks exported from a [`PreEventRecorder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clip {
+    /// The requested start or the earlier keyframe the clip starts with
+    pub start: Instant,
+    /// In time order
+    pub frames: Vec<RecordedFrame>,
+}
+
+/// Keeps the last seconds of every track so they can be saved when an
+/// alarm fires ("pre-event recording").
+///
+/// Once [`PreEventRecorder::freeze`] is called, new frames are ignored so
+/// the content stays as it was at the trigger until it is drained.
+#[derive(Debug)]
+pub struct PreEventRecorder {
+    window: Duration,
+    tracks: HashMap<usize, FrameRing>,
+    frozen: bool,
+}
+
+impl PreEventRecorder {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            tracks: HashMap::new(),
+            frozen: false,
+        }
+    }
+
+    pub fn push(&mut self, track: usize, time: Instant, frame: Frame) {
+        if self.frozen {
+            return;
+        }
+        let window = self.window;
+        self.tracks
+            .entry(track)
+            .or_insert_with(|| FrameRing::new(window))
+            .push(time, frame);
+    }
+
+    pub fn track(&self, track: usize) -> Option<&FrameRing> {
+        self.tracks.get(&track)
+    }
+
+    /// Push time of the oldest frame of all tracks
+    pub fn earliest(&self) -> Option<Instant> {
+        self.tracks
+            .values()
+            .filter_map(|r| r.iter().next())
+            .map(|(t, _)| *t)
+            .min()
+    }
+
+    /// Push time of the newest frame of all tracks
+    pub fn latest(&self) -> Option<Instant> {
+        self.tracks
+            .values()
+            .filter_map(|r| r.iter().last())
+            .map(|(t, _)| *t)
+            .max()
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Copies the frames of `range` of all tracks without draining them,
+    /// e.g. to extract a clip around an incident.
+    ///
+    /// Every video track starts at its last keyframe before the range, so
+    /// the clip can be decoded from the start. Other tracks start with the
+    /// earliest video track. Video tracks without a keyframe up to the end
+    /// of the range are left out. The times are those passed to
+    /// [`PreEventRecorder::push`], push capture times of a
+    /// [`Synchronizer`](crate::sync::Synchronizer) for tracks that are
+    /// aligned on the sender's clock.
+    pub fn export(&self, range: Range<Instant>) -> Clip {
+        // Start of every video track, None if it has no keyframe
+        let mut video_starts = HashMap::new();
+        for (&track, ring) in &self.tracks {
+            if !ring.iter().any(|(_, f)| f.media_type == MediaType::Video) {
+                continue;
+            }
+            let keyframes = ring
+                .iter()
+                .filter(|(time, f)| *time < range.end && f.is_keyframe())
+                .map(|(time, _)| *time);
+            let mut start = None;
+            for time in keyframes {
+                if start.is_some() && time > range.start {
+                    break;
+                }
+                start = Some(time);
+            }
+            video_starts.insert(track, start);
+        }
+        let start = video_starts
+            .values()
+            .flatten()
+            .min()
+            .copied()
+            .map_or(range.start, |start| start.min(range.start));
+
+        let mut frames: Vec<RecordedFrame> = self
+            .tracks
+            .iter()
+            .flat_map(|(&track, ring)| {
+                let track_start = match video_starts.get(&track) {
+                    Some(Some(video_start)) => Some(*video_start),
+                    Some(None) => None,
+                    None => Some(start),
+                };
+                ring.iter()
+                    .filter(move |(time, _)| track_start.is_some_and(|s| *time >= s && *time < range.end))
+                    .map(move |(time, frame)| RecordedFrame {
+                        track,
+                        time: *time,
+                        frame: frame.clone(),
+                    })
+            })
+            .collect();
+        frames.sort_by_key(|f| f.time);
+        Clip { start, frames }
+    }
+
+    /// Takes the frames of all tracks in time order and resumes recording
+    pub fn drain(&mut self) -> Vec<RecordedFrame> {
+        let mut frames: Vec<RecordedFrame> = self
+            .tracks
+            .iter_mut()
+            .flat_map(|(&track, ring)| {
+                ring.drain()
+                    .map(move |(time, frame)| RecordedFrame { track, time, frame })
+            })
+            .collect();
+        // Stable, frames of a track with equal times keep their order
+        frames.sort_by_key(|f| f.time);
+        self.frozen = false;
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FrameMetadata, FrameType, MediaType};
+
+    fn frame(timestamp: u32) -> Frame {
+        Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp,
+            data: vec![0; 10],
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn test_frame_ring_window() {
+        let start = Instant::now();
+        let mut ring = FrameRing::new(Duration::from_secs(2));
+        for i in 0..5 {
+            ring.push(start + Duration::from_secs(i), frame(i as u32));
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.bytes(), 30);
+        assert_eq!(ring.span(), Duration::from_secs(2));
+        let timestamps: Vec<u32> = ring.drain().map(|(_, f)| f.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3, 4]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn test_pre_event_recorder() {
+        let start = Instant::now();
+        let mut recorder = PreEventRecorder::new(Duration::from_secs(10));
+        recorder.push(0, start, frame(1));
+        recorder.push(1, start + Duration::from_millis(5), frame(2));
+        recorder.push(0, start + Duration::from_millis(10), frame(3));
+        recorder.freeze();
+        recorder.push(0, start + Duration::from_millis(15), frame(4));
+        let frames = recorder.drain();
+        let order: Vec<(usize, u32)> = frames.iter().map(|f| (f.track, f.frame.timestamp)).collect();
+        assert_eq!(order, vec![(0, 1), (1, 2), (0, 3)]);
+        assert!(!recorder.is_frozen());
+        assert!(recorder.track(0).unwrap().is_empty());
+    }
+
+    fn h264(timestamp: u32, keyframe: bool) -> Frame {
+        let nal = if keyframe { 0x65 } else { 0x41 };
+        Frame {
+            data: vec![0, 0, 0, 1, nal, 0],
+            ..frame(timestamp)
+        }
+    }
+
+    fn audio(timestamp: u32) -> Frame {
+        Frame {
+            media_type: MediaType::Audio,
+            frame_type: FrameType::AAC,
+            ..frame(timestamp)
+        }
+    }
+
+    #[test]
+    fn test_pre_event_recorder_export() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut recorder = PreEventRecorder::new(Duration::from_secs(10));
+        // GOPs of camera 0 start at 0 and 400 ms, of camera 1 at 300 ms
+        for i in 0..8 {
+            recorder.push(0, at(i * 100), h264(i as u32, i % 4 == 0));
+            recorder.push(1, at(i * 100 + 10), h264(i as u32, i == 3));
+            recorder.push(2, at(i * 100 + 20), audio(i as u32));
+        }
+        // Camera 2 never sent a keyframe
+        recorder.push(3, at(0), h264(0, false));
+
+        let clip = recorder.export(at(350)..at(600));
+        assert_eq!(clip.start, at(0));
+        let frames = |track| -> Vec<u32> {
+            clip.frames
+                .iter()
+                .filter(|f| f.track == track)
+                .map(|f| f.frame.timestamp)
+                .collect()
+        };
+        assert_eq!(frames(0), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(frames(1), [3, 4, 5]);
+        assert_eq!(frames(2), [0, 1, 2, 3, 4, 5]);
+        assert!(frames(3).is_empty());
+        assert!(clip.frames.windows(2).all(|w| w[0].time <= w[1].time));
+        // Exporting doesn't drain
+        assert_eq!(recorder.track(0).unwrap().len(), 8);
+
+        // A range starting before any keyframe starts at the first one
+        let clip = recorder.export(at(0)..at(350));
+        assert_eq!(clip.frames.iter().filter(|f| f.track == 1).count(), 1);
+    }
+}
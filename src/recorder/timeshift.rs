@@ -0,0 +1,250 @@
+use super::{PreEventRecorder, RecordedFrame};
+use crate::fanout::{Fanout, FanoutReceiver, Received};
+use crate::types::Frame;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Live frames buffered per reader unless set with [`TimeShift::with_capacity`]
+pub const DEFAULT_LIVE_CAPACITY: usize = 256;
+
+/// How a [`TimeShiftReader`] delivers the recorded frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// The recorded frames at once, then the live ones as they arrive
+    Burst,
+    /// Every frame as long after its push as the offset of the attach, so
+    /// the reader stays behind the live stream
+    Delayed,
+}
+
+struct Inner {
+    recorder: PreEventRecorder,
+    live_tx: Fanout<RecordedFrame>,
+}
+
+/// Keeps the last `window` of a live session and lets consumers attach
+/// with an offset, e.g. to start 10 seconds ago. A reader gets the
+/// recorded frames from the offset on and continues with the live frames
+/// without a gap or a duplicate, since a frame is recorded and sent to the
+/// readers at once.
+///
+/// Recorded playback starts at the last keyframe before the offset of
+/// every video track, like [`PreEventRecorder::export`].
+///
+/// ```ignore
+/// let timeshift = TimeShift::new(Duration::from_secs(30));
+/// // Producer
+/// timeshift.push(track, Instant::now(), frame);
+/// // Consumer
+/// let mut reader = timeshift.attach(Duration::from_secs(10), Pacing::Delayed);
+/// while let Some(frame) = reader.recv().await { .. }
+/// ```
+#[derive(Clone)]
+pub struct TimeShift {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl TimeShift {
+    pub fn new(window: Duration) -> Self {
+        Self::with_capacity(window, DEFAULT_LIVE_CAPACITY)
+    }
+
+    /// Buffers up to `capacity` live frames for a reader that falls
+    /// behind, older ones are skipped
+    pub fn with_capacity(window: Duration, capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                recorder: PreEventRecorder::new(window),
+                live_tx: Fanout::new(capacity),
+            })),
+        }
+    }
+
+    pub fn push(&self, track: usize, time: Instant, frame: Frame) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.recorder.push(track, time, frame.clone());
+        // Without readers the frame is only recorded for later ones
+        inner.live_tx.send(RecordedFrame { track, time, frame });
+    }
+
+    /// Time between the oldest and the newest recorded frame
+    pub fn span(&self) -> Duration {
+        let inner = self.inner.lock().unwrap();
+        match (inner.recorder.earliest(), inner.recorder.latest()) {
+            (Some(earliest), Some(latest)) => latest.saturating_duration_since(earliest),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn readers(&self) -> usize {
+        self.inner.lock().unwrap().live_tx.receivers()
+    }
+
+    /// A reader starting `offset` before the newest frame, as far back as
+    /// the window reaches. A zero offset reads live.
+    pub fn attach(&self, offset: Duration, pacing: Pacing) -> TimeShiftReader {
+        let inner = self.inner.lock().unwrap();
+        let live_rx = inner.live_tx.subscribe();
+        let (backlog, start) = match inner.recorder.latest() {
+            Some(latest) if !offset.is_zero() => {
+                let start = latest.checked_sub(offset).unwrap_or(latest);
+                let end = latest + Duration::from_nanos(1);
+                let clip = inner.recorder.export(start..end);
+                (clip.frames.into(), Some(start))
+            }
+            _ => (VecDeque::new(), None),
+        };
+        TimeShiftReader {
+            backlog,
+            live_rx,
+            live_closed: false,
+            pacing,
+            shift: start.map(|start| (start, tokio::time::Instant::now())),
+        }
+    }
+}
+
+/// Frames of a [`TimeShift`] from the offset of the attach on, recorded
+/// ones first
+pub struct TimeShiftReader {
+    backlog: VecDeque<RecordedFrame>,
+    live_rx: FanoutReceiver<RecordedFrame>,
+    live_closed: bool,
+    pacing: Pacing,
+    // Requested start and when the reader attached
+    shift: Option<(Instant, tokio::time::Instant)>,
+}
+
+impl TimeShiftReader {
+    /// Whether the reader caught up with the live stream
+    pub fn is_live(&self) -> bool {
+        self.backlog.is_empty()
+    }
+
+    /// Live frames skipped because the reader fell behind
+    pub fn skipped(&self) -> u64 {
+        self.live_rx.lost()
+    }
+
+    /// The next frame, `None` once the [`TimeShift`] is dropped and all
+    /// frames were delivered
+    pub async fn recv(&mut self) -> Option<RecordedFrame> {
+        loop {
+            if let Some(frame) = self.backlog.front() {
+                let Some(due) = self.due(frame) else {
+                    return self.backlog.pop_front();
+                };
+                if self.live_closed || due <= tokio::time::Instant::now() {
+                    tokio::time::sleep_until(due).await;
+                    return self.backlog.pop_front();
+                }
+                // Live frames are queued while waiting, the reader would lag otherwise
+                tokio::select! {
+                    _ = tokio::time::sleep_until(due) => {}
+                    live = self.live_rx.recv() => self.queue(live),
+                }
+                continue;
+            }
+            if self.live_closed {
+                return None;
+            }
+            let live = self.live_rx.recv().await;
+            match self.pacing {
+                Pacing::Burst => match live {
+                    Received::Value(frame) => return Some(frame),
+                    live => self.queue(live),
+                },
+                Pacing::Delayed => self.queue(live),
+            }
+        }
+    }
+
+    /// When a frame is delivered, `None` for right away
+    fn due(&self, frame: &RecordedFrame) -> Option<tokio::time::Instant> {
+        match (self.pacing, self.shift) {
+            (Pacing::Delayed, Some((start, attached))) => Some(attached + frame.time.saturating_duration_since(start)),
+            _ => None,
+        }
+    }
+
+    fn queue(&mut self, live: Received<RecordedFrame>) {
+        match live {
+            Received::Value(frame) => self.backlog.push_back(frame),
+            Received::Lagged(n) => log::warn!("Time-shift reader too slow, skipped {} frames", n),
+            Received::Closed => self.live_closed = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FrameMetadata, FrameType, MediaType};
+
+    fn h264(timestamp: u32, keyframe: bool) -> Frame {
+        let nal = if keyframe { 0x65 } else { 0x41 };
+        Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp,
+            data: vec![0, 0, 0, 1, nal, 0],
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeshift_burst() {
+        let timeshift = TimeShift::new(Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        // A keyframe every 4 frames of 100 ms
+        for i in 0..10 {
+            timeshift.push(0, at(i * 100), h264(i as u32, i % 4 == 0));
+        }
+        assert_eq!(timeshift.span(), Duration::from_millis(900));
+
+        // 300 ms back starts at the keyframe at 400 ms
+        let mut reader = timeshift.attach(Duration::from_millis(300), Pacing::Burst);
+        let mut live = timeshift.attach(Duration::ZERO, Pacing::Burst);
+        assert!(live.is_live());
+        assert_eq!(timeshift.readers(), 2);
+        timeshift.push(0, at(1000), h264(10, false));
+        let mut timestamps = Vec::new();
+        for _ in 0..7 {
+            timestamps.push(reader.recv().await.unwrap().frame.timestamp);
+        }
+        assert_eq!(timestamps, [4, 5, 6, 7, 8, 9, 10]);
+        assert!(reader.is_live());
+        assert_eq!(live.recv().await.unwrap().frame.timestamp, 10);
+
+        drop(timeshift);
+        assert_eq!(reader.recv().await, None);
+        assert_eq!(live.recv().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeshift_delayed() {
+        let timeshift = TimeShift::new(Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        for i in 0..5 {
+            timeshift.push(0, at(i * 100), h264(i as u32, true));
+        }
+        let attached = tokio::time::Instant::now();
+        let mut reader = timeshift.attach(Duration::from_millis(200), Pacing::Delayed);
+        let producer = timeshift.clone();
+        let live = at(500);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            producer.push(0, live, h264(5, true));
+        });
+
+        // Frames keep the 200 ms offset, the live one included
+        for (timestamp, delay) in [(2, 0), (3, 100), (4, 200), (5, 300)] {
+            let frame = reader.recv().await.unwrap();
+            assert_eq!(frame.frame.timestamp, timestamp);
+            assert_eq!(attached.elapsed(), Duration::from_millis(delay));
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use super::Header;
+use std::io;
+
+/// RTCP Application-Defined packet
+/// - SSRC/CSRC: 32 bits
+/// - name: 4 ASCII characters
+/// - application-dependent data
+pub struct App<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> App<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        if buf.len() < 12 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid RTCP App"));
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn header(&self) -> Header<'_> {
+        Header::new(&self.buf[0..4]).unwrap()
+    }
+
+    /// The subtype, carried in the count field
+    pub fn subtype(&self) -> u8 {
+        self.header().count() as u8
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+    }
+
+    pub fn name(&self) -> &'a [u8] {
+        &self.buf[8..12]
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        &self.buf[12..]
+    }
+}
@@ -0,0 +1,85 @@
+use super::{Header, PacketType};
+use std::io;
+
+/// Serializes a Generic NACK (RFC 4585 6.2.1) requesting the retransmission
+/// of the `lost` packets of `media_ssrc`. Sequence numbers within 16 of the
+/// first one of an entry share it, so they are best passed in order.
+pub fn generic_nack(sender_ssrc: u32, media_ssrc: u32, lost: &[u16]) -> Vec<u8> {
+    let mut entries: Vec<(u16, u16)> = Vec::new();
+    for &seq in lost {
+        match entries.last_mut() {
+            Some((pid, blp)) if (1..=16).contains(&seq.wrapping_sub(*pid)) => {
+                *blp |= 1 << (seq.wrapping_sub(*pid) - 1);
+            }
+            Some((pid, _)) if *pid == seq => {}
+            _ => entries.push((seq, 0)),
+        }
+    }
+    let mut out = Vec::with_capacity(12 + 4 * entries.len());
+    out.push(0x81);
+    out.push(PacketType::TransportLayerFeedback as u8);
+    out.extend_from_slice(&((2 + entries.len()) as u16).to_be_bytes());
+    out.extend_from_slice(&sender_ssrc.to_be_bytes());
+    out.extend_from_slice(&media_ssrc.to_be_bytes());
+    for (pid, blp) in entries {
+        out.extend_from_slice(&pid.to_be_bytes());
+        out.extend_from_slice(&blp.to_be_bytes());
+    }
+    out
+}
+
+/// RTCP feedback message (RFC 4585 6.1), a transport layer (RTPFB) or
+/// payload specific (PSFB) packet
+/// - SSRC of packet sender: 32 bits
+/// - SSRC of media source: 32 bits
+/// - feedback control information (FCI), depending on the format
+pub struct Feedback<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Feedback<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        if buf.len() < 12 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid RTCP Feedback"));
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn header(&self) -> Header<'_> {
+        Header::new(&self.buf[0..4]).unwrap()
+    }
+
+    /// The feedback message type, carried in the count field
+    pub fn format(&self) -> u8 {
+        self.header().count() as u8
+    }
+
+    pub fn sender_ssrc(&self) -> u32 {
+        u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+    }
+
+    pub fn media_ssrc(&self) -> u32 {
+        u32::from_be_bytes([self.buf[8], self.buf[9], self.buf[10], self.buf[11]])
+    }
+
+    pub fn fci(&self) -> &'a [u8] {
+        &self.buf[12..]
+    }
+
+    /// Generic NACK entries (RTPFB, format 1): the first lost sequence
+    /// number and the bitmask of the following 16 lost packets
+    pub fn nacks(&self) -> Vec<(u16, u16)> {
+        if self.header().packet_type() != PacketType::TransportLayerFeedback || self.format() != 1 {
+            return Vec::new();
+        }
+        self.fci()
+            .chunks_exact(4)
+            .map(|b| (u16::from_be_bytes([b[0], b[1]]), u16::from_be_bytes([b[2], b[3]])))
+            .collect()
+    }
+
+    /// Picture Loss Indication (PSFB, format 1)
+    pub fn is_pli(&self) -> bool {
+        self.header().packet_type() == PacketType::PayloadSpecificFeedback && self.format() == 1
+    }
+}
@@ -0,0 +1,41 @@
+use super::Header;
+use std::io;
+
+/// RTCP Goodbye (BYE) packet
+/// - SSRC/CSRC: 32 bits each, as many as the count field says
+/// - optional reason: length (8 bits) followed by the text
+pub struct Goodbye<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Goodbye<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        let end = 4 + Header::new(buf)?.count() * 4;
+        let valid = match buf.get(end) {
+            Some(&len) => buf.len() > end + len as usize,
+            None => buf.len() == end,
+        };
+        if !valid {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid RTCP Goodbye"));
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn header(&self) -> Header<'_> {
+        Header::new(&self.buf[0..4]).unwrap()
+    }
+
+    pub fn sources(&self) -> Vec<u32> {
+        self.buf[4..4 + self.header().count() * 4]
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    /// Reason for leaving, `None` if absent or not valid UTF-8
+    pub fn reason(&self) -> Option<&'a str> {
+        let offset = 4 + self.header().count() * 4;
+        let len = *self.buf.get(offset)? as usize;
+        std::str::from_utf8(&self.buf[offset + 1..offset + 1 + len]).ok()
+    }
+}
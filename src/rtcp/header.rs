@@ -1,5 +1,6 @@
 use std::io;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PacketType {
     Unknown = 0,
     SenderReport = 200,
@@ -1,10 +1,32 @@
+mod app;
+mod feedback;
+mod goodbye;
 mod header;
 mod packet;
+mod receiver_report;
+mod reception;
 mod report_block;
 mod sender_report;
 mod sdes;
 
+pub use app::App;
+pub use goodbye::Goodbye;
 pub use header::Header;
+pub use header::PacketType;
+pub use header::Version;
+pub use packet::CompoundPacket;
+pub use packet::CompoundPacketIterator;
 pub use packet::Packet;
+pub use packet::RtcpPacket;
+pub use receiver_report::ReceiverReport;
 pub use report_block::ReportBlock;
+pub use sdes::SDESChunk;
+pub use sdes::SDESItem;
+pub use sdes::SDESItemType;
+pub use sdes::SourceDescription;
 pub use sender_report::SenderReport;
+pub use feedback::Feedback;
+pub use feedback::generic_nack;
+pub use reception::receiver_report;
+pub use reception::ReceptionReport;
+pub use reception::ReceptionStats;
@@ -1,6 +1,11 @@
-use super::{Header, SenderReport};
+use super::{App, Feedback, PacketType, Goodbye, Header, ReceiverReport, SenderReport, SourceDescription};
+use bytes::Bytes;
 use std::io;
 
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
 pub struct Packet<'a> {
     pub buf: &'a [u8],
 }
@@ -16,12 +21,43 @@ impl<'a> Packet<'a> {
         Ok(Self { buf })
     }
 
-    pub fn header(&self) -> Header {
+    pub fn header(&self) -> Header<'_> {
         Header::new(&self.buf[0..4]).unwrap()
     }
 
-    pub fn to_sender_report(&self) -> Result<SenderReport, io::Error> {
-        SenderReport::new(&self.buf)
+    pub fn to_sender_report(&self) -> Result<SenderReport<'_>, io::Error> {
+        SenderReport::new(self.buf)
+    }
+}
+
+/// A RTCP packet parsed according to its packet type
+pub enum RtcpPacket<'a> {
+    SenderReport(SenderReport<'a>),
+    ReceiverReport(ReceiverReport<'a>),
+    Sdes(SourceDescription<'a>),
+    Bye(Goodbye<'a>),
+    App(App<'a>),
+    /// Transport layer or payload specific feedback (RFC 4585)
+    Feedback(Feedback<'a>),
+    /// Packet types without typed parsing, e.g. extended reports
+    Other(Packet<'a>),
+}
+
+impl<'a> RtcpPacket<'a> {
+    /// Parses a single packet, `buf` must not include padding
+    pub fn parse(buf: &'a [u8]) -> Result<Self, io::Error> {
+        let packet = match Header::new(buf)?.packet_type() {
+            PacketType::SenderReport => RtcpPacket::SenderReport(SenderReport::new(buf)?),
+            PacketType::ReceiverReport => RtcpPacket::ReceiverReport(ReceiverReport::new(buf)?),
+            PacketType::SourceDescription => RtcpPacket::Sdes(SourceDescription::new(buf)?),
+            PacketType::Goodbye => RtcpPacket::Bye(Goodbye::new(buf)?),
+            PacketType::ApplicationDefined => RtcpPacket::App(App::new(buf)?),
+            PacketType::TransportLayerFeedback | PacketType::PayloadSpecificFeedback => {
+                RtcpPacket::Feedback(Feedback::new(buf)?)
+            }
+            _ => RtcpPacket::Other(Packet::new(buf)?),
+        };
+        Ok(packet)
     }
 }
 
@@ -39,46 +75,261 @@ impl<'a> Packet<'a> {
 /// |                                                                  |
 /// |<-----------------------  compound packet ----------------------->|
 /// |<--------------------------  UDP packet ------------------------->|
+///
+/// Servers using RTP/AVPF may also send reduced-size RTCP (RFC 5506), e.g.
+/// a single feedback message without a leading SR/RR. These are accepted
+/// the same way, see [`CompoundPacket::is_reduced_size`].
 pub struct CompoundPacket {
-    pub payload: Vec<u8>,
+    pub payload: Bytes,
 }
 
-struct CompoundPacketIterator<'a> {
+pub struct CompoundPacketIterator<'a> {
     buf: &'a [u8],
     offset: usize,
 }
 
+impl<'a> CompoundPacketIterator<'a> {
+    fn next_packet(&mut self) -> Result<RtcpPacket<'a>, io::Error> {
+        let rest = &self.buf[self.offset..];
+        let header = Header::new(rest)?;
+        if header.version() != 2 {
+            return Err(invalid("Invalid RTCP version"));
+        }
+        let len = (header.length() + 1) * 4;
+        if len > rest.len() {
+            return Err(invalid("RTCP packet length exceeds compound packet"));
+        }
+        let mut packet = &rest[..len];
+        if header.padding() {
+            if len != rest.len() {
+                return Err(invalid("RTCP padding is only allowed in the last packet"));
+            }
+            let padding = packet[len - 1] as usize;
+            if padding == 0 || padding > len - 4 {
+                return Err(invalid("Invalid RTCP padding"));
+            }
+            packet = &packet[..len - padding];
+        }
+        self.offset += len;
+        RtcpPacket::parse(packet)
+    }
+}
+
+/// Yields the packets of a compound packet, iteration stops after the
+/// first invalid packet.
 impl<'a> Iterator for CompoundPacketIterator<'a> {
-    type Item = Packet<'a>;
+    type Item = Result<RtcpPacket<'a>, io::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.offset >= self.buf.len() {
             return None;
         }
-        let packet = Packet::new(&self.buf[self.offset..]);
-        match packet {
-            Ok(p) => {
-                self.offset += (1 + p.header().length() as usize) * 4;
-                Some(p)
-            }
-            Err(_) => {
-                // TODO: log error
-                self.offset = self.buf.len();
-                None
-            }
+        let result = self.next_packet();
+        if result.is_err() {
+            self.offset = self.buf.len();
         }
+        Some(result)
     }
 }
 
 impl CompoundPacket {
-    pub fn new(payload: Vec<u8>) -> Self {
-        Self { payload }
+    pub fn new(payload: impl Into<Bytes>) -> Self {
+        Self {
+            payload: payload.into(),
+        }
     }
 
-    pub fn iter(&self) -> CompoundPacketIterator {
+    pub fn iter(&self) -> CompoundPacketIterator<'_> {
         CompoundPacketIterator {
             buf: &self.payload,
             offset: 0,
         }
     }
+
+    /// True if the packet doesn't start with a SR or RR as RFC 3550
+    /// requires for compound packets
+    pub fn is_reduced_size(&self) -> bool {
+        !matches!(
+            Header::new(&self.payload).map(|h| h.packet_type()),
+            Ok(PacketType::SenderReport | PacketType::ReceiverReport)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcp::{ReportBlock, SDESItemType};
+
+    fn compound() -> Vec<u8> {
+        let mut buf = vec![
+            // RR with one report block
+            0x81, 201, 0, 7, 0, 0, 0, 1, // ssrc
+            0, 0, 0, 2, // source
+            0x10, 0xff, 0xff, 0xfe, // fraction lost, cumulative lost -2
+            0, 1, 0, 100, // highest sequence
+            0, 0, 0, 5, // jitter
+            0, 0, 0, 0, 0, 0, 0, 0, // lsr, dlsr
+            // SDES with one chunk: CNAME "cam", TOOL "x", null terminated and padded
+            0x81, 202, 0, 4, 0, 0, 0, 1, 1, 3, b'c', b'a', b'm', 6, 1, b'x', 0, 0, 0, 0,
+        ];
+        // BYE with one source and reason "done", padded by 4 bytes
+        buf.extend_from_slice(&[0xa1, 203, 0, 4, 0, 0, 0, 1, 4, b'd', b'o', b'n', b'e', 0, 0, 0]);
+        buf.extend_from_slice(&[0, 0, 0, 4]);
+        buf
+    }
+
+    #[test]
+    fn test_compound_packet() {
+        let compound = CompoundPacket::new(compound());
+        let packets: Vec<_> = compound.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(packets.len(), 3);
+        let RtcpPacket::ReceiverReport(rr) = &packets[0] else {
+            panic!("expected receiver report");
+        };
+        assert_eq!(rr.ssrc(), 1);
+        let blocks = rr.report_blocks();
+        assert_eq!(blocks[0].ssrc(), 2);
+        assert_eq!(blocks[0].fraction_lost(), 0x10);
+        assert_eq!(blocks[0].packets_lost(), -2);
+        assert_eq!(blocks[0].highest_sequence(), 65636);
+        assert_eq!(blocks[0].jitter(), 5);
+        let RtcpPacket::Sdes(sdes) = &packets[1] else {
+            panic!("expected source description");
+        };
+        let chunk = &sdes.chunks()[0];
+        assert_eq!(chunk.ssrc, 1);
+        assert_eq!(chunk.cname(), Some("cam"));
+        assert_eq!(chunk.item(SDESItemType::Tool).unwrap().str(), Some("x"));
+        let RtcpPacket::Bye(bye) = &packets[2] else {
+            panic!("expected goodbye");
+        };
+        assert_eq!(bye.sources(), vec![1]);
+        assert_eq!(bye.reason(), Some("done"));
+    }
+
+    #[test]
+    fn test_compound_packet_invalid_length() {
+        let mut buf = compound();
+        buf[3] = 20;
+        let results: Vec<bool> = CompoundPacket::new(buf).iter().map(|p| p.is_ok()).collect();
+        assert_eq!(results, vec![false]);
+    }
+
+    #[test]
+    fn test_compound_packet_padding_not_last() {
+        let mut buf = compound();
+        buf[0] |= 0x20;
+        let results: Vec<bool> = CompoundPacket::new(buf).iter().map(|p| p.is_ok()).collect();
+        assert_eq!(results, vec![false]);
+    }
+
+    #[test]
+    fn test_compound_packet_invalid_padding() {
+        let mut buf = compound();
+        let len = buf.len();
+        buf[len - 1] = 40;
+        let results: Vec<bool> = CompoundPacket::new(buf).iter().map(|p| p.is_ok()).collect();
+        assert_eq!(results, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_reduced_size_packet() {
+        let compound = CompoundPacket::new(compound());
+        assert!(!compound.is_reduced_size());
+
+        // Generic NACK followed by a PLI, without a report
+        let buf = vec![
+            0x81, 205, 0, 3, 0, 0, 0, 1, 0, 0, 0, 2, 0x01, 0x00, 0x00, 0x05, // NACK
+            0x81, 206, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, // PLI
+        ];
+        let reduced = CompoundPacket::new(buf);
+        assert!(reduced.is_reduced_size());
+        let packets: Vec<_> = reduced.iter().collect::<Result<_, _>>().unwrap();
+        let [RtcpPacket::Feedback(nack), RtcpPacket::Feedback(pli)] = &packets[..] else {
+            panic!("expected feedback messages");
+        };
+        assert_eq!(nack.sender_ssrc(), 1);
+        assert_eq!(nack.media_ssrc(), 2);
+        assert_eq!(nack.nacks(), vec![(256, 5)]);
+        assert!(pli.is_pli());
+        assert!(pli.nacks().is_empty());
+    }
+
+    // Calls the accessors of a parsed packet, which must not panic
+    fn inspect(packet: &RtcpPacket) {
+        let blocks = match packet {
+            RtcpPacket::SenderReport(sr) => {
+                let _ = (sr.ssrc(), sr.ntp_timestamp(), sr.octets_sent());
+                sr.report_blocks()
+            }
+            RtcpPacket::ReceiverReport(rr) => {
+                let _ = rr.ssrc();
+                rr.report_blocks()
+            }
+            RtcpPacket::Sdes(sdes) => {
+                let _: Vec<_> = sdes.chunks().iter().map(|c| c.cname()).collect();
+                Vec::new()
+            }
+            RtcpPacket::Bye(bye) => {
+                let _ = (bye.sources(), bye.reason());
+                Vec::new()
+            }
+            RtcpPacket::App(app) => {
+                let _ = (app.name(), app.data());
+                Vec::new()
+            }
+            RtcpPacket::Feedback(fb) => {
+                let _ = (fb.media_ssrc(), fb.nacks());
+                Vec::new()
+            }
+            RtcpPacket::Other(other) => {
+                let _ = other.header().length();
+                Vec::new()
+            }
+        };
+        for block in blocks {
+            let _ = (block.packets_lost(), block.dlsr());
+        }
+    }
+
+    #[test]
+    fn test_compound_packet_truncated_or_corrupted() {
+        let buf = compound();
+        for len in 0..buf.len() {
+            CompoundPacket::new(buf[..len].to_vec()).iter().flatten().for_each(|p| inspect(&p));
+        }
+        // Every value of every byte, e.g. counts and lengths beyond the packet
+        for i in 0..buf.len() {
+            for value in 0..=255 {
+                let mut buf = buf.clone();
+                buf[i] = value;
+                CompoundPacket::new(buf).iter().flatten().for_each(|p| inspect(&p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_report_block_too_short() {
+        assert!(ReportBlock::new(&[0; 23]).is_err());
+        assert!(ReportBlock::new(&[0; ReportBlock::SIZE]).is_ok());
+    }
+
+    #[test]
+    fn test_generic_nack() {
+        // 65535 and 0 to 2 fit into one entry, 40 needs another
+        let buf = crate::rtcp::generic_nack(1, 2, &[65535, 0, 2, 40]);
+        let Ok(RtcpPacket::Feedback(nack)) = RtcpPacket::parse(&buf) else {
+            panic!("expected feedback");
+        };
+        assert_eq!(nack.header().length(), 4);
+        assert_eq!(nack.media_ssrc(), 2);
+        assert_eq!(nack.nacks(), vec![(65535, 0b101), (40, 0)]);
+    }
+
+    #[test]
+    fn test_sdes_unterminated_chunk() {
+        let buf = [0x81, 202, 0, 2, 0, 0, 0, 1, 1, 2, b'a', b'b'];
+        assert!(RtcpPacket::parse(&buf).is_err());
+    }
 }
@@ -0,0 +1,49 @@
+use super::{Header, ReportBlock};
+use std::io;
+
+pub struct ReceiverReport<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> ReceiverReport<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        if buf.len() < 8 || buf.len() < 8 + Header::new(buf)?.count() * ReportBlock::SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid RTCP Receiver Report",
+            ));
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn header(&self) -> Header<'_> {
+        Header::new(&self.buf[0..4]).unwrap()
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+    }
+
+    pub fn report_blocks(&self) -> Vec<ReportBlock<'_>> {
+        self.buf[8..]
+            .chunks_exact(ReportBlock::SIZE)
+            .take(self.header().count())
+            .filter_map(|b| ReportBlock::new(b).ok())
+            .collect()
+    }
+
+    pub fn size(&self) -> usize {
+        8 + self.header().count() * ReportBlock::SIZE
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ReceiverReport<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut report = serializer.serialize_struct("ReceiverReport", 2)?;
+        report.serialize_field("ssrc", &self.ssrc())?;
+        report.serialize_field("report_blocks", &self.report_blocks())?;
+        report.end()
+    }
+}
@@ -0,0 +1,283 @@
+use super::{PacketType, SenderReport};
+use crate::rtp::{Packet, SequenceExtender};
+use crate::sync::NtpTimestamp;
+use std::time::{Duration, Instant};
+
+/// Content of a report block about one source, see [`ReportBlock`](super::ReportBlock)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceptionReport {
+    pub ssrc: u32,
+    /// Fraction of the packets lost since the previous report, in 1/256
+    pub fraction_lost: u8,
+    /// Cumulative number of packets lost, clamped to 24 bits
+    pub packets_lost: i32,
+    pub highest_sequence: u32,
+    /// Interarrival jitter in timestamp units
+    pub jitter: u32,
+    /// Middle 32 bits of the NTP timestamp of the last sender report
+    pub lsr: u32,
+    /// Time since the last sender report in 1/65536 seconds
+    pub dlsr: u32,
+}
+
+impl ReceptionReport {
+    fn write(&self, out: &mut Vec<u8>) {
+        let lost = self.packets_lost.clamp(-0x80_0000, 0x7f_ffff);
+        out.extend_from_slice(&self.ssrc.to_be_bytes());
+        out.push(self.fraction_lost);
+        out.extend_from_slice(&lost.to_be_bytes()[1..]);
+        out.extend_from_slice(&self.highest_sequence.to_be_bytes());
+        out.extend_from_slice(&self.jitter.to_be_bytes());
+        out.extend_from_slice(&self.lsr.to_be_bytes());
+        out.extend_from_slice(&self.dlsr.to_be_bytes());
+    }
+}
+
+/// Serializes the compound packet a receiver sends: a receiver report with
+/// up to 31 report blocks followed by a SDES with the CNAME (RFC 3550 6.1)
+pub fn receiver_report(ssrc: u32, reports: &[ReceptionReport], cname: &str) -> Vec<u8> {
+    let reports = &reports[..reports.len().min(31)];
+    let mut out = Vec::with_capacity(8 + 24 * reports.len() + 12 + cname.len());
+    let words = 1 + 6 * reports.len();
+    out.push(0x80 | reports.len() as u8);
+    out.push(PacketType::ReceiverReport as u8);
+    out.extend_from_slice(&(words as u16).to_be_bytes());
+    out.extend_from_slice(&ssrc.to_be_bytes());
+    for report in reports {
+        report.write(&mut out);
+    }
+
+    let cname = &cname.as_bytes()[..cname.len().min(255)];
+    // SSRC, CNAME item and at least one null octet ending the item list
+    let chunk = 4 + 2 + cname.len() + 1;
+    let padded = chunk.div_ceil(4) * 4;
+    out.push(0x81);
+    out.push(PacketType::SourceDescription as u8);
+    out.extend_from_slice(&((padded / 4) as u16).to_be_bytes());
+    out.extend_from_slice(&ssrc.to_be_bytes());
+    out.push(1);
+    out.push(cname.len() as u8);
+    out.extend_from_slice(cname);
+    out.resize(out.len() + padded - chunk + 1, 0);
+    out
+}
+
+/// Reception statistics of one source for its report blocks (RFC 3550 A.3, A.8)
+#[derive(Debug)]
+pub struct ReceptionStats {
+    clock_rate: u32,
+    ssrc: u32,
+    extender: SequenceExtender,
+    base: Option<i64>,
+    received: u64,
+    expected_prior: u64,
+    received_prior: u64,
+    // Relative transit time of the previous packet and jitter, scaled by 16
+    transit: Option<i64>,
+    jitter: u64,
+    epoch: Instant,
+    // Compact NTP timestamp of the last sender report and its arrival
+    last_sr: Option<(u32, Instant)>,
+}
+
+impl ReceptionStats {
+    /// The jitter is only measured if `clock_rate` is known, i.e. not 0
+    pub fn new(clock_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            ssrc: 0,
+            extender: SequenceExtender::new(),
+            base: None,
+            received: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            transit: None,
+            jitter: 0,
+            epoch: Instant::now(),
+            last_sr: None,
+        }
+    }
+
+    pub fn record_packet(&mut self, packet: &Packet, arrival: Instant) {
+        if packet.ssrc() != self.ssrc && self.base.is_some() {
+            // A new source starts over
+            *self = Self::new(self.clock_rate);
+        }
+        self.ssrc = packet.ssrc();
+        let ext = self.extender.extend(packet.sequence_number());
+        let base = *self.base.get_or_insert(ext);
+        if ext < base {
+            self.base = Some(ext);
+        }
+        self.received += 1;
+        if self.clock_rate == 0 {
+            return;
+        }
+        let ticks = arrival.saturating_duration_since(self.epoch).as_nanos() * self.clock_rate as u128 / 1_000_000_000;
+        let transit = ticks as i64 - packet.timestamp() as i64;
+        if let Some(previous) = self.transit.replace(transit) {
+            let d = (transit - previous).unsigned_abs();
+            // Timestamp wraps and restarts are not jitter
+            if d < self.clock_rate as u64 * 10 {
+                self.jitter = self.jitter + d - ((self.jitter + 8) >> 4);
+            }
+        }
+    }
+
+    /// Remembers a sender report of the source for the round-trip time
+    /// calculation of the sender
+    pub fn record_sender_report(&mut self, report: &SenderReport, arrival: Instant) {
+        let compact = NtpTimestamp(report.ntp_timestamp()).compact();
+        self.last_sr = Some((compact, arrival));
+    }
+
+    /// Packets expected since the first one, from the sequence numbers
+    fn expected(&self) -> u64 {
+        match (self.base, self.extender.highest()) {
+            (Some(base), Some(highest)) => (highest as i64 - base + 1).max(0) as u64,
+            _ => 0,
+        }
+    }
+
+    /// Packets missing in the sequence numbers received so far
+    pub fn lost(&self) -> u64 {
+        self.expected().saturating_sub(self.received)
+    }
+
+    /// Fraction of the expected packets lost since the first one
+    pub fn loss(&self) -> f64 {
+        match self.expected() {
+            0 => 0.0,
+            expected => self.lost() as f64 / expected as f64,
+        }
+    }
+
+    /// Interarrival jitter, zero if the clock rate is unknown
+    pub fn jitter(&self) -> Duration {
+        match self.clock_rate {
+            0 => Duration::ZERO,
+            clock_rate => Duration::from_nanos((self.jitter >> 4) * 1_000_000_000 / clock_rate as u64),
+        }
+    }
+
+    /// Report block for the interval since the previous one, None before
+    /// the first packet
+    pub fn report(&mut self, now: Instant) -> Option<ReceptionReport> {
+        let base = self.base?;
+        let highest = self.extender.highest()?;
+        let expected = (highest as i64 - base + 1).max(0) as u64;
+        let lost = expected as i64 - self.received as i64;
+        let expected_interval = expected.saturating_sub(self.expected_prior);
+        let received_interval = self.received.saturating_sub(self.received_prior);
+        self.expected_prior = expected;
+        self.received_prior = self.received;
+        let lost_interval = expected_interval as i64 - received_interval as i64;
+        let fraction_lost = match expected_interval == 0 || lost_interval <= 0 {
+            true => 0,
+            false => ((lost_interval << 8) / expected_interval as i64).min(255) as u8,
+        };
+        let (lsr, dlsr) = match self.last_sr {
+            Some((lsr, arrival)) => {
+                let delay = now.saturating_duration_since(arrival);
+                (
+                    lsr,
+                    (delay.as_micros() * 65_536 / 1_000_000).min(u32::MAX as u128) as u32,
+                )
+            }
+            None => (0, 0),
+        };
+        Some(ReceptionReport {
+            ssrc: self.ssrc,
+            fraction_lost,
+            packets_lost: lost.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            highest_sequence: highest,
+            jitter: (self.jitter >> 4) as u32,
+            lsr,
+            dlsr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcp::{CompoundPacket, RtcpPacket};
+    use crate::testing::rtp_packet;
+
+    fn packet(seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(96, 7, seq, timestamp, false, &[0xab])
+    }
+
+    #[test]
+    fn test_receiver_report() {
+        let report = ReceptionReport {
+            ssrc: 7,
+            fraction_lost: 64,
+            packets_lost: -2,
+            highest_sequence: 0x1_0005,
+            jitter: 90,
+            lsr: 0x1234_5678,
+            dlsr: 65_536,
+        };
+        let buf = receiver_report(1, &[report], "rs-streamer");
+        assert_eq!(buf.len() % 4, 0);
+        let compound = CompoundPacket::new(buf);
+        let mut packets = compound.iter();
+        let Some(Ok(RtcpPacket::ReceiverReport(rr))) = packets.next() else {
+            panic!("expected a receiver report");
+        };
+        assert_eq!(rr.ssrc(), 1);
+        let block = &rr.report_blocks()[0];
+        assert_eq!(block.ssrc(), 7);
+        assert_eq!(block.fraction_lost(), 64);
+        assert_eq!(block.packets_lost(), -2);
+        assert_eq!(block.highest_sequence(), 0x1_0005);
+        assert_eq!(block.jitter(), 90);
+        assert_eq!(block.lsr(), 0x1234_5678);
+        assert_eq!(block.dlsr(), 65_536);
+        let Some(Ok(RtcpPacket::Sdes(sdes))) = packets.next() else {
+            panic!("expected a SDES");
+        };
+        assert_eq!(sdes.chunks()[0].cname(), Some("rs-streamer"));
+        assert!(packets.next().is_none());
+    }
+
+    #[test]
+    fn test_reception_stats() {
+        let mut stats = ReceptionStats::new(90_000);
+        let start = Instant::now();
+        assert_eq!(stats.report(start), None);
+        for seq in [65_534, 65_535, 1, 2] {
+            stats.record_packet(&packet(seq, 0), start);
+        }
+        assert_eq!(stats.lost(), 1);
+        let report = stats.report(start).unwrap();
+        assert_eq!(report.ssrc, 7);
+        // Sequence number 0 is missing after the wrap
+        assert_eq!(report.packets_lost, 1);
+        assert_eq!(report.fraction_lost, 51);
+        assert_eq!(report.highest_sequence, 0x1_0002);
+
+        // Nothing lost in the next interval
+        stats.record_packet(&packet(3, 0), start);
+        let report = stats.report(start).unwrap();
+        assert_eq!(report.fraction_lost, 0);
+        assert_eq!(report.packets_lost, 1);
+        assert_eq!(report.lsr, 0);
+    }
+
+    #[test]
+    fn test_reception_stats_sender_report() {
+        let mut stats = ReceptionStats::new(90_000);
+        let start = Instant::now();
+        stats.record_packet(&packet(1, 0), start);
+        let mut sr = vec![0x80, 200, 0, 6, 0, 0, 0, 7];
+        sr.extend_from_slice(&0x0001_2345_6789_0000u64.to_be_bytes());
+        sr.extend_from_slice(&[0; 12]);
+        stats.record_sender_report(&SenderReport::new(&sr).unwrap(), start);
+        let report = stats.report(start + Duration::from_millis(500)).unwrap();
+        assert_eq!(report.lsr, 0x2345_6789);
+        assert_eq!(report.dlsr, 32_768);
+    }
+}
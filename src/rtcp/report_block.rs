@@ -1,10 +1,21 @@
+use super::ReceptionReport;
+use crate::sync::NtpTimestamp;
+use std::io;
+use std::time::{Duration, SystemTime};
+
 pub struct ReportBlock<'a> {
     buf: &'a [u8],
 }
 
+/// A reception report block, 24 bytes long
 impl<'a> ReportBlock<'a> {
-    pub fn new(buf: &'a [u8]) -> Self {
-        Self { buf }
+    pub const SIZE: usize = 24;
+
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        if buf.len() < Self::SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid RTCP Report Block"));
+        }
+        Ok(Self { buf })
     }
 
     pub fn ssrc(&self) -> u32 {
@@ -15,23 +26,62 @@ impl<'a> ReportBlock<'a> {
         self.buf[4]
     }
 
-    pub fn packets_lost(&self) -> u32 {
-        u32::from_be_bytes([self.buf[5], self.buf[6], self.buf[7], self.buf[8]])
+    /// Cumulative number of packets lost, a signed 24 bit value
+    pub fn packets_lost(&self) -> i32 {
+        i32::from_be_bytes([self.buf[5], self.buf[6], self.buf[7], 0]) >> 8
     }
 
     pub fn highest_sequence(&self) -> u32 {
-        u32::from_be_bytes([self.buf[9], self.buf[10], self.buf[11], self.buf[12]])
+        u32::from_be_bytes([self.buf[8], self.buf[9], self.buf[10], self.buf[11]])
     }
 
     pub fn jitter(&self) -> u32 {
-        u32::from_be_bytes([self.buf[13], self.buf[14], self.buf[15], self.buf[16]])
+        u32::from_be_bytes([self.buf[12], self.buf[13], self.buf[14], self.buf[15]])
     }
 
     pub fn lsr(&self) -> u32 {
-        u32::from_be_bytes([self.buf[17], self.buf[18], self.buf[19], self.buf[20]])
+        u32::from_be_bytes([self.buf[16], self.buf[17], self.buf[18], self.buf[19]])
     }
 
     pub fn dlsr(&self) -> u32 {
-        u32::from_be_bytes([self.buf[21], self.buf[22], self.buf[23], self.buf[24]])
+        u32::from_be_bytes([self.buf[20], self.buf[21], self.buf[22], self.buf[23]])
+    }
+
+    /// Round-trip time to the reporter of a block about one of our sources
+    /// that arrived at `arrival`, None without a sender report of ours to
+    /// refer to or if the clocks are out of sync (RFC 3550 6.4.1)
+    pub fn round_trip_time(&self, arrival: SystemTime) -> Option<Duration> {
+        if self.lsr() == 0 {
+            return None;
+        }
+        let now = NtpTimestamp::from_system_time(arrival).compact();
+        let rtt = now.wrapping_sub(self.lsr()).wrapping_sub(self.dlsr());
+        // Compact NTP is in 1/65536 seconds, a negative rtt means unsynced clocks
+        if (rtt as i32) < 0 {
+            return None;
+        }
+        Some(Duration::from_micros(rtt as u64 * 1_000_000 / 65_536))
+    }
+}
+
+impl From<&ReportBlock<'_>> for ReceptionReport {
+    fn from(block: &ReportBlock<'_>) -> Self {
+        Self {
+            ssrc: block.ssrc(),
+            fraction_lost: block.fraction_lost(),
+            packets_lost: block.packets_lost(),
+            highest_sequence: block.highest_sequence(),
+            jitter: block.jitter(),
+            lsr: block.lsr(),
+            dlsr: block.dlsr(),
+        }
+    }
+}
+
+/// Serialized as its [`ReceptionReport`]
+#[cfg(feature = "serde")]
+impl serde::Serialize for ReportBlock<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ReceptionReport::from(self).serialize(serializer)
     }
 }
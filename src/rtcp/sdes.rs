@@ -1,14 +1,139 @@
+use super::Header;
+use crate::io::bytes::ReadBytes;
+use std::io::{self, Cursor};
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SDESItemType {
+    End,
+    CName,
+    Name,
+    Email,
+    Phone,
+    Location,
+    Tool,
+    Note,
+    Private,
+    Unknown(u8),
+}
+
+impl From<u8> for SDESItemType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => SDESItemType::End,
+            1 => SDESItemType::CName,
+            2 => SDESItemType::Name,
+            3 => SDESItemType::Email,
+            4 => SDESItemType::Phone,
+            5 => SDESItemType::Location,
+            6 => SDESItemType::Tool,
+            7 => SDESItemType::Note,
+            8 => SDESItemType::Private,
+            v => SDESItemType::Unknown(v),
+        }
+    }
+}
+
+/// A single SDES item: type (8 bits), length (8 bits) and value
 pub struct SDESItem<'a> {
     buf: &'a [u8],
 }
 
 impl<'a> SDESItem<'a> {
-    pub fn new(buf: &'a [u8]) -> Self {
-        Self { buf }
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
+            return Err(invalid("Invalid RTCP SDES item"));
+        }
+        Ok(Self { buf })
+    }
+
+    pub fn item_type(&self) -> SDESItemType {
+        self.buf[0].into()
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        &self.buf[2..2 + self.buf[1] as usize]
+    }
+
+    /// The value as text, `None` if it isn't valid UTF-8
+    pub fn str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.value()).ok()
+    }
+
+    pub fn size(&self) -> usize {
+        2 + self.buf[1] as usize
+    }
+}
+
+/// The items describing one source
+pub struct SDESChunk<'a> {
+    pub ssrc: u32,
+    pub items: Vec<SDESItem<'a>>,
+}
+
+impl<'a> SDESChunk<'a> {
+    pub fn item(&self, item_type: SDESItemType) -> Option<&SDESItem<'a>> {
+        self.items.iter().find(|i| i.item_type() == item_type)
+    }
+
+    pub fn cname(&self) -> Option<&'a str> {
+        self.item(SDESItemType::CName).and_then(|i| i.str())
+    }
+
+    /// Parses a chunk and returns it together with its size including padding
+    fn parse(buf: &'a [u8]) -> Result<(Self, usize), io::Error> {
+        let mut reader = Cursor::new(buf);
+        let ssrc = reader.read_u32().map_err(|_| invalid("Invalid RTCP SDES chunk"))?;
+        let mut items = Vec::new();
+        loop {
+            let offset = reader.position() as usize;
+            match reader.read_u8() {
+                Err(_) => return Err(invalid("Unterminated RTCP SDES chunk")),
+                Ok(0) => break,
+                Ok(_) => {
+                    let item = SDESItem::new(&buf[offset..])?;
+                    reader.set_position((offset + item.size()) as u64);
+                    items.push(item);
+                }
+            }
+        }
+        // The list of items ends with at least one null octet and is padded to 32 bits
+        let size = (reader.position() as usize).next_multiple_of(4);
+        if size > buf.len() {
+            return Err(invalid("Unterminated RTCP SDES chunk"));
+        }
+        Ok((Self { ssrc, items }, size))
+    }
+}
+
+/// RTCP Source Description packet
+pub struct SourceDescription<'a> {
+    buf: &'a [u8],
+    chunks: Vec<SDESChunk<'a>>,
+}
+
+impl<'a> SourceDescription<'a> {
+    pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
+        let count = Header::new(buf)?.count();
+        let mut chunks = Vec::with_capacity(count);
+        let mut offset = 4;
+        for _ in 0..count {
+            let rest = buf.get(offset..).ok_or_else(|| invalid("Invalid RTCP SDES"))?;
+            let (chunk, size) = SDESChunk::parse(rest)?;
+            offset += size;
+            chunks.push(chunk);
+        }
+        Ok(Self { buf, chunks })
+    }
+
+    pub fn header(&self) -> Header<'_> {
+        Header::new(&self.buf[0..4]).unwrap()
     }
 
-    pub fn str(&self) -> &str {
-        let length = self.buf[1] as usize;
-        std::str::from_utf8(&self.buf[2..length + 2]).unwrap()
+    pub fn chunks(&self) -> &[SDESChunk<'a>] {
+        &self.chunks
     }
 }
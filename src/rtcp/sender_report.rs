@@ -7,7 +7,7 @@ pub struct SenderReport<'a> {
 
 impl<'a> SenderReport<'a> {
     pub fn new(buf: &'a [u8]) -> Result<Self, io::Error> {
-        if buf.len() < 24 {
+        if buf.len() < 28 || buf.len() < 28 + Header::new(buf)?.count() * ReportBlock::SIZE {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "Invalid RTCP Sender Report",
@@ -16,7 +16,7 @@ impl<'a> SenderReport<'a> {
         Ok(Self { buf })
     }
 
-    pub fn header(&self) -> Header {
+    pub fn header(&self) -> Header<'_> {
         Header::new(&self.buf[0..4]).unwrap()
     }
 
@@ -49,17 +49,30 @@ impl<'a> SenderReport<'a> {
         u32::from_be_bytes([self.buf[24], self.buf[25], self.buf[26], self.buf[27]])
     }
 
-    pub fn report_blocks(&self) -> Vec<ReportBlock> {
-        let mut blocks = Vec::new();
-        let mut offset = 28;
-        for _ in 0..self.header().count() {
-            blocks.push(ReportBlock::new(&self.buf[offset..offset + 24]));
-            offset += 24;
-        }
-        blocks
+    pub fn report_blocks(&self) -> Vec<ReportBlock<'_>> {
+        self.buf[28..]
+            .chunks_exact(ReportBlock::SIZE)
+            .take(self.header().count())
+            .filter_map(|b| ReportBlock::new(b).ok())
+            .collect()
     }
 
     pub fn size(&self) -> usize {
-        28 + self.header().count() * 24
+        28 + self.header().count() * ReportBlock::SIZE
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SenderReport<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut report = serializer.serialize_struct("SenderReport", 6)?;
+        report.serialize_field("ssrc", &self.ssrc())?;
+        report.serialize_field("ntp_timestamp", &self.ntp_timestamp())?;
+        report.serialize_field("rtp_ts", &self.rtp_ts())?;
+        report.serialize_field("packets_sent", &self.packets_sent())?;
+        report.serialize_field("octets_sent", &self.octets_sent())?;
+        report.serialize_field("report_blocks", &self.report_blocks())?;
+        report.end()
     }
 }
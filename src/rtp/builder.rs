@@ -0,0 +1,94 @@
+use super::Packet;
+
+/// A random SSRC for a new source, never 0 as some receivers treat it as unset
+pub fn random_ssrc() -> u32 {
+    loop {
+        let ssrc = rand::random::<u32>();
+        if ssrc != 0 {
+            return ssrc;
+        }
+    }
+}
+
+/// Serializes the RTP packets of one outgoing source, e.g. the audio of an
+/// ONVIF backchannel.
+///
+/// Sequence numbers increase by one per packet and, like the timestamps,
+/// start at a random offset (RFC 3550 5.1). Timestamps are passed in clock
+/// ticks since the start of the stream.
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    payload_type: u8,
+    ssrc: u32,
+    sequence_number: u16,
+    timestamp_offset: u32,
+}
+
+impl PacketBuilder {
+    /// `ssrc` identifies the source, usually a [`random_ssrc`]
+    pub fn new(payload_type: u8, ssrc: u32) -> Self {
+        Self {
+            payload_type: payload_type & 0x7f,
+            ssrc,
+            sequence_number: rand::random(),
+            timestamp_offset: rand::random(),
+        }
+    }
+
+    /// Sequence number of the next packet
+    pub fn sequence_number(mut self, sequence_number: u16) -> Self {
+        self.sequence_number = sequence_number;
+        self
+    }
+
+    /// RTP timestamp of tick 0
+    pub fn timestamp_offset(mut self, offset: u32) -> Self {
+        self.timestamp_offset = offset;
+        self
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Builds the next packet, `ticks` is the time of its first sample in
+    /// units of the clock rate. The marker bit flags the last packet of a
+    /// video frame or the first packet of a talkspurt.
+    pub fn build(&mut self, payload: &[u8], ticks: u32, marker: bool) -> Packet {
+        let mut buf = Vec::with_capacity(12 + payload.len());
+        buf.push(0x80);
+        buf.push(self.payload_type | if marker { 0x80 } else { 0 });
+        buf.extend_from_slice(&self.sequence_number.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp_offset.wrapping_add(ticks).to_be_bytes());
+        buf.extend_from_slice(&self.ssrc.to_be_bytes());
+        buf.extend_from_slice(payload);
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        Packet::new(buf).expect("RTP header is complete")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_builder() {
+        let mut builder = PacketBuilder::new(0, 0x1234)
+            .sequence_number(65535)
+            .timestamp_offset(u32::MAX);
+        let packet = builder.build(&[0xd5; 160], 0, true);
+        assert_eq!(packet.version(), 2);
+        assert!(packet.marker());
+        assert_eq!(packet.payload_type(), 0);
+        assert_eq!(packet.sequence_number(), 65535);
+        assert_eq!(packet.timestamp(), u32::MAX);
+        assert_eq!(packet.ssrc(), 0x1234);
+        assert_eq!(packet.data(), &[0xd5; 160]);
+
+        let packet = builder.build(&[0xd5; 160], 160, false);
+        assert!(!packet.marker());
+        assert_eq!(packet.sequence_number(), 0);
+        assert_eq!(packet.timestamp(), 159);
+        assert_ne!(random_ssrc(), 0);
+    }
+}
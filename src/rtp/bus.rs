@@ -0,0 +1,230 @@
+use super::{FrameStream, TimedFrame};
+use crate::fanout::{Fanout, FanoutReceiver, Received};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Frames a [`FrameBus`] buffers unless created with [`FrameBus::with_capacity`]
+pub const DEFAULT_BUS_CAPACITY: usize = 64;
+
+/// Delivery counts of one [`FrameSubscriber`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriberStats {
+    /// Frames returned by [`FrameSubscriber::recv`]
+    pub received: u64,
+    /// Frames overwritten before the subscriber read them
+    pub dropped: u64,
+    /// Times the subscriber fell behind by the capacity of the bus
+    pub lags: u64,
+    /// Frames discarded while waiting for a keyframe after a lag
+    pub skipped: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    subscribers: BTreeMap<u64, (String, SubscriberStats)>,
+}
+
+/// Delivers the frames of a track to any number of in-process consumers,
+/// e.g. a recorder, analytics and a live view of the same session.
+///
+/// Every subscriber reads at its own pace. One that falls behind by more
+/// than the capacity loses the oldest frames without slowing the others
+/// down, the losses are counted per subscriber in [`SubscriberStats`].
+///
+/// ```ignore
+/// let bus = FrameBus::new();
+/// let mut recorder = bus.subscribe("recorder");
+/// let mut live_view = bus.subscribe("live-view");
+/// task::spawn(bus.clone().forward(frame_stream));
+/// while let Some(frame) = live_view.recv().await {
+///     render(&frame.frame.data);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct FrameBus {
+    tx: Fanout<Arc<TimedFrame>>,
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl FrameBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_BUS_CAPACITY)
+    }
+
+    /// Keeps up to `capacity` frames for the slowest subscriber
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            tx: Fanout::new(capacity),
+            registry: Arc::default(),
+        }
+    }
+
+    /// Receives the frames published from now on, `name` identifies the
+    /// subscriber in [`FrameBus::stats`]
+    pub fn subscribe(&self, name: &str) -> FrameSubscriber {
+        let mut registry = self.registry.lock().unwrap();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry
+            .subscribers
+            .insert(id, (name.to_string(), SubscriberStats::default()));
+        FrameSubscriber {
+            rx: self.tx.subscribe(),
+            id,
+            registry: self.registry.clone(),
+            stats: SubscriberStats::default(),
+            resume_at_keyframe: true,
+            waiting: false,
+        }
+    }
+
+    /// Sends `frame` to every subscriber and returns how many there are,
+    /// frames nobody subscribed to are dropped
+    pub fn publish(&self, frame: TimedFrame) -> usize {
+        self.tx.send(Arc::new(frame))
+    }
+
+    /// Publishes the frames of `stream` until it ends
+    pub async fn forward(self, mut stream: FrameStream) {
+        while let Some(frame) = stream.next().await {
+            self.publish(frame);
+        }
+    }
+
+    /// The counts of every subscriber by name, in the order they subscribed
+    pub fn stats(&self) -> Vec<(String, SubscriberStats)> {
+        let registry = self.registry.lock().unwrap();
+        registry.subscribers.values().cloned().collect()
+    }
+}
+
+impl Default for FrameBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives the frames of a [`FrameBus`], unsubscribes when dropped
+pub struct FrameSubscriber {
+    rx: FanoutReceiver<Arc<TimedFrame>>,
+    id: u64,
+    registry: Arc<Mutex<Registry>>,
+    stats: SubscriberStats,
+    resume_at_keyframe: bool,
+    // Frames are skipped until the next keyframe after a lag
+    waiting: bool,
+}
+
+impl FrameSubscriber {
+    /// Whether frames are skipped after a lag until decoding can start
+    /// again, the default. Consumers of raw frames, e.g. a recorder that
+    /// repairs gaps itself, turn it off.
+    pub fn resume_at_keyframe(mut self, enabled: bool) -> Self {
+        self.resume_at_keyframe = enabled;
+        self
+    }
+
+    /// Waits for the next frame, None once every [`FrameBus`] handle is
+    /// dropped and the buffered frames are read
+    pub async fn recv(&mut self) -> Option<Arc<TimedFrame>> {
+        loop {
+            let frame = match self.rx.recv().await {
+                Received::Value(frame) => frame,
+                Received::Lagged(_) => {
+                    self.stats.dropped = self.rx.lost();
+                    self.stats.lags = self.rx.lags();
+                    self.waiting = self.resume_at_keyframe;
+                    self.publish_stats();
+                    continue;
+                }
+                Received::Closed => return None,
+            };
+            if self.waiting && !frame.keyframe {
+                self.stats.skipped += 1;
+                continue;
+            }
+            self.waiting = false;
+            self.stats.received += 1;
+            self.publish_stats();
+            return Some(frame);
+        }
+    }
+
+    pub fn stats(&self) -> SubscriberStats {
+        self.stats
+    }
+
+    fn publish_stats(&self) {
+        let mut registry = self.registry.lock().unwrap();
+        if let Some((_, stats)) = registry.subscribers.get_mut(&self.id) {
+            *stats = self.stats;
+        }
+    }
+}
+
+impl Drop for FrameSubscriber {
+    fn drop(&mut self) {
+        self.registry.lock().unwrap().subscribers.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Frame, FrameMetadata, FrameType, MediaType};
+    use std::time::Duration;
+
+    fn frame(timestamp: u32, keyframe: bool) -> TimedFrame {
+        TimedFrame {
+            frame: Frame {
+                media_type: MediaType::Video,
+                frame_type: FrameType::H264,
+                timestamp,
+                data: vec![0, 0, 0, 1, 0x65],
+                metadata: FrameMetadata::default(),
+            },
+            pts: Duration::ZERO,
+            ntp: None,
+            keyframe,
+            discontinuity: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_bus() {
+        let bus = FrameBus::with_capacity(2);
+        assert_eq!(bus.publish(frame(0, true)), 0);
+        let mut fast = bus.subscribe("live-view");
+        let mut slow = bus.subscribe("recorder").resume_at_keyframe(false);
+        let mut lagging = bus.subscribe("analytics");
+
+        bus.publish(frame(1, true));
+        assert_eq!(fast.recv().await.unwrap().rtp_timestamp(), 1);
+        for timestamp in 2..6 {
+            bus.publish(frame(timestamp, timestamp == 5));
+            assert_eq!(fast.recv().await.unwrap().rtp_timestamp(), timestamp);
+        }
+        // Only the last two frames are left for the others
+        assert_eq!(slow.recv().await.unwrap().rtp_timestamp(), 4);
+        assert_eq!(lagging.recv().await.unwrap().rtp_timestamp(), 5);
+        assert_eq!(
+            lagging.stats(),
+            SubscriberStats {
+                received: 1,
+                dropped: 3,
+                lags: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(fast.stats().dropped, 0);
+        assert_eq!(bus.stats()[1], ("recorder".to_string(), slow.stats()));
+
+        drop(fast);
+        assert_eq!(bus.stats().len(), 2);
+        drop(bus);
+        assert_eq!(slow.recv().await.unwrap().rtp_timestamp(), 5);
+        assert!(slow.recv().await.is_none());
+    }
+}
@@ -0,0 +1,281 @@
+use super::{Delivery, ExtensionMap, ExtensionValues, Packet, TrackPacket, TrackQueue};
+use std::collections::{HashMap, VecDeque};
+use tokio::time::{Duration, Instant};
+
+/// Sources a [`Demuxer`] keeps unless set with [`Demuxer::max_sources`]
+pub const DEFAULT_MAX_SOURCES: usize = 32;
+
+/// Time without packets after which a [`Demuxer`] forgets a source unless
+/// set with [`Demuxer::source_timeout`]
+pub const DEFAULT_SOURCE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A packet tagged with the track and source it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemuxedPacket {
+    /// Index of the track in SETUP order
+    pub track: usize,
+    /// Interleaved RTP channel the packet arrived on
+    pub channel: u8,
+    pub ssrc: u32,
+    pub packet: TrackPacket,
+    /// Header extensions of the packet mapped with [`Demuxer::map_extensions`]
+    pub extensions: ExtensionValues,
+}
+
+/// Splits the packets of a session by channel and SSRC, so every source
+/// has its own sequence numbering, reordering and gap counting. A track
+/// carries more than one source when the server sends retransmissions or
+/// FEC with their own SSRC, or restarts its stream.
+///
+/// Channels are mapped to tracks in the order of [`Demuxer::map_channel`],
+/// usually the order of the SETUP responses. Without any mapped channel
+/// packets go to track `channel / 2`, following the even RTP channel
+/// convention. Once channels are mapped, packets of other channels are
+/// dropped, their track can't be told apart from the mapped ones.
+///
+/// Sources that sent nothing for the [timeout](Demuxer::source_timeout)
+/// are forgotten, and the least recently seen one makes room once
+/// [`Demuxer::max_sources`] are known, with the packets they held back.
+pub struct Demuxer {
+    delivery: Delivery,
+    // Tracks delivered in another mode than `delivery`
+    track_delivery: HashMap<usize, Delivery>,
+    tracks: HashMap<u8, usize>,
+    extensions: HashMap<u8, ExtensionMap>,
+    // Queue of every source and when its last packet arrived
+    sources: HashMap<(u8, u32), (TrackQueue, Instant)>,
+    max_sources: usize,
+    source_timeout: Duration,
+    // Packets of channels that weren't mapped
+    unmapped: u64,
+    out: VecDeque<DemuxedPacket>,
+}
+
+impl Demuxer {
+    pub fn new(delivery: Delivery) -> Self {
+        Self {
+            delivery,
+            track_delivery: HashMap::new(),
+            tracks: HashMap::new(),
+            extensions: HashMap::new(),
+            sources: HashMap::new(),
+            max_sources: DEFAULT_MAX_SOURCES,
+            source_timeout: DEFAULT_SOURCE_TIMEOUT,
+            unmapped: 0,
+            out: VecDeque::new(),
+        }
+    }
+
+    /// Limits the number of sources kept at once
+    pub fn max_sources(mut self, max: usize) -> Self {
+        self.max_sources = max.max(1);
+        self
+    }
+
+    /// Forgets sources after `timeout` without packets
+    pub fn source_timeout(mut self, timeout: Duration) -> Self {
+        self.source_timeout = timeout;
+        self
+    }
+
+    /// Delivers the sources of `track` in `delivery` mode, e.g. a video
+    /// track passed through to a WebRTC re-streamer while the audio track
+    /// is reordered
+    pub fn track_delivery(mut self, track: usize, delivery: Delivery) -> Self {
+        self.track_delivery.insert(track, delivery);
+        self
+    }
+
+    /// Assigns the next track to the RTP `channel` and returns it, a
+    /// channel that is already mapped keeps its track
+    pub fn map_channel(&mut self, channel: u8) -> usize {
+        let next = self.tracks.len();
+        *self.tracks.entry(channel).or_insert(next)
+    }
+
+    /// Decodes the header extensions of the packets of `channel` with
+    /// `extensions`, usually from the media description of its track
+    pub fn map_extensions(&mut self, channel: u8, extensions: ExtensionMap) {
+        self.extensions.insert(channel, extensions);
+    }
+
+    /// Track of the RTP `channel`, None for a channel that wasn't mapped
+    /// while others are
+    pub fn track(&self, channel: u8) -> Option<usize> {
+        match self.tracks.is_empty() {
+            true => Some(channel as usize / 2),
+            false => self.tracks.get(&channel).copied(),
+        }
+    }
+
+    /// Number of sources currently known
+    pub fn sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Packets dropped because their channel wasn't mapped
+    pub fn unmapped(&self) -> u64 {
+        self.unmapped
+    }
+
+    pub fn push(&mut self, channel: u8, packet: Packet) {
+        let Some(track) = self.track(channel) else {
+            self.unmapped += 1;
+            return;
+        };
+        let ssrc = packet.ssrc();
+        let now = Instant::now();
+        if !self.sources.contains_key(&(channel, ssrc)) {
+            self.make_room(now);
+        }
+        let delivery = self.track_delivery.get(&track).copied().unwrap_or(self.delivery);
+        let (queue, last_seen) = self
+            .sources
+            .entry((channel, ssrc))
+            .or_insert_with(|| (TrackQueue::new(delivery), now));
+        *last_seen = now;
+        queue.push(packet);
+        let extensions = self.extensions.get(&channel);
+        while let Some(packet) = queue.pop() {
+            let extensions = extensions.map(|map| map.parse(&packet.packet)).unwrap_or_default();
+            self.out.push_back(DemuxedPacket {
+                track,
+                channel,
+                ssrc,
+                packet,
+                extensions,
+            });
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<DemuxedPacket> {
+        self.out.pop_front()
+    }
+
+    /// The packets of `track` held back by reordering, in sequence order
+    /// per source, see [`TrackQueue::buffered`]
+    pub fn buffered(&self, track: usize) -> impl Iterator<Item = &Packet> {
+        self.sources
+            .iter()
+            .filter(move |((channel, _), _)| self.track(*channel) == Some(track))
+            .flat_map(|(_, (queue, _))| queue.buffered())
+    }
+
+    /// Forgets the idle sources and, if there are still too many for a
+    /// new one, the least recently seen
+    fn make_room(&mut self, now: Instant) {
+        let timeout = self.source_timeout;
+        self.sources
+            .retain(|_, (_, last_seen)| now.saturating_duration_since(*last_seen) < timeout);
+        while self.sources.len() >= self.max_sources {
+            let oldest = self.sources.iter().min_by_key(|(_, (_, last_seen))| *last_seen);
+            let Some((&key, _)) = oldest else {
+                break;
+            };
+            log::debug!("Forgetting RTP source {:08x} of channel {}", key.1, key.0);
+            self.sources.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn packet(seq: u16, ssrc: u32) -> Packet {
+        rtp_packet(96, ssrc, seq, 0, false, &[])
+    }
+
+    #[test]
+    fn test_demuxer() {
+        let mut demuxer = Demuxer::new(Delivery::Reordered { max_len: 4 });
+        assert_eq!(demuxer.map_channel(2), 0);
+        assert_eq!(demuxer.map_channel(0), 1);
+        assert_eq!(demuxer.map_channel(2), 0);
+        // Video on channel 2 with an RTX source of its own numbering, audio
+        // on channel 0 and an unmapped channel, whose packet is dropped
+        for (channel, seq, ssrc) in [(2, 100, 1), (2, 7, 9), (0, 50, 3), (2, 102, 1), (2, 101, 1), (6, 1, 4)] {
+            demuxer.push(channel, packet(seq, ssrc));
+        }
+        let mut out = Vec::new();
+        while let Some(p) = demuxer.pop() {
+            out.push((p.track, p.ssrc, p.packet.packet.sequence_number(), p.packet.gap));
+        }
+        assert_eq!(
+            out,
+            [
+                (0, 1, 100, 0),
+                (0, 9, 7, 0),
+                (1, 3, 50, 0),
+                (0, 1, 101, 0),
+                (0, 1, 102, 0),
+            ]
+        );
+        assert_eq!((demuxer.sources(), demuxer.unmapped()), (3, 1));
+        assert_eq!(demuxer.track(6), None);
+
+        // Waiting for 104 of the video source
+        demuxer.push(2, packet(105, 1));
+        let buffered: Vec<_> = demuxer.buffered(0).map(|p| p.sequence_number()).collect();
+        assert_eq!(buffered, [105]);
+        assert_eq!(demuxer.buffered(1).count(), 0);
+
+        // Level 30 from the header extension of the audio channel
+        demuxer.map_extensions(0, ExtensionMap::new().audio_level(1));
+        let mut buf = packet(51, 3).as_bytes().to_vec();
+        buf[0] |= 0x10;
+        buf.splice(12..12, [0xbe, 0xde, 0, 1, 0x10, 0x9e, 0, 0]);
+        demuxer.push(0, Packet::new(buf).unwrap());
+        let demuxed = demuxer.pop().unwrap();
+        assert_eq!(demuxed.extensions.audio_level.map(|l| l.level), Some(30));
+    }
+    #[test]
+    fn test_demuxer_track_delivery() {
+        let mut demuxer = Demuxer::new(Delivery::default()).track_delivery(1, Delivery::Passthrough);
+        for (channel, seq) in [(0, 1), (0, 3), (2, 1), (2, 3), (0, 2), (2, 2)] {
+            demuxer.push(channel, packet(seq, 1));
+        }
+        let mut out = Vec::new();
+        while let Some(p) = demuxer.pop() {
+            out.push((p.track, p.packet.packet.sequence_number(), p.packet.reordered));
+        }
+        assert_eq!(
+            out,
+            [
+                (0, 1, false),
+                (1, 1, false),
+                (1, 3, false),
+                (0, 2, false),
+                (0, 3, false),
+                (1, 2, true)
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_demuxer_sources() {
+        let mut demuxer = Demuxer::new(Delivery::Passthrough)
+            .max_sources(2)
+            .source_timeout(Duration::from_secs(5));
+        // Without mapped channels the convention decides the track
+        demuxer.push(4, packet(1, 1));
+        assert_eq!(demuxer.pop().unwrap().track, 2);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        demuxer.push(0, packet(1, 2));
+        // The third source replaces the least recently seen
+        tokio::time::advance(Duration::from_secs(1)).await;
+        demuxer.push(0, packet(1, 3));
+        let mut sources: Vec<_> = demuxer.sources.keys().copied().collect();
+        sources.sort_unstable();
+        assert_eq!(sources, [(0, 2), (0, 3)]);
+        // The source that went idle makes room, not the active one
+        tokio::time::advance(Duration::from_secs(5)).await;
+        demuxer.push(0, packet(2, 3));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        demuxer.push(2, packet(1, 4));
+        let mut sources: Vec<_> = demuxer.sources.keys().copied().collect();
+        sources.sort_unstable();
+        assert_eq!(sources, [(0, 3), (2, 4)]);
+    }
+}
@@ -0,0 +1,276 @@
+use super::depacketizer::Result;
+use super::{Depacketizer, Error};
+use crate::rtp::Packet;
+use crate::sdp::Fmtp;
+use crate::types::{Frame, FrameMetadata, FrameOrigin, FrameType, MediaType};
+use std::collections::VecDeque;
+
+struct BitReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, bits: u8) -> Option<u32> {
+        if bits as usize > self.remaining() {
+            return None;
+        }
+        let mut value = 0u32;
+        for _ in 0..bits {
+            let bit = (self.buf[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+fn parse_param(fmtp: &Fmtp, name: &'static str, default: Option<u8>) -> Result<u8> {
+    match fmtp.get(name) {
+        Some(v) => v.parse().ok().filter(|v| *v <= 16).ok_or(Error::InvalidParameter(name)),
+        None => default.ok_or(Error::InvalidParameter(name)),
+    }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// MPEG4-GENERIC audio (RFC 3640) in AAC-hbr/AAC-lbr mode.
+///
+/// Each access unit becomes a frame holding the raw AAC data without
+/// ADTS header, the AudioSpecificConfig is available via [`AacDepacketizer::config`].
+pub struct AacDepacketizer {
+    size_length: u8,
+    index_length: u8,
+    index_delta_length: u8,
+    frame_duration: u32,
+    config: Vec<u8>,
+    fragment: Vec<u8>,
+    fragment_size: usize,
+    fragment_timestamp: u32,
+    fragment_origin: FrameOrigin,
+    last_sequence_number: Option<u16>,
+    frames: VecDeque<Frame>,
+}
+
+impl AacDepacketizer {
+    pub fn new(fmtp: &Fmtp) -> Result<Self> {
+        let config = match fmtp.get("config") {
+            Some(c) => parse_hex(c).ok_or(Error::InvalidParameter("config"))?,
+            None => Vec::new(),
+        };
+        let frame_duration = match fmtp.get("constantduration") {
+            Some(d) => d.parse().map_err(|_| Error::InvalidParameter("constantduration"))?,
+            None => 1024,
+        };
+        let size_length = parse_param(fmtp, "sizelength", None)?;
+        if size_length == 0 {
+            return Err(Error::InvalidParameter("sizelength"));
+        }
+        Ok(Self {
+            size_length,
+            index_length: parse_param(fmtp, "indexlength", Some(0))?,
+            index_delta_length: parse_param(fmtp, "indexdeltalength", Some(0))?,
+            frame_duration,
+            config,
+            fragment: Vec::new(),
+            fragment_size: 0,
+            fragment_timestamp: 0,
+            fragment_origin: FrameOrigin::new(0, 0),
+            last_sequence_number: None,
+            frames: VecDeque::new(),
+        })
+    }
+
+    /// AudioSpecificConfig from the fmtp `config` parameter
+    pub fn config(&self) -> &[u8] {
+        &self.config
+    }
+
+    fn parse_au_sizes(&self, headers: &[u8], bits: usize) -> Result<Vec<usize>> {
+        let mut reader = BitReader::new(headers);
+        let mut sizes = Vec::new();
+        let mut consumed = 0;
+        while consumed < bits {
+            let index_length = if sizes.is_empty() {
+                self.index_length
+            } else {
+                self.index_delta_length
+            };
+            let size = reader.read(self.size_length).ok_or(Error::InvalidPayload)?;
+            reader.read(index_length).ok_or(Error::InvalidPayload)?;
+            consumed += (self.size_length + index_length) as usize;
+            sizes.push(size as usize);
+        }
+        Ok(sizes)
+    }
+
+    fn emit(&mut self, timestamp: u32, data: Vec<u8>, origin: FrameOrigin) {
+        self.frames.push_back(Frame {
+            media_type: MediaType::Audio,
+            frame_type: FrameType::AAC,
+            timestamp,
+            data,
+            metadata: FrameMetadata {
+                origin: Some(origin),
+                ..Default::default()
+            },
+        });
+    }
+}
+
+impl Depacketizer for AacDepacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<()> {
+        let sequence_number = packet.sequence_number();
+        if self
+            .last_sequence_number
+            .is_some_and(|last| last.wrapping_add(1) != sequence_number)
+        {
+            self.fragment.clear();
+        }
+        self.last_sequence_number = Some(sequence_number);
+
+        let data = packet.data();
+        if data.len() < 2 {
+            return Err(Error::InvalidPayload);
+        }
+        let header_bits = u16::from_be_bytes([data[0], data[1]]) as usize;
+        let header_bytes = header_bits.div_ceil(8);
+        let headers = data.get(2..2 + header_bytes).ok_or(Error::InvalidPayload)?;
+        let sizes = self.parse_au_sizes(headers, header_bits)?;
+        let payload = &data[2 + header_bytes..];
+
+        if !self.fragment.is_empty() || (sizes.len() == 1 && sizes[0] > payload.len()) {
+            // A single access unit fragmented over several packets
+            if self.fragment.is_empty() {
+                self.fragment_size = sizes[0];
+                self.fragment_timestamp = packet.timestamp();
+                self.fragment_origin = FrameOrigin::new(packet.ssrc(), sequence_number);
+            }
+            self.fragment_origin.last_sequence = sequence_number;
+            self.fragment.extend_from_slice(payload);
+            if self.fragment.len() >= self.fragment_size || packet.marker() {
+                let data = std::mem::take(&mut self.fragment);
+                if data.len() != self.fragment_size {
+                    log::warn!("AAC fragment size mismatch, discarding");
+                    return Ok(());
+                }
+                self.emit(self.fragment_timestamp, data, self.fragment_origin);
+            }
+            return Ok(());
+        }
+
+        let mut offset = 0;
+        let mut timestamp = packet.timestamp();
+        let origin = FrameOrigin::new(packet.ssrc(), sequence_number);
+        for size in sizes {
+            let au = payload.get(offset..offset + size).ok_or(Error::InvalidPayload)?;
+            self.emit(timestamp, au.to_vec(), origin);
+            offset += size;
+            timestamp = timestamp.wrapping_add(self.frame_duration);
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn buffered(&self) -> usize {
+        self.fragment.len() + self.frames.iter().map(|f| f.data.len()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn hbr_fmtp() -> Fmtp {
+        "97 streamtype=5;profile-level-id=15;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3;config=1410"
+            .parse()
+            .unwrap()
+    }
+
+    fn packet(seq: u16, ts: u32, marker: bool, payload: &[u8]) -> Packet {
+        rtp_packet(97, 0, seq, ts, marker, payload)
+    }
+
+    #[test]
+    fn test_aac_multiple_access_units() {
+        let mut depacketizer = AacDepacketizer::new(&hbr_fmtp()).unwrap();
+        assert_eq!(depacketizer.config(), &[0x14, 0x10]);
+        // Two AU headers of 16 bits: size 3 index 0, size 2 delta 0
+        let payload = [0x00, 0x20, 0x00, 0x18, 0x00, 0x10, 1, 2, 3, 4, 5];
+        depacketizer.push(&packet(1, 1000, true, &payload)).unwrap();
+        let first = depacketizer.pop().unwrap();
+        assert_eq!(first.frame_type, FrameType::AAC);
+        assert_eq!(first.timestamp, 1000);
+        assert_eq!(first.data, vec![1, 2, 3]);
+        let second = depacketizer.pop().unwrap();
+        assert_eq!(second.timestamp, 2024);
+        assert_eq!(second.data, vec![4, 5]);
+        assert!(depacketizer.pop().is_none());
+    }
+
+    #[test]
+    fn test_aac_fragmented_access_unit() {
+        let mut depacketizer = AacDepacketizer::new(&hbr_fmtp()).unwrap();
+        // One AU of size 4 split over two packets
+        depacketizer
+            .push(&packet(1, 1000, false, &[0x00, 0x10, 0x00, 0x20, 1, 2]))
+            .unwrap();
+        assert!(depacketizer.pop().is_none());
+        depacketizer
+            .push(&packet(2, 1000, true, &[0x00, 0x10, 0x00, 0x20, 3, 4]))
+            .unwrap();
+        let frame = depacketizer.pop().unwrap();
+        assert_eq!(frame.data, vec![1, 2, 3, 4]);
+        let origin = frame.metadata.origin.unwrap();
+        assert_eq!((origin.first_sequence, origin.last_sequence), (1, 2));
+        assert_eq!(origin.packets(), 2);
+    }
+
+    #[test]
+    fn test_aac_fragment_lost() {
+        let mut depacketizer = AacDepacketizer::new(&hbr_fmtp()).unwrap();
+        depacketizer
+            .push(&packet(1, 1000, false, &[0x00, 0x10, 0x00, 0x30, 1, 2]))
+            .unwrap();
+        depacketizer
+            .push(&packet(3, 1000, true, &[0x00, 0x10, 0x00, 0x30, 5, 6]))
+            .unwrap();
+        assert!(depacketizer.pop().is_none());
+    }
+
+    #[test]
+    fn test_aac_truncated_payload() {
+        let mut depacketizer = AacDepacketizer::new(&hbr_fmtp()).unwrap();
+        let result = depacketizer.push(&packet(1, 0, true, &[0x00, 0x20, 0x00, 0x18]));
+        assert!(matches!(result, Err(Error::InvalidPayload)));
+    }
+
+    #[test]
+    fn test_aac_missing_sizelength() {
+        let fmtp: Fmtp = "97 mode=AAC-hbr".parse().unwrap();
+        assert!(matches!(
+            AacDepacketizer::new(&fmtp),
+            Err(Error::InvalidParameter("sizelength"))
+        ));
+    }
+}
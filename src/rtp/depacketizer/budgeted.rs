@@ -0,0 +1,86 @@
+use super::depacketizer::Result;
+use super::{CodecChanged, Depacketizer};
+use crate::metrics::{Charge, Component, MemoryBudget};
+use crate::rtp::Packet;
+use crate::types::Frame;
+
+/// Charges the frames a depacketizer holds to a [`MemoryBudget`].
+///
+/// The payload of a packet is reserved before it is pushed, a packet that
+/// exceeds the budget is rejected with [`super::Error::BudgetExceeded`] and
+/// never reaches the inner depacketizer, which then discards the partial
+/// frame as it sees a sequence gap.
+pub struct BudgetedDepacketizer {
+    inner: Box<dyn Depacketizer>,
+    charge: Charge,
+}
+
+impl BudgetedDepacketizer {
+    pub fn new(inner: Box<dyn Depacketizer>, budget: MemoryBudget) -> Self {
+        Self {
+            inner,
+            charge: Charge::new(budget, Component::Assembly),
+        }
+    }
+
+    pub fn into_inner(self) -> Box<dyn Depacketizer> {
+        self.inner
+    }
+}
+
+impl Depacketizer for BudgetedDepacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<()> {
+        self.charge.resize(self.inner.buffered() + packet.data().len())?;
+        let result = self.inner.push(packet);
+        // Headers and discarded fragments are not kept
+        self.charge.resize(self.inner.buffered())?;
+        result
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        let frame = self.inner.pop();
+        // Shrinking always succeeds
+        let _ = self.charge.resize(self.inner.buffered());
+        frame
+    }
+
+    fn buffered(&self) -> usize {
+        self.inner.buffered()
+    }
+
+    fn codec_change(&mut self) -> Option<CodecChanged> {
+        self.inner.codec_change()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::depacketizer::{Error, G711Depacketizer};
+    use crate::testing::rtp_packet;
+    use crate::types::FrameType;
+
+    fn packet(seq: u16, len: usize) -> Packet {
+        rtp_packet(8, 0, seq, 0, false, &vec![0xd5; len])
+    }
+
+    #[test]
+    fn test_budgeted_depacketizer() {
+        let budget = MemoryBudget::new(300);
+        let mut depacketizer =
+            BudgetedDepacketizer::new(Box::new(G711Depacketizer::new(FrameType::PCMA)), budget.clone());
+        depacketizer.push(&packet(1, 160)).unwrap();
+        assert_eq!(budget.usage().assembly, 160);
+        assert!(matches!(
+            depacketizer.push(&packet(2, 160)),
+            Err(Error::BudgetExceeded(_))
+        ));
+        assert_eq!(depacketizer.pop().unwrap().data.len(), 160);
+        assert_eq!(budget.usage().used, 0);
+        depacketizer.push(&packet(3, 160)).unwrap();
+        drop(depacketizer);
+        let usage = budget.usage();
+        assert_eq!(usage.used, 0);
+        assert_eq!(usage.rejected, 1);
+    }
+}
@@ -0,0 +1,87 @@
+use super::{AacDepacketizer, CodecChanged, G711Depacketizer, H264Depacketizer};
+use crate::metrics::BudgetError;
+use crate::rtp::Packet;
+use crate::sdp::{Codec, Media};
+use crate::types::{Frame, FrameType};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Unsupported codec {0}")]
+    UnsupportedCodec(Codec),
+    #[error("Media has no payload type")]
+    MissingPayloadType,
+    #[error("Missing rtpmap for payload type {0}")]
+    MissingRtpMap(u8),
+    #[error("Missing or invalid fmtp parameter {0}")]
+    InvalidParameter(&'static str),
+    #[error("Invalid payload")]
+    InvalidPayload,
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Turns the RTP packets of a track into frames.
+pub trait Depacketizer: Send {
+    /// Feeds the next packet, packets are expected in sequence order.
+    /// Gaps in the sequence numbers discard partially assembled frames.
+    fn push(&mut self, packet: &Packet) -> Result<()>;
+
+    /// Returns the next completed frame
+    fn pop(&mut self) -> Option<Frame>;
+
+    /// Bytes held in partially assembled and completed frames
+    fn buffered(&self) -> usize {
+        0
+    }
+
+    /// Returns the next change of the payload format, only a
+    /// [`SwitchingDepacketizer`](super::SwitchingDepacketizer) changes it
+    fn codec_change(&mut self) -> Option<CodecChanged> {
+        None
+    }
+}
+
+/// Creates the depacketizer for the first payload type of the media description
+pub fn new_depacketizer(media: &Media) -> Result<Box<dyn Depacketizer>> {
+    let payload_type = *media.formats.first().ok_or(Error::MissingPayloadType)?;
+    new_depacketizer_for(media, payload_type)
+}
+
+/// Creates the depacketizer for `payload_type` of the media description
+pub fn new_depacketizer_for(media: &Media, payload_type: u8) -> Result<Box<dyn Depacketizer>> {
+    let rtpmap = media.rtpmap(payload_type).ok_or(Error::MissingRtpMap(payload_type))?;
+    match rtpmap.codec {
+        Codec::AAC => {
+            let fmtp = media.fmtp(payload_type).ok_or(Error::InvalidParameter("fmtp"))?;
+            Ok(Box::new(AacDepacketizer::new(&fmtp)?))
+        }
+        Codec::H264 => match media.fmtp(payload_type) {
+            Some(fmtp) => Ok(Box::new(H264Depacketizer::from_fmtp(&fmtp)?)),
+            None => Ok(Box::new(H264Depacketizer::new())),
+        },
+        Codec::PCMU => Ok(Box::new(G711Depacketizer::new(FrameType::PCMU))),
+        Codec::PCMA => Ok(Box::new(G711Depacketizer::new(FrameType::PCMA))),
+        codec => Err(Error::UnsupportedCodec(codec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_depacketizer() {
+        let media: Media = "audio 0 RTP/AVP 8".parse().unwrap();
+        assert!(new_depacketizer(&media).is_ok());
+        let media: Media = "video 0 RTP/AVP 26".parse().unwrap();
+        assert!(matches!(new_depacketizer(&media), Err(Error::MissingRtpMap(26))));
+        let mut media: Media = "audio 0 RTP/AVP 97".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("97 MPEG4-GENERIC/16000/1".to_string())));
+        assert!(matches!(new_depacketizer(&media), Err(Error::InvalidParameter("fmtp"))));
+    }
+}
@@ -0,0 +1,67 @@
+use super::depacketizer::Result;
+use super::Depacketizer;
+use crate::rtp::Packet;
+use crate::types::{Frame, FrameMetadata, FrameOrigin, FrameType, MediaType};
+use std::collections::VecDeque;
+
+/// PCMU/PCMA (RFC 3551), every packet payload is a frame
+pub struct G711Depacketizer {
+    frame_type: FrameType,
+    frames: VecDeque<Frame>,
+}
+
+impl G711Depacketizer {
+    pub fn new(frame_type: FrameType) -> Self {
+        Self {
+            frame_type,
+            frames: VecDeque::new(),
+        }
+    }
+}
+
+impl Depacketizer for G711Depacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<()> {
+        if !packet.data().is_empty() {
+            self.frames.push_back(Frame {
+                media_type: MediaType::Audio,
+                frame_type: self.frame_type,
+                timestamp: packet.timestamp(),
+                data: packet.data().to_vec(),
+                metadata: FrameMetadata {
+                    origin: Some(FrameOrigin::new(packet.ssrc(), packet.sequence_number())),
+                    ..Default::default()
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn buffered(&self) -> usize {
+        self.frames.iter().map(|f| f.data.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_g711_depacketizer() {
+        let mut depacketizer = G711Depacketizer::new(FrameType::PCMA);
+        let packet = Packet::new(vec![
+            0x80, 0x08, 0x00, 0x01, 0x00, 0x00, 0x00, 0xA0, 0x00, 0x00, 0x00, 0x00, 0xD5, 0xD5, 0x55,
+        ])
+        .unwrap();
+        depacketizer.push(&packet).unwrap();
+        let frame = depacketizer.pop().unwrap();
+        assert_eq!(frame.media_type, MediaType::Audio);
+        assert_eq!(frame.frame_type, FrameType::PCMA);
+        assert_eq!(frame.timestamp, 160);
+        assert_eq!(frame.data, vec![0xD5, 0xD5, 0x55]);
+        assert!(depacketizer.pop().is_none());
+    }
+}
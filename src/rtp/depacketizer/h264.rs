@@ -0,0 +1,247 @@
+use super::depacketizer::Result;
+use super::{Depacketizer, Error};
+use crate::codec::{is_random_access, nal_type, NalUnits};
+use crate::rtp::Packet;
+use crate::sdp::Fmtp;
+use crate::types::{Frame, FrameMetadata, FrameOrigin, FrameType, MediaType};
+use base64::prelude::*;
+use std::collections::VecDeque;
+
+const STAP_A: u8 = 24;
+const FU_A: u8 = 28;
+const SPS: u8 = 7;
+
+/// H.264 (RFC 6184) in single NAL unit or non-interleaved mode.
+///
+/// The NAL units of an access unit are collected until the marker bit or
+/// the next timestamp and become a frame in Annex B format. The parameter
+/// sets of the fmtp `sprop-parameter-sets` are put in front of keyframes
+/// that come without them, so decoding can start at any keyframe.
+pub struct H264Depacketizer {
+    parameter_sets: Vec<Vec<u8>>,
+    access_unit: Vec<u8>,
+    timestamp: u32,
+    origin: FrameOrigin,
+    // FU-A fragments of the NAL unit being reassembled, empty if none
+    fragment: Vec<u8>,
+    last_sequence_number: Option<u16>,
+    frames: VecDeque<Frame>,
+}
+
+impl Default for H264Depacketizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl H264Depacketizer {
+    pub fn new() -> Self {
+        Self {
+            parameter_sets: Vec::new(),
+            access_unit: Vec::new(),
+            timestamp: 0,
+            origin: FrameOrigin::new(0, 0),
+            fragment: Vec::new(),
+            last_sequence_number: None,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Takes the parameter sets from the `sprop-parameter-sets` of `fmtp`
+    pub fn from_fmtp(fmtp: &Fmtp) -> Result<Self> {
+        let mut depacketizer = Self::new();
+        if let Some(sets) = fmtp.get("sprop-parameter-sets") {
+            for set in sets.split(',').filter(|s| !s.is_empty()) {
+                let nal = BASE64_STANDARD
+                    .decode(set)
+                    .map_err(|_| Error::InvalidParameter("sprop-parameter-sets"))?;
+                depacketizer.parameter_sets.push(nal);
+            }
+        }
+        Ok(depacketizer)
+    }
+
+    /// SPS and PPS from the fmtp, without start codes
+    pub fn parameter_sets(&self) -> &[Vec<u8>] {
+        &self.parameter_sets
+    }
+
+    fn append(&mut self, nal: &[u8]) {
+        self.access_unit.extend_from_slice(&[0, 0, 0, 1]);
+        self.access_unit.extend_from_slice(nal);
+    }
+
+    fn finish(&mut self) {
+        if self.access_unit.is_empty() {
+            return;
+        }
+        let mut data = std::mem::take(&mut self.access_unit);
+        let keyframe = NalUnits::new(&data).any(|nal| is_random_access(FrameType::H264, nal));
+        let has_sps = NalUnits::new(&data).any(|nal| nal_type(FrameType::H264, nal) == Some(SPS));
+        if keyframe && !has_sps && !self.parameter_sets.is_empty() {
+            let mut with_sets = Vec::with_capacity(data.len() + 64);
+            for set in &self.parameter_sets {
+                with_sets.extend_from_slice(&[0, 0, 0, 1]);
+                with_sets.extend_from_slice(set);
+            }
+            with_sets.append(&mut data);
+            data = with_sets;
+        }
+        self.frames.push_back(Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp: self.timestamp,
+            data,
+            metadata: FrameMetadata {
+                origin: Some(self.origin),
+                ..Default::default()
+            },
+        });
+    }
+}
+
+impl Depacketizer for H264Depacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<()> {
+        let sequence_number = packet.sequence_number();
+        if self
+            .last_sequence_number
+            .is_some_and(|last| last.wrapping_add(1) != sequence_number)
+        {
+            self.fragment.clear();
+            self.access_unit.clear();
+        }
+        self.last_sequence_number = Some(sequence_number);
+        if packet.timestamp() != self.timestamp {
+            // The marker bit of the previous access unit was lost
+            self.finish();
+            self.fragment.clear();
+        }
+        if self.access_unit.is_empty() && self.fragment.is_empty() {
+            self.timestamp = packet.timestamp();
+            self.origin = FrameOrigin::new(packet.ssrc(), sequence_number);
+        }
+        self.origin.last_sequence = sequence_number;
+
+        let data = packet.data();
+        let header = *data.first().ok_or(Error::InvalidPayload)?;
+        match header & 0x1f {
+            1..=23 => self.append(data),
+            STAP_A => {
+                let mut rest = &data[1..];
+                while !rest.is_empty() {
+                    let size = match rest {
+                        [a, b, ..] => u16::from_be_bytes([*a, *b]) as usize,
+                        _ => return Err(Error::InvalidPayload),
+                    };
+                    let nal = rest.get(2..2 + size).ok_or(Error::InvalidPayload)?;
+                    self.append(nal);
+                    rest = &rest[2 + size..];
+                }
+            }
+            FU_A => {
+                let fu_header = *data.get(1).ok_or(Error::InvalidPayload)?;
+                let (start, end) = (fu_header & 0x80 != 0, fu_header & 0x40 != 0);
+                if start {
+                    self.fragment.clear();
+                    self.fragment.push((header & 0xe0) | (fu_header & 0x1f));
+                } else if self.fragment.is_empty() {
+                    // The start of the NAL unit was lost
+                    return Ok(());
+                }
+                self.fragment.extend_from_slice(&data[2..]);
+                if end {
+                    let nal = std::mem::take(&mut self.fragment);
+                    self.append(&nal);
+                }
+            }
+            _ => return Err(Error::InvalidPayload),
+        }
+        if packet.marker() {
+            self.finish();
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+
+    fn buffered(&self) -> usize {
+        self.access_unit.len() + self.fragment.len() + self.frames.iter().map(|f| f.data.len()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn packet(seq: u16, ts: u32, marker: bool, payload: &[u8]) -> Packet {
+        rtp_packet(96, 5, seq, ts, marker, payload)
+    }
+
+    #[test]
+    fn test_h264_access_units() {
+        let mut depacketizer = H264Depacketizer::new();
+        // STAP-A with SPS and PPS, then an IDR slice in two FU-A fragments
+        depacketizer
+            .push(&packet(1, 3000, false, &[0x78, 0, 2, 0x67, 1, 0, 2, 0x68, 2]))
+            .unwrap();
+        depacketizer.push(&packet(2, 3000, false, &[0x7c, 0x85, 1, 2])).unwrap();
+        assert!(depacketizer.pop().is_none());
+        depacketizer.push(&packet(3, 3000, true, &[0x7c, 0x45, 3])).unwrap();
+        let frame = depacketizer.pop().unwrap();
+        assert_eq!(frame.media_type, MediaType::Video);
+        assert_eq!(frame.timestamp, 3000);
+        assert_eq!(
+            frame.data,
+            [0, 0, 0, 1, 0x67, 1, 0, 0, 0, 1, 0x68, 2, 0, 0, 0, 1, 0x65, 1, 2, 3]
+        );
+        assert!(frame.is_keyframe());
+        assert_eq!(frame.metadata.origin.unwrap().packets(), 3);
+
+        // A P slice whose marker bit is lost ends with the next timestamp
+        depacketizer.push(&packet(4, 6000, false, &[0x41, 9])).unwrap();
+        depacketizer.push(&packet(5, 9000, true, &[0x41, 8])).unwrap();
+        let frame = depacketizer.pop().unwrap();
+        assert_eq!(
+            (frame.timestamp, frame.data.as_slice()),
+            (6000, &[0, 0, 0, 1, 0x41, 9][..])
+        );
+        assert!(!frame.is_keyframe());
+        assert_eq!(depacketizer.pop().unwrap().timestamp, 9000);
+    }
+
+    #[test]
+    fn test_h264_lost_fragment() {
+        let mut depacketizer = H264Depacketizer::new();
+        depacketizer.push(&packet(1, 3000, false, &[0x7c, 0x85, 1, 2])).unwrap();
+        depacketizer.push(&packet(3, 3000, true, &[0x7c, 0x45, 3])).unwrap();
+        assert!(depacketizer.pop().is_none());
+        assert_eq!(depacketizer.buffered(), 0);
+        assert!(matches!(
+            depacketizer.push(&packet(4, 6000, true, &[0x78, 0, 5, 0x41])),
+            Err(Error::InvalidPayload)
+        ));
+    }
+
+    #[test]
+    fn test_h264_sprop_parameter_sets() {
+        let fmtp: Fmtp = "96 packetization-mode=1;sprop-parameter-sets=Z0IAHg==,aM4G4g=="
+            .parse()
+            .unwrap();
+        let mut depacketizer = H264Depacketizer::from_fmtp(&fmtp).unwrap();
+        assert_eq!(
+            depacketizer.parameter_sets(),
+            [vec![0x67, 0x42, 0, 0x1e], vec![0x68, 0xce, 6, 0xe2]]
+        );
+        depacketizer.push(&packet(1, 0, true, &[0x65, 1])).unwrap();
+        let frame = depacketizer.pop().unwrap();
+        assert_eq!(
+            frame.data,
+            [0, 0, 0, 1, 0x67, 0x42, 0, 0x1e, 0, 0, 0, 1, 0x68, 0xce, 6, 0xe2, 0, 0, 0, 1, 0x65, 1]
+        );
+        depacketizer.push(&packet(2, 3000, true, &[0x41, 1])).unwrap();
+        assert_eq!(depacketizer.pop().unwrap().data, [0, 0, 0, 1, 0x41, 1]);
+    }
+}
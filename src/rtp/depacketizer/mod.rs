@@ -0,0 +1,17 @@
+mod aac;
+mod budgeted;
+mod depacketizer;
+mod g711;
+mod h264;
+mod switching;
+
+pub use aac::AacDepacketizer;
+pub use budgeted::BudgetedDepacketizer;
+pub use depacketizer::new_depacketizer;
+pub use depacketizer::new_depacketizer_for;
+pub use depacketizer::Depacketizer;
+pub use depacketizer::Error;
+pub use g711::G711Depacketizer;
+pub use h264::H264Depacketizer;
+pub use switching::CodecChanged;
+pub use switching::SwitchingDepacketizer;
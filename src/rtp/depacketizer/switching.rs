@@ -0,0 +1,180 @@
+use super::depacketizer::{Error, Result};
+use super::{new_depacketizer_for, Depacketizer};
+use crate::rtp::Packet;
+use crate::sdp::{Fmtp, Media, RtpMap};
+use crate::types::Frame;
+use std::collections::VecDeque;
+
+/// The payload format of a track changed, timestamps continue with the
+/// clock rate of `current` and the jitter buffer and synchronizer have to
+/// be rebuilt, see [`JitterBuffer::set_clock_rate`](crate::rtp::JitterBuffer::set_clock_rate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecChanged {
+    pub previous: RtpMap,
+    pub current: RtpMap,
+}
+
+struct Current {
+    rtpmap: RtpMap,
+    fmtp: Option<Fmtp>,
+    depacketizer: Box<dyn Depacketizer>,
+}
+
+/// Depacketizes a track whose payload type may change mid-session, e.g.
+/// after a PLAY following a seek or when the camera was reconfigured.
+///
+/// The depacketizer is created for the payload type of each packet, so a
+/// new payload type, or a new rtpmap or fmtp of the current one after
+/// [`update_media`](Self::update_media), rebuilds it. Completed frames of
+/// the previous depacketizer are still returned, partially assembled ones
+/// are discarded. Packets of payload types without a usable rtpmap are
+/// rejected instead of being fed to the wrong depacketizer.
+pub struct SwitchingDepacketizer {
+    media: Media,
+    current: Option<Current>,
+    // Completed frames of replaced depacketizers
+    frames: VecDeque<Frame>,
+    changes: VecDeque<CodecChanged>,
+}
+
+impl SwitchingDepacketizer {
+    pub fn new(media: Media) -> Self {
+        Self {
+            media,
+            current: None,
+            frames: VecDeque::new(),
+            changes: VecDeque::new(),
+        }
+    }
+
+    /// Replaces the media description, e.g. with the one of a re-DESCRIBE.
+    /// The depacketizer is rebuilt with the next packet if the mapping of
+    /// its payload type changed.
+    pub fn update_media(&mut self, media: Media) {
+        self.media = media;
+    }
+
+    /// The rtpmap of the payload type currently depacketized
+    pub fn rtpmap(&self) -> Option<&RtpMap> {
+        self.current.as_ref().map(|c| &c.rtpmap)
+    }
+
+    /// Clock rate of the RTP timestamps of the current payload type
+    pub fn clock_rate(&self) -> Option<u32> {
+        self.rtpmap().map(|r| r.timebase)
+    }
+
+    fn switch(&mut self, payload_type: u8) -> Result<()> {
+        let rtpmap = self
+            .media
+            .rtpmap(payload_type)
+            .ok_or(Error::MissingRtpMap(payload_type))?;
+        let fmtp = self.media.fmtp(payload_type);
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|c| c.rtpmap == rtpmap && c.fmtp == fmtp)
+        {
+            return Ok(());
+        }
+        let depacketizer = new_depacketizer_for(&self.media, payload_type)?;
+        let current = Current {
+            rtpmap: rtpmap.clone(),
+            fmtp,
+            depacketizer,
+        };
+        if let Some(mut previous) = self.current.replace(current) {
+            while let Some(frame) = previous.depacketizer.pop() {
+                self.frames.push_back(frame);
+            }
+            log::info!(
+                "Payload format changed from {} {}/{} to {} {}/{}",
+                previous.rtpmap.payload_type,
+                previous.rtpmap.codec,
+                previous.rtpmap.timebase,
+                rtpmap.payload_type,
+                rtpmap.codec,
+                rtpmap.timebase
+            );
+            self.changes.push_back(CodecChanged {
+                previous: previous.rtpmap,
+                current: rtpmap,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Depacketizer for SwitchingDepacketizer {
+    fn push(&mut self, packet: &Packet) -> Result<()> {
+        self.switch(packet.payload_type())?;
+        match &mut self.current {
+            Some(current) => current.depacketizer.push(packet),
+            None => Err(Error::MissingRtpMap(packet.payload_type())),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        self.frames
+            .pop_front()
+            .or_else(|| self.current.as_mut()?.depacketizer.pop())
+    }
+
+    fn buffered(&self) -> usize {
+        let pending: usize = self.frames.iter().map(|f| f.data.len()).sum();
+        pending + self.current.as_ref().map_or(0, |c| c.depacketizer.buffered())
+    }
+
+    /// Returns the next change of the payload format, in the order they happened
+    fn codec_change(&mut self) -> Option<CodecChanged> {
+        self.changes.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::Codec;
+    use crate::types::FrameType;
+
+    fn packet(payload_type: u8, seq: u16) -> Packet {
+        let mut buf = vec![0x80, payload_type];
+        buf.extend_from_slice(&seq.to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 160, 0, 0, 0, 1, 0xd5]);
+        Packet::new(buf).unwrap()
+    }
+
+    #[test]
+    fn test_switching_depacketizer() {
+        let media: Media = "audio 0 RTP/AVP 0 8".parse().unwrap();
+        let mut depacketizer = SwitchingDepacketizer::new(media);
+        depacketizer.push(&packet(0, 1)).unwrap();
+        assert_eq!(depacketizer.clock_rate(), Some(8000));
+        assert!(depacketizer.codec_change().is_none());
+
+        // The frame of the previous codec is still returned
+        depacketizer.push(&packet(8, 2)).unwrap();
+        let change = depacketizer.codec_change().unwrap();
+        assert_eq!(change.previous.codec, Codec::PCMU);
+        assert_eq!(change.current.codec, Codec::PCMA);
+        assert_eq!(depacketizer.pop().unwrap().frame_type, FrameType::PCMU);
+        assert_eq!(depacketizer.pop().unwrap().frame_type, FrameType::PCMA);
+
+        // Unknown payload types are rejected instead of misinterpreted
+        assert!(matches!(
+            depacketizer.push(&packet(97, 3)),
+            Err(Error::MissingRtpMap(97))
+        ));
+
+        // A re-DESCRIBE maps the payload type to another clock rate
+        let mut media: Media = "audio 0 RTP/AVP 8".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("8 PCMA/16000".to_string())));
+        depacketizer.update_media(media);
+        depacketizer.push(&packet(8, 4)).unwrap();
+        assert_eq!(depacketizer.codec_change().unwrap().current.timebase, 16000);
+        assert_eq!(depacketizer.clock_rate(), Some(16000));
+        assert!(depacketizer.codec_change().is_none());
+    }
+}
@@ -0,0 +1,220 @@
+use super::Packet;
+use crate::sdp::Media;
+
+/// URI of the client-to-mixer audio level extension (RFC 6464)
+pub const AUDIO_LEVEL_URI: &str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+/// URI of the coordination of video orientation extension (3GPP TS 26.114)
+pub const VIDEO_ORIENTATION_URI: &str = "urn:3gpp:video-orientation";
+
+const ONE_BYTE_PROFILE: u16 = 0xbede;
+// The low 4 bits of the two-byte profile are application bits
+const TWO_BYTE_PROFILE: u16 = 0x1000;
+
+/// Header extension of an RTP packet (RFC 3550 5.3.1), the data excludes
+/// the profile and length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderExtension<'a> {
+    pub profile: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> HeaderExtension<'a> {
+    pub fn new(profile: u16, data: &'a [u8]) -> Self {
+        Self { profile, data }
+    }
+
+    /// Id and data of the elements of one-byte or two-byte headers (RFC
+    /// 8285), nothing for other profiles
+    pub fn elements(&self) -> impl Iterator<Item = (u8, &'a [u8])> {
+        let two_byte = self.profile & 0xfff0 == TWO_BYTE_PROFILE;
+        let mut data = match self.profile == ONE_BYTE_PROFILE || two_byte {
+            true => self.data,
+            false => &[],
+        };
+        std::iter::from_fn(move || loop {
+            let (&first, rest) = data.split_first()?;
+            // Padding between elements
+            if first == 0 {
+                data = rest;
+                continue;
+            }
+            let (id, len, rest) = match two_byte {
+                true => {
+                    let (&len, rest) = rest.split_first()?;
+                    (first, len as usize, rest)
+                }
+                // Id 15 ends the one-byte elements
+                false if first >> 4 == 15 => return None,
+                false => (first >> 4, (first & 0x0f) as usize + 1, rest),
+            };
+            let element = rest.get(..len)?;
+            data = &rest[len..];
+            return Some((id, element));
+        })
+    }
+}
+
+/// Level of the audio of a packet (RFC 6464)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioLevel {
+    /// Level in -dBov, from 0 for the loudest to 127 for silence
+    pub level: u8,
+    /// The voice activity detection of the sender found speech
+    pub voice: bool,
+}
+
+impl AudioLevel {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let byte = *data.first()?;
+        Some(Self {
+            level: byte & 0x7f,
+            voice: byte & 0x80 != 0,
+        })
+    }
+}
+
+/// How the video has to be turned for display, the coordination of video
+/// orientation of 3GPP TS 26.114
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoOrientation {
+    /// Clockwise rotation in degrees to apply, 0, 90, 180 or 270
+    pub rotation: u16,
+    /// Horizontal flip to apply before the rotation
+    pub flip: bool,
+    /// Captured by a back-facing camera
+    pub back_camera: bool,
+}
+
+impl VideoOrientation {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let byte = *data.first()?;
+        Some(Self {
+            rotation: (byte & 0x03) as u16 * 90,
+            flip: byte & 0x04 != 0,
+            back_camera: byte & 0x08 != 0,
+        })
+    }
+}
+
+/// Metadata of a packet decoded from its header extension
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtensionValues {
+    pub audio_level: Option<AudioLevel>,
+    pub orientation: Option<VideoOrientation>,
+}
+
+impl ExtensionValues {
+    pub fn is_empty(&self) -> bool {
+        self.audio_level.is_none() && self.orientation.is_none()
+    }
+}
+
+/// Ids of the header extensions of a track, negotiated by the `a=extmap`
+/// attributes of its media description. Only the extensions with a
+/// decoder in [`ExtensionValues`] are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionMap {
+    audio_level: Option<u8>,
+    orientation: Option<u8>,
+}
+
+impl ExtensionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The extensions of `media` this crate decodes
+    pub fn from_media(media: &Media) -> Self {
+        let mut map = Self::new();
+        for extmap in media.extmaps() {
+            match extmap.uri.as_str() {
+                AUDIO_LEVEL_URI => map.audio_level = Some(extmap.id),
+                VIDEO_ORIENTATION_URI => map.orientation = Some(extmap.id),
+                _ => {}
+            }
+        }
+        map
+    }
+
+    /// Decodes the audio level from the extension element `id`
+    pub fn audio_level(mut self, id: u8) -> Self {
+        self.audio_level = Some(id);
+        self
+    }
+
+    /// Decodes the video orientation from the extension element `id`
+    pub fn orientation(mut self, id: u8) -> Self {
+        self.orientation = Some(id);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.audio_level.is_none() && self.orientation.is_none()
+    }
+
+    /// Decodes the mapped extensions of `packet`
+    pub fn parse(&self, packet: &Packet) -> ExtensionValues {
+        let mut values = ExtensionValues::default();
+        let Some(extension) = packet.header_extension() else {
+            return values;
+        };
+        for (id, data) in extension.elements() {
+            if Some(id) == self.audio_level {
+                values.audio_level = AudioLevel::parse(data);
+            } else if Some(id) == self.orientation {
+                values.orientation = VideoOrientation::parse(data);
+            }
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(extension: &[u8]) -> Packet {
+        let mut buf = vec![0x90, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        buf.extend_from_slice(extension);
+        Packet::new(buf).unwrap()
+    }
+
+    #[test]
+    fn test_header_extension_elements() {
+        // One-byte elements 1 and 3 with padding in between
+        let extension = HeaderExtension::new(0xbede, &[0x10, 0xaa, 0x00, 0x31, 0xbb, 0xcc, 0x00, 0x00]);
+        let elements: Vec<_> = extension.elements().collect();
+        assert_eq!(elements, [(1, &[0xaa][..]), (3, &[0xbb, 0xcc][..])]);
+        // Two-byte element 20, an empty element 21 and one cut off
+        let extension = HeaderExtension::new(0x1000, &[20, 1, 0xaa, 21, 0, 0, 22, 4, 0xbb]);
+        let elements: Vec<_> = extension.elements().collect();
+        assert_eq!(elements, [(20, &[0xaa][..]), (21, &[][..])]);
+        assert_eq!(HeaderExtension::new(0xabcd, &[0x10, 0xaa]).elements().count(), 0);
+    }
+
+    #[test]
+    fn test_extension_map() {
+        let mut media: Media = "video 0 RTP/AVP 96".parse().unwrap();
+        for extmap in [
+            "1 urn:ietf:params:rtp-hdrext:ssrc-audio-level vad=on",
+            "4/recvonly urn:3gpp:video-orientation",
+        ] {
+            media.attributes.push(("extmap".to_string(), Some(extmap.to_string())));
+        }
+        let map = ExtensionMap::from_media(&media);
+        assert_eq!(map, ExtensionMap::new().audio_level(1).orientation(4));
+
+        // Level 30 with voice, rotated by 90 degrees and flipped
+        let values = map.parse(&packet(&[0xbe, 0xde, 0, 1, 0x10, 0x9e, 0x40, 0x05]));
+        assert_eq!(values.audio_level, Some(AudioLevel { level: 30, voice: true }));
+        let orientation = values.orientation.unwrap();
+        assert_eq!(
+            (orientation.rotation, orientation.flip, orientation.back_camera),
+            (90, true, false)
+        );
+        assert!(map.parse(&packet(&[0xbe, 0xde, 0, 0])).is_empty());
+        assert!(ExtensionMap::new()
+            .parse(&packet(&[0xbe, 0xde, 0, 1, 0x10, 0x9e, 0, 0]))
+            .is_empty());
+    }
+}
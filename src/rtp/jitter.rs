@@ -0,0 +1,579 @@
+use super::queue::MAX_MISORDER;
+use super::{Packet, SequenceExtender};
+use crate::metrics::{Charge, Component, MemoryBudget};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// How often a missing packet is requested, see [`JitterBuffer::nack`]
+const MAX_NACKS: u32 = 3;
+/// Longest gap that is requested, longer ones are outages rather than loss
+const MAX_NACK_GAP: i64 = 256;
+
+/// What the jitter buffer hands out, in sequence order
+#[derive(Debug, PartialEq, Eq)]
+pub enum JitterOutput {
+    Packet(Packet),
+    /// `count` packets starting at `sequence_number` never arrived in
+    /// time, decoders should conceal them
+    Lost {
+        sequence_number: u16,
+        count: u64,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JitterStats {
+    pub released: u64,
+    pub lost: u64,
+    /// Packets that arrived after their slot was released or skipped
+    pub late: u64,
+    pub duplicates: u64,
+    /// Packets dropped because they exceeded the memory budget
+    pub over_budget: u64,
+    /// Retransmission requests, see [`JitterBuffer::nacks`]
+    pub nacked: u64,
+    /// Requested packets that arrived in time
+    pub recovered: u64,
+    /// Restarts of the sequence numbering and changes of the source
+    pub resets: u64,
+}
+
+/// Holds packets for a target delay and releases them in order.
+///
+/// The playout time of a packet is derived from its RTP timestamp relative
+/// to the first packet, so bursts and reordering within the delay are
+/// smoothed out. Once the playout time of a later packet has passed, the
+/// missing ones before it are reported as lost.
+///
+/// A packet that jumps back by more than the reorder window restarts the
+/// buffer once its successor follows it (RFC 3550 A.1), as does a packet
+/// of another SSRC.
+pub struct JitterBuffer {
+    clock_rate: u32,
+    delay: Duration,
+    max_len: usize,
+    extender: SequenceExtender,
+    packets: BTreeMap<i64, Packet>,
+    // Extended sequence number of the next packet to release
+    next: Option<i64>,
+    // Arrival time and RTP timestamp of the first packet
+    base: Option<(Instant, u32)>,
+    // Source of the buffered packets
+    ssrc: Option<u32>,
+    // A packet that jumped back, the numbering restarts once its successor
+    // follows it (RFC 3550 A.1, bad_seq)
+    probation: Option<Packet>,
+    stats: JitterStats,
+    // Bytes of the buffered packets charged to the budget
+    charge: Option<Charge>,
+    nack_interval: Option<Duration>,
+    // Missing packets with the time and count of their requests
+    nacked: BTreeMap<i64, (Instant, u32)>,
+}
+
+impl JitterBuffer {
+    pub fn new(clock_rate: u32, delay: Duration) -> Self {
+        Self {
+            clock_rate: clock_rate.max(1),
+            delay,
+            max_len: 1024,
+            extender: SequenceExtender::new(),
+            packets: BTreeMap::new(),
+            next: None,
+            base: None,
+            ssrc: None,
+            probation: None,
+            stats: JitterStats::default(),
+            charge: None,
+            nack_interval: None,
+            nacked: BTreeMap::new(),
+        }
+    }
+
+    /// Upper bound of buffered packets, the oldest are released early once it is reached
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len.max(1);
+        self
+    }
+
+    /// Charges the buffered packets to `budget`, packets exceeding it are dropped
+    pub fn budget(mut self, budget: MemoryBudget) -> Self {
+        self.charge = Some(Charge::new(budget, Component::Jitter));
+        self
+    }
+
+    /// Reports missing packets for retransmission requests (RFC 4585), see
+    /// [`JitterBuffer::nacks`]. A packet is requested again after `interval`,
+    /// e.g. the round-trip time, up to 3 times. The delay must leave room for
+    /// the retransmission to arrive.
+    pub fn nack(mut self, interval: Duration) -> Self {
+        self.nack_interval = Some(interval);
+        self
+    }
+
+    /// Changes the clock rate after a [`CodecChanged`](super::CodecChanged).
+    /// Playout times are derived from the next pushed packet, the buffered
+    /// packets become due immediately.
+    pub fn set_clock_rate(&mut self, clock_rate: u32) {
+        self.clock_rate = clock_rate.max(1);
+        self.base = None;
+    }
+
+    pub fn push(&mut self, packet: Packet, arrival: Instant) {
+        if self.ssrc.is_some_and(|ssrc| ssrc != packet.ssrc()) {
+            self.reset();
+            self.stats.resets += 1;
+        }
+        self.ssrc = Some(packet.ssrc());
+        let ext = self.extender.extend(packet.sequence_number());
+        if self.next.is_some_and(|next| next - ext > MAX_MISORDER) {
+            let Some(stray) = self
+                .probation
+                .take()
+                .filter(|stray| stray.sequence_number().wrapping_add(1) == packet.sequence_number())
+            else {
+                self.probation = Some(packet);
+                return;
+            };
+            self.reset();
+            self.stats.resets += 1;
+            self.push(stray, arrival);
+            self.push(packet, arrival);
+            return;
+        }
+        if self.base.is_none() {
+            self.base = Some((arrival, packet.timestamp()));
+        }
+        if self.next.is_some_and(|next| ext < next) {
+            self.stats.late += 1;
+            return;
+        }
+        if let Some(charge) = &mut self.charge {
+            if charge.resize(charge.charged() + packet.len()).is_err() {
+                self.stats.over_budget += 1;
+                return;
+            }
+        }
+        if self.nacked.remove(&ext).is_some() {
+            self.stats.recovered += 1;
+        }
+        if let Some(duplicate) = self.packets.insert(ext, packet) {
+            self.stats.duplicates += 1;
+            self.uncharge(duplicate.len());
+        }
+        if self.next.is_none() {
+            self.next = Some(ext);
+        }
+    }
+
+    /// Drops the buffered packets and starts over with the next pushed one,
+    /// e.g. after a [`StreamItem::Discontinuity`](crate::rtsp::client::StreamItem::Discontinuity)
+    /// of a reconnect. The stats are kept.
+    pub fn reset(&mut self) {
+        let buffered: usize = self.packets.values().map(Packet::len).sum();
+        self.uncharge(buffered);
+        self.packets.clear();
+        self.nacked.clear();
+        self.extender.reset();
+        self.next = None;
+        self.base = None;
+        self.ssrc = None;
+        self.probation = None;
+    }
+
+    /// Returns the next packet or loss marker that is due at `now`
+    pub fn pop(&mut self, now: Instant) -> Option<JitterOutput> {
+        let next = self.next?;
+        let (&ext, packet) = self.packets.first_key_value()?;
+        let due =
+            self.base.is_none() || self.playout_time(packet.timestamp()) <= now || self.packets.len() > self.max_len;
+        if !due {
+            return None;
+        }
+        if ext > next {
+            self.next = Some(ext);
+            self.stats.lost += (ext - next) as u64;
+            return Some(JitterOutput::Lost {
+                sequence_number: next as u16,
+                count: (ext - next) as u64,
+            });
+        }
+        self.next = Some(ext + 1);
+        self.stats.released += 1;
+        let (_, packet) = self.packets.pop_first()?;
+        self.uncharge(packet.len());
+        Some(JitterOutput::Packet(packet))
+    }
+
+    /// Sequence numbers of the missing packets that should be requested at
+    /// `now`, e.g. with a [`generic_nack`](crate::rtcp::generic_nack).
+    /// Always empty unless enabled with [`JitterBuffer::nack`].
+    pub fn nacks(&mut self, now: Instant) -> Vec<u16> {
+        let (Some(interval), Some(next), Some((&last, _))) =
+            (self.nack_interval, self.next, self.packets.last_key_value())
+        else {
+            return Vec::new();
+        };
+        // Slots that were released or skipped can't be filled anymore
+        self.nacked = self.nacked.split_off(&next);
+        let mut lost = Vec::new();
+        for ext in next.max(last - MAX_NACK_GAP)..last {
+            if self.packets.contains_key(&ext) {
+                continue;
+            }
+            let (sent, count) = self.nacked.entry(ext).or_insert((now, 0));
+            if *count == 0 || (*count < MAX_NACKS && now >= *sent + interval) {
+                *sent = now;
+                *count += 1;
+                lost.push(ext as u16);
+            }
+        }
+        self.stats.nacked += lost.len() as u64;
+        lost
+    }
+
+    /// When the next packet or loss marker becomes due, to sleep until then
+    pub fn next_release(&self) -> Option<Instant> {
+        let (_, packet) = self.packets.first_key_value()?;
+        Some(self.playout_time(packet.timestamp()))
+    }
+
+    /// The packets held back, in sequence order, e.g. for a consumer that
+    /// peeks ahead of the playout. Missing packets show as gaps in the
+    /// sequence numbers. Peeking leaves the buffer and its stats untouched.
+    pub fn buffered(&self) -> impl Iterator<Item = &Packet> {
+        self.packets.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        self.stats
+    }
+
+    fn uncharge(&mut self, n: usize) {
+        if let Some(charge) = &mut self.charge {
+            // Shrinking always succeeds
+            let _ = charge.resize(charge.charged() - n);
+        }
+    }
+
+    fn playout_time(&self, timestamp: u32) -> Instant {
+        let Some((base_arrival, base_timestamp)) = self.base else {
+            return Instant::now();
+        };
+        let ticks = timestamp.wrapping_sub(base_timestamp) as i32 as i64;
+        let offset = Duration::from_nanos(ticks.unsigned_abs() * 1_000_000_000 / self.clock_rate as u64);
+        let reference = base_arrival + self.delay;
+        if ticks >= 0 {
+            reference + offset
+        } else {
+            reference.checked_sub(offset).unwrap_or(base_arrival)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+    use crate::testing::{Impairment, ImpairmentStats, LossyQueue};
+
+    fn packet(seq: u16, timestamp: u32) -> Packet {
+        packet_of(0, seq, timestamp)
+    }
+
+    fn packet_of(ssrc: u32, seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(96, ssrc, seq, timestamp, false, &[])
+    }
+
+    fn sequence(output: Option<JitterOutput>) -> u16 {
+        match output {
+            Some(JitterOutput::Packet(p)) => p.sequence_number(),
+            other => panic!("expected packet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_jitter_buffer_holds_for_delay() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100));
+        buffer.push(packet(1, 0), start);
+        assert!(buffer.pop(start).is_none());
+        assert_eq!(buffer.next_release(), Some(start + Duration::from_millis(100)));
+        assert_eq!(sequence(buffer.pop(start + Duration::from_millis(100))), 1);
+    }
+
+    #[test]
+    fn test_jitter_buffer_peek() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100));
+        for seq in [1, 4, 2] {
+            buffer.push(packet(seq, seq as u32 * 3000), start);
+        }
+        let buffered: Vec<_> = buffer.buffered().map(|p| p.sequence_number()).collect();
+        assert_eq!(buffered, [1, 2, 4]);
+        assert_eq!(buffer.stats(), JitterStats::default());
+        // Peeking doesn't release anything
+        let later = start + Duration::from_secs(1);
+        assert_eq!(sequence(buffer.pop(later)), 1);
+        assert_eq!(sequence(buffer.pop(later)), 2);
+        assert_eq!(buffer.buffered().count(), 1);
+    }
+
+    #[test]
+    fn test_jitter_buffer_clock_rate_change() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100));
+        buffer.push(packet(1, 0), start);
+        buffer.set_clock_rate(8000);
+        assert_eq!(sequence(buffer.pop(start)), 1);
+        // Timestamps of the new clock are relative to the first packet after the change
+        let later = start + Duration::from_millis(20);
+        buffer.push(packet(2, 5_000_000), later);
+        buffer.push(packet(3, 5_000_800), later);
+        assert_eq!(buffer.next_release(), Some(later + Duration::from_millis(100)));
+        assert_eq!(sequence(buffer.pop(later + Duration::from_millis(100))), 2);
+        assert!(buffer.pop(later + Duration::from_millis(150)).is_none());
+        assert_eq!(sequence(buffer.pop(later + Duration::from_millis(200))), 3);
+    }
+
+    #[test]
+    fn test_jitter_buffer_reorders() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(50));
+        buffer.push(packet(1, 0), start);
+        buffer.push(packet(3, 1800), start + Duration::from_millis(5));
+        buffer.push(packet(2, 900), start + Duration::from_millis(30));
+        let later = start + Duration::from_millis(200);
+        assert_eq!(sequence(buffer.pop(later)), 1);
+        assert_eq!(sequence(buffer.pop(later)), 2);
+        assert_eq!(sequence(buffer.pop(later)), 3);
+        assert!(buffer.pop(later).is_none());
+    }
+
+    #[test]
+    fn test_jitter_buffer_loss_marker() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(50));
+        buffer.push(packet(65534, 0), start);
+        buffer.push(packet(1, 2700), start);
+        let now = start + Duration::from_millis(50);
+        assert_eq!(sequence(buffer.pop(now)), 65534);
+        // 65535 and 0 are still awaited until the playout time of 1
+        assert!(buffer.pop(now).is_none());
+        let now = start + Duration::from_millis(80);
+        assert_eq!(
+            buffer.pop(now),
+            Some(JitterOutput::Lost {
+                sequence_number: 65535,
+                count: 2
+            })
+        );
+        assert_eq!(sequence(buffer.pop(now)), 1);
+        buffer.push(packet(0, 1800), now);
+        assert_eq!(buffer.stats().late, 1);
+        assert_eq!(buffer.stats().lost, 2);
+    }
+
+    #[test]
+    fn test_jitter_buffer_nacks() {
+        let start = Instant::now();
+        let rtt = Duration::from_millis(20);
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100)).nack(rtt);
+        buffer.push(packet(65534, 0), start);
+        assert!(buffer.nacks(start).is_empty());
+        buffer.push(packet(2, 3600), start);
+        assert_eq!(buffer.nacks(start), [65535, 0, 1]);
+        // Requested again after the interval, not before
+        assert!(buffer.nacks(start + Duration::from_millis(10)).is_empty());
+        buffer.push(packet(0, 1800), start + Duration::from_millis(15));
+        assert_eq!(buffer.nacks(start + rtt), [65535, 1]);
+        assert_eq!(buffer.nacks(start + rtt * 2), [65535, 1]);
+        assert!(buffer.nacks(start + rtt * 3).is_empty());
+        let stats = buffer.stats();
+        assert_eq!((stats.nacked, stats.recovered), (7, 1));
+    }
+
+    /// Arrivals of 40 packets sent every 33 ms through `impairment`
+    fn impaired(impairment: Impairment, start: Instant) -> (Vec<(Instant, Packet)>, ImpairmentStats) {
+        let mut queue = LossyQueue::new(impairment);
+        let mut arrivals = Vec::new();
+        for seq in 1..=40u16 {
+            let sent = start + Duration::from_millis(33 * seq as u64);
+            queue.push(packet(seq, seq as u32 * 3000));
+            if seq == 40 {
+                queue.flush();
+            }
+            while let Some((delay, packet)) = queue.pop() {
+                arrivals.push((sent + delay, packet));
+            }
+        }
+        (arrivals, queue.stats())
+    }
+
+    fn outputs(buffer: &mut JitterBuffer, now: Instant) -> Vec<(u16, u64)> {
+        let mut outputs = Vec::new();
+        while let Some(output) = buffer.pop(now) {
+            outputs.push(match output {
+                JitterOutput::Packet(p) => (p.sequence_number(), 0),
+                JitterOutput::Lost { sequence_number, count } => (sequence_number, count),
+            });
+        }
+        outputs
+    }
+
+    #[test]
+    fn test_jitter_buffer_impaired() {
+        let start = Instant::now();
+        let impairment = Impairment::new(4)
+            .drop(0.1)
+            .reorder(0.2)
+            .duplicate(0.1)
+            .delay(Duration::from_millis(20));
+        let (arrivals, impaired) = impaired(impairment, start);
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100));
+        let mut released = Vec::new();
+        for (arrival, packet) in arrivals {
+            buffer.push(packet, arrival);
+            released.extend(outputs(&mut buffer, arrival));
+        }
+        released.extend(outputs(&mut buffer, start + Duration::from_secs(10)));
+        let expected = ImpairmentStats {
+            delivered: 37,
+            dropped: 5,
+            duplicated: 2,
+            reordered: 6,
+        };
+        assert_eq!(impaired, expected);
+        // Reordering and duplicates are absorbed, every drop is a loss marker
+        assert!(released.iter().map(|(seq, _)| *seq).eq(1..=40));
+        let lost: Vec<_> = released.into_iter().filter(|(_, count)| *count > 0).collect();
+        assert_eq!(lost, [(4, 1), (6, 1), (13, 1), (23, 1), (37, 1)]);
+        let stats = buffer.stats();
+        assert_eq!(
+            (stats.released, stats.lost, stats.late, stats.duplicates),
+            (35, 5, 0, 2)
+        );
+    }
+
+    #[test]
+    fn test_jitter_buffer_impaired_nacks() {
+        let start = Instant::now();
+        let impairment = Impairment::new(4)
+            .drop(0.1)
+            .reorder(0.2)
+            .duplicate(0.1)
+            .delay(Duration::from_millis(20));
+        let (arrivals, _) = impaired(impairment, start);
+        let rtt = Duration::from_millis(10);
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(100)).nack(rtt);
+        let mut retransmissions = Vec::new();
+        let mut released = Vec::new();
+        for (arrival, received) in arrivals {
+            // The retransmissions arrive a round trip after the request
+            retransmissions.sort_by_key(|(at, _): &(Instant, u16)| *at);
+            while let Some(&(at, seq)) = retransmissions.first().filter(|(at, _)| *at <= arrival) {
+                retransmissions.remove(0);
+                buffer.push(packet(seq, seq as u32 * 3000), at);
+                released.extend(outputs(&mut buffer, at));
+            }
+            buffer.push(received, arrival);
+            for seq in buffer.nacks(arrival) {
+                retransmissions.push((arrival + rtt, seq));
+            }
+            released.extend(outputs(&mut buffer, arrival));
+        }
+        released.extend(outputs(&mut buffer, start + Duration::from_secs(10)));
+        // Every gap is repaired in time, the requests for packets that were
+        // only reordered bring duplicates
+        assert_eq!(released, (1..=40).map(|seq| (seq, 0)).collect::<Vec<_>>());
+        let stats = buffer.stats();
+        assert_eq!(
+            (stats.released, stats.lost, stats.nacked, stats.recovered),
+            (40, 0, 11, 11)
+        );
+        assert_eq!(stats.duplicates, 8);
+    }
+
+    #[test]
+    fn test_jitter_buffer_max_len() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_secs(1)).max_len(2);
+        for seq in 0..3 {
+            buffer.push(packet(seq, 0), start);
+        }
+        assert_eq!(sequence(buffer.pop(start)), 0);
+        assert!(buffer.pop(start).is_none());
+    }
+
+    #[test]
+    fn test_jitter_buffer_budget() {
+        let start = Instant::now();
+        let budget = MemoryBudget::new(30);
+        let mut buffer = JitterBuffer::new(90_000, Duration::ZERO).budget(budget.clone());
+        buffer.push(packet(1, 0), start);
+        buffer.push(packet(2, 0), start);
+        buffer.push(packet(3, 0), start);
+        assert_eq!(buffer.stats().over_budget, 1);
+        assert_eq!(budget.usage().jitter, 24);
+        assert_eq!(sequence(buffer.pop(start)), 1);
+        assert_eq!(budget.usage().jitter, 12);
+        drop(buffer);
+        assert_eq!(budget.usage().used, 0);
+    }
+
+    #[test]
+    fn test_jitter_buffer_restart() {
+        let start = Instant::now();
+        let mut buffer = JitterBuffer::new(90_000, Duration::ZERO);
+        for seq in 1000..1010 {
+            buffer.push(packet(seq, 0), start);
+            assert_eq!(sequence(buffer.pop(start)), seq);
+        }
+        // A single stray packet doesn't restart the numbering, two sequential ones do
+        buffer.push(packet(500, 0), start);
+        buffer.push(packet(5, 90_000), start);
+        assert!(buffer.is_empty());
+        buffer.push(packet(6, 90_000), start);
+        assert_eq!(sequence(buffer.pop(start)), 5);
+        assert_eq!(sequence(buffer.pop(start)), 6);
+        for seq in 7..507 {
+            buffer.push(packet(seq, 90_000), start);
+            assert_eq!(sequence(buffer.pop(start)), seq);
+        }
+        let stats = buffer.stats();
+        assert_eq!((stats.released, stats.late, stats.resets), (512, 0, 1));
+    }
+
+    #[test]
+    fn test_jitter_buffer_ssrc_change() {
+        let start = Instant::now();
+        let budget = MemoryBudget::new(1024);
+        let mut buffer = JitterBuffer::new(90_000, Duration::from_millis(50)).budget(budget.clone());
+        buffer.push(packet_of(1, 100, 0), start);
+        buffer.push(packet_of(1, 102, 6000), start);
+        // The new source starts its own numbering and timeline
+        let later = start + Duration::from_millis(10);
+        buffer.push(packet_of(2, 7, 123_456), later);
+        assert_eq!(buffer.stats().resets, 1);
+        assert_eq!(budget.usage().jitter, 12);
+        assert!(buffer.pop(later).is_none());
+        assert_eq!(sequence(buffer.pop(later + Duration::from_millis(50))), 7);
+        buffer.push(packet_of(2, 8, 126_456), later);
+        assert_eq!(buffer.stats().late, 0);
+
+        buffer.reset();
+        assert!(buffer.is_empty());
+        assert_eq!(budget.usage().jitter, 0);
+        buffer.push(packet_of(2, 1, 0), later);
+        assert_eq!(sequence(buffer.pop(later + Duration::from_millis(50))), 1);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::codec;
+use crate::sdp::Codec;
+use crate::types::FrameType;
+
+const H264_STAP_A: u8 = 24;
+const H264_FU_A: u8 = 28;
+const H265_AP: u8 = 48;
+const H265_FU: u8 = 49;
+
+/// Whether the RTP payload starts a frame decoding can start at, without
+/// depacketizing it. For H.264 and H.265 that is a packet with an IDR or
+/// IRAP NAL unit, or the first fragment of one. Frames of other codecs
+/// don't depend on earlier ones, so every packet qualifies.
+pub fn starts_keyframe(codec: &Codec, payload: &[u8]) -> bool {
+    match codec {
+        Codec::H264 => starts_h264_keyframe(payload),
+        Codec::H265 => starts_h265_keyframe(payload),
+        _ => true,
+    }
+}
+
+fn starts_h264_keyframe(payload: &[u8]) -> bool {
+    let Some(header) = payload.first() else {
+        return false;
+    };
+    match header & 0x1f {
+        H264_STAP_A => aggregated_nals(&payload[1..]).any(|nal| codec::is_random_access(FrameType::H264, nal)),
+        // Only the start fragment carries the beginning of the NAL unit
+        H264_FU_A => payload.get(1).is_some_and(|fu| fu & 0x80 != 0 && fu & 0x1f == 5),
+        _ => codec::is_random_access(FrameType::H264, payload),
+    }
+}
+
+fn starts_h265_keyframe(payload: &[u8]) -> bool {
+    let Some(header) = payload.first() else {
+        return false;
+    };
+    match (header >> 1) & 0x3f {
+        H265_AP => aggregated_nals(payload.get(2..).unwrap_or_default())
+            .any(|nal| codec::is_random_access(FrameType::H265, nal)),
+        H265_FU => payload
+            .get(2)
+            .is_some_and(|fu| fu & 0x80 != 0 && matches!(fu & 0x3f, 16..=21)),
+        _ => codec::is_random_access(FrameType::H265, payload),
+    }
+}
+
+/// NAL units of an aggregation packet, each preceded by its 16 bit size
+fn aggregated_nals(mut data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let size = u16::from_be_bytes([*data.first()?, *data.get(1)?]) as usize;
+        let nal = data.get(2..2 + size)?;
+        data = &data[2 + size..];
+        Some(nal)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_keyframe() {
+        // IDR slice, non-IDR slice, and FU-A start and middle fragments of an IDR slice
+        assert!(starts_keyframe(&Codec::H264, &[0x65, 0x88]));
+        assert!(!starts_keyframe(&Codec::H264, &[0x41, 0x9a]));
+        assert!(starts_keyframe(&Codec::H264, &[0x7c, 0x85, 0x88]));
+        assert!(!starts_keyframe(&Codec::H264, &[0x7c, 0x05, 0x88]));
+        // STAP-A with SPS and PPS only, then with an IDR slice
+        assert!(!starts_keyframe(&Codec::H264, &[0x78, 0, 1, 0x67, 0, 1, 0x68]));
+        assert!(starts_keyframe(&Codec::H264, &[0x78, 0, 1, 0x67, 0, 2, 0x65, 0x88]));
+        // H.265 IDR_W_RADL, TRAIL_R and an FU start fragment of a CRA picture
+        assert!(starts_keyframe(&Codec::H265, &[0x26, 0x01, 0xaf]));
+        assert!(!starts_keyframe(&Codec::H265, &[0x02, 0x01, 0xd0]));
+        assert!(starts_keyframe(&Codec::H265, &[0x62, 0x01, 0x95, 0xaf]));
+        assert!(!starts_keyframe(&Codec::H264, &[]));
+        assert!(starts_keyframe(&Codec::PCMU, &[0xff]));
+    }
+}
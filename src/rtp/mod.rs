@@ -1,6 +1,61 @@
+mod builder;
+mod bus;
+mod demux;
+mod depacketizer;
+mod extension;
+mod jitter;
+mod keyframe;
 mod packet;
+mod packetizer;
 mod queue;
+mod rewrite;
+mod rtx;
+mod stream;
+mod track;
 
-pub use packet::Packet as Packet;
+pub use builder::random_ssrc;
+pub use builder::PacketBuilder;
+pub use depacketizer::new_depacketizer;
+pub use depacketizer::new_depacketizer_for;
+pub use depacketizer::AacDepacketizer;
+pub use depacketizer::BudgetedDepacketizer;
+pub use depacketizer::CodecChanged;
+pub use depacketizer::Depacketizer;
+pub use depacketizer::Error as DepacketizerError;
+pub use depacketizer::G711Depacketizer;
+pub use depacketizer::H264Depacketizer;
+pub use depacketizer::SwitchingDepacketizer;
+pub use jitter::JitterBuffer;
+pub use jitter::JitterOutput;
+pub use jitter::JitterStats;
 pub use packet::Error as PacketError;
-pub use queue::ReorderQueue as ReorderQueue;
+pub use packet::Packet;
+pub use queue::ReorderQueue;
+pub use queue::ReorderStats;
+pub use queue::SequenceExtender;
+pub use rewrite::Rewriter;
+pub use rtx::RtxMapper;
+pub use stream::FrameStream;
+pub use stream::TimedFrame;
+pub use stream::DEFAULT_FRAME_DELAY;
+pub use track::Delivery;
+pub use track::TrackPacket;
+pub use track::TrackQueue;
+pub use demux::DemuxedPacket;
+pub use demux::Demuxer;
+pub use keyframe::starts_keyframe;
+pub use extension::AudioLevel;
+pub use extension::ExtensionMap;
+pub use extension::ExtensionValues;
+pub use extension::HeaderExtension;
+pub use extension::VideoOrientation;
+pub use extension::AUDIO_LEVEL_URI;
+pub use extension::VIDEO_ORIENTATION_URI;
+pub use packetizer::Packetizer;
+pub use packetizer::DEFAULT_MAX_PAYLOAD;
+pub use bus::FrameBus;
+pub use bus::FrameSubscriber;
+pub use bus::SubscriberStats;
+pub use bus::DEFAULT_BUS_CAPACITY;
+pub use demux::DEFAULT_MAX_SOURCES;
+pub use demux::DEFAULT_SOURCE_TIMEOUT;
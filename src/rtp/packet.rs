@@ -1,9 +1,13 @@
+use super::HeaderExtension;
+use bytes::Bytes;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Buffer too short to be an RTP packet")]
     BufferTooShort,
+    #[error("Padding length exceeds the RTP packet")]
+    InvalidPadding,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -23,19 +27,30 @@ pub type Result<T> = std::result::Result<T, Error>;
 +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 */
 
-#[derive(PartialEq, Eq)]
-
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Packet {
-    buf: Vec<u8>,
+    buf: Bytes,
 }
 
 impl Packet {
     const CSRC_OFFSET: u32 = 12;
-    pub fn new(buf: Vec<u8>) -> Result<Packet> {
-        let packet = Packet { buf };
-        if packet.len() < 12 || packet.len() < packet.data_offset() as usize {
+    /// Takes the buffer without copying, a `Vec<u8>` or a slice of a
+    /// shared receive buffer
+    pub fn new(buf: impl Into<Bytes>) -> Result<Packet> {
+        let packet = Packet { buf: buf.into() };
+        if packet.len() < 12 || packet.len() < packet.extension_offset() + 4 * packet.extension() as usize {
+            return Err(Error::BufferTooShort);
+        }
+        if packet.len() < packet.data_offset() as usize {
             return Err(Error::BufferTooShort);
         }
+        if packet.padding() {
+            // The last octet counts the padding including itself
+            let padding_len = packet.buf[packet.len() - 1] as usize;
+            if padding_len == 0 || packet.data_offset() as usize + padding_len > packet.len() {
+                return Err(Error::InvalidPadding);
+            }
+        }
         Ok(packet)
     }
 
@@ -79,8 +94,54 @@ impl Packet {
         self.buf.len()
     }
 
+    /// The whole packet including the header, as sent on the wire
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// The whole packet as a shared buffer, cloning it doesn't copy
+    pub fn bytes(&self) -> &Bytes {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.buf
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Length of the fixed header, the CSRC list and the header extension
+    pub fn header_len(&self) -> usize {
+        self.data_offset() as usize
+    }
+
+    /// The header extension if the extension bit is set
+    pub fn header_extension(&self) -> Option<HeaderExtension<'_>> {
+        if !self.extension() {
+            return None;
+        }
+        let offset = self.extension_offset();
+        let profile = u16::from_be_bytes([self.buf[offset], self.buf[offset + 1]]);
+        Some(HeaderExtension::new(
+            profile,
+            &self.buf[offset + 4..self.data_offset() as usize],
+        ))
+    }
+
+    fn extension_offset(&self) -> usize {
+        Packet::CSRC_OFFSET as usize + self.csrc_count() as usize * 4
+    }
+
     fn data_offset(&self) -> u32 {
-        Packet::CSRC_OFFSET + (self.csrc_count() * 4) as u32
+        let offset = self.extension_offset();
+        if !self.extension() {
+            return offset as u32;
+        }
+        // The extension length counts 32 bit words after its own header
+        let words = u16::from_be_bytes([self.buf[offset + 2], self.buf[offset + 3]]);
+        (offset + 4 + words as usize * 4) as u32
     }
 
     pub fn data(&self) -> &[u8] {
@@ -92,6 +153,12 @@ impl Packet {
         }
     }
 
+    /// The payload as a shared buffer, see [`Packet::data`]
+    pub fn data_bytes(&self) -> Bytes {
+        let data = self.data();
+        self.buf.slice_ref(data)
+    }
+
     pub fn csrc(&self) -> Vec<u32> {
         let mut csrc = Vec::new();
         for i in 0..self.csrc_count() {
@@ -109,7 +176,7 @@ impl Packet {
 
 impl PartialOrd for Packet {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(other.sequence_number().cmp(&self.sequence_number()))
+        Some(self.cmp(other))
     }
 }
 
@@ -135,10 +202,10 @@ mod tests {
         ];
         let packet = Packet::new(packet).unwrap();
         assert_eq!(packet.version(), 2);
-        assert_eq!(packet.padding(), false);
-        assert_eq!(packet.extension(), false);
+        assert!(!packet.padding());
+        assert!(!packet.extension());
         assert_eq!(packet.csrc_count(), 0);
-        assert_eq!(packet.marker(), false);
+        assert!(!packet.marker());
         assert_eq!(packet.payload_type(), 96);
         assert_eq!(packet.sequence_number(), 23);
         assert_eq!(packet.timestamp(), 0);
@@ -146,4 +213,43 @@ mod tests {
         assert_eq!(packet.len(), 12);
         assert_eq!(packet.data().len(), 0);
     }
+
+    #[test]
+    fn test_packet_shares_buffer() {
+        let buf = Bytes::from_static(&[0x80, 0x60, 0x00, 0x17, 0, 0, 0, 0, 0, 0, 0, 0, 0xab, 0xcd]);
+        let packet = Packet::new(buf.clone()).unwrap();
+        assert_eq!(packet.bytes().as_ptr(), buf.as_ptr());
+        let data = packet.data_bytes();
+        assert_eq!(&data[..], &[0xab, 0xcd]);
+        assert_eq!(data.as_ptr(), buf[12..].as_ptr());
+    }
+
+    #[test]
+    fn test_packet_extension() {
+        let mut buf = vec![0x90, 0x60, 0x00, 0x17, 0, 0, 0, 0, 0, 0, 0, 0];
+        buf.extend_from_slice(&[0xbe, 0xde, 0x00, 0x01, 0x10, 0xff, 0x00, 0x00, 0xab]);
+        let packet = Packet::new(buf.clone()).unwrap();
+        assert_eq!(packet.header_len(), 20);
+        assert_eq!(packet.data(), &[0xab]);
+        let extension = packet.header_extension().unwrap();
+        assert_eq!((extension.profile, extension.data), (0xbede, &[0x10, 0xff, 0, 0][..]));
+
+        // Extension length beyond the packet, and a missing extension header
+        buf[15] = 2;
+        assert!(matches!(Packet::new(buf.clone()), Err(Error::BufferTooShort)));
+        assert!(matches!(Packet::new(buf[..14].to_vec()), Err(Error::BufferTooShort)));
+    }
+
+    #[test]
+    fn test_packet_padding() {
+        let mut buf = vec![0xa0, 0x60, 0x00, 0x17, 0, 0, 0, 0, 0, 0, 0, 0, 0xab, 0, 0, 3];
+        assert_eq!(Packet::new(buf.clone()).unwrap().data(), &[0xab]);
+
+        // Padding longer than the payload or of length 0
+        buf[15] = 5;
+        assert!(matches!(Packet::new(buf.clone()), Err(Error::InvalidPadding)));
+        buf[15] = 0;
+        assert!(matches!(Packet::new(buf.clone()), Err(Error::InvalidPadding)));
+        assert!(matches!(Packet::new(buf[..12].to_vec()), Err(Error::InvalidPadding)));
+    }
 }
@@ -0,0 +1,153 @@
+use super::{Packet, PacketBuilder};
+use crate::codec::NalUnits;
+use crate::types::{Frame, FrameType};
+
+/// Largest RTP payload unless set with [`Packetizer::max_payload`], a
+/// packet with IP, UDP and RTP headers fits an Ethernet MTU
+pub const DEFAULT_MAX_PAYLOAD: usize = 1400;
+
+const FU_A: u8 = 28;
+
+/// Splits frames into the RTP packets of one source, the reverse of the
+/// depacketizers.
+///
+/// H.264 frames in Annex B format are sent as single NAL unit packets and
+/// FU-A fragments (RFC 6184, packetization-mode=1). AAC frames without ADTS
+/// header are sent one per packet in AAC-hbr mode (RFC 3640) with 13 bit
+/// sizes and 3 bit indexes. Frames of other codecs are sent as they are.
+/// The frame timestamps are passed to the [`PacketBuilder`] as ticks.
+#[derive(Debug, Clone)]
+pub struct Packetizer {
+    frame_type: FrameType,
+    builder: PacketBuilder,
+    max_payload: usize,
+}
+
+impl Packetizer {
+    pub fn new(frame_type: FrameType, builder: PacketBuilder) -> Self {
+        Self {
+            frame_type,
+            builder,
+            max_payload: DEFAULT_MAX_PAYLOAD,
+        }
+    }
+
+    /// Largest payload of a packet, larger NAL units are fragmented
+    pub fn max_payload(mut self, max: usize) -> Self {
+        self.max_payload = max.max(16);
+        self
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.builder.ssrc()
+    }
+
+    pub fn packetize(&mut self, frame: &Frame) -> Vec<Packet> {
+        match self.frame_type {
+            FrameType::H264 => self.packetize_h264(frame),
+            FrameType::AAC => {
+                // AU-headers-length in bits, then the size of the single access unit
+                let size = (frame.data.len().min(0x1fff) as u16) << 3;
+                let mut payload = Vec::with_capacity(4 + frame.data.len());
+                payload.extend_from_slice(&16u16.to_be_bytes());
+                payload.extend_from_slice(&size.to_be_bytes());
+                payload.extend_from_slice(&frame.data);
+                vec![self.builder.build(&payload, frame.timestamp, true)]
+            }
+            _ => vec![self.builder.build(&frame.data, frame.timestamp, true)],
+        }
+    }
+
+    fn packetize_h264(&mut self, frame: &Frame) -> Vec<Packet> {
+        // Payloads first, the marker bit goes on the last packet of the frame
+        let mut payloads = Vec::new();
+        for nal in NalUnits::new(&frame.data) {
+            if nal.len() <= self.max_payload {
+                payloads.push(nal.to_vec());
+                continue;
+            }
+            let indicator = (nal[0] & 0xe0) | FU_A;
+            let chunks: Vec<_> = nal[1..].chunks(self.max_payload - 2).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let mut header = nal[0] & 0x1f;
+                if i == 0 {
+                    header |= 0x80;
+                }
+                if i == chunks.len() - 1 {
+                    header |= 0x40;
+                }
+                let mut payload = Vec::with_capacity(2 + chunk.len());
+                payload.extend_from_slice(&[indicator, header]);
+                payload.extend_from_slice(chunk);
+                payloads.push(payload);
+            }
+        }
+        let last = payloads.len().saturating_sub(1);
+        payloads
+            .iter()
+            .enumerate()
+            .map(|(i, payload)| self.builder.build(payload, frame.timestamp, i == last))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::{Depacketizer, H264Depacketizer};
+    use crate::types::{FrameMetadata, MediaType};
+
+    #[test]
+    fn test_packetize_h264() {
+        let mut idr = vec![0x65];
+        idr.extend((0..3000).map(|i| i as u8));
+        let mut data = vec![
+            0, 0, 0, 1, 0x67, 0x42, 0, 0x1e, 0, 0, 0, 1, 0x68, 0xce, 6, 0xe2, 0, 0, 0, 1,
+        ];
+        data.extend_from_slice(&idr);
+        let frame = Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp: 3000,
+            data,
+            metadata: FrameMetadata::default(),
+        };
+        let builder = PacketBuilder::new(96, 5).sequence_number(1).timestamp_offset(0);
+        let mut packetizer = Packetizer::new(FrameType::H264, builder);
+        let packets = packetizer.packetize(&frame);
+        // SPS, PPS and the IDR slice in three fragments
+        assert_eq!(packets.len(), 5);
+        assert!(packets
+            .iter()
+            .all(|p| p.timestamp() == 3000 && p.data().len() <= DEFAULT_MAX_PAYLOAD));
+        assert_eq!(packets.iter().filter(|p| p.marker()).count(), 1);
+        assert!(packets[4].marker());
+        assert_eq!(&packets[2].data()[..2], &[0x7c, 0x85]);
+
+        let mut depacketizer = H264Depacketizer::new();
+        for packet in &packets {
+            depacketizer.push(packet).unwrap();
+        }
+        let depacketized = depacketizer.pop().unwrap();
+        assert_eq!(depacketized.data, frame.data);
+        assert_eq!(depacketized.timestamp, 3000);
+    }
+
+    #[test]
+    fn test_packetize_aac() {
+        let frame = Frame {
+            media_type: MediaType::Audio,
+            frame_type: FrameType::AAC,
+            timestamp: 1024,
+            data: vec![0x21; 300],
+            metadata: FrameMetadata::default(),
+        };
+        let mut packetizer = Packetizer::new(FrameType::AAC, PacketBuilder::new(97, 5).timestamp_offset(0));
+        let packets = packetizer.packetize(&frame);
+        assert_eq!(packets.len(), 1);
+        // 16 bits of AU headers, the size 300 shifted by the index length
+        assert_eq!(&packets[0].data()[..4], &[0, 16, 0x09, 0x60]);
+        assert_eq!(packets[0].data().len(), 304);
+        assert!(packets[0].marker());
+    }
+}
@@ -1,27 +1,96 @@
 use super::Packet;
-use std::collections::BinaryHeap;
+use crate::trace;
+use std::collections::BTreeMap;
+
+/// Packets older than this are not considered reordered anymore but a
+/// restart of the sequence numbering once their successor follows them
+/// (RFC 3550 A.1, MAX_MISORDER)
+pub(super) const MAX_MISORDER: i64 = 100;
+
+/// Extends 16 bit RTP sequence numbers with a roll-over counter, using
+/// serial number arithmetic to tell wrapped numbers from late ones.
+#[derive(Debug, Default, Clone)]
+pub struct SequenceExtender {
+    highest: Option<i64>,
+}
+
+impl SequenceExtender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the extended sequence number, which may be lower than the
+    /// first one seen (even negative) for late packets.
+    pub fn extend(&mut self, seq: u16) -> i64 {
+        let ext = match self.highest {
+            None => seq as i64,
+            Some(highest) => highest + seq.wrapping_sub(highest as u16) as i16 as i64,
+        };
+        if self.highest.is_none_or(|h| ext > h) {
+            self.highest = Some(ext);
+        }
+        ext
+    }
+
+    /// Highest extended sequence number in the 32 bit form of RTCP
+    /// reception reports (cycles in the upper 16 bits)
+    pub fn highest(&self) -> Option<u32> {
+        self.highest.map(|h| h as u32)
+    }
+
+    pub fn reset(&mut self) {
+        self.highest = None;
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReorderStats {
+    /// Packets that were skipped because the queue was full
+    pub lost: u64,
+    /// Packets that arrived after their successor was released
+    pub late: u64,
+    pub duplicates: u64,
+    /// Restarts of the sequence numbering
+    pub resets: u64,
+}
 
 pub struct ReorderQueue {
-    queue: BinaryHeap<Packet>,
+    queue: BTreeMap<i64, Packet>,
     max_len: usize,
-    last_read_sn: u16,
+    extender: SequenceExtender,
+    // Extended sequence number of the next packet to release, None until the first packet
+    next: Option<i64>,
+    // A packet that jumped back, the numbering restarts once its successor
+    // follows it (RFC 3550 A.1, bad_seq)
+    probation: Option<Packet>,
+    stats: ReorderStats,
 }
 
 impl ReorderQueue {
     pub fn new(max_len: usize) -> Self {
         Self {
-            queue: BinaryHeap::new(),
+            queue: BTreeMap::new(),
             max_len,
-            last_read_sn: 0,
+            extender: SequenceExtender::new(),
+            next: None,
+            probation: None,
+            stats: ReorderStats::default(),
         }
     }
 
     pub fn pop(&mut self) -> Option<Packet> {
-        if let Some(packet) = self.queue.peek() {
-            if packet.sequence_number() == self.last_read_sn + 1 || self.queue.len() >= self.max_len {
-                self.last_read_sn = packet.sequence_number();
-                return self.queue.pop();
+        let (&ext, _) = self.queue.first_key_value()?;
+        let next = self.next?;
+        if ext == next || self.queue.len() >= self.max_len {
+            if ext > next {
+                #[cfg(not(feature = "tracing"))]
+                log::debug!("Lost {} packets", ext - next);
+                trace::event!(debug, lost = ext - next, "Lost RTP packets");
+                self.stats.lost += (ext - next) as u64;
             }
+            self.next = Some(ext + 1);
+            return self.queue.pop_first().map(|(_, packet)| packet);
         }
         None
     }
@@ -29,61 +98,160 @@ impl ReorderQueue {
     /// pushes a packet to the queue if it is not too old
     /// or returns the packet again if it is the next in line
     pub fn push_or_return(&mut self, packet: Packet) -> Option<Packet> {
-        if self.last_read_sn == 0 || packet.sequence_number() == self.last_read_sn + 1 {
-            self.last_read_sn = packet.sequence_number();
-            Some(packet)
-        } else if packet.sequence_number() < self.last_read_sn {
-            log::warn!("Packet too old, discarding");
-            None
-        } else {
-            self.queue.push(packet);
-            None
+        let ext = self.extender.extend(packet.sequence_number());
+        match self.next {
+            Some(next) if next - ext > MAX_MISORDER => {
+                let Some(stray) = self
+                    .probation
+                    .take()
+                    .filter(|stray| stray.sequence_number().wrapping_add(1) == packet.sequence_number())
+                else {
+                    self.probation = Some(packet);
+                    return None;
+                };
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("Sequence number jumped back by {}, resetting", next - ext);
+                trace::event!(warn, jump = next - ext, "RTP sequence number jumped back");
+                self.reset();
+                self.stats.resets += 1;
+                let first = self.extender.extend(stray.sequence_number());
+                let second = self.extender.extend(packet.sequence_number());
+                self.next = Some(first + 1);
+                // Released by the next pop
+                self.queue.insert(second, packet);
+                Some(stray)
+            }
+            Some(next) if ext < next => {
+                #[cfg(not(feature = "tracing"))]
+                log::debug!("Packet too old, discarding");
+                trace::event!(debug, seq = packet.sequence_number(), "Discarding late RTP packet");
+                self.stats.late += 1;
+                None
+            }
+            Some(next) if ext > next => {
+                if self.queue.insert(ext, packet).is_some() {
+                    self.stats.duplicates += 1;
+                }
+                None
+            }
+            _ => {
+                self.next = Some(ext + 1);
+                Some(packet)
+            }
         }
     }
+
+    /// Highest extended sequence number received so far
+    pub fn highest_sequence(&self) -> Option<u32> {
+        self.extender.highest()
+    }
+
+    pub fn stats(&self) -> ReorderStats {
+        self.stats
+    }
+
+    /// The packets waiting for a missing predecessor, in sequence order
+    pub fn buffered(&self) -> impl Iterator<Item = &Packet> {
+        self.queue.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.queue.clear();
+        self.extender.reset();
+        self.next = None;
+        self.probation = None;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::rtp_packet;
     #[tokio::test]
     async fn test_reorder_queue() {
-        // Create buffer with 5 rtp packets (with 2 byte size prefix)
-        // ts and ssrc are set to 0
-        // sequence number is set to 23, 25, 27, 24, 26
-        let mut packet_bufs = vec![
-            vec![0x80, 0x60, 0x00, 0x17, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // seq 23
-            vec![0x80, 0x60, 0x00, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // seq 25
-            vec![0x80, 0x60, 0x00, 0x1B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // seq 27
-            vec![0x80, 0x60, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // seq 24
-            vec![0x80, 0x60, 0x00, 0x1A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // seq 26
-        ];
         let mut reorder_queue = ReorderQueue::new(5);
-        assert_eq!(
-            reorder_queue
-                .push_or_return(Packet::new(packet_bufs.remove(0)).unwrap())
-                .unwrap()
-                .sequence_number(),
-            23
-        );
-        assert!(reorder_queue
-            .push_or_return(Packet::new(packet_bufs.remove(0)).unwrap())
-            .is_none());
-        assert!(reorder_queue
-            .push_or_return(Packet::new(packet_bufs.remove(0)).unwrap())
-            .is_none());
-        assert_eq!(
-            reorder_queue
-                .push_or_return(Packet::new(packet_bufs.remove(0)).unwrap())
-                .unwrap()
-                .sequence_number(),
-            24
-        );
-        assert!(reorder_queue
-            .push_or_return(Packet::new(packet_bufs.remove(0)).unwrap())
-            .is_none());
+        assert_eq!(reorder_queue.push_or_return(packet(23)).unwrap().sequence_number(), 23);
+        assert!(reorder_queue.push_or_return(packet(25)).is_none());
+        assert!(reorder_queue.push_or_return(packet(27)).is_none());
+        assert_eq!(reorder_queue.push_or_return(packet(24)).unwrap().sequence_number(), 24);
+        assert!(reorder_queue.push_or_return(packet(26)).is_none());
         assert_eq!(reorder_queue.pop().unwrap().sequence_number(), 25);
         assert_eq!(reorder_queue.pop().unwrap().sequence_number(), 26);
         assert_eq!(reorder_queue.pop().unwrap().sequence_number(), 27);
         assert!(reorder_queue.pop().is_none());
     }
+
+    fn packet(seq: u16) -> Packet {
+        rtp_packet(96, 0, seq, 0, false, &[])
+    }
+
+    fn drain(queue: &mut ReorderQueue, seqs: &[u16]) -> Vec<u16> {
+        let mut out = Vec::new();
+        for &seq in seqs {
+            if let Some(p) = queue.push_or_return(packet(seq)) {
+                out.push(p.sequence_number());
+            }
+            while let Some(p) = queue.pop() {
+                out.push(p.sequence_number());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_sequence_extender() {
+        let mut extender = SequenceExtender::new();
+        assert_eq!(extender.extend(65534), 65534);
+        assert_eq!(extender.extend(1), 65537);
+        assert_eq!(extender.extend(65535), 65535);
+        assert_eq!(extender.highest(), Some(65537));
+    }
+
+    #[test]
+    fn test_reorder_queue_wraparound() {
+        let mut queue = ReorderQueue::new(5);
+        let out = drain(&mut queue, &[65533, 65535, 0, 65534, 1]);
+        assert_eq!(out, vec![65533, 65534, 65535, 0, 1]);
+        assert_eq!(queue.highest_sequence(), Some(65536 + 1));
+        assert_eq!(queue.stats(), ReorderStats::default());
+    }
+
+    #[test]
+    fn test_reorder_queue_starts_at_zero() {
+        let mut queue = ReorderQueue::new(5);
+        assert_eq!(drain(&mut queue, &[0, 2, 1]), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_reorder_queue_stats() {
+        let mut queue = ReorderQueue::new(2);
+        let out = drain(&mut queue, &[10, 13, 13, 14, 11]);
+        // 11 and 12 are skipped once the queue is full, 11 arrives too late
+        assert_eq!(out, vec![10, 13, 14]);
+        let stats = queue.stats();
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.lost, 2);
+        assert_eq!(stats.late, 1);
+    }
+
+    #[test]
+    fn test_reorder_queue_restart() {
+        let mut queue = ReorderQueue::new(5);
+        assert_eq!(drain(&mut queue, &[30000, 30001, 100, 101]), vec![30000, 30001, 100, 101]);
+        assert_eq!(queue.stats().resets, 1);
+
+        // A single stray packet doesn't restart the numbering
+        let mut queue = ReorderQueue::new(5);
+        let out = drain(&mut queue, &[30000, 30001, 5, 30002, 100, 101, 102]);
+        assert_eq!(out, vec![30000, 30001, 30002, 100, 101, 102]);
+        assert_eq!(queue.stats().resets, 1);
+    }
 }
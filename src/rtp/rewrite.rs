@@ -0,0 +1,161 @@
+use super::{Packet, SequenceExtender};
+use bytes::BytesMut;
+use std::time::Duration;
+
+/// Where the current input starts, in input and output numbering
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    ssrc: u32,
+    in_seq: i64,
+    out_seq: i64,
+    ts_offset: u32,
+}
+
+/// Rewrites SSRC, sequence numbers and timestamps so that consumers see
+/// one continuous stream across splices, e.g. when a proxy switches the
+/// source or a reconnect starts a new session.
+///
+/// The first input passes through with its own numbering. After a
+/// [`splice`](Self::splice), or when the SSRC changes, the next packet
+/// continues the output sequence numbers and its timestamp follows the
+/// last one by the given gap. Both wrap around like RTP numbers do. Late
+/// packets from before the splice are dropped, so the output never goes
+/// back behind a splice point.
+#[derive(Debug, Clone, Default)]
+pub struct Rewriter {
+    // Output SSRC, taken from the first packet if not set
+    ssrc: Option<u32>,
+    extender: SequenceExtender,
+    segment: Option<Segment>,
+    // SSRC of the previous input, its late packets are dropped
+    previous_ssrc: Option<u32>,
+    // Highest extended output sequence number and its timestamp
+    last: Option<(i64, u32)>,
+    // Timestamp gap of the pending splice
+    splice: Option<u32>,
+}
+
+impl Rewriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output SSRC, e.g. a [`random_ssrc`](super::random_ssrc) of the proxy
+    pub fn ssrc(mut self, ssrc: u32) -> Self {
+        self.ssrc = Some(ssrc);
+        self
+    }
+
+    /// Starts a new input with the next packet, its timestamp follows the
+    /// last output timestamp by `gap` ticks. A gap of 0 would repeat the
+    /// timestamp, so at least 1 is used.
+    pub fn splice(&mut self, gap: u32) {
+        self.splice = Some(gap.max(1));
+    }
+
+    /// Like [`splice`](Self::splice) with the gap in time, e.g. the
+    /// downtime of a reconnect
+    pub fn splice_after(&mut self, gap: Duration, clock_rate: u32) {
+        let ticks = gap.as_nanos() * clock_rate as u128 / 1_000_000_000;
+        self.splice(ticks.min(u32::MAX as u128) as u32);
+    }
+
+    /// Returns the rewritten packet, None if it belongs to the input
+    /// before the last splice
+    pub fn rewrite(&mut self, packet: &Packet) -> Option<Packet> {
+        let ssrc = packet.ssrc();
+        let new_input = self.segment.is_some_and(|s| s.ssrc != ssrc);
+        if new_input && self.previous_ssrc == Some(ssrc) && self.splice.is_none() {
+            return None;
+        }
+        if self.segment.is_none() || new_input || self.splice.is_some() {
+            self.start_segment(packet);
+        }
+        let segment = self.segment?;
+        let ext = self.extender.extend(packet.sequence_number());
+        if ext < segment.in_seq {
+            return None;
+        }
+        let out_seq = segment.out_seq + (ext - segment.in_seq);
+        let timestamp = packet.timestamp().wrapping_add(segment.ts_offset);
+        if self.last.is_none_or(|(last, _)| out_seq > last) {
+            self.last = Some((out_seq, timestamp));
+        }
+        let out_ssrc = *self.ssrc.get_or_insert(ssrc);
+        let mut buf = BytesMut::from(packet.as_bytes());
+        buf[2..4].copy_from_slice(&(out_seq as u16).to_be_bytes());
+        buf[4..8].copy_from_slice(&timestamp.to_be_bytes());
+        buf[8..12].copy_from_slice(&out_ssrc.to_be_bytes());
+        Packet::new(buf.freeze()).ok()
+    }
+
+    fn start_segment(&mut self, packet: &Packet) {
+        let gap = self.splice.take().unwrap_or(1);
+        if let Some(segment) = self.segment {
+            if segment.ssrc != packet.ssrc() {
+                self.previous_ssrc = Some(segment.ssrc);
+            }
+        }
+        self.extender.reset();
+        let in_seq = self.extender.extend(packet.sequence_number());
+        let (out_seq, ts_offset) = match self.last {
+            Some((last, timestamp)) => (last + 1, timestamp.wrapping_add(gap).wrapping_sub(packet.timestamp())),
+            None => (in_seq, 0),
+        };
+        self.segment = Some(Segment {
+            ssrc: packet.ssrc(),
+            in_seq,
+            out_seq,
+            ts_offset,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn packet(ssrc: u32, seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(96, ssrc, seq, timestamp, false, &[0xab])
+    }
+
+    fn rewrite(rewriter: &mut Rewriter, p: Packet) -> Option<(u32, u16, u32)> {
+        let out = rewriter.rewrite(&p)?;
+        assert_eq!(out.data(), &[0xab]);
+        Some((out.ssrc(), out.sequence_number(), out.timestamp()))
+    }
+
+    #[test]
+    fn test_rewriter_splice() {
+        let mut rewriter = Rewriter::new();
+        // The first input passes through unchanged
+        assert_eq!(rewrite(&mut rewriter, packet(1, 65534, 1000)), Some((1, 65534, 1000)));
+        assert_eq!(rewrite(&mut rewriter, packet(1, 65535, 4000)), Some((1, 65535, 4000)));
+
+        // A reconnect restarts numbering, the output continues after 3000 ticks
+        rewriter.splice(3000);
+        assert_eq!(rewrite(&mut rewriter, packet(1, 10, 500)), Some((1, 0, 7000)));
+        assert_eq!(rewrite(&mut rewriter, packet(1, 12, 6500)), Some((1, 2, 13000)));
+        assert_eq!(rewrite(&mut rewriter, packet(1, 11, 3500)), Some((1, 1, 10000)));
+        // Late packet from before the splice point
+        assert_eq!(rewrite(&mut rewriter, packet(1, 9, 0)), None);
+    }
+
+    #[test]
+    fn test_rewriter_ssrc_change() {
+        let mut rewriter = Rewriter::new().ssrc(0x55);
+        assert_eq!(
+            rewrite(&mut rewriter, packet(1, 100, u32::MAX)),
+            Some((0x55, 100, u32::MAX))
+        );
+        // A new source is spliced 1 tick after the last packet, timestamps wrap
+        assert_eq!(rewrite(&mut rewriter, packet(2, 7, 9000)), Some((0x55, 101, 0)));
+        // Late packets of the previous source are dropped
+        assert_eq!(rewrite(&mut rewriter, packet(1, 99, 0)), None);
+        assert_eq!(rewrite(&mut rewriter, packet(2, 8, 12000)), Some((0x55, 102, 3000)));
+
+        rewriter.splice_after(Duration::from_millis(100), 90_000);
+        assert_eq!(rewrite(&mut rewriter, packet(3, 0, 0)), Some((0x55, 103, 12000)));
+    }
+}
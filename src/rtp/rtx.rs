@@ -0,0 +1,123 @@
+use super::Packet;
+use crate::sdp::Media;
+use std::collections::HashMap;
+
+/// Maps retransmissions (RFC 4588) back to the packets they repair, so
+/// they can be put into a [`JitterBuffer`](super::JitterBuffer) or
+/// [`ReorderQueue`](super::ReorderQueue) like the originals.
+///
+/// A retransmission carries the original sequence number (OSN) in front of
+/// the payload and uses its own payload type and SSRC.
+#[derive(Debug, Default, Clone)]
+pub struct RtxMapper {
+    // Retransmission payload type to the repaired one
+    payload_types: HashMap<u8, u8>,
+    // Retransmission SSRC to the repaired one
+    ssrcs: HashMap<u32, u32>,
+    // SSRC of the last original packet, for streams without ssrc-group
+    media_ssrc: Option<u32>,
+}
+
+impl RtxMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the payload types from the `rtx` rtpmaps and the SSRCs from
+    /// `a=ssrc-group:FID` of the media description
+    pub fn from_media(media: &Media) -> Self {
+        let mut mapper = Self::new();
+        for (rtx, apt) in media.rtx_payload_types() {
+            mapper = mapper.payload_type(rtx, apt);
+        }
+        if let Some(&[ssrc, rtx_ssrc]) = media.ssrc_group("FID").as_deref() {
+            mapper = mapper.ssrc(rtx_ssrc, ssrc);
+        }
+        mapper
+    }
+
+    /// Packets of `rtx` payload type repair those of `apt`
+    pub fn payload_type(mut self, rtx: u8, apt: u8) -> Self {
+        self.payload_types.insert(rtx, apt);
+        self
+    }
+
+    /// Packets of `rtx_ssrc` repair those of `ssrc`. Without it the SSRC of
+    /// the last original packet is used.
+    pub fn ssrc(mut self, rtx_ssrc: u32, ssrc: u32) -> Self {
+        self.ssrcs.insert(rtx_ssrc, ssrc);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.payload_types.is_empty()
+    }
+
+    pub fn is_rtx(&self, packet: &Packet) -> bool {
+        self.payload_types.contains_key(&packet.payload_type())
+    }
+
+    /// Returns the original of a retransmission, other packets unchanged.
+    /// None for retransmissions without OSN, e.g. padding-only packets
+    /// sent for bandwidth probing, or of an unknown source.
+    pub fn map(&mut self, packet: Packet) -> Option<Packet> {
+        let Some(&apt) = self.payload_types.get(&packet.payload_type()) else {
+            self.media_ssrc = Some(packet.ssrc());
+            return Some(packet);
+        };
+        let data = packet.data();
+        if data.len() < 2 {
+            return None;
+        }
+        let ssrc = self.ssrcs.get(&packet.ssrc()).copied().or(self.media_ssrc)?;
+        let header = &packet.as_bytes()[..packet.header_len()];
+        let mut buf = Vec::with_capacity(header.len() + data.len() - 2);
+        buf.extend_from_slice(header);
+        // Without padding, the payload keeps the marker bit
+        buf[0] &= !0x20;
+        buf[1] = (buf[1] & 0x80) | apt;
+        buf[2..4].copy_from_slice(&data[..2]);
+        buf[8..12].copy_from_slice(&ssrc.to_be_bytes());
+        buf.extend_from_slice(&data[2..]);
+        Packet::new(buf).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::PacketBuilder;
+
+    #[test]
+    fn test_rtx_mapper() {
+        let mut media: Media = "video 0 RTP/AVPF 96 97".parse().unwrap();
+        for (name, value) in [
+            ("rtpmap", "96 H264/90000"),
+            ("rtpmap", "97 rtx/90000"),
+            ("fmtp", "97 apt=96;rtx-time=3000"),
+            ("ssrc-group", "FID 1111 2222"),
+        ] {
+            media.attributes.push((name.to_string(), Some(value.to_string())));
+        }
+        let mut mapper = RtxMapper::from_media(&media);
+        assert!(mapper.is_enabled());
+
+        let original = PacketBuilder::new(96, 1111)
+            .sequence_number(500)
+            .timestamp_offset(0)
+            .build(&[1, 2, 3], 9000, true);
+        assert_eq!(mapper.map(original.clone()), Some(original.clone()));
+
+        // The retransmission has its own sequence number and the OSN in front of the payload
+        let rtx = PacketBuilder::new(97, 2222)
+            .sequence_number(7)
+            .timestamp_offset(0)
+            .build(&[0x01, 0xf4, 1, 2, 3], 9000, true);
+        assert!(mapper.is_rtx(&rtx));
+        assert_eq!(mapper.map(rtx), Some(original));
+
+        // Padding-only probe without OSN
+        let probe = PacketBuilder::new(97, 2222).build(&[], 9000, false);
+        assert_eq!(mapper.map(probe), None);
+    }
+}
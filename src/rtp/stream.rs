@@ -0,0 +1,682 @@
+use super::{new_depacketizer, Depacketizer, DepacketizerError, JitterBuffer, JitterOutput, JitterStats, Packet};
+use super::{AudioLevel, CodecChanged, ExtensionMap, ExtensionValues, RtxMapper, VideoOrientation};
+use super::{BudgetedDepacketizer, SwitchingDepacketizer};
+use crate::metrics::MemoryBudget;
+use crate::rtsp::client::{Command, Event};
+use crate::sdp::Media;
+use crate::sync::{NtpTimestamp, Synchronizer};
+use crate::trace;
+use crate::types::{Frame, MediaType};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Delay of the jitter buffer unless set with [`FrameStream::delay`]
+pub const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(200);
+
+// Header extension values kept for packets whose frame wasn't popped yet
+const MAX_PENDING_EXTENSIONS: usize = 256;
+
+/// A complete frame with the times to present it at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedFrame {
+    pub frame: Frame,
+    /// Time since the first frame of the stream, from the RTP timestamps.
+    /// Frames presented before the first one, e.g. B-frames, are at 0.
+    pub pts: Duration,
+    /// Capture time on the sender's clock, None until a sender report of
+    /// the source arrived
+    pub ntp: Option<NtpTimestamp>,
+    /// Decoding can start at this frame, e.g. an H.264 IDR picture
+    pub keyframe: bool,
+    /// Packets before this frame were lost, a decoder may have to wait for
+    /// the next keyframe
+    pub discontinuity: bool,
+}
+
+impl TimedFrame {
+    pub fn media_type(&self) -> MediaType {
+        self.frame.media_type
+    }
+
+    pub fn rtp_timestamp(&self) -> u32 {
+        self.frame.timestamp
+    }
+}
+
+/// Turns the RTP packets of a track into [`TimedFrame`]s.
+///
+/// Packets go through a [`JitterBuffer`] to be reordered, the depacketizer
+/// assembles them into frames at marker bits and timestamp changes, and
+/// the RTP timestamps are mapped to a presentation time and, with a
+/// [`Synchronizer`] fed with the sender reports, to the sender's clock.
+/// The audio level and video orientation of the RTP header extensions go
+/// into the [`FrameMetadata`](crate::types::FrameMetadata).
+///
+/// Retransmissions (RFC 4588) of the payload types described as `rtx` are
+/// mapped back to the packets they repair before reordering, with
+/// [`FrameStream::nack`] the stream requests them for the gaps it sees.
+///
+/// A stream created from the media description follows changes of the
+/// payload type with a [`SwitchingDepacketizer`], the presentation times
+/// continue at the clock rate of the new payload format.
+pub struct FrameStream {
+    packets: mpsc::Receiver<Packet>,
+    // Payload types of the track, other packets are dropped, empty for all
+    payload_types: Vec<u8>,
+    depacketizer: Box<dyn Depacketizer>,
+    jitter: JitterBuffer,
+    // Budget the buffered packets and partial frames are charged to
+    budget: Option<MemoryBudget>,
+    rtx: RtxMapper,
+    // Channel commands go to, the interleaved RTP channel of the track and
+    // how often a missing packet is requested again
+    nack: Option<(mpsc::Sender<Command>, u8, Duration)>,
+    // Source of the last original packet, the one NACKs are about
+    ssrc: Option<u32>,
+    // Where codec changes are reported and the RTP channel of the track
+    event_tx: Option<(mpsc::Sender<Event>, u8)>,
+    clock_rate: u32,
+    synchronizer: Option<Arc<Mutex<Synchronizer>>>,
+    extensions: ExtensionMap,
+    // Decoded extensions by sequence number of the depacketized packets,
+    // and the orientation signaled last
+    extension_values: VecDeque<(u16, ExtensionValues)>,
+    orientation: Option<VideoOrientation>,
+    // Last RTP timestamp, its extension to 64 bits and the first extended one
+    timeline: Option<(u32, i64, i64)>,
+    // Presentation time the timeline starts at, the last one before a codec change
+    pts_base: Duration,
+    discontinuity: bool,
+    closed: bool,
+}
+
+impl FrameStream {
+    /// Receives the packets of the track described by `media` from `packets`,
+    /// fails if the first payload type of the media can't be depacketized
+    pub fn new(packets: mpsc::Receiver<Packet>, media: &Media) -> Result<Self, DepacketizerError> {
+        new_depacketizer(media)?;
+        let depacketizer = Box::new(SwitchingDepacketizer::new(media.clone()));
+        let clock_rate = media
+            .formats
+            .first()
+            .and_then(|pt| media.rtpmap(*pt))
+            .map_or(0, |rtpmap| rtpmap.timebase);
+        let mut stream = Self::with_depacketizer(packets, depacketizer, clock_rate);
+        stream.payload_types = media.formats.clone();
+        stream.extensions = ExtensionMap::from_media(media);
+        stream.rtx = RtxMapper::from_media(media);
+        Ok(stream)
+    }
+
+    /// Receives packets for `depacketizer` whose timestamps run at `clock_rate`
+    pub fn with_depacketizer(
+        packets: mpsc::Receiver<Packet>,
+        depacketizer: Box<dyn Depacketizer>,
+        clock_rate: u32,
+    ) -> Self {
+        Self {
+            packets,
+            payload_types: Vec::new(),
+            depacketizer,
+            jitter: JitterBuffer::new(clock_rate, DEFAULT_FRAME_DELAY),
+            budget: None,
+            rtx: RtxMapper::new(),
+            nack: None,
+            ssrc: None,
+            event_tx: None,
+            clock_rate: clock_rate.max(1),
+            synchronizer: None,
+            extensions: ExtensionMap::new(),
+            extension_values: VecDeque::new(),
+            orientation: None,
+            timeline: None,
+            pts_base: Duration::ZERO,
+            discontinuity: false,
+            closed: false,
+        }
+    }
+
+    /// How long packets are held for reordering, missing ones are given up
+    /// after this delay
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.jitter = JitterBuffer::new(self.clock_rate, delay);
+        if let Some((_, _, interval)) = self.nack {
+            self.jitter = self.jitter.nack(interval);
+        }
+        if let Some(budget) = &self.budget {
+            self.jitter = self.jitter.budget(budget.clone());
+        }
+        self
+    }
+
+    /// Charges the packets in the jitter buffer and the frames being
+    /// assembled to `budget`, see [`JitterBuffer::budget`] and
+    /// [`BudgetedDepacketizer`]. Packets exceeding it are dropped.
+    pub fn budget(mut self, budget: MemoryBudget) -> Self {
+        self.jitter = self.jitter.budget(budget.clone());
+        self.depacketizer = Box::new(BudgetedDepacketizer::new(self.depacketizer, budget.clone()));
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Requests missing packets with [`Command::Nack`] sent to `cmd_tx`,
+    /// the channel the track is received on, for the interleaved RTP
+    /// `channel` of the track. A packet is requested again after
+    /// `interval` as described at [`JitterBuffer::nack`], the delay must
+    /// leave room for the retransmission to arrive.
+    pub fn nack(mut self, cmd_tx: mpsc::Sender<Command>, channel: u8, interval: Duration) -> Self {
+        self.jitter = self.jitter.nack(interval);
+        self.nack = Some((cmd_tx, channel, interval));
+        self
+    }
+
+    /// Maps retransmissions with `rtx` instead of the one described by the
+    /// media, e.g. for a server that announces the retransmission SSRC late
+    pub fn rtx(mut self, rtx: RtxMapper) -> Self {
+        self.rtx = rtx;
+        self
+    }
+
+    /// Reports changes of the payload format as [`Event::CodecChanged`] of
+    /// the RTP `channel` of the track to `event_tx`, e.g. the sender of
+    /// [`Channel::events`](crate::rtsp::client::Channel::events)
+    pub fn events(mut self, event_tx: mpsc::Sender<Event>, channel: u8) -> Self {
+        self.event_tx = Some((event_tx, channel));
+        self
+    }
+
+    /// Maps RTP timestamps to NTP time with the sender reports given to
+    /// `synchronizer`, see [`Channel::synchronizer`](crate::rtsp::client::Channel::synchronizer)
+    pub fn synchronizer(mut self, synchronizer: Arc<Mutex<Synchronizer>>) -> Self {
+        self.synchronizer = Some(synchronizer);
+        self
+    }
+
+    /// Decodes the header extensions of `extensions`, by default the ones
+    /// of the `a=extmap` attributes of the media
+    pub fn extensions(mut self, extensions: ExtensionMap) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    pub fn jitter_stats(&self) -> JitterStats {
+        self.jitter.stats()
+    }
+
+    /// The packets in the jitter buffer that weren't depacketized yet, see
+    /// [`JitterBuffer::buffered`]
+    pub fn buffered(&self) -> impl Iterator<Item = &Packet> {
+        self.jitter.buffered()
+    }
+
+    /// Starts over, e.g. on a [`StreamItem::Discontinuity`](crate::rtsp::client::StreamItem::Discontinuity)
+    /// when the packets of the reconnected session arrive on the same
+    /// receiver. The buffered packets are dropped, the presentation times
+    /// continue where they ended and the next frame is a discontinuity.
+    /// The stream does the same when the sequence numbering restarts or
+    /// the SSRC changes.
+    pub fn reset(&mut self) {
+        self.jitter.reset();
+        self.extension_values.clear();
+        self.restart_timeline();
+        self.discontinuity = true;
+    }
+
+    /// Waits for the next frame, None once the packet sender is dropped and
+    /// the buffered packets are drained
+    pub async fn next(&mut self) -> Option<TimedFrame> {
+        loop {
+            if let Some(frame) = self.depacketizer.pop() {
+                return Some(self.timed(frame));
+            }
+            let now = Instant::now();
+            // Once closed, the remaining packets are released without waiting
+            let release = match self.closed {
+                true => self
+                    .jitter
+                    .next_release()
+                    .map_or(now.into_std(), |t| t.max(now.into_std())),
+                false => now.into_std(),
+            };
+            match self.jitter.pop(release) {
+                Some(JitterOutput::Packet(packet)) => {
+                    self.record_extensions(&packet);
+                    if let Err(e) = self.depacketizer.push(&packet) {
+                        #[cfg(not(feature = "tracing"))]
+                        log::debug!("Dropping RTP packet {}: {}", packet.sequence_number(), e);
+                        trace::event!(debug, seq = packet.sequence_number(), error = %e, "Failed to depacketize");
+                        self.discontinuity = true;
+                    }
+                    while let Some(change) = self.depacketizer.codec_change() {
+                        self.codec_changed(change);
+                    }
+                    continue;
+                }
+                Some(JitterOutput::Lost { .. }) => {
+                    self.discontinuity = true;
+                    continue;
+                }
+                None if self.closed => return None,
+                None => {}
+            }
+            let deadline = self.jitter.next_release().map(Instant::from_std);
+            tokio::select! {
+                packet = self.packets.recv() => match packet {
+                    Some(packet) => self.receive(packet),
+                    None => self.closed = true,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or(now)), if deadline.is_some() => {}
+            }
+        }
+    }
+
+    fn receive(&mut self, packet: Packet) {
+        // Retransmissions without original, e.g. bandwidth probes, are dropped
+        let Some(packet) = self.rtx.map(packet) else {
+            return;
+        };
+        if !self.accepts(&packet) {
+            return;
+        }
+        self.ssrc = Some(packet.ssrc());
+        let now = Instant::now().into_std();
+        let resets = self.jitter.stats().resets;
+        self.jitter.push(packet, now);
+        if self.jitter.stats().resets != resets {
+            self.restart_timeline();
+            self.discontinuity = true;
+        }
+        let (Some((cmd_tx, channel, _)), Some(ssrc)) = (&self.nack, self.ssrc) else {
+            return;
+        };
+        let lost = self.jitter.nacks(now);
+        if !lost.is_empty() {
+            trace::event!(debug, channel, ssrc, lost = lost.len(), "Requesting retransmissions");
+            let nack = Command::Nack {
+                channel: *channel,
+                ssrc,
+                lost,
+            };
+            if cmd_tx.try_send(nack).is_err() {
+                log::debug!("Dropping NACK of channel {}, the channel is busy or closed", channel);
+            }
+        }
+    }
+
+    /// Lets the timeline start over where the old one ended
+    fn restart_timeline(&mut self) {
+        if let Some((_, extended, first)) = self.timeline.take() {
+            self.pts_base += ticks_to_duration((extended - first).max(0) as u64, self.clock_rate);
+        }
+    }
+
+    fn codec_changed(&mut self, change: CodecChanged) {
+        // The timeline continues at the new clock rate
+        self.restart_timeline();
+        self.clock_rate = change.current.timebase.max(1);
+        self.jitter.set_clock_rate(self.clock_rate);
+        if let Some((event_tx, channel)) = &self.event_tx {
+            let event = Event::CodecChanged {
+                channel: *channel,
+                previous: change.previous,
+                current: change.current,
+            };
+            if event_tx.try_send(event).is_err() {
+                log::debug!(
+                    "Dropping codec change of channel {}, the receiver is busy or closed",
+                    channel
+                );
+            }
+        }
+    }
+
+    fn accepts(&self, packet: &Packet) -> bool {
+        self.payload_types.is_empty() || self.payload_types.contains(&packet.payload_type())
+    }
+
+    fn record_extensions(&mut self, packet: &Packet) {
+        let values = self.extensions.parse(packet);
+        if values.is_empty() {
+            return;
+        }
+        if self.extension_values.len() == MAX_PENDING_EXTENSIONS {
+            self.extension_values.pop_front();
+        }
+        self.extension_values.push_back((packet.sequence_number(), values));
+    }
+
+    /// Applies the extensions of the packets up to the last one of `frame`
+    fn annotate(&mut self, frame: &mut Frame) {
+        let mut audio_level = None;
+        while let Some((sequence, values)) = self.extension_values.front() {
+            let past = frame
+                .metadata
+                .origin
+                .is_none_or(|origin| origin.last_sequence.wrapping_sub(*sequence) < 0x8000);
+            if !past {
+                break;
+            }
+            // The loudest packet has the lowest level
+            audio_level = match (audio_level, values.audio_level) {
+                (Some(a), Some(b)) => Some(std::cmp::min_by_key(a, b, |l: &AudioLevel| l.level)),
+                (a, b) => a.or(b),
+            };
+            self.orientation = values.orientation.or(self.orientation);
+            self.extension_values.pop_front();
+        }
+        frame.metadata.audio_level = audio_level;
+        frame.metadata.orientation = self.orientation;
+    }
+
+    fn timed(&mut self, mut frame: Frame) -> TimedFrame {
+        self.annotate(&mut frame);
+        let timestamp = frame.timestamp;
+        let (extended, first) = match self.timeline {
+            Some((last, extended, first)) => (extended + timestamp.wrapping_sub(last) as i32 as i64, first),
+            None => (timestamp as i64, timestamp as i64),
+        };
+        self.timeline = Some((timestamp, extended, first));
+        let ticks = (extended - first).max(0) as u64;
+        let pts = self.pts_base + ticks_to_duration(ticks, self.clock_rate);
+        let ntp = match (&self.synchronizer, frame.metadata.origin) {
+            (Some(sync), Some(origin)) => sync
+                .lock()
+                .unwrap()
+                .capture_time(origin.ssrc, timestamp)
+                .map(NtpTimestamp::from_system_time),
+            _ => None,
+        };
+        TimedFrame {
+            keyframe: frame.is_keyframe(),
+            discontinuity: std::mem::take(&mut self.discontinuity),
+            frame,
+            pts,
+            ntp,
+        }
+    }
+}
+
+/// Duration of `ticks` of a clock running at `clock_rate`
+fn ticks_to_duration(ticks: u64, clock_rate: u32) -> Duration {
+    let rate = clock_rate as u64;
+    Duration::from_secs(ticks / rate) + Duration::from_nanos(ticks % rate * 1_000_000_000 / rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::PacketBuilder;
+    use crate::testing::rtp_packet;
+    use crate::testing::{Impairment, LossyQueue};
+    use crate::types::FrameType;
+    use std::time::SystemTime;
+
+    fn packet(seq: u16, timestamp: u32, marker: bool, payload: &[u8]) -> Packet {
+        rtp_packet(96, 5, seq, timestamp, marker, payload)
+    }
+
+    fn h264_media() -> Media {
+        let mut media: Media = "video 0 RTP/AVP 96".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("96 H264/90000".to_string())));
+        media
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let epoch = NtpTimestamp::from_system_time(SystemTime::now()).to_system_time();
+        let sync = Arc::new(Mutex::new(Synchronizer::new()));
+        sync.lock()
+            .unwrap()
+            .update(5, NtpTimestamp::from_system_time(epoch), u32::MAX - 2999, 90_000);
+        let mut stream = FrameStream::new(packet_rx, &h264_media())
+            .unwrap()
+            .delay(Duration::from_millis(100))
+            .synchronizer(sync);
+        // An IDR picture in two FU-A fragments with a P picture after the
+        // timestamp wrap in between, and a packet of another track
+        packet_tx
+            .send(packet(1, u32::MAX - 2999, false, &[0x7c, 0x85, 1, 2]))
+            .await
+            .unwrap();
+        let other = Packet::new(vec![0x80, 0x08, 0, 1, 0, 0, 0, 0, 0, 0, 0, 9, 0xd5]).unwrap();
+        packet_tx.send(other).await.unwrap();
+        packet_tx.send(packet(3, 0, true, &[0x41, 9])).await.unwrap();
+        packet_tx
+            .send(packet(2, u32::MAX - 2999, true, &[0x7c, 0x45, 3]))
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let first = stream.next().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(first.media_type(), MediaType::Video);
+        assert_eq!(first.frame.frame_type, FrameType::H264);
+        assert_eq!(first.rtp_timestamp(), u32::MAX - 2999);
+        assert_eq!(first.frame.data, [0, 0, 0, 1, 0x65, 1, 2, 3]);
+        assert!(first.keyframe && !first.discontinuity);
+        assert_eq!(
+            (first.pts, first.ntp),
+            (Duration::ZERO, Some(NtpTimestamp::from_system_time(epoch)))
+        );
+
+        let second = stream.next().await.unwrap();
+        assert!(!second.keyframe);
+        assert_eq!(second.pts, Duration::from_nanos(33_333_333));
+        let capture = second.ntp.unwrap().to_system_time().duration_since(epoch).unwrap();
+        assert_eq!(capture.as_millis(), 33);
+        assert_eq!(stream.jitter_stats().released, 3);
+    }
+
+    #[test]
+    fn test_ticks_to_duration() {
+        assert_eq!(ticks_to_duration(3000, 90_000), Duration::from_nanos(33_333_333));
+        // A week at 90 kHz overflows the nanoseconds in u64 when multiplied first
+        let week = 7 * 24 * 3600;
+        assert_eq!(
+            ticks_to_duration(week * 90_000 + 45_000, 90_000),
+            Duration::from_millis(week * 1000 + 500)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_loss() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let mut stream = FrameStream::new(packet_rx, &h264_media()).unwrap();
+        packet_tx.send(packet(1, 0, true, &[0x65, 1])).await.unwrap();
+        packet_tx.send(packet(3, 6000, true, &[0x41, 3])).await.unwrap();
+        packet_tx.send(packet(4, 9000, true, &[0x41, 4])).await.unwrap();
+        drop(packet_tx);
+        let mut frames = Vec::new();
+        while let Some(frame) = stream.next().await {
+            frames.push(frame);
+        }
+        assert_eq!(
+            frames
+                .iter()
+                .map(|f| (f.rtp_timestamp(), f.discontinuity))
+                .collect::<Vec<_>>(),
+            [(0, false), (6000, true), (9000, false)]
+        );
+        assert_eq!(stream.jitter_stats().lost, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_impaired() {
+        let (packet_tx, packet_rx) = mpsc::channel(64);
+        let mut stream = FrameStream::new(packet_rx, &h264_media()).unwrap();
+        let impairment = Impairment::new(4)
+            .drop(0.1)
+            .reorder(0.2)
+            .delay(Duration::from_millis(20));
+        let mut queue = LossyQueue::new(impairment);
+        let start = Instant::now();
+        tokio::spawn(async move {
+            for seq in 1..=30u16 {
+                let nal = if seq == 1 { 0x65 } else { 0x41 };
+                queue.push(packet(seq, seq as u32 * 3000, true, &[nal, 1]));
+                if seq == 30 {
+                    queue.flush();
+                }
+                let sent = start + Duration::from_millis(33 * seq as u64);
+                while let Some((delay, packet)) = queue.pop() {
+                    tokio::time::sleep_until(sent + delay).await;
+                    packet_tx.send(packet).await.unwrap();
+                }
+            }
+            assert_eq!(queue.stats().dropped, 3);
+        });
+        let mut frames = Vec::new();
+        while let Some(frame) = stream.next().await {
+            frames.push((frame.rtp_timestamp() / 3000, frame.discontinuity));
+        }
+        // The frames after the dropped packets 4, 6 and 13 are discontinuities
+        let expected: Vec<_> = (1..=30)
+            .filter(|n| ![4, 6, 13].contains(n))
+            .map(|n| (n, [5, 7, 14].contains(&n)))
+            .collect();
+        assert_eq!(frames, expected);
+        let stats = stream.jitter_stats();
+        assert_eq!((stats.released, stats.lost, stats.late), (27, 3, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_restart() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let mut stream = FrameStream::new(packet_rx, &h264_media()).unwrap();
+        let mut frames = Vec::new();
+        for (seq, timestamp) in [(1000, 0), (1001, 3000)] {
+            packet_tx.send(packet(seq, timestamp, true, &[0x41, 1])).await.unwrap();
+            let frame = stream.next().await.unwrap();
+            frames.push((frame.pts.as_millis(), frame.discontinuity));
+        }
+        // The sender restarts its numbering and timestamps, e.g. after a reboot
+        for (seq, timestamp) in [(5, 777), (6, 3777)] {
+            packet_tx.send(packet(seq, timestamp, true, &[0x41, 1])).await.unwrap();
+        }
+        drop(packet_tx);
+        while let Some(frame) = stream.next().await {
+            frames.push((frame.pts.as_millis(), frame.discontinuity));
+        }
+        assert_eq!(frames, [(0, false), (33, false), (33, true), (66, false)]);
+        assert_eq!(stream.jitter_stats().resets, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_budget() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let budget = MemoryBudget::new(30);
+        // The budget survives the jitter buffer being replaced by the delay
+        let mut stream = FrameStream::new(packet_rx, &h264_media())
+            .unwrap()
+            .budget(budget.clone())
+            .delay(Duration::from_millis(100));
+        for (seq, timestamp) in [(1, 0), (2, 3000), (3, 6000)] {
+            packet_tx.send(packet(seq, timestamp, true, &[0x41, 1])).await.unwrap();
+        }
+        drop(packet_tx);
+        let mut frames = Vec::new();
+        while let Some(frame) = stream.next().await {
+            frames.push(frame.rtp_timestamp());
+        }
+        // Two packets of 14 bytes fit, the third one is dropped
+        assert_eq!(frames, [0, 3000]);
+        let usage = budget.usage();
+        assert_eq!((usage.used, usage.peak, usage.rejected), (0, 28, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_codec_change() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let (event_tx, mut event_rx) = mpsc::channel(4);
+        let mut media: Media = "audio 0 RTP/AVP 0 8".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("8 PCMA/16000".to_string())));
+        let mut stream = FrameStream::new(packet_rx, &media).unwrap().events(event_tx, 2);
+        // 20 ms of PCMU at 8 kHz, then PCMA at 16 kHz
+        let mut pcmu = PacketBuilder::new(0, 5).sequence_number(1).timestamp_offset(0);
+        let mut pcma = PacketBuilder::new(8, 5).sequence_number(3).timestamp_offset(0);
+        for packet in [
+            pcmu.build(&[0xff; 160], 0, false),
+            pcmu.build(&[0xff; 160], 160, false),
+            pcma.build(&[0xd5; 320], 320, false),
+            pcma.build(&[0xd5; 320], 640, false),
+        ] {
+            packet_tx.send(packet).await.unwrap();
+        }
+        drop(packet_tx);
+
+        let mut frames = Vec::new();
+        while let Some(frame) = stream.next().await {
+            frames.push((frame.frame.frame_type, frame.pts));
+        }
+        let ms = Duration::from_millis;
+        assert_eq!(
+            frames,
+            [
+                (FrameType::PCMU, ms(0)),
+                (FrameType::PCMU, ms(20)),
+                (FrameType::PCMA, ms(20)),
+                (FrameType::PCMA, ms(40)),
+            ]
+        );
+        let Some(Event::CodecChanged {
+            channel,
+            previous,
+            current,
+        }) = event_rx.recv().await
+        else {
+            panic!("expected a codec change");
+        };
+        assert_eq!(channel, 2);
+        assert_eq!((previous.payload_type, previous.timebase), (0, 8000));
+        assert_eq!((current.payload_type, current.timebase), (8, 16000));
+        assert!(event_rx.try_recv().is_err());
+    }
+
+    /// `packet` with a one-byte header extension of `elements`
+    fn with_extension(packet: Packet, elements: &[u8; 4]) -> Packet {
+        let bytes = packet.as_bytes();
+        let mut buf = vec![bytes[0] | 0x10];
+        buf.extend_from_slice(&bytes[1..12]);
+        buf.extend_from_slice(&[0xbe, 0xde, 0, 1]);
+        buf.extend_from_slice(elements);
+        buf.extend_from_slice(&bytes[12..]);
+        Packet::new(buf).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_stream_extensions() {
+        let (packet_tx, packet_rx) = mpsc::channel(16);
+        let mut media = h264_media();
+        for extmap in [
+            "1 urn:ietf:params:rtp-hdrext:ssrc-audio-level",
+            "2 urn:3gpp:video-orientation",
+        ] {
+            media.attributes.push(("extmap".to_string(), Some(extmap.to_string())));
+        }
+        let mut stream = FrameStream::new(packet_rx, &media).unwrap();
+        // Levels 40 and 20 in the fragments of the first frame, which is
+        // rotated by 180 degrees, nothing signaled with the second one
+        let first = with_extension(packet(1, 0, false, &[0x7c, 0x85, 1]), &[0x10, 40, 0, 0]);
+        let second = with_extension(packet(2, 0, true, &[0x7c, 0x45, 2]), &[0x10, 20, 0x20, 2]);
+        for packet in [first, second, packet(3, 3000, true, &[0x41, 3])] {
+            packet_tx.send(packet).await.unwrap();
+        }
+        drop(packet_tx);
+
+        let frame = stream.next().await.unwrap().frame;
+        assert_eq!(frame.data, [0, 0, 0, 1, 0x65, 1, 2]);
+        assert_eq!(frame.metadata.audio_level.map(|l| l.level), Some(20));
+        assert_eq!(frame.metadata.orientation.map(|o| o.rotation), Some(180));
+        let frame = stream.next().await.unwrap().frame;
+        assert_eq!(frame.metadata.audio_level, None);
+        assert_eq!(frame.metadata.orientation.map(|o| o.rotation), Some(180));
+    }
+}
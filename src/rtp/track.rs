@@ -0,0 +1,141 @@
+use super::{Packet, ReorderQueue, SequenceExtender};
+use std::collections::VecDeque;
+
+/// How the packets of a track are handed to the consumer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Packets are put back in sequence order, waiting for at most `max_len` packets
+    Reordered { max_len: usize },
+    /// Packets are delivered in arrival order for consumers with their own
+    /// jitter buffer, e.g. WebRTC re-streamers
+    Passthrough,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Delivery::Reordered { max_len: 32 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackPacket {
+    pub packet: Packet,
+    /// Sequence numbers skipped since the previously delivered packet
+    pub gap: u64,
+    /// The packet is older than one delivered before, only in passthrough mode
+    pub reordered: bool,
+}
+
+enum Queue {
+    Reordered(ReorderQueue),
+    Passthrough,
+}
+
+/// Delivers the packets of one track according to its [`Delivery`] mode
+pub struct TrackQueue {
+    queue: Queue,
+    extender: SequenceExtender,
+    last: Option<i64>,
+    out: VecDeque<TrackPacket>,
+}
+
+impl TrackQueue {
+    pub fn new(delivery: Delivery) -> Self {
+        let queue = match delivery {
+            Delivery::Reordered { max_len } => Queue::Reordered(ReorderQueue::new(max_len)),
+            Delivery::Passthrough => Queue::Passthrough,
+        };
+        Self {
+            queue,
+            extender: SequenceExtender::new(),
+            last: None,
+            out: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, packet: Packet) {
+        match &mut self.queue {
+            Queue::Reordered(queue) => {
+                let mut ready = Vec::new();
+                ready.extend(queue.push_or_return(packet));
+                while let Some(packet) = queue.pop() {
+                    ready.push(packet);
+                }
+                for packet in ready {
+                    self.deliver(packet);
+                }
+            }
+            Queue::Passthrough => self.deliver(packet),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<TrackPacket> {
+        self.out.pop_front()
+    }
+
+    /// The packets held back by reordering, in sequence order, without
+    /// releasing them. Always empty in passthrough mode.
+    pub fn buffered(&self) -> impl Iterator<Item = &Packet> {
+        let queue = match &self.queue {
+            Queue::Reordered(queue) => Some(queue),
+            Queue::Passthrough => None,
+        };
+        queue.into_iter().flat_map(ReorderQueue::buffered)
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        let ext = self.extender.extend(packet.sequence_number());
+        let (gap, reordered) = match self.last {
+            Some(last) if ext <= last => (0, true),
+            Some(last) => ((ext - last - 1) as u64, false),
+            None => (0, false),
+        };
+        if !reordered {
+            self.last = Some(ext);
+        }
+        self.out.push_back(TrackPacket { packet, gap, reordered });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn packet(seq: u16) -> Packet {
+        rtp_packet(96, 0, seq, 0, false, &[])
+    }
+
+    fn run(delivery: Delivery, seqs: &[u16]) -> Vec<(u16, u64, bool)> {
+        let mut queue = TrackQueue::new(delivery);
+        let mut out = Vec::new();
+        for &seq in seqs {
+            queue.push(packet(seq));
+            while let Some(p) = queue.pop() {
+                out.push((p.packet.sequence_number(), p.gap, p.reordered));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_passthrough_keeps_arrival_order() {
+        let out = run(Delivery::Passthrough, &[1, 3, 2, 6]);
+        assert_eq!(out, vec![(1, 0, false), (3, 1, false), (2, 0, true), (6, 2, false)]);
+    }
+
+    #[test]
+    fn test_reordered_reports_gaps() {
+        let out = run(Delivery::Reordered { max_len: 2 }, &[1, 3, 2, 6, 7]);
+        assert_eq!(
+            out,
+            vec![
+                (1, 0, false),
+                (2, 0, false),
+                (3, 0, false),
+                (6, 2, false),
+                (7, 0, false)
+            ]
+        );
+    }
+}
@@ -1,61 +1,122 @@
+use crate::metrics::{BudgetError, Charge, Component, MemoryBudget};
+use bytes::{Buf, Bytes, BytesMut};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum BufferError {
     #[error("Not enough buffer space")]
     NotEnoughSpace,
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetError),
 }
 
 type Result<T> = std::result::Result<T, BufferError>;
 
+/// Linear buffer of received or outgoing bytes.
+///
+/// Read data can be split off as [`Bytes`] without copying, the memory is
+/// shared until the last reference is dropped. With a budget the split off
+/// bytes stay charged until then.
 pub struct Buffer {
-    data: Vec<u8>,
+    // Unread bytes followed by zeroed space for the next write
+    data: BytesMut,
+    filled: usize,
     max_capacity: usize,
-    read_pos: usize,
-    write_pos: usize,
+    charge: Option<Charge>,
 }
 
 impl Buffer {
     pub fn new(max_capacity: usize) -> Self {
         Self {
-            data: Vec::new(),
+            data: BytesMut::new(),
+            filled: 0,
             max_capacity,
-            read_pos: 0,
-            write_pos: 0,
+            charge: None,
         }
     }
 
+    /// Charges the buffered bytes and write space to `budget`
+    pub fn budget(mut self, budget: MemoryBudget, component: Component) -> Self {
+        let mut charge = Charge::new(budget, component);
+        // Only fails if the budget is already exhausted, growth is checked again on write
+        let _ = charge.resize(self.data.len());
+        self.charge = Some(charge);
+        self
+    }
+
+    pub fn max_capacity(&self) -> usize {
+        self.max_capacity
+    }
+
+    /// Changes the maximum capacity. Memory beyond a lowered capacity is
+    /// released once the buffered data has been read.
+    pub fn set_max_capacity(&mut self, max_capacity: usize) {
+        self.max_capacity = max_capacity;
+    }
+
     pub fn get_read_slice(&self) -> &[u8] {
-        let slice = &self.data[self.read_pos..self.write_pos];
-        slice
+        &self.data[..self.filled]
     }
 
     pub fn notify_read(&mut self, n: usize) {
-        self.read_pos += n;
-        if self.read_pos == self.write_pos {
-            self.read_pos = 0;
-            self.write_pos = 0;
+        self.data.advance(n);
+        self.filled -= n;
+        self.release();
+    }
+
+    /// Removes the next `n` unread bytes and returns them without copying
+    pub fn split_read(&mut self, n: usize) -> Bytes {
+        let bytes = self.data.split_to(n).freeze();
+        self.filled -= n;
+        let bytes = match &mut self.charge {
+            Some(charge) => Bytes::from_owner(ChargedBytes {
+                bytes,
+                _charge: charge.split(n),
+            }),
+            None => bytes,
+        };
+        self.release();
+        bytes
+    }
+
+    fn release(&mut self) {
+        if self.filled == 0 && self.data.capacity() > self.max_capacity {
+            self.data = BytesMut::new();
+        }
+        if let Some(charge) = &mut self.charge {
+            // Shrinking always succeeds
+            let _ = charge.resize(self.data.len());
         }
     }
 
     pub fn get_write_slice(&mut self, n: usize) -> Result<&mut [u8]> {
-        if self.write_pos + n > self.data.len() {
-            if n <= self.read_pos {
-                self.data.copy_within(self.read_pos..self.write_pos, 0);
-                self.write_pos -= self.read_pos;
-                self.read_pos = 0;
-            } else if self.write_pos + n <= self.max_capacity {
-                self.data.resize(self.write_pos + n, 0);
-            } else {
+        if self.filled + n > self.data.len() {
+            if self.filled + n > self.max_capacity {
                 return Err(BufferError::NotEnoughSpace);
             }
+            if let Some(charge) = &mut self.charge {
+                charge.resize(self.filled + n)?;
+            }
+            // Reuses the memory once all split off bytes are dropped
+            self.data.resize(self.filled + n, 0);
         }
-        let slice = &mut self.data[self.write_pos..];
-        Ok(slice)
+        Ok(&mut self.data[self.filled..])
     }
 
     pub fn notify_write(&mut self, n: usize) {
-        self.write_pos += n;
+        self.filled += n;
+    }
+}
+
+/// Bytes split off a budgeted buffer, charged until dropped
+struct ChargedBytes {
+    bytes: Bytes,
+    _charge: Charge,
+}
+
+impl AsRef<[u8]> for ChargedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
     }
 }
 
@@ -81,4 +142,58 @@ mod tests {
         let slice = buffer.get_read_slice();
         assert_eq!(slice, &[11, 12, 13, 14, 15]);
     }
+
+    #[test]
+    fn test_buffer_split_read() {
+        let mut buffer = Buffer::new(16);
+        buffer.get_write_slice(6).unwrap()[..6].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        buffer.notify_write(6);
+        let bytes = buffer.split_read(4);
+        assert_eq!(&bytes[..], &[1, 2, 3, 4]);
+        assert_eq!(buffer.get_read_slice(), &[5, 6]);
+        buffer.get_write_slice(2).unwrap()[..2].copy_from_slice(&[7, 8]);
+        buffer.notify_write(2);
+        assert_eq!(buffer.get_read_slice(), &[5, 6, 7, 8]);
+        // The split off bytes are not touched by later writes
+        assert_eq!(&bytes[..], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_buffer_budget() {
+        let budget = MemoryBudget::new(12);
+        let mut buffer = Buffer::new(16).budget(budget.clone(), Component::ReceiveBuffer);
+        buffer.get_write_slice(8).unwrap();
+        buffer.notify_write(8);
+        assert!(matches!(buffer.get_write_slice(8), Err(BufferError::BudgetExceeded(_))));
+        assert_eq!(budget.usage().receive_buffer, 8);
+        // The split off bytes still use the memory of the buffer
+        let bytes = buffer.split_read(6);
+        assert_eq!(budget.usage().receive_buffer, 8);
+        assert!(matches!(buffer.get_write_slice(8), Err(BufferError::BudgetExceeded(_))));
+        let clone = bytes.slice(2..);
+        drop(bytes);
+        assert_eq!(budget.usage().receive_buffer, 8);
+        drop(clone);
+        assert_eq!(budget.usage().receive_buffer, 2);
+        buffer.get_write_slice(8).unwrap();
+        assert_eq!(budget.usage().receive_buffer, 10);
+        let bytes = buffer.split_read(2);
+        drop(buffer);
+        assert_eq!(budget.usage().used, 2);
+        assert_eq!(&bytes[..], [0, 0]);
+        drop(bytes);
+        assert_eq!(budget.usage().used, 0);
+    }
+
+    #[test]
+    fn test_buffer_lower_capacity() {
+        let mut buffer = Buffer::new(10);
+        buffer.get_write_slice(8).unwrap();
+        buffer.notify_write(8);
+        buffer.set_max_capacity(4);
+        assert_eq!(buffer.get_read_slice().len(), 8);
+        buffer.notify_read(8);
+        assert!(buffer.get_write_slice(5).is_err());
+        assert_eq!(buffer.get_write_slice(4).unwrap().len(), 4);
+    }
 }
@@ -0,0 +1,345 @@
+use super::*;
+use crate::rtsp::Transport;
+use crate::sdp::Sdp;
+use crate::stats::BandwidthEstimate;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use url::Url;
+
+// Upgrades that failed right away double the time until the next attempt, up to this factor
+const MAX_UPGRADE_BACKOFF: u32 = 8;
+
+/// One of several media descriptions of the same content, e.g. the main
+/// and the sub stream of a camera described in one SDP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// Index of the media description
+    pub media: usize,
+    /// Control url of the track
+    pub url: Url,
+    /// Advertised bitrate in bits per second, see [`Media::bitrate`](crate::sdp::Media::bitrate)
+    pub bitrate: Option<u64>,
+}
+
+impl Variant {
+    /// The media descriptions of `media_type` with a control, the highest
+    /// advertised bitrate first and the ones without bitrate last
+    pub fn from_sdp(sdp: &Sdp, base: &Url, media_type: &str) -> Vec<Variant> {
+        let mut variants: Vec<_> = sdp
+            .media
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.media_type == media_type)
+            .filter_map(|(index, m)| {
+                Some(Variant {
+                    media: index,
+                    url: control_url(base, m.control()?),
+                    bitrate: m.bitrate(),
+                })
+            })
+            .collect();
+        variants.sort_by_key(|v| std::cmp::Reverse(v.bitrate));
+        variants
+    }
+}
+
+/// When a [`StreamSelector`] recommends another variant
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveConfig {
+    /// Loss fraction above which the link is too bad for the variant
+    pub downgrade_loss: f64,
+    /// Fraction of the advertised bitrate that has to arrive, less means
+    /// the link can't carry the variant
+    pub min_delivery: f64,
+    /// Loss fraction below which the link counts as clean
+    pub upgrade_loss: f64,
+    /// How long the link has to be bad before a downgrade
+    pub downgrade_after: Duration,
+    /// How long the link has to be clean before trying the next better variant
+    pub upgrade_after: Duration,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            downgrade_loss: 0.05,
+            min_delivery: 0.7,
+            upgrade_loss: 0.01,
+            downgrade_after: Duration::from_secs(5),
+            upgrade_after: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recommendation {
+    Keep,
+    /// The link can't carry the current variant
+    Downgrade(Variant),
+    /// The link was clean long enough to try the better variant
+    Upgrade(Variant),
+}
+
+/// Recommends which of the [`Variant`]s of a description to receive, from
+/// the [`BandwidthEstimate`]s of the current one. The selector only
+/// advises, the application switches, e.g. with [`switch_variant`], and
+/// reports it with [`StreamSelector::switched`].
+///
+/// A downgrade follows a link that stayed bad for
+/// [`AdaptiveConfig::downgrade_after`]. Since the spare capacity of a link
+/// can't be measured without using it, upgrades are tried after the link
+/// was clean for [`AdaptiveConfig::upgrade_after`], and an upgrade that
+/// had to be undone right away doubles the time until the next one.
+#[derive(Debug, Clone)]
+pub struct StreamSelector {
+    variants: Vec<Variant>,
+    current: usize,
+    config: AdaptiveConfig,
+    bad_since: Option<Instant>,
+    clean_since: Option<Instant>,
+    last_upgrade: Option<Instant>,
+    upgrade_backoff: u32,
+}
+
+impl StreamSelector {
+    /// Selects among `variants` ordered from the best to the worst, like
+    /// [`Variant::from_sdp`] returns them, starting with the best one
+    pub fn new(variants: Vec<Variant>) -> Self {
+        Self {
+            variants,
+            current: 0,
+            config: AdaptiveConfig::default(),
+            bad_since: None,
+            clean_since: None,
+            last_upgrade: None,
+            upgrade_backoff: 1,
+        }
+    }
+
+    pub fn config(mut self, config: AdaptiveConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    pub fn current(&self) -> Option<&Variant> {
+        self.variants.get(self.current)
+    }
+
+    /// The variant to receive given the latest estimate of the current one
+    pub fn recommend(&mut self, estimate: &BandwidthEstimate, now: Instant) -> Recommendation {
+        let Some(current) = self.current() else {
+            return Recommendation::Keep;
+        };
+        let config = &self.config;
+        let starved = current
+            .bitrate
+            .is_some_and(|bitrate| (estimate.bitrate as f64) < bitrate as f64 * config.min_delivery);
+        let bad = estimate.loss > config.downgrade_loss || starved;
+        let clean = estimate.loss < config.upgrade_loss && !starved;
+        if !bad {
+            self.bad_since = None;
+        }
+        if !clean {
+            self.clean_since = None;
+        }
+        if bad {
+            let since = *self.bad_since.get_or_insert(now);
+            let worse = self.variants.get(self.current + 1);
+            if let Some(worse) = worse.filter(|_| now.saturating_duration_since(since) >= config.downgrade_after) {
+                return Recommendation::Downgrade(worse.clone());
+            }
+        } else if clean && self.current > 0 {
+            let since = *self.clean_since.get_or_insert(now);
+            let wait = config.upgrade_after * self.upgrade_backoff;
+            if now.saturating_duration_since(since) >= wait {
+                return Recommendation::Upgrade(self.variants[self.current - 1].clone());
+            }
+        }
+        Recommendation::Keep
+    }
+
+    /// The application receives `variant` from `now` on
+    pub fn switched(&mut self, variant: &Variant, now: Instant) {
+        let Some(index) = self.variants.iter().position(|v| v == variant) else {
+            return;
+        };
+        if index < self.current {
+            self.last_upgrade = Some(now);
+        } else if index > self.current {
+            let failed = self
+                .last_upgrade
+                .is_some_and(|at| now.saturating_duration_since(at) < self.config.upgrade_after);
+            self.upgrade_backoff = match failed {
+                true => (self.upgrade_backoff * 2).min(MAX_UPGRADE_BACKOFF),
+                false => 1,
+            };
+            self.last_upgrade = None;
+        }
+        self.current = index;
+        self.bad_since = None;
+        self.clean_since = None;
+    }
+}
+
+/// Replaces the track of `from` with the one of `to` within the session of
+/// the channel behind `cmd_tx`: SETUP of the new track with `transport`,
+/// TEARDOWN of the old one and PLAY of the `aggregate` url. The server has
+/// to allow adding tracks to a playing session, otherwise the session has
+/// to be set up again.
+pub async fn switch_variant(
+    cmd_tx: &mpsc::Sender<Command>,
+    aggregate: &Url,
+    from: &Variant,
+    to: &Variant,
+    transport: Transport,
+) -> CommandResult<SetupResponse> {
+    let url = to.url.clone();
+    let setup = CommandHandle::send(cmd_tx, |tx| Request::Setup(Setup::new(url, transport, tx)));
+    let response = setup.await?.await?;
+    let url = from.url.clone();
+    CommandHandle::send(cmd_tx, |tx| Request::Teardown(Teardown::new(url, tx)))
+        .await?
+        .await?;
+    let url = aggregate.clone();
+    CommandHandle::send(cmd_tx, |tx| Request::Play(Play::new(url, tx)))
+        .await?
+        .await?;
+    log::info!("Switched from {} to {}", from.url, to.url);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::Method;
+    use crate::testing::{MockResponse, MockServer};
+
+    const SDP: &str = "v=0\r\n\
+        m=video 0 RTP/AVP 96\r\n\
+        b=AS:512\r\n\
+        a=control:trackID=2\r\n\
+        m=audio 0 RTP/AVP 0\r\n\
+        a=control:trackID=3\r\n\
+        m=video 0 RTP/AVP 96\r\n\
+        b=TIAS:4000000\r\n\
+        a=control:trackID=1\r\n";
+
+    fn estimate(bitrate: u64, loss: f64) -> BandwidthEstimate {
+        BandwidthEstimate {
+            bitrate,
+            loss,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let sdp = Sdp::try_from(SDP).unwrap();
+        let base = Url::parse("rtsp://cam/live/").unwrap();
+        let variants = Variant::from_sdp(&sdp, &base, "video");
+        let found: Vec<_> = variants.iter().map(|v| (v.media, v.url.as_str(), v.bitrate)).collect();
+        assert_eq!(
+            found,
+            [
+                (2, "rtsp://cam/live/trackID=1", Some(4_000_000)),
+                (0, "rtsp://cam/live/trackID=2", Some(512_000))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_selector() {
+        let sdp = Sdp::try_from(SDP).unwrap();
+        let variants = Variant::from_sdp(&sdp, &Url::parse("rtsp://cam/live/").unwrap(), "video");
+        let mut selector = StreamSelector::new(variants.clone());
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert_eq!(
+            selector.recommend(&estimate(4_000_000, 0.0), at(0)),
+            Recommendation::Keep
+        );
+        // Half of the bitrate arrives, a downgrade once that lasts
+        assert_eq!(
+            selector.recommend(&estimate(2_000_000, 0.0), at(1)),
+            Recommendation::Keep
+        );
+        assert_eq!(
+            selector.recommend(&estimate(2_000_000, 0.0), at(6)),
+            Recommendation::Downgrade(variants[1].clone())
+        );
+        selector.switched(&variants[1], at(6));
+        // The worst variant is kept however bad the link is
+        assert_eq!(
+            selector.recommend(&estimate(100_000, 0.2), at(20)),
+            Recommendation::Keep
+        );
+        assert_eq!(
+            selector.recommend(&estimate(500_000, 0.0), at(30)),
+            Recommendation::Keep
+        );
+        assert_eq!(
+            selector.recommend(&estimate(500_000, 0.0), at(90)),
+            Recommendation::Upgrade(variants[0].clone())
+        );
+
+        // An upgrade undone right away waits twice as long the next time
+        selector.switched(&variants[0], at(90));
+        selector.switched(&variants[1], at(100));
+        assert_eq!(
+            selector.recommend(&estimate(500_000, 0.0), at(100)),
+            Recommendation::Keep
+        );
+        assert_eq!(
+            selector.recommend(&estimate(500_000, 0.0), at(160)),
+            Recommendation::Keep
+        );
+        assert_eq!(
+            selector.recommend(&estimate(500_000, 0.0), at(220)),
+            Recommendation::Upgrade(variants[0].clone())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_switch_variant() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let base = Url::parse("rtsp://cam/live/").unwrap();
+        let setup = MockResponse::ok()
+            .header("Session", "abc")
+            .header("Transport", "RTP/AVP/TCP;unicast;interleaved=2-3");
+        let server = MockServer::new()
+            .expect(
+                Method::Describe,
+                MockResponse::ok().header("Content-Base", base.as_str()).sdp(SDP),
+            )
+            .expect(Method::Setup, setup)
+            .expect(Method::Teardown, MockResponse::ok().header("Session", "abc"))
+            .expect(Method::Play, MockResponse::ok().header("Session", "abc"))
+            .serve(sstream);
+
+        let describe = CommandHandle::send(&cmd_tx, |tx| Request::Describe(Describe::new(base.clone(), tx)));
+        let sdp = describe.await.unwrap().await.unwrap().into_sdp().unwrap();
+        let variants = Variant::from_sdp(&sdp, &base, "video");
+        let transport = "RTP/AVP/TCP;unicast;interleaved=2-3".parse().unwrap();
+        let response = switch_variant(&cmd_tx, &base, &variants[0], &variants[1], transport)
+            .await
+            .unwrap();
+        assert_eq!(response.session.id, "abc");
+        drop(cmd_tx);
+        handle.abort();
+
+        let requests = server.await.unwrap().unwrap();
+        assert_eq!(requests[1].uri, "rtsp://cam/live/trackID=2");
+        // The PLAY after tearing down one track still belongs to the session
+        assert_eq!(requests[2].uri, "rtsp://cam/live/trackID=1");
+        assert_eq!(requests[3].uri, "rtsp://cam/live/");
+        assert_eq!(requests[3].header("Session"), Some("abc"));
+    }
+}
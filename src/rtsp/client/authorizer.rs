@@ -1,6 +1,7 @@
 use crate::rtsp::protocol::*;
+use crate::trace;
 use base64::prelude::*;
-use digest_auth::{AuthContext, HttpMethod, WwwAuthenticateHeader};
+use digest_auth::{AlgorithmType, AuthContext, HttpMethod, WwwAuthenticateHeader};
 use std::borrow::Cow;
 
 use std::option::Option;
@@ -62,6 +63,11 @@ impl Digest {
         );
         Ok(self.www_authenticate.respond(&context)?.to_string())
     }
+
+    /// The hash algorithm of the challenge, e.g. `MD5-sess` or `SHA-256`
+    pub fn algorithm(&self) -> String {
+        self.www_authenticate.algorithm.to_string()
+    }
 }
 
 pub enum Authorizer {
@@ -71,6 +77,7 @@ pub enum Authorizer {
 
 impl Authorizer {
     pub fn answer(&mut self, method: Method, url: &Url) -> Result<Answer> {
+        trace::event!(trace, scheme = self.scheme(), method = %method, url = %url, "Answering challenge");
         match self {
             Authorizer::Basic(basic) => basic.answer(),
             Authorizer::Digest(digest) => digest.answer(method, url),
@@ -78,15 +85,138 @@ impl Authorizer {
     }
 
     pub fn new(user: &str, pass: &str, www_auth: &str) -> Result<Self> {
-        let mut iter = www_auth.splitn(2, ' ');
+        let mut iter = www_auth.trim().splitn(2, ' ');
         let auth_type = iter.next().ok_or(Error::InvalidHeader)?;
         let auth_data = iter.next().ok_or(Error::InvalidHeader)?;
         match auth_type {
-            "Basic" => Ok(Authorizer::Basic(Basic::new(user, pass))),
-            "Digest" => Ok(Authorizer::Digest(Digest::new(user, pass, auth_data)?)),
+            t if t.eq_ignore_ascii_case("Basic") => Ok(Authorizer::Basic(Basic::new(user, pass))),
+            t if t.eq_ignore_ascii_case("Digest") => Ok(Authorizer::Digest(Digest::new(user, pass, auth_data)?)),
             _ => Err(Error::UnknownType),
         }
     }
+
+    /// Answers the strongest of all challenges of the `WWW-Authenticate`
+    /// headers, Digest is preferred over Basic and SHA-512-256 over SHA-256
+    /// over MD5. Challenges that can't be answered are skipped.
+    pub fn select<'a>(user: &str, pass: &str, headers: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+        let mut best: Option<Authorizer> = None;
+        let mut error = Error::InvalidHeader;
+        for challenge in headers.into_iter().flat_map(split_challenges) {
+            match Authorizer::new(user, pass, challenge) {
+                Ok(authorizer) => {
+                    if best.as_ref().is_none_or(|b| authorizer.strength() > b.strength()) {
+                        best = Some(authorizer);
+                    }
+                }
+                Err(e) => {
+                    #[cfg(not(feature = "tracing"))]
+                    log::debug!("Ignoring challenge {}: {}", challenge, e);
+                    trace::event!(debug, challenge, error = %e, "Ignoring challenge");
+                    error = e;
+                }
+            }
+        }
+        #[cfg(feature = "tracing")]
+        if let Some(best) = &best {
+            tracing::debug!(scheme = best.scheme(), algorithm = ?best.algorithm(), "Selected challenge");
+        }
+        best.ok_or(error)
+    }
+
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Authorizer::Basic(_) => "Basic",
+            Authorizer::Digest(_) => "Digest",
+        }
+    }
+
+    /// The hash algorithm of Digest
+    pub fn algorithm(&self) -> Option<String> {
+        match self {
+            Authorizer::Basic(_) => None,
+            Authorizer::Digest(digest) => Some(digest.algorithm()),
+        }
+    }
+
+    /// Schemes of all challenges of the header, e.g. `["Digest", "Basic"]`
+    pub fn schemes(header: &str) -> Vec<String> {
+        split_challenges(header)
+            .into_iter()
+            .filter_map(|challenge| challenge.split_whitespace().next())
+            .map(|scheme| scheme.to_string())
+            .collect()
+    }
+
+    /// Realm of the first challenge of the header that has one
+    pub fn realm(header: &str) -> Option<String> {
+        split_challenges(header).into_iter().find_map(|challenge| {
+            let (_, params) = challenge.split_once(' ')?;
+            let (_, rest) = params.split_once("realm")?;
+            let value = rest.trim_start().strip_prefix('=')?.trim_start();
+            match value.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next(),
+                None => value.split(',').next(),
+            }
+            .map(|realm| realm.trim().to_string())
+        })
+    }
+
+    fn strength(&self) -> u8 {
+        match self {
+            Authorizer::Basic(_) => 0,
+            Authorizer::Digest(digest) => match digest.www_authenticate.algorithm.algo {
+                AlgorithmType::MD5 => 1,
+                AlgorithmType::SHA2_256 => 2,
+                AlgorithmType::SHA2_512_256 => 3,
+            },
+        }
+    }
+
+    /// Whether a Digest challenge of the header only rejects an outdated
+    /// nonce, the credentials are still valid and can be sent again with the new one
+    pub fn is_stale(header: &str) -> bool {
+        split_challenges(header)
+            .into_iter()
+            .any(|challenge| match challenge.split_once(' ') {
+                Some((scheme, data)) if scheme.eq_ignore_ascii_case("Digest") => {
+                    WwwAuthenticateHeader::parse(data).is_ok_and(|h| h.stale)
+                }
+                _ => false,
+            })
+    }
+}
+
+/// Splits a header value into its challenges (RFC 7235 4.1), e.g.
+/// `Digest realm="cam", nonce="1", Basic realm="cam"` into the Digest and
+/// the Basic challenge. A new challenge starts after a comma with a token
+/// followed by a space instead of a `=`.
+fn split_challenges(value: &str) -> Vec<&str> {
+    let starts_challenge = |rest: &str| {
+        let rest = rest.trim_start();
+        match rest.find([' ', '=', ',']) {
+            Some(i) if i > 0 && rest.as_bytes()[i] == b' ' => !rest[i..].trim_start().starts_with('='),
+            _ => false,
+        }
+    };
+    let mut challenges = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted && starts_challenge(&value[i + 1..]) => {
+                challenges.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    challenges.push(value[start..].trim());
+    challenges.retain(|c| !c.is_empty());
+    challenges
 }
 
 #[cfg(test)]
@@ -100,4 +230,66 @@ mod tests {
         let answer = authorizer.answer(Method::Options, &url).unwrap();
         assert_eq!(answer, "Basic dXNlcjpwYXNz");
     }
+
+    #[test]
+    fn test_digest_authorizer_nonce_count() {
+        let challenge = r#"Digest realm="cam", nonce="abc", qop="auth""#;
+        let mut authorizer = Authorizer::new("user", "pass", challenge).unwrap();
+        let url = Url::parse("rtsp://localhost:554/test").unwrap();
+        let first = authorizer.answer(Method::Options, &url).unwrap();
+        let second = authorizer.answer(Method::Describe, &url).unwrap();
+        assert!(first.contains("nc=00000001"));
+        assert!(second.contains("nc=00000002"));
+        assert!(second.contains("cnonce="));
+    }
+
+    #[test]
+    fn test_split_challenges() {
+        let header = r#"Digest realm="a, b", nonce="1", algorithm=MD5, Basic realm="cam",Negotiate abc"#;
+        assert_eq!(
+            split_challenges(header),
+            [
+                r#"Digest realm="a, b", nonce="1", algorithm=MD5"#,
+                r#"Basic realm="cam""#,
+                "Negotiate abc"
+            ]
+        );
+        assert_eq!(split_challenges(r#"Basic realm = "cam", charset="UTF-8""#).len(), 1);
+    }
+
+    #[test]
+    fn test_select_strongest_challenge() {
+        let headers = [
+            r#"Basic realm="cam""#,
+            r#"Digest realm="cam", nonce="1", algorithm=MD5-sess, Digest realm="cam", nonce="1", algorithm=SHA-256"#,
+            r#"Negotiate abc"#,
+        ];
+        let authorizer = Authorizer::select("user", "pass", headers).unwrap();
+        let Authorizer::Digest(digest) = &authorizer else {
+            panic!("expected digest");
+        };
+        assert_eq!(digest.algorithm(), "SHA-256");
+        let authorizer = Authorizer::select("user", "pass", [r#"Negotiate abc, basic realm="cam""#]).unwrap();
+        assert_eq!(authorizer.scheme(), "Basic");
+        assert!(matches!(
+            Authorizer::select("user", "pass", ["Negotiate abc"]),
+            Err(Error::UnknownType)
+        ));
+    }
+
+    #[test]
+    fn test_challenge_schemes_and_realm() {
+        let header = r#"Digest realm="IP Camera", nonce="1", Basic realm="cam""#;
+        assert_eq!(Authorizer::schemes(header), ["Digest", "Basic"]);
+        assert_eq!(Authorizer::realm(header).as_deref(), Some("IP Camera"));
+        assert_eq!(Authorizer::realm("Basic realm=cam").as_deref(), Some("cam"));
+        assert_eq!(Authorizer::realm("Negotiate abc"), None);
+    }
+
+    #[test]
+    fn test_stale_challenge() {
+        assert!(Authorizer::is_stale(r#"Digest realm="cam", nonce="new", stale=true"#));
+        assert!(!Authorizer::is_stale(r#"Digest realm="cam", nonce="new""#));
+        assert!(!Authorizer::is_stale(r#"Basic realm="cam""#));
+    }
 }
@@ -1,16 +1,28 @@
 use super::*;
+use crate::http::{MessageFramer, ParseTextError};
+use crate::metrics::{Component, MemoryBudget};
+use crate::rtcp;
 use crate::rtp;
+use crate::sdp;
+use crate::srtp;
+use crate::stats::{StatsHandle, TrackRecorder};
+use crate::sync::Synchronizer;
+use crate::task;
+use crate::trace;
 use crate::rtsp::*;
-use base64::prelude::*;
 use rustls_pki_types::InvalidDnsNameError;
 use std::collections::HashMap;
 use std::collections::VecDeque;
-use std::vec;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::fmt;
+use rand::Rng;
 use thiserror;
 use tokio::io;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::{mpsc, oneshot};
-use url::Url;
+use bytes::Bytes;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{Duration, Instant};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -48,6 +60,8 @@ impl From<Error> for CommandError {
             Error::UnexpectedStatus(status) => CommandError::UnexpectedStatus(status),
             Error::Unauthorized => CommandError::Unauthorized,
             Error::BadResponse => CommandError::BadResponse,
+            Error::InvalidAuthorization(e) => CommandError::Authorization(e),
+            Error::BufferError(BufferError::BudgetExceeded(e)) => CommandError::BudgetExceeded(e),
             _ => CommandError::Unknown,
         }
     }
@@ -57,37 +71,292 @@ type Result<T> = std::result::Result<T, Error>;
 
 type CSeq = u32;
 
+/// Default time to wait for a response
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default number of requests sent without waiting for their responses
+pub const DEFAULT_MAX_OUTSTANDING: usize = 8;
+/// Interval of RTCP receiver reports suggested by RFC 3550 6.2
+pub const DEFAULT_RTCP_INTERVAL: Duration = Duration::from_secs(5);
+/// Default number of times a request is sent again after consecutive
+/// authentication challenges, see [`Channel::auth_retry_policy`]
+pub const DEFAULT_AUTH_RETRIES: u32 = 3;
+/// Option tag of the ONVIF audio backchannel, see [`Channel::require`]
+pub const ONVIF_BACKCHANNEL: &str = "www.onvif.org/ver20/backchannel";
+
+/// The proxy headers that were received, written as request headers
+struct ProxyHeaders<'a>(&'a [(String, Option<String>)]);
+
+impl fmt::Display for ProxyHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in self.0 {
+            if let Some(value) = value {
+                write!(f, "{}: {}\r\n", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A request whose caller gave up or that timed out, its response is
+/// discarded if it arrives before the deadline
+struct Abandoned {
+    method: Method,
+    url: url::Url,
+    deadline: Instant,
+}
+
+/// A track of the last DESCRIBE response, applied to its RTP channel once
+/// a SETUP of its control maps one
+struct DescribedTrack {
+    control: String,
+    // Payload types, the clock rate and codec are the ones of the first
+    formats: Vec<u8>,
+    clock_rate: Option<u32>,
+    codec: Option<sdp::Codec>,
+    extensions: rtp::ExtensionMap,
+}
+
+impl DescribedTrack {
+    /// The tracks of a description that have a control
+    fn parse_all(body: &str) -> Vec<Self> {
+        let Ok(sdp) = sdp::Sdp::try_from(body) else {
+            return Vec::new();
+        };
+        sdp.media
+            .iter()
+            .filter_map(|media| {
+                let rtpmap = media.formats.first().and_then(|pt| media.rtpmap(*pt));
+                Some(Self {
+                    control: media.control()?.to_string(),
+                    formats: media.formats.clone(),
+                    clock_rate: rtpmap.as_ref().map(|rtpmap| rtpmap.timebase),
+                    codec: rtpmap.map(|rtpmap| rtpmap.codec),
+                    extensions: rtp::ExtensionMap::from_media(media),
+                })
+            })
+            .collect()
+    }
+}
+
+struct Pending {
+    req: Request,
+    deadline: Instant,
+    sent: Instant,
+    // Number of earlier attempts that timed out
+    attempt: u32,
+    // Sent with an Authorization or Proxy-Authorization header
+    authorized: bool,
+    proxy_authorized: bool,
+}
+
+/// A retry waiting for the delay of its [`RetryPolicy`]
+struct Delayed {
+    due: Instant,
+    req: Request,
+    attempt: u32,
+    // Retransmissions of datagram requests keep their CSeq
+    cseq: Option<CSeq>,
+}
+
 pub struct Channel<Stream> {
     stream: Stream,
     cseq: CSeq,
     buffer_rx: Buffer,
     buffer_tx: Buffer,
+    // Scans the response at the start of the receive buffer across reads,
+    // it is parsed once complete
+    rx_framer: MessageFramer,
     cmd_rx: mpsc::Receiver<Command>,
-    req_pending: HashMap<CSeq, Request>,
+    req_pending: HashMap<CSeq, Pending>,
     req_retry: VecDeque<Request>,
+    req_delayed: Vec<Delayed>,
+    // Requests waiting for a free slot or for the session to be established
+    req_queue: VecDeque<Request>,
+    max_outstanding: usize,
+    // Requests the caller gave up on, their responses are discarded
+    req_abandoned: HashMap<CSeq, Abandoned>,
+    timeout: Duration,
+    // Retries of timed out requests
+    retry_policy: Arc<dyn RetryPolicy>,
+    // Retries of requests answered with 401 or 407
+    auth_retry_policy: Arc<dyn RetryPolicy>,
+    // Bytes read from the stream at once
+    read_size: usize,
+    max_header_size: usize,
+    max_body_size: usize,
+    user_agent: String,
+    // Requests and responses are datagrams (rtspu), retransmissions keep their CSeq
+    datagram: bool,
+    // Answers the last challenge, attached to every following request
     authorizer: Option<Authorizer>,
+    proxy_authorizer: Option<Authorizer>,
+    // Consecutive requests retried with credentials
+    auth_retries: u32,
+    session: Option<Session>,
     user: Option<String>,
     pass: String,
+    // Credentials for 407 responses, the RTSP credentials are used if unset
+    proxy_user: Option<String>,
+    proxy_pass: String,
+    // Option tags sent as Require with DESCRIBE, SETUP and PLAY
+    require: Vec<String>,
+    // Entry of the client in the Via header of its requests
+    via: Option<Via>,
+    // Headers proxies insert into responses by name, with the last value
+    // received, sent back with every following request
+    proxy_headers: Vec<(String, Option<String>)>,
+    // RTP/RTCP kind of the interleaved channels negotiated by SETUP and
+    // the other channel of their pair
+    interleaved: HashMap<u8, (Traffic, u8)>,
+    // RTCP channel of every negotiated RTP channel, shared with the interleaved writers
+    rtcp_channels: Arc<Mutex<HashMap<u8, u8>>>,
+    usage: UsageMeter,
+    latency: LatencyMeter,
+    server_info: ServerInfoHandle,
+    // Receives the clock skew of the server whenever a response updates it
+    stats: Option<StatsHandle>,
+    // Absolute Range times of PLAY are converted to the server's clock
+    server_clock_ranges: bool,
+    // Largest accepted interleaved payload, None means no limit besides the 16 bit length
+    max_frame_size: Option<usize>,
+    // Blocksize requested from the server, derived from max_frame_size if unset
+    blocksize: Option<usize>,
+    // Bytes of an oversized frame that still have to be discarded
+    skip_remaining: usize,
+    // Buffer sizes were set by the user instead of derived from the SDP
+    limits_pinned: bool,
+    // Tolerated deviations of the server, set by the user or detected once
+    quirks: Quirks,
+    quirks_pinned: bool,
+    // Responses are validated and their violations reported
+    strict: bool,
+    // Requests the server sent to the client
+    server_request_tx: Option<mpsc::Sender<IncomingRequest>>,
+    event_tx: Option<mpsc::Sender<Event>>,
+    // Why the channel shuts down, if not on request
+    disconnect: Option<DisconnectReason>,
+    // Raw interleaved frames for debugging, sent before any parsing
+    tap: Option<broadcast::Sender<(u8, Bytes)>>,
+    // Per source reordering of the RTP packets, tagged with their track
+    demux: Option<(rtp::Demuxer, mpsc::Sender<rtp::DemuxedPacket>)>,
+    // SRTP contexts by RTP channel, the RTCP channel follows the RTP one
+    srtp: HashMap<u8, srtp::SrtpContext>,
+    // Interval of the receiver reports, None if they are disabled
+    rtcp_interval: Option<Duration>,
+    next_report: Option<Instant>,
+    // SSRC of the reports, the CNAME is derived from it
+    rtcp_ssrc: u32,
+    // Reception statistics and clock rates by RTP channel
+    reception: HashMap<u8, rtcp::ReceptionStats>,
+    clock_rates: HashMap<u8, u32>,
+    // Recorders of the tracks of `stats` by RTP channel
+    recorders: HashMap<u8, TrackRecorder>,
+    // Tracks of the last DESCRIBE response, a TEARDOWN of one of several
+    // keeps the session
+    described: Vec<DescribedTrack>,
+    // Payload types of the set up RTP channels, a channel is removed once
+    // its mismatch was reported
+    channel_formats: HashMap<u8, Vec<u8>>,
+    channel_codecs: HashMap<u8, sdp::Codec>,
+    // When the last PLAY was sent, and the channels that received a packet
+    // since then with whether a keyframe started
+    play_sent: Option<Instant>,
+    started: HashMap<u8, bool>,
+    // Fed with the sender reports of the interleaved tracks
+    synchronizer: Option<Arc<Mutex<Synchronizer>>>,
+    // Reception quality of the interleaved tracks, updated on their RTCP packets
+    quality: Option<QualityHandle>,
+    // Interleaved frames waiting for room in the send buffer, and the ones
+    // of the writers handed out
+    frame_queue: VecDeque<(u8, Bytes)>,
+    frame_tx: mpsc::Sender<(u8, Bytes)>,
+    frame_rx: mpsc::Receiver<(u8, Bytes)>,
     // For sending processed packets to the client
     packet_tx: mpsc::Sender<rtp::Packet>,
+    // RTP packets received per channel, traced when the channel stops
+    #[cfg(feature = "tracing")]
+    packets: HashMap<u8, u64>,
     shutdown: bool,
+    task_name: String,
 }
 
 impl<Stream: AsyncReadExt + AsyncWriteExt + Send + Unpin + 'static> Channel<Stream> {
     pub fn new(stream: Stream, cmd_rx: mpsc::Receiver<Command>, packet_tx: mpsc::Sender<rtp::Packet>) -> Self {
+        let config = ChannelConfig::default();
+        let (frame_tx, frame_rx) = mpsc::channel(DEFAULT_WRITER_CAPACITY);
         Self {
             stream,
             cseq: 1,
-            buffer_rx: Buffer::new(512 * 1024),
-            buffer_tx: Buffer::new(512 * 1024),
+            buffer_rx: Buffer::new(ProfileLimits::default().receive_buffer),
+            buffer_tx: Buffer::new(ProfileLimits::default().send_buffer),
+            rx_framer: MessageFramer::new(),
             cmd_rx,
             req_pending: HashMap::new(),
             req_retry: VecDeque::new(),
+            req_delayed: Vec::new(),
+            req_queue: VecDeque::new(),
+            max_outstanding: config.max_outstanding,
+            req_abandoned: HashMap::new(),
+            timeout: config.timeout,
+            retry_policy: Arc::new(Backoff::immediate().max_retries(config.retries)),
+            auth_retry_policy: Arc::new(Backoff::immediate().max_retries(DEFAULT_AUTH_RETRIES)),
+            read_size: config.read_size,
+            max_header_size: config.max_header_size,
+            max_body_size: config.max_body_size,
+            user_agent: config.user_agent,
+            datagram: false,
             authorizer: None,
+            proxy_authorizer: None,
+            auth_retries: 0,
+            session: None,
             user: None,
             pass: String::new(),
+            proxy_user: None,
+            proxy_pass: String::new(),
+            require: Vec::new(),
+            via: None,
+            proxy_headers: Vec::new(),
+            interleaved: HashMap::new(),
+            rtcp_channels: Arc::default(),
+            usage: UsageMeter::new(),
+            latency: LatencyMeter::new(),
+            server_info: ServerInfoHandle::new(),
+            stats: None,
+            server_clock_ranges: false,
+            max_frame_size: None,
+            blocksize: None,
+            skip_remaining: 0,
+            limits_pinned: false,
+            quirks: Quirks::none(),
+            quirks_pinned: false,
+            strict: config.strict,
+            server_request_tx: None,
+            event_tx: None,
+            disconnect: None,
+            tap: None,
+            demux: None,
+            srtp: HashMap::new(),
+            rtcp_interval: None,
+            next_report: None,
+            rtcp_ssrc: rtp::random_ssrc(),
+            reception: HashMap::new(),
+            clock_rates: HashMap::new(),
+            recorders: HashMap::new(),
+            described: Vec::new(),
+            channel_formats: HashMap::new(),
+            channel_codecs: HashMap::new(),
+            play_sent: None,
+            started: HashMap::new(),
+            synchronizer: None,
+            quality: None,
+            frame_queue: VecDeque::new(),
+            frame_tx,
+            frame_rx,
             packet_tx,
+            #[cfg(feature = "tracing")]
+            packets: HashMap::new(),
             shutdown: false,
+            task_name: task::CHANNEL.to_string(),
         }
     }
 
@@ -101,34 +370,412 @@ impl<Stream: AsyncReadExt + AsyncWriteExt + Send + Unpin + 'static> Channel<Stre
         self
     }
 
-    pub fn create_authorizer(user: &Option<String>, pass: &str, www_authenticate: Option<&str>) -> Result<Authorizer> {
+    /// Credentials for a proxy answering with 407 Proxy Authentication Required
+    pub fn proxy_credentials(mut self, user: &str, pass: &str) -> Self {
+        self.proxy_user = Some(user.to_string());
+        self.proxy_pass = pass.to_string();
+        self
+    }
+
+    /// Requires the server to support the option tag `feature` for DESCRIBE,
+    /// SETUP and PLAY, e.g. [`ONVIF_BACKCHANNEL`] so that the SDP includes
+    /// the `a=sendonly` backchannel media. Its RTP packets are then sent
+    /// with [`Command::Interleaved`] on the channel negotiated by SETUP.
+    pub fn require(mut self, feature: &str) -> Self {
+        self.require.push(feature.to_string());
+        self
+    }
+
+    /// Adds `via` as Via header to every request, e.g. when the client
+    /// forwards the requests of a proxy. A response listing it twice passed
+    /// this proxy before, its request fails with [`CommandError::LoopDetected`].
+    pub fn via(mut self, via: Via) -> Self {
+        self.via = Some(via);
+        self
+    }
+
+    /// Sends the header `name` back with every request once a response
+    /// carried it, e.g. the routing header a load balancing proxy inserts
+    /// to keep the requests of a session on the same server
+    pub fn proxy_header(mut self, name: &str) -> Self {
+        self.proxy_headers.push((name.to_string(), None));
+        self
+    }
+
+    /// Applies all settings of `config`, see [`ChannelConfig`]
+    pub fn config(mut self, config: ChannelConfig) -> Self {
+        if let Some(limits) = config.limits {
+            self = self.limits(limits);
+        }
+        if let Some(quirks) = config.quirks {
+            self = self.quirks(quirks);
+        }
+        self.read_size(config.read_size)
+            .max_header_size(config.max_header_size)
+            .max_body_size(config.max_body_size)
+            .user_agent(&config.user_agent)
+            .timeout(config.timeout)
+            .retries(config.retries)
+            .max_outstanding(config.max_outstanding)
+            .strict(config.strict)
+    }
+
+    /// Bytes read from the connection at once, the receive buffer must
+    /// have room for them or the channel fails with [`Error::BufferError`]
+    pub fn read_size(mut self, size: usize) -> Self {
+        self.read_size = size.max(1);
+        self.fit_receive_buffer();
+        self
+    }
+
+    /// Longest status line and headers of a response, longer ones fail the
+    /// channel with [`Error::HeaderTooLong`]
+    pub fn max_header_size(mut self, size: usize) -> Self {
+        self.max_header_size = size;
+        self.fit_receive_buffer();
+        self
+    }
+
+    /// Longest body of a response or server request, longer ones fail the
+    /// channel with [`Error::RequestTooLong`]
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self.fit_receive_buffer();
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Sets the name of the spawned channel task, e.g. to tell
+    /// several cameras apart in tokio-console.
+    pub fn name(mut self, name: &str) -> Self {
+        self.task_name = name.to_string();
+        self
+    }
+
+    /// Sends every interleaved frame as (channel, payload) to `tap` before
+    /// it is parsed, e.g. to capture what a camera sends.
+    pub fn tap(mut self, tap: broadcast::Sender<(u8, Bytes)>) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    /// Also sends the RTP packets to `tx` split by channel and SSRC, each
+    /// source reordered according to `delivery` and the packets tagged with
+    /// their track in the order of the SETUP responses
+    pub fn demux(mut self, tx: mpsc::Sender<rtp::DemuxedPacket>, delivery: rtp::Delivery) -> Self {
+        self.demux = Some((rtp::Demuxer::new(delivery), tx));
+        self
+    }
+
+    /// Delivers the packets of `track` in `delivery` mode instead of the
+    /// one of [`Channel::demux`], e.g. [`Delivery::Passthrough`](rtp::Delivery::Passthrough)
+    /// for a track with a jitter buffer of its own. Has no effect without
+    /// [`Channel::demux`].
+    pub fn track_delivery(mut self, track: usize, delivery: rtp::Delivery) -> Self {
+        self.demux = self
+            .demux
+            .map(|(demuxer, tx)| (demuxer.track_delivery(track, delivery), tx));
+        self
+    }
+
+    /// Authenticates and decrypts the SRTP packets of the interleaved
+    /// `rtp_channel` and the SRTCP packets of the following channel with
+    /// `ctx`, e.g. keyed from the `a=crypto` attribute of the media.
+    /// Packets that fail are dropped before they reach the reorder queue.
+    pub fn srtp(mut self, rtp_channel: u8, ctx: srtp::SrtpContext) -> Self {
+        self.srtp.insert(rtp_channel, ctx);
+        self
+    }
+
+    /// Sends the requests the server sends on the connection to `tx`, e.g.
+    /// ANNOUNCE on stream changes. They are answered by the channel, with
+    /// 200 OK for OPTIONS, GET_PARAMETER, SET_PARAMETER, ANNOUNCE and REDIRECT.
+    pub fn server_requests(mut self, tx: mpsc::Sender<IncomingRequest>) -> Self {
+        self.server_request_tx = Some(tx);
+        self
+    }
+
+    /// Sends lifecycle [`Event`]s to `tx`, e.g. to reconnect once the
+    /// channel was disconnected. Events are dropped if `tx` is full, except
+    /// the final [`Event::Disconnected`] which waits for room.
+    pub fn events(mut self, tx: mpsc::Sender<Event>) -> Self {
+        self.event_tx = Some(tx);
+        self
+    }
+
+    /// Validates every response against RFC 7826, e.g. to test camera
+    /// firmware, see [`validate_response`]. Violations are logged and
+    /// reported as [`Event::SpecViolation`], the responses are processed
+    /// as usual. The values of the headers are checked before the quirks
+    /// normalize them.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Time to wait for the response to a request before it fails with
+    /// [`CommandError::Timeout`]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends a RTCP receiver report and SDES for every interleaved track
+    /// about every `interval`, randomized as RFC 3550 6.3.1 asks, e.g.
+    /// [`DEFAULT_RTCP_INTERVAL`]. Each goes to the RTCP channel of its track.
+    /// Some servers close sessions without them. Tracks encrypted with SRTP
+    /// are not reported. The timer runs in the channel task, no task is
+    /// spawned for it.
+    pub fn receiver_reports(mut self, interval: Duration) -> Self {
+        self.rtcp_interval = Some(interval);
+        self
+    }
+
+    /// Passes the sender reports of the interleaved tracks to `synchronizer`,
+    /// e.g. the one of a [`FrameStream`](rtp::FrameStream), so RTP timestamps
+    /// map to the sender's wall clock. Requires the DESCRIBE and SETUP of
+    /// the tracks to go through the channel for their clock rates.
+    pub fn synchronizer(mut self, synchronizer: Arc<Mutex<Synchronizer>>) -> Self {
+        self.synchronizer = Some(synchronizer);
+        self
+    }
+
+    /// Sends idempotent requests (OPTIONS, DESCRIBE, GET_PARAMETER,
+    /// TEARDOWN) up to `retries` more times after they timed out, see
+    /// [`Channel::retry_policy`] to wait between the attempts
+    pub fn retries(self, retries: u32) -> Self {
+        self.retry_policy(Backoff::immediate().max_retries(retries))
+    }
+
+    /// Decides whether and when idempotent requests are sent again after
+    /// they timed out, requests of datagram channels are always retransmitted
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Decides whether and when a request answered with 401 or 407 is sent
+    /// again with credentials, the attempts count the consecutive challenges.
+    /// Retries are immediate and limited to [`DEFAULT_AUTH_RETRIES`] by
+    /// default, e.g. for a server that keeps answering with a stale nonce.
+    /// Rejected credentials always fail the request.
+    pub fn auth_retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.auth_retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Limits the number of requests that are sent before their responses
+    /// arrived, further requests are queued in order. Responses are matched
+    /// by CSeq, so servers may answer pipelined requests in any order.
+    pub fn max_outstanding(mut self, max: usize) -> Self {
+        self.max_outstanding = max.max(1);
+        self
+    }
+
+    /// For datagram based streams such as the [`DatagramStream`](super::DatagramStream)
+    /// of a `rtspu://` url. Requests of any method are retransmitted with
+    /// their CSeq after the [timeout](Channel::timeout), up to the number of
+    /// [retries](Channel::retries), and duplicate responses are discarded.
+    /// Only one request is outstanding, so every datagram holds one message.
+    pub fn datagram(mut self) -> Self {
+        self.datagram = true;
+        self.max_outstanding = 1;
+        self
+    }
+
+    /// Limits the payload size of interleaved frames. Larger frames are
+    /// discarded and the limit is advertised to the server as Blocksize.
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = Some(size);
+        self
+    }
+
+    /// Requests smaller media packets with the Blocksize header on SETUP
+    /// and PLAY, e.g. after a [`MtuIssue`] was reported for UDP.
+    pub fn blocksize(mut self, size: usize) -> Self {
+        self.blocksize = Some(size);
+        self
+    }
+
+    /// Sizes the buffers for the given profile. By default the profile is
+    /// derived from the SDP of the first DESCRIBE response, so audio-only
+    /// and metadata sessions shrink their buffers automatically.
+    pub fn profile(self, profile: MediaProfile) -> Self {
+        self.limits(profile.limits())
+    }
+
+    /// Sets custom buffer sizes, which disables the profile detection
+    pub fn limits(mut self, limits: ProfileLimits) -> Self {
+        self.apply_limits(limits);
+        self.limits_pinned = true;
+        self
+    }
+
+    /// Tolerates the deviations of `quirks`, which disables detecting them
+    /// from the Server header of the first response
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self.quirks_pinned = true;
+        self.rx_framer = self.framer();
+        self
+    }
+
+    /// Charges the receive and send buffers to `budget`. Once it is
+    /// exhausted, requests fail with [`CommandError::BudgetExceeded`] and
+    /// the channel shuts down if it can't receive anymore.
+    pub fn budget(mut self, budget: MemoryBudget) -> Self {
+        self.buffer_rx = Buffer::new(self.buffer_rx.max_capacity()).budget(budget.clone(), Component::ReceiveBuffer);
+        self.buffer_tx = Buffer::new(self.buffer_tx.max_capacity()).budget(budget, Component::SendBuffer);
+        self
+    }
+
+    /// Records the server's details into `info` instead of a handle of its
+    /// own, e.g. to keep them across the channels of reconnects
+    pub fn share_server_info(mut self, info: ServerInfoHandle) -> Self {
+        self.server_info = info;
+        self
+    }
+
+    /// Publishes the metrics of every interleaved track to `stats`, by the
+    /// number of its RTP channel, and the [`ClockSkew`](crate::sync::ClockSkew)
+    /// of the server estimated from the Date headers of its responses
+    pub fn stats(mut self, stats: StatsHandle) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Converts the absolute `clock` times of the Range of PLAY requests
+    /// from the local clock to the server's with the estimated skew, for
+    /// recorders whose clocks drift. Off by default, ranges are sent as
+    /// given until the skew is known.
+    pub fn server_clock_ranges(mut self, enabled: bool) -> Self {
+        self.server_clock_ranges = enabled;
+        self
+    }
+
+    /// Handle to send interleaved frames to the server besides the frames
+    /// of [`Command::Interleaved`], see [`InterleavedWriter`]
+    pub fn interleaved_writer(&self) -> InterleavedWriter {
+        InterleavedWriter::new(self.frame_tx.clone(), self.rtcp_channels.clone())
+    }
+
+    /// Publishes the [`TrackQuality`] of every interleaved track to
+    /// `quality` whenever an RTCP packet of the track arrives
+    pub fn share_quality(mut self, quality: QualityHandle) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    fn apply_limits(&mut self, limits: ProfileLimits) {
+        self.buffer_rx.set_max_capacity(limits.receive_buffer);
+        self.buffer_tx.set_max_capacity(limits.send_buffer);
+        self.fit_receive_buffer();
+    }
+
+    /// Raises the receive buffer to hold the largest interleaved frame or
+    /// response and a read on top, smaller buffers would stall the channel
+    fn fit_receive_buffer(&mut self) {
+        let message = MAX_INTERLEAVED_FRAME.max(self.max_header_size + self.max_body_size);
+        let min = message + self.read_size;
+        if self.buffer_rx.max_capacity() < min {
+            self.buffer_rx.set_max_capacity(min);
+        }
+    }
+
+    /// Returns a handle to the traffic counters of this channel,
+    /// which keeps working after the channel has been started.
+    pub fn usage(&self) -> UsageMeter {
+        self.usage.clone()
+    }
+
+    /// Returns a handle to the request latency histograms of this channel
+    pub fn latency(&self) -> LatencyMeter {
+        self.latency.clone()
+    }
+
+    /// Returns a handle to what the responses told about the server
+    pub fn server_info(&self) -> ServerInfoHandle {
+        self.server_info.clone()
+    }
+
+    fn emit(&self, event: Event) {
+        if let Some(tx) = &self.event_tx {
+            if let Err(e) = tx.try_send(event) {
+                log::warn!("Dropping channel event: {}", e);
+            }
+        }
+    }
+
+    /// Answers the strongest challenge of all `WWW-Authenticate` headers
+    pub fn create_authorizer(user: &Option<String>, pass: &str, www_authenticate: &[&str]) -> Result<Authorizer> {
         match www_authenticate {
-            Some(www_authenticate) => match user {
-                Some(user) => Ok(Authorizer::new(user, pass, www_authenticate)?),
+            [] => Err(Error::BadResponse),
+            headers => match user {
+                Some(user) => Ok(Authorizer::select(user, pass, headers.iter().copied())?),
                 None => Err(Error::Unauthorized),
             },
-            None => Err(Error::BadResponse),
         }
     }
 
     fn read_rtsp_packet(&mut self) -> Result<usize> {
         let read_buf = self.buffer_rx.get_read_slice();
+        let framed = match self.rx_framer.feed(read_buf) {
+            Err(ParseTextError::ContentLengthTooLarge) => return Err(Error::RequestTooLong),
+            framed => framed.map_err(ParseError::from)?,
+        };
+        let Some(length) = framed else {
+            let Some(length) = self.rx_framer.message_bytes() else {
+                // The body of a header without Content-Length ends with the connection
+                let too_long = match self.rx_framer.header_length() {
+                    Some(header_length) => read_buf.len() - header_length > self.max_body_size,
+                    None => read_buf.len() > self.max_header_size,
+                };
+                return Err(match (too_long, self.rx_framer.header_length()) {
+                    (true, Some(_)) => Error::RequestTooLong,
+                    (true, None) => Error::HeaderTooLong,
+                    (false, _) => Error::IncompleteResponse,
+                });
+            };
+            return Err(match length - read_buf.len() > self.max_body_size {
+                true => Error::RequestTooLong,
+                false => Error::IncompleteResponse,
+            });
+        };
+        let body_length = length - self.rx_framer.header_length().unwrap_or(length);
+        self.rx_framer = self.framer();
+        let read_buf = &read_buf[..length];
         let mut cseq: Option<CSeq> = None;
-        let mut www_authenticate: Option<&str> = None;
+        let mut www_authenticate: Vec<&str> = Vec::new();
+        let mut proxy_authenticate: Vec<&str> = Vec::new();
         let mut status: Option<Status> = None;
-        let mut body: Option<&str> = None;
-        let mut headers: Vec<Header> = Vec::new();
-        let mut parser = ResponseParser::new();
-        while let Some(item) = parser.parse_next(read_buf)? {
+        let mut body: Option<&[u8]> = None;
+        let mut headers: Vec<Header> = Vec::with_capacity(16);
+        let mut parser = ResponseParser::new().lenient_headers(self.quirks.lenient_headers);
+        if self.quirks.body_until_close {
+            parser = parser.body_length(body_length);
+        }
+        loop {
+            let item = match parser.parse_next(read_buf) {
+                Ok(Some(item)) => item,
+                // A line is still incomplete, the header limit is checked below
+                Ok(None) => break,
+                Err(e) if e.is_incomplete() => break,
+                Err(e) => return Err(e.into()),
+            };
             match item {
                 ParseItem::Header(h) => {
                     if h.name.eq_ignore_ascii_case("cseq") {
                         cseq = Some(h.value.parse().map_err(|_| Error::InvalidCSeq)?);
                     } else if h.name.eq_ignore_ascii_case("www-authenticate") {
-                        www_authenticate = Some(h.value);
-                    } else {
-                        headers.push(Header::new(h.name, h.value));
+                        www_authenticate.push(h.value);
+                    } else if h.name.eq_ignore_ascii_case("proxy-authenticate") {
+                        proxy_authenticate.push(h.value);
                     }
+                    headers.push(Header::new(h.name, h.value));
                 }
                 ParseItem::Status(s) => {
                     status = Some(s);
@@ -140,35 +787,225 @@ impl<Stream: AsyncReadExt + AsyncWriteExt + Send + Unpin + 'static> Channel<Stre
             }
         }
         if !parser.is_done() {
-            let bytes = parser.missing_bytes().ok_or(if read_buf.len() > 1024 {
-                Error::HeaderTooLong
-            } else {
-                Error::IncompleteResponse
-            })?;
-            if bytes > 32 * 1024 {
-                return Err(Error::RequestTooLong);
-            } else {
-                return Err(Error::IncompleteResponse);
-            }
+            return Err(Error::BadResponse);
+        }
+        // Only the values the quirks rewrite are allocated, the rest borrow the read buffer
+        let normalized: Vec<(usize, String)> = headers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| Some((i, self.quirks.normalize(h)?)))
+            .collect();
+        let detected = match self.quirks_pinned {
+            true => None,
+            false => Self::detect_quirks(&headers),
+        };
+        if let Some(quirks) = detected {
+            self.quirks = quirks;
+            self.quirks_pinned = true;
+            self.rx_framer = self.framer();
         }
+        self.usage.received(Traffic::Control, parser.parsed_bytes());
         let cseq = cseq.ok_or(Error::InvalidCSeq)?;
-        let cmd = self.req_pending.remove(&cseq).ok_or(Error::InvalidCSeq)?;
+        if let Some(abandoned) = self.req_abandoned.remove(&cseq) {
+            let n = parser.parsed_bytes();
+            // The server set the track up anyway, the session and its channels are in use
+            if abandoned.method == Method::Setup && status == Some(Status::OK) {
+                Self::update_session(&mut self.session, Method::Setup, &headers);
+                if let Some(channel) = Self::update_interleaved(&mut self.interleaved, &self.rtcp_channels, &headers) {
+                    self.map_track(&abandoned.url, channel);
+                }
+            }
+            log::debug!("Discarding response to abandoned request {}", cseq);
+            return Ok(n);
+        }
+        let Some(pending) = self.req_pending.remove(&cseq) else {
+            if self.datagram {
+                log::debug!("Discarding duplicate response to retransmitted request {}", cseq);
+                return Ok(parser.parsed_bytes());
+            }
+            return Err(Error::InvalidCSeq);
+        };
+        let elapsed = pending.sent.elapsed();
+        self.latency.record(pending.req.method(), elapsed);
+        let received = SystemTime::now();
+        self.server_info.record_date(&headers, received - elapsed, received);
+        if let Some(stats) = &self.stats {
+            stats.set_clock_skew(self.server_info.snapshot().clock_skew);
+        }
+        let cmd = pending.req;
+        Self::update_proxy_headers(&mut self.proxy_headers, &headers);
+        if self.via.as_ref().is_some_and(|via| Self::via_loop(via, &headers)) {
+            log::warn!("{} {} looped through the proxy chain", cmd.method(), cmd.url());
+            cmd.cancel(CommandError::LoopDetected);
+            return Ok(parser.parsed_bytes());
+        }
+        if let (true, Some(status)) = (self.strict, status) {
+            for violation in validate_response(cmd.method(), status, cmd.transport(), &headers, body) {
+                log::warn!("{} response to {} {}: {}", status, cmd.method(), cmd.url(), violation);
+                self.emit(Event::SpecViolation {
+                    method: cmd.method(),
+                    status,
+                    violation,
+                });
+            }
+        }
+        for (i, value) in &normalized {
+            headers[*i].value = value;
+        }
+        trace::event!(
+            debug,
+            cseq,
+            method = %cmd.method(),
+            status = ?status,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "Received response"
+        );
         if let Some(status) = status {
+            if !matches!(status, Status::Unauthorized | Status::ProxyAuthenticationRequired) {
+                self.auth_retries = 0;
+            }
             match status {
-                Status::Unauthorized => {
-                    let result = Self::create_authorizer(&self.user, &self.pass, www_authenticate);
-                    match result {
-                        Ok(authorizer) => {
-                            self.authorizer = Some(authorizer);
-                            self.req_retry.push_back(cmd);
+                Status::Unauthorized | Status::ProxyAuthenticationRequired if cmd.is_closed() => {
+                    log::debug!("Not retrying abandoned request {}", cseq);
+                }
+                Status::Unauthorized | Status::ProxyAuthenticationRequired => {
+                    let proxy = status == Status::ProxyAuthenticationRequired;
+                    let (challenge, authorized) = match proxy {
+                        true => (proxy_authenticate, pending.proxy_authorized),
+                        false => (www_authenticate, pending.authorized),
+                    };
+                    let method = cmd.method();
+                    let realm = challenge.iter().find_map(|c| Authorizer::realm(c));
+                    self.emit(Event::AuthChallenge {
+                        method,
+                        proxy,
+                        schemes: challenge.iter().flat_map(|c| Authorizer::schemes(c)).collect(),
+                        realm: realm.clone(),
+                    });
+                    let failure = if authorized && !challenge.iter().any(|c| Authorizer::is_stale(c)) {
+                        // The credentials were rejected, retrying would loop forever
+                        cmd.cancel(CommandError::Unauthorized);
+                        Some(AuthFailure::Rejected)
+                    } else {
+                        let (user, pass) = match &self.proxy_user {
+                            Some(_) if proxy => (&self.proxy_user, &self.proxy_pass),
+                            _ => (&self.user, &self.pass),
+                        };
+                        match Self::create_authorizer(user, pass, &challenge) {
+                            Ok(authorizer) => {
+                                self.emit(Event::AuthSchemeSelected {
+                                    scheme: authorizer.scheme().to_string(),
+                                    algorithm: authorizer.algorithm(),
+                                    proxy,
+                                });
+                                match proxy {
+                                    true => self.proxy_authorizer = Some(authorizer),
+                                    false => self.authorizer = Some(authorizer),
+                                }
+                                self.auth_retries += 1;
+                                match self.auth_retry_policy.delay(self.auth_retries) {
+                                    Some(delay) => {
+                                        trace::event!(
+                                            info,
+                                            cseq,
+                                            method = %method,
+                                            proxy,
+                                            attempt = self.auth_retries,
+                                            "Retrying after authentication challenge"
+                                        );
+                                        self.emit(Event::UnauthorizedRetry {
+                                            method,
+                                            proxy,
+                                            attempt: self.auth_retries,
+                                        });
+                                        match delay.is_zero() {
+                                            true => self.req_retry.push_back(cmd),
+                                            false => self.delay_request(cmd, delay, 0, None),
+                                        }
+                                        None
+                                    }
+                                    None => {
+                                        cmd.cancel(CommandError::Unauthorized);
+                                        Some(AuthFailure::RetriesExhausted)
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                let failure = match &e {
+                                    Error::Unauthorized => AuthFailure::MissingCredentials,
+                                    Error::InvalidAuthorization(AuthorizerError::UnknownType) => {
+                                        AuthFailure::UnsupportedScheme
+                                    }
+                                    _ => AuthFailure::InvalidChallenge,
+                                };
+                                cmd.cancel(e.into());
+                                Some(failure)
+                            }
                         }
-                        Err(e) => cmd.cancel(e.into()),
+                    };
+                    if let Some(failure) = failure {
+                        trace::event!(warn, cseq, method = %method, proxy, failure = ?failure, "Authentication failed");
+                        self.emit(Event::AuthFailed {
+                            method,
+                            proxy,
+                            realm,
+                            failure,
+                        });
                     }
                 }
                 Status::OK => {
-                    cmd.handle_response(status, &headers, body.ok_or(Error::BadResponse)?);
+                    let track_teardown = cmd.method() == Method::Teardown && self.is_track(cmd.url());
+                    match track_teardown {
+                        // The other tracks of the aggregate keep playing
+                        true => log::debug!("Tore down the track {}", cmd.url()),
+                        false => Self::update_session(&mut self.session, cmd.method(), &headers),
+                    }
+                    let track = match cmd.method() {
+                        Method::Setup => Self::update_interleaved(&mut self.interleaved, &self.rtcp_channels, &headers)
+                            .map(|channel| (cmd.url().clone(), channel)),
+                        _ => None,
+                    };
+                    let body = body.ok_or(Error::BadResponse)?;
+                    // A body that isn't text only fails the command, the channel keeps going
+                    let text = std::str::from_utf8(body).unwrap_or_default();
+                    if cmd.method() == Method::Describe {
+                        self.described = DescribedTrack::parse_all(text);
+                    }
+                    self.server_info.record(cmd.method(), &headers, text);
+                    let profile = match cmd.method() {
+                        Method::Describe if !self.limits_pinned => Self::detect_profile(text),
+                        _ => None,
+                    };
+                    cmd.handle_response(status, &headers, body);
+                    if let Some((url, channel)) = track {
+                        self.map_track(&url, channel);
+                    }
+                    if let Some(profile) = profile {
+                        log::debug!("Using buffer limits of the {:?} profile", profile);
+                        self.apply_limits(profile.limits());
+                        self.limits_pinned = true;
+                    }
+                }
+                Status::MovedPermanently | Status::MovedTemporarily | Status::SeeOther => {
+                    let location = headers
+                        .iter()
+                        .find(|h| h.name.eq_ignore_ascii_case("location"))
+                        .and_then(|h| cmd.url().join(h.value).ok());
+                    match location {
+                        Some(location) => {
+                            log::info!("{} {} redirected to {}", cmd.method(), cmd.url(), location);
+                            cmd.cancel(CommandError::Redirected(location));
+                        }
+                        None => cmd.cancel(CommandError::UnexpectedStatus(status)),
+                    }
+                }
+                _ => {
+                    if status == Status::SessionNotFound {
+                        let session = self.session.as_ref().map(|s| s.id.clone());
+                        self.emit(Event::SessionTimeout { session });
+                    }
+                    cmd.cancel(CommandError::UnexpectedStatus(status));
                 }
-                _ => cmd.cancel(CommandError::UnexpectedStatus(status)),
             }
         } else {
             cmd.cancel(CommandError::BadResponse);
@@ -176,134 +1013,797 @@ impl<Stream: AsyncReadExt + AsyncWriteExt + Send + Unpin + 'static> Channel<Stre
         Ok(parser.parsed_bytes())
     }
 
-    fn read_rtp_or_rtcp_packet(&mut self) -> Result<usize> {
-        Ok(0)
+    fn framer(&self) -> MessageFramer {
+        MessageFramer::new()
+            .lenient_headers(self.quirks.lenient_headers)
+            .until_close(self.quirks.body_until_close)
     }
 
-    fn read_packet(&mut self) -> Result<usize> {
-        let read_buf = self.buffer_rx.get_read_slice();
-        if read_buf.is_empty() {
-            return Ok(0);
-        }
-        // check if we have a rtp/rtcp packet i.e the first byte is '$'
-        if read_buf[0] == b'$' {
-            self.read_rtp_or_rtcp_packet()
-        } else {
-            self.read_rtsp_packet()
+    /// Quirks of the server named by the Server header, they apply from the
+    /// next response on
+    fn detect_quirks(headers: &[Header]) -> Option<Quirks> {
+        let server = headers.iter().find(|h| h.name.eq_ignore_ascii_case("server"))?;
+        let quirks = Quirks::detect(server.value);
+        if quirks != Quirks::none() {
+            log::debug!("Tolerating {:?} of {}", quirks, server.value);
         }
+        Some(quirks)
     }
 
-    fn handle_data(&mut self) {
-        loop {
-            match self.read_packet() {
-                Ok(n) => {
-                    if n == 0 {
-                        break;
-                    }
-                    self.buffer_rx.notify_read(n);
-                }
-                Err(e) => match e {
-                    Error::IncompleteResponse => {
-                        break; // Simply retry later
-                    }
-                    _ => {
-                        log::error!("Error reading packet: {}, shutdown", e);
-                        self.shutdown();
-                        break;
-                    }
-                },
+    fn detect_profile(body: &str) -> Option<MediaProfile> {
+        let sdp = sdp::Sdp::try_from(body).ok()?;
+        (!sdp.media.is_empty()).then(|| MediaProfile::from_sdp(&sdp))
+    }
+
+    /// Remembers the values of the proxy headers in a response
+    fn update_proxy_headers(proxy_headers: &mut [(String, Option<String>)], headers: &[Header]) {
+        for (name, value) in proxy_headers.iter_mut() {
+            if let Some(h) = headers.iter().find(|h| h.name.eq_ignore_ascii_case(name)) {
+                *value = Some(h.value.to_string());
             }
         }
     }
 
-    fn shutdown(&mut self) {
-        self.shutdown = true;
-        for (_, cmd) in self.req_pending.drain() {
-            cmd.cancel(CommandError::Cancelled);
+    /// Whether the Via headers of a response list `via` more than once,
+    /// entries that fail to parse are skipped
+    fn via_loop(via: &Via, headers: &[Header]) -> bool {
+        let hops = headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case("via"))
+            .flat_map(|h| Via::parse_list(h.value).unwrap_or_default())
+            .filter(|entry| entry.is_same_hop(via))
+            .count();
+        hops > 1
+    }
+
+    fn update_session(session: &mut Option<Session>, method: Method, headers: &[Header]) {
+        if method == Method::Teardown {
+            *session = None;
+        } else if let Some(h) = headers.iter().find(|h| h.name.eq_ignore_ascii_case("session")) {
+            match h.value.parse() {
+                Ok(s) => *session = Some(s),
+                Err(e) => log::warn!("Ignoring invalid session header {}: {}", h.value, e),
+            }
         }
     }
 
-    async fn send_outstanding_data(&mut self) -> Result<()> {
-        let write_buf = self.buffer_tx.get_read_slice();
-        if !write_buf.is_empty() {
-            let result = self.stream.write_all(write_buf).await;
-            match result {
-                Ok(_) => {
-                    let n = write_buf.len();
-                    self.buffer_tx.notify_read(n);
-                }
-                Err(e) => {
-                    return Err(e.into());
-                }
+    /// Applies the description of the track set up at `url` to its RTP `channel`
+    fn map_track(&mut self, url: &url::Url, channel: u8) {
+        let track = self
+            .described
+            .iter()
+            .find(|track| Self::matches_control(url, &track.control));
+        if let Some((demuxer, _)) = self.demux.as_mut() {
+            demuxer.map_channel(channel);
+            if let Some(track) = track.filter(|track| !track.extensions.is_empty()) {
+                demuxer.map_extensions(channel, track.extensions.clone());
             }
         }
-        Ok(())
+        let Some(track) = track else {
+            return;
+        };
+        if let Some(clock_rate) = track.clock_rate {
+            self.clock_rates.insert(channel, clock_rate);
+        }
+        // Mismatches are only reported as events
+        if self.event_tx.is_some() {
+            self.channel_formats.insert(channel, track.formats.clone());
+        }
+        if let Some(codec) = &track.codec {
+            self.channel_codecs.insert(channel, codec.clone());
+        }
     }
 
-    fn handle_retry_req(&mut self) {
-        while let Some(req) = self.req_retry.pop_front() {
-            self.handle_request(req);
+    /// Returns the RTP channel of an interleaved transport
+    fn update_interleaved(
+        interleaved: &mut HashMap<u8, (Traffic, u8)>,
+        rtcp_channels: &Mutex<HashMap<u8, u8>>,
+        headers: &[Header],
+    ) -> Option<u8> {
+        let transport = headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("transport"))
+            .and_then(|h| h.value.parse::<Transport>().ok());
+        let (rtp, rtcp) = transport.and_then(|t| t.interleaved)?;
+        interleaved.insert(rtp, (Traffic::Rtp, rtcp));
+        interleaved.insert(rtcp, (Traffic::Rtcp, rtp));
+        rtcp_channels.lock().unwrap().insert(rtp, rtcp);
+        Some(rtp)
+    }
+
+    /// The RTCP channel of the track on `rtp_channel`, the following one
+    /// unless SETUP negotiated another
+    fn rtcp_channel(&self, rtp_channel: u8) -> u8 {
+        match self.interleaved.get(&rtp_channel) {
+            Some((Traffic::Rtp, rtcp)) => *rtcp,
+            _ => rtp_channel.wrapping_add(1),
         }
     }
 
-    async fn poll_until_shutdown(&mut self) -> Result<()> {
-        while !self.shutdown {
-            self.handle_retry_req();
-            self.send_outstanding_data().await?;
-            let mut read_buf = self.buffer_rx.get_write_slice(4096).unwrap();
-            tokio::select! {
-                result = self.stream.read(&mut read_buf) => {
-                    match result {
-                        Ok(n) => {
-                            if n == 0 {
-                                log::info!("Stream closed");
-                                break;
-                            }
-                            self.buffer_rx.notify_write(n);
-                            self.handle_data();
-                        }
-                        Err(e) => {
-                            log::error!("Error reading from stream: {}", e);
-                            break;
-                        }
-                    }
-                },
-                Some(cmd) = self.cmd_rx.recv() => {
-                    self.handle_command(cmd);
-                }
-            }
+    /// The RTP channel of the track whose RTCP arrives on `rtcp_channel`,
+    /// the preceding one unless SETUP negotiated another
+    fn rtp_channel(&self, rtcp_channel: u8) -> u8 {
+        match self.interleaved.get(&rtcp_channel) {
+            Some((Traffic::Rtcp, rtp)) => *rtp,
+            _ => rtcp_channel.wrapping_sub(1),
         }
-        Ok(())
     }
 
-    fn next_cseq(&mut self) -> CSeq {
-        let cseq = self.cseq;
-        self.cseq += 1;
-        cseq
+    /// Whether the SETUP url is the one of the track with `control`
+    fn matches_control(url: &url::Url, control: &str) -> bool {
+        let url = url.as_str().trim_end_matches('/');
+        control != "*" && url.ends_with(control.trim_end_matches('/'))
     }
 
-    fn handle_request(&mut self, req: Request) {
-        let cseq = self.next_cseq();
-        let mut write_buf = self.buffer_tx.get_write_slice(4096).unwrap();
-        let builder = RequestBuilder::new()
-            .header("CSeq", cseq)
-            .header("User-Agent", "rs-streamer")
-            .opt_header(
-                "Authorization",
-                self.authorizer
-                    .as_mut()
-                    .and_then(|a| a.answer(req.method(), req.url()).ok()),
-            )
+    /// Whether `url` is the control of one of several described tracks
+    /// rather than of the aggregate
+    fn is_track(&self, url: &url::Url) -> bool {
+        self.described.len() > 1
+            && self
+                .described
+                .iter()
+                .any(|track| Self::matches_control(url, &track.control))
+    }
+
+    /// Records and reports the first packet and the first keyframe of a
+    /// channel since PLAY was sent
+    fn check_startup(&mut self, channel: u8, packet: &rtp::Packet) {
+        let Some(play_sent) = self.play_sent else {
+            return;
+        };
+        if self.started.get(&channel) == Some(&true) {
+            return;
+        }
+        let latency = play_sent.elapsed();
+        if !self.started.contains_key(&channel) {
+            self.latency.record_first_packet(channel, latency);
+            self.emit(Event::FirstPacket { channel, latency });
+        }
+        // Tracks of unknown codec count every packet as a keyframe
+        let keyframe = self
+            .channel_codecs
+            .get(&channel)
+            .is_none_or(|codec| rtp::starts_keyframe(codec, packet.data()));
+        if keyframe {
+            log::debug!("First keyframe on channel {} after {:?}", channel, latency);
+            self.latency.record_first_keyframe(channel, latency);
+            self.emit(Event::FirstKeyframe { channel, latency });
+        }
+        self.started.insert(channel, keyframe);
+    }
+
+    /// Reports the first packet of a channel whose payload type isn't described
+    fn check_payload_type(&mut self, channel: u8, payload_type: u8) {
+        let mismatch = self
+            .channel_formats
+            .get(&channel)
+            .is_some_and(|formats| !formats.contains(&payload_type));
+        if !mismatch {
+            return;
+        }
+        let described = self.channel_formats.remove(&channel).unwrap_or_default();
+        log::warn!(
+            "Payload type {} on channel {} isn't described, the SDP lists {:?}",
+            payload_type,
+            channel,
+            described
+        );
+        self.emit(Event::PayloadTypeMismatch {
+            channel,
+            described,
+            received: payload_type,
+        });
+    }
+
+    fn read_server_request(&mut self) -> Result<usize> {
+        let read_buf = self.buffer_rx.get_read_slice();
+        let Some((request, n)) = IncomingRequest::parse(read_buf)? else {
+            return Err(if read_buf.len() > self.max_header_size + self.max_body_size {
+                Error::RequestTooLong
+            } else {
+                Error::IncompleteResponse
+            });
+        };
+        self.usage.received(Traffic::Control, n);
+        let status = match request.method {
+            Some(
+                Method::Options | Method::GetParameter | Method::SetParameter | Method::Announce | Method::Redirect,
+            ) => Status::OK,
+            Some(_) => Status::MethodNotAllowed,
+            None => Status::NotImplemented,
+        };
+        log::debug!("Answering server request {} with {}", request.uri, status);
+        self.send_response(ResponseBuilder::new(status, request.cseq));
+        if self.event_tx.is_some() {
+            self.emit(Event::ServerRequest(request.clone()));
+        }
+        if let Some(tx) = &self.server_request_tx {
+            if let Err(e) = tx.try_send(request) {
+                log::warn!("Dropping server request: {}", e);
+            }
+        }
+        Ok(n)
+    }
+
+    fn send_response(&mut self, response: ResponseBuilder) {
+        let result = self
+            .buffer_tx
+            .get_write_slice(4096)
+            .map_err(Error::from)
+            .and_then(|buf| Ok(response.serialize(buf)?));
+        match result {
+            Ok(n) => {
+                self.buffer_tx.notify_write(n);
+                self.usage.sent(Traffic::Control, n);
+            }
+            Err(e) => log::warn!("Failed to send response: {}", e),
+        }
+    }
+
+    fn read_rtp_or_rtcp_packet(&mut self) -> Result<usize> {
+        let read_buf = self.buffer_rx.get_read_slice();
+        if read_buf.len() < 4 {
+            return Err(Error::IncompleteResponse);
+        }
+        let channel = read_buf[1];
+        let len = 4 + u16::from_be_bytes([read_buf[2], read_buf[3]]) as usize;
+        // Channels not negotiated by SETUP follow the even RTP / odd RTCP convention
+        let traffic = self.interleaved.get(&channel).map(|(t, _)| *t).unwrap_or(if channel.is_multiple_of(2) {
+            Traffic::Rtp
+        } else {
+            Traffic::Rtcp
+        });
+        if self.max_frame_size.is_some_and(|max| len - 4 > max) {
+            log::warn!("Discarding interleaved frame of {} bytes on channel {}", len - 4, channel);
+            self.usage.received(traffic, len);
+            let n = len.min(read_buf.len());
+            self.skip_remaining = len - n;
+            return Ok(n);
+        }
+        if read_buf.len() < len {
+            return Err(Error::IncompleteResponse);
+        }
+        self.usage.received(traffic, len);
+        // The frame is split off the receive buffer, packets share its memory
+        let frame = self.buffer_rx.split_read(len).slice(4..);
+        if let Some(tap) = self.tap.as_ref().filter(|t| t.receiver_count() > 0) {
+            let _ = tap.send((channel, frame.clone()));
+        }
+        let Some(frame) = self.unprotect(channel, traffic, frame) else {
+            return Ok(0);
+        };
+        let rtcp_used = self.event_tx.is_some()
+            || self.rtcp_interval.is_some()
+            || self.synchronizer.is_some()
+            || self.quality.is_some()
+            || self.stats.is_some();
+        if traffic == Traffic::Rtcp && rtcp_used {
+            self.handle_rtcp(channel, frame);
+        } else if traffic == Traffic::Rtp {
+            match rtp::Packet::new(frame) {
+                Ok(packet) => {
+                    trace::event!(
+                        trace,
+                        channel,
+                        seq = packet.sequence_number(),
+                        timestamp = packet.timestamp(),
+                        "Received RTP packet"
+                    );
+                    #[cfg(feature = "tracing")]
+                    {
+                        *self.packets.entry(channel).or_default() += 1;
+                    }
+                    if self.rtcp_interval.is_some() || self.quality.is_some() {
+                        self.record_reception(channel, &packet);
+                    }
+                    self.record_stats(channel, &packet);
+                    self.check_payload_type(channel, packet.payload_type());
+                    self.check_startup(channel, &packet);
+                    if let Some((demuxer, tx)) = self.demux.as_mut() {
+                        demuxer.push(channel, packet.clone());
+                        while let Some(demuxed) = demuxer.pop() {
+                            if let Err(e) = tx.try_send(demuxed) {
+                                log::warn!("Dropping demuxed RTP packet on channel {}: {}", channel, e);
+                            }
+                        }
+                        let recorder = self.recorders.get_mut(&channel);
+                        if let (Some(track), Some(recorder)) = (demuxer.track(channel), recorder) {
+                            recorder.set_reorder_depth(demuxer.buffered(track).count());
+                        }
+                    }
+                    if let Err(e) = self.packet_tx.try_send(packet) {
+                        log::warn!("Dropping RTP packet on channel {}: {}", channel, e);
+                    }
+                }
+                Err(e) => {
+                    #[cfg(not(feature = "tracing"))]
+                    log::warn!("Invalid RTP packet on channel {}: {}", channel, e);
+                    trace::event!(warn, channel, error = %e, "Invalid RTP packet");
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    /// Decrypts the frame if the channel uses SRTP, None if it failed
+    fn unprotect(&mut self, channel: u8, traffic: Traffic, frame: Bytes) -> Option<Bytes> {
+        let rtp_channel = match traffic {
+            Traffic::Rtcp => self.rtp_channel(channel),
+            _ => channel,
+        };
+        let Some(ctx) = self.srtp.get_mut(&rtp_channel) else {
+            return Some(frame);
+        };
+        let result = match traffic {
+            Traffic::Rtp => ctx.unprotect_rtp(&frame),
+            Traffic::Rtcp => ctx.unprotect_rtcp(&frame),
+            _ => return Some(frame),
+        };
+        match result {
+            Ok(packet) => Some(Bytes::from(packet)),
+            Err(e) => {
+                log::warn!("Dropping SRTP packet on channel {}: {}", channel, e);
+                None
+            }
+        }
+    }
+
+    fn handle_rtcp(&mut self, channel: u8, frame: Bytes) {
+        let rtp_channel = self.rtp_channel(channel);
+        let arrival = SystemTime::now();
+        let mut sender_report = false;
+        let mut rtt = None;
+        for packet in rtcp::CompoundPacket::new(frame).iter() {
+            match packet {
+                Ok(rtcp::RtcpPacket::Bye(bye)) => self.emit(Event::RtcpBye {
+                    ssrcs: bye.sources(),
+                    reason: bye.reason().map(str::to_string),
+                }),
+                Ok(rtcp::RtcpPacket::SenderReport(sr)) => {
+                    sender_report = true;
+                    rtt = rtt.or(self.round_trip_time(&sr.report_blocks(), arrival));
+                    if let Some(stats) = self.reception.get_mut(&rtp_channel) {
+                        stats.record_sender_report(&sr, Instant::now().into_std());
+                    }
+                    let clock_rate = self.clock_rates.get(&rtp_channel);
+                    if let (Some(sync), Some(clock_rate)) = (&self.synchronizer, clock_rate) {
+                        sync.lock().unwrap().handle_sender_report(&sr, *clock_rate);
+                    }
+                }
+                Ok(rtcp::RtcpPacket::ReceiverReport(rr)) => {
+                    rtt = rtt.or(self.round_trip_time(&rr.report_blocks(), arrival));
+                }
+                Ok(_) => {}
+                Err(e) => log::debug!("Invalid RTCP packet: {}", e),
+            }
+        }
+        if let (Some(rtt), Some(recorder)) = (rtt, self.recorders.get_mut(&rtp_channel)) {
+            recorder.record_rtt(rtt);
+        }
+        if let Some(quality) = &self.quality {
+            let reception = self.reception.get(&rtp_channel);
+            quality.update(rtp_channel, |q| {
+                if let Some(reception) = reception {
+                    q.loss_percent = reception.loss() * 100.0;
+                    q.jitter = reception.jitter();
+                }
+                if rtt.is_some() {
+                    q.rtt = rtt;
+                }
+                if sender_report {
+                    q.last_sender_report = Some(arrival);
+                }
+            });
+        }
+    }
+
+    /// Round-trip time from the report block about our source, if any
+    fn round_trip_time(&self, blocks: &[rtcp::ReportBlock], arrival: SystemTime) -> Option<Duration> {
+        blocks
+            .iter()
+            .find(|b| b.ssrc() == self.rtcp_ssrc)
+            .and_then(|b| b.round_trip_time(arrival))
+    }
+
+    fn read_packet(&mut self) -> Result<usize> {
+        let read_buf = self.buffer_rx.get_read_slice();
+        if read_buf.is_empty() {
+            return Ok(0);
+        }
+        if self.skip_remaining > 0 {
+            let n = self.skip_remaining.min(read_buf.len());
+            self.skip_remaining -= n;
+            return Ok(n);
+        }
+        // check if we have a rtp/rtcp packet i.e the first byte is '$'
+        if read_buf[0] == b'$' {
+            return self.read_rtp_or_rtcp_packet();
+        }
+        // Responses start with the protocol, anything else is a request of the server
+        let prefix = &read_buf[..read_buf.len().min(5)];
+        if !b"RTSP/".starts_with(prefix) {
+            self.read_server_request()
+        } else if prefix.len() < 5 {
+            Err(Error::IncompleteResponse)
+        } else {
+            self.read_rtsp_packet()
+        }
+    }
+
+    fn handle_data(&mut self) {
+        while !self.buffer_rx.get_read_slice().is_empty() {
+            // Bytes still to be released, interleaved frames are split off the buffer instead
+            match self.read_packet() {
+                Ok(n) => self.buffer_rx.notify_read(n),
+                Err(e) => match e {
+                    Error::IncompleteResponse => {
+                        break; // Simply retry later
+                    }
+                    _ => {
+                        trace::event!(
+                            debug,
+                            error = %e,
+                            data = %trace::hex_dump(self.buffer_rx.get_read_slice()),
+                            "Failed to parse received data"
+                        );
+                        log::error!("Error reading packet: {}, shutdown", e);
+                        self.disconnect = Some(DisconnectReason::Error(e.to_string()));
+                        self.shutdown();
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Frees the pending slots of requests whose caller dropped the receiver,
+    /// and forgets abandoned requests whose response is no longer expected
+    fn drop_abandoned_requests(&mut self) {
+        let now = Instant::now();
+        self.req_abandoned.retain(|_, abandoned| abandoned.deadline > now);
+        let abandoned: Vec<CSeq> = self
+            .req_pending
+            .iter()
+            .filter(|(_, pending)| pending.req.is_closed())
+            .map(|(cseq, _)| *cseq)
+            .collect();
+        for cseq in abandoned {
+            if let Some(pending) = self.req_pending.remove(&cseq) {
+                self.abandon(cseq, &pending.req, pending.deadline);
+            }
+        }
+    }
+
+    /// Discards the response to `req` if it arrives before `deadline`
+    fn abandon(&mut self, cseq: CSeq, req: &Request, deadline: Instant) {
+        let abandoned = Abandoned {
+            method: req.method(),
+            url: req.url().clone(),
+            deadline,
+        };
+        self.req_abandoned.insert(cseq, abandoned);
+    }
+
+    fn shutdown(&mut self) {
+        self.shutdown = true;
+        self.req_abandoned.clear();
+        for (_, pending) in self.req_pending.drain() {
+            pending.req.cancel(CommandError::Cancelled);
+        }
+        let delayed = self.req_delayed.drain(..).map(|d| d.req);
+        for req in self.req_queue.drain(..).chain(self.req_retry.drain(..)).chain(delayed) {
+            req.cancel(CommandError::Cancelled);
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let delayed = self.req_delayed.iter().map(|d| d.due);
+        self.req_pending
+            .values()
+            .map(|p| p.deadline)
+            .chain(delayed)
+            .chain(self.next_report)
+            .min()
+    }
+
+    fn delay_request(&mut self, req: Request, delay: Duration, attempt: u32, cseq: Option<CSeq>) {
+        self.req_delayed.push(Delayed {
+            due: Instant::now() + delay,
+            req,
+            attempt,
+            cseq,
+        });
+    }
+
+    /// Sends the delayed retries that are due
+    fn send_delayed_requests(&mut self) {
+        let now = Instant::now();
+        let (due, waiting) = self.req_delayed.drain(..).partition(|d| d.due <= now);
+        self.req_delayed = waiting;
+        for delayed in due {
+            match delayed.cseq {
+                Some(cseq) if !delayed.req.is_closed() => self.write_request(delayed.req, cseq, delayed.attempt),
+                Some(_) => {}
+                None => self.send_request(delayed.req, delayed.attempt),
+            }
+        }
+    }
+
+    fn handle_timeouts(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<CSeq> = self
+            .req_pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(cseq, _)| *cseq)
+            .collect();
+        for cseq in expired {
+            let Some(pending) = self.req_pending.remove(&cseq) else {
+                continue;
+            };
+            let method = pending.req.method();
+            let delay = match self.datagram || method.is_idempotent() {
+                true => self.retry_policy.delay(pending.attempt + 1),
+                false => None,
+            };
+            self.emit(Event::RequestTimeout {
+                method,
+                retrying: delay.is_some(),
+            });
+            if let (Some(delay), true) = (delay, self.datagram) {
+                // The server recognizes the retransmission by its CSeq
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("{} request {} timed out, retransmitting", method, cseq);
+                trace::event!(warn, cseq, method = %method, attempt = pending.attempt + 1, "Retransmitting request");
+                match delay.is_zero() {
+                    true => self.write_request(pending.req, cseq, pending.attempt + 1),
+                    false => self.delay_request(pending.req, delay, pending.attempt + 1, Some(cseq)),
+                }
+                continue;
+            }
+            // A late response must not be taken for an unknown CSeq
+            self.abandon(cseq, &pending.req, now + self.timeout);
+            if let Some(delay) = delay {
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("{} request {} timed out, retrying in {:?}", method, cseq, delay);
+                trace::event!(warn, cseq, method = %method, delay = ?delay, "Request timed out, retrying");
+                match delay.is_zero() {
+                    true => self.send_request(pending.req, pending.attempt + 1),
+                    false => self.delay_request(pending.req, delay, pending.attempt + 1, None),
+                }
+            } else {
+                #[cfg(not(feature = "tracing"))]
+                log::warn!("{} request {} timed out", method, cseq);
+                trace::event!(warn, cseq, method = %method, "Request timed out");
+                pending.req.cancel(CommandError::Timeout);
+            }
+        }
+    }
+
+    async fn send_outstanding_data(&mut self) -> Result<()> {
+        let write_buf = self.buffer_tx.get_read_slice();
+        if !write_buf.is_empty() {
+            let result = self.stream.write_all(write_buf).await;
+            match result {
+                Ok(_) => {
+                    let n = write_buf.len();
+                    self.buffer_tx.notify_read(n);
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Retries go before queued requests, keeping their order
+    fn handle_retry_req(&mut self) {
+        while let Some(req) = self.req_retry.pop_back() {
+            if !req.is_closed() {
+                self.req_queue.push_front(req);
+            }
+        }
+    }
+
+    /// Whether the next queued request can be written now. Requests that
+    /// depend on the session wait while a SETUP that may create it is pending.
+    fn can_send(&self, req: &Request) -> bool {
+        if self.req_pending.len() >= self.max_outstanding {
+            return false;
+        }
+        let needs_session = !matches!(req.method(), Method::Options | Method::Describe);
+        !(needs_session
+            && self.session.is_none()
+            && self.req_pending.values().any(|p| p.req.method() == Method::Setup))
+    }
+
+    fn send_queued_requests(&mut self) {
+        while let Some(req) = self.req_queue.front() {
+            if req.is_closed() {
+                self.req_queue.pop_front();
+                continue;
+            }
+            if !self.can_send(req) {
+                break;
+            }
+            if let Some(req) = self.req_queue.pop_front() {
+                self.send_request(req, 0);
+            }
+        }
+    }
+
+    async fn poll_until_shutdown(&mut self) -> Result<DisconnectReason> {
+        while !self.shutdown {
+            self.drop_abandoned_requests();
+            self.handle_retry_req();
+            self.send_queued_requests();
+            self.send_queued_frames();
+            self.send_outstanding_data().await?;
+            let deadline = self.next_deadline();
+            let read_buf = self.buffer_rx.get_write_slice(self.read_size)?;
+            tokio::select! {
+                result = self.stream.read(read_buf) => {
+                    match result {
+                        Ok(n) => {
+                            if n == 0 {
+                                // A body that ends with the connection is complete now
+                                if self.rx_framer.close() {
+                                    self.handle_data();
+                                }
+                                log::info!("Stream closed");
+                                return Ok(DisconnectReason::Closed);
+                            }
+                            self.buffer_rx.notify_write(n);
+                            self.handle_data();
+                        }
+                        Err(e) => {
+                            log::error!("Error reading from stream: {}", e);
+                            return Ok(DisconnectReason::Io(e.kind()));
+                        }
+                    }
+                },
+                Some(cmd) = self.cmd_rx.recv() => {
+                    self.handle_command(cmd);
+                }
+                // Writers wait while frames can't be written
+                Some((channel, payload)) = self.frame_rx.recv(), if self.frame_queue.is_empty() => {
+                    self.queue_interleaved(channel, payload);
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.handle_timeouts();
+                    self.send_delayed_requests();
+                    self.send_receiver_reports();
+                }
+            }
+        }
+        Ok(self.disconnect.take().unwrap_or(DisconnectReason::Shutdown))
+    }
+
+    fn next_cseq(&mut self) -> CSeq {
+        let cseq = self.cseq;
+        self.cseq += 1;
+        cseq
+    }
+
+    /// Blocksize excludes the RTP header, max_frame_size includes it
+    fn requested_blocksize(&self, method: Method) -> Option<usize> {
+        match method {
+            Method::Setup | Method::Play => self
+                .blocksize
+                .or(self.max_frame_size.map(|max| max.saturating_sub(12))),
+            _ => None,
+        }
+    }
+
+    fn handle_request(&mut self, req: Request) {
+        self.req_queue.push_back(req);
+    }
+
+    fn send_request(&mut self, req: Request, attempt: u32) {
+        if req.is_closed() || !req.tracker().mark_sent() {
+            return;
+        }
+        let cseq = self.next_cseq();
+        self.write_request(req, cseq, attempt);
+    }
+
+    /// `range` on the server's clock if enabled and the skew is known
+    fn server_range(&self, range: Range) -> Range {
+        let skew = self.server_info.snapshot().clock_skew;
+        match (range, skew) {
+            (Range::Clock { start, end }, Some(skew)) if self.server_clock_ranges => Range::Clock {
+                start: skew.to_server_time(start),
+                end: end.map(|end| skew.to_server_time(end)),
+            },
+            (range, _) => range,
+        }
+    }
+
+    fn write_request(&mut self, req: Request, cseq: CSeq, attempt: u32) {
+        let blocksize = self.requested_blocksize(req.method());
+        let range = req.range().map(|range| self.server_range(*range));
+        let require = (!self.require.is_empty()
+            && matches!(req.method(), Method::Describe | Method::Setup | Method::Play))
+        .then(|| self.require.join(", "));
+        // Room for the headers of any request besides the body
+        let size = 4096 + req.body().map_or(0, str::len);
+        let write_buf = match self.buffer_tx.get_write_slice(size) {
+            Ok(buf) => buf,
+            Err(e) => {
+                log::warn!("Failed to send request: {}", e);
+                req.cancel(Error::from(e).into());
+                return;
+            }
+        };
+        let answer =
+            |authorizer: Option<&mut Authorizer>| authorizer.map(|a| a.answer(req.method(), req.url())).transpose();
+        let (authorization, proxy_authorization) =
+            match (answer(self.authorizer.as_mut()), answer(self.proxy_authorizer.as_mut())) {
+                (Ok(authorization), Ok(proxy_authorization)) => (authorization, proxy_authorization),
+                (Err(e), _) | (_, Err(e)) => {
+                    log::warn!("Failed to answer authentication challenge: {}", e);
+                    req.cancel(e.into());
+                    return;
+                }
+            };
+        let authorized = authorization.is_some();
+        let proxy_authorized = proxy_authorization.is_some();
+        let builder = RequestBuilder::new()
+            .header("CSeq", cseq)
+            .header("User-Agent", &self.user_agent)
+            .opt_header("Authorization", authorization)
+            .opt_header("Proxy-Authorization", proxy_authorization)
+            .opt_header("Session", self.session.as_ref())
+            .opt_header("Transport", req.transport())
+            .opt_header("Range", range)
+            .opt_header("Scale", req.scale())
+            .opt_header("Blocksize", blocksize)
+            .opt_header("Require", require)
+            .opt_header("Via", self.via.as_ref())
+            .headers(ProxyHeaders(&self.proxy_headers))
             .method(req.method())
             .url(req.url());
-        match builder.serialize(&mut write_buf) {
+        let result = match req.body() {
+            Some(body) => builder
+                .header("Content-Type", "text/parameters")
+                .body(body)
+                .serialize(write_buf),
+            None => builder.serialize(write_buf),
+        };
+        match result {
             Ok(n) => {
+                trace::event!(
+                    debug,
+                    cseq,
+                    method = %req.method(),
+                    url = %req.url(),
+                    attempt,
+                    authorized,
+                    proxy_authorized,
+                    "Sent request"
+                );
                 self.buffer_tx.notify_write(n);
-                self.req_pending.insert(cseq, req);
+                self.usage.sent(Traffic::Control, n);
+                let sent = Instant::now();
+                if req.method() == Method::Play {
+                    self.play_sent = Some(sent);
+                    self.started.clear();
+                }
+                self.req_pending.insert(
+                    cseq,
+                    Pending {
+                        req,
+                        deadline: sent + self.timeout,
+                        sent,
+                        attempt,
+                        authorized,
+                        proxy_authorized,
+                    },
+                );
             }
             Err(_) => {
                 req.cancel(CommandError::Unknown);
-                return;
             }
         }
     }
@@ -318,50 +1818,1799 @@ impl<Stream: AsyncReadExt + AsyncWriteExt + Send + Unpin + 'static> Channel<Stre
         match cmd {
             Command::Request(req) => self.handle_request(req),
             Command::Ctrl(ctrl) => self.handle_ctrl(ctrl),
+            Command::Interleaved { channel, payload } => self.queue_interleaved(channel, payload),
+            Command::Nack { channel, ssrc, lost } => self.send_nack(channel, ssrc, &lost),
+        }
+    }
+
+    fn send_nack(&mut self, channel: u8, ssrc: u32, lost: &[u16]) {
+        // Outgoing RTCP can't be encrypted
+        if lost.is_empty() || self.srtp.contains_key(&channel) {
+            return;
+        }
+        // Feedback is sent in a compound packet starting with a report (RFC 4585 3.1)
+        let mut packet = rtcp::receiver_report(self.rtcp_ssrc, &[], &self.cname());
+        packet.extend_from_slice(&rtcp::generic_nack(self.rtcp_ssrc, ssrc, lost));
+        self.queue_interleaved(self.rtcp_channel(channel), packet.into());
+    }
+
+    fn cname(&self) -> String {
+        format!("{:08x}@{}", self.rtcp_ssrc, self.user_agent)
+    }
+
+    /// Queues an interleaved frame, it is written after the queued requests
+    /// once the send buffer has room. Like the queue of the
+    /// [`InterleavedWriter`], at most [`DEFAULT_WRITER_CAPACITY`] frames
+    /// wait, further ones are dropped and counted in [`Usage::dropped`].
+    fn queue_interleaved(&mut self, channel: u8, payload: Bytes) {
+        let n = 4 + payload.len();
+        if self.datagram || payload.len() > u16::MAX as usize || n > self.buffer_tx.max_capacity() {
+            log::warn!("Can't send {} bytes interleaved on channel {}", payload.len(), channel);
+            return;
+        }
+        if self.frame_queue.len() >= DEFAULT_WRITER_CAPACITY {
+            log::debug!("Send queue full, dropping interleaved frame on channel {}", channel);
+            self.usage.dropped(n);
+            return;
+        }
+        self.frame_queue.push_back((channel, payload));
+    }
+
+    /// Writes the queued interleaved frames that fit into the send buffer,
+    /// the rest waits until the buffer was written to the stream
+    fn send_queued_frames(&mut self) {
+        while let Some((channel, payload)) = self.frame_queue.front() {
+            let (channel, n) = (*channel, 4 + payload.len());
+            match self.buffer_tx.get_write_slice(n) {
+                Ok(buf) => {
+                    buf[0] = b'$';
+                    buf[1] = channel;
+                    buf[2..4].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+                    buf[4..n].copy_from_slice(payload);
+                    self.buffer_tx.notify_write(n);
+                    let traffic = self.interleaved.get(&channel).map_or(Traffic::Rtp, |(t, _)| *t);
+                    self.usage.sent(traffic, n);
+                }
+                Err(BufferError::NotEnoughSpace) => break,
+                Err(e) => log::warn!("Dropping interleaved frame on channel {}: {}", channel, e),
+            }
+            self.frame_queue.pop_front();
+        }
+    }
+
+    fn record_reception(&mut self, channel: u8, packet: &rtp::Packet) {
+        let now = Instant::now();
+        let clock_rate = self.clock_rates.get(&channel).copied().unwrap_or(0);
+        self.reception
+            .entry(channel)
+            .or_insert_with(|| rtcp::ReceptionStats::new(clock_rate))
+            .record_packet(packet, now.into_std());
+        if let (None, Some(interval)) = (self.next_report, self.rtcp_interval) {
+            self.next_report = Some(now + Self::report_delay(interval));
+        }
+    }
+
+    fn record_stats(&mut self, channel: u8, packet: &rtp::Packet) {
+        let Some(stats) = &self.stats else {
+            return;
+        };
+        let clock_rate = self.clock_rates.get(&channel).copied().unwrap_or(0);
+        self.recorders
+            .entry(channel)
+            .or_insert_with(|| stats.track(channel as usize, clock_rate))
+            .record_packet(packet, Instant::now().into_std());
+    }
+
+    fn report_delay(interval: Duration) -> Duration {
+        interval.mul_f64(rand::rng().random_range(0.5..1.5))
+    }
+
+    /// Sends the receiver reports if they are due. Like requests they are
+    /// written to the send buffer as a whole, so they never end up inside
+    /// a partially written request.
+    fn send_receiver_reports(&mut self) {
+        let (Some(interval), Some(due)) = (self.rtcp_interval, self.next_report) else {
+            return;
+        };
+        let now = Instant::now();
+        if due > now {
+            return;
+        }
+        self.next_report = Some(now + Self::report_delay(interval));
+        let cname = self.cname();
+        let mut channels: Vec<u8> = self.reception.keys().copied().collect();
+        channels.sort_unstable();
+        for channel in channels {
+            // Outgoing RTCP can't be encrypted
+            if self.srtp.contains_key(&channel) {
+                continue;
+            }
+            let Some(report) = self.reception.get_mut(&channel).and_then(|s| s.report(now.into_std())) else {
+                continue;
+            };
+            let report = rtcp::receiver_report(self.rtcp_ssrc, &[report], &cname);
+            self.queue_interleaved(self.rtcp_channel(channel), report.into());
         }
     }
 
     async fn run(mut self) {
-        let result = self.poll_until_shutdown().await;
-        if let Err(e) = result {
-            log::error!("Stream shutdown with error: {}", e);
+        self.emit(Event::Connected);
+        let reason = match self.poll_until_shutdown().await {
+            Ok(reason) => reason,
+            Err(e) => {
+                log::error!("Stream shutdown with error: {}", e);
+                match e {
+                    Error::Io(e) => DisconnectReason::Io(e.kind()),
+                    e => DisconnectReason::Error(e.to_string()),
+                }
+            }
+        };
+        trace::event!(info, reason = ?reason, "Disconnected");
+        #[cfg(feature = "tracing")]
+        for (channel, packets) in &self.packets {
+            tracing::debug!(channel, packets, "RTP packets received");
+        }
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(Event::Disconnected { reason }).await;
         }
     }
 
     pub fn start(self) -> tokio::task::JoinHandle<()> {
-        tokio::task::spawn(self.run())
+        let name = self.task_name.clone();
+        // Events of the channel carry its task name
+        #[cfg(feature = "tracing")]
+        let run = tracing::Instrument::instrument(self.run(), tracing::info_span!("rtsp_channel", task = %name));
+        #[cfg(not(feature = "tracing"))]
+        let run = self.run();
+        task::spawn(&name, run)
     }
 }
 
 #[cfg(test)]
-use std::io::Write;
-#[tokio::test]
-async fn test_channel() {
-    use command::Describe;
-
-    let (cmd_tx, cmd_rx) = mpsc::channel(8);
-    let (packet_tx, _) = mpsc::channel(8);
-    let (cstream, sstream) = tokio::io::duplex(4096);
-    tokio::spawn(async move {
-        let mut sstream = sstream;
+mod tests {
+    use super::*;
+    use crate::sync::NtpTimestamp;
+    use std::io::Write;
+    use tokio::sync::oneshot;
+    use url::Url;
+
+    #[tokio::test]
+    async fn test_channel() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut sstream = sstream;
+            let mut read_buf = vec![0u8; 4096];
+            let n = sstream.read(&mut read_buf).await.unwrap();
+            assert_eq!(
+                std::str::from_utf8(&read_buf[..n]).unwrap(),
+                "DESCRIBE rtsp://test.com RTSP/1.0\r\nCSeq: 1\r\nUser-Agent: rs-streamer\r\n\r\n"
+            );
+            let mut write_buf = Vec::<u8>::new();
+            write!(write_buf, "RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 4\r\n\r\ntest").unwrap();
+            sstream.write_all(&write_buf).await.unwrap();
+        });
+        let channel = Channel::new(cstream, cmd_rx, packet_tx);
+        let handle = channel.start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(
+            Url::parse("rtsp://test.com").unwrap(),
+            tx,
+        )));
+        cmd_tx.send(cmd).await.unwrap();
+        rx.await.unwrap().unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_fragmented_response() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let (tx, describe) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com").unwrap();
+        let cmd = Command::Request(Request::Describe(Describe::new(url, tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        // The response trickles in, followed by an interleaved packet in the same read
+        let mut data = format!("RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 72\r\n\r\n{}", SDP_H264).into_bytes();
+        data.extend_from_slice(&[b'$', 0, 0, 12, 0x80, 96, 0, 1, 0, 0, 0, 0, 0, 0, 0, 5]);
+        for chunk in data.chunks(5) {
+            sstream.write_all(chunk).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        describe.await.unwrap().unwrap();
+        assert_eq!(packet_rx.recv().await.unwrap().sequence_number(), 1);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_undecodable_body() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let url = Url::parse("rtsp://test.com").unwrap();
+        let (tx, latin1) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(url.clone(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        let mut data = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 13\r\n\r\n".to_vec();
+        data.extend_from_slice(b"v=0\r\ns=Cam\xe9ra");
+        sstream.write_all(&data).await.unwrap();
+        assert!(matches!(latin1.await.unwrap(), Err(CommandError::Encoding(_))));
+        // Only the command failed, the channel answers the next one
+        let (tx, raw) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(url, tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        let mut data = b"RTSP/1.0 200 OK\r\nCSeq: 2\r\nContent-Type: application/octet-stream\r\n".to_vec();
+        data.extend_from_slice(b"Content-Length: 2\r\n\r\n\xff\xfe");
+        sstream.write_all(&data).await.unwrap();
+        let description = raw.await.unwrap().unwrap();
+        assert!(matches!(description, Description::Raw { body, .. } if body[..] == b"\xff\xfe"[..]));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_quirks() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).quirks(Quirks::all()).start();
+        let methods = options(&cmd_tx);
+        read_requests(&mut sstream, 1).await;
+        let response = "RTSP/1.0 200 OK\r\nCSeq : 1\r\nPublic: options, Describe\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        assert_eq!(methods.await.unwrap().unwrap(), [Method::Options, Method::Describe]);
+
+        // The body has no Content-Length and ends with the connection
+        let (tx, describe) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com/live").unwrap();
+        let cmd = Command::Request(Request::Describe(Describe::new(url, tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        let response = format!(
+            "RTSP/1.0 200 OK\r\nCSeq: 2\r\nContent-Type: application/sdp\r\nContent-Base: rtsp://test.com/live/\r\n\r\n{}",
+            SDP_H264
+        );
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        drop(sstream);
+        let sdp = describe.await.unwrap().unwrap().into_sdp().unwrap();
+        assert_eq!(sdp.media[0].control(), Some("rtsp://test.com/live/trackID=0"));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_detect_quirks() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        // Lenient from the response after the one naming the server
+        for cseq in 1..=2 {
+            let methods = options(&cmd_tx);
+            read_requests(&mut sstream, 1).await;
+            let response = format!(
+                "RTSP/1.0 200 OK\r\nCSeq: {}\r\nServer: Hikvision-Webs\r\nPublic: OPTIONS, describe\r\n\r\n",
+                cseq
+            );
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            let expected = match cseq {
+                1 => vec![Method::Options],
+                _ => vec![Method::Options, Method::Describe],
+            };
+            assert_eq!(methods.await.unwrap().unwrap(), expected);
+        }
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_rejected_credentials() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            for cseq in 1..=2 {
+                let n = sstream.read(&mut read_buf).await.unwrap();
+                let request = std::str::from_utf8(&read_buf[..n]).unwrap();
+                assert_eq!(request.contains("Authorization: Basic"), cseq == 2);
+                let response = format!(
+                    "RTSP/1.0 401 Unauthorized\r\nCSeq: {}\r\nWWW-Authenticate: Basic realm=\"test\"\r\n\r\n",
+                    cseq
+                );
+                sstream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let channel = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("wrong")
+            .events(event_tx);
+        let handle = channel.start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(
+            Url::parse("rtsp://test.com").unwrap(),
+            tx,
+        )));
+        cmd_tx.send(cmd).await.unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::Unauthorized)));
+        handle.await.unwrap();
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+        assert!(events.contains(&Event::UnauthorizedRetry {
+            method: Method::Describe,
+            proxy: false,
+            attempt: 1,
+        }));
+        assert!(events.contains(&Event::AuthFailed {
+            method: Method::Describe,
+            proxy: false,
+            realm: Some("test".to_string()),
+            failure: AuthFailure::Rejected,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_channel_usage() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 4\r\n\r\ntest";
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            assert!(sstream.read(&mut read_buf).await.unwrap() > 0);
+            let mut write_buf = response.as_bytes().to_vec();
+            // RTP packet of 12 bytes on channel 0 and a 4 byte RTCP packet on channel 1
+            write_buf.extend_from_slice(&[b'$', 0, 0, 12, 0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+            write_buf.extend_from_slice(&[b'$', 1, 0, 4, 0x80, 0xc9, 0, 0]);
+            sstream.write_all(&write_buf).await.unwrap();
+            // Keep the connection open until the channel is gone
+            assert_eq!(sstream.read(&mut read_buf).await.unwrap(), 0);
+        });
+        let channel = Channel::new(cstream, cmd_rx, packet_tx);
+        let usage = channel.usage();
+        let handle = channel.start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(
+            Url::parse("rtsp://test.com").unwrap(),
+            tx,
+        )));
+        cmd_tx.send(cmd).await.unwrap();
+        rx.await.unwrap().unwrap();
+        let packet = packet_rx.recv().await.unwrap();
+        assert_eq!(packet.sequence_number(), 1);
+
+        let usage = usage.snapshot();
+        assert_eq!(usage.sent.control.packets, 1);
+        assert_eq!(usage.received.control.bytes, response.len() as u64);
+        assert_eq!(usage.received.rtp, Counter { bytes: 16, packets: 1 });
+        assert_eq!(usage.received.rtcp, Counter { bytes: 8, packets: 1 });
+        drop(cmd_tx);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_channel_abandoned_request() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let (received_tx, received_rx) = oneshot::channel();
+        let (dropped_tx, dropped_rx) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            let n = sstream.read(&mut read_buf).await.unwrap();
+            assert!(std::str::from_utf8(&read_buf[..n]).unwrap().starts_with("DESCRIBE"));
+            received_tx.send(()).unwrap();
+            dropped_rx.await.unwrap();
+            let response = "RTSP/1.0 401 Unauthorized\r\nCSeq: 1\r\nWWW-Authenticate: Basic realm=\"test\"\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            // The abandoned DESCRIBE must not be retried
+            let n = sstream.read(&mut read_buf).await.unwrap();
+            let request = std::str::from_utf8(&read_buf[..n]).unwrap();
+            assert!(request.starts_with("OPTIONS"));
+            assert!(request.contains("CSeq: 2\r\n"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nPublic: DESCRIBE\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let channel = Channel::new(cstream, cmd_rx, packet_tx).user("user").pass("pass");
+        let handle = channel.start();
+        let url = Url::parse("rtsp://test.com").unwrap();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Describe(Describe::new(url.clone(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        received_rx.await.unwrap();
+        drop(rx);
+        dropped_tx.send(()).unwrap();
+
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(url, tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        assert_eq!(rx.await.unwrap().unwrap(), vec![Method::Describe]);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_abandoned_setup() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let (received_tx, received_rx) = oneshot::channel();
+        let (dropped_tx, dropped_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            assert!(read_requests(&mut sstream, 1).await[0].starts_with("SETUP"));
+            received_tx.send(()).unwrap();
+            dropped_rx.await.unwrap();
+            // The server set the track up although the caller gave up
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nSession: 12345678\r\n\
+                            Transport: RTP/AVP/TCP;interleaved=0-1\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            assert!(read_requests(&mut sstream, 1).await[0].starts_with("OPTIONS"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nPublic: PLAY\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            let play = read_requests(&mut sstream, 1).await.remove(0);
+            assert!(play.contains("\r\nSession: 12345678"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 3\r\nSession: 12345678\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            sstream
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let (tx, rx) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com/trackID=0").unwrap();
+        let cmd = Command::Request(Request::Setup(Setup::new(url, Transport::tcp((0, 1)), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        received_rx.await.unwrap();
+        drop(rx);
+        dropped_tx.send(()).unwrap();
+        // Answered after the abandoned SETUP, so its response was handled
+        let methods = options(&cmd_tx);
+        assert_eq!(methods.await.unwrap().unwrap(), vec![Method::Play]);
+
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Play(Play::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        rx.await.unwrap().unwrap();
+        drop(server.await.unwrap());
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_abandoned_expiry() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, _sstream) = tokio::io::duplex(4096);
+        let mut channel = Channel::new(cstream, cmd_rx, packet_tx).timeout(Duration::from_secs(5));
+        let (tx, _rx) = oneshot::channel();
+        let req = Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx));
+        let now = Instant::now();
+        channel.abandon(1, &req, now);
+        channel.abandon(2, &req, now + Duration::from_secs(5));
+        // The response to the first can't arrive anymore
+        channel.drop_abandoned_requests();
+        assert_eq!(channel.req_abandoned.keys().collect::<Vec<_>>(), [&2]);
+    }
+
+    #[tokio::test]
+    async fn test_channel_tap() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (tap_tx, mut tap_rx) = broadcast::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        // A truncated RTP packet is still tapped before parsing fails
+        sstream.write_all(&[b'$', 0, 0, 2, 0x80, 0x60, b'$', 1, 0, 1, 0xff]).await.unwrap();
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).tap(tap_tx).start();
+        assert_eq!(tap_rx.recv().await.unwrap(), (0, Bytes::from_static(&[0x80, 0x60])));
+        assert_eq!(tap_rx.recv().await.unwrap(), (1, Bytes::from_static(&[0xff])));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_srtp() {
+        let key = srtp::MasterKey::new([1; 16], [2; 14]);
+        let suite = srtp::CryptoSuite::AesCm128HmacSha1_80;
+        let mut sender = srtp::SrtpContext::new(suite, &key);
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .srtp(0, srtp::SrtpContext::new(suite, &key))
+            .start();
+        let frame = |payload: &[u8]| {
+            let mut frame = vec![b'$', 0];
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            frame.extend_from_slice(payload);
+            frame
+        };
+        let rtp = |seq: u8, payload: u8| [0x80, 0x60, 0, seq, 0, 0, 0, 0, 0, 0, 0, 1, payload];
+        let mut tampered = sender.protect_rtp(&rtp(1, 0xaa)).unwrap();
+        tampered[12] ^= 0xff;
+        let valid = sender.protect_rtp(&rtp(2, 0xbb)).unwrap();
+        // The tampered packet is dropped, the valid one arrives decrypted
+        sstream.write_all(&frame(&tampered)).await.unwrap();
+        sstream.write_all(&frame(&valid)).await.unwrap();
+        let packet = packet_rx.recv().await.unwrap();
+        assert_eq!(packet.sequence_number(), 2);
+        assert_eq!(packet.data(), &[0xbb]);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_max_frame_size() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).max_frame_size(16).start();
+        // An oversized frame arriving in two parts, followed by a valid RTP packet
+        let mut oversized = vec![b'$', 0, 0, 40];
+        oversized.extend_from_slice(&[0xaa; 40]);
+        sstream.write_all(&oversized[..10]).await.unwrap();
+        tokio::task::yield_now().await;
+        sstream.write_all(&oversized[10..]).await.unwrap();
+        sstream
+            .write_all(&[b'$', 0, 0, 12, 0x80, 0x60, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        let packet = packet_rx.recv().await.unwrap();
+        assert_eq!(packet.sequence_number(), 7);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_server_request() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (request_tx, mut request_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            let announce = "ANNOUNCE rtsp://test.com RTSP/1.0\r\nCSeq: 8\r\nContent-Length: 5\r\n\r\nv=0\r\n";
+            let requests = format!("OPTIONS * RTSP/1.0\r\nCSeq: 7\r\n\r\n{}", announce);
+            sstream.write_all(requests.as_bytes()).await.unwrap();
+            let mut answers = String::new();
+            while !answers.contains("CSeq: 8") {
+                let n = sstream.read(&mut read_buf).await.unwrap();
+                answers.push_str(std::str::from_utf8(&read_buf[..n]).unwrap());
+            }
+            assert_eq!(
+                answers,
+                "RTSP/1.0 200 OK\r\nCSeq: 7\r\n\r\nRTSP/1.0 200 OK\r\nCSeq: 8\r\n\r\n"
+            );
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .server_requests(request_tx)
+            .start();
+        assert_eq!(request_rx.recv().await.unwrap().method, Some(Method::Options));
+        let announce = request_rx.recv().await.unwrap();
+        assert_eq!(announce.method, Some(Method::Announce));
+        assert_eq!(announce.body, "v=0\r\n");
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.await.unwrap();
+    }
+
+    #[test]
+    fn test_channel_detect_profile() {
+        let audio = "v=0\r\ns=Intercom\r\nm=audio 0 RTP/AVP 0\r\na=control:trackID=0\r\n";
+        assert_eq!(
+            Channel::<tokio::io::DuplexStream>::detect_profile(audio),
+            Some(MediaProfile::Audio)
+        );
+        assert_eq!(Channel::<tokio::io::DuplexStream>::detect_profile("v=0\r\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_channel_blocksize() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            let n = sstream.read(&mut read_buf).await.unwrap();
+            let request = std::str::from_utf8(&read_buf[..n]).unwrap();
+            assert!(request.contains("Blocksize: 1000\r\n"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nSession: 1\r\nTransport: RTP/AVP;unicast;client_port=5000-5001\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).blocksize(1000).start();
+        let (tx, rx) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com/track1").unwrap();
+        let cmd = Command::Request(Request::Setup(Setup::new(url, Transport::udp((5000, 5001)), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        rx.await.unwrap().unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_timeout() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, _sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .timeout(Duration::from_secs(5))
+            .start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Play(Play::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        let start = Instant::now();
+        cmd_tx.send(cmd).await.unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::Timeout)));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        handle.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_latency() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            assert!(sstream.read(&mut read_buf).await.unwrap() > 0);
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nPublic: OPTIONS\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            assert_eq!(sstream.read(&mut read_buf).await.unwrap(), 0);
+        });
+        let channel = Channel::new(cstream, cmd_rx, packet_tx);
+        let latency = channel.latency();
+        let handle = channel.start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        rx.await.unwrap().unwrap();
+        let keepalive = latency.snapshot().keepalive().unwrap();
+        assert_eq!(keepalive.count(), 1);
+        assert_eq!(keepalive.quantile(0.5), Some(150_000));
+        assert!(latency.snapshot().describe().is_none());
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_channel_clock_skew() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let stats = StatsHandle::new();
+        let channel = Channel::new(cstream, cmd_rx, packet_tx)
+            .stats(stats.clone())
+            .server_clock_ranges(true);
+        let server_info = channel.server_info();
+        let handle = channel.start();
+        let rx = options(&cmd_tx);
+        read_requests(&mut sstream, 1).await;
+        // A camera whose clock was never set
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        rx.await.unwrap().unwrap();
+        let skew = server_info.snapshot().clock_skew.unwrap();
+        let now = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+        let expected = 784_111_777_000 - now.as_millis() as i64;
+        assert!((skew.offset_ms - expected).abs() < 2000);
+        assert_eq!(stats.snapshot().clock_skew, Some(skew));
+
+        // The last hour of the recording, on the camera's clock
+        let start = SystemTime::now() - Duration::from_secs(3600);
+        let (tx, _rx) = oneshot::channel();
+        let play = Play::new(Url::parse("rtsp://test.com").unwrap(), tx).range(Range::clock(start, None));
+        cmd_tx.send(Command::Request(Request::Play(play))).await.unwrap();
+        let request = read_requests(&mut sstream, 1).await.remove(0);
+        let range = Range::clock(skew.to_server_time(start), None);
+        assert!(request.contains(&format!("\r\nRange: {}", range)), "{}", request);
+        assert!(request.contains("Range: clock=1994"));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_timeout_retry() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            for cseq in 1..=2 {
+                let n = sstream.read(&mut read_buf).await.unwrap();
+                let request = std::str::from_utf8(&read_buf[..n]).unwrap();
+                assert!(request.starts_with("OPTIONS"));
+                assert!(request.contains(&format!("CSeq: {}\r\n", cseq)));
+            }
+            // Answer the retry first, the late answer to the first attempt is dropped
+            for cseq in [2, 1] {
+                let response = format!("RTSP/1.0 200 OK\r\nCSeq: {}\r\nPublic: PLAY\r\n\r\n", cseq);
+                sstream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .timeout(Duration::from_secs(5))
+            .retries(1)
+            .start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        assert_eq!(rx.await.unwrap().unwrap(), vec![Method::Play]);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_retry_policy() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .timeout(Duration::from_secs(5))
+            .retry_policy(Backoff::fixed(Duration::from_secs(3)).max_retries(1))
+            .start();
+        let start = Instant::now();
+        let rx = options(&cmd_tx);
+        read_requests(&mut sstream, 1).await;
+        // The retry waits for the backoff after the timeout
+        let request = read_requests(&mut sstream, 1).await;
+        assert!(request[0].contains("CSeq: 2\r\n"));
+        assert_eq!(start.elapsed(), Duration::from_secs(8));
+        // The retry times out as well and the policy gives up
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::Timeout)));
+        assert_eq!(start.elapsed(), Duration::from_secs(13));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_auth_retry_policy() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("pass")
+            .auth_retry_policy(Backoff::never())
+            .events(event_tx)
+            .start();
+        let rx = options(&cmd_tx);
+        read_requests(&mut sstream, 1).await;
+        let response = "RTSP/1.0 401 Unauthorized\r\nCSeq: 1\r\nWWW-Authenticate: Basic realm=\"test\"\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::Unauthorized)));
+        drop(sstream);
+        handle.await.unwrap();
+        let mut events = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            events.push(event);
+        }
+        assert!(events.contains(&Event::AuthFailed {
+            method: Method::Options,
+            proxy: false,
+            realm: Some("test".to_string()),
+            failure: AuthFailure::RetriesExhausted,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_channel_auth_retries_stale() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("pass")
+            .start();
+        let rx = options(&cmd_tx);
+        // A server that considers every nonce stale
+        for cseq in 1..=DEFAULT_AUTH_RETRIES + 1 {
+            read_requests(&mut sstream, 1).await;
+            let response = format!(
+                "RTSP/1.0 401 Unauthorized\r\nCSeq: {}\r\n\
+                 WWW-Authenticate: Digest realm=\"test\", nonce=\"{}\", stale=true\r\n\r\n",
+                cseq, cseq
+            );
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        }
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::Unauthorized)));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_receiver_reports() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .receiver_reports(Duration::from_secs(5))
+            .start();
+        for (channel, seq) in [(0, 1), (2, 7), (0, 3)] {
+            let mut frame = vec![b'$', channel, 0, 13, 0x80, 0x60];
+            frame.extend_from_slice(&u16::to_be_bytes(seq));
+            frame.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, channel + 1, 0xab]);
+            sstream.write_all(&frame).await.unwrap();
+            packet_rx.recv().await.unwrap();
+        }
+        // Two frames with a receiver report and a SDES of 28 bytes each
+        let mut buf = vec![0u8; 2 * (4 + 32 + 32)];
+        let start = Instant::now();
+        sstream.read_exact(&mut buf).await.unwrap();
+        assert!(start.elapsed() <= Duration::from_millis(7500));
+        let mut reports = Vec::new();
+        let mut data = &buf[..];
+        while let [b'$', channel, high, low, rest @ ..] = data {
+            let len = u16::from_be_bytes([*high, *low]) as usize;
+            let compound = rtcp::CompoundPacket::new(Bytes::copy_from_slice(&rest[..len]));
+            let Some(Ok(rtcp::RtcpPacket::ReceiverReport(rr))) = compound.iter().next() else {
+                panic!("expected a receiver report");
+            };
+            let block = &rr.report_blocks()[0];
+            reports.push((*channel, block.ssrc(), block.packets_lost(), block.highest_sequence()));
+            data = &rest[len..];
+        }
+        assert!(data.is_empty());
+        // One report per track on its RTCP channel, the first track lost packet 2
+        assert_eq!(reports, [(1, 1, 1, 3), (3, 3, 0, 7)]);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_budget() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, _sstream) = tokio::io::duplex(4096);
+        // Enough for the receive buffer but not for a request
+        let budget = MemoryBudget::new(5000);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).budget(budget.clone()).start();
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::BudgetExceeded(_))));
+        let usage = budget.usage();
+        assert_eq!(usage.receive_buffer, 4096);
+        assert_eq!(usage.rejected, 1);
+        drop(cmd_tx);
+        handle.abort();
+        let _ = handle.await;
+        assert_eq!(budget.usage().used, 0);
+    }
+
+    #[tokio::test]
+    async fn test_channel_events() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut read_buf = vec![0u8; 4096];
+            assert!(sstream.read(&mut read_buf).await.unwrap() > 0);
+            let mut data = b"RTSP/1.0 454 Session Not Found\r\nCSeq: 1\r\n\r\n".to_vec();
+            // RTCP BYE of one source with reason "end"
+            data.extend_from_slice(&[b'$', 1, 0, 12, 0x81, 0xcb, 0, 2, 0, 0, 0, 9, 3, b'e', b'n', b'd']);
+            data.extend_from_slice(b"OPTIONS * RTSP/1.0\r\nCSeq: 3\r\n\r\n");
+            sstream.write_all(&data).await.unwrap();
+            assert!(sstream.read(&mut read_buf).await.unwrap() > 0);
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).events(event_tx).start();
+        assert_eq!(event_rx.recv().await.unwrap(), Event::Connected);
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        assert!(rx.await.unwrap().is_err());
+        assert_eq!(event_rx.recv().await.unwrap(), Event::SessionTimeout { session: None });
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            Event::RtcpBye {
+                ssrcs: vec![9],
+                reason: Some("end".to_string())
+            }
+        );
+        match event_rx.recv().await.unwrap() {
+            Event::ServerRequest(request) => assert_eq!(request.method, Some(Method::Options)),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            Event::Disconnected {
+                reason: DisconnectReason::Closed
+            }
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_events_full() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(1);
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        drop(sstream);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).events(event_tx).start();
+        // The channel is closed while Connected still fills the queue
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        assert_eq!(event_rx.recv().await.unwrap(), Event::Connected);
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            Event::Disconnected {
+                reason: DisconnectReason::Closed
+            }
+        );
+        handle.await.unwrap();
+    }
+
+    /// Reads from a scripted server until `count` requests arrived
+    async fn read_requests(stream: &mut tokio::io::DuplexStream, count: usize) -> Vec<String> {
+        let mut data = String::new();
         let mut read_buf = vec![0u8; 4096];
-        let n = sstream.read(&mut read_buf).await.unwrap();
+        while data.matches("\r\n\r\n").count() < count {
+            let n = stream.read(&mut read_buf).await.unwrap();
+            assert!(n > 0);
+            data.push_str(std::str::from_utf8(&read_buf[..n]).unwrap());
+        }
+        data.split_terminator("\r\n\r\n").map(str::to_string).collect()
+    }
+
+    /// A single H.264 track with the control trackID=0
+    const SDP_H264: &str = "v=0\r\nm=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:trackID=0\r\n";
+
+    /// Answers the DESCRIBE with [`SDP_H264`] and the SETUP of its track on
+    /// the channels 0-1 of session 1, as a scripted server
+    async fn describe_and_setup(cmd_tx: &mpsc::Sender<Command>, sstream: &mut tokio::io::DuplexStream) {
+        let (tx, describe) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com").unwrap();
+        cmd_tx
+            .send(Command::Request(Request::Describe(Describe::new(url, tx))))
+            .await
+            .unwrap();
+        read_requests(sstream, 1).await;
+        let response = format!(
+            "RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: {}\r\n\r\n{}",
+            SDP_H264.len(),
+            SDP_H264
+        );
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        describe.await.unwrap().unwrap();
+        let (tx, setup) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com/trackID=0").unwrap();
+        let cmd = Command::Request(Request::Setup(Setup::new(url, Transport::tcp((0, 1)), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(sstream, 1).await;
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nSession: 1\r\nTransport: RTP/AVP/TCP;interleaved=0-1\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        setup.await.unwrap().unwrap();
+    }
+
+    fn options(cmd_tx: &mpsc::Sender<Command>) -> oneshot::Receiver<CommandResult<Vec<Method>>> {
+        let (tx, rx) = oneshot::channel();
+        let cmd = Command::Request(Request::Options(Options::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.try_send(cmd).unwrap();
+        rx
+    }
+
+    #[tokio::test]
+    async fn test_channel_config() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let config = ChannelConfig {
+            user_agent: "camera-client".to_string(),
+            max_header_size: 64,
+            ..Default::default()
+        };
+        let channel = Channel::new(cstream, cmd_rx, packet_tx).config(config);
+        let handle = channel.events(event_tx).start();
+        let rx = options(&cmd_tx);
+        assert!(read_requests(&mut sstream, 1).await[0].contains("\r\nUser-Agent: camera-client"));
+        // The headers exceed the limit before they are complete
+        let mut response = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\nX-Padding: ".to_vec();
+        response.extend_from_slice(&[b'a'; 64]);
+        sstream.write_all(&response).await.unwrap();
+        assert!(rx.await.unwrap().is_err());
+        assert_eq!(event_rx.recv().await.unwrap(), Event::Connected);
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            Event::Disconnected {
+                reason: DisconnectReason::Error(Error::HeaderTooLong.to_string())
+            }
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_receive_buffer() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (cstream, _sstream) = tokio::io::duplex(4096);
+        let limits = ProfileLimits {
+            receive_buffer: 1024,
+            ..MediaProfile::Audio.limits()
+        };
+        let channel = Channel::new(cstream, cmd_rx, packet_tx).limits(limits);
+        assert_eq!(channel.buffer_rx.max_capacity(), MAX_INTERLEAVED_FRAME + 4096);
+        let channel = channel.max_body_size(128 * 1024);
+        assert_eq!(channel.buffer_rx.max_capacity(), 1024 + 128 * 1024 + 4096);
+    }
+
+    #[tokio::test]
+    async fn test_channel_nack() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let nack = Command::Nack {
+            channel: 0,
+            ssrc: 7,
+            lost: vec![10, 12],
+        };
+        cmd_tx.send(nack).await.unwrap();
+        let mut header = [0u8; 4];
+        sstream.read_exact(&mut header).await.unwrap();
+        assert_eq!(&header[..2], &[b'$', 1]);
+        let mut payload = vec![0u8; u16::from_be_bytes([header[2], header[3]]) as usize];
+        sstream.read_exact(&mut payload).await.unwrap();
+        let compound = rtcp::CompoundPacket::new(payload);
+        let packets: Vec<_> = compound.iter().collect::<std::result::Result<_, _>>().unwrap();
+        let [rtcp::RtcpPacket::ReceiverReport(rr), rtcp::RtcpPacket::Sdes(_), rtcp::RtcpPacket::Feedback(nack)] =
+            &packets[..]
+        else {
+            panic!("expected RR, SDES and NACK");
+        };
+        assert!(rr.report_blocks().is_empty());
+        assert_eq!(nack.sender_ssrc(), rr.ssrc());
+        assert_eq!(nack.media_ssrc(), 7);
+        assert_eq!(nack.nacks(), vec![(10, 0b10)]);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_interleaved_pair() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let channel = Channel::new(cstream, cmd_rx, packet_tx);
+        let writer = channel.interleaved_writer();
+        let handle = channel.start();
+        let (tx, setup) = oneshot::channel();
+        let url = Url::parse("rtsp://test.com/trackID=0").unwrap();
+        let cmd = Command::Request(Request::Setup(Setup::new(url, Transport::tcp((2, 3)), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        // The server picks channels that aren't adjacent
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nSession: 1\r\nTransport: RTP/AVP/TCP;interleaved=2-4\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        setup.await.unwrap().unwrap();
+
+        // RTCP on channel 4 isn't taken for RTP
+        let mut frames = vec![b'$', 4, 0, 8, 0x80, 0xc9, 0, 1, 0, 0, 0, 9];
+        frames.extend_from_slice(&[b'$', 2, 0, 13, 0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0, 5, 0xab]);
+        sstream.write_all(&frames).await.unwrap();
+        assert_eq!(packet_rx.recv().await.unwrap().ssrc(), 5);
+
+        let nack = Command::Nack {
+            channel: 2,
+            ssrc: 5,
+            lost: vec![3],
+        };
+        cmd_tx.send(nack).await.unwrap();
+        writer.send_rtcp(2, vec![0x80, 0xcb, 0, 0]).await.unwrap();
+        for _ in 0..2 {
+            let mut header = [0u8; 4];
+            sstream.read_exact(&mut header).await.unwrap();
+            assert_eq!(&header[..2], &[b'$', 4]);
+            let mut payload = vec![0u8; u16::from_be_bytes([header[2], header[3]]) as usize];
+            sstream.read_exact(&mut payload).await.unwrap();
+        }
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_rtx() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let mut media: sdp::Media = "video 0 RTP/AVPF 96 97".parse().unwrap();
+        for (name, value) in [
+            ("rtpmap", "96 H264/90000"),
+            ("rtpmap", "97 rtx/90000"),
+            ("fmtp", "97 apt=96"),
+        ] {
+            media.attributes.push((name.to_string(), Some(value.to_string())));
+        }
+        let mut stream = rtp::FrameStream::new(packet_rx, &media)
+            .unwrap()
+            .delay(Duration::from_millis(500))
+            .nack(cmd_tx.clone(), 0, Duration::from_millis(100));
+
+        let interleaved = |channel: u8, packet: rtp::Packet| {
+            let mut frame = vec![b'$', channel];
+            frame.extend_from_slice(&(packet.as_bytes().len() as u16).to_be_bytes());
+            frame.extend_from_slice(packet.as_bytes());
+            frame
+        };
+        let mut builder = rtp::PacketBuilder::new(96, 7).sequence_number(1).timestamp_offset(0);
+        let first = builder.build(&[0x65, 1], 0, true);
+        builder.build(&[0x41, 2], 3000, true);
+        let third = builder.build(&[0x41, 3], 6000, true);
+        sstream.write_all(&interleaved(0, first)).await.unwrap();
+        sstream.write_all(&interleaved(0, third)).await.unwrap();
+        let server = tokio::spawn(async move {
+            // The gap is reported on the RTCP channel of the track
+            let mut header = [0u8; 4];
+            sstream.read_exact(&mut header).await.unwrap();
+            assert_eq!(&header[..2], &[b'$', 1]);
+            let mut payload = vec![0u8; u16::from_be_bytes([header[2], header[3]]) as usize];
+            sstream.read_exact(&mut payload).await.unwrap();
+            let compound = rtcp::CompoundPacket::new(payload);
+            let nack = compound.iter().find_map(|packet| match packet {
+                Ok(rtcp::RtcpPacket::Feedback(nack)) => Some((nack.media_ssrc(), nack.nacks())),
+                _ => None,
+            });
+            assert_eq!(nack, Some((7, vec![(2, 0)])));
+            // The retransmission of the lost packet, its sequence number in front of the payload
+            let mut rtx = rtp::PacketBuilder::new(97, 7).sequence_number(100).timestamp_offset(0);
+            let rtx = rtx.build(&[0, 2, 0x41, 2], 3000, true);
+            sstream.write_all(&interleaved(0, rtx)).await.unwrap();
+            sstream
+        });
+
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            let frame = stream.next().await.unwrap();
+            frames.push((frame.rtp_timestamp(), frame.frame.data.clone(), frame.discontinuity));
+        }
+        assert_eq!(
+            frames,
+            [
+                (0, vec![0, 0, 0, 1, 0x65, 1], false),
+                (3000, vec![0, 0, 0, 1, 0x41, 2], false),
+                (6000, vec![0, 0, 0, 1, 0x41, 3], false),
+            ]
+        );
+        assert_eq!(stream.jitter_stats().lost, 0);
+        drop(server.await.unwrap());
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_synchronizer() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let sync = Arc::new(Mutex::new(Synchronizer::new()));
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .synchronizer(sync.clone())
+            .start();
+        describe_and_setup(&cmd_tx, &mut sstream).await;
+
+        // A sender report mapping RTP timestamp 90000 to NTP second 100
+        let mut frame = vec![b'$', 1, 0, 28, 0x80, 200, 0, 6, 0, 0, 0, 5, 0, 0, 0, 100, 0, 0, 0, 0];
+        frame.extend_from_slice(&90_000u32.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        // The packet after it shows that it was handled
+        frame.extend_from_slice(&[b'$', 0, 0, 13, 0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0, 5, 0xab]);
+        sstream.write_all(&frame).await.unwrap();
+        packet_rx.recv().await.unwrap();
+        let capture = sync.lock().unwrap().capture_time(5, 180_000).unwrap();
+        assert_eq!(capture, NtpTimestamp(101 << 32).to_system_time());
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_quality() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let quality = QualityHandle::new();
+        let mut video = quality.subscribe(0);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .share_quality(quality.clone())
+            .start();
+        // Packet 2 is lost
+        for seq in [1, 3, 4] {
+            let mut frame = vec![b'$', 0, 0, 13, 0x80, 0x60];
+            frame.extend_from_slice(&u16::to_be_bytes(seq));
+            frame.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 5, 0xab]);
+            sstream.write_all(&frame).await.unwrap();
+            packet_rx.recv().await.unwrap();
+        }
+        assert!(!video.has_changed().unwrap());
+        let mut frame = vec![b'$', 1, 0, 28, 0x80, 200, 0, 6, 0, 0, 0, 5];
+        frame.extend_from_slice(&[0; 24]);
+        sstream.write_all(&frame).await.unwrap();
+        video.changed().await.unwrap();
+        let track = *video.borrow();
+        assert_eq!(track.loss_percent, 25.0);
+        assert!(track.last_sender_report.is_some());
+        assert_eq!(track.rtt, None);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_stats() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let stats = StatsHandle::new();
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).stats(stats.clone()).start();
+        // Packet 2 is lost
+        for seq in [1, 3, 4] {
+            let mut frame = vec![b'$', 2, 0, 13, 0x80, 0x60];
+            frame.extend_from_slice(&u16::to_be_bytes(seq));
+            frame.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 5, 0xab]);
+            sstream.write_all(&frame).await.unwrap();
+            packet_rx.recv().await.unwrap();
+        }
+        let snapshot = stats.snapshot();
+        let track = snapshot.track(2).unwrap();
+        assert_eq!((track.packets, track.bytes, track.lost), (3, 39, 1));
+        assert_eq!(track.last_timestamp, Some(0));
+        assert!(snapshot.track(0).is_none());
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_demux() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (demux_tx, mut demux_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let stats = StatsHandle::new();
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .stats(stats.clone())
+            .demux(demux_tx, rtp::Delivery::default())
+            .track_delivery(1, rtp::Delivery::Passthrough)
+            .start();
+        // The video track is set up first but on the higher channels
+        for (cseq, channels) in [(1, (2, 3)), (2, (0, 1))] {
+            let (tx, setup) = oneshot::channel();
+            let url = Url::parse(&format!("rtsp://test.com/trackID={}", cseq - 1)).unwrap();
+            let cmd = Command::Request(Request::Setup(Setup::new(url, Transport::tcp(channels), tx)));
+            cmd_tx.send(cmd).await.unwrap();
+            read_requests(&mut sstream, 1).await;
+            let response = format!(
+                "RTSP/1.0 200 OK\r\nCSeq: {}\r\nSession: 1\r\nTransport: RTP/AVP/TCP;interleaved={}-{}\r\n\r\n",
+                cseq, channels.0, channels.1
+            );
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            setup.await.unwrap().unwrap();
+        }
+
+        let mut frames = Vec::new();
+        // The audio track is passed through in arrival order, 14 of the video track waits for 13
+        for (channel, seq, ssrc) in [
+            (2u8, 10u16, 1u8),
+            (2, 12, 1),
+            (0, 6, 3),
+            (0, 5, 3),
+            (2, 11, 1),
+            (2, 14, 1),
+        ] {
+            frames.extend_from_slice(&[b'$', channel, 0, 12, 0x80, 0x60]);
+            frames.extend_from_slice(&seq.to_be_bytes());
+            frames.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, ssrc]);
+        }
+        sstream.write_all(&frames).await.unwrap();
+        let mut out = Vec::new();
+        for _ in 0..5 {
+            let p = demux_rx.recv().await.unwrap();
+            out.push((p.track, p.ssrc, p.packet.packet.sequence_number()));
+        }
+        assert_eq!(out, [(0, 1, 10), (1, 3, 6), (1, 3, 5), (0, 1, 11), (0, 1, 12)]);
+        for _ in 0..6 {
+            packet_rx.recv().await.unwrap();
+        }
+        assert_eq!(stats.snapshot().track(2).unwrap().reorder_depth, 1);
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_payload_type_mismatch() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).events(event_tx).start();
+        describe_and_setup(&cmd_tx, &mut sstream).await;
+
+        // The camera sends 97 instead of the described 96, reported once
+        let mut frames = Vec::new();
+        for seq in 1..=2 {
+            frames.extend_from_slice(&[b'$', 0, 0, 12, 0x80, 97, 0, seq, 0, 0, 0, 0, 0, 0, 0, 5]);
+        }
+        sstream.write_all(&frames).await.unwrap();
+        packet_rx.recv().await.unwrap();
+        packet_rx.recv().await.unwrap();
+        let mismatches: Vec<_> = std::iter::from_fn(|| event_rx.try_recv().ok())
+            .filter(|e| matches!(e, Event::PayloadTypeMismatch { .. }))
+            .collect();
+        assert_eq!(
+            mismatches,
+            [Event::PayloadTypeMismatch {
+                channel: 0,
+                described: vec![96],
+                received: 97
+            }]
+        );
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_startup_latency() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let channel = Channel::new(cstream, cmd_rx, packet_tx).events(event_tx);
+        let latency = channel.latency();
+        let handle = channel.start();
+        describe_and_setup(&cmd_tx, &mut sstream).await;
+        let (tx, play) = oneshot::channel();
+        let cmd = Command::Request(Request::Play(Play::new(Url::parse("rtsp://test.com").unwrap(), tx)));
+        cmd_tx.send(cmd).await.unwrap();
+        read_requests(&mut sstream, 1).await;
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 3\r\nSession: 1\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        play.await.unwrap().unwrap();
+
+        // A non-IDR slice, then an IDR slice and another one
+        let mut frames = Vec::new();
+        for (seq, nal) in [(1, 0x41), (2, 0x65), (3, 0x65)] {
+            frames.extend_from_slice(&[b'$', 0, 0, 13, 0x80, 96, 0, seq, 0, 0, 0, 0, 0, 0, 0, 5, nal]);
+        }
+        sstream.write_all(&frames).await.unwrap();
+        for _ in 0..3 {
+            packet_rx.recv().await.unwrap();
+        }
+        let startup: Vec<_> = std::iter::from_fn(|| event_rx.try_recv().ok())
+            .filter_map(|e| match e {
+                Event::FirstPacket { channel, .. } => Some(("packet", channel)),
+                Event::FirstKeyframe { channel, .. } => Some(("keyframe", channel)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(startup, [("packet", 0), ("keyframe", 0)]);
+        let latencies = latency.snapshot();
+        assert_eq!(latencies.first_packet(0).unwrap().count(), 1);
+        assert_eq!(latencies.first_keyframe(0).unwrap().count(), 1);
+        assert!(latencies.first_packet(2).is_none());
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_redirect() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let (tx, rx) = oneshot::channel();
+            let url = Url::parse("rtsp://lb.test.com/live").unwrap();
+            let cmd = Command::Request(Request::Describe(Describe::new(url, tx)));
+            cmd_tx.send(cmd).await.unwrap();
+            results.push(rx);
+        }
+        read_requests(&mut sstream, 2).await;
+        let responses = "RTSP/1.0 302 Moved Temporarily\r\nCSeq: 1\r\nLocation: rtsp://node1.test.com/live\r\n\r\n\
+            RTSP/1.0 302 Moved Temporarily\r\nCSeq: 2\r\n\r\n";
+        sstream.write_all(responses.as_bytes()).await.unwrap();
+        let location = match results.remove(0).await.unwrap() {
+            Err(CommandError::Redirected(location)) => location,
+            other => panic!("expected a redirect, got {:?}", other),
+        };
+        assert_eq!(location.as_str(), "rtsp://node1.test.com/live");
+        // Without a location the status is all there is
+        assert!(matches!(
+            results.remove(0).await.unwrap(),
+            Err(CommandError::UnexpectedStatus(Status::MovedTemporarily))
+        ));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_backchannel() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .require(ONVIF_BACKCHANNEL)
+            .start();
+        // OPTIONS doesn't carry the Require header
+        let rx = options(&cmd_tx);
+        assert!(!read_requests(&mut sstream, 1).await[0].contains("Require"));
+        sstream.write_all(b"RTSP/1.0 200 OK\r\nCSeq: 1\r\n\r\n").await.unwrap();
+        rx.await.unwrap().unwrap();
+        let (tx, _rx) = oneshot::channel();
+        let describe = Request::Describe(Describe::new(Url::parse("rtsp://test.com").unwrap(), tx));
+        cmd_tx.send(Command::Request(describe)).await.unwrap();
+        let request = &read_requests(&mut sstream, 1).await[0];
+        assert!(request.contains("\r\nRequire: www.onvif.org/ver20/backchannel"));
+
+        let packet = rtp::PacketBuilder::new(0, 1).build(&[0xd5; 4], 0, false);
+        let payload = packet.bytes().clone();
+        cmd_tx.send(Command::Interleaved { channel: 2, payload }).await.unwrap();
+        let mut frame = [0u8; 20];
+        sstream.read_exact(&mut frame).await.unwrap();
+        assert_eq!(&frame[..4], &[b'$', 2, 0, 16]);
+        assert_eq!(&frame[4..], packet.as_bytes());
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_via() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .via(Via::new("edge.test.com"))
+            .proxy_header("X-Route")
+            .start();
+        let rx = options(&cmd_tx);
+        let request = &read_requests(&mut sstream, 1).await[0];
+        assert!(request.contains("\r\nVia: RTSP/1.0 edge.test.com"));
+        assert!(!request.contains("X-Route"));
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nVia: RTSP/1.0 edge.test.com, RTSP/1.0 core\r\n\
+                        X-Route: node7\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        rx.await.unwrap().unwrap();
+
+        // The routing header is sent back, the request passed the edge twice
+        let rx = options(&cmd_tx);
+        assert!(read_requests(&mut sstream, 1).await[0].contains("\r\nX-Route: node7"));
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nVia: RTSP/1.0 edge.test.com\r\n\
+                        Via: RTSP/1.0 core, RTSP/1.0 Edge.test.com\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        assert!(matches!(rx.await.unwrap(), Err(CommandError::LoopDetected)));
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_strict() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _packet_rx) = mpsc::channel(8);
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .strict(true)
+            .quirks(Quirks::all())
+            .events(event_tx)
+            .start();
+        assert_eq!(event_rx.recv().await.unwrap(), Event::Connected);
+        let rx = options(&cmd_tx);
+        read_requests(&mut sstream, 1).await;
+        let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nSession: 1234;timeout=\r\nPublic: describe, play\r\n\r\n";
+        sstream.write_all(response.as_bytes()).await.unwrap();
+        // The response is still processed, the quirks fix the methods
+        assert_eq!(rx.await.unwrap().unwrap(), [Method::Describe, Method::Play]);
+        let violation = |violation| Event::SpecViolation {
+            method: Method::Options,
+            status: Status::OK,
+            violation,
+        };
+        assert_eq!(
+            event_rx.recv().await.unwrap(),
+            violation(Violation::MissingHeader("Date"))
+        );
         assert_eq!(
-            std::str::from_utf8(&read_buf[..n]).unwrap(),
-            "DESCRIBE rtsp://test.com RTSP/1.0\r\nCSeq: 1\r\nUser-Agent: rs-streamer\r\n\r\n"
+            event_rx.recv().await.unwrap(),
+            violation(Violation::InvalidHeader {
+                name: "Session",
+                value: "1234;timeout=".to_string()
+            })
         );
-        let mut write_buf = Vec::<u8>::new();
-        write!(write_buf, "RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 4\r\n\r\ntest").unwrap();
-        sstream.write_all(&write_buf).await.unwrap();
-    });
-    let channel = Channel::new(cstream, cmd_rx, packet_tx);
-    let handle = channel.start();
-    let (tx, rx) = oneshot::channel();
-    let cmd = Command::Request(Request::Describe(Describe::new(
-        Url::parse("rtsp://test.com").unwrap(),
-        tx,
-    )));
-    cmd_tx.send(cmd).await.unwrap();
-    let response = rx.await.unwrap().unwrap();
-    handle.await.unwrap();
+        assert!(event_rx.try_recv().is_err());
+        drop(sstream);
+        handle.await.unwrap();
+    }
+
+    #[test]
+    fn test_channel_interleaved_queue_limit() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, _sstream) = tokio::io::duplex(16);
+        let mut channel = Channel::new(cstream, cmd_rx, packet_tx);
+        // Commands, NACKs and receiver reports can't queue more than writers
+        for _ in 0..DEFAULT_WRITER_CAPACITY + 2 {
+            channel.handle_command(Command::Interleaved {
+                channel: 2,
+                payload: Bytes::from_static(&[0; 8]),
+            });
+        }
+        channel.send_nack(0, 1, &[5]);
+        assert_eq!(channel.frame_queue.len(), DEFAULT_WRITER_CAPACITY);
+        assert_eq!(channel.usage().snapshot().dropped.packets, 3);
+    }
+
+    #[tokio::test]
+    async fn test_channel_interleaved_writer() {
+        let (_cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let channel = Channel::new(cstream, cmd_rx, packet_tx);
+        let writer = channel.interleaved_writer();
+        for i in 0..DEFAULT_WRITER_CAPACITY {
+            writer.try_send(4, vec![i as u8]).unwrap();
+        }
+        assert_eq!(writer.try_send(4, vec![0]), Err(InterleavedError::Full));
+        let handle = channel.start();
+        let mut frames = vec![0u8; DEFAULT_WRITER_CAPACITY * 5];
+        sstream.read_exact(&mut frames).await.unwrap();
+        for (i, frame) in frames.chunks(5).enumerate() {
+            assert_eq!(frame, [b'$', 4, 0, 1, i as u8]);
+        }
+
+        writer.send_rtcp(0, vec![0x80, 201, 0, 1, 0, 0, 0, 9]).await.unwrap();
+        let mut frame = [0u8; 12];
+        sstream.read_exact(&mut frame).await.unwrap();
+        assert_eq!(&frame[..4], &[b'$', 1, 0, 8]);
+        drop(sstream);
+        handle.await.unwrap();
+        assert!(writer.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_channel_pipelining() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let requests = read_requests(&mut sstream, 2).await;
+            assert!(requests[0].contains("CSeq: 1\r\n") && requests[1].contains("CSeq: 2\r\n"));
+            // The third request waits for a free slot
+            let mut read_buf = [0u8; 1];
+            let read = tokio::time::timeout(Duration::from_millis(50), sstream.read(&mut read_buf));
+            assert!(read.await.is_err());
+            let responses = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nPublic: PLAY\r\n\r\n\
+                RTSP/1.0 200 OK\r\nCSeq: 1\r\nPublic: DESCRIBE\r\n\r\n";
+            sstream.write_all(responses.as_bytes()).await.unwrap();
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(requests[0].contains("CSeq: 3\r\n"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 3\r\nPublic: SETUP\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).max_outstanding(2).start();
+        let first = options(&cmd_tx);
+        let second = options(&cmd_tx);
+        let third = options(&cmd_tx);
+        // Responses arrived out of order but are matched by CSeq
+        assert_eq!(first.await.unwrap().unwrap(), [Method::Describe]);
+        assert_eq!(second.await.unwrap().unwrap(), [Method::Play]);
+        assert_eq!(third.await.unwrap().unwrap(), [Method::Setup]);
+        server.await.unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_pipelined_setup_waits_for_session() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let requests = read_requests(&mut sstream, 2).await;
+            assert!(requests[0].starts_with("OPTIONS") && requests[1].starts_with("SETUP"));
+            let mut read_buf = [0u8; 1];
+            let read = tokio::time::timeout(Duration::from_millis(50), sstream.read(&mut read_buf));
+            assert!(read.await.is_err());
+            let responses = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nSession: abc\r\n\
+                Transport: RTP/AVP/TCP;unicast;interleaved=0-1\r\n\r\n\
+                RTSP/1.0 200 OK\r\nCSeq: 1\r\nPublic: SETUP\r\n\r\n";
+            sstream.write_all(responses.as_bytes()).await.unwrap();
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(requests[0].starts_with("SETUP rtsp://test.com/track2"));
+            assert!(requests[0].contains("Session: abc\r\n"));
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let _options = options(&cmd_tx);
+        // Requests whose receiver was dropped are not sent
+        let mut receivers = Vec::new();
+        for (track, channel) in [("track1", 0), ("track2", 2)] {
+            let (tx, rx) = oneshot::channel();
+            let url = Url::parse(&format!("rtsp://test.com/{}", track)).unwrap();
+            let setup = Setup::new(url, Transport::tcp((channel, channel + 1)), tx);
+            cmd_tx.try_send(Command::Request(Request::Setup(setup))).unwrap();
+            receivers.push(rx);
+        }
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_channel_proxy_authentication() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(!requests[0].contains("Authorization"));
+            let response = "RTSP/1.0 407 Proxy Authentication Required\r\nCSeq: 1\r\n\
+                Proxy-Authenticate: Basic realm=\"proxy\"\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            // Retried with the proxy credentials and sent preemptively afterwards
+            for cseq in 2..=3 {
+                let requests = read_requests(&mut sstream, 1).await;
+                assert!(requests[0].contains("\r\nProxy-Authorization: Basic cHJveHk6c2VjcmV0"));
+                assert!(!requests[0].contains("\r\nAuthorization"));
+                let response = format!("RTSP/1.0 200 OK\r\nCSeq: {}\r\nPublic: DESCRIBE\r\n\r\n", cseq);
+                sstream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("pass")
+            .proxy_credentials("proxy", "secret")
+            .events(event_tx)
+            .start();
+        assert_eq!(options(&cmd_tx).await.unwrap().unwrap(), [Method::Describe]);
+        assert_eq!(options(&cmd_tx).await.unwrap().unwrap(), [Method::Describe]);
+        assert_eq!(event_rx.recv().await, Some(Event::Connected));
+        let challenge = Event::AuthChallenge {
+            method: Method::Options,
+            proxy: true,
+            schemes: vec!["Basic".to_string()],
+            realm: Some("proxy".to_string()),
+        };
+        assert_eq!(event_rx.recv().await, Some(challenge));
+        let selected = Event::AuthSchemeSelected {
+            scheme: "Basic".to_string(),
+            algorithm: None,
+            proxy: true,
+        };
+        assert_eq!(event_rx.recv().await, Some(selected));
+        let retry = Event::UnauthorizedRetry {
+            method: Method::Options,
+            proxy: true,
+            attempt: 1,
+        };
+        assert_eq!(event_rx.recv().await, Some(retry));
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_digest_stale_nonce() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let challenge = |cseq, nonce, stale| {
+                format!(
+                    "RTSP/1.0 401 Unauthorized\r\nCSeq: {}\r\n\
+                    WWW-Authenticate: Digest realm=\"cam\", nonce=\"{}\", qop=\"auth\", stale={}\r\n\r\n",
+                    cseq, nonce, stale
+                )
+            };
+            let ok = |cseq| format!("RTSP/1.0 200 OK\r\nCSeq: {}\r\nPublic: DESCRIBE\r\n\r\n", cseq);
+            let mut exchange = [
+                (None, challenge(1, "a", false)),
+                (Some(("a", 1)), ok(2)),
+                // Sent preemptively with the next nonce count
+                (Some(("a", 2)), challenge(3, "b", true)),
+                (Some(("b", 1)), ok(4)),
+            ]
+            .into_iter();
+            for (authorization, response) in &mut exchange {
+                let requests = read_requests(&mut sstream, 1).await;
+                match authorization {
+                    Some((nonce, nc)) => {
+                        assert!(requests[0].contains(&format!("nonce=\"{}\"", nonce)));
+                        assert!(requests[0].contains(&format!("nc={:08x}", nc)));
+                    }
+                    None => assert!(!requests[0].contains("Authorization")),
+                }
+                sstream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("pass")
+            .start();
+        assert!(options(&cmd_tx).await.unwrap().is_ok());
+        assert!(options(&cmd_tx).await.unwrap().is_ok());
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_datagram_retransmission() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            // PLAY isn't idempotent but is retransmitted with the same CSeq
+            for _ in 0..2 {
+                let requests = read_requests(&mut sstream, 1).await;
+                assert!(requests[0].starts_with("PLAY") && requests[0].contains("CSeq: 1\r\n"));
+            }
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\n\r\n";
+            sstream.write_all(response.repeat(2).as_bytes()).await.unwrap();
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(requests[0].contains("CSeq: 2\r\n"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nPublic: PLAY\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .datagram()
+            .timeout(Duration::from_millis(500))
+            .retries(1)
+            .start();
+        let (tx, rx) = oneshot::channel();
+        let play = Play::new(Url::parse("rtsp://test.com").unwrap(), tx);
+        cmd_tx.send(Command::Request(Request::Play(play))).await.unwrap();
+        assert!(rx.await.unwrap().is_ok());
+        // The duplicate response was discarded without failing the channel
+        assert_eq!(options(&cmd_tx).await.unwrap().unwrap(), [Method::Play]);
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_command_handle() {
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let (cstream, mut sstream) = tokio::io::duplex(4096);
+        let (respond_tx, respond_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(requests[0].starts_with("OPTIONS"));
+            respond_rx.await.unwrap();
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 1\r\nPublic: PLAY\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+            // The cancelled DESCRIBE is skipped
+            let requests = read_requests(&mut sstream, 1).await;
+            assert!(requests[0].starts_with("OPTIONS") && requests[0].contains("CSeq: 2\r\n"));
+            let response = "RTSP/1.0 200 OK\r\nCSeq: 2\r\nPublic: PLAY\r\n\r\n";
+            sstream.write_all(response.as_bytes()).await.unwrap();
+        });
+        let handle = Channel::new(cstream, cmd_rx, packet_tx).max_outstanding(1).start();
+        let url = Url::parse("rtsp://test.com").unwrap();
+        let first = CommandHandle::send(&cmd_tx, |tx| Request::Options(Options::new(url.clone(), tx)))
+            .await
+            .unwrap();
+        let second = CommandHandle::send(&cmd_tx, |tx| Request::Describe(Describe::new(url.clone(), tx)))
+            .await
+            .unwrap();
+        let third = CommandHandle::send(&cmd_tx, |tx| Request::Options(Options::new(url.clone(), tx)))
+            .await
+            .unwrap();
+        while first.state() == CommandState::Queued {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(first.state(), CommandState::Sent);
+        assert_eq!(second.state(), CommandState::Queued);
+        assert!(second.cancel());
+        assert_eq!(second.state(), CommandState::Cancelled);
+        respond_tx.send(()).unwrap();
+        assert!(matches!(second.await, Err(CommandError::Cancelled)));
+        assert_eq!(third.await.unwrap(), [Method::Play]);
+        assert_eq!(first.state(), CommandState::Completed);
+        assert!(!first.cancel());
+        assert_eq!(first.await.unwrap(), [Method::Play]);
+        server.await.unwrap();
+        drop(cmd_tx);
+        handle.await.unwrap();
+    }
 }
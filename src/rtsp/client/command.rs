@@ -1,9 +1,12 @@
-use super::*;
+use super::authorizer::Error as AuthorizerError;
+use super::handle::Tracker;
+use super::quirks::{content_base, control_url};
+use crate::metrics::BudgetError;
 use crate::rtsp::protocol::*;
 use crate::sdp;
 
+use bytes::Bytes;
 use std::fmt;
-
 use thiserror::Error;
 use tokio::sync::oneshot;
 
@@ -11,34 +14,159 @@ use tokio::sync::oneshot;
 pub enum Error {
     #[error(transparent)]
     ParseSdp(#[from] sdp::ParseError),
+    #[error(transparent)]
+    ParseTransport(#[from] ParseTransportError),
+    #[error(transparent)]
+    ParseSession(#[from] ParseSessionError),
     #[error("Unexpected status code: {0}")]
     UnexpectedStatus(Status),
+    /// The server answered with 301, 302 or 303 and the resolved `Location`,
+    /// the request has to be sent there
+    #[error("Redirected to {0}")]
+    Redirected(url::Url),
     #[error("Unauthorized")]
     Unauthorized,
+    /// The credentials for the challenge of the server couldn't be computed
+    #[error(transparent)]
+    Authorization(#[from] AuthorizerError),
     #[error("Cancelled")]
     Cancelled,
+    #[error("Timeout")]
+    Timeout,
     #[error("Bad response")]
     BadResponse,
+    #[error(transparent)]
+    ParseContentType(#[from] ParseContentTypeError),
+    #[error("Unsupported content encoding {0}")]
+    UnsupportedContentEncoding(ContentEncoding),
+    /// The body isn't valid UTF-8, only the command fails
+    #[error(transparent)]
+    Encoding(#[from] std::str::Utf8Error),
+    #[error("Unsupported charset {0}")]
+    UnsupportedCharset(String),
+    #[error("Expected SDP but got {0}")]
+    NotSdp(ContentType),
+    #[error("Missing header {0}")]
+    MissingHeader(&'static str),
+    /// The description is WebRTC style, the attributes and protocols that
+    /// need ICE or DTLS are named, see [`Sdp::webrtc_attributes`](sdp::Sdp::webrtc_attributes)
+    #[error("Unsupported transport, the description requires {}", .0.join(", "))]
+    UnsupportedTransport(Vec<String>),
+    #[error(transparent)]
+    BudgetExceeded(#[from] BudgetError),
+    /// The response lists the [`Via`] of the channel twice, the request
+    /// came back through the same proxy
+    #[error("Request loop detected")]
+    LoopDetected,
     #[error("Unknown error")]
     Unknown,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+fn find_header<'a>(headers: &'a [Header], name: &'static str) -> Result<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value)
+        .ok_or(Error::MissingHeader(name))
+}
+
+/// Decodes a text body, the charset of the Content-Type defaults to UTF-8
+fn decode<'a>(content_type: Option<&ContentType>, body: &'a [u8]) -> Result<&'a str> {
+    match content_type.and_then(|c| c.param("charset")) {
+        Some(charset) if !charset.eq_ignore_ascii_case("utf-8") && !charset.eq_ignore_ascii_case("us-ascii") => {
+            Err(Error::UnsupportedCharset(charset.to_string()))
+        }
+        _ => Ok(std::str::from_utf8(body)?),
+    }
+}
+
+/// Result of a DESCRIBE, the body is only parsed if it is SDP
+#[derive(Debug, Clone)]
+pub enum Description {
+    Sdp(sdp::Sdp),
+    /// Body of any other content type, passed on unparsed and undecoded
+    Raw { content_type: ContentType, body: Bytes },
+}
+
+impl Description {
+    pub fn sdp(&self) -> Option<&sdp::Sdp> {
+        match self {
+            Description::Sdp(sdp) => Some(sdp),
+            Description::Raw { .. } => None,
+        }
+    }
+
+    pub fn into_sdp(self) -> Result<sdp::Sdp> {
+        match self {
+            Description::Sdp(sdp) => Ok(sdp),
+            Description::Raw { content_type, .. } => Err(Error::NotSdp(content_type)),
+        }
+    }
+}
+
+/// Makes the track controls absolute, resolved against the Content-Base or
+/// Content-Location of the response
+fn resolve_controls(sdp: &mut sdp::Sdp, base: &url::Url) {
+    let media = sdp.media.iter_mut().map(|m| &mut m.attributes);
+    let controls = std::iter::once(&mut sdp.attributes)
+        .chain(media)
+        .flat_map(|attributes| attributes.iter_mut())
+        .filter_map(|(name, value)| value.as_mut().filter(|_| name == "control"));
+    for control in controls.filter(|control| *control != "*") {
+        *control = control_url(base, control).to_string();
+    }
+}
+
 pub struct Describe {
     url: url::Url,
-    tx: oneshot::Sender<Result<sdp::Sdp>>,
+    tx: oneshot::Sender<Result<Description>>,
+    tracker: Tracker,
 }
 
 impl Describe {
-    pub fn handle_response(self, status: Status, _headers: &[Header], body: &str) {
+    fn parse_sdp(body: &str, base: Option<&url::Url>) -> Result<Description> {
+        let mut sdp = sdp::Sdp::try_from(body)?;
+        let webrtc = sdp.webrtc_attributes();
+        if !webrtc.is_empty() {
+            return Err(Error::UnsupportedTransport(webrtc));
+        }
+        if let Some(base) = base {
+            resolve_controls(&mut sdp, base);
+        }
+        Ok(Description::Sdp(sdp))
+    }
+
+    fn parse_response(&self, headers: &[Header], body: &[u8]) -> Result<Description> {
+        let encoding = ContentEncoding::from(find_header(headers, "Content-Encoding").unwrap_or_default());
+        if !encoding.is_identity() {
+            return Err(Error::UnsupportedContentEncoding(encoding));
+        }
+        let base = content_base(&self.url, headers);
+        // A missing Content-Type is common with cameras, assume SDP then
+        match find_header(headers, "Content-Type") {
+            Ok(value) => {
+                let content_type: ContentType = value.parse()?;
+                if content_type.is_sdp() {
+                    Self::parse_sdp(decode(Some(&content_type), body)?, base.as_ref())
+                } else {
+                    Ok(Description::Raw {
+                        content_type,
+                        body: Bytes::copy_from_slice(body),
+                    })
+                }
+            }
+            Err(_) => Self::parse_sdp(decode(None, body)?, base.as_ref()),
+        }
+    }
+
+    pub fn handle_response(self, status: Status, headers: &[Header], body: &[u8]) {
         if status != Status::OK {
             let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
         } else {
-            match sdp::Sdp::try_from(body) {
-                Ok(sdp) => self.tx.send(Ok(sdp)),
-                Err(e) => self.tx.send(Err(Error::ParseSdp(e))),
-            };
+            let description = self.parse_response(headers, body);
+            let _ = self.tx.send(description);
         }
     }
 
@@ -54,37 +182,483 @@ impl Describe {
         let _ = self.tx.send(Err(e));
     }
 
-    pub fn new(url: url::Url, tx: oneshot::Sender<Result<sdp::Sdp>>) -> Self {
-        Self { url, tx }
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new(url: url::Url, tx: oneshot::Sender<Result<Description>>) -> Self {
+        Self {
+            url,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+/// Queries the methods supported by the server, as listed in the `Public` header
+pub struct Options {
+    url: url::Url,
+    tx: oneshot::Sender<Result<Vec<Method>>>,
+    tracker: Tracker,
+}
+
+impl Options {
+    pub fn handle_response(self, status: Status, headers: &[Header], _body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let methods = find_header(headers, "Public")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|m| m.trim().parse().ok())
+                .collect();
+            let _ = self.tx.send(Ok(methods));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::Options
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new(url: url::Url, tx: oneshot::Sender<Result<Vec<Method>>>) -> Self {
+        Self {
+            url,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SetupResponse {
+    pub session: Session,
+    pub transport: Transport,
+}
+
+pub struct Setup {
+    url: url::Url,
+    transport: Transport,
+    tx: oneshot::Sender<Result<SetupResponse>>,
+    tracker: Tracker,
+}
+
+impl Setup {
+    fn parse_response(headers: &[Header]) -> Result<SetupResponse> {
+        Ok(SetupResponse {
+            session: find_header(headers, "Session")?.parse()?,
+            transport: find_header(headers, "Transport")?.parse()?,
+        })
+    }
+
+    pub fn handle_response(self, status: Status, headers: &[Header], _body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let _ = self.tx.send(Self::parse_response(headers));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::Setup
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new(url: url::Url, transport: Transport, tx: oneshot::Sender<Result<SetupResponse>>) -> Self {
+        Self {
+            url,
+            transport,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+/// Where and how fast the server plays, from the PLAY response
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayResponse {
+    /// The range the server plays, missing or in an unsupported unit for
+    /// many live servers
+    pub range: Option<Range>,
+    /// The scale the server plays at, it may differ from the requested one
+    pub scale: Option<f64>,
+}
+
+pub struct Play {
+    url: url::Url,
+    range: Option<Range>,
+    scale: Option<f64>,
+    tx: oneshot::Sender<Result<PlayResponse>>,
+    tracker: Tracker,
+}
+
+impl Play {
+    fn parse_response(headers: &[Header]) -> PlayResponse {
+        let range = find_header(headers, "Range").ok().and_then(|v| match v.parse() {
+            Ok(range) => Some(range),
+            Err(e) => {
+                log::debug!("Ignoring range {}: {}", v, e);
+                None
+            }
+        });
+        let scale = find_header(headers, "Scale").ok().and_then(|v| v.trim().parse().ok());
+        PlayResponse { range, scale }
+    }
+
+    pub fn handle_response(self, status: Status, headers: &[Header], _body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let _ = self.tx.send(Ok(Self::parse_response(headers)));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::Play
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new(url: url::Url, tx: oneshot::Sender<Result<PlayResponse>>) -> Self {
+        Self {
+            url,
+            range: None,
+            scale: None,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+
+    /// Plays `range`, e.g. a `clock` range of a recording, or seeks to it
+    /// when the session is playing already
+    pub fn range(mut self, range: Range) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Playback speed relative to normal, e.g. 4 for fast forward and a
+    /// negative scale for reverse playback
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+}
+
+pub struct Teardown {
+    url: url::Url,
+    tx: oneshot::Sender<Result<()>>,
+    tracker: Tracker,
+}
+
+impl Teardown {
+    pub fn handle_response(self, status: Status, _headers: &[Header], _body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let _ = self.tx.send(Ok(()));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::Teardown
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new(url: url::Url, tx: oneshot::Sender<Result<()>>) -> Self {
+        Self {
+            url,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+/// Parses a `text/parameters` body, lines of `name: value`
+pub fn parse_parameters(body: &str) -> Vec<(String, String)> {
+    body.lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':').unwrap_or((line, ""));
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Reads parameters, without names it only keeps the session alive
+pub struct GetParameter {
+    url: url::Url,
+    body: Option<String>,
+    tx: oneshot::Sender<Result<Vec<(String, String)>>>,
+    tracker: Tracker,
+}
+
+impl GetParameter {
+    pub fn handle_response(self, status: Status, headers: &[Header], body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let content_type = find_header(headers, "Content-Type").ok().and_then(|v| v.parse().ok());
+            let _ = self.tx.send(decode(content_type.as_ref(), body).map(parse_parameters));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::GetParameter
+    }
+
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new<S: AsRef<str>>(url: url::Url, names: &[S], tx: oneshot::Sender<Result<Vec<(String, String)>>>) -> Self {
+        let body = (!names.is_empty()).then(|| names.iter().map(|n| format!("{}\r\n", n.as_ref())).collect());
+        Self {
+            url,
+            body,
+            tx,
+            tracker: Tracker::default(),
+        }
+    }
+}
+
+pub struct SetParameter {
+    url: url::Url,
+    body: String,
+    tx: oneshot::Sender<Result<()>>,
+    tracker: Tracker,
+}
+
+impl SetParameter {
+    pub fn handle_response(self, status: Status, _headers: &[Header], _body: &[u8]) {
+        if status != Status::OK {
+            let _ = self.tx.send(Err(Error::UnexpectedStatus(status)));
+        } else {
+            let _ = self.tx.send(Ok(()));
+        }
+    }
+
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> Method {
+        Method::SetParameter
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn cancel(self, e: Error) {
+        let _ = self.tx.send(Err(e));
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    pub fn new<N: fmt::Display, V: fmt::Display>(
+        url: url::Url,
+        parameters: &[(N, V)],
+        tx: oneshot::Sender<Result<()>>,
+    ) -> Self {
+        let body = parameters
+            .iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect();
+        Self {
+            url,
+            body,
+            tx,
+            tracker: Tracker::default(),
+        }
     }
 }
 
 pub enum Request {
+    Options(Options),
     Describe(Describe),
+    Setup(Setup),
+    Play(Play),
+    Teardown(Teardown),
+    GetParameter(GetParameter),
+    SetParameter(SetParameter),
 }
 
 impl Request {
-    pub fn handle_response(self, status: Status, headers: &[Header], body: &str) {
+    pub fn handle_response(self, status: Status, headers: &[Header], body: &[u8]) {
         match self {
+            Request::Options(options) => options.handle_response(status, headers, body),
             Request::Describe(describe) => describe.handle_response(status, headers, body),
+            Request::Setup(setup) => setup.handle_response(status, headers, body),
+            Request::Play(play) => play.handle_response(status, headers, body),
+            Request::Teardown(teardown) => teardown.handle_response(status, headers, body),
+            Request::GetParameter(get) => get.handle_response(status, headers, body),
+            Request::SetParameter(set) => set.handle_response(status, headers, body),
         }
     }
 
     pub fn cancel(self, e: Error) {
         match self {
+            Request::Options(options) => options.cancel(e),
             Request::Describe(describe) => describe.cancel(e),
+            Request::Setup(setup) => setup.cancel(e),
+            Request::Play(play) => play.cancel(e),
+            Request::Teardown(teardown) => teardown.cancel(e),
+            Request::GetParameter(get) => get.cancel(e),
+            Request::SetParameter(set) => set.cancel(e),
         }
     }
 
     pub fn url(&self) -> &url::Url {
         match self {
+            Request::Options(options) => options.url(),
             Request::Describe(describe) => describe.url(),
+            Request::Setup(setup) => setup.url(),
+            Request::Play(play) => play.url(),
+            Request::Teardown(teardown) => teardown.url(),
+            Request::GetParameter(get) => get.url(),
+            Request::SetParameter(set) => set.url(),
         }
     }
 
     pub fn method(&self) -> Method {
         match self {
+            Request::Options(options) => options.method(),
             Request::Describe(describe) => describe.method(),
+            Request::Setup(setup) => setup.method(),
+            Request::Play(play) => play.method(),
+            Request::Teardown(teardown) => teardown.method(),
+            Request::GetParameter(get) => get.method(),
+            Request::SetParameter(set) => set.method(),
+        }
+    }
+
+    /// True if the caller dropped the receiver and nobody waits for the
+    /// result, or cancelled it through its [`CommandHandle`](super::CommandHandle)
+    pub fn is_closed(&self) -> bool {
+        let closed = match self {
+            Request::Options(options) => options.is_closed(),
+            Request::Describe(describe) => describe.is_closed(),
+            Request::Setup(setup) => setup.is_closed(),
+            Request::Play(play) => play.is_closed(),
+            Request::Teardown(teardown) => teardown.is_closed(),
+            Request::GetParameter(get) => get.is_closed(),
+            Request::SetParameter(set) => set.is_closed(),
+        };
+        closed || self.tracker().is_cancelled()
+    }
+
+    pub(crate) fn tracker(&self) -> &Tracker {
+        match self {
+            Request::Options(options) => &options.tracker,
+            Request::Describe(describe) => &describe.tracker,
+            Request::Setup(setup) => &setup.tracker,
+            Request::Play(play) => &play.tracker,
+            Request::Teardown(teardown) => &teardown.tracker,
+            Request::GetParameter(get) => &get.tracker,
+            Request::SetParameter(set) => &set.tracker,
+        }
+    }
+
+    pub(crate) fn tracker_mut(&mut self) -> &mut Tracker {
+        match self {
+            Request::Options(options) => &mut options.tracker,
+            Request::Describe(describe) => &mut describe.tracker,
+            Request::Setup(setup) => &mut setup.tracker,
+            Request::Play(play) => &mut play.tracker,
+            Request::Teardown(teardown) => &mut teardown.tracker,
+            Request::GetParameter(get) => &mut get.tracker,
+            Request::SetParameter(set) => &mut set.tracker,
+        }
+    }
+
+    pub fn transport(&self) -> Option<&Transport> {
+        match self {
+            Request::Setup(setup) => Some(setup.transport()),
+            _ => None,
+        }
+    }
+
+    pub fn range(&self) -> Option<&Range> {
+        match self {
+            Request::Play(play) => play.range.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn scale(&self) -> Option<f64> {
+        match self {
+            Request::Play(play) => play.scale,
+            _ => None,
+        }
+    }
+
+    /// Body of the request, always `text/parameters`
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Request::GetParameter(get) => get.body(),
+            Request::SetParameter(set) => Some(set.body()),
+            _ => None,
         }
     }
 }
@@ -96,4 +670,168 @@ pub enum Ctrl {
 pub enum Command {
     Request(Request),
     Ctrl(Ctrl),
+    /// Writes an interleaved frame on `channel`, e.g. an RTP packet of an
+    /// ONVIF backchannel built with [`rtp::PacketBuilder`](crate::rtp::PacketBuilder)
+    Interleaved {
+        channel: u8,
+        payload: Bytes,
+    },
+    /// Requests the retransmission of the `lost` packets of `ssrc` received
+    /// on the interleaved RTP `channel`, e.g. the sequence numbers of
+    /// [`JitterBuffer::nacks`](crate::rtp::JitterBuffer::nacks). The Generic
+    /// NACK is sent on the RTCP channel after an empty receiver report.
+    Nack {
+        channel: u8,
+        ssrc: u32,
+        lost: Vec<u16>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_response() {
+        let (tx, mut rx) = oneshot::channel();
+        let options = Options::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let headers = [Header::new("Public", "OPTIONS, DESCRIBE, SETUP, FOO, PLAY")];
+        options.handle_response(Status::OK, &headers, b"");
+        let methods = rx.try_recv().unwrap().unwrap();
+        assert_eq!(methods, vec![Method::Options, Method::Describe, Method::Setup, Method::Play]);
+    }
+
+    #[test]
+    fn test_setup_response() {
+        let (tx, mut rx) = oneshot::channel();
+        let setup = Setup::new(
+            url::Url::parse("rtsp://test.com/track1").unwrap(),
+            Transport::tcp((0, 1)),
+            tx,
+        );
+        let headers = [
+            Header::new("Session", "abc;timeout=30"),
+            Header::new("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1"),
+        ];
+        setup.handle_response(Status::OK, &headers, b"");
+        let response = rx.try_recv().unwrap().unwrap();
+        assert_eq!(response.session.id, "abc");
+        assert_eq!(response.transport.interleaved, Some((0, 1)));
+    }
+
+    #[test]
+    fn test_setup_response_missing_session() {
+        let (tx, mut rx) = oneshot::channel();
+        let setup = Setup::new(url::Url::parse("rtsp://test.com").unwrap(), Transport::tcp((0, 1)), tx);
+        setup.handle_response(Status::OK, &[], b"");
+        assert!(matches!(rx.try_recv().unwrap(), Err(Error::MissingHeader("Session"))));
+    }
+
+    #[test]
+    fn test_describe_response_sdp() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let headers = [Header::new("Content-Type", "application/sdp")];
+        describe.handle_response(Status::OK, &headers, b"v=0\r\nm=video 0 RTP/AVP 96\r\n");
+        let description = rx.try_recv().unwrap().unwrap();
+        assert_eq!(description.sdp().unwrap().media.len(), 1);
+    }
+
+    #[test]
+    fn test_describe_response_content_base() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com/live").unwrap(), tx);
+        let headers = [Header::new("Content-Base", "rtsp://test.com/live/")];
+        let sdp = "v=0\r\na=control:*\r\nm=video 0 RTP/AVP 96\r\na=control:trackID=0\r\n";
+        describe.handle_response(Status::OK, &headers, sdp.as_bytes());
+        let sdp = rx.try_recv().unwrap().unwrap().into_sdp().unwrap();
+        assert_eq!(sdp.attribute("control"), Some("*"));
+        assert_eq!(sdp.media[0].control(), Some("rtsp://test.com/live/trackID=0"));
+    }
+
+    #[test]
+    fn test_describe_response_raw() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let headers = [Header::new("Content-Type", "application/octet-stream")];
+        describe.handle_response(Status::OK, &headers, b"m=\xff\xfe");
+        let description = rx.try_recv().unwrap().unwrap();
+        assert!(matches!(&description, Description::Raw { body, .. } if body[..] == b"m=\xff\xfe"[..]));
+        assert!(matches!(description.into_sdp(), Err(Error::NotSdp(_))));
+    }
+
+    #[test]
+    fn test_describe_response_undecodable() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        describe.handle_response(Status::OK, &[], b"v=0\r\ns=Cam\xe9ra\r\n");
+        assert!(matches!(rx.try_recv().unwrap(), Err(Error::Encoding(_))));
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let headers = [Header::new("Content-Type", "application/sdp; charset=ISO-8859-1")];
+        describe.handle_response(Status::OK, &headers, b"v=0\r\n");
+        assert!(matches!(rx.try_recv().unwrap(), Err(Error::UnsupportedCharset(c)) if c == "ISO-8859-1"));
+    }
+
+    #[test]
+    fn test_describe_response_webrtc() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let sdp = "v=0\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\na=ice-ufrag:abcd\r\na=ice-pwd:secret\r\n";
+        describe.handle_response(Status::OK, &[], sdp.as_bytes());
+        let error = rx.try_recv().unwrap().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported transport, the description requires ice-ufrag, ice-pwd, UDP/TLS/RTP/SAVPF"
+        );
+    }
+
+    #[test]
+    fn test_describe_response_unsupported_encoding() {
+        let (tx, mut rx) = oneshot::channel();
+        let describe = Describe::new(url::Url::parse("rtsp://test.com").unwrap(), tx);
+        let headers = [
+            Header::new("Content-Type", "application/sdp"),
+            Header::new("Content-Encoding", "gzip"),
+        ];
+        describe.handle_response(Status::OK, &headers, b"");
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(Error::UnsupportedContentEncoding(ContentEncoding::Other(_)))
+        ));
+    }
+
+    #[test]
+    fn test_get_parameter_response() {
+        let (tx, mut rx) = oneshot::channel();
+        let get = GetParameter::new(url::Url::parse("rtsp://test.com").unwrap(), &["volume", "bitrate"], tx);
+        assert_eq!(get.body(), Some("volume\r\nbitrate\r\n"));
+        get.handle_response(Status::OK, &[], b"volume: 80\r\nbitrate:2048\r\n");
+        let parameters = rx.try_recv().unwrap().unwrap();
+        assert_eq!(
+            parameters,
+            vec![
+                ("volume".to_string(), "80".to_string()),
+                ("bitrate".to_string(), "2048".to_string())
+            ]
+        );
+        let (tx, _) = oneshot::channel();
+        assert!(
+            GetParameter::new::<&str>(url::Url::parse("rtsp://test.com").unwrap(), &[], tx)
+                .body()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_set_parameter() {
+        let (tx, mut rx) = oneshot::channel();
+        let set = SetParameter::new(url::Url::parse("rtsp://test.com").unwrap(), &[("volume", 50)], tx);
+        assert_eq!(set.body(), "volume: 50\r\n");
+        set.handle_response(Status::ParameterNotUnderstood, &[], b"");
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            Err(Error::UnexpectedStatus(Status::ParameterNotUnderstood))
+        ));
+    }
 }
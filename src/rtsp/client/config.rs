@@ -0,0 +1,47 @@
+use super::{ProfileLimits, Quirks, DEFAULT_MAX_OUTSTANDING, DEFAULT_TIMEOUT};
+use std::time::Duration;
+
+/// User-Agent header of requests unless configured otherwise
+pub const DEFAULT_USER_AGENT: &str = "rs-streamer";
+
+/// Settings of a [`Channel`](super::Channel), applied at once with
+/// [`Channel::config`](super::Channel::config), e.g. from a configuration
+/// file. Every setting also has its own builder method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelConfig {
+    /// Buffer sizes, derived from the SDP of the first DESCRIBE if unset
+    pub limits: Option<ProfileLimits>,
+    /// Tolerated deviations of the server, detected from its Server header
+    /// if unset
+    pub quirks: Option<Quirks>,
+    /// Bytes read from the connection at once, the receive buffer must
+    /// have room for them
+    pub read_size: usize,
+    /// Longest status line and headers of a response
+    pub max_header_size: usize,
+    /// Longest body of a response or server request
+    pub max_body_size: usize,
+    pub user_agent: String,
+    pub timeout: Duration,
+    pub retries: u32,
+    pub max_outstanding: usize,
+    /// Validates every response against RFC 7826
+    pub strict: bool,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            limits: None,
+            quirks: None,
+            read_size: 4096,
+            max_header_size: 1024,
+            max_body_size: 32 * 1024,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            retries: 0,
+            max_outstanding: DEFAULT_MAX_OUTSTANDING,
+            strict: false,
+        }
+    }
+}
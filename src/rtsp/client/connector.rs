@@ -0,0 +1,261 @@
+use super::connection::{default_port, is_datagram_scheme, is_tls_scheme, Error, Result};
+use super::{http_tunnel_url, Connection, DatagramStream, TlsConfig};
+use crate::http::{Tunnel, DEFAULT_HTTP_PORT};
+use rustls::pki_types::ServerName;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpSocket, TcpStream};
+use url::Url;
+
+pub type ConnectFuture<'a> = Pin<Box<dyn Future<Output = Result<Connection>> + Send + 'a>>;
+
+/// Opens the connection a [`Channel`](super::Channel) runs on, e.g. to add
+/// a transport the crate doesn't provide, like a SOCKS proxy, to the
+/// [`Supervisor`](super::Supervisor)
+pub trait Connector: Send + Sync {
+    /// Connects to the server of `url`
+    fn connect<'a>(&'a self, url: &'a Url) -> ConnectFuture<'a>;
+}
+
+/// How the TCP connections of the built-in connectors are opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectOptions {
+    timeout: Option<Duration>,
+    local_addr: Option<IpAddr>,
+}
+
+impl ConnectOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gives up connecting after `timeout`, including the TLS handshake and
+    /// the setup of an HTTP tunnel
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Connects from `addr`, e.g. to pick the interface of a camera network.
+    /// Only server addresses of the same family are tried.
+    pub fn local_addr(mut self, addr: IpAddr) -> Self {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    async fn with_timeout<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, future)
+                .await
+                .map_err(|_| Error::Timeout)?,
+            None => future.await,
+        }
+    }
+
+    /// Connects to the first address of `host` that accepts the connection
+    async fn tcp(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let Some(local) = self.local_addr else {
+            return Ok(TcpStream::connect((host, port)).await?);
+        };
+        let mut last_error = None;
+        for addr in tokio::net::lookup_host((host, port)).await? {
+            if addr.is_ipv4() != local.is_ipv4() {
+                continue;
+            }
+            let socket = match addr {
+                SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => TcpSocket::new_v6()?,
+            };
+            socket.bind(SocketAddr::new(local, 0))?;
+            match socket.connect(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let e = last_error.unwrap_or_else(|| {
+            let msg = format!("{} has no address of the family of {}", host, local);
+            io::Error::new(io::ErrorKind::AddrNotAvailable, msg)
+        });
+        Err(e.into())
+    }
+}
+
+fn host_and_port(url: &Url) -> Result<(&str, u16)> {
+    let port = url.port().unwrap_or(default_port(url)?);
+    Ok((url.host_str().ok_or(Error::MissingHost)?, port))
+}
+
+/// Plain TCP, whatever the scheme of the url
+#[derive(Debug, Clone, Default)]
+pub struct TcpConnector {
+    options: ConnectOptions,
+}
+
+impl TcpConnector {
+    pub fn new(options: ConnectOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Connector for TcpConnector {
+    fn connect<'a>(&'a self, url: &'a Url) -> ConnectFuture<'a> {
+        Box::pin(self.options.with_timeout(async move {
+            let (host, port) = host_and_port(url)?;
+            Ok(Connection::Tcp(self.options.tcp(host, port).await?))
+        }))
+    }
+}
+
+/// TLS over TCP, as used by `rtsps://` urls
+#[derive(Debug, Clone)]
+pub struct TlsConnector {
+    tls: TlsConfig,
+    options: ConnectOptions,
+}
+
+impl TlsConnector {
+    pub fn new(tls: TlsConfig, options: ConnectOptions) -> Self {
+        Self { tls, options }
+    }
+}
+
+impl Connector for TlsConnector {
+    fn connect<'a>(&'a self, url: &'a Url) -> ConnectFuture<'a> {
+        Box::pin(self.options.with_timeout(async move {
+            let (host, port) = host_and_port(url)?;
+            let tcp = self.options.tcp(host, port).await?;
+            let name = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']').to_string())?;
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(self.tls.client_config()));
+            let stream = connector.connect(name, tcp).await?;
+            Ok(Connection::Tls(Box::new(stream)))
+        }))
+    }
+}
+
+/// RTSP tunneled through HTTP on `port` of the server, `http://` urls
+/// use their own port
+#[derive(Debug, Clone)]
+pub struct HttpTunnelConnector {
+    port: u16,
+    options: ConnectOptions,
+}
+
+impl HttpTunnelConnector {
+    pub fn new(port: u16, options: ConnectOptions) -> Self {
+        Self { port, options }
+    }
+}
+
+impl Connector for HttpTunnelConnector {
+    fn connect<'a>(&'a self, url: &'a Url) -> ConnectFuture<'a> {
+        Box::pin(self.options.with_timeout(async move {
+            let url = match url.scheme().eq_ignore_ascii_case("http") {
+                true => url.clone(),
+                false => http_tunnel_url(url, self.port),
+            };
+            let (host, port) = host_and_port(&url)?;
+            let get = self.options.tcp(host, port).await?;
+            let post = self.options.tcp(host, port).await?;
+            Ok(Connection::Http(Box::new(Tunnel::open(get, post, &url).await?)))
+        }))
+    }
+}
+
+/// Picks the transport by the scheme of the url: TLS for `rtsps`, an HTTP
+/// tunnel for `http` or if [`DefaultConnector::http_tunnel`] is set, UDP
+/// for `rtspu` and plain TCP for `rtsp`. The local address doesn't apply
+/// to UDP.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultConnector {
+    tls: TlsConfig,
+    options: ConnectOptions,
+    http_tunnel: Option<u16>,
+}
+
+impl DefaultConnector {
+    pub fn new(tls: TlsConfig) -> Self {
+        Self {
+            tls,
+            ..Default::default()
+        }
+    }
+
+    pub fn options(mut self, options: ConnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Tunnels `rtsp://` and `rtsps://` urls through HTTP on `port`
+    pub fn http_tunnel(mut self, port: u16) -> Self {
+        self.http_tunnel = Some(port);
+        self
+    }
+}
+
+impl Connector for DefaultConnector {
+    fn connect<'a>(&'a self, url: &'a Url) -> ConnectFuture<'a> {
+        Box::pin(async move {
+            let http = url.scheme().eq_ignore_ascii_case("http");
+            if http || (self.http_tunnel.is_some() && !is_datagram_scheme(url)) {
+                let port = self.http_tunnel.unwrap_or(DEFAULT_HTTP_PORT);
+                return HttpTunnelConnector::new(port, self.options).connect(url).await;
+            }
+            if is_datagram_scheme(url) {
+                let (host, port) = host_and_port(url)?;
+                let stream = self
+                    .options
+                    .with_timeout(async { Ok(DatagramStream::connect((host, port)).await?) });
+                return Ok(Connection::Udp(stream.await?));
+            }
+            if is_tls_scheme(url) {
+                return TlsConnector::new(self.tls.clone(), self.options).connect(url).await;
+            }
+            TcpConnector::new(self.options).connect(url).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_connect_local_addr() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", listener.local_addr().unwrap())).unwrap();
+        let options = ConnectOptions::new().local_addr("127.0.0.1".parse().unwrap());
+        let connection = DefaultConnector::new(TlsConfig::new())
+            .options(options)
+            .connect(&url)
+            .await
+            .unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+        match connection {
+            Connection::Tcp(stream) => assert_eq!(stream.local_addr().unwrap(), peer),
+            _ => panic!("expected a TCP connection"),
+        }
+
+        let options = ConnectOptions::new().local_addr("::1".parse().unwrap());
+        let result = TcpConnector::new(options).connect(&url).await;
+        assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::AddrNotAvailable));
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // Accepts the tunnel connections but never answers the GET request
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/live", listener.local_addr().unwrap())).unwrap();
+        let options = ConnectOptions::new().timeout(Duration::from_millis(50));
+        let result = DefaultConnector::new(TlsConfig::new())
+            .options(options)
+            .connect(&url)
+            .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+        drop(listener);
+    }
+}
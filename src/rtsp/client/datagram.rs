@@ -0,0 +1,112 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{lookup_host, ToSocketAddrs, UdpSocket};
+
+/// Largest UDP payload
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// RTSP control connection over UDP (`rtspu://`, RFC 2326 10.12) with a
+/// stream interface, so it can be driven by a [`Channel`](super::Channel)
+/// in [datagram mode](super::Channel::datagram).
+///
+/// Every write is sent as one datagram. Received datagrams are read in
+/// order, a datagram larger than the read buffer is returned by the
+/// following reads.
+#[derive(Debug)]
+pub struct DatagramStream {
+    socket: UdpSocket,
+    recv_buf: Vec<u8>,
+    // Unread part of the last datagram
+    pos: usize,
+    len: usize,
+}
+
+impl DatagramStream {
+    /// `socket` must be connected to the server
+    pub fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket,
+            recv_buf: vec![0; MAX_DATAGRAM_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Binds an ephemeral port and connects it to the first address of `addr`
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let addr = lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No address for host"))?;
+        let local = match addr {
+            SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(addr).await?;
+        Ok(Self::new(socket))
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl AsyncRead for DatagramStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        // An empty read means EOF to the caller, so empty datagrams are skipped
+        while this.pos == this.len {
+            let mut recv = ReadBuf::new(&mut this.recv_buf);
+            ready!(this.socket.poll_recv(cx, &mut recv))?;
+            this.len = recv.filled().len();
+            this.pos = 0;
+        }
+        let n = (this.len - this.pos).min(buf.remaining());
+        buf.put_slice(&this.recv_buf[this.pos..this.pos + n]);
+        this.pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for DatagramStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.socket.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_datagram_stream() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut stream = DatagramStream::connect(server.local_addr().unwrap()).await.unwrap();
+        stream.write_all(b"OPTIONS").await.unwrap();
+        let mut buf = [0u8; 16];
+        let (n, client) = server.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"OPTIONS");
+
+        server.send_to(b"", client).await.unwrap();
+        server.send_to(b"RTSP/1.0", client).await.unwrap();
+        // Read in parts, the empty datagram is skipped
+        let mut part = [0u8; 5];
+        assert_eq!(stream.read(&mut part).await.unwrap(), 5);
+        assert_eq!(&part, b"RTSP/");
+        assert_eq!(stream.read(&mut part).await.unwrap(), 3);
+        assert_eq!(&part[..3], b"1.0");
+    }
+}
@@ -0,0 +1,102 @@
+use super::Violation;
+use crate::rtsp::{IncomingRequest, Method, Status};
+use crate::sdp::RtpMap;
+use std::io;
+use std::time::Duration;
+
+/// Why a channel stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Shut down on request
+    Shutdown,
+    /// The server closed the connection
+    Closed,
+    Io(io::ErrorKind),
+    /// A protocol error, e.g. an unparsable response
+    Error(String),
+}
+
+/// Why authentication gave up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// The credentials were rejected, usually a wrong user or password
+    Rejected,
+    /// None of the offered schemes is supported
+    UnsupportedScheme,
+    /// The server asks for credentials but none are configured
+    MissingCredentials,
+    /// The challenge is missing or can't be parsed
+    InvalidChallenge,
+    /// The server kept challenging until the auth retry policy gave up
+    RetriesExhausted,
+}
+
+/// Lifecycle changes of a [`Channel`](super::Channel), see [`Channel::events`](super::Channel::events)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The channel started processing the connection
+    Connected,
+    /// The channel stopped, no events follow
+    Disconnected { reason: DisconnectReason },
+    /// The server no longer knows the session, usually because no
+    /// keep-alive arrived within its timeout
+    SessionTimeout { session: Option<String> },
+    /// A source left the session with an RTCP BYE
+    RtcpBye { ssrcs: Vec<u32>, reason: Option<String> },
+    /// A request was answered with 401, or 407 if `proxy` is set, offering
+    /// the authentication `schemes`
+    AuthChallenge {
+        method: Method,
+        proxy: bool,
+        schemes: Vec<String>,
+        realm: Option<String>,
+    },
+    /// The scheme answering the challenge, `algorithm` is the hash of Digest
+    AuthSchemeSelected {
+        scheme: String,
+        algorithm: Option<String>,
+        proxy: bool,
+    },
+    /// The challenged request is sent again with credentials, `attempt`
+    /// counts the retries since the last response that wasn't a challenge
+    UnauthorizedRetry { method: Method, proxy: bool, attempt: u32 },
+    /// The request failed with [`CommandError::Unauthorized`](super::CommandError::Unauthorized)
+    /// or an authorization error
+    AuthFailed {
+        method: Method,
+        proxy: bool,
+        realm: Option<String>,
+        failure: AuthFailure,
+    },
+    /// A request got no response within the timeout
+    RequestTimeout { method: Method, retrying: bool },
+    /// A request the server sent on the connection, it was already answered
+    ServerRequest(IncomingRequest),
+    /// The first RTP packet on `channel` with a payload type the SDP doesn't
+    /// list for the track, see [`PayloadTypeMap`](crate::sdp::PayloadTypeMap)
+    PayloadTypeMismatch {
+        channel: u8,
+        described: Vec<u8>,
+        received: u8,
+    },
+    /// The first RTP packet on `channel` since PLAY was sent, `latency` is
+    /// counted from sending the request
+    FirstPacket { channel: u8, latency: Duration },
+    /// The first packet on `channel` since PLAY was sent that starts a
+    /// keyframe, see [`rtp::starts_keyframe`](crate::rtp::starts_keyframe)
+    FirstKeyframe { channel: u8, latency: Duration },
+    /// The payload format of the track received on `channel` changed, see
+    /// [`FrameStream::events`](crate::rtp::FrameStream::events)
+    CodecChanged {
+        channel: u8,
+        previous: RtpMap,
+        current: RtpMap,
+    },
+    /// A response deviates from RFC 7826, only reported in strict mode,
+    /// see [`Channel::strict`](super::Channel::strict)
+    SpecViolation {
+        method: Method,
+        status: Status,
+        violation: Violation,
+    },
+}
@@ -0,0 +1,115 @@
+use super::{Command, CommandError, CommandResult, Request};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, oneshot};
+
+const QUEUED: u8 = 0;
+const SENT: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// Where a request enqueued with [`CommandHandle::send`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandState {
+    /// Waiting in the channel's queue, it can still be cancelled
+    Queued,
+    /// Written to the connection, awaiting the response
+    Sent,
+    /// The result is available
+    Completed,
+    /// Cancelled before it was sent
+    Cancelled,
+}
+
+/// Shared state of a request and its [`CommandHandle`], untracked
+/// requests never become cancelled
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Tracker(Option<Arc<AtomicU8>>);
+
+impl Tracker {
+    fn new() -> Self {
+        Self(Some(Arc::new(AtomicU8::new(QUEUED))))
+    }
+
+    fn state(&self) -> u8 {
+        self.0.as_ref().map_or(QUEUED, |s| s.load(Ordering::Acquire))
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.state() == CANCELLED
+    }
+
+    /// Called before the request is written, false if it was cancelled
+    pub(crate) fn mark_sent(&self) -> bool {
+        match &self.0 {
+            Some(state) => !matches!(
+                state.compare_exchange(QUEUED, SENT, Ordering::AcqRel, Ordering::Acquire),
+                Err(CANCELLED)
+            ),
+            None => true,
+        }
+    }
+
+    fn cancel(&self) -> bool {
+        self.0.as_ref().is_some_and(|s| {
+            s.compare_exchange(QUEUED, CANCELLED, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        })
+    }
+}
+
+/// An enqueued request, to query its state, cancel it while it is queued
+/// or await its result.
+#[derive(Debug)]
+pub struct CommandHandle<T> {
+    tracker: Tracker,
+    rx: oneshot::Receiver<CommandResult<T>>,
+}
+
+impl<T> CommandHandle<T> {
+    /// Enqueues the request built by `request` on the channel of `cmd_tx`,
+    /// e.g. `CommandHandle::send(&cmd_tx, |tx| Request::Play(Play::new(url, tx)))`
+    pub async fn send(
+        cmd_tx: &mpsc::Sender<Command>,
+        request: impl FnOnce(oneshot::Sender<CommandResult<T>>) -> Request,
+    ) -> CommandResult<Self> {
+        let (tx, rx) = oneshot::channel();
+        let mut request = request(tx);
+        let tracker = Tracker::new();
+        *request.tracker_mut() = tracker.clone();
+        cmd_tx
+            .send(Command::Request(request))
+            .await
+            .map_err(|_| CommandError::Cancelled)?;
+        Ok(Self { tracker, rx })
+    }
+
+    pub fn state(&self) -> CommandState {
+        if !self.rx.is_empty() {
+            return CommandState::Completed;
+        }
+        match self.tracker.state() {
+            QUEUED => CommandState::Queued,
+            SENT => CommandState::Sent,
+            _ => CommandState::Cancelled,
+        }
+    }
+
+    /// Removes the request from the queue, false if it was already sent.
+    /// Awaiting a cancelled request fails with [`CommandError::Cancelled`].
+    pub fn cancel(&self) -> bool {
+        self.tracker.cancel()
+    }
+}
+
+impl<T> Future for CommandHandle<T> {
+    type Output = CommandResult<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(CommandError::Cancelled)))
+    }
+}
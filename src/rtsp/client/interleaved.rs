@@ -0,0 +1,101 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Frames an [`InterleavedWriter`] queues before [`InterleavedWriter::send`]
+/// waits for the channel to write them
+pub const DEFAULT_WRITER_CAPACITY: usize = 64;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("Channel closed")]
+    Closed,
+    #[error("Too many interleaved frames queued")]
+    Full,
+    #[error("Payload of {0} bytes too large for an interleaved frame")]
+    TooLarge(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Sends `$`-framed binary data to the server on an interleaved channel,
+/// e.g. RTCP of a custom report scheduler or keep-alive data, created by
+/// [`Channel::interleaved_writer`](super::Channel::interleaved_writer).
+///
+/// Frames are written whole between the requests of the channel, queued
+/// requests go first. They wait for room in the send buffer instead of
+/// being dropped, once [`DEFAULT_WRITER_CAPACITY`] frames wait
+/// [`send`](Self::send) waits and [`try_send`](Self::try_send) fails.
+#[derive(Debug, Clone)]
+pub struct InterleavedWriter {
+    tx: mpsc::Sender<(u8, Bytes)>,
+    // RTCP channel of every RTP channel negotiated by SETUP
+    rtcp_channels: Arc<Mutex<HashMap<u8, u8>>>,
+}
+
+impl InterleavedWriter {
+    pub(super) fn new(tx: mpsc::Sender<(u8, Bytes)>, rtcp_channels: Arc<Mutex<HashMap<u8, u8>>>) -> Self {
+        Self { tx, rtcp_channels }
+    }
+
+    fn check(payload: &Bytes) -> Result<()> {
+        match payload.len() > u16::MAX as usize {
+            true => Err(Error::TooLarge(payload.len())),
+            false => Ok(()),
+        }
+    }
+
+    /// Queues `payload` for `channel`, waiting while the queue is full
+    pub async fn send(&self, channel: u8, payload: impl Into<Bytes>) -> Result<()> {
+        let payload = payload.into();
+        Self::check(&payload)?;
+        self.tx.send((channel, payload)).await.map_err(|_| Error::Closed)
+    }
+
+    /// Queues `payload` for `channel` unless the queue is full
+    pub fn try_send(&self, channel: u8, payload: impl Into<Bytes>) -> Result<()> {
+        let payload = payload.into();
+        Self::check(&payload)?;
+        self.tx.try_send((channel, payload)).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => Error::Full,
+            mpsc::error::TrySendError::Closed(_) => Error::Closed,
+        })
+    }
+
+    /// Queues a RTCP packet about the track on the interleaved `rtp_channel`,
+    /// it is sent on the RTCP channel SETUP negotiated for the track, the
+    /// following one if there is none
+    pub async fn send_rtcp(&self, rtp_channel: u8, packet: impl Into<Bytes>) -> Result<()> {
+        let rtcp_channel = self.rtcp_channels.lock().unwrap().get(&rtp_channel).copied();
+        let rtcp_channel = rtcp_channel.unwrap_or(rtp_channel.wrapping_add(1));
+        self.send(rtcp_channel, packet).await
+    }
+
+    /// Whether the channel stopped, frames can't be sent anymore
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interleaved_writer() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let rtcp_channels = Arc::new(Mutex::new(HashMap::from([(4, 7)])));
+        let writer = InterleavedWriter::new(tx, rtcp_channels);
+        writer.send_rtcp(2, vec![1, 2]).await.unwrap();
+        assert_eq!(writer.try_send(0, vec![3]), Err(Error::Full));
+        assert_eq!(rx.recv().await, Some((3, Bytes::from_static(&[1, 2]))));
+        writer.send_rtcp(4, vec![4]).await.unwrap();
+        assert_eq!(rx.recv().await, Some((7, Bytes::from_static(&[4]))));
+        assert_eq!(writer.try_send(0, vec![0; 70_000]), Err(Error::TooLarge(70_000)));
+        drop(rx);
+        assert!(writer.is_closed());
+        assert_eq!(writer.send(0, vec![3]).await, Err(Error::Closed));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::metrics::Histogram;
+use crate::rtsp::Method;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Smallest bucket bound of the latency histograms in microseconds, the
+/// 16 doubling buckets reach about 33 seconds
+const FIRST_BOUND_US: u64 = 1000;
+const BUCKETS: usize = 16;
+
+fn histogram() -> Histogram {
+    Histogram::new(FIRST_BOUND_US, BUCKETS)
+}
+
+/// Request to response latencies by method, in microseconds. Requests
+/// that time out are not recorded.
+///
+/// Also the startup latencies of the tracks by RTP channel: the time from
+/// sending PLAY to the first RTP packet and to the first packet of a
+/// keyframe, i.e. how long a viewer waits for a picture.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Latencies {
+    methods: HashMap<Method, Histogram>,
+    first_packets: HashMap<u8, Histogram>,
+    first_keyframes: HashMap<u8, Histogram>,
+}
+
+impl Latencies {
+    pub fn method(&self, method: Method) -> Option<&Histogram> {
+        self.methods.get(&method)
+    }
+
+    pub fn describe(&self) -> Option<&Histogram> {
+        self.method(Method::Describe)
+    }
+
+    pub fn setup(&self) -> Option<&Histogram> {
+        self.method(Method::Setup)
+    }
+
+    pub fn play(&self) -> Option<&Histogram> {
+        self.method(Method::Play)
+    }
+
+    /// Keep-alive requests, i.e. OPTIONS and GET_PARAMETER
+    pub fn keepalive(&self) -> Option<Histogram> {
+        [Method::Options, Method::GetParameter]
+            .iter()
+            .filter_map(|m| self.method(*m))
+            .fold(None, |merged, h| {
+                let mut merged = merged.unwrap_or_else(histogram);
+                merged.merge(h);
+                Some(merged)
+            })
+    }
+
+    /// PLAY to the first RTP packet on `channel`
+    pub fn first_packet(&self, channel: u8) -> Option<&Histogram> {
+        self.first_packets.get(&channel)
+    }
+
+    /// PLAY to the first packet of a keyframe on `channel`, see [`rtp::starts_keyframe`](crate::rtp::starts_keyframe)
+    pub fn first_keyframe(&self, channel: u8) -> Option<&Histogram> {
+        self.first_keyframes.get(&channel)
+    }
+}
+
+fn micros(latency: Duration) -> u64 {
+    latency.as_micros().min(u64::MAX as u128) as u64
+}
+
+/// Shared handle to the latency histograms of a channel, see [`UsageMeter`](super::UsageMeter)
+#[derive(Debug, Default, Clone)]
+pub struct LatencyMeter {
+    latencies: Arc<Mutex<Latencies>>,
+}
+
+impl LatencyMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> Latencies {
+        self.latencies.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.latencies.lock().unwrap() = Latencies::default();
+    }
+
+    pub(crate) fn record(&self, method: Method, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies.methods.entry(method).or_insert_with(histogram);
+        histogram.record(micros(latency));
+    }
+
+    pub(crate) fn record_first_packet(&self, channel: u8, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies.first_packets.entry(channel).or_insert_with(histogram);
+        histogram.record(micros(latency));
+    }
+
+    pub(crate) fn record_first_keyframe(&self, channel: u8, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies.first_keyframes.entry(channel).or_insert_with(histogram);
+        histogram.record(micros(latency));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_meter() {
+        let meter = LatencyMeter::new();
+        meter.record(Method::Describe, Duration::from_millis(3));
+        meter.record(Method::Options, Duration::from_millis(1));
+        meter.record(Method::GetParameter, Duration::from_millis(40));
+        let latencies = meter.snapshot();
+        assert_eq!(latencies.describe().unwrap().quantile(1.0), Some(3000));
+        assert!(latencies.setup().is_none());
+        let keepalive = latencies.keepalive().unwrap();
+        assert_eq!(keepalive.count(), 2);
+        assert_eq!(keepalive.max(), Some(40_000));
+        meter.record_first_packet(0, Duration::from_millis(120));
+        meter.record_first_keyframe(0, Duration::from_millis(900));
+        let latencies = meter.snapshot();
+        assert_eq!(latencies.first_packet(0).unwrap().max(), Some(120_000));
+        assert_eq!(latencies.first_keyframe(0).unwrap().max(), Some(900_000));
+        assert!(latencies.first_packet(2).is_none());
+        meter.reset();
+        assert!(meter.snapshot().keepalive().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_latencies_serde() {
+        let meter = LatencyMeter::new();
+        meter.record(Method::Play, Duration::from_millis(3));
+        meter.record_first_keyframe(2, Duration::from_millis(700));
+        let json = serde_json::to_value(meter.snapshot()).unwrap();
+        assert_eq!(json["methods"]["PLAY"]["count"], 1);
+        let latencies: Latencies = serde_json::from_value(json).unwrap();
+        assert_eq!(latencies, meter.snapshot());
+    }
+}
@@ -1,10 +1,44 @@
 mod channel;
 mod command;
 mod authorizer;
+mod connection;
+mod usage;
+mod mtu;
+mod udp;
+mod standby;
+mod profile;
+mod latency;
+mod supervisor;
+mod parameters;
+mod event;
+mod datagram;
+mod handle;
+mod config;
+mod server_info;
+mod retry;
+mod connector;
+mod prober;
+mod quirks;
+mod adaptive;
+mod quality;
+mod interleaved;
+mod validation;
 
 pub use channel::Channel;
 pub use channel::Error as ChannelError;
+pub use channel::DEFAULT_TIMEOUT;
+pub use channel::DEFAULT_MAX_OUTSTANDING;
+pub use channel::DEFAULT_RTCP_INTERVAL;
 pub use command::Describe;
+pub use command::GetParameter;
+pub use command::SetParameter;
+pub use command::parse_parameters;
+pub use command::Description;
+pub use command::Options;
+pub use command::Play;
+pub use command::Setup;
+pub use command::SetupResponse;
+pub use command::Teardown;
 pub use command::Command;
 pub use command::Request;
 pub use command::Ctrl;
@@ -14,3 +48,86 @@ pub use authorizer::Authorizer;
 pub use authorizer::Error as AuthorizerError;
 pub use authorizer::Basic;
 pub use authorizer::Digest;
+pub use connection::connect;
+pub use connection::http_tunnel_url;
+pub use connection::is_tls_scheme;
+pub use connection::Connection;
+pub use connection::Error as ConnectionError;
+pub use connection::TlsConfig;
+pub use usage::Counter;
+pub use usage::DirectionUsage;
+pub use usage::Traffic;
+pub use usage::Usage;
+pub use usage::UsageMeter;
+pub use mtu::MtuDetector;
+pub use mtu::MtuIssue;
+pub use udp::bind_pair;
+pub use udp::UdpReceiver;
+pub use udp::UdpSender;
+pub use standby::Error as StandbyError;
+pub use standby::StandbyConnection;
+pub use standby::WarmStandby;
+pub use profile::MediaProfile;
+pub use profile::ProfileLimits;
+pub use latency::Latencies;
+pub use latency::LatencyMeter;
+pub use supervisor::Discontinuity;
+pub use supervisor::Error as SupervisorError;
+pub use supervisor::StreamItem;
+pub use supervisor::Supervisor;
+pub use supervisor::SupervisorHandle;
+pub use parameters::Capabilities;
+pub use parameters::Error as ParameterError;
+pub use parameters::ParameterControl;
+pub use parameters::ParameterValue;
+pub use parameters::Preset;
+pub use parameters::{AUDIO_ENABLED, BACKCHANNEL_VOLUME, BITRATE, FRAMERATE, GOP_LENGTH, JITTER, PACKETS_RECEIVED};
+pub use event::AuthFailure;
+pub use event::DisconnectReason;
+pub use event::Event;
+pub use connection::is_datagram_scheme;
+pub use datagram::DatagramStream;
+pub use handle::CommandHandle;
+pub use handle::CommandState;
+pub use channel::ONVIF_BACKCHANNEL;
+pub use config::ChannelConfig;
+pub use config::DEFAULT_USER_AGENT;
+pub use server_info::ServerInfo;
+pub use server_info::ServerInfoHandle;
+pub use server_info::Vendor;
+pub use retry::Backoff;
+pub use retry::RetryPolicy;
+pub use supervisor::DEFAULT_MAX_REDIRECTS;
+pub use supervisor::TEARDOWN_TIMEOUT;
+pub use connection::ConnectionStream;
+pub use connector::ConnectFuture;
+pub use connector::ConnectOptions;
+pub use connector::Connector;
+pub use connector::DefaultConnector;
+pub use connector::HttpTunnelConnector;
+pub use connector::TcpConnector;
+pub use connector::TlsConnector;
+pub use prober::Error as ProbeError;
+pub use prober::Probed;
+pub use prober::Prober;
+pub use prober::COMMON_PATHS;
+pub use prober::DEFAULT_PARALLELISM;
+pub use quirks::content_base;
+pub use quirks::control_url;
+pub use quirks::Quirks;
+pub use adaptive::switch_variant;
+pub use adaptive::AdaptiveConfig;
+pub use adaptive::Recommendation;
+pub use adaptive::StreamSelector;
+pub use adaptive::Variant;
+pub use command::PlayResponse;
+pub use quality::QualityHandle;
+pub use quality::TrackQuality;
+pub use interleaved::Error as InterleavedError;
+pub use interleaved::InterleavedWriter;
+pub use interleaved::DEFAULT_WRITER_CAPACITY;
+pub use validation::validate_response;
+pub use validation::Violation;
+pub use channel::DEFAULT_AUTH_RETRIES;
+pub use profile::MAX_INTERLEAVED_FRAME;
+pub use connection::tunneled_url;
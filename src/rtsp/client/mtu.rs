@@ -0,0 +1,136 @@
+use crate::rtp::{Packet, SequenceExtender};
+
+/// Packets in one evaluation window
+const WINDOW: u64 = 256;
+/// Share of lost packets that makes the window suspicious
+const MIN_LOSS_RATIO: f64 = 0.02;
+/// Share of the losses that must be inside fragmented frames
+const MIN_IN_FRAME_RATIO: f64 = 0.9;
+
+/// Reported when large datagrams are consistently lost on the path,
+/// typically behind VPNs or tunnels with a reduced MTU that drop
+/// fragmented IP packets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MtuIssue {
+    /// Largest datagram that made it through during the window
+    pub largest_received: usize,
+    pub loss_ratio: f64,
+    /// A Blocksize that should fit through the path, request it on SETUP
+    /// or fall back to interleaved TCP
+    pub suggested_blocksize: usize,
+}
+
+/// Detects size dependent loss of RTP datagrams.
+///
+/// Large frames are split into max-size fragments sharing a timestamp.
+/// If nearly all losses fall inside such frames while packets between
+/// frames arrive fine, the fragments are most likely too big for the path.
+#[derive(Debug, Default)]
+pub struct MtuDetector {
+    extender: SequenceExtender,
+    expected: Option<i64>,
+    last_timestamp: u32,
+    received: u64,
+    lost: u64,
+    lost_in_frame: u64,
+    largest: usize,
+    reported: bool,
+}
+
+impl MtuDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a received datagram, returns an issue at most once per stream
+    /// at the end of a suspicious window.
+    pub fn observe(&mut self, packet: &Packet, size: usize) -> Option<MtuIssue> {
+        let ext = self.extender.extend(packet.sequence_number());
+        if let Some(expected) = self.expected {
+            if ext < expected {
+                // Reordered, it was counted as lost before
+                self.lost = self.lost.saturating_sub(1);
+                return None;
+            }
+            let gap = (ext - expected) as u64;
+            self.lost += gap;
+            if gap > 0 && packet.timestamp() == self.last_timestamp {
+                self.lost_in_frame += gap;
+            }
+        }
+        self.expected = Some(ext + 1);
+        self.last_timestamp = packet.timestamp();
+        self.received += 1;
+        self.largest = self.largest.max(size);
+        if self.received < WINDOW {
+            return None;
+        }
+        let issue = self.evaluate();
+        self.received = 0;
+        self.lost = 0;
+        self.lost_in_frame = 0;
+        self.largest = 0;
+        issue
+    }
+
+    fn evaluate(&mut self) -> Option<MtuIssue> {
+        if self.reported || self.lost == 0 {
+            return None;
+        }
+        let loss_ratio = self.lost as f64 / (self.lost + self.received) as f64;
+        let in_frame_ratio = self.lost_in_frame as f64 / self.lost as f64;
+        if loss_ratio < MIN_LOSS_RATIO || in_frame_ratio < MIN_IN_FRAME_RATIO {
+            return None;
+        }
+        self.reported = true;
+        Some(MtuIssue {
+            largest_received: self.largest,
+            loss_ratio,
+            // Payload without RTP header, rounded down to a multiple of 64
+            suggested_blocksize: (self.largest.saturating_sub(12) / 64 * 64).max(512),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::rtp_packet;
+
+    fn packet(seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(96, 0, seq, timestamp, false, &[])
+    }
+
+    /// Frames of 4 fragments, `lose` decides which sequence numbers never arrive
+    fn run(detector: &mut MtuDetector, lose: impl Fn(u16) -> bool) -> Option<MtuIssue> {
+        let mut issue = None;
+        for seq in 0..2000u16 {
+            if lose(seq) {
+                continue;
+            }
+            let last_fragment = seq % 4 == 3;
+            let size = if last_fragment { 600 } else { 1400 };
+            issue = issue.or(detector.observe(&packet(seq, (seq / 4) as u32 * 3000), size));
+        }
+        issue
+    }
+
+    #[test]
+    fn test_mtu_issue_detected() {
+        let mut detector = MtuDetector::new();
+        // The middle fragments of every 8th frame don't make it
+        let issue = run(&mut detector, |seq| seq % 32 == 1 || seq % 32 == 2).unwrap();
+        assert!(issue.loss_ratio > 0.05);
+        assert_eq!(issue.largest_received, 1400);
+        assert_eq!(issue.suggested_blocksize, 1344);
+    }
+
+    #[test]
+    fn test_random_loss_not_reported() {
+        let mut detector = MtuDetector::new();
+        // Whole frames lost, gaps span different timestamps
+        assert!(run(&mut detector, |seq| seq % 32 < 4).is_none());
+        let mut detector = MtuDetector::new();
+        assert!(run(&mut detector, |_| false).is_none());
+    }
+}
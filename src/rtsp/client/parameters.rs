@@ -0,0 +1,287 @@
+use super::*;
+use crate::rtsp::{Method, Status};
+use std::marker::PhantomData;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("Channel closed")]
+    ChannelClosed,
+    #[error("Parameter {0} missing in response")]
+    Missing(String),
+    #[error("Invalid value {value:?} of parameter {name}")]
+    InvalidValue { name: String, value: String },
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Conversion of a parameter value from and to its `text/parameters` form
+pub trait ParameterValue: Sized {
+    fn decode(value: &str) -> Option<Self>;
+    fn encode(&self) -> String;
+}
+
+macro_rules! impl_parameter_value {
+    ($($t:ty),*) => {
+        $(impl ParameterValue for $t {
+            fn decode(value: &str) -> Option<Self> {
+                value.parse().ok()
+            }
+
+            fn encode(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+impl_parameter_value!(u8, u16, u32, i32, f64, String);
+
+impl ParameterValue for bool {
+    fn decode(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "1" | "true" | "on" | "yes" => Some(true),
+            "0" | "false" | "off" | "no" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> String {
+        if *self { "1" } else { "0" }.to_string()
+    }
+}
+
+/// A parameter name together with the type of its value. Names differ
+/// between vendors, use [`Preset::new`] for ones not listed here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset<T> {
+    pub name: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Preset<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: PhantomData,
+        }
+    }
+}
+
+/// Packets the server received from the client, the example parameter of
+/// GET_PARAMETER in RFC 2326 10.8 and RFC 7826 13.8
+pub const PACKETS_RECEIVED: Preset<u32> = Preset::new("packets_received");
+/// Interarrival jitter in seconds, from the same example
+pub const JITTER: Preset<f64> = Preset::new("jitter");
+
+// Neither RFC defines further names and ONVIF configures encoders through
+// its web services, not through parameters. The names below are the ones
+// this crate uses for the common settings, they are not taken from a vendor
+// specification, so check the camera documentation and fall back to
+// `Preset::new` with the vendor's name.
+
+/// Volume of the audio backchannel in percent
+pub const BACKCHANNEL_VOLUME: Preset<u8> = Preset::new("backchannel_volume");
+/// Video bitrate in kbit/s
+pub const BITRATE: Preset<u32> = Preset::new("bitrate");
+pub const FRAMERATE: Preset<u32> = Preset::new("framerate");
+/// Frames between two keyframes
+pub const GOP_LENGTH: Preset<u32> = Preset::new("gop_length");
+pub const AUDIO_ENABLED: Preset<bool> = Preset::new("audio_enabled");
+
+/// What a server supports of the parameter API
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub get_parameter: bool,
+    pub set_parameter: bool,
+    /// The probed parameters the server answered
+    pub parameters: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn supports<T>(&self, preset: &Preset<T>) -> bool {
+        self.parameters.iter().any(|p| p.eq_ignore_ascii_case(preset.name))
+    }
+}
+
+/// Typed GET_PARAMETER/SET_PARAMETER requests on a running [`Channel`]
+#[derive(Clone)]
+pub struct ParameterControl {
+    url: Url,
+    cmd_tx: mpsc::Sender<Command>,
+}
+
+impl ParameterControl {
+    pub fn new(url: Url, cmd_tx: mpsc::Sender<Command>) -> Self {
+        Self { url, cmd_tx }
+    }
+
+    async fn request<T>(&self, request: impl FnOnce(oneshot::Sender<CommandResult<T>>) -> Request) -> Result<T> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::Request(request(tx)))
+            .await
+            .map_err(|_| Error::ChannelClosed)?;
+        Ok(rx.await.map_err(|_| Error::ChannelClosed)??)
+    }
+
+    pub async fn get_raw<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<(String, String)>> {
+        let url = self.url.clone();
+        self.request(|tx| Request::GetParameter(GetParameter::new(url, names, tx)))
+            .await
+    }
+
+    pub async fn set_raw(&self, parameters: &[(&str, &str)]) -> Result<()> {
+        let url = self.url.clone();
+        self.request(|tx| Request::SetParameter(SetParameter::new(url, parameters, tx)))
+            .await
+    }
+
+    pub async fn get<T: ParameterValue>(&self, preset: &Preset<T>) -> Result<T> {
+        let parameters = self.get_raw(&[preset.name]).await?;
+        let (name, value) = parameters
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(preset.name))
+            .ok_or_else(|| Error::Missing(preset.name.to_string()))?;
+        T::decode(&value).ok_or(Error::InvalidValue { name, value })
+    }
+
+    pub async fn set<T: ParameterValue>(&self, preset: &Preset<T>, value: T) -> Result<()> {
+        self.set_raw(&[(preset.name, &value.encode())]).await
+    }
+
+    /// Checks the methods in the OPTIONS response, then reads every
+    /// parameter on its own, as a single unknown one fails the whole
+    /// request with 451 Parameter Not Understood
+    pub async fn probe(&self, names: &[&str]) -> Result<Capabilities> {
+        let url = self.url.clone();
+        let methods = self.request(|tx| Request::Options(Options::new(url, tx))).await?;
+        let mut capabilities = Capabilities {
+            get_parameter: methods.contains(&Method::GetParameter),
+            set_parameter: methods.contains(&Method::SetParameter),
+            parameters: Vec::new(),
+        };
+        if !capabilities.get_parameter {
+            return Ok(capabilities);
+        }
+        for name in names {
+            match self.get_raw(&[name]).await {
+                Ok(parameters) if parameters.iter().any(|(n, _)| n.eq_ignore_ascii_case(name)) => {
+                    capabilities.parameters.push(name.to_string())
+                }
+                Ok(_) => {}
+                Err(Error::Command(CommandError::UnexpectedStatus(
+                    Status::ParameterNotUnderstood | Status::NotImplemented | Status::BadRequest,
+                ))) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(capabilities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::{IncomingRequest, ResponseBuilder};
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    /// Knows the volume and bitrate parameters and the example of RFC 7826
+    /// 13.8, only the bitrate can be set
+    async fn serve(mut stream: DuplexStream) {
+        let mut values = HashMap::from([
+            ("backchannel_volume", "40".to_string()),
+            ("bitrate", "2048".to_string()),
+            ("packets_received", "10".to_string()),
+            ("jitter", "0.3838".to_string()),
+        ]);
+        let mut buf = Vec::new();
+        loop {
+            let mut chunk = [0u8; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            while let Some((request, n)) = IncomingRequest::parse(&buf).unwrap() {
+                buf.drain(..n);
+                let requested = parse_parameters(&request.body);
+                let response = match request.method.unwrap() {
+                    Method::Options => ResponseBuilder::new(Status::OK, request.cseq)
+                        .header("Public", "OPTIONS, DESCRIBE, GET_PARAMETER, SET_PARAMETER"),
+                    Method::GetParameter if requested.iter().all(|(n, _)| values.contains_key(n.as_str())) => {
+                        let body = requested
+                            .iter()
+                            .map(|(n, _)| format!("{}: {}\r\n", n, values[n.as_str()]))
+                            .collect();
+                        ResponseBuilder::new(Status::OK, request.cseq).body("text/parameters", body)
+                    }
+                    Method::SetParameter if requested.iter().all(|(n, _)| n == "bitrate") => {
+                        for (_, value) in requested {
+                            values.insert("bitrate", value);
+                        }
+                        ResponseBuilder::new(Status::OK, request.cseq)
+                    }
+                    Method::SetParameter => ResponseBuilder::new(Status::ParameterIsReadOnly, request.cseq),
+                    _ => ResponseBuilder::new(Status::ParameterNotUnderstood, request.cseq),
+                };
+                stream.write_all(response.to_string().as_bytes()).await.unwrap();
+            }
+        }
+    }
+
+    fn control() -> ParameterControl {
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        tokio::spawn(serve(sstream));
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        Channel::new(cstream, cmd_rx, packet_tx).start();
+        ParameterControl::new(Url::parse("rtsp://test.com/live").unwrap(), cmd_tx)
+    }
+
+    #[tokio::test]
+    async fn test_parameter_presets() {
+        let control = control();
+        assert_eq!(control.get(&BACKCHANNEL_VOLUME).await.unwrap(), 40);
+        control.set(&BITRATE, 4096).await.unwrap();
+        assert_eq!(control.get(&BITRATE).await.unwrap(), 4096);
+        assert!(matches!(
+            control.set(&BACKCHANNEL_VOLUME, 80).await,
+            Err(Error::Command(CommandError::UnexpectedStatus(
+                Status::ParameterIsReadOnly
+            )))
+        ));
+        assert!(control.get(&GOP_LENGTH).await.is_err());
+        assert_eq!(control.get(&PACKETS_RECEIVED).await.unwrap(), 10);
+        assert_eq!(control.get(&JITTER).await.unwrap(), 0.3838);
+        // Without names the request only keeps the session alive
+        assert!(control.get_raw::<&str>(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parameter_probe() {
+        let control = control();
+        let capabilities = control
+            .probe(&[BITRATE.name, GOP_LENGTH.name, BACKCHANNEL_VOLUME.name])
+            .await
+            .unwrap();
+        assert!(capabilities.get_parameter && capabilities.set_parameter);
+        assert!(capabilities.supports(&BITRATE));
+        assert!(!capabilities.supports(&GOP_LENGTH));
+        assert_eq!(capabilities.parameters, ["bitrate", "backchannel_volume"]);
+    }
+
+    #[test]
+    fn test_parameter_value() {
+        assert_eq!(bool::decode("On"), Some(true));
+        assert_eq!(bool::decode("0"), Some(false));
+        assert_eq!(true.encode(), "1");
+        assert_eq!(u8::decode("300"), None);
+        assert_eq!(f64::decode("0.3838"), Some(0.3838));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::rtp::Delivery;
+use crate::sdp::Sdp;
+use std::time::Duration;
+
+/// Largest interleaved frame, the `$` header and a 16 bit length of payload
+pub const MAX_INTERLEAVED_FRAME: usize = 4 + u16::MAX as usize;
+
+/// Kind of media a session carries, which decides how much buffering it needs.
+///
+/// Video needs room for large frames and deep reordering, an audio intercom
+/// or a metadata stream gets along with a fraction of that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaProfile {
+    Video,
+    /// Audio without video
+    Audio,
+    /// Neither audio nor video, e.g. ONVIF metadata or events
+    Metadata,
+}
+
+impl MediaProfile {
+    pub fn from_sdp(sdp: &Sdp) -> Self {
+        if sdp.media.iter().any(|m| m.media_type == "video") {
+            MediaProfile::Video
+        } else if sdp.media.iter().any(|m| m.media_type == "audio") {
+            MediaProfile::Audio
+        } else {
+            MediaProfile::Metadata
+        }
+    }
+
+    pub fn limits(&self) -> ProfileLimits {
+        match self {
+            MediaProfile::Video => ProfileLimits {
+                receive_buffer: 512 * 1024,
+                send_buffer: 512 * 1024,
+                reorder_len: 32,
+                jitter_delay: Duration::from_millis(200),
+            },
+            MediaProfile::Audio => ProfileLimits {
+                receive_buffer: 96 * 1024,
+                send_buffer: 16 * 1024,
+                reorder_len: 8,
+                jitter_delay: Duration::from_millis(60),
+            },
+            MediaProfile::Metadata => ProfileLimits {
+                receive_buffer: 96 * 1024,
+                send_buffer: 16 * 1024,
+                reorder_len: 4,
+                jitter_delay: Duration::ZERO,
+            },
+        }
+    }
+}
+
+/// Buffer sizes used for a [`MediaProfile`]. The receive buffer must hold
+/// the largest interleaved frame or response and a read on top, a
+/// [`Channel`](super::Channel) raises smaller limits to that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileLimits {
+    /// Maximum size of the channel's receive buffer
+    pub receive_buffer: usize,
+    /// Maximum size of the channel's send buffer
+    pub send_buffer: usize,
+    /// Packets a [`TrackQueue`](crate::rtp::TrackQueue) waits for missing ones
+    pub reorder_len: usize,
+    /// Suggested delay of a [`JitterBuffer`](crate::rtp::JitterBuffer)
+    pub jitter_delay: Duration,
+}
+
+impl ProfileLimits {
+    pub fn delivery(&self) -> Delivery {
+        Delivery::Reordered {
+            max_len: self.reorder_len,
+        }
+    }
+}
+
+impl Default for ProfileLimits {
+    fn default() -> Self {
+        MediaProfile::Video.limits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sdp(media: &[&str]) -> Sdp {
+        let mut text = String::from("v=0\r\ns=Test\r\n");
+        for m in media {
+            text.push_str(&format!("m={} 0 RTP/AVP 96\r\n", m));
+        }
+        Sdp::try_from(text.as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_profile_from_sdp() {
+        assert_eq!(MediaProfile::from_sdp(&sdp(&["audio", "video"])), MediaProfile::Video);
+        assert_eq!(
+            MediaProfile::from_sdp(&sdp(&["audio", "application"])),
+            MediaProfile::Audio
+        );
+        assert_eq!(MediaProfile::from_sdp(&sdp(&["application"])), MediaProfile::Metadata);
+    }
+
+    #[test]
+    fn test_profile_limits() {
+        let audio = MediaProfile::Audio.limits();
+        assert!(audio.receive_buffer < ProfileLimits::default().receive_buffer);
+        // A frame of the largest size and a read of the default 4 KiB
+        assert!(audio.receive_buffer >= MAX_INTERLEAVED_FRAME + 4096);
+        assert_eq!(audio.delivery(), Delivery::Reordered { max_len: 8 });
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+/// Reception quality of one track, derived from its RTP packets and
+/// updated with every RTCP packet of the track
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackQuality {
+    /// Percentage of the expected packets lost since the first one
+    pub loss_percent: f64,
+    /// Interarrival jitter, zero if the clock rate of the track is unknown
+    pub jitter: Duration,
+    /// Round-trip time from the last report of the server about one of our
+    /// sources, e.g. of a backchannel
+    pub rtt: Option<Duration>,
+    /// When the last sender report of the track arrived
+    pub last_sender_report: Option<SystemTime>,
+}
+
+/// Watch channels of the [`TrackQuality`] by RTP channel, shared with a
+/// [`Channel`](super::Channel) by
+/// [`Channel::share_quality`](super::Channel::share_quality).
+///
+/// A UI binds to one track instead of polling the whole stats:
+///
+/// ```ignore
+/// let quality = QualityHandle::new();
+/// let mut video = quality.subscribe(0);
+/// let channel = Channel::new(stream, cmd_rx, packet_tx).share_quality(quality.clone());
+/// while video.changed().await.is_ok() {
+///     let q = *video.borrow();
+///     label.set_text(&format!("{:.1}% lost, {:?} jitter", q.loss_percent, q.jitter));
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct QualityHandle {
+    tracks: Arc<Mutex<BTreeMap<u8, watch::Sender<TrackQuality>>>>,
+}
+
+impl QualityHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receives the quality of the track on `rtp_channel`, also before it
+    /// is set up. The receiver stays valid across reconnects that share
+    /// the handle.
+    pub fn subscribe(&self, rtp_channel: u8) -> watch::Receiver<TrackQuality> {
+        let mut tracks = self.tracks.lock().unwrap();
+        tracks
+            .entry(rtp_channel)
+            .or_insert_with(|| watch::channel(TrackQuality::default()).0)
+            .subscribe()
+    }
+
+    /// The latest quality of every track
+    pub fn snapshot(&self) -> BTreeMap<u8, TrackQuality> {
+        let tracks = self.tracks.lock().unwrap();
+        tracks.iter().map(|(channel, tx)| (*channel, *tx.borrow())).collect()
+    }
+
+    pub(super) fn update(&self, rtp_channel: u8, update: impl FnOnce(&mut TrackQuality)) {
+        let mut tracks = self.tracks.lock().unwrap();
+        let tx = tracks
+            .entry(rtp_channel)
+            .or_insert_with(|| watch::channel(TrackQuality::default()).0);
+        // Receivers are notified even if nothing changed, an RTCP packet arrived
+        tx.send_modify(update);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_quality_handle() {
+        let quality = QualityHandle::new();
+        let mut rx = quality.subscribe(2);
+        assert_eq!(*rx.borrow(), TrackQuality::default());
+        quality.update(2, |q| q.loss_percent = 5.0);
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow_and_update().loss_percent, 5.0);
+        // Tracks nobody subscribed to are kept for later receivers
+        quality.update(0, |q| q.jitter = Duration::from_millis(3));
+        assert_eq!(quality.subscribe(0).borrow().jitter, Duration::from_millis(3));
+        assert_eq!(quality.snapshot().keys().copied().collect::<Vec<_>>(), [0, 2]);
+    }
+}
@@ -0,0 +1,154 @@
+use super::Vendor;
+use crate::rtsp::{Header, Session};
+use url::Url;
+
+/// Deviations from the specification a [`Channel`](super::Channel)
+/// tolerates, for servers whose responses the strict parsers reject. Every
+/// quirk is toggled on its own, [`Quirks::detect`] picks the ones of a known
+/// server.
+///
+/// Resolving the track controls against the Content-Base or
+/// Content-Location of a DESCRIBE response is required by the
+/// specification and always done, see [`control_url`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// Spaces around header names, e.g. `CSeq : 2`
+    pub lenient_headers: bool,
+    /// Session headers like `id;timeout=` or `id; timeout = 60s`
+    pub lenient_session: bool,
+    /// Method names of the Public header in any case, e.g. `describe`
+    pub lenient_methods: bool,
+    /// A body with a Content-Type but without a Content-Length, e.g. of a
+    /// DESCRIBE response, ends when the server closes the connection
+    pub body_until_close: bool,
+}
+
+impl Quirks {
+    /// Strict parsing, the default
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn all() -> Self {
+        Self {
+            lenient_headers: true,
+            lenient_session: true,
+            lenient_methods: true,
+            body_until_close: true,
+        }
+    }
+
+    /// The quirks of the servers of `vendor`
+    pub fn for_vendor(vendor: Vendor) -> Self {
+        match vendor {
+            // Camera firmware, whose RTSP stacks differ between models
+            Vendor::Hikvision | Vendor::Dahua | Vendor::Uniview => Self {
+                lenient_headers: true,
+                lenient_session: true,
+                lenient_methods: true,
+                body_until_close: false,
+            },
+            _ => Self::none(),
+        }
+    }
+
+    /// The quirks of the implementation named by a Server or User-Agent
+    /// header, none if it is unknown
+    pub fn detect(product: &str) -> Self {
+        Vendor::identify(product).map_or_else(Self::none, Self::for_vendor)
+    }
+
+    /// The standard form of a header value the strict parsers would reject
+    pub(crate) fn normalize(&self, header: &Header) -> Option<String> {
+        if self.lenient_session && header.name.eq_ignore_ascii_case("session") {
+            if header.value.parse::<Session>().is_ok() {
+                return None;
+            }
+            let session = Session::parse_lenient(header.value).ok()?;
+            return Some(match session.timeout {
+                Some(timeout) => format!("{};timeout={}", session.id, timeout),
+                None => session.id,
+            });
+        }
+        let methods = header.name.eq_ignore_ascii_case("public") || header.name.eq_ignore_ascii_case("allow");
+        if self.lenient_methods && methods && header.value.bytes().any(|b| b.is_ascii_lowercase()) {
+            return Some(header.value.to_ascii_uppercase());
+        }
+        None
+    }
+}
+
+/// Base url of the tracks of a DESCRIBE response to `request`: the
+/// Content-Base or else the Content-Location header, relative values are
+/// resolved against the request url
+pub fn content_base(request: &Url, headers: &[Header]) -> Option<Url> {
+    ["content-base", "content-location"].iter().find_map(|name| {
+        let header = headers.iter().find(|h| h.name.eq_ignore_ascii_case(name))?;
+        request.join(header.value).ok()
+    })
+}
+
+/// Resolves the `a=control` of a track against the base url of the
+/// description. A relative control is appended to the base as given, also
+/// behind a query, like the `?channel=1&subtype=0/` bases of some cameras.
+pub fn control_url(base: &Url, control: &str) -> Url {
+    if control == "*" {
+        return base.clone();
+    }
+    if let Ok(url) = Url::parse(control) {
+        return url;
+    }
+    if control.starts_with('/') {
+        return base.join(control).unwrap_or_else(|_| base.clone());
+    }
+    let separator = if base.as_str().ends_with('/') { "" } else { "/" };
+    Url::parse(&format!("{}{}{}", base, separator, control)).unwrap_or_else(|_| base.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_quirks() {
+        assert_eq!(Quirks::detect("Hikvision-Webs"), Quirks::for_vendor(Vendor::Hikvision));
+        assert!(Quirks::detect("Dahua Rtsp Server/3.0").lenient_session);
+        assert_eq!(Quirks::detect("GStreamer RTSP server"), Quirks::none());
+        assert_eq!(Quirks::detect("unknown"), Quirks::none());
+    }
+
+    #[test]
+    fn test_normalize_headers() {
+        let quirks = Quirks::all();
+        let session = Header::new("Session", "12345678; timeout = 60s");
+        assert_eq!(quirks.normalize(&session).as_deref(), Some("12345678;timeout=60"));
+        let session = Header::new("Session", "12345678;timeout=");
+        assert_eq!(quirks.normalize(&session).as_deref(), Some("12345678"));
+        assert_eq!(quirks.normalize(&Header::new("Session", "12345678;timeout=60")), None);
+        let public = Header::new("Public", "OPTIONS, describe, Setup");
+        assert_eq!(quirks.normalize(&public).as_deref(), Some("OPTIONS, DESCRIBE, SETUP"));
+        assert_eq!(Quirks::none().normalize(&public), None);
+    }
+
+    #[test]
+    fn test_control_url() {
+        let request = Url::parse("rtsp://cam/live").unwrap();
+        let headers = [Header::new("Content-Base", "rtsp://cam/live/")];
+        let base = content_base(&request, &headers).unwrap();
+        assert_eq!(control_url(&base, "trackID=1").as_str(), "rtsp://cam/live/trackID=1");
+        assert_eq!(control_url(&base, "*").as_str(), "rtsp://cam/live/");
+        assert_eq!(control_url(&base, "/other/track").as_str(), "rtsp://cam/other/track");
+        assert_eq!(control_url(&base, "rtsp://other/track").as_str(), "rtsp://other/track");
+
+        let headers = [Header::new("Content-Location", "stream/")];
+        let base = content_base(&request, &headers).unwrap();
+        assert_eq!(control_url(&base, "track1").as_str(), "rtsp://cam/stream/track1");
+        assert_eq!(content_base(&request, &[]), None);
+
+        let base = Url::parse("rtsp://cam/cam/realmonitor?channel=1&subtype=0/").unwrap();
+        assert_eq!(
+            control_url(&base, "trackID=0").as_str(),
+            "rtsp://cam/cam/realmonitor?channel=1&subtype=0/trackID=0"
+        );
+    }
+}
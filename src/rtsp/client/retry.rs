@@ -0,0 +1,121 @@
+use rand::Rng;
+use std::fmt;
+use std::time::Duration;
+
+/// Decides whether and when a failed operation is attempted again, e.g. a
+/// timed out request of a [`Channel`](super::Channel) or a failed connect of
+/// a [`Supervisor`](super::Supervisor).
+pub trait RetryPolicy: fmt::Debug + Send + Sync {
+    /// Delay before retry number `retry`, counted from 1, or None to give up
+    fn delay(&self, retry: u32) -> Option<Duration>;
+}
+
+/// Exponential backoff: the first retry waits `initial`, every following
+/// one `multiplier` times longer up to `max_delay`. Jitter spreads the
+/// delays of many clients, e.g. cameras reconnecting after a network outage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    initial: Duration,
+    multiplier: f64,
+    max_delay: Duration,
+    max_retries: Option<u32>,
+    jitter: f64,
+}
+
+impl Backoff {
+    /// Doubles the delay up to a minute, without a limit on the retries
+    pub fn new(initial: Duration) -> Self {
+        Self {
+            initial,
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(60),
+            max_retries: None,
+            jitter: 0.0,
+        }
+    }
+
+    /// Waits `delay` before every retry
+    pub fn fixed(delay: Duration) -> Self {
+        Self::new(delay).multiplier(1.0).max_delay(delay)
+    }
+
+    /// Retries immediately
+    pub fn immediate() -> Self {
+        Self::fixed(Duration::ZERO)
+    }
+
+    /// Never retries
+    pub fn never() -> Self {
+        Self::immediate().max_retries(0)
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = Some(retries);
+        self
+    }
+
+    /// Varies each delay randomly by up to `fraction` of it in either direction
+    pub fn jitter(mut self, fraction: f64) -> Self {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+}
+
+impl RetryPolicy for Backoff {
+    fn delay(&self, retry: u32) -> Option<Duration> {
+        if retry == 0 || self.max_retries.is_some_and(|max| retry > max) {
+            return None;
+        }
+        let exponent = i32::try_from(retry - 1).unwrap_or(i32::MAX);
+        let delay = (self.initial.as_secs_f64() * self.multiplier.powi(exponent)).min(self.max_delay.as_secs_f64());
+        let factor = match self.jitter > 0.0 {
+            true => 1.0 + rand::rng().random_range(-self.jitter..=self.jitter),
+            false => 1.0,
+        };
+        Some(Duration::try_from_secs_f64(delay * factor).unwrap_or(self.max_delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let backoff = Backoff::new(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(350))
+            .max_retries(4);
+        let delays: Vec<_> = (1..=5).map(|retry| backoff.delay(retry)).collect();
+        assert_eq!(
+            delays,
+            [
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(350)),
+                Some(Duration::from_millis(350)),
+                None
+            ]
+        );
+        assert_eq!(Backoff::never().delay(1), None);
+        assert_eq!(Backoff::immediate().delay(u32::MAX), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let backoff = Backoff::fixed(Duration::from_secs(1)).jitter(0.5);
+        for retry in 1..100 {
+            let delay = backoff.delay(retry).unwrap();
+            assert!((Duration::from_millis(500)..=Duration::from_millis(1500)).contains(&delay));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::http::parse_http_date;
+use crate::rtsp::{Header, Method};
+use crate::sdp::Sdp;
+use crate::sync::{ClockSkew, SkewEstimator};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Server implementations with known behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Vendor {
+    Hikvision,
+    Dahua,
+    Axis,
+    Uniview,
+    Live555,
+    GStreamer,
+    Ffmpeg,
+    MediaMtx,
+    Wowza,
+}
+
+/// Substrings of the Server header or SDP tool identifying a [`Vendor`],
+/// compared case-insensitively in order
+const KNOWN_SERVERS: &[(&str, Vendor)] = &[
+    ("hikvision", Vendor::Hikvision),
+    ("dahua", Vendor::Dahua),
+    ("axis", Vendor::Axis),
+    ("uniview", Vendor::Uniview),
+    ("live555", Vendor::Live555),
+    ("gstreamer", Vendor::GStreamer),
+    ("libavformat", Vendor::Ffmpeg),
+    ("lavf", Vendor::Ffmpeg),
+    ("mediamtx", Vendor::MediaMtx),
+    ("rtsp-simple-server", Vendor::MediaMtx),
+    ("wowza", Vendor::Wowza),
+];
+
+impl Vendor {
+    /// Finds the vendor of a Server header or SDP tool line
+    pub fn identify(s: &str) -> Option<Vendor> {
+        let s = s.to_ascii_lowercase();
+        KNOWN_SERVERS
+            .iter()
+            .find(|(pattern, _)| s.contains(pattern))
+            .map(|(_, vendor)| *vendor)
+    }
+}
+
+/// What is known about the server of a channel, collected from the
+/// responses to OPTIONS and DESCRIBE, e.g. to log the firmware a camera runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Value of the `Server` header
+    pub server: Option<String>,
+    /// Methods of the `Public` header of the OPTIONS response
+    pub methods: Vec<Method>,
+    /// Session level `a=tool` attribute of the SDP
+    pub sdp_tool: Option<String>,
+    /// Identified from the server or, if it is unknown, from the SDP tool
+    pub vendor: Option<Vendor>,
+    /// Offset of the server's clock, estimated from the `Date` headers of
+    /// its responses
+    pub clock_skew: Option<ClockSkew>,
+}
+
+impl ServerInfo {
+    fn update(&mut self, method: Method, headers: &[Header], body: &str) {
+        if let Some(server) = headers.iter().find(|h| h.name.eq_ignore_ascii_case("server")) {
+            self.server = Some(server.value.to_string());
+        }
+        match method {
+            Method::Options => {
+                if let Some(public) = headers.iter().find(|h| h.name.eq_ignore_ascii_case("public")) {
+                    self.methods = public.value.split(',').filter_map(|m| m.trim().parse().ok()).collect();
+                }
+            }
+            Method::Describe => {
+                if let Ok(sdp) = Sdp::try_from(body) {
+                    self.sdp_tool = sdp.attribute("tool").map(|t| t.to_string());
+                }
+            }
+            _ => {}
+        }
+        self.vendor = self
+            .server
+            .as_deref()
+            .and_then(Vendor::identify)
+            .or_else(|| self.sdp_tool.as_deref().and_then(Vendor::identify));
+    }
+
+    pub fn supports(&self, method: Method) -> bool {
+        self.methods.contains(&method)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    info: ServerInfo,
+    skew: SkewEstimator,
+}
+
+/// Shared handle to the [`ServerInfo`] of a channel, see [`UsageMeter`](super::UsageMeter)
+#[derive(Debug, Default, Clone)]
+pub struct ServerInfoHandle {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl ServerInfoHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> ServerInfo {
+        self.shared.lock().unwrap().info.clone()
+    }
+
+    /// Records a successful response to a request of `method`
+    pub(crate) fn record(&self, method: Method, headers: &[Header], body: &str) {
+        self.shared.lock().unwrap().info.update(method, headers, body);
+    }
+
+    /// Records the `Date` header of any response to a request sent at
+    /// `sent` and received at `received`
+    pub(crate) fn record_date(&self, headers: &[Header], sent: SystemTime, received: SystemTime) {
+        let Some(date) = headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("date"))
+            .and_then(|h| parse_http_date(h.value))
+        else {
+            return;
+        };
+        let mut shared = self.shared.lock().unwrap();
+        shared.skew.record(date, sent, received);
+        shared.info.clock_skew = shared.skew.estimate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_identify_vendor() {
+        assert_eq!(Vendor::identify("Hikvision-Webs"), Some(Vendor::Hikvision));
+        assert_eq!(
+            Vendor::identify("LIVE555 Streaming Media v2021.08.24"),
+            Some(Vendor::Live555)
+        );
+        assert_eq!(Vendor::identify("Lavf58.76.100"), Some(Vendor::Ffmpeg));
+        assert_eq!(Vendor::identify("Rtsp Server/3.0"), None);
+    }
+
+    #[test]
+    fn test_server_info() {
+        let handle = ServerInfoHandle::new();
+        let headers = [
+            Header::new("Server", "Rtsp Server/3.0"),
+            Header::new("Public", "OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN"),
+        ];
+        handle.record(Method::Options, &headers, "");
+        let sdp = "v=0\r\ns=Media Presentation\r\na=tool:LIVE555 Streaming Media v2017.10.28\r\n";
+        handle.record(Method::Describe, &[], sdp);
+        let info = handle.snapshot();
+        assert_eq!(info.server.as_deref(), Some("Rtsp Server/3.0"));
+        assert!(info.supports(Method::Play));
+        assert!(!info.supports(Method::Record));
+        assert_eq!(info.sdp_tool.as_deref(), Some("LIVE555 Streaming Media v2017.10.28"));
+        assert_eq!(info.vendor, Some(Vendor::Live555));
+    }
+
+    #[test]
+    fn test_server_info_clock_skew() {
+        let handle = ServerInfoHandle::new();
+        let sent = UNIX_EPOCH + Duration::from_secs(784_111_700);
+        handle.record_date(&[], sent, sent);
+        assert_eq!(handle.snapshot().clock_skew, None);
+        let headers = [Header::new("Date", "Sun, 06 Nov 1994 08:49:37 GMT")];
+        handle.record_date(&headers, sent, sent + Duration::from_millis(200));
+        let skew = handle.snapshot().clock_skew.unwrap();
+        assert_eq!(skew.offset_ms, 77_400);
+        assert_eq!(skew.uncertainty, Duration::from_millis(600));
+    }
+}
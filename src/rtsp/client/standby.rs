@@ -0,0 +1,255 @@
+use super::*;
+use crate::rtp;
+use crate::task;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("Channel closed")]
+    ChannelClosed,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A running, authenticated channel without any SETUP, ready to take over.
+pub struct StandbyConnection {
+    pub cmd_tx: mpsc::Sender<Command>,
+    pub packet_rx: mpsc::Receiver<rtp::Packet>,
+    pub handle: JoinHandle<()>,
+    /// Result of the DESCRIBE that authenticated the connection
+    pub description: Description,
+}
+
+/// Keeps a second control connection to a camera open, so a failed
+/// primary session can be replaced without connecting and authenticating
+/// again. Only SETUP and PLAY remain to be done after [`WarmStandby::promote`].
+pub struct WarmStandby {
+    url: Url,
+    tls: TlsConfig,
+    user: Option<String>,
+    pass: String,
+    keepalive: Duration,
+    keepalive_retry_policy: Arc<dyn RetryPolicy>,
+    ready: Option<(StandbyConnection, JoinHandle<()>)>,
+}
+
+impl WarmStandby {
+    pub fn new(url: Url, tls: TlsConfig) -> Self {
+        Self {
+            url,
+            tls,
+            user: None,
+            pass: String::new(),
+            keepalive: Duration::from_secs(30),
+            keepalive_retry_policy: Arc::new(Backoff::never()),
+            ready: None,
+        }
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    pub fn pass(mut self, pass: &str) -> Self {
+        self.pass = pass.to_string();
+        self
+    }
+
+    /// Interval of the OPTIONS requests that keep the idle connection open
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = interval;
+        self
+    }
+
+    /// Decides whether a failed keep-alive is sent again before the standby
+    /// connection is given up, by default it is given up at once
+    pub fn keepalive_retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.keepalive_retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Connects and authenticates the standby connection, replacing a
+    /// previous one.
+    pub async fn prepare(&mut self) -> Result<()> {
+        self.close();
+        let stream = connect(&self.url, &self.tls).await?;
+        let datagram = stream.is_datagram();
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, packet_rx) = mpsc::channel(1024);
+        let mut channel = Channel::new(stream, cmd_rx, packet_tx)
+            .name("rtsp-standby")
+            .pass(&self.pass);
+        if datagram {
+            channel = channel.datagram();
+        }
+        if let Some(user) = &self.user {
+            channel = channel.user(user);
+        }
+        let handle = channel.start();
+        let (tx, rx) = oneshot::channel();
+        let describe = Describe::new(self.url.clone(), tx);
+        cmd_tx
+            .send(Command::Request(Request::Describe(describe)))
+            .await
+            .map_err(|_| Error::ChannelClosed)?;
+        let description = rx.await.map_err(|_| Error::ChannelClosed)??;
+        let keepalive = task::spawn(
+            task::KEEPALIVE,
+            keep_alive(
+                cmd_tx.clone(),
+                self.url.clone(),
+                self.keepalive,
+                self.keepalive_retry_policy.clone(),
+            ),
+        );
+        let connection = StandbyConnection {
+            cmd_tx,
+            packet_rx,
+            handle,
+            description,
+        };
+        self.ready = Some((connection, keepalive));
+        Ok(())
+    }
+
+    /// True if a prepared connection is still alive
+    pub fn is_ready(&self) -> bool {
+        self.ready
+            .as_ref()
+            .is_some_and(|(c, keepalive)| !c.handle.is_finished() && !keepalive.is_finished())
+    }
+
+    /// Hands out the standby connection to replace the primary one.
+    /// Call [`WarmStandby::prepare`] again for a new standby.
+    pub fn promote(&mut self) -> Option<StandbyConnection> {
+        if !self.is_ready() {
+            self.close();
+            return None;
+        }
+        let (connection, keepalive) = self.ready.take()?;
+        keepalive.abort();
+        Some(connection)
+    }
+
+    fn close(&mut self) {
+        if let Some((connection, keepalive)) = self.ready.take() {
+            keepalive.abort();
+            connection.handle.abort();
+        }
+    }
+}
+
+impl Drop for WarmStandby {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+async fn keep_alive(cmd_tx: mpsc::Sender<Command>, url: Url, interval: Duration, retry_policy: Arc<dyn RetryPolicy>) {
+    let mut failures = 0;
+    let mut wait = interval;
+    loop {
+        tokio::time::sleep(wait).await;
+        let (tx, rx) = oneshot::channel();
+        let options = Options::new(url.clone(), tx);
+        if cmd_tx.send(Command::Request(Request::Options(options))).await.is_err() {
+            break;
+        }
+        match rx.await {
+            Ok(Ok(_)) => {
+                failures = 0;
+                wait = interval;
+            }
+            Ok(Err(e)) => {
+                failures += 1;
+                match retry_policy.delay(failures) {
+                    Some(delay) => {
+                        log::warn!("Standby keep-alive failed: {}, retrying in {:?}", e, delay);
+                        wait = delay;
+                    }
+                    None => {
+                        log::warn!("Standby keep-alive failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers DESCRIBE with 401 until credentials are sent, OPTIONS with 200
+    async fn serve(listener: TcpListener, options_tx: mpsc::Sender<()>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            let request = std::str::from_utf8(&buf[..n]).unwrap();
+            let cseq = request
+                .lines()
+                .find_map(|l| l.strip_prefix("CSeq: "))
+                .unwrap()
+                .to_string();
+            let response = if request.starts_with("OPTIONS") {
+                let _ = options_tx.try_send(());
+                format!("RTSP/1.0 200 OK\r\nCSeq: {}\r\nPublic: DESCRIBE\r\n\r\n", cseq)
+            } else if request.contains("Authorization") {
+                let body = "v=0\r\nm=video 0 RTP/AVP 96\r\n";
+                format!(
+                    "RTSP/1.0 200 OK\r\nCSeq: {}\r\nContent-Type: application/sdp\r\nContent-Length: {}\r\n\r\n{}",
+                    cseq,
+                    body.len(),
+                    body
+                )
+            } else {
+                format!(
+                    "RTSP/1.0 401 Unauthorized\r\nCSeq: {}\r\nWWW-Authenticate: Basic realm=\"cam\"\r\n\r\n",
+                    cseq
+                )
+            };
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_standby() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("rtsp://{}/stream", listener.local_addr().unwrap())).unwrap();
+        let (options_tx, mut options_rx) = mpsc::channel(8);
+        tokio::spawn(serve(listener, options_tx));
+
+        let mut standby = WarmStandby::new(url, TlsConfig::new())
+            .user("admin")
+            .pass("secret")
+            .keepalive(Duration::from_millis(10));
+        assert!(standby.promote().is_none());
+        standby.prepare().await.unwrap();
+        assert!(standby.is_ready());
+        // The idle connection is kept alive
+        options_rx.recv().await.unwrap();
+
+        let connection = standby.promote().unwrap();
+        assert!(!standby.is_ready());
+        assert_eq!(connection.description.sdp().unwrap().media.len(), 1);
+        connection.handle.abort();
+    }
+}
@@ -0,0 +1,748 @@
+use super::*;
+use crate::rtp;
+use crate::rtsp::{Range, Transport};
+use crate::task;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[error("Channel closed")]
+    ChannelClosed,
+    #[error("Too many redirects, the last one to {0}")]
+    TooManyRedirects(Url),
+    #[error("Not playing")]
+    NotPlaying,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Time to wait for the TEARDOWN response in [`SupervisorHandle::shutdown`]
+pub const TEARDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Redirects followed per connection unless set with [`Supervisor::max_redirects`]
+pub const DEFAULT_MAX_REDIRECTS: u32 = 5;
+
+/// Marks where the output of a new connection starts. Sequence numbers and
+/// timestamps start over, depacketizers and jitter buffers must be reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Discontinuity {
+    /// Successful reconnects so far, including this one
+    pub reconnects: u32,
+    /// Time between losing the previous connection and playing again
+    pub downtime: Duration,
+}
+
+/// Range and scale of the PLAY requests, the latest seek is played again
+/// after a reconnect
+#[derive(Debug, Clone, Copy, Default)]
+struct Playback {
+    range: Option<Range>,
+    scale: Option<f64>,
+}
+
+impl Playback {
+    fn play(&self, url: Url, tx: oneshot::Sender<CommandResult<PlayResponse>>) -> Request {
+        let mut play = Play::new(url, tx);
+        if let Some(range) = self.range {
+            play = play.range(range);
+        }
+        if let Some(scale) = self.scale {
+            play = play.scale(scale);
+        }
+        Request::Play(play)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamItem {
+    Packet(rtp::Packet),
+    Discontinuity(Discontinuity),
+}
+
+/// Plays a stream and reconnects whenever the connection is lost.
+///
+/// All connections deliver into the same sender, so consumers keep their
+/// receiver across reconnects and only see a [`StreamItem::Discontinuity`]
+/// at the boundary. Every track of the SDP is set up interleaved, so
+/// `rtspu://` urls, whose requests are datagrams, aren't supported.
+pub struct Supervisor {
+    url: Url,
+    tls: TlsConfig,
+    user: Option<String>,
+    pass: String,
+    retry_policy: Arc<dyn RetryPolicy>,
+    // HTTP port to tunnel the connection through
+    http_tunnel: Option<u16>,
+    connect_options: ConnectOptions,
+    // Replaces the transport picked by the url scheme
+    connector: Option<Arc<dyn Connector>>,
+    // Send OPTIONS before DESCRIBE to learn about the server
+    fingerprint: bool,
+    max_redirects: u32,
+    // Where the latest connection ended up after redirects
+    current_url: Arc<Mutex<Url>>,
+    playback: Arc<Mutex<Playback>>,
+    // Commands of the connection that is playing, for seeks
+    session_tx: Arc<Mutex<Option<mpsc::Sender<Command>>>>,
+    server_info: ServerInfoHandle,
+    quality: Option<QualityHandle>,
+    item_tx: mpsc::Sender<StreamItem>,
+}
+
+/// Stops the supervisor when dropped, see [`SupervisorHandle::shutdown`]
+/// to end the session gracefully
+pub struct SupervisorHandle {
+    reconnect_tx: mpsc::Sender<()>,
+    shutdown_tx: mpsc::Sender<()>,
+    current_url: Arc<Mutex<Url>>,
+    playback: Arc<Mutex<Playback>>,
+    session_tx: Arc<Mutex<Option<mpsc::Sender<Command>>>>,
+    server_info: ServerInfoHandle,
+    handle: JoinHandle<()>,
+}
+
+impl SupervisorHandle {
+    /// Drops the current connection and connects again, e.g. when the
+    /// stream stalled without the connection being closed
+    pub fn reconnect(&self) {
+        let _ = self.reconnect_tx.try_send(());
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Tears the session down and waits until the supervisor stopped, the
+    /// stream then closes. The TEARDOWN response is awaited for up to
+    /// [`TEARDOWN_TIMEOUT`], so servers free the session right away
+    /// instead of when it times out.
+    pub async fn shutdown(mut self) {
+        if self.shutdown_tx.send(()).await.is_ok() {
+            let _ = (&mut self.handle).await;
+        }
+    }
+
+    /// The url the latest connection plays after following redirects,
+    /// the one given to the supervisor before the first connection
+    pub fn url(&self) -> Url {
+        self.current_url.lock().unwrap().clone()
+    }
+
+    /// What the server told about itself on the latest connection, see
+    /// [`Supervisor::fingerprint`]
+    pub fn server_info(&self) -> ServerInfo {
+        self.server_info.snapshot()
+    }
+
+    /// Plays `range` at `scale` on the current session, e.g. to jump in a
+    /// recording or to fast forward. PLAY is sent again without a TEARDOWN,
+    /// so the transport and the stream stay. Reconnects play from the
+    /// latest seek.
+    pub async fn seek(&self, range: Range, scale: Option<f64>) -> Result<PlayResponse> {
+        let session_tx = self.session_tx.lock().unwrap().clone();
+        let session_tx = session_tx.ok_or(Error::NotPlaying)?;
+        let playback = Playback {
+            range: Some(range),
+            scale,
+        };
+        let url = self.url();
+        let response = request(&session_tx, |tx| playback.play(url, tx)).await?;
+        *self.playback.lock().unwrap() = playback;
+        Ok(response)
+    }
+}
+
+impl Drop for SupervisorHandle {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+struct Playing {
+    handle: JoinHandle<()>,
+    // Dropping the command sender would shut the channel down
+    cmd_tx: mpsc::Sender<Command>,
+    packet_rx: mpsc::Receiver<rtp::Packet>,
+    url: Url,
+}
+
+impl Playing {
+    /// Sends TEARDOWN and shuts the channel down
+    async fn teardown(self) {
+        let url = self.url.clone();
+        let teardown = request(&self.cmd_tx, |tx| Request::Teardown(Teardown::new(url, tx)));
+        match tokio::time::timeout(TEARDOWN_TIMEOUT, teardown).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("TEARDOWN of {} failed: {}", self.url, e),
+            Err(_) => log::warn!("TEARDOWN of {} timed out", self.url),
+        }
+        let _ = self.cmd_tx.send(Command::Ctrl(Ctrl::Shutdown)).await;
+        let _ = self.handle.await;
+    }
+}
+
+enum Ended {
+    ConnectionLost,
+    ReconnectRequested,
+    ShutdownRequested,
+    ConsumerGone,
+}
+
+impl Supervisor {
+    pub fn new(url: Url, tls: TlsConfig, item_tx: mpsc::Sender<StreamItem>) -> Self {
+        Self {
+            current_url: Arc::new(Mutex::new(url.clone())),
+            playback: Arc::new(Mutex::new(Playback::default())),
+            session_tx: Arc::new(Mutex::new(None)),
+            url,
+            tls,
+            user: None,
+            pass: String::new(),
+            retry_policy: Arc::new(Backoff::fixed(Duration::from_secs(2))),
+            http_tunnel: None,
+            connect_options: ConnectOptions::new(),
+            connector: None,
+            fingerprint: false,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            server_info: ServerInfoHandle::new(),
+            quality: None,
+            item_tx,
+        }
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    pub fn pass(mut self, pass: &str) -> Self {
+        self.pass = pass.to_string();
+        self
+    }
+
+    /// Wait before connecting again after a failed attempt, see
+    /// [`Supervisor::retry_policy`] for backoff
+    pub fn retry_delay(self, delay: Duration) -> Self {
+        self.retry_policy(Backoff::fixed(delay))
+    }
+
+    /// Decides when to connect again after consecutive failed attempts. The
+    /// supervisor stops when the policy gives up, which closes the stream.
+    /// Lost connections are reconnected immediately.
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Tunnels RTSP through HTTP on the given port, requests still use the RTSP url
+    pub fn http_tunnel(mut self, port: u16) -> Self {
+        self.http_tunnel = Some(port);
+        self
+    }
+
+    /// Timeout and local address of the connections
+    pub fn connect_options(mut self, options: ConnectOptions) -> Self {
+        self.connect_options = options;
+        self
+    }
+
+    /// Opens the connections with `connector` instead of picking the
+    /// transport by the url scheme, the TLS config, HTTP tunnel and connect
+    /// options are left to it
+    pub fn connector(mut self, connector: impl Connector + 'static) -> Self {
+        self.connector = Some(Arc::new(connector));
+        self
+    }
+
+    /// Queries the server with OPTIONS on every connection, so its
+    /// [`ServerInfo`] includes the supported methods. The Server header and
+    /// SDP tool are recorded either way.
+    pub fn fingerprint(mut self) -> Self {
+        self.fingerprint = true;
+        self
+    }
+
+    /// Follows up to `max` redirects (301, 302 and 303) to other urls per
+    /// connection, e.g. of load balancing servers. Every connection starts
+    /// at the url given to the supervisor, since the server redirected to
+    /// may not be there anymore.
+    pub fn max_redirects(mut self, max: u32) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Publishes the reception quality of the tracks of every connection
+    /// to `quality`, the tracks are on the RTP channels 0, 2, 4 and so on
+    pub fn share_quality(mut self, quality: QualityHandle) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Range of the first PLAY, e.g. a `clock` range of a recording,
+    /// instead of the server's default
+    pub fn range(self, range: Range) -> Self {
+        self.playback.lock().unwrap().range = Some(range);
+        self
+    }
+
+    /// Playback speed of the first PLAY, servers of recordings support fast
+    /// forward or reverse playback
+    pub fn scale(self, scale: f64) -> Self {
+        self.playback.lock().unwrap().scale = Some(scale);
+        self
+    }
+
+    pub fn start(self) -> SupervisorHandle {
+        let (reconnect_tx, reconnect_rx) = mpsc::channel(1);
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        let server_info = self.server_info.clone();
+        let current_url = self.current_url.clone();
+        let playback = self.playback.clone();
+        let session_tx = self.session_tx.clone();
+        let handle = task::spawn(task::SUPERVISOR, self.run(reconnect_rx, shutdown_rx));
+        SupervisorHandle {
+            reconnect_tx,
+            shutdown_tx,
+            current_url,
+            playback,
+            session_tx,
+            server_info,
+            handle,
+        }
+    }
+
+    async fn run(self, mut reconnect_rx: mpsc::Receiver<()>, mut shutdown_rx: mpsc::Receiver<()>) {
+        let mut reconnects = 0;
+        // When the previous connection was lost, None before the first one
+        let mut lost: Option<Instant> = None;
+        let mut failures = 0;
+        loop {
+            let result = tokio::select! {
+                result = self.play() => result,
+                _ = shutdown_rx.recv() => return,
+            };
+            let mut playing = match result {
+                Ok(playing) => playing,
+                Err(e) => {
+                    failures += 1;
+                    let Some(delay) = self.retry_policy.delay(failures) else {
+                        log::error!(
+                            "Failed to play {}: {}, giving up after {} attempts",
+                            self.url,
+                            e,
+                            failures
+                        );
+                        return;
+                    };
+                    log::warn!("Failed to play {}: {}, retrying in {:?}", self.url, e, delay);
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = self.item_tx.closed() => return,
+                        _ = shutdown_rx.recv() => return,
+                    }
+                    continue;
+                }
+            };
+            failures = 0;
+            if let Some(lost) = lost.take() {
+                reconnects += 1;
+                let discontinuity = Discontinuity {
+                    reconnects,
+                    downtime: lost.elapsed(),
+                };
+                if self
+                    .item_tx
+                    .send(StreamItem::Discontinuity(discontinuity))
+                    .await
+                    .is_err()
+                {
+                    playing.handle.abort();
+                    return;
+                }
+            }
+            let handle = playing.handle.abort_handle();
+            *self.session_tx.lock().unwrap() = Some(playing.cmd_tx.clone());
+            let ended = self.forward(&mut playing, &mut reconnect_rx, &mut shutdown_rx).await;
+            self.session_tx.lock().unwrap().take();
+            match ended {
+                Ended::ConnectionLost => log::warn!("Lost connection to {}, reconnecting", self.url),
+                Ended::ReconnectRequested => log::info!("Reconnecting to {}", self.url),
+                Ended::ShutdownRequested => return playing.teardown().await,
+                Ended::ConsumerGone => return handle.abort(),
+            }
+            handle.abort();
+            lost = Some(Instant::now());
+            reconnect_rx.try_recv().ok();
+        }
+    }
+
+    async fn forward(
+        &self,
+        playing: &mut Playing,
+        reconnect_rx: &mut mpsc::Receiver<()>,
+        shutdown_rx: &mut mpsc::Receiver<()>,
+    ) -> Ended {
+        loop {
+            tokio::select! {
+                packet = playing.packet_rx.recv() => {
+                    let Some(packet) = packet else {
+                        return Ended::ConnectionLost;
+                    };
+                    if self.item_tx.send(StreamItem::Packet(packet)).await.is_err() {
+                        return Ended::ConsumerGone;
+                    }
+                }
+                Some(()) = reconnect_rx.recv() => return Ended::ReconnectRequested,
+                Some(()) = shutdown_rx.recv() => return Ended::ShutdownRequested,
+                _ = self.item_tx.closed() => return Ended::ConsumerGone,
+            }
+        }
+    }
+
+    /// Plays the url, following redirects to other urls
+    async fn play(&self) -> Result<Playing> {
+        let mut url = self.url.clone();
+        let mut redirects = 0;
+        loop {
+            match self.play_url(&url).await {
+                Err(Error::Command(CommandError::Redirected(location))) => {
+                    if redirects == self.max_redirects {
+                        return Err(Error::TooManyRedirects(location));
+                    }
+                    redirects += 1;
+                    log::info!("{} redirected to {}", url, location);
+                    url = location;
+                }
+                Ok(playing) => {
+                    *self.current_url.lock().unwrap() = url;
+                    return Ok(playing);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Connects and runs DESCRIBE, SETUP of every track and PLAY
+    async fn play_url(&self, url: &Url) -> Result<Playing> {
+        let stream = match (&self.connector, self.http_tunnel) {
+            (Some(connector), _) => connector.connect(url).await?,
+            (None, tunnel) => {
+                let mut connector = DefaultConnector::new(self.tls.clone()).options(self.connect_options);
+                if let Some(port) = tunnel {
+                    connector = connector.http_tunnel(port);
+                }
+                connector.connect(url).await?
+            }
+        };
+        // Interleaved media needs a stream connection
+        if stream.is_datagram() {
+            return Err(ConnectionError::UnsupportedScheme(url.scheme().to_string()).into());
+        }
+        let url = &tunneled_url(url);
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, packet_rx) = mpsc::channel(1024);
+        let mut channel = Channel::new(stream, cmd_rx, packet_tx)
+            .name(task::SUPERVISOR)
+            .pass(&self.pass)
+            .share_server_info(self.server_info.clone());
+        if let Some(user) = &self.user {
+            channel = channel.user(user);
+        }
+        if let Some(quality) = &self.quality {
+            channel = channel.share_quality(quality.clone());
+        }
+        let handle = channel.start();
+        match self.negotiate(&cmd_tx, url).await {
+            Ok(()) => Ok(Playing {
+                handle,
+                cmd_tx,
+                packet_rx,
+                url: url.clone(),
+            }),
+            Err(e) => {
+                handle.abort();
+                Err(e)
+            }
+        }
+    }
+
+    async fn negotiate(&self, cmd_tx: &mpsc::Sender<Command>, base: &Url) -> Result<()> {
+        if self.fingerprint {
+            let url = base.clone();
+            // Servers without OPTIONS can still play
+            if let Err(e) = request(cmd_tx, |tx| Request::Options(Options::new(url, tx))).await {
+                log::debug!("OPTIONS of {} failed: {}", base, e);
+            }
+            let info = self.server_info.snapshot();
+            log::info!(
+                "Server of {}: {} ({:?})",
+                base,
+                info.server.as_deref().unwrap_or("unknown"),
+                info.vendor
+            );
+        }
+        let url = base.clone();
+        let sdp = request(cmd_tx, |tx| Request::Describe(Describe::new(url, tx)))
+            .await?
+            .into_sdp()?;
+        let controls = sdp.media.iter().filter_map(|m| m.control());
+        for (channel, control) in (0..=u8::MAX).step_by(2).zip(controls) {
+            let url = control_url(base, control);
+            let transport = Transport::tcp((channel, channel + 1));
+            request(cmd_tx, |tx| Request::Setup(Setup::new(url, transport, tx))).await?;
+        }
+        let url = base.clone();
+        let playback = *self.playback.lock().unwrap();
+        request(cmd_tx, |tx| playback.play(url, tx)).await?;
+        Ok(())
+    }
+}
+
+async fn request<T>(
+    cmd_tx: &mpsc::Sender<Command>,
+    request: impl FnOnce(oneshot::Sender<CommandResult<T>>) -> Request,
+) -> Result<T> {
+    let (tx, rx) = oneshot::channel();
+    cmd_tx
+        .send(Command::Request(request(tx)))
+        .await
+        .map_err(|_| Error::ChannelClosed)?;
+    Ok(rx.await.map_err(|_| Error::ChannelClosed)??)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::server::Server;
+    use crate::rtsp::{Method, Status};
+    use crate::testing::{test_packet, MockResponse, MockServer, TestSource, TEST_SDP};
+    use tokio::sync::broadcast;
+
+    /// Sends packets until one arrives, the source has no subscriber until PLAY was handled
+    async fn next_packet(
+        tx: &broadcast::Sender<rtp::Packet>,
+        rx: &mut mpsc::Receiver<StreamItem>,
+        seq: u16,
+    ) -> StreamItem {
+        loop {
+            let _ = tx.send(test_packet(seq));
+            if let Ok(item) = tokio::time::timeout(Duration::from_millis(20), rx.recv()).await {
+                return item.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_datagram() {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("rtspu://{}/live", socket.local_addr().unwrap())).unwrap();
+        let (item_tx, _item_rx) = mpsc::channel(16);
+        let result = Supervisor::new(url, TlsConfig::new(), item_tx).play().await;
+        assert!(matches!(
+            result,
+            Err(Error::Connection(ConnectionError::UnsupportedScheme(scheme))) if scheme == "rtspu"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_reconnect() {
+        let (tx, _) = broadcast::channel(16);
+        let server = Server::bind("127.0.0.1:0", TestSource::new(tx.clone())).await.unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", server.local_addr().unwrap())).unwrap();
+        server.start();
+
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let supervisor = Supervisor::new(url, TlsConfig::new(), item_tx).fingerprint().start();
+        assert_eq!(
+            next_packet(&tx, &mut item_rx, 1).await,
+            StreamItem::Packet(test_packet(1))
+        );
+        assert!(supervisor.server_info().supports(Method::Play));
+
+        supervisor.reconnect();
+        // Packets of the old connection may still be queued
+        let discontinuity = loop {
+            match next_packet(&tx, &mut item_rx, 2).await {
+                StreamItem::Discontinuity(d) => break d,
+                StreamItem::Packet(_) => {}
+            }
+        };
+        assert_eq!(discontinuity.reconnects, 1);
+        // The new connection delivers into the same receiver
+        while next_packet(&tx, &mut item_rx, 3).await != StreamItem::Packet(test_packet(3)) {}
+
+        drop(item_rx);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !supervisor.is_finished() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    /// Connects to a fixed address whatever the url says, like a proxy
+    struct FixedConnector(std::net::SocketAddr);
+
+    impl Connector for FixedConnector {
+        fn connect<'a>(&'a self, _url: &'a Url) -> ConnectFuture<'a> {
+            Box::pin(async move {
+                let stream = tokio::net::TcpStream::connect(self.0).await?;
+                Ok(Connection::Custom(Box::new(stream)))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_connector() {
+        let (tx, _) = broadcast::channel(16);
+        let server = Server::bind("127.0.0.1:0", TestSource::new(tx.clone())).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        server.start();
+
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let url = Url::parse("rtsp://camera.invalid/live").unwrap();
+        let _supervisor = Supervisor::new(url, TlsConfig::new(), item_tx)
+            .connector(FixedConnector(addr))
+            .start();
+        assert_eq!(
+            next_packet(&tx, &mut item_rx, 1).await,
+            StreamItem::Packet(test_packet(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_redirect() {
+        let (tx, _) = broadcast::channel(16);
+        let server = Server::bind("127.0.0.1:0", TestSource::new(tx.clone())).await.unwrap();
+        let location = format!("rtsp://{}/live", server.local_addr().unwrap());
+        server.start();
+        let redirect = MockResponse::new(Status::MovedTemporarily).header("Location", &location);
+        let (addr, balancer) = MockServer::new()
+            .expect(Method::Describe, redirect)
+            .listen()
+            .await
+            .unwrap();
+
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let url = Url::parse(&format!("rtsp://{}/live", addr)).unwrap();
+        let supervisor = Supervisor::new(url.clone(), TlsConfig::new(), item_tx).start();
+        assert_eq!(supervisor.url(), url);
+        assert_eq!(
+            next_packet(&tx, &mut item_rx, 1).await,
+            StreamItem::Packet(test_packet(1))
+        );
+        assert_eq!(supervisor.url().as_str(), location);
+        assert_eq!(balancer.await.unwrap().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_too_many_redirects() {
+        let redirect = MockResponse::new(Status::MovedPermanently).header("Location", "/other");
+        let (addr, _balancer) = MockServer::new()
+            .expect(Method::Describe, redirect)
+            .listen()
+            .await
+            .unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", addr)).unwrap();
+        let (item_tx, _item_rx) = mpsc::channel(16);
+        let supervisor = Supervisor::new(url.clone(), TlsConfig::new(), item_tx).max_redirects(0);
+        match supervisor.play().await {
+            Err(Error::TooManyRedirects(location)) => assert_eq!(location, url.join("/other").unwrap()),
+            _ => panic!("expected too many redirects"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_shutdown() {
+        let setup = MockResponse::ok()
+            .header("Session", "12345678")
+            .header("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1");
+        let (addr, server) = MockServer::new()
+            .expect(Method::Describe, MockResponse::ok().sdp(TEST_SDP))
+            .expect(Method::Setup, setup)
+            .expect(Method::Play, MockResponse::ok())
+            .interleaved(0, test_packet(1).as_bytes())
+            .expect(Method::Teardown, MockResponse::ok())
+            .listen()
+            .await
+            .unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", addr)).unwrap();
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let supervisor = Supervisor::new(url, TlsConfig::new(), item_tx).start();
+        assert_eq!(item_rx.recv().await, Some(StreamItem::Packet(test_packet(1))));
+        supervisor.shutdown().await;
+        assert_eq!(item_rx.recv().await, None);
+        let requests = server.await.unwrap().unwrap();
+        assert_eq!(requests.last().unwrap().method, Some(Method::Teardown));
+        assert!(requests.last().unwrap().header("Session").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_seek() {
+        let setup = MockResponse::ok()
+            .header("Session", "12345678")
+            .header("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1");
+        let seeked = MockResponse::ok()
+            .header("Range", "clock=20240501T080000Z-")
+            .header("Scale", "4.0");
+        let (addr, server) = MockServer::new()
+            .expect(Method::Describe, MockResponse::ok().sdp(TEST_SDP))
+            .expect(Method::Setup, setup)
+            .expect(Method::Play, MockResponse::ok().header("Range", "npt=0-"))
+            .interleaved(0, test_packet(1).as_bytes())
+            .expect(Method::Play, seeked)
+            .interleaved(0, test_packet(2).as_bytes())
+            .expect(Method::Teardown, MockResponse::ok())
+            .listen()
+            .await
+            .unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", addr)).unwrap();
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let supervisor = Supervisor::new(url, TlsConfig::new(), item_tx)
+            .range(Range::npt(Duration::ZERO))
+            .start();
+        assert_eq!(item_rx.recv().await, Some(StreamItem::Packet(test_packet(1))));
+
+        let start = std::time::UNIX_EPOCH + Duration::from_secs(1_714_550_400);
+        let response = supervisor.seek(Range::clock(start, None), Some(4.0)).await.unwrap();
+        assert_eq!(response.range, Some(Range::clock(start, None)));
+        assert_eq!(response.scale, Some(4.0));
+        // The stream continues on the same connection
+        assert_eq!(item_rx.recv().await, Some(StreamItem::Packet(test_packet(2))));
+        supervisor.shutdown().await;
+
+        let requests = server.await.unwrap().unwrap();
+        let plays: Vec<_> = requests.iter().filter(|r| r.method == Some(Method::Play)).collect();
+        assert_eq!(plays[0].header("Range"), Some("npt=0.000-"));
+        assert_eq!(plays[1].header("Range"), Some("clock=20240501T080000Z-"));
+        assert_eq!(plays[1].header("Scale"), Some("4"));
+        assert_eq!(requests.last().unwrap().method, Some(Method::Teardown));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_supervisor_retry_policy() {
+        // Nothing listens on the port of a dropped listener
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("rtsp://{}/live", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+
+        let (item_tx, mut item_rx) = mpsc::channel(16);
+        let start = Instant::now();
+        let _supervisor = Supervisor::new(url, TlsConfig::new(), item_tx)
+            .retry_policy(Backoff::new(Duration::from_secs(1)).max_retries(2))
+            .start();
+        // Gives up after waiting 1s and 2s, which ends the stream
+        assert_eq!(item_rx.recv().await, None);
+        assert!(start.elapsed() >= Duration::from_secs(3));
+    }
+}
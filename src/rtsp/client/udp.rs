@@ -0,0 +1,195 @@
+use super::{MtuDetector, MtuIssue, Traffic, UsageMeter};
+use crate::rtp;
+use crate::srtp;
+use crate::task;
+use bytes::BytesMut;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+
+/// Binds a RTP/RTCP socket pair on consecutive ports, RTP on the even one.
+pub async fn bind_pair(ip: IpAddr) -> io::Result<(UdpSocket, UdpSocket)> {
+    for _ in 0..16 {
+        let rtp = UdpSocket::bind(SocketAddr::new(ip, 0)).await?;
+        let port = rtp.local_addr()?.port();
+        if !port.is_multiple_of(2) || port == u16::MAX {
+            continue;
+        }
+        if let Ok(rtcp) = UdpSocket::bind(SocketAddr::new(ip, port + 1)).await {
+            return Ok((rtp, rtcp));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AddrInUse, "No free RTP/RTCP port pair"))
+}
+
+/// Receives RTP datagrams of one track and forwards them like the
+/// interleaved packets of the channel.
+pub struct UdpReceiver {
+    socket: UdpSocket,
+    packet_tx: mpsc::Sender<rtp::Packet>,
+    detector: MtuDetector,
+    mtu_tx: Option<mpsc::Sender<MtuIssue>>,
+    usage: Option<UsageMeter>,
+    srtp: Option<srtp::SrtpContext>,
+}
+
+impl UdpReceiver {
+    pub fn new(socket: UdpSocket, packet_tx: mpsc::Sender<rtp::Packet>) -> Self {
+        Self {
+            socket,
+            packet_tx,
+            detector: MtuDetector::new(),
+            mtu_tx: None,
+            usage: None,
+            srtp: None,
+        }
+    }
+
+    /// Reports a probable path MTU problem, see [`MtuDetector`]
+    pub fn mtu_issues(mut self, tx: mpsc::Sender<MtuIssue>) -> Self {
+        self.mtu_tx = Some(tx);
+        self
+    }
+
+    /// Accounts the received datagrams, usually to the channel's meter
+    pub fn usage(mut self, usage: UsageMeter) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Authenticates and decrypts the datagrams as SRTP, failing ones are dropped
+    pub fn srtp(mut self, ctx: srtp::SrtpContext) -> Self {
+        self.srtp = Some(ctx);
+        self
+    }
+
+    async fn run(mut self) {
+        let mut buf = BytesMut::new();
+        loop {
+            // Datagrams are split off, so the memory is only reused once they are dropped
+            buf.reserve(u16::MAX as usize);
+            let n = match self.socket.recv_buf(&mut buf).await {
+                Ok(n) => n,
+                Err(e) => {
+                    log::error!("Error receiving RTP datagram: {}", e);
+                    break;
+                }
+            };
+            if let Some(usage) = &self.usage {
+                usage.received(Traffic::Rtp, n);
+            }
+            let mut datagram = buf.split().freeze();
+            if let Some(ctx) = &mut self.srtp {
+                match ctx.unprotect_rtp(&datagram) {
+                    Ok(packet) => datagram = packet.into(),
+                    Err(e) => {
+                        log::warn!("Dropping SRTP datagram: {}", e);
+                        continue;
+                    }
+                }
+            }
+            let packet = match rtp::Packet::new(datagram) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    log::warn!("Invalid RTP datagram: {}", e);
+                    continue;
+                }
+            };
+            if let Some(issue) = self.detector.observe(&packet, n) {
+                log::warn!(
+                    "Probable MTU issue, {:.1}% loss inside fragmented frames, largest datagram {} bytes",
+                    issue.loss_ratio * 100.0,
+                    issue.largest_received
+                );
+                if let Some(tx) = &self.mtu_tx {
+                    let _ = tx.try_send(issue);
+                }
+            }
+            if self.packet_tx.send(packet).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    pub fn start(self) -> tokio::task::JoinHandle<()> {
+        task::spawn(task::UDP_RX, self.run())
+    }
+}
+
+/// Sends the RTP packets of one outgoing track over UDP, e.g. of an ONVIF
+/// backchannel set up with a UDP transport, to the server port of SETUP.
+pub struct UdpSender {
+    socket: UdpSocket,
+    dest: SocketAddr,
+    usage: Option<UsageMeter>,
+}
+
+impl UdpSender {
+    pub fn new(socket: UdpSocket, dest: SocketAddr) -> Self {
+        Self {
+            socket,
+            dest,
+            usage: None,
+        }
+    }
+
+    /// Accounts the sent datagrams, usually to the channel's meter
+    pub fn usage(mut self, usage: UsageMeter) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    pub async fn send(&self, packet: &rtp::Packet) -> io::Result<()> {
+        let n = self.socket.send_to(packet.as_bytes(), self.dest).await?;
+        if let Some(usage) = &self.usage {
+            usage.sent(Traffic::Rtp, n);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[tokio::test]
+    async fn test_bind_pair() {
+        let (rtp, rtcp) = bind_pair(IpAddr::V4(Ipv4Addr::LOCALHOST)).await.unwrap();
+        let port = rtp.local_addr().unwrap().port();
+        assert!(port.is_multiple_of(2));
+        assert_eq!(rtcp.local_addr().unwrap().port(), port + 1);
+    }
+
+    #[tokio::test]
+    async fn test_udp_receiver() {
+        let (rtp, _rtcp) = bind_pair(IpAddr::V4(Ipv4Addr::LOCALHOST)).await.unwrap();
+        let addr = rtp.local_addr().unwrap();
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let usage = UsageMeter::new();
+        let handle = UdpReceiver::new(rtp, packet_tx).usage(usage.clone()).start();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender
+            .send_to(&[0x80, 0x60, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0], addr)
+            .await
+            .unwrap();
+        assert_eq!(packet_rx.recv().await.unwrap().sequence_number(), 9);
+        assert_eq!(usage.snapshot().received.rtp.bytes, 12);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_udp_sender() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let usage = UsageMeter::new();
+        let sender = UdpSender::new(socket, receiver.local_addr().unwrap()).usage(usage.clone());
+        let packet = rtp::PacketBuilder::new(0, 1).build(&[0xd5; 4], 0, false);
+        sender.send(&packet).await.unwrap();
+        let mut buf = [0u8; 32];
+        let n = receiver.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], packet.as_bytes());
+        assert_eq!(usage.snapshot().sent.rtp.bytes, 16);
+    }
+}
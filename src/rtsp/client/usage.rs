@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+/// Bytes and packets transferred over one kind of traffic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Counter {
+    pub bytes: u64,
+    pub packets: u64,
+}
+
+impl Counter {
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+        self.packets += 1;
+    }
+}
+
+/// Traffic of one direction, split by RTSP control messages, RTP and RTCP.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirectionUsage {
+    pub control: Counter,
+    pub rtp: Counter,
+    pub rtcp: Counter,
+}
+
+impl DirectionUsage {
+    pub fn total(&self) -> Counter {
+        Counter {
+            bytes: self.control.bytes + self.rtp.bytes + self.rtcp.bytes,
+            packets: self.control.packets + self.rtp.packets + self.rtcp.packets,
+        }
+    }
+}
+
+/// Cumulative traffic of a session. Byte counts cover what went over the
+/// wire, including the interleaved frame headers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Usage {
+    pub sent: DirectionUsage,
+    pub received: DirectionUsage,
+    /// Outgoing interleaved frames dropped because too many were waiting
+    /// for room in the send buffer
+    pub dropped: Counter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traffic {
+    Control,
+    Rtp,
+    Rtcp,
+}
+
+/// Shared handle to the usage counters of a channel, cheap to clone and
+/// readable while the channel is running.
+#[derive(Debug, Default, Clone)]
+pub struct UsageMeter {
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl UsageMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> Usage {
+        *self.usage.lock().unwrap()
+    }
+
+    pub fn reset(&self) {
+        *self.usage.lock().unwrap() = Usage::default();
+    }
+
+    pub(crate) fn sent(&self, traffic: Traffic, bytes: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        Self::counter(&mut usage.sent, traffic).add(bytes);
+    }
+
+    pub(crate) fn received(&self, traffic: Traffic, bytes: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        Self::counter(&mut usage.received, traffic).add(bytes);
+    }
+
+    pub(crate) fn dropped(&self, bytes: usize) {
+        self.usage.lock().unwrap().dropped.add(bytes);
+    }
+
+    fn counter(direction: &mut DirectionUsage, traffic: Traffic) -> &mut Counter {
+        match traffic {
+            Traffic::Control => &mut direction.control,
+            Traffic::Rtp => &mut direction.rtp,
+            Traffic::Rtcp => &mut direction.rtcp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_meter() {
+        let meter = UsageMeter::new();
+        let shared = meter.clone();
+        meter.sent(Traffic::Control, 100);
+        meter.received(Traffic::Control, 200);
+        meter.received(Traffic::Rtp, 1000);
+        meter.received(Traffic::Rtp, 500);
+        meter.received(Traffic::Rtcp, 50);
+        let usage = shared.snapshot();
+        assert_eq!(usage.sent.control, Counter { bytes: 100, packets: 1 });
+        assert_eq!(
+            usage.received.rtp,
+            Counter {
+                bytes: 1500,
+                packets: 2
+            }
+        );
+        assert_eq!(
+            usage.received.total(),
+            Counter {
+                bytes: 1750,
+                packets: 4
+            }
+        );
+        shared.reset();
+        assert_eq!(meter.snapshot(), Usage::default());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::rtsp::{ContentType, Header, LowerTransport, Method, Range, Session, Status, Transport};
+use thiserror::Error;
+
+/// A deviation of a response from RFC 7826, found by a [`Channel`](super::Channel)
+/// in strict mode, see [`Channel::strict`](super::Channel::strict). The
+/// response is still processed, e.g. a camera that omits the Range of a
+/// PLAY response keeps playing.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Violation {
+    /// A header the response must carry
+    #[error("Missing header {0}")]
+    MissingHeader(&'static str),
+    /// A header that doesn't follow its grammar
+    #[error("Invalid header {name}: {value}")]
+    InvalidHeader { name: &'static str, value: String },
+    /// The Transport of a SETUP response isn't a single valid choice of
+    /// the requested ones
+    #[error("Invalid transport: {0}")]
+    InvalidTransport(&'static str),
+    /// A DESCRIBE response without a description
+    #[error("Missing body")]
+    MissingBody,
+}
+
+fn find<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value)
+}
+
+/// Headers a response with `status` to `method` must carry
+fn mandatory_headers(method: Method, status: Status) -> &'static [&'static str] {
+    match (status, method) {
+        (Status::OK, Method::Options) => &["Public"],
+        (Status::OK, Method::Describe) => &["Content-Type", "Content-Length"],
+        (Status::OK, Method::Setup) => &["Session", "Transport"],
+        (Status::OK, Method::Play) => &["Session", "Range"],
+        (Status::OK, Method::Pause) => &["Session"],
+        (Status::MovedPermanently | Status::MovedTemporarily | Status::SeeOther | Status::UseProxy, _) => &["Location"],
+        (Status::Unauthorized, _) => &["WWW-Authenticate"],
+        (Status::ProxyAuthenticationRequired, _) => &["Proxy-Authenticate"],
+        (Status::MethodNotAllowed, _) => &["Allow"],
+        _ => &[],
+    }
+}
+
+/// Whether a Range header follows RFC 7826 18.40. Only npt and clock
+/// times are parsed, the ranges of other units, e.g. smpte, just need a
+/// unit and a start or end.
+fn is_valid_range(value: &str) -> bool {
+    let range = value.split(';').next().unwrap_or_default().trim();
+    let Some((unit, times)) = range.split_once('=') else {
+        return false;
+    };
+    let unit = unit.trim();
+    if unit.eq_ignore_ascii_case("npt") || unit.eq_ignore_ascii_case("clock") {
+        return value.parse::<Range>().is_ok();
+    }
+    let is_token = !unit.is_empty() && unit.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+    is_token && times.contains('-')
+}
+
+/// The transport chosen by a SETUP response, `requested` is the one the
+/// client offered
+fn validate_transport(value: &str, requested: Option<&Transport>, violations: &mut Vec<Violation>) {
+    if value.contains(',') {
+        violations.push(Violation::InvalidTransport("more than one transport specification"));
+    }
+    let Ok(transport) = value.parse::<Transport>() else {
+        violations.push(Violation::InvalidHeader {
+            name: "Transport",
+            value: value.to_string(),
+        });
+        return;
+    };
+    match (transport.lower, transport.interleaved) {
+        (LowerTransport::Tcp, None) => violations.push(Violation::InvalidTransport("TCP without interleaved")),
+        (LowerTransport::Udp, Some(_)) => violations.push(Violation::InvalidTransport("UDP with interleaved")),
+        _ => {}
+    }
+    if transport.multicast && transport.lower == LowerTransport::Tcp {
+        violations.push(Violation::InvalidTransport("multicast over TCP"));
+    }
+    if let Some(requested) = requested {
+        if requested.lower != transport.lower || requested.profile != transport.profile {
+            violations.push(Violation::InvalidTransport("not the requested protocol"));
+        }
+    }
+}
+
+/// Checks a response with `status` to `method` against RFC 7826: the
+/// mandatory headers of the method and status, the grammar of the Session,
+/// Range, Content-Type and Transport headers, and the transport a SETUP
+/// response chose from `requested`. An empty result means no violation was
+/// found.
+pub fn validate_response(
+    method: Method,
+    status: Status,
+    requested: Option<&Transport>,
+    headers: &[Header],
+    body: Option<&[u8]>,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    // Every response carries a Date, RFC 7826 18.21
+    for name in std::iter::once(&"Date").chain(mandatory_headers(method, status)) {
+        if find(headers, name).is_none() {
+            violations.push(Violation::MissingHeader(name));
+        }
+    }
+    if let Some(value) = find(headers, "Session") {
+        if value.parse::<Session>().is_err() {
+            violations.push(Violation::InvalidHeader {
+                name: "Session",
+                value: value.to_string(),
+            });
+        }
+    }
+    if let Some(value) = find(headers, "Range") {
+        if !is_valid_range(value) {
+            violations.push(Violation::InvalidHeader {
+                name: "Range",
+                value: value.to_string(),
+            });
+        }
+    }
+    if let Some(value) = find(headers, "Content-Type") {
+        if value.parse::<ContentType>().is_err() {
+            violations.push(Violation::InvalidHeader {
+                name: "Content-Type",
+                value: value.to_string(),
+            });
+        }
+    }
+    if status == Status::OK && method == Method::Setup {
+        if let Some(value) = find(headers, "Transport") {
+            validate_transport(value, requested, &mut violations);
+        }
+    }
+    if status == Status::OK && method == Method::Describe && body.is_none_or(<[u8]>::is_empty) {
+        violations.push(Violation::MissingBody);
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATE: &str = "Wed, 01 May 2024 08:00:00 GMT";
+
+    #[test]
+    fn test_validate_response() {
+        let headers = [
+            Header::new("Date", DATE),
+            Header::new("Session", "12345678;timeout=60"),
+            Header::new("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1"),
+        ];
+        let requested = Transport::tcp((0, 1));
+        let violations = validate_response(Method::Setup, Status::OK, Some(&requested), &headers, None);
+        assert_eq!(violations, []);
+
+        let headers = [Header::new("Session", "12345678;timeout=")];
+        assert_eq!(
+            validate_response(Method::Play, Status::OK, None, &headers, None),
+            [
+                Violation::MissingHeader("Date"),
+                Violation::MissingHeader("Range"),
+                Violation::InvalidHeader {
+                    name: "Session",
+                    value: "12345678;timeout=".to_string()
+                },
+            ]
+        );
+        let headers = [Header::new("Date", DATE)];
+        assert_eq!(
+            validate_response(Method::Describe, Status::Unauthorized, None, &headers, None),
+            [Violation::MissingHeader("WWW-Authenticate")]
+        );
+        assert!(
+            validate_response(Method::Describe, Status::OK, None, &headers, Some(b"v=0\r\n"))
+                .contains(&Violation::MissingHeader("Content-Type"))
+        );
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let play = |range| {
+            let headers = [
+                Header::new("Date", DATE),
+                Header::new("Session", "1"),
+                Header::new("Range", range),
+            ];
+            validate_response(Method::Play, Status::OK, None, &headers, None)
+        };
+        assert_eq!(play("npt=0.000-"), []);
+        assert_eq!(play("smpte=10:07:00-10:07:33:05.01"), []);
+        for range in ["npt=abc-", "smpte", "=0-", "smpte=10:07:00"] {
+            assert_eq!(
+                play(range),
+                [Violation::InvalidHeader {
+                    name: "Range",
+                    value: range.to_string()
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_transport() {
+        let requested = Transport::udp((5000, 5001));
+        let setup = |transport| {
+            let headers = [
+                Header::new("Date", DATE),
+                Header::new("Session", "1"),
+                Header::new("Transport", transport),
+            ];
+            validate_response(Method::Setup, Status::OK, Some(&requested), &headers, None)
+        };
+        assert_eq!(setup("RTP/AVP;unicast;client_port=5000-5001;server_port=6000-6001"), []);
+        assert_eq!(
+            setup("RTP/AVP/TCP;unicast"),
+            [
+                Violation::InvalidTransport("TCP without interleaved"),
+                Violation::InvalidTransport("not the requested protocol"),
+            ]
+        );
+        assert_eq!(
+            setup("RTP/AVP;unicast;interleaved=0-1,RTP/AVP;multicast"),
+            [
+                Violation::InvalidTransport("more than one transport specification"),
+                Violation::InvalidTransport("UDP with interleaved"),
+            ]
+        );
+        assert!(matches!(setup("MP2T/H2221/UDP")[..], [Violation::InvalidHeader { .. }]));
+    }
+}
@@ -1,6 +1,7 @@
 mod protocol;
 mod buffer;
 pub mod client;
+pub mod server;
 
 pub use buffer::Buffer;
 pub use buffer::BufferError;
@@ -9,7 +9,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub struct NoHeader {}
 
 impl fmt::Display for NoHeader {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
         Ok(())
     }
 }
@@ -51,8 +51,7 @@ pub struct NoUrl {}
 
 impl fmt::Display for NoUrl {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "rtsp://");
-        Ok(())
+        write!(f, "rtsp://")
     }
 }
 
@@ -87,6 +86,12 @@ impl RequestBuilder<NoUrl, NoHeader, NoBody> {
     }
 }
 
+impl Default for RequestBuilder<NoUrl, NoHeader, NoBody> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<U, H, B> RequestBuilder<U, H, B> {
     pub fn version(self, version: Version) -> Self {
         Self { version, ..self }
@@ -98,7 +103,7 @@ impl<U, H, B> RequestBuilder<U, H, B> {
 }
 
 impl<H, B> RequestBuilder<NoUrl, H, B> {
-    pub fn url<'a>(self, url: &'a Url) -> RequestBuilder<&'a Url, H, B> {
+    pub fn url(self, url: &Url) -> RequestBuilder<&Url, H, B> {
         RequestBuilder {
             method: self.method,
             url,
@@ -151,7 +156,22 @@ impl<U, H> RequestBuilder<U, H, NoBody> {
         }
     }
 
-    pub fn body<'a>(self, body: &'a str) -> RequestBuilder<U, Composite<H, Header<'static, usize>>, &'a str> {
+    /// Appends header lines that are written by `headers` as they are,
+    /// each ending with CRLF, e.g. a list of headers known at runtime
+    pub fn headers<V: fmt::Display>(self, headers: V) -> RequestBuilder<U, Composite<H, V>, NoBody> {
+        RequestBuilder {
+            method: self.method,
+            url: self.url,
+            version: self.version,
+            headers: Composite {
+                a: self.headers,
+                b: headers,
+            },
+            body: self.body,
+        }
+    }
+
+    pub fn body(self, body: &str) -> RequestBuilder<U, Composite<H, Header<'static, usize>>, &str> {
         let builder = self.header("Content-Length", body.len());
         RequestBuilder {
             method: builder.method,
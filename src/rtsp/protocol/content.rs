@@ -0,0 +1,113 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Content-Type header, e.g. `Content-Type: application/sdp; charset=utf-8`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    /// Lowercased `type/subtype`
+    pub media_type: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl ContentType {
+    pub fn is_sdp(&self) -> bool {
+        self.media_type == "application/sdp"
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.media_type)?;
+        for (name, value) in &self.params {
+            write!(f, "; {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseContentTypeError {
+    #[error("Invalid media type {0}")]
+    InvalidMediaType(String),
+}
+
+impl FromStr for ContentType {
+    type Err = ParseContentTypeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';');
+        let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        match media_type.split_once('/') {
+            Some((t, st)) if !t.is_empty() && !st.is_empty() => {}
+            _ => return Err(ParseContentTypeError::InvalidMediaType(media_type)),
+        }
+        let params = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(n, v)| (n.trim().to_string(), v.trim().trim_matches('"').to_string()))
+            .collect();
+        Ok(ContentType { media_type, params })
+    }
+}
+
+/// Content-Encoding header, only `identity` is supported by the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Other(String),
+}
+
+impl ContentEncoding {
+    pub fn is_identity(&self) -> bool {
+        *self == ContentEncoding::Identity
+    }
+}
+
+impl From<&str> for ContentEncoding {
+    fn from(s: &str) -> Self {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("identity") {
+            ContentEncoding::Identity
+        } else {
+            ContentEncoding::Other(s.to_ascii_lowercase())
+        }
+    }
+}
+
+impl fmt::Display for ContentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContentEncoding::Identity => write!(f, "identity"),
+            ContentEncoding::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content_type() {
+        let content_type: ContentType = "Application/SDP; charset=\"utf-8\"".parse().unwrap();
+        assert!(content_type.is_sdp());
+        assert_eq!(content_type.param("Charset"), Some("utf-8"));
+        assert_eq!(content_type.to_string(), "application/sdp; charset=utf-8");
+        assert!("sdp".parse::<ContentType>().is_err());
+    }
+
+    #[test]
+    fn test_content_encoding() {
+        assert!(ContentEncoding::from(" Identity ").is_identity());
+        assert_eq!(
+            ContentEncoding::from("GZIP"),
+            ContentEncoding::Other("gzip".to_string())
+        );
+    }
+}
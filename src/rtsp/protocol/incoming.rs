@@ -0,0 +1,131 @@
+use super::{Method, ParseError, ParseItem, RequestParser};
+use std::ops::Range;
+
+/// A complete request received from the peer, e.g. by the server or
+/// a request sent by a camera on the client connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingRequest {
+    /// `None` for methods the crate doesn't know
+    pub method: Option<Method>,
+    pub uri: String,
+    pub cseq: Option<u32>,
+    // Names and values of all headers, one after another so a request
+    // allocates once for them instead of twice per header
+    head: String,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    pub body: String,
+}
+
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+impl IncomingRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    /// Names and values of the headers in the order they were received
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (&self.head[name.clone()], &self.head[value.clone()]))
+    }
+
+    /// Parses a complete request at the start of `buf` and returns it along
+    /// with its size, `None` if the request isn't complete yet.
+    pub fn parse(buf: &[u8]) -> Result<Option<(IncomingRequest, usize)>, ParseError> {
+        // Parsing starts once the header is complete, errors are never caused by missing data
+        let Some(header_length) = find_header_end(buf) else {
+            return Ok(None);
+        };
+        let mut request = IncomingRequest {
+            method: None,
+            uri: String::new(),
+            cseq: None,
+            head: String::with_capacity(header_length),
+            headers: Vec::with_capacity(16),
+            body: String::new(),
+        };
+        let mut parser = RequestParser::new();
+        loop {
+            let item = match parser.parse_next(buf) {
+                Ok(Some(item)) => item,
+                Ok(None) => break,
+                // Answered with 501, the rest is parsed for the CSeq and to skip the body
+                Err(ParseError::ParseMethod(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            match item {
+                ParseItem::Method(method) => request.method = Some(method),
+                ParseItem::Uri(uri) => request.uri = uri.to_string(),
+                ParseItem::Header(header) => {
+                    if header.name.eq_ignore_ascii_case("cseq") {
+                        request.cseq = header.value.parse().ok();
+                    }
+                    let name = request.head.len()..request.head.len() + header.name.len();
+                    request.head.push_str(header.name);
+                    let value = request.head.len()..request.head.len() + header.value.len();
+                    request.head.push_str(header.value);
+                    request.headers.push((name, value));
+                }
+                ParseItem::Body(body) => request.body = std::str::from_utf8(body)?.to_string(),
+                _ => {}
+            }
+        }
+        if !parser.is_done() {
+            return Ok(None);
+        }
+        Ok(Some((request, parser.parsed_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let buf =
+            b"SETUP rtsp://host/live/trackID=0 RTSP/1.0\r\nCSeq: 3\r\nTransport: RTP/AVP/TCP;interleaved=0-1\r\n\r\n$";
+        let (request, n) = IncomingRequest::parse(buf).unwrap().unwrap();
+        assert_eq!(n, buf.len() - 1);
+        assert_eq!(request.method, Some(Method::Setup));
+        assert_eq!(request.uri, "rtsp://host/live/trackID=0");
+        assert_eq!(request.cseq, Some(3));
+        assert_eq!(request.header("transport"), Some("RTP/AVP/TCP;interleaved=0-1"));
+        assert_eq!(
+            request.headers().collect::<Vec<_>>(),
+            [("CSeq", "3"), ("Transport", "RTP/AVP/TCP;interleaved=0-1")]
+        );
+    }
+
+    #[test]
+    fn test_parse_incomplete_request() {
+        assert!(IncomingRequest::parse(b"OPTIONS * RTSP/1.0\r\nCSeq: 1\r\n")
+            .unwrap()
+            .is_none());
+        let buf = b"SET_PARAMETER rtsp://host RTSP/1.0\r\nContent-Length: 5\r\n\r\nab";
+        assert!(IncomingRequest::parse(buf).unwrap().is_none());
+        assert!(IncomingRequest::parse(b"OPTIONS *\r\n\r\n").is_err());
+        let (request, n) = IncomingRequest::parse(b"FETCH * RTSP/1.0\r\nCSeq: 1\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.method, None);
+        assert_eq!(n, 29);
+    }
+
+    #[test]
+    fn test_parse_unknown_method() {
+        let buf = b"FETCH * RTSP/1.0\r\nCSeq: 4\r\nContent-Length: 5\r\n\r\nabc";
+        assert!(IncomingRequest::parse(buf).unwrap().is_none());
+        let buf = b"FETCH * RTSP/1.0\r\nCSeq: 4\r\nContent-Length: 5\r\n\r\nabcdeOPTIONS";
+        let (request, n) = IncomingRequest::parse(buf).unwrap().unwrap();
+        assert_eq!(request.method, None);
+        assert_eq!(request.cseq, Some(4));
+        assert_eq!(request.body, "abcde");
+        assert_eq!(n, buf.len() - 7);
+    }
+}
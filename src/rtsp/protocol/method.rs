@@ -2,13 +2,19 @@ use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Method {
     Options,
     Describe,
+    Announce,
     Setup,
     Play,
+    Pause,
+    Record,
     Teardown,
+    GetParameter,
+    SetParameter,
+    Redirect,
 }
 
 impl Method {
@@ -16,22 +22,30 @@ impl Method {
         match self {
             Method::Options => "OPTIONS",
             Method::Describe => "DESCRIBE",
+            Method::Announce => "ANNOUNCE",
             Method::Setup => "SETUP",
             Method::Play => "PLAY",
+            Method::Pause => "PAUSE",
+            Method::Record => "RECORD",
             Method::Teardown => "TEARDOWN",
+            Method::GetParameter => "GET_PARAMETER",
+            Method::SetParameter => "SET_PARAMETER",
+            Method::Redirect => "REDIRECT",
         }
     }
+
+    /// Methods that can be sent again without changing the server state
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::Options | Method::Describe | Method::GetParameter | Method::Teardown
+        )
+    }
 }
 
 impl fmt::Display for Method {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Method::Options => write!(f, "OPTIONS"),
-            Method::Describe => write!(f, "DESCRIBE"),
-            Method::Setup => write!(f, "SETUP"),
-            Method::Play => write!(f, "PLAY"),
-            Method::Teardown => write!(f, "TEARDOWN"),
-        }
+        write!(f, "{}", self.as_str())
     }
 }
 
@@ -46,10 +60,56 @@ impl FromStr for Method {
         match s {
             "OPTIONS" => Ok(Method::Options),
             "DESCRIBE" => Ok(Method::Describe),
+            "ANNOUNCE" => Ok(Method::Announce),
             "SETUP" => Ok(Method::Setup),
             "PLAY" => Ok(Method::Play),
+            "PAUSE" => Ok(Method::Pause),
+            "RECORD" => Ok(Method::Record),
             "TEARDOWN" => Ok(Method::Teardown),
+            "GET_PARAMETER" => Ok(Method::GetParameter),
+            "SET_PARAMETER" => Ok(Method::SetParameter),
+            "REDIRECT" => Ok(Method::Redirect),
             _ => Err(ParseMethodError::InvalidMethod),
         }
     }
 }
+
+/// Serialized as the method name, e.g. `"GET_PARAMETER"`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Method {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Method {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!("GET_PARAMETER".parse::<Method>().unwrap(), Method::GetParameter);
+        assert_eq!(Method::SetParameter.to_string(), "SET_PARAMETER");
+        assert!("get_parameter".parse::<Method>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_method_serde() {
+        assert_eq!(
+            serde_json::to_string(&Method::GetParameter).unwrap(),
+            "\"GET_PARAMETER\""
+        );
+        assert_eq!(serde_json::from_str::<Method>("\"PLAY\"").unwrap(), Method::Play);
+        assert!(serde_json::from_str::<Method>("\"play\"").is_err());
+    }
+}
@@ -3,6 +3,13 @@ mod protocol;
 mod status;
 mod parser;
 mod builder;
+mod session;
+mod content;
+mod transport;
+mod incoming;
+mod response;
+mod range;
+mod via;
 
 pub use crate::http::Header;
 pub use crate::http::ParseHeaderError;
@@ -15,6 +22,7 @@ pub use protocol::Protocol;
 pub use status::ParseStatusError;
 pub use status::Status;
 pub use parser::ResponseParser;
+pub use parser::RequestParser;
 pub use parser::ParseItem;
 pub use parser::ParseError;
 pub use builder::RequestBuilder;
@@ -23,3 +31,18 @@ pub use builder::NoBody;
 pub use builder::NoUrl;
 pub use builder::Error;
 pub use builder::Serialize;
+pub use session::ParseSessionError;
+pub use session::Session;
+pub use transport::LowerTransport;
+pub use transport::ParseTransportError;
+pub use transport::Profile;
+pub use transport::Transport;
+pub use content::ContentEncoding;
+pub use content::ContentType;
+pub use content::ParseContentTypeError;
+pub use incoming::IncomingRequest;
+pub use response::ResponseBuilder;
+pub use range::ParseRangeError;
+pub use range::Range;
+pub use via::ParseViaError;
+pub use via::Via;
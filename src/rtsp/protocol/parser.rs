@@ -1,22 +1,34 @@
 use super::*;
-use std::iter::Iterator;
-use thiserror::Error;
+use crate::http::{ParseTextError, TextParser};
 use std::fmt;
+use thiserror::Error;
 
 #[derive(Debug, PartialEq, Eq)]
 enum State {
     ExpectProtocol,
     ExpectStatus,
+    ExpectMethod,
+    ExpectUri,
+    ExpectRequestProtocol,
     ExpectHeader,
     ExpectBody,
     Done,
 }
 
-pub struct ResponseParser {
+/// Start line of RTSP on top of the line based parser shared with HTTP
+struct Parser {
     state: State,
-    pos: usize,
-    header_length: usize,
-    content_length: usize,
+    text: TextParser,
+}
+
+pub struct ResponseParser {
+    parser: Parser,
+}
+
+/// Incremental parser of RTSP requests, e.g. sent by a client to a server
+/// or by a camera to the client on the shared connection.
+pub struct RequestParser {
+    parser: Parser,
 }
 
 #[derive(Debug, Error)]
@@ -35,16 +47,28 @@ pub enum ParseError {
     ParseStatus(#[from] ParseStatusError),
     #[error("Failed to parse content length")]
     ParseContentLength(#[from] std::num::ParseIntError),
+    #[error("Content length too large")]
+    ContentLengthTooLarge,
     #[error(transparent)]
     Encoding(#[from] std::str::Utf8Error),
 }
 
 #[derive(Debug)]
 pub enum ParseItem<'a> {
+    Method(Method),
+    Uri(&'a str),
     Protocol(Protocol),
     Status(Status),
     Header(Header<'a>),
-    Body(&'a str),
+    /// The body as received, it is up to the consumer to decode it, e.g.
+    /// by its Content-Type
+    Body(&'a [u8]),
+}
+
+impl From<Method> for ParseItem<'_> {
+    fn from(m: Method) -> Self {
+        ParseItem::Method(m)
+    }
 }
 
 impl From<Protocol> for ParseItem<'_> {
@@ -65,150 +89,211 @@ impl<'a> From<Header<'a>> for ParseItem<'a> {
     }
 }
 
-impl <'a> fmt::Display for ParseItem<'a> {
+impl<'a> fmt::Display for ParseItem<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
+            ParseItem::Method(m) => write!(f, "{}", m),
+            ParseItem::Uri(u) => write!(f, "{}", u),
             ParseItem::Protocol(p) => write!(f, "{}", p),
             ParseItem::Status(s) => write!(f, "{}", s),
             ParseItem::Header(h) => write!(f, "{}", h),
-            ParseItem::Body(b) => write!(f, "{}", b),
+            ParseItem::Body(b) => write!(f, "{}", String::from_utf8_lossy(b)),
         }
     }
 }
 
-type Result<T> = std::result::Result<T, ParseError>;
-
-impl ResponseParser {
-    pub fn new() -> Self {
-        Self {
-            state: State::ExpectProtocol,
-            pos: 0,
-            header_length: 0,
-            content_length: 0,
-        }
+impl ParseError {
+    /// The line being parsed isn't complete yet, see [`ParseTextError::is_incomplete`]
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, ParseError::ExpectedEndOfLine | ParseError::ExpectedSpace)
     }
+}
 
-    fn get_next_line<'a>(&mut self, data: &'a [u8]) -> Result<&'a str> {
-        let data = &data[self.pos..];
-        for (i, w) in data.windows(2).enumerate() {
-            if w == b"\r\n" {
-                let line = std::str::from_utf8(&data[..i])?;
-                self.pos += i + 2;
-                return Ok(line);
-            }
+impl From<ParseTextError> for ParseError {
+    fn from(e: ParseTextError) -> Self {
+        match e {
+            ParseTextError::ExpectedEndOfLine => ParseError::ExpectedEndOfLine,
+            ParseTextError::ExpectedSpace => ParseError::ExpectedSpace,
+            ParseTextError::ParseHeader(e) => ParseError::ParseHeader(e),
+            ParseTextError::ParseContentLength(e) => ParseError::ParseContentLength(e),
+            ParseTextError::ContentLengthTooLarge => ParseError::ContentLengthTooLarge,
+            ParseTextError::Encoding(e) => ParseError::Encoding(e),
+            ParseTextError::UnexpectedProtocol(_) => ParseError::ParseProtocol(ParseProtocolError::UnexpectedToken),
+            ParseTextError::ParseVersion(e) => ParseError::ParseProtocol(ParseProtocolError::ParseVersion(e)),
         }
-        Err(ParseError::ExpectedEndOfLine)
-    }
-
-    fn get_next_token<'a>(&mut self, data: &'a [u8]) -> Result<&'a str> {
-        let data = &data[self.pos..];
-        for (i, w) in data.windows(2).enumerate() {
-            if w[0] == b' ' {
-                let line = std::str::from_utf8(&data[..i])?;
-                self.pos += i + 1;
-                return Ok(line);
-            } else if w == b"\r\n" {
-                return Err(ParseError::ExpectedSpace);
-            }
-        }
-        Err(ParseError::ExpectedSpace)
     }
+}
 
-    fn discard_line(&mut self, data: &[u8]) -> Result<()> {
-        let data = &data[self.pos..];
-        for (i, w) in data.windows(2).enumerate() {
-            if w == b"\r\n" {
-                self.pos += i + 2;
-                return Ok(());
-            }
+type Result<T> = std::result::Result<T, ParseError>;
+
+impl Parser {
+    fn new(state: State) -> Self {
+        Self {
+            state,
+            text: TextParser::new("RTSP"),
         }
-        Err(ParseError::ExpectedEndOfLine)
     }
 
     fn parse_protocol<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
-        let token = self.get_next_token(data)?;
+        let token = self.text.next_token(data)?;
         let protcol: Protocol = token.parse()?;
         self.state = State::ExpectStatus;
         Ok(Some(protcol.into()))
     }
 
+    fn parse_method<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+        let token = self.text.next_token(data)?;
+        // The rest of a request with an unknown method can still be parsed
+        self.state = State::ExpectUri;
+        let method: Method = token.parse()?;
+        Ok(Some(method.into()))
+    }
+
+    fn parse_uri<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+        let token = self.text.next_token(data)?;
+        self.state = State::ExpectRequestProtocol;
+        Ok(Some(ParseItem::Uri(token)))
+    }
+
+    fn parse_request_protocol<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+        let line = self.text.next_line(data)?;
+        let protocol: Protocol = line.parse()?;
+        self.state = State::ExpectHeader;
+        Ok(Some(protocol.into()))
+    }
+
     fn parse_status<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
-        let token = self.get_next_token(data)?;
+        let token = self.text.next_token(data)?;
         let status: Status = token.parse()?;
-        self.discard_line(data)?;
+        self.text.discard_line(data)?;
         self.state = State::ExpectHeader;
         Ok(Some(status.into()))
     }
 
-    fn handle_special_header<'a>(&mut self, header: &Header<'a>) -> Result<()> {
-        if header.name.eq_ignore_ascii_case("content-length") {
-            self.content_length = header.value.parse()?;
-        }
-        Ok(())
-    }
-
     fn parse_header_field<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
-        let line = self.get_next_line(data)?;
-        if line.is_empty() {
-            if self.content_length > 0 {
-                self.state = State::ExpectBody;
-            } else {
-                self.state = State::Done;
+        match self.text.next_header(data)? {
+            Some(header) => Ok(Some(header.into())),
+            None => {
+                if self.text.content_length().unwrap_or(0) > 0 {
+                    self.state = State::ExpectBody;
+                } else {
+                    self.state = State::Done;
+                }
+                self.parse_body(data)
             }
-            self.header_length = self.pos;
-            self.parse_body(data)
-        } else {
-            let header: Header<'a> = line.try_into()?;
-            self.handle_special_header(&header)?;
-            Ok(Some(header.into()))
         }
     }
 
     fn parse_body<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
-        let data = &data[self.pos..];
-        if data.len() >= self.content_length {
-            self.pos += self.content_length;
-            self.state = State::Done;
-            Ok(Some(ParseItem::Body(std::str::from_utf8(
-                &data[..self.content_length],
-            )?)))
-        } else {
-            Ok(None)
+        match self.text.body(data) {
+            Some(body) => {
+                self.state = State::Done;
+                Ok(Some(ParseItem::Body(body)))
+            }
+            None => Ok(None),
         }
     }
 
-    pub fn parse_next<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+    fn parse_next<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
         match self.state {
             State::ExpectProtocol => self.parse_protocol(data),
             State::ExpectStatus => self.parse_status(data),
+            State::ExpectMethod => self.parse_method(data),
+            State::ExpectUri => self.parse_uri(data),
+            State::ExpectRequestProtocol => self.parse_request_protocol(data),
             State::ExpectHeader => self.parse_header_field(data),
             State::ExpectBody => self.parse_body(data),
             State::Done => Ok(None),
         }
     }
 
-    pub fn is_done(&self) -> bool {
+    fn is_done(&self) -> bool {
         self.state == State::Done
     }
+}
 
-    pub fn missing_bytes(&self) -> Option<usize> {
-        if self.header_length > 0 {
-            Some(self.header_length + self.content_length - self.pos)
-        } else {
-            None
+impl Default for ResponseParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseParser {
+    pub fn new() -> Self {
+        Self {
+            parser: Parser::new(State::ExpectProtocol),
         }
     }
 
+    /// Parses headers with spaces around the name, see [`Header::parse_lenient`]
+    pub fn lenient_headers(mut self, lenient: bool) -> Self {
+        self.parser.text = self.parser.text.lenient_headers(lenient);
+        self
+    }
+
+    /// Body length of a response without a Content-Length header, for
+    /// servers that end the body by closing the connection
+    pub fn body_length(mut self, length: usize) -> Self {
+        self.parser.text = self.parser.text.body_length(length);
+        self
+    }
+
+    pub fn parse_next<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+        self.parser.parse_next(data)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.parser.is_done()
+    }
+
+    pub fn missing_bytes(&self) -> Option<usize> {
+        self.parser.text.missing_bytes()
+    }
+
     pub fn response_bytes(&self) -> Option<usize> {
-        if self.header_length > 0 {
-            Some(self.header_length + self.content_length)
-        } else {
-            None
+        self.parser.text.message_bytes()
+    }
+
+    pub fn parsed_bytes(&self) -> usize {
+        self.parser.text.pos()
+    }
+}
+
+impl Default for RequestParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestParser {
+    pub fn new() -> Self {
+        Self {
+            parser: Parser::new(State::ExpectMethod),
         }
     }
 
+    /// Returns the next item of the request in `data`, which must start
+    /// with the request and may grow between calls. `None` means more data
+    /// is needed or the request is done, see [`RequestParser::is_done`].
+    pub fn parse_next<'a>(&mut self, data: &'a [u8]) -> Result<Option<ParseItem<'a>>> {
+        self.parser.parse_next(data)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.parser.is_done()
+    }
+
+    /// Bytes still missing once the header is complete
+    pub fn missing_bytes(&self) -> Option<usize> {
+        self.parser.text.missing_bytes()
+    }
+
+    pub fn request_bytes(&self) -> Option<usize> {
+        self.parser.text.message_bytes()
+    }
+
     pub fn parsed_bytes(&self) -> usize {
-        self.pos
+        self.parser.text.pos()
     }
 }
 
@@ -225,11 +310,12 @@ mod tests {
                 Some(ParseItem::Protocol(p)) => assert_eq!(p, Protocol::new(Version::new(1, 0))),
                 Some(ParseItem::Status(s)) => assert_eq!(s, Status::OK),
                 Some(ParseItem::Header(h)) => assert_eq!(h, Header::new("CSeq", "1")),
-                Some(ParseItem::Body(b)) => assert_eq!(b, ""),
+                Some(ParseItem::Body(b)) => assert_eq!(b, b""),
+                Some(item) => panic!("Unexpected item {}", item),
                 None => break,
             }
         }
-        assert_eq!(parser.is_done(), true);
+        assert!(parser.is_done());
     }
 
     #[test]
@@ -245,11 +331,25 @@ mod tests {
                     "Content-Length" => assert_eq!(h.value, "5"),
                     _ => panic!("Unexpected header: {:?}", h),
                 },
-                Some(ParseItem::Body(b)) => assert_eq!(b, "hello"),
+                Some(ParseItem::Body(b)) => assert_eq!(b, b"hello"),
+                Some(item) => panic!("Unexpected item {}", item),
                 None => break,
             }
         }
-        assert_eq!(parser.is_done(), true);
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn test_parse_response_with_binary_body() {
+        let mut parser = ResponseParser::new();
+        let response = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 3\r\n\r\n\xff\x00\xe9";
+        let mut body = None;
+        while let Some(item) = parser.parse_next(response).unwrap() {
+            if let ParseItem::Body(b) = item {
+                body = Some(b);
+            }
+        }
+        assert_eq!(body, Some(&b"\xff\x00\xe9"[..]));
     }
 
     #[test]
@@ -265,17 +365,83 @@ mod tests {
                     "Content-Length" => assert_eq!(h.value, "11"),
                     _ => panic!("Unexpected header: {:?}", h),
                 },
-                ParseItem::Body(b) => assert_eq!(b, "hello"),
+                ParseItem::Body(b) => assert_eq!(b, b"hello"),
+                ParseItem::Method(_) | ParseItem::Uri(_) => panic!("Unexpected item"),
             }
         }
-        assert_eq!(parser.is_done(), false);
+        assert!(!parser.is_done());
         let response = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 11\r\n\r\nhello world";
         while let Some(item) = parser.parse_next(response).unwrap() {
             match item {
-                ParseItem::Body(b) => assert_eq!(b, "hello world"),
+                ParseItem::Body(b) => assert_eq!(b, b"hello world"),
                 _ => panic!("Unexpected item"),
             }
         }
-        assert_eq!(parser.is_done(), true);
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let mut parser = RequestParser::new();
+        let request = b"SETUP rtsp://host/live/trackID=0 RTSP/1.0\r\nCSeq: 2\r\nTransport: RTP/AVP;unicast\r\n\r\n";
+        let mut items = Vec::new();
+        while let Some(item) = parser.parse_next(request).unwrap() {
+            items.push(item.to_string());
+        }
+        assert!(parser.is_done());
+        assert_eq!(parser.request_bytes(), Some(request.len()));
+        assert_eq!(
+            items,
+            [
+                "SETUP",
+                "rtsp://host/live/trackID=0",
+                "RTSP/1.0",
+                "CSeq: 2\r\n",
+                "Transport: RTP/AVP;unicast\r\n",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_request_incrementally() {
+        let request = b"ANNOUNCE rtsp://host/live RTSP/1.0\r\nCSeq: 5\r\nContent-Length: 5\r\n\r\nv=0\r\n";
+        let mut parser = RequestParser::new();
+        let mut body = None;
+        for len in 0..=request.len() {
+            let data = &request[..len];
+            loop {
+                match parser.parse_next(data) {
+                    Ok(Some(ParseItem::Body(b))) => body = Some(b.to_vec()),
+                    Ok(Some(_)) => {}
+                    // Incomplete line, parsed again once more data arrived
+                    Ok(None) => break,
+                    Err(e) if e.is_incomplete() => break,
+                    Err(e) => panic!("Unexpected error {}", e),
+                }
+            }
+            if len < request.len() {
+                assert!(!parser.is_done());
+            }
+        }
+        assert!(parser.is_done());
+        assert_eq!(parser.missing_bytes(), Some(0));
+        assert_eq!(body.as_deref(), Some(&b"v=0\r\n"[..]));
+    }
+
+    #[test]
+    fn test_parse_request_invalid() {
+        let mut parser = RequestParser::new();
+        let request = b"FETCH rtsp://host RTSP/1.0\r\n\r\n";
+        assert!(matches!(parser.parse_next(request), Err(ParseError::ParseMethod(_))));
+        assert!(matches!(
+            parser.parse_next(request),
+            Ok(Some(ParseItem::Uri("rtsp://host")))
+        ));
+        let mut parser = RequestParser::new();
+        let request = b"OPTIONS * HTTP/1.1\r\n\r\n";
+        assert!(parser.parse_next(request).unwrap().is_some());
+        assert!(parser.parse_next(request).unwrap().is_some());
+        assert!(matches!(parser.parse_next(request), Err(ParseError::ParseProtocol(_))));
     }
 }
@@ -3,6 +3,7 @@ use std::str::FromStr;
 use thiserror::Error;
 
 use super::{ParseVersionError, Version};
+use crate::http::{parse_protocol_version, ParseTextError};
 
 #[derive(Debug, PartialEq)]
 pub struct Protocol {
@@ -32,13 +33,11 @@ pub enum ParseProtocolError {
 impl FromStr for Protocol {
     type Err = ParseProtocolError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut iter = s.split('/');
-        let token = iter.next().ok_or(ParseProtocolError::UnexpectedToken)?;
-        (token == "RTSP")
-            .then_some(())
-            .ok_or(ParseProtocolError::UnexpectedToken)?;
-        let version = iter.next().ok_or(ParseProtocolError::UnexpectedToken)?.parse()?;
-        Ok(Protocol::new(version))
+        match parse_protocol_version("RTSP", s) {
+            Ok(version) => Ok(Protocol::new(version)),
+            Err(ParseTextError::ParseVersion(e)) => Err(ParseProtocolError::ParseVersion(e)),
+            Err(_) => Err(ParseProtocolError::UnexpectedToken),
+        }
     }
 }
 
@@ -0,0 +1,214 @@
+use crate::http::{civil_from_days, days_from_civil};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// RTSP Range header (RFC 2326 12.29), e.g. `Range: npt=10-` or
+/// `Range: clock=20240501T080000Z-20240501T090000Z`.
+///
+/// Recorders and DVR servers address their footage with absolute `clock`
+/// times in UTC on the server's clock, see
+/// [`ClockSkew::to_server_time`](crate::sync::ClockSkew::to_server_time)
+/// to convert local times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Range {
+    /// Normal play time from the start of the presentation, `None` as
+    /// start is `now`, the live point
+    Npt {
+        start: Option<Duration>,
+        end: Option<Duration>,
+    },
+    /// Absolute times
+    Clock { start: SystemTime, end: Option<SystemTime> },
+}
+
+impl Range {
+    /// From `start` to the end of the presentation
+    pub fn npt(start: Duration) -> Self {
+        Range::Npt {
+            start: Some(start),
+            end: None,
+        }
+    }
+
+    /// `npt=now-`, the live stream
+    pub fn now() -> Self {
+        Range::Npt { start: None, end: None }
+    }
+
+    pub fn clock(start: SystemTime, end: Option<SystemTime>) -> Self {
+        Range::Clock { start, end }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseRangeError {
+    #[error("Unsupported range unit {0}")]
+    UnsupportedUnit(String),
+    #[error("Invalid range time {0}")]
+    InvalidTime(String),
+}
+
+/// Seconds with millisecond precision, trailing zeros are kept for servers
+/// that expect a fraction
+fn write_npt(f: &mut fmt::Formatter, time: Duration) -> fmt::Result {
+    write!(f, "{}.{:03}", time.as_secs(), time.subsec_millis())
+}
+
+/// `YYYYMMDDThhmmss[.fraction]Z`
+fn write_clock(f: &mut fmt::Formatter, time: SystemTime) -> fmt::Result {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs = secs % 86_400;
+    write!(
+        f,
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )?;
+    match since_epoch.subsec_millis() {
+        0 => write!(f, "Z"),
+        millis => write!(f, ".{:03}Z", millis),
+    }
+}
+
+fn parse_npt(s: &str) -> Result<Option<Duration>, ParseRangeError> {
+    let invalid = || ParseRangeError::InvalidTime(s.to_string());
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("now") {
+        return Ok(None);
+    }
+    // Seconds, or hours, minutes and seconds separated by colons
+    let mut secs = 0.0;
+    for part in s.split(':') {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        secs = secs * 60.0 + value;
+    }
+    Duration::try_from_secs_f64(secs).map(Some).map_err(|_| invalid())
+}
+
+fn parse_clock(s: &str) -> Result<SystemTime, ParseRangeError> {
+    let invalid = || ParseRangeError::InvalidTime(s.to_string());
+    let s = s.trim();
+    let (date, time) = s.split_once('T').ok_or_else(invalid)?;
+    let time = time.strip_suffix('Z').ok_or_else(invalid)?;
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    if date.len() != 8 || time.len() != 6 || !date.bytes().chain(time.bytes()).all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+    let (year, month, day) = (number(&date[..4])?, number(&date[4..6])?, number(&date[6..])?);
+    let (hours, minutes, seconds) = (number(&time[..2])?, number(&time[2..4])?, number(&time[4..])?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return Err(invalid());
+    }
+    let days = u64::try_from(days_from_civil(year as i64, month, day)).map_err(|_| invalid())?;
+    let secs = days * 86_400 + hours as u64 * 3600 + minutes as u64 * 60 + seconds as u64;
+    let fraction = match fraction {
+        "" => Duration::ZERO,
+        f => Duration::from_secs_f64(format!("0.{}", f).parse().map_err(|_| invalid())?),
+    };
+    Ok(UNIX_EPOCH + Duration::from_secs(secs) + fraction)
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Range::Npt { start, end } => {
+                write!(f, "npt=")?;
+                match start {
+                    Some(start) => write_npt(f, *start)?,
+                    None => write!(f, "now")?,
+                }
+                write!(f, "-")?;
+                end.map_or(Ok(()), |end| write_npt(f, end))
+            }
+            Range::Clock { start, end } => {
+                write!(f, "clock=")?;
+                write_clock(f, *start)?;
+                write!(f, "-")?;
+                end.map_or(Ok(()), |end| write_clock(f, end))
+            }
+        }
+    }
+}
+
+impl FromStr for Range {
+    type Err = ParseRangeError;
+
+    /// Parses the header value, a `time` parameter is ignored
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let range = s.split(';').next().unwrap_or_default().trim();
+        let (unit, value) = range
+            .split_once('=')
+            .ok_or_else(|| ParseRangeError::UnsupportedUnit(range.to_string()))?;
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| ParseRangeError::InvalidTime(value.to_string()))?;
+        let end = Some(end.trim()).filter(|e| !e.is_empty());
+        match unit.trim().to_ascii_lowercase().as_str() {
+            "npt" => Ok(Range::Npt {
+                start: parse_npt(start)?,
+                end: end.map(parse_npt).transpose()?.flatten(),
+            }),
+            "clock" => Ok(Range::Clock {
+                start: parse_clock(start)?,
+                end: end.map(parse_clock).transpose()?,
+            }),
+            unit => Err(ParseRangeError::UnsupportedUnit(unit.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        let range: Range = "npt=10-20.5".parse().unwrap();
+        assert_eq!(
+            range,
+            Range::Npt {
+                start: Some(Duration::from_secs(10)),
+                end: Some(Duration::from_millis(20_500))
+            }
+        );
+        assert_eq!(range.to_string(), "npt=10.000-20.500");
+        assert_eq!("npt=now-".parse::<Range>().unwrap(), Range::now());
+        assert_eq!(
+            "npt=1:02:03.5-".parse::<Range>().unwrap(),
+            Range::npt(Duration::from_millis(3_723_500))
+        );
+        assert_eq!(Range::npt(Duration::ZERO).to_string(), "npt=0.000-");
+        assert!(matches!(
+            "smpte=10:07:00-".parse::<Range>(),
+            Err(ParseRangeError::UnsupportedUnit(_))
+        ));
+        assert!("npt=abc-".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn test_parse_clock_range() {
+        // 2024-05-01 08:00:00 UTC
+        let start = UNIX_EPOCH + Duration::from_secs(1_714_550_400);
+        let range: Range = "clock=20240501T080000Z-20240501T090000.25Z;time=20240501T080000Z"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            range,
+            Range::clock(start, Some(start + Duration::from_millis(3_600_250)))
+        );
+        assert_eq!(range.to_string(), "clock=20240501T080000Z-20240501T090000.250Z");
+        assert_eq!(Range::clock(start, None).to_string(), "clock=20240501T080000Z-");
+        assert!("clock=20241301T080000Z-".parse::<Range>().is_err());
+        assert!("clock=20240501T080000-".parse::<Range>().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use super::{Header, Status};
+use std::fmt;
+use std::fmt::Write;
+
+/// Response to an [`IncomingRequest`](super::IncomingRequest), the CSeq of
+/// the request is mirrored
+pub struct ResponseBuilder {
+    status: Status,
+    // Serialized header lines, a response allocates once for all of them
+    headers: String,
+    body: Option<(&'static str, String)>,
+}
+
+impl ResponseBuilder {
+    pub fn new(status: Status, cseq: Option<u32>) -> Self {
+        let builder = Self {
+            status,
+            headers: String::with_capacity(256),
+            body: None,
+        };
+        match cseq {
+            Some(cseq) => builder.header("CSeq", cseq),
+            None => builder,
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl fmt::Display) -> Self {
+        let _ = write!(self.headers, "{}: {}\r\n", name, value);
+        self
+    }
+
+    pub fn body(mut self, content_type: &'static str, body: String) -> Self {
+        self.body = Some((content_type, body));
+        self
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+impl fmt::Display for ResponseBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RTSP/1.0 {}\r\n{}", self.status, self.headers)?;
+        match &self.body {
+            Some((content_type, body)) => {
+                write!(f, "{}", Header::new("Content-Type", content_type))?;
+                write!(f, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+            }
+            None => write!(f, "\r\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_response() {
+        let response = ResponseBuilder::new(Status::OK, Some(2))
+            .header("Session", "1234")
+            .body("application/sdp", "v=0\r\n".to_string());
+        assert_eq!(
+            response.to_string(),
+            "RTSP/1.0 200 OK\r\nCSeq: 2\r\nSession: 1234\r\nContent-Type: application/sdp\r\nContent-Length: 5\r\n\r\nv=0\r\n"
+        );
+    }
+}
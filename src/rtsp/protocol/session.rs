@@ -0,0 +1,107 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// RTSP Session header, e.g. `Session: 12345678;timeout=60`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: String,
+    pub timeout: Option<u32>,
+}
+
+impl Session {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            timeout: None,
+        }
+    }
+
+    /// Parses the header of servers that don't follow the grammar, e.g.
+    /// `id;timeout=` or `id; timeout = 60s`. Only the id is required, a
+    /// timeout without leading digits is ignored.
+    pub fn parse_lenient(s: &str) -> Result<Self, ParseSessionError> {
+        let mut params = s.split(';');
+        let id = params.next().unwrap_or_default().trim();
+        if id.is_empty() {
+            return Err(ParseSessionError::EmptyId);
+        }
+        let mut session = Session::new(id);
+        for param in params {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            if name.trim().eq_ignore_ascii_case("timeout") {
+                let value = value.trim();
+                let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+                session.timeout = value[..digits].parse().ok();
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// Only the id is written, clients must not send the timeout back
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseSessionError {
+    #[error("Empty session id")]
+    EmptyId,
+    #[error("Failed to parse session timeout {0}")]
+    ParseTimeout(#[from] ParseIntError),
+}
+
+impl FromStr for Session {
+    type Err = ParseSessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = s.split(';');
+        let id = params.next().unwrap_or_default().trim();
+        if id.is_empty() {
+            return Err(ParseSessionError::EmptyId);
+        }
+        let mut session = Session::new(id);
+        for param in params {
+            let mut iter = param.splitn(2, '=');
+            let name = iter.next().unwrap_or_default().trim();
+            if name.eq_ignore_ascii_case("timeout") {
+                session.timeout = Some(iter.next().unwrap_or_default().trim().parse()?);
+            }
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session() {
+        let session: Session = "12345678;timeout=60".parse().unwrap();
+        assert_eq!(session.id, "12345678");
+        assert_eq!(session.timeout, Some(60));
+        assert_eq!(session.to_string(), "12345678");
+    }
+
+    #[test]
+    fn test_parse_session_lenient() {
+        assert!("12345678;timeout=".parse::<Session>().is_err());
+        let session = Session::parse_lenient("12345678;timeout=").unwrap();
+        assert_eq!((session.id.as_str(), session.timeout), ("12345678", None));
+        let session = Session::parse_lenient("12345678; Timeout = 60s").unwrap();
+        assert_eq!(session.timeout, Some(60));
+        assert!(matches!(
+            Session::parse_lenient(" ;timeout=60"),
+            Err(ParseSessionError::EmptyId)
+        ));
+    }
+
+    #[test]
+    fn test_parse_session_empty() {
+        assert!(matches!("".parse::<Session>(), Err(ParseSessionError::EmptyId)));
+    }
+}
@@ -4,8 +4,13 @@ use std::num::ParseIntError;
 use std::str::FromStr;
 use thiserror::Error;
 
-/// RTSP Status codes
+/// RTSP Status codes, serialized as their number
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "u32", try_from = "u32")
+)]
 pub enum Status {
     Continue = 100,
     OK = 200,
@@ -224,4 +229,12 @@ mod tests {
         let status = Status::from_str("500").unwrap();
         assert_eq!(status, Status::InternalServerError);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_status_serde() {
+        assert_eq!(serde_json::to_string(&Status::SessionNotFound).unwrap(), "454");
+        assert_eq!(serde_json::from_str::<Status>("200").unwrap(), Status::OK);
+        assert!(serde_json::from_str::<Status>("299").is_err());
+    }
 }
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Profile {
+    Avp,
+    Savp,
+    /// AVP with RTCP based feedback (RFC 4585), used by WebRTC derived servers
+    Avpf,
+    Savpf,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Profile::Avp => "AVP",
+            Profile::Savp => "SAVP",
+            Profile::Avpf => "AVPF",
+            Profile::Savpf => "SAVPF",
+        }
+    }
+
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Profile::Savp | Profile::Savpf)
+    }
+
+    /// Whether RTCP feedback and reduced-size RTCP (RFC 5506) may be used
+    pub fn has_feedback(&self) -> bool {
+        matches!(self, Profile::Avpf | Profile::Savpf)
+    }
+
+    /// Parses the profile part of a protocol, e.g. `AVPF` of `RTP/AVPF`
+    pub fn parse(s: &str) -> Option<Self> {
+        [Profile::Avp, Profile::Savp, Profile::Avpf, Profile::Savpf]
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LowerTransport {
+    Udp,
+    Tcp,
+}
+
+/// RTSP Transport header (RFC 2326 12.39), only the parameters
+/// relevant for a RTP client are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transport {
+    pub profile: Profile,
+    pub lower: LowerTransport,
+    pub multicast: bool,
+    pub interleaved: Option<(u8, u8)>,
+    pub client_port: Option<(u16, u16)>,
+    pub server_port: Option<(u16, u16)>,
+    pub ssrc: Option<u32>,
+}
+
+impl Transport {
+    /// RTP interleaved on the RTSP connection, using the given channel ids
+    pub fn tcp(interleaved: (u8, u8)) -> Self {
+        Self {
+            profile: Profile::Avp,
+            lower: LowerTransport::Tcp,
+            multicast: false,
+            interleaved: Some(interleaved),
+            client_port: None,
+            server_port: None,
+            ssrc: None,
+        }
+    }
+
+    /// Unicast RTP/RTCP over UDP to the given client ports
+    pub fn udp(client_port: (u16, u16)) -> Self {
+        Self {
+            profile: Profile::Avp,
+            lower: LowerTransport::Udp,
+            multicast: false,
+            interleaved: None,
+            client_port: Some(client_port),
+            server_port: None,
+            ssrc: None,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RTP/{}", self.profile.as_str())?;
+        if self.lower == LowerTransport::Tcp {
+            write!(f, "/TCP")?;
+        }
+        write!(f, ";{}", if self.multicast { "multicast" } else { "unicast" })?;
+        if let Some((a, b)) = self.interleaved {
+            write!(f, ";interleaved={}-{}", a, b)?;
+        }
+        if let Some((a, b)) = self.client_port {
+            write!(f, ";client_port={}-{}", a, b)?;
+        }
+        if let Some((a, b)) = self.server_port {
+            write!(f, ";server_port={}-{}", a, b)?;
+        }
+        if let Some(ssrc) = self.ssrc {
+            write!(f, ";ssrc={:08X}", ssrc)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseTransportError {
+    #[error("Unsupported transport protocol {0}")]
+    UnsupportedProtocol(String),
+    #[error("Invalid transport parameter {0}")]
+    InvalidParameter(String),
+    #[error("Failed to parse transport number {0}")]
+    ParseInt(#[from] ParseIntError),
+}
+
+fn parse_range<T: FromStr<Err = ParseIntError> + Copy>(value: &str) -> Result<(T, T), ParseTransportError> {
+    let mut iter = value.splitn(2, '-');
+    let a = iter.next().unwrap_or_default().trim().parse()?;
+    match iter.next() {
+        Some(b) => Ok((a, b.trim().parse()?)),
+        None => Ok((a, a)),
+    }
+}
+
+impl FromStr for Transport {
+    type Err = ParseTransportError;
+
+    /// Parses the first transport specification of the header
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spec = s.split(',').next().unwrap_or_default();
+        let mut params = spec.split(';');
+        let protocol = params.next().unwrap_or_default().trim();
+        let mut parts = protocol.split('/');
+        if !parts.next().is_some_and(|p| p.eq_ignore_ascii_case("RTP")) {
+            return Err(ParseTransportError::UnsupportedProtocol(protocol.to_string()));
+        }
+        let profile = parts
+            .next()
+            .and_then(Profile::parse)
+            .ok_or_else(|| ParseTransportError::UnsupportedProtocol(protocol.to_string()))?;
+        let lower = match parts.next() {
+            None => LowerTransport::Udp,
+            Some(p) if p.eq_ignore_ascii_case("UDP") => LowerTransport::Udp,
+            Some(p) if p.eq_ignore_ascii_case("TCP") => LowerTransport::Tcp,
+            Some(_) => return Err(ParseTransportError::UnsupportedProtocol(protocol.to_string())),
+        };
+        let mut transport = Transport {
+            profile,
+            lower,
+            multicast: false,
+            interleaved: None,
+            client_port: None,
+            server_port: None,
+            ssrc: None,
+        };
+        for param in params {
+            let mut iter = param.splitn(2, '=');
+            let name = iter.next().unwrap_or_default().trim();
+            let value = iter.next().map(str::trim);
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("unicast", None) => transport.multicast = false,
+                ("multicast", None) => transport.multicast = true,
+                ("interleaved", Some(v)) => transport.interleaved = Some(parse_range(v)?),
+                ("client_port", Some(v)) => transport.client_port = Some(parse_range(v)?),
+                ("server_port", Some(v)) => transport.server_port = Some(parse_range(v)?),
+                ("ssrc", Some(v)) => {
+                    let ssrc = u32::from_str_radix(v, 16)
+                        .map_err(|_| ParseTransportError::InvalidParameter(param.to_string()))?;
+                    transport.ssrc = Some(ssrc);
+                }
+                ("", None) => {}
+                _ => log::debug!("Ignoring transport parameter {}", param),
+            }
+        }
+        Ok(transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_transport() {
+        assert_eq!(
+            Transport::tcp((0, 1)).to_string(),
+            "RTP/AVP/TCP;unicast;interleaved=0-1"
+        );
+        assert_eq!(
+            Transport::udp((5000, 5001)).to_string(),
+            "RTP/AVP;unicast;client_port=5000-5001"
+        );
+    }
+
+    #[test]
+    fn test_parse_transport() {
+        let transport: Transport =
+            "RTP/AVP;unicast;client_port=5000-5001;server_port=6970-6971;ssrc=1F2E3D4C;mode=play"
+                .parse()
+                .unwrap();
+        assert_eq!(transport.lower, LowerTransport::Udp);
+        assert_eq!(transport.client_port, Some((5000, 5001)));
+        assert_eq!(transport.server_port, Some((6970, 6971)));
+        assert_eq!(transport.ssrc, Some(0x1F2E3D4C));
+    }
+
+    #[test]
+    fn test_parse_transport_interleaved() {
+        let transport: Transport = "RTP/AVP/TCP;unicast;interleaved=2-3".parse().unwrap();
+        assert_eq!(transport, Transport::tcp((2, 3)));
+    }
+
+    #[test]
+    fn test_parse_transport_feedback_profile() {
+        let transport: Transport = "RTP/SAVPF/TCP;unicast;interleaved=0-1".parse().unwrap();
+        assert_eq!(transport.profile, Profile::Savpf);
+        assert!(transport.profile.is_secure());
+        assert!(transport.profile.has_feedback());
+        assert_eq!(transport.to_string(), "RTP/SAVPF/TCP;unicast;interleaved=0-1");
+        let transport: Transport = "RTP/AVPF;unicast;client_port=5000-5001".parse().unwrap();
+        assert_eq!(transport.profile, Profile::Avpf);
+    }
+
+    #[test]
+    fn test_parse_transport_invalid_protocol() {
+        let result = "RAW/RAW/UDP;unicast".parse::<Transport>();
+        assert!(matches!(result, Err(ParseTransportError::UnsupportedProtocol(_))));
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// An entry of the RTSP Via header (RFC 7826 18.57), e.g.
+/// `RTSP/1.0 proxy.example.com:554;ttl=8 (Relay 1.2)`. Every proxy a
+/// message passes adds one, a header lists them in the order they were
+/// added, see [`Via::parse_list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Via {
+    /// Protocol of the hop, e.g. `RTSP/1.0`
+    pub protocol: String,
+    /// Host and port or a pseudonym of the proxy
+    pub received_by: String,
+    pub params: Vec<(String, Option<String>)>,
+    pub comment: Option<String>,
+}
+
+impl Via {
+    /// An entry received by `received_by` over RTSP/1.0
+    pub fn new(received_by: &str) -> Self {
+        Self {
+            protocol: "RTSP/1.0".to_string(),
+            received_by: received_by.to_string(),
+            params: Vec::new(),
+            comment: None,
+        }
+    }
+
+    pub fn comment(mut self, comment: &str) -> Self {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Whether both entries name the same proxy
+    pub fn is_same_hop(&self, other: &Via) -> bool {
+        self.received_by.eq_ignore_ascii_case(&other.received_by)
+    }
+
+    /// Parses the comma separated entries of a Via header
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, ParseViaError> {
+        let mut entries = Vec::new();
+        let mut start = 0;
+        let mut depth = 0usize;
+        let mut quoted = false;
+        for (i, c) in s.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '(' if !quoted => depth += 1,
+                ')' if !quoted => depth = depth.saturating_sub(1),
+                ',' if !quoted && depth == 0 => {
+                    entries.push(s[start..i].parse()?);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        entries.push(s[start..].parse()?);
+        Ok(entries)
+    }
+}
+
+impl fmt::Display for Via {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.protocol, self.received_by)?;
+        for (name, value) in &self.params {
+            match value {
+                Some(value) => write!(f, ";{}={}", name, value)?,
+                None => write!(f, ";{}", name)?,
+            }
+        }
+        match &self.comment {
+            Some(comment) => write!(f, " ({})", comment),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseViaError {
+    #[error("Missing protocol")]
+    MissingProtocol,
+    #[error("Missing received-by")]
+    MissingReceivedBy,
+    #[error("Unterminated comment")]
+    UnterminatedComment,
+}
+
+impl FromStr for Via {
+    type Err = ParseViaError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (s, comment) = match s.split_once('(') {
+            Some((s, comment)) => {
+                let comment = comment.strip_suffix(')').ok_or(ParseViaError::UnterminatedComment)?;
+                (s, Some(comment.to_string()))
+            }
+            None => (s, None),
+        };
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseViaError::MissingProtocol);
+        }
+        let (protocol, rest) = s
+            .split_once(char::is_whitespace)
+            .ok_or(ParseViaError::MissingReceivedBy)?;
+        let mut params = rest.split(';');
+        let received_by = params.next().unwrap_or_default().trim();
+        if received_by.is_empty() {
+            return Err(ParseViaError::MissingReceivedBy);
+        }
+        Ok(Self {
+            protocol: protocol.to_string(),
+            received_by: received_by.to_string(),
+            params: params
+                .map(|param| match param.split_once('=') {
+                    Some((name, value)) => (name.trim().to_string(), Some(value.trim().to_string())),
+                    None => (param.trim().to_string(), None),
+                })
+                .collect(),
+            comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_via() {
+        let via: Via = "RTSP/1.0 proxy.test.com:554;ttl=8;hidden (Relay, 1.2)".parse().unwrap();
+        assert_eq!(via.protocol, "RTSP/1.0");
+        assert_eq!(via.received_by, "proxy.test.com:554");
+        assert_eq!(
+            via.params,
+            [("ttl".to_string(), Some("8".to_string())), ("hidden".to_string(), None)]
+        );
+        assert_eq!(via.comment.as_deref(), Some("Relay, 1.2"));
+        assert_eq!(via.to_string(), "RTSP/1.0 proxy.test.com:554;ttl=8;hidden (Relay, 1.2)");
+        assert_eq!(Via::new("edge").comment("mm").to_string(), "RTSP/1.0 edge (mm)");
+    }
+
+    #[test]
+    fn test_parse_via_list() {
+        let entries = Via::parse_list("RTSP/1.0 a (one, two), 1.0 b;x=\"1,2\" ,RTSP/2.0 c").unwrap();
+        let hops: Vec<_> = entries.iter().map(|v| v.received_by.as_str()).collect();
+        assert_eq!(hops, ["a", "b", "c"]);
+        assert_eq!(entries[1].params, [("x".to_string(), Some("\"1,2\"".to_string()))]);
+        assert!(entries[0].is_same_hop(&Via::new("A")));
+    }
+
+    #[test]
+    fn test_parse_via_invalid() {
+        assert!(matches!("".parse::<Via>(), Err(ParseViaError::MissingProtocol)));
+        assert!(matches!(
+            "RTSP/1.0".parse::<Via>(),
+            Err(ParseViaError::MissingReceivedBy)
+        ));
+        assert!(matches!(
+            "RTSP/1.0 a (b".parse::<Via>(),
+            Err(ParseViaError::UnterminatedComment)
+        ));
+        assert!(Via::parse_list("RTSP/1.0 a,").is_err());
+    }
+}
@@ -0,0 +1,18 @@
+mod reader;
+mod relay;
+mod server;
+mod session;
+mod source;
+
+pub use server::Error;
+pub use server::Server;
+pub use source::MediaSource;
+pub use reader::describe_tracks;
+pub use reader::MediaReader;
+pub use reader::PacketReader;
+pub use reader::SourceFrame;
+pub use reader::SourcePacket;
+pub use reader::SourceTrack;
+pub use reader::FIRST_PAYLOAD_TYPE;
+pub use relay::LiveRelay;
+pub use relay::RelayFeed;
@@ -0,0 +1,296 @@
+use crate::rtp::{self, PacketBuilder, Packetizer};
+use crate::sdp::Sdp;
+use crate::types::{Frame, FrameType, MediaType};
+use base64::prelude::*;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io;
+use std::time::Duration;
+
+/// First dynamic payload type of the tracks of a [`MediaReader`], the
+/// tracks follow in order
+pub const FIRST_PAYLOAD_TYPE: u8 = 96;
+
+/// A track of a [`MediaReader`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceTrack {
+    pub media_type: MediaType,
+    pub frame_type: FrameType,
+    /// Clock rate of the RTP timestamps, 90000 for video and the sample
+    /// rate for audio
+    pub clock_rate: u32,
+    /// Audio channels, None for video
+    pub channels: Option<u16>,
+    /// SPS and PPS of H.264 without start codes, the AudioSpecificConfig
+    /// of AAC
+    pub parameter_sets: Vec<Vec<u8>>,
+}
+
+impl SourceTrack {
+    /// The rtpmap and fmtp of the track
+    fn write_media(&self, sdp: &mut String, payload_type: u8) -> std::fmt::Result {
+        let media = match self.media_type {
+            MediaType::Video => "video",
+            MediaType::Audio => "audio",
+        };
+        write!(sdp, "m={} 0 RTP/AVP {}\r\n", media, payload_type)?;
+        let encoding = match self.frame_type {
+            FrameType::H264 => "H264",
+            FrameType::H265 => "H265",
+            FrameType::AAC => "MPEG4-GENERIC",
+            FrameType::Opus => "opus",
+            FrameType::PCMU => "PCMU",
+            FrameType::PCMA => "PCMA",
+            FrameType::VP8 => "VP8",
+            FrameType::VP9 => "VP9",
+            FrameType::AV1 => "AV1",
+            FrameType::JPEG => "JPEG",
+            FrameType::G711 => "PCMU",
+            FrameType::G722 => "G722",
+            FrameType::G729 => "G729",
+        };
+        write!(sdp, "a=rtpmap:{} {}/{}", payload_type, encoding, self.clock_rate)?;
+        match self.channels {
+            Some(channels) if self.media_type == MediaType::Audio => write!(sdp, "/{}\r\n", channels)?,
+            _ => write!(sdp, "\r\n")?,
+        }
+        match self.frame_type {
+            FrameType::H264 => {
+                write!(sdp, "a=fmtp:{} packetization-mode=1", payload_type)?;
+                if let Some(sps) = self.parameter_sets.first().filter(|sps| sps.len() >= 4) {
+                    write!(sdp, ";profile-level-id={:02X}{:02X}{:02X}", sps[1], sps[2], sps[3])?;
+                }
+                if !self.parameter_sets.is_empty() {
+                    let sets: Vec<_> = self.parameter_sets.iter().map(|s| BASE64_STANDARD.encode(s)).collect();
+                    write!(sdp, ";sprop-parameter-sets={}", sets.join(","))?;
+                }
+                write!(sdp, "\r\n")
+            }
+            FrameType::AAC => {
+                let config: String = self
+                    .parameter_sets
+                    .concat()
+                    .iter()
+                    .map(|b| format!("{:02X}", b))
+                    .collect();
+                write!(
+                    sdp,
+                    "a=fmtp:{} streamtype=5;profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;\
+                     indexdeltalength=3;config={}\r\n",
+                    payload_type, config
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Description of the tracks with [`FIRST_PAYLOAD_TYPE`] on and the
+/// controls `trackID=<n>` the [`Server`](super::Server) expects
+pub fn describe_tracks(tracks: &[SourceTrack]) -> Sdp {
+    let mut sdp = String::from("v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=Recording\r\nt=0 0\r\n");
+    for (i, track) in tracks.iter().enumerate() {
+        // Writing to a String doesn't fail
+        let _ = track.write_media(&mut sdp, FIRST_PAYLOAD_TYPE + i as u8);
+        let _ = write!(sdp, "a=control:trackID={}\r\n", i);
+    }
+    Sdp::try_from(sdp.as_str()).expect("the SDP of the tracks is valid")
+}
+
+/// A frame of a [`MediaReader`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFrame {
+    /// Index of the track in [`MediaReader::tracks`]
+    pub track: usize,
+    /// When the frame is due, from the start of the stream
+    pub time: Duration,
+    /// Its timestamp is in units of the clock rate of the track
+    pub frame: Frame,
+}
+
+/// Reads a stream at the pace of one session, e.g. a recording that every
+/// client plays and seeks on its own, see [`MediaSource::open`](super::MediaSource::open).
+///
+/// Reads are called from the server's tasks and should not block for
+/// long, e.g. read from memory or a local file.
+pub trait MediaReader: Send + 'static {
+    fn tracks(&self) -> &[SourceTrack];
+
+    /// The next frame of any track in the order they are due, None at the
+    /// end of the stream. H.264 frames are in Annex B format, AAC frames
+    /// without ADTS header.
+    fn read_frame(&mut self) -> io::Result<Option<SourceFrame>>;
+
+    /// Continues at the last keyframe at or before `position` and returns
+    /// its time
+    fn seek(&mut self, position: Duration) -> io::Result<Duration>;
+
+    /// Length of the stream, None if unknown
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// An RTP packet of a [`PacketReader`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePacket {
+    pub track: usize,
+    /// When the frame of the packet is due, from the start of the stream
+    pub time: Duration,
+    pub packet: rtp::Packet,
+}
+
+/// Packetizes the frames of a [`MediaReader`] with the payload types of
+/// [`describe_tracks`], one random SSRC per track
+pub struct PacketReader {
+    reader: Box<dyn MediaReader>,
+    packetizers: Vec<Packetizer>,
+    queue: VecDeque<SourcePacket>,
+}
+
+impl PacketReader {
+    pub fn new(reader: Box<dyn MediaReader>) -> Self {
+        let packetizers = reader
+            .tracks()
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let builder = PacketBuilder::new(FIRST_PAYLOAD_TYPE + i as u8, rtp::random_ssrc());
+                Packetizer::new(track.frame_type, builder)
+            })
+            .collect();
+        Self {
+            reader,
+            packetizers,
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn tracks(&self) -> &[SourceTrack] {
+        self.reader.tracks()
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        self.reader.duration()
+    }
+
+    /// The next packet, None at the end of the stream
+    pub fn read_packet(&mut self) -> io::Result<Option<SourcePacket>> {
+        while self.queue.is_empty() {
+            let Some(frame) = self.reader.read_frame()? else {
+                return Ok(None);
+            };
+            let Some(packetizer) = self.packetizers.get_mut(frame.track) else {
+                continue;
+            };
+            let (track, time) = (frame.track, frame.time);
+            let packets = packetizer.packetize(&frame.frame);
+            self.queue
+                .extend(packets.into_iter().map(|packet| SourcePacket { track, time, packet }));
+        }
+        Ok(self.queue.pop_front())
+    }
+
+    /// Drops the packets of the current frame and seeks the reader
+    pub fn seek(&mut self, position: Duration) -> io::Result<Duration> {
+        self.queue.clear();
+        self.reader.seek(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::Codec;
+    use crate::types::FrameMetadata;
+
+    /// A frame per track every 100 ms, keyframes every second
+    struct TestReader {
+        tracks: Vec<SourceTrack>,
+        next: u32,
+    }
+
+    impl MediaReader for TestReader {
+        fn tracks(&self) -> &[SourceTrack] {
+            &self.tracks
+        }
+
+        fn read_frame(&mut self) -> io::Result<Option<SourceFrame>> {
+            let i = self.next;
+            self.next += 1;
+            let nal = if i.is_multiple_of(10) { 0x65 } else { 0x41 };
+            Ok((i < 20).then(|| SourceFrame {
+                track: 0,
+                time: Duration::from_millis(100 * i as u64),
+                frame: Frame {
+                    media_type: MediaType::Video,
+                    frame_type: FrameType::H264,
+                    timestamp: 9000 * i,
+                    data: vec![0, 0, 0, 1, nal, i as u8],
+                    metadata: FrameMetadata::default(),
+                },
+            }))
+        }
+
+        fn seek(&mut self, position: Duration) -> io::Result<Duration> {
+            self.next = (position.as_millis() / 1000 * 10) as u32;
+            Ok(Duration::from_secs(position.as_secs()))
+        }
+    }
+
+    fn h264() -> SourceTrack {
+        SourceTrack {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            clock_rate: 90_000,
+            channels: None,
+            parameter_sets: vec![vec![0x67, 0x42, 0, 0x1e], vec![0x68, 0xce, 6, 0xe2]],
+        }
+    }
+
+    #[test]
+    fn test_describe_tracks() {
+        let aac = SourceTrack {
+            media_type: MediaType::Audio,
+            frame_type: FrameType::AAC,
+            clock_rate: 48_000,
+            channels: Some(2),
+            parameter_sets: vec![vec![0x11, 0x90]],
+        };
+        let sdp = describe_tracks(&[h264(), aac]);
+        assert_eq!(sdp.media.len(), 2);
+        assert_eq!(sdp.media[0].control(), Some("trackID=0"));
+        assert_eq!(sdp.media[1].control(), Some("trackID=1"));
+        let text = sdp.to_string();
+        assert!(text
+            .contains("a=fmtp:96 packetization-mode=1;profile-level-id=42001E;sprop-parameter-sets=Z0IAHg==,aM4G4g=="));
+        assert!(text.contains("a=rtpmap:97 MPEG4-GENERIC/48000/2"));
+        assert!(text.contains(";config=1190"));
+        assert_eq!(sdp.media[0].rtpmap(96).unwrap().codec, Codec::H264);
+        assert_eq!(sdp.media[1].rtpmap(97).unwrap().codec, Codec::AAC);
+    }
+
+    #[test]
+    fn test_packet_reader() {
+        let reader = TestReader {
+            tracks: vec![h264()],
+            next: 0,
+        };
+        let mut reader = PacketReader::new(Box::new(reader));
+        let first = reader.read_packet().unwrap().unwrap();
+        assert_eq!((first.track, first.time), (0, Duration::ZERO));
+        assert_eq!(first.packet.payload_type(), FIRST_PAYLOAD_TYPE);
+        assert_eq!(first.packet.data(), [0x65, 0]);
+
+        assert_eq!(
+            reader.seek(Duration::from_millis(1500)).unwrap(),
+            Duration::from_secs(1)
+        );
+        let packet = reader.read_packet().unwrap().unwrap();
+        assert_eq!(packet.time, Duration::from_secs(1));
+        assert_eq!(packet.packet.data(), [0x65, 10]);
+        assert_eq!(packet.packet.ssrc(), first.packet.ssrc());
+        let rest = std::iter::from_fn(|| reader.read_packet().unwrap()).count();
+        assert_eq!(rest, 9);
+    }
+}
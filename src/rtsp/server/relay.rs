@@ -0,0 +1,148 @@
+use super::MediaSource;
+use crate::rtp;
+use crate::sdp::Sdp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+/// Packets a [`LiveRelay`] buffers per track for slow sessions
+const RELAY_CAPACITY: usize = 512;
+
+struct Published {
+    sdp: Sdp,
+    tracks: Vec<broadcast::Sender<rtp::Packet>>,
+    // Identifies the feed that published the stream
+    feed: Arc<()>,
+}
+
+/// Serves live streams that are pushed into it, e.g. the packets a client
+/// receives from a camera, to all sessions of a [`Server`](super::Server).
+///
+/// ```ignore
+/// let relay = LiveRelay::new();
+/// let feed = relay.publish("/camera1", camera_sdp);
+/// task::spawn(feed.forward(demuxed_rx));
+/// Server::bind("0.0.0.0:8554", relay).await?.start();
+/// ```
+#[derive(Default, Clone)]
+pub struct LiveRelay {
+    streams: Arc<Mutex<HashMap<String, Published>>>,
+}
+
+impl LiveRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes a stream at `path`, replacing a previous one. The controls
+    /// of the media sections are rewritten to `trackID=<n>`. The stream is
+    /// removed when the returned feed is dropped.
+    pub fn publish(&self, path: &str, mut sdp: Sdp) -> RelayFeed {
+        sdp.attributes.retain(|(name, _)| name != "control");
+        for (i, media) in sdp.media.iter_mut().enumerate() {
+            media.attributes.retain(|(name, _)| name != "control");
+            media
+                .attributes
+                .push(("control".to_string(), Some(format!("trackID={}", i))));
+        }
+        let tracks: Vec<_> = sdp.media.iter().map(|_| broadcast::channel(RELAY_CAPACITY).0).collect();
+        let feed = RelayFeed {
+            relay: self.clone(),
+            path: path.to_string(),
+            tracks: tracks.clone(),
+            id: Arc::new(()),
+        };
+        let published = Published {
+            sdp,
+            tracks,
+            feed: feed.id.clone(),
+        };
+        self.streams.lock().unwrap().insert(path.to_string(), published);
+        feed
+    }
+
+    /// Whether a stream is published at `path`
+    pub fn contains(&self, path: &str) -> bool {
+        self.streams.lock().unwrap().contains_key(path)
+    }
+}
+
+impl MediaSource for LiveRelay {
+    fn describe(&self, path: &str) -> Option<Sdp> {
+        self.streams.lock().unwrap().get(path).map(|s| s.sdp.clone())
+    }
+
+    fn subscribe(&self, path: &str, track: usize) -> Option<broadcast::Receiver<rtp::Packet>> {
+        let streams = self.streams.lock().unwrap();
+        streams.get(path)?.tracks.get(track).map(|tx| tx.subscribe())
+    }
+}
+
+/// Pushes the packets of a stream published on a [`LiveRelay`]
+pub struct RelayFeed {
+    relay: LiveRelay,
+    path: String,
+    tracks: Vec<broadcast::Sender<rtp::Packet>>,
+    id: Arc<()>,
+}
+
+impl RelayFeed {
+    /// Sends a packet of the track to every session playing it, packets
+    /// nobody plays are dropped
+    pub fn push(&self, track: usize, packet: rtp::Packet) {
+        if let Some(tx) = self.tracks.get(track) {
+            let _ = tx.send(packet);
+        }
+    }
+
+    /// Relays the packets of a [`Demuxer`](rtp::Demuxer) until the sender
+    /// is dropped, tracks in SETUP order map to the media sections
+    pub async fn forward(self, mut packet_rx: mpsc::Receiver<rtp::DemuxedPacket>) {
+        while let Some(demuxed) = packet_rx.recv().await {
+            self.push(demuxed.track, demuxed.packet.packet);
+        }
+    }
+}
+
+impl Drop for RelayFeed {
+    fn drop(&mut self) {
+        let mut streams = self.relay.streams.lock().unwrap();
+        // A newer feed may have replaced this one
+        if streams.get(&self.path).is_some_and(|s| Arc::ptr_eq(&s.feed, &self.id)) {
+            streams.remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDP: &str = "v=0\r\ns=Camera\r\na=control:*\r\nm=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\n\
+                       a=control:rtsp://camera/stream/video\r\nm=audio 0 RTP/AVP 0\r\na=control:audio\r\n";
+
+    #[test]
+    fn test_live_relay() {
+        let relay = LiveRelay::new();
+        let feed = relay.publish("/camera", Sdp::try_from(SDP).unwrap());
+        let sdp = relay.describe("/camera").unwrap();
+        assert_eq!(sdp.attribute("control"), None);
+        assert_eq!(sdp.media[0].control(), Some("trackID=0"));
+        assert_eq!(sdp.media[1].control(), Some("trackID=1"));
+        assert!(relay.subscribe("/camera", 2).is_none());
+
+        let mut rx = relay.subscribe("/camera", 1).unwrap();
+        let packet = rtp::Packet::new(vec![0x80, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+        feed.push(1, packet.clone());
+        feed.push(0, packet.clone());
+        assert_eq!(rx.try_recv().unwrap(), packet);
+        assert!(rx.try_recv().is_err());
+
+        // Only the current feed of a path unpublishes it
+        let newer = relay.publish("/camera", Sdp::try_from(SDP).unwrap());
+        drop(feed);
+        assert!(relay.contains("/camera"));
+        drop(newer);
+        assert!(!relay.contains("/camera"));
+    }
+}
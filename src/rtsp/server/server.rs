@@ -0,0 +1,759 @@
+use super::session::{Output, Session, Sessions};
+use super::{MediaSource, PacketReader};
+use crate::rtsp::client::bind_pair;
+use crate::rtsp::{
+    Buffer, BufferError, IncomingRequest, LowerTransport, Method, ParseError, Range, ResponseBuilder, Status, Transport,
+};
+use crate::task;
+use crate::trace;
+use bytes::Bytes;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Out of buffer space")]
+    BufferError(#[from] BufferError),
+    #[error(transparent)]
+    ParseRequest(#[from] ParseError),
+    #[error("Request too long")]
+    RequestTooLong,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Sessions without a request or RTCP packet of the client for this long
+/// are closed unless configured with [`Server::session_timeout`]
+const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Requests larger than this are rejected instead of buffered
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+const PUBLIC_METHODS: &str = "OPTIONS, DESCRIBE, SETUP, PLAY, TEARDOWN";
+
+/// RTSP server streaming the tracks of a [`MediaSource`] to its clients,
+/// interleaved on the RTSP connection or over UDP.
+pub struct Server {
+    listener: TcpListener,
+    source: Arc<dyn MediaSource>,
+    sessions: Sessions,
+    session_timeout: Duration,
+}
+
+impl Server {
+    pub async fn bind(addr: impl ToSocketAddrs, source: impl MediaSource) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            source: Arc::new(source),
+            sessions: Sessions::default(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+        })
+    }
+
+    /// Closes sessions the client didn't keep alive for `timeout`, it is
+    /// advertised in the Session header of SETUP responses
+    pub fn session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, peer)) => {
+                    log::debug!("Accepted RTSP connection from {}", peer);
+                    let sessions = self.sessions.clone();
+                    let connection = Connection::new(stream, peer, self.source.clone(), sessions, self.session_timeout);
+                    #[cfg(feature = "tracing")]
+                    let run = tracing::Instrument::instrument(
+                        connection.run(),
+                        tracing::info_span!("rtsp_server_connection", peer = %peer),
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    let run = connection.run();
+                    task::spawn(task::SERVER_CONNECTION, run);
+                }
+                Err(e) => log::warn!("Failed to accept RTSP connection: {}", e),
+            }
+        }
+    }
+
+    /// Accepts connections until the returned handle is aborted. Aborting
+    /// doesn't close connections that were already accepted.
+    pub fn start(self) -> tokio::task::JoinHandle<()> {
+        task::spawn(task::SERVER, self.run())
+    }
+}
+
+/// Splits a track url path like `/live/trackID=1` into stream path and track index
+fn split_track(path: &str) -> Option<(&str, usize)> {
+    let (path, track) = path.rsplit_once('/')?;
+    Some((path, track.strip_prefix("trackID=")?.parse().ok()?))
+}
+
+/// The interleaved channels of a track the client didn't choose any for,
+/// None if they don't fit
+fn interleaved_channels(track: usize) -> Option<(u8, u8)> {
+    let rtp = u8::try_from(track.checked_mul(2)?).ok()?;
+    Some((rtp, rtp.checked_add(1)?))
+}
+
+struct Connection {
+    stream: TcpStream,
+    peer: SocketAddr,
+    source: Arc<dyn MediaSource>,
+    sessions: Sessions,
+    session_timeout: Duration,
+    // Sessions created on this connection, they end with it
+    owned: Vec<String>,
+    buffer_rx: Buffer,
+    // Serialized responses, reused for every response of the connection
+    buffer_tx: String,
+    // Interleaved frames of the playing sessions
+    frame_tx: mpsc::Sender<Bytes>,
+    frame_rx: mpsc::Receiver<Bytes>,
+}
+
+impl Connection {
+    fn new(
+        stream: TcpStream,
+        peer: SocketAddr,
+        source: Arc<dyn MediaSource>,
+        sessions: Sessions,
+        session_timeout: Duration,
+    ) -> Self {
+        let (frame_tx, frame_rx) = mpsc::channel(256);
+        Self {
+            stream,
+            peer,
+            source,
+            sessions,
+            session_timeout,
+            owned: Vec::new(),
+            buffer_rx: Buffer::new(2 * MAX_REQUEST_SIZE),
+            buffer_tx: String::new(),
+            frame_tx,
+            frame_rx,
+        }
+    }
+
+    /// The session of the request, which keeps it alive
+    fn session_id(&self, request: &IncomingRequest) -> Option<String> {
+        let id = request.header("Session")?.split(';').next()?.trim();
+        let touched = self.sessions.with(id, |s| s.last_seen = Instant::now());
+        touched.is_some().then(|| id.to_string())
+    }
+
+    /// When the first session of the connection times out
+    fn session_deadline(&self) -> Option<Instant> {
+        let last_seen = self
+            .owned
+            .iter()
+            .filter_map(|id| self.sessions.with(id, |s| s.last_seen));
+        last_seen.min().map(|last_seen| last_seen + self.session_timeout)
+    }
+
+    fn expire_sessions(&mut self) {
+        let now = Instant::now();
+        let (sessions, timeout) = (&self.sessions, self.session_timeout);
+        self.owned.retain(|id| {
+            let expired = sessions.with(id, |s| s.last_seen + timeout <= now).unwrap_or(true);
+            if expired {
+                log::info!("Session {} timed out", id);
+                sessions.remove(id);
+            }
+            !expired
+        });
+    }
+
+    fn describe(&self, request: &IncomingRequest, url: &Url) -> ResponseBuilder {
+        match self.source.describe(url.path()) {
+            Some(sdp) => ResponseBuilder::new(Status::OK, request.cseq)
+                .header("Content-Base", format!("{}/", url.as_str().trim_end_matches('/')))
+                .body("application/sdp", sdp.to_string()),
+            None => ResponseBuilder::new(Status::NotFound, request.cseq),
+        }
+    }
+
+    async fn output(&self, transport: &Transport, track: usize) -> Option<(Output, Transport)> {
+        if transport.multicast {
+            return None;
+        }
+        match transport.lower {
+            LowerTransport::Tcp => {
+                let channels = transport.interleaved.or_else(|| interleaved_channels(track))?;
+                let output = Output::Interleaved {
+                    frame_tx: self.frame_tx.clone(),
+                    channel: channels.0,
+                };
+                Some((output, Transport::tcp(channels)))
+            }
+            LowerTransport::Udp => {
+                let client_port = transport.client_port?;
+                let local = self.stream.local_addr().ok()?;
+                let (socket, rtcp) = match bind_pair(local.ip()).await {
+                    Ok(pair) => pair,
+                    Err(e) => {
+                        log::warn!("Failed to bind UDP ports: {}", e);
+                        return None;
+                    }
+                };
+                let server_port = socket.local_addr().ok()?.port();
+                let mut reply = Transport::udp(client_port);
+                reply.server_port = Some((server_port, server_port + 1));
+                let output = Output::Udp {
+                    socket,
+                    _rtcp: rtcp,
+                    target: SocketAddr::new(self.peer.ip(), client_port.0),
+                };
+                Some((output, reply))
+            }
+        }
+    }
+
+    async fn setup(&mut self, request: &IncomingRequest, url: &Url) -> ResponseBuilder {
+        let cseq = request.cseq;
+        let Some((path, track)) = split_track(url.path()) else {
+            return ResponseBuilder::new(Status::NotFound, cseq);
+        };
+        if self.source.describe(path).is_none_or(|sdp| track >= sdp.media.len()) {
+            return ResponseBuilder::new(Status::NotFound, cseq);
+        }
+        // Clients may offer several transports, the first usable one is taken
+        let transport = request
+            .header("Transport")
+            .and_then(|t| t.split(',').find_map(|t| t.parse::<Transport>().ok()));
+        let Some(transport) = transport else {
+            return ResponseBuilder::new(Status::UnsupportedTransport, cseq);
+        };
+        let id = match request.header("Session") {
+            Some(_) => match self.session_id(request) {
+                Some(id) => id,
+                None => return ResponseBuilder::new(Status::SessionNotFound, cseq),
+            },
+            None => {
+                let id = format!("{:016X}", rand::random::<u64>());
+                self.sessions.insert(id.clone(), Session::new(path));
+                self.owned.push(id.clone());
+                id
+            }
+        };
+        let Some((output, reply)) = self.output(&transport, track).await else {
+            return ResponseBuilder::new(Status::UnsupportedTransport, cseq);
+        };
+        let added = self.sessions.with(&id, |session| {
+            if session.path != path || session.is_playing() {
+                return false;
+            }
+            session.tracks.insert(track, output);
+            true
+        });
+        if added != Some(true) {
+            return ResponseBuilder::new(Status::MethodNotValidInThisState, cseq);
+        }
+        ResponseBuilder::new(Status::OK, cseq)
+            .header(
+                "Session",
+                format!("{};timeout={}", id, self.session_timeout.as_secs().max(1)),
+            )
+            .header("Transport", reply)
+    }
+
+    /// Starts the session, a PLAY of a session that plays a recording seeks
+    /// to the start of its `Range`
+    async fn play(&self, request: &IncomingRequest) -> ResponseBuilder {
+        let cseq = request.cseq;
+        let Some(id) = self.session_id(request) else {
+            return ResponseBuilder::new(Status::SessionNotFound, cseq);
+        };
+        let start = match request.header("Range").map(|range| range.parse::<Range>()) {
+            None => None,
+            Some(Ok(Range::Npt { start, .. })) => start,
+            Some(_) => return ResponseBuilder::new(Status::InvalidRange, cseq),
+        };
+        if let (Some(position), Some(seek_tx)) = (start, self.sessions.with(&id, |s| s.seek_tx()).flatten()) {
+            let (tx, rx) = oneshot::channel();
+            let seeked = match seek_tx.send((position, tx)).await {
+                Ok(()) => rx.await.ok(),
+                Err(_) => None,
+            };
+            return match seeked {
+                Some(Ok(position)) => ResponseBuilder::new(Status::OK, cseq)
+                    .header("Session", id)
+                    .header("Range", Range::npt(position)),
+                _ => ResponseBuilder::new(Status::InvalidRange, cseq),
+            };
+        }
+        let (path, tracks) = self
+            .sessions
+            .with(&id, |s| (s.path.clone(), s.tracks.keys().copied().collect::<Vec<_>>()))
+            .unwrap_or_default();
+        if tracks.is_empty() {
+            let playing = self.sessions.with(&id, |s| s.is_playing()).unwrap_or(false);
+            return match playing {
+                true => ResponseBuilder::new(Status::OK, cseq).header("Session", id),
+                false => ResponseBuilder::new(Status::MethodNotValidInThisState, cseq),
+            };
+        }
+        if let Some(reader) = self.source.open(&path) {
+            let mut reader = PacketReader::new(reader);
+            let position = match start.map(|position| reader.seek(position)) {
+                None => Duration::ZERO,
+                Some(Ok(position)) => position,
+                Some(Err(e)) => {
+                    log::warn!("Failed to seek {} to {:?}: {}", path, start, e);
+                    return ResponseBuilder::new(Status::InvalidRange, cseq);
+                }
+            };
+            let range = Range::Npt {
+                start: Some(position),
+                end: reader.duration(),
+            };
+            self.sessions.with(&id, |s| s.play_reader(reader));
+            return ResponseBuilder::new(Status::OK, cseq)
+                .header("Session", id)
+                .header("Range", range);
+        }
+        for track in tracks {
+            match self.source.subscribe(&path, track) {
+                Some(packet_rx) => {
+                    self.sessions.with(&id, |s| s.play(track, packet_rx));
+                }
+                None => return ResponseBuilder::new(Status::NotFound, cseq),
+            }
+        }
+        ResponseBuilder::new(Status::OK, cseq)
+            .header("Session", id)
+            .header("Range", "npt=0.000-")
+    }
+
+    fn teardown(&mut self, request: &IncomingRequest) -> ResponseBuilder {
+        let Some(id) = self.session_id(request) else {
+            return ResponseBuilder::new(Status::SessionNotFound, request.cseq);
+        };
+        self.sessions.remove(&id);
+        self.owned.retain(|owned| *owned != id);
+        ResponseBuilder::new(Status::OK, request.cseq)
+    }
+
+    async fn handle_request(&mut self, request: IncomingRequest) -> ResponseBuilder {
+        let cseq = request.cseq;
+        // Any request of a session keeps it alive, e.g. an OPTIONS keep-alive
+        self.session_id(&request);
+        let Some(method) = request.method else {
+            return ResponseBuilder::new(Status::NotImplemented, cseq);
+        };
+        if method == Method::Options {
+            return ResponseBuilder::new(Status::OK, cseq).header("Public", PUBLIC_METHODS);
+        }
+        let Ok(url) = Url::parse(&request.uri) else {
+            return ResponseBuilder::new(Status::BadRequest, cseq);
+        };
+        match method {
+            Method::Describe => self.describe(&request, &url),
+            Method::Setup => self.setup(&request, &url).await,
+            Method::Play => self.play(&request).await,
+            Method::Teardown => self.teardown(&request),
+            _ => ResponseBuilder::new(Status::MethodNotAllowed, cseq).header("Allow", PUBLIC_METHODS),
+        }
+    }
+
+    async fn send_response(&mut self, response: ResponseBuilder) -> Result<()> {
+        #[cfg(not(feature = "tracing"))]
+        log::debug!("Sending {} to {}", response.status(), self.peer);
+        trace::event!(debug, status = %response.status(), "Sending response");
+        self.buffer_tx.clear();
+        let _ = write!(self.buffer_tx, "{}", response);
+        self.stream.write_all(self.buffer_tx.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn handle_data(&mut self) -> Result<()> {
+        loop {
+            let read_buf = self.buffer_rx.get_read_slice();
+            if read_buf.is_empty() {
+                return Ok(());
+            }
+            // Interleaved RTCP of the client, e.g. receiver reports
+            if read_buf[0] == b'$' {
+                if read_buf.len() < 4 {
+                    return Ok(());
+                }
+                let len = 4 + u16::from_be_bytes([read_buf[2], read_buf[3]]) as usize;
+                if read_buf.len() < len {
+                    return Ok(());
+                }
+                self.buffer_rx.notify_read(len);
+                for id in &self.owned {
+                    self.sessions.with(id, |s| s.last_seen = Instant::now());
+                }
+                continue;
+            }
+            match IncomingRequest::parse(read_buf) {
+                Ok(Some((request, n))) => {
+                    self.buffer_rx.notify_read(n);
+                    trace::event!(
+                        debug,
+                        cseq = ?request.cseq,
+                        method = ?request.method,
+                        uri = %request.uri,
+                        "Received request"
+                    );
+                    let response = self.handle_request(request).await;
+                    self.send_response(response).await?;
+                }
+                Ok(None) if read_buf.len() > MAX_REQUEST_SIZE => {
+                    self.send_response(ResponseBuilder::new(Status::RequestEntityTooLarge, None))
+                        .await?;
+                    return Err(Error::RequestTooLong);
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    trace::event!(debug, error = %e, data = %trace::hex_dump(read_buf), "Failed to parse request");
+                    self.send_response(ResponseBuilder::new(Status::BadRequest, None))
+                        .await?;
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> Result<()> {
+        loop {
+            let deadline = self.session_deadline();
+            let read_buf = self.buffer_rx.get_write_slice(4096)?;
+            tokio::select! {
+                result = self.stream.read(read_buf) => {
+                    let n = result?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    self.buffer_rx.notify_write(n);
+                    self.handle_data().await?;
+                }
+                Some(frame) = self.frame_rx.recv() => {
+                    self.stream.write_all(&frame).await?;
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.expire_sessions();
+                }
+            }
+        }
+    }
+
+    async fn run(mut self) {
+        match self.poll().await {
+            Ok(()) => log::debug!("RTSP connection from {} closed", self.peer),
+            Err(e) => log::warn!("RTSP connection from {} failed: {}", self.peer, e),
+        }
+        for id in &self.owned {
+            self.sessions.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp;
+    use crate::rtsp::client::{
+        Channel, Command, CommandError, CommandResult, Describe, Options, Play, PlayResponse, Request as ClientRequest,
+        Setup, SetupResponse, Teardown,
+    };
+    use crate::rtsp::server::{describe_tracks, MediaReader, SourceFrame, SourceTrack};
+    use crate::sdp::Sdp;
+    use crate::testing::{test_packet, TestSource};
+    use crate::types::{Frame, FrameMetadata, FrameType, MediaType};
+    use tokio::net::UdpSocket;
+    use tokio::sync::{broadcast, oneshot};
+
+    /// A recording of 3 seconds with a frame every 100 ms and a keyframe
+    /// every second, the frames carry their index
+    struct Recording {
+        tracks: Vec<SourceTrack>,
+        next: u32,
+    }
+
+    impl Recording {
+        fn new() -> Self {
+            let track = SourceTrack {
+                media_type: MediaType::Video,
+                frame_type: FrameType::H264,
+                clock_rate: 90_000,
+                channels: None,
+                parameter_sets: Vec::new(),
+            };
+            Self {
+                tracks: vec![track],
+                next: 0,
+            }
+        }
+    }
+
+    impl MediaReader for Recording {
+        fn tracks(&self) -> &[SourceTrack] {
+            &self.tracks
+        }
+
+        fn read_frame(&mut self) -> std::io::Result<Option<SourceFrame>> {
+            let i = self.next;
+            self.next += 1;
+            let nal = if i.is_multiple_of(10) { 0x65 } else { 0x41 };
+            Ok((i < 30).then(|| SourceFrame {
+                track: 0,
+                time: Duration::from_millis(100 * i as u64),
+                frame: Frame {
+                    media_type: MediaType::Video,
+                    frame_type: FrameType::H264,
+                    timestamp: 9000 * i,
+                    data: vec![0, 0, 0, 1, nal, i as u8],
+                    metadata: FrameMetadata::default(),
+                },
+            }))
+        }
+
+        fn seek(&mut self, position: Duration) -> std::io::Result<Duration> {
+            self.next = position.as_secs() as u32 * 10;
+            Ok(Duration::from_secs(position.as_secs()))
+        }
+
+        fn duration(&self) -> Option<Duration> {
+            Some(Duration::from_secs(3))
+        }
+    }
+
+    struct RecordingSource;
+
+    impl MediaSource for RecordingSource {
+        fn describe(&self, path: &str) -> Option<Sdp> {
+            (path == "/live").then(|| describe_tracks(&Recording::new().tracks))
+        }
+
+        fn subscribe(&self, _path: &str, _track: usize) -> Option<broadcast::Receiver<rtp::Packet>> {
+            None
+        }
+
+        fn open(&self, path: &str) -> Option<Box<dyn MediaReader>> {
+            (path == "/live").then(|| Box::new(Recording::new()) as Box<dyn MediaReader>)
+        }
+    }
+
+    struct TestClient {
+        cmd_tx: mpsc::Sender<Command>,
+        packet_rx: mpsc::Receiver<rtp::Packet>,
+        url: Url,
+    }
+
+    impl TestClient {
+        async fn connect(addr: SocketAddr) -> Self {
+            let (cmd_tx, cmd_rx) = mpsc::channel(8);
+            let (packet_tx, packet_rx) = mpsc::channel(8);
+            let stream = TcpStream::connect(addr).await.unwrap();
+            Channel::new(stream, cmd_rx, packet_tx).start();
+            let url = Url::parse(&format!("rtsp://{}/live", addr)).unwrap();
+            Self { cmd_tx, packet_rx, url }
+        }
+
+        async fn request<T>(
+            &self,
+            request: impl FnOnce(oneshot::Sender<CommandResult<T>>) -> ClientRequest,
+        ) -> CommandResult<T> {
+            let (tx, rx) = oneshot::channel();
+            self.cmd_tx.send(Command::Request(request(tx))).await.unwrap();
+            rx.await.unwrap()
+        }
+
+        async fn setup(&self, transport: Transport) -> CommandResult<SetupResponse> {
+            let url = self.url.join("live/trackID=0").unwrap();
+            self.request(|tx| ClientRequest::Setup(Setup::new(url, transport, tx)))
+                .await
+        }
+
+        async fn play(&self) -> CommandResult<PlayResponse> {
+            let url = self.url.clone();
+            self.request(|tx| ClientRequest::Play(Play::new(url, tx))).await
+        }
+
+        async fn play_from(&self, start: Duration) -> CommandResult<PlayResponse> {
+            let url = self.url.clone();
+            self.request(|tx| ClientRequest::Play(Play::new(url, tx).range(Range::npt(start))))
+                .await
+        }
+    }
+
+    async fn start_server() -> (SocketAddr, broadcast::Sender<rtp::Packet>) {
+        let (tx, _) = broadcast::channel(16);
+        let server = Server::bind("127.0.0.1:0", TestSource::new(tx.clone())).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        server.start();
+        (addr, tx)
+    }
+
+    #[tokio::test]
+    async fn test_server_interleaved() {
+        let (addr, tx) = start_server().await;
+        let mut client = TestClient::connect(addr).await;
+        let url = client.url.clone();
+        let description = client
+            .request(|tx| ClientRequest::Describe(Describe::new(url, tx)))
+            .await
+            .unwrap();
+        assert_eq!(description.into_sdp().unwrap().media.len(), 1);
+
+        let response = client.setup(Transport::tcp((4, 5))).await.unwrap();
+        assert_eq!(response.transport.interleaved, Some((4, 5)));
+        client.play().await.unwrap();
+        tx.send(test_packet(7)).unwrap();
+        let received = client.packet_rx.recv().await.unwrap();
+        assert_eq!(received, test_packet(7));
+
+        let url = client.url.clone();
+        client
+            .request(|tx| ClientRequest::Teardown(Teardown::new(url, tx)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_udp() {
+        let (addr, tx) = start_server().await;
+        let client = TestClient::connect(addr).await;
+        let (rtp_socket, _rtcp_socket) = bind_pair(addr.ip()).await.unwrap();
+        let port = rtp_socket.local_addr().unwrap().port();
+        let response = client.setup(Transport::udp((port, port + 1))).await.unwrap();
+        let (server_port, _) = response.transport.server_port.unwrap();
+        client.play().await.unwrap();
+        tx.send(test_packet(9)).unwrap();
+        let mut buf = [0u8; 1500];
+        let (n, from) = UdpSocket::recv_from(&rtp_socket, &mut buf).await.unwrap();
+        assert_eq!(from.port(), server_port);
+        assert_eq!(&buf[..n], test_packet(9).as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_server_recording() {
+        let server = Server::bind("127.0.0.1:0", RecordingSource).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        server.start();
+        let mut client = TestClient::connect(addr).await;
+        client.setup(Transport::tcp((0, 1))).await.unwrap();
+
+        // Playing starts at the keyframe before the requested start
+        let response = client.play_from(Duration::from_millis(1500)).await.unwrap();
+        let expected = Range::Npt {
+            start: Some(Duration::from_secs(1)),
+            end: Some(Duration::from_secs(3)),
+        };
+        assert_eq!(response.range, Some(expected));
+        let first = client.packet_rx.recv().await.unwrap();
+        assert_eq!(first.payload_type(), 96);
+        assert_eq!(first.data(), [0x65, 10]);
+
+        // Another PLAY seeks the same session
+        let response = client.play_from(Duration::from_millis(2200)).await.unwrap();
+        assert_eq!(response.range, Some(Range::npt(Duration::from_secs(2))));
+        loop {
+            let packet = client.packet_rx.recv().await.unwrap();
+            if packet.data() == [0x65, 20] {
+                assert_eq!(packet.ssrc(), first.ssrc());
+                break;
+            }
+            assert!(packet.data()[1] < 13, "frame {} after the seek", packet.data()[1]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_errors() {
+        let (addr, _tx) = start_server().await;
+        let client = TestClient::connect(addr).await;
+        let url = client.url.join("missing").unwrap();
+        let result = client
+            .request(|tx| ClientRequest::Describe(Describe::new(url, tx)))
+            .await;
+        assert!(matches!(result, Err(CommandError::UnexpectedStatus(Status::NotFound))));
+        let result = client.play().await;
+        assert!(matches!(
+            result,
+            Err(CommandError::UnexpectedStatus(Status::SessionNotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_session_timeout() {
+        let (tx, _) = broadcast::channel(16);
+        let server = Server::bind("127.0.0.1:0", TestSource::new(tx))
+            .await
+            .unwrap()
+            .session_timeout(Duration::from_millis(300));
+        let addr = server.local_addr().unwrap();
+        server.start();
+        let client = TestClient::connect(addr).await;
+        client.setup(Transport::tcp((0, 1))).await.unwrap();
+        // Requests of the session keep it alive
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let url = client.url.clone();
+            client
+                .request(|tx| ClientRequest::Options(Options::new(url, tx)))
+                .await
+                .unwrap();
+        }
+        client.play().await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let result = client.play().await;
+        assert!(matches!(
+            result,
+            Err(CommandError::UnexpectedStatus(Status::SessionNotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_unknown_method() {
+        let (addr, _tx) = start_server().await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let requests = "FETCH rtsp://host/live RTSP/1.0\r\nCSeq: 1\r\nContent-Length: 9\r\n\r\nOPTIONS *\
+                        OPTIONS * RTSP/1.0\r\nCSeq: 2\r\n\r\n";
+        stream.write_all(requests.as_bytes()).await.unwrap();
+        let mut responses = String::new();
+        let mut buf = [0u8; 1024];
+        while responses.matches("\r\n\r\n").count() < 2 {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert_ne!(n, 0);
+            responses.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+        let (first, second) = responses.split_once("\r\n\r\n").unwrap();
+        assert!(first.starts_with("RTSP/1.0 501"));
+        assert!(first.contains("\r\nCSeq: 1"));
+        assert!(second.starts_with("RTSP/1.0 200"));
+        assert!(second.contains("\r\nCSeq: 2"));
+    }
+
+    #[test]
+    fn test_split_track() {
+        assert_eq!(split_track("/live/trackID=2"), Some(("/live", 2)));
+        assert_eq!(split_track("/live"), None);
+    }
+
+    #[test]
+    fn test_interleaved_channels() {
+        assert_eq!(interleaved_channels(1), Some((2, 3)));
+        assert_eq!(interleaved_channels(127), Some((254, 255)));
+        assert_eq!(interleaved_channels(128), None);
+        assert_eq!(interleaved_channels(usize::MAX), None);
+    }
+}
@@ -0,0 +1,191 @@
+use super::PacketReader;
+use crate::rtp;
+use crate::task;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// A seek of a playing reader, answered with the position it continues at
+pub type Seek = (Duration, oneshot::Sender<io::Result<Duration>>);
+
+/// Where the packets of a track are sent to
+pub enum Output {
+    /// Interleaved frames on the RTSP connection, written by the connection task
+    Interleaved { frame_tx: mpsc::Sender<Bytes>, channel: u8 },
+    Udp {
+        socket: UdpSocket,
+        // Kept bound so the advertised RTCP port stays reserved, reports are ignored
+        _rtcp: UdpSocket,
+        target: SocketAddr,
+    },
+}
+
+impl Output {
+    async fn send(&self, packet: &rtp::Packet) -> bool {
+        match self {
+            Output::Interleaved { frame_tx, channel } => {
+                let data = packet.as_bytes();
+                let Ok(len) = u16::try_from(data.len()) else {
+                    log::warn!(
+                        "Dropped RTP packet of {} bytes, too large for an interleaved frame",
+                        data.len()
+                    );
+                    return true;
+                };
+                let mut frame = Vec::with_capacity(4 + data.len());
+                frame.extend_from_slice(&[b'$', *channel]);
+                frame.extend_from_slice(&len.to_be_bytes());
+                frame.extend_from_slice(data);
+                frame_tx.send(frame.into()).await.is_ok()
+            }
+            Output::Udp { socket, target, .. } => match socket.send_to(packet.as_bytes(), target).await {
+                Ok(_) => true,
+                Err(e) => {
+                    log::warn!("Failed to send RTP packet to {}: {}", target, e);
+                    true
+                }
+            },
+        }
+    }
+}
+
+/// A client session, created by the first SETUP
+pub struct Session {
+    pub path: String,
+    pub tracks: BTreeMap<usize, Output>,
+    /// When the last request or RTCP packet of the client was received
+    pub last_seen: Instant,
+    streams: Vec<JoinHandle<()>>,
+    // Seeks the reader of a session playing a recording
+    seek_tx: Option<mpsc::Sender<Seek>>,
+}
+
+impl Session {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            tracks: BTreeMap::new(),
+            last_seen: Instant::now(),
+            streams: Vec::new(),
+            seek_tx: None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    /// Starts forwarding the packets of the track to its output
+    pub fn play(&mut self, track: usize, packet_rx: broadcast::Receiver<rtp::Packet>) {
+        if let Some(output) = self.tracks.remove(&track) {
+            self.streams
+                .push(task::spawn(task::SERVER_STREAM, forward(packet_rx, output)));
+        }
+    }
+
+    /// Starts sending the packets of the reader to the outputs of all
+    /// tracks, at the pace of their times
+    pub fn play_reader(&mut self, reader: PacketReader) {
+        let outputs = std::mem::take(&mut self.tracks);
+        let (seek_tx, seek_rx) = mpsc::channel(1);
+        self.seek_tx = Some(seek_tx);
+        self.streams.push(task::spawn(
+            task::SERVER_STREAM,
+            stream_reader(reader, outputs, seek_rx),
+        ));
+    }
+
+    /// Seeks the reader, None unless playing a reader
+    pub fn seek_tx(&self) -> Option<mpsc::Sender<Seek>> {
+        self.seek_tx.clone()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for stream in &self.streams {
+            stream.abort();
+        }
+    }
+}
+
+async fn forward(mut packet_rx: broadcast::Receiver<rtp::Packet>, output: Output) {
+    loop {
+        match packet_rx.recv().await {
+            Ok(packet) => {
+                if !output.send(&packet).await {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => log::warn!("Client too slow, skipped {} packets", n),
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn stream_reader(mut reader: PacketReader, outputs: BTreeMap<usize, Output>, mut seek_rx: mpsc::Receiver<Seek>) {
+    // Time of the first packet after the start or a seek and when it was sent
+    let mut origin: Option<(Duration, Instant)> = None;
+    loop {
+        let packet = match reader.read_packet() {
+            Ok(Some(packet)) => packet,
+            // Clients may still seek back at the end of a recording
+            Ok(None) => match seek_rx.recv().await {
+                Some((position, tx)) => {
+                    let _ = tx.send(reader.seek(position));
+                    origin = None;
+                    continue;
+                }
+                None => break,
+            },
+            Err(e) => {
+                log::warn!("Failed to read media: {}", e);
+                break;
+            }
+        };
+        let (start, started) = *origin.get_or_insert((packet.time, Instant::now()));
+        let due = started + packet.time.saturating_sub(start);
+        tokio::select! {
+            _ = tokio::time::sleep_until(due) => {}
+            Some((position, tx)) = seek_rx.recv() => {
+                let _ = tx.send(reader.seek(position));
+                origin = None;
+                continue;
+            }
+        }
+        let Some(output) = outputs.get(&packet.track) else {
+            continue;
+        };
+        if !output.send(&packet.packet).await {
+            break;
+        }
+    }
+}
+
+/// Sessions of all connections, a session ends with TEARDOWN or when the
+/// connection that created it is closed.
+#[derive(Default, Clone)]
+pub struct Sessions {
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl Sessions {
+    pub fn insert(&self, id: String, session: Session) {
+        self.sessions.lock().unwrap().insert(id, session);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Session> {
+        self.sessions.lock().unwrap().remove(id)
+    }
+
+    pub fn with<T>(&self, id: &str, f: impl FnOnce(&mut Session) -> T) -> Option<T> {
+        self.sessions.lock().unwrap().get_mut(id).map(f)
+    }
+}
@@ -0,0 +1,29 @@
+use super::MediaReader;
+use crate::rtp;
+use crate::sdp::Sdp;
+use tokio::sync::broadcast;
+
+/// Provides the streams served by a [`Server`](super::Server).
+///
+/// Streams are addressed by the path of the request url, e.g. `/camera1`.
+/// The media sections of the SDP must carry `a=control:trackID=<n>`,
+/// where `n` is the index of the media section, which is how SETUP
+/// requests are mapped to tracks.
+///
+/// Live streams are shared by all sessions through [`subscribe`](Self::subscribe).
+/// Recordings are read by every session on its own through [`open`](Self::open),
+/// which lets clients play them from a `Range` and seek with another PLAY.
+pub trait MediaSource: Send + Sync + 'static {
+    /// Returns the description of the stream, `None` if there is no such stream
+    fn describe(&self, path: &str) -> Option<Sdp>;
+
+    /// Subscribes to the RTP packets of one track, called on PLAY
+    fn subscribe(&self, path: &str, track: usize) -> Option<broadcast::Receiver<rtp::Packet>>;
+
+    /// Opens a reader of the stream for one session, called on PLAY before
+    /// [`subscribe`](Self::subscribe). The tracks of the reader must match
+    /// the description, e.g. from [`describe_tracks`](super::describe_tracks).
+    fn open(&self, _path: &str) -> Option<Box<dyn MediaReader>> {
+        None
+    }
+}
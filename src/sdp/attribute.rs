@@ -1,19 +1,281 @@
-enum Codec {
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Codec {
     H264,
     H265,
     AAC,
     PCMU,
     PCMA,
     OPUS,
+    /// Retransmissions of another payload type (RFC 4588)
+    RTX,
     Unknown(String),
 }
 
-struct RtpMap {
-    payload_type: u8,
-    codec: Codec,
-    timebase: u32,
+impl From<&str> for Codec {
+    fn from(name: &str) -> Self {
+        match name.to_ascii_uppercase().as_str() {
+            "H264" => Codec::H264,
+            "H265" => Codec::H265,
+            "MPEG4-GENERIC" => Codec::AAC,
+            "PCMU" => Codec::PCMU,
+            "PCMA" => Codec::PCMA,
+            "OPUS" => Codec::OPUS,
+            "RTX" => Codec::RTX,
+            _ => Codec::Unknown(name.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Codec::H264 => write!(f, "H264"),
+            Codec::H265 => write!(f, "H265"),
+            Codec::AAC => write!(f, "MPEG4-GENERIC"),
+            Codec::PCMU => write!(f, "PCMU"),
+            Codec::PCMA => write!(f, "PCMA"),
+            Codec::OPUS => write!(f, "OPUS"),
+            Codec::RTX => write!(f, "rtx"),
+            Codec::Unknown(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseAttributeError {
+    #[error("Invalid rtpmap attribute")]
+    InvalidRtpMap,
+    #[error("Invalid fmtp attribute")]
+    InvalidFmtp,
+    #[error("Invalid ts-refclk attribute")]
+    InvalidRefClock,
+    #[error("Invalid mediaclk attribute")]
+    InvalidMediaClock,
+    #[error("Invalid extmap attribute")]
+    InvalidExtMap,
+    #[error("Invalid bandwidth line")]
+    InvalidBandwidth,
+}
+
+/// `a=rtpmap:<payload type> <encoding name>/<clock rate>[/<channels>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtpMap {
+    pub payload_type: u8,
+    pub codec: Codec,
+    pub timebase: u32,
+    pub channels: Option<u8>,
+}
+
+impl RtpMap {
+    /// Mapping of the static payload types of RFC 3551 that are supported
+    pub fn from_static(payload_type: u8) -> Option<Self> {
+        let codec = match payload_type {
+            0 => Codec::PCMU,
+            8 => Codec::PCMA,
+            _ => return None,
+        };
+        Some(Self {
+            payload_type,
+            codec,
+            timebase: 8000,
+            channels: Some(1),
+        })
+    }
+}
+
+impl FromStr for RtpMap {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (payload_type, encoding) = s.trim().split_once(' ').ok_or(ParseAttributeError::InvalidRtpMap)?;
+        let payload_type = payload_type.parse().map_err(|_| ParseAttributeError::InvalidRtpMap)?;
+        let mut iter = encoding.trim().split('/');
+        let codec = Codec::from(iter.next().ok_or(ParseAttributeError::InvalidRtpMap)?);
+        let timebase = iter
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or(ParseAttributeError::InvalidRtpMap)?;
+        let channels = iter.next().and_then(|c| c.parse().ok());
+        Ok(RtpMap {
+            payload_type,
+            codec,
+            timebase,
+            channels,
+        })
+    }
+}
+
+/// `a=fmtp:<payload type> <name>=<value>;...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fmtp {
+    pub payload_type: u8,
+    pub params: Vec<(String, String)>,
+}
+
+impl Fmtp {
+    /// Looks up a parameter, names are case-insensitive
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl FromStr for Fmtp {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (payload_type, params) = s.split_once(' ').unwrap_or((s, ""));
+        let payload_type = payload_type.parse().map_err(|_| ParseAttributeError::InvalidFmtp)?;
+        let params = params
+            .split(';')
+            .filter_map(|p| {
+                let p = p.trim();
+                if p.is_empty() {
+                    return None;
+                }
+                let (name, value) = p.split_once('=').unwrap_or((p, ""));
+                Some((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect();
+        Ok(Fmtp { payload_type, params })
+    }
 }
 
-struct Fmtp {
-    payload_type : u8,
+/// `a=extmap:<id>[/<direction>] <uri> [<attributes>]` (RFC 8285)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtMap {
+    pub id: u8,
+    pub direction: Option<String>,
+    pub uri: String,
+    pub attributes: Option<String>,
+}
+
+impl FromStr for ExtMap {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id, rest) = s.trim().split_once(' ').ok_or(ParseAttributeError::InvalidExtMap)?;
+        let (id, direction) = match id.split_once('/') {
+            Some((id, direction)) => (id, Some(direction.to_string())),
+            None => (id, None),
+        };
+        let id = id.parse().map_err(|_| ParseAttributeError::InvalidExtMap)?;
+        let rest = rest.trim();
+        let (uri, attributes) = match rest.split_once(' ') {
+            Some((uri, attributes)) => (uri, Some(attributes.trim().to_string())),
+            None => (rest, None),
+        };
+        Ok(ExtMap {
+            id,
+            direction,
+            uri: uri.to_string(),
+            attributes,
+        })
+    }
+}
+
+/// `b=<modifier>:<value>` line, e.g. `AS:2048` for kilobits per second
+/// (RFC 4566) or `TIAS:2000000` for bits per second without the IP and
+/// transport overhead (RFC 3890)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bandwidth {
+    pub modifier: String,
+    pub value: u64,
+}
+
+impl Bandwidth {
+    /// The bandwidth in bits per second, `None` for modifiers other than
+    /// `AS`, `CT` and `TIAS`
+    pub fn bits_per_second(&self) -> Option<u64> {
+        match self.modifier.to_ascii_uppercase().as_str() {
+            "AS" | "CT" => Some(self.value * 1000),
+            "TIAS" => Some(self.value),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Bandwidth {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifier, value) = s.trim().split_once(':').ok_or(ParseAttributeError::InvalidBandwidth)?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|_| ParseAttributeError::InvalidBandwidth)?;
+        Ok(Bandwidth {
+            modifier: modifier.to_string(),
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.modifier, self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rtpmap() {
+        let rtpmap: RtpMap = "97 MPEG4-GENERIC/44100/2".parse().unwrap();
+        assert_eq!(rtpmap.payload_type, 97);
+        assert_eq!(rtpmap.codec, Codec::AAC);
+        assert_eq!(rtpmap.timebase, 44100);
+        assert_eq!(rtpmap.channels, Some(2));
+    }
+
+    #[test]
+    fn test_parse_rtpmap_invalid() {
+        assert!("96 H264".parse::<RtpMap>().is_err());
+        assert!("x H264/90000".parse::<RtpMap>().is_err());
+    }
+
+    #[test]
+    fn test_parse_fmtp() {
+        let fmtp: Fmtp = "97 streamtype=5; mode=AAC-hbr; SizeLength=13; config=1210"
+            .parse()
+            .unwrap();
+        assert_eq!(fmtp.payload_type, 97);
+        assert_eq!(fmtp.get("sizelength"), Some("13"));
+        assert_eq!(fmtp.get("config"), Some("1210"));
+        assert_eq!(fmtp.get("indexlength"), None);
+    }
+
+    #[test]
+    fn test_parse_extmap() {
+        let extmap: ExtMap = "1/sendonly urn:ietf:params:rtp-hdrext:ssrc-audio-level vad=on"
+            .parse()
+            .unwrap();
+        assert_eq!((extmap.id, extmap.direction.as_deref()), (1, Some("sendonly")));
+        assert_eq!(extmap.uri, "urn:ietf:params:rtp-hdrext:ssrc-audio-level");
+        assert_eq!(extmap.attributes.as_deref(), Some("vad=on"));
+        let extmap: ExtMap = "3 urn:3gpp:video-orientation".parse().unwrap();
+        assert_eq!((extmap.id, extmap.direction, extmap.attributes), (3, None, None));
+        assert!("urn:3gpp:video-orientation".parse::<ExtMap>().is_err());
+    }
+
+    #[test]
+    fn test_parse_bandwidth() {
+        let bandwidth: Bandwidth = "AS:2048".parse().unwrap();
+        assert_eq!(bandwidth.bits_per_second(), Some(2_048_000));
+        assert_eq!(bandwidth.to_string(), "AS:2048");
+        let bandwidth: Bandwidth = "TIAS:1500000".parse().unwrap();
+        assert_eq!(bandwidth.bits_per_second(), Some(1_500_000));
+        assert_eq!("RR:800".parse::<Bandwidth>().unwrap().bits_per_second(), None);
+        assert!("AS".parse::<Bandwidth>().is_err());
+    }
 }
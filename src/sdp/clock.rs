@@ -0,0 +1,235 @@
+use super::ParseAttributeError;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900) and the unix epoch
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// TAI - UTC, PTP counts TAI seconds since 1970
+const TAI_UTC_OFFSET: u64 = 37;
+
+/// Reference clock of a stream, `a=ts-refclk` (RFC 7273)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RefClock {
+    /// `ntp=<server>` or `ntp=/traceable/`
+    Ntp {
+        server: Option<String>,
+        traceable: bool,
+    },
+    /// `ptp=<version>:<grandmaster id>[:<domain>]` or `ptp=<version>:traceable`
+    Ptp {
+        version: String,
+        grandmaster: Option<String>,
+        domain: Option<u8>,
+        traceable: bool,
+    },
+    Gps,
+    Galileo,
+    Glonass,
+    Local,
+    Private {
+        traceable: bool,
+    },
+    /// `localmac=<mac>`, a clock local to the given interface
+    LocalMac(String),
+    Other(String),
+}
+
+impl RefClock {
+    /// Epoch of the clock as wall clock time, `None` if it isn't known.
+    /// PTP time is TAI, the current 37 s offset to UTC is taken into account.
+    pub fn epoch(&self) -> Option<SystemTime> {
+        match self {
+            RefClock::Ntp { .. } => UNIX_EPOCH.checked_sub(Duration::from_secs(NTP_UNIX_OFFSET)),
+            RefClock::Ptp { .. } => UNIX_EPOCH.checked_sub(Duration::from_secs(TAI_UTC_OFFSET)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for RefClock {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (source, value) = match s.split_once('=') {
+            Some((source, value)) => (source, Some(value)),
+            None => (s, None),
+        };
+        let clock = match (source.to_ascii_lowercase().as_str(), value) {
+            ("ntp", Some(value)) => match value {
+                "/traceable/" => RefClock::Ntp {
+                    server: None,
+                    traceable: true,
+                },
+                server => RefClock::Ntp {
+                    server: Some(server.to_string()),
+                    traceable: false,
+                },
+            },
+            ("ptp", Some(value)) => {
+                let mut parts = value.split(':');
+                let version = parts.next().unwrap_or_default().to_string();
+                let id = parts.next().ok_or(ParseAttributeError::InvalidRefClock)?;
+                let traceable = id == "traceable";
+                let domain = match parts.next() {
+                    Some(d) => Some(d.parse().map_err(|_| ParseAttributeError::InvalidRefClock)?),
+                    None => None,
+                };
+                RefClock::Ptp {
+                    version,
+                    grandmaster: (!traceable).then(|| id.to_string()),
+                    domain,
+                    traceable,
+                }
+            }
+            ("gps", None) => RefClock::Gps,
+            ("gal", None) => RefClock::Galileo,
+            ("glonass", None) => RefClock::Glonass,
+            ("local", None) => RefClock::Local,
+            ("private", None) => RefClock::Private { traceable: false },
+            ("private", Some("traceable")) => RefClock::Private { traceable: true },
+            ("localmac", Some(mac)) => RefClock::LocalMac(mac.to_string()),
+            ("", _) => return Err(ParseAttributeError::InvalidRefClock),
+            _ => RefClock::Other(s.to_string()),
+        };
+        Ok(clock)
+    }
+}
+
+/// Relation of the RTP clock to the reference clock, `a=mediaclk` (RFC 7273)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MediaClock {
+    /// The RTP timestamp is derived from the reference clock, it equals
+    /// `offset` at the clock's epoch. `rate` scales the nominal clock rate.
+    Direct {
+        offset: u32,
+        rate: Option<(u32, u32)>,
+    },
+    /// The media clock is the sender's own, mapped with RTCP sender reports
+    Sender,
+    /// `IEEE1722=<stream id>`
+    Ieee1722(String),
+    Other(String),
+}
+
+impl MediaClock {
+    /// Clock rate of the RTP timestamps for the nominal `clock_rate`
+    pub fn effective_rate(&self, clock_rate: u32) -> f64 {
+        match self {
+            MediaClock::Direct {
+                rate: Some((num, den)), ..
+            } if *den != 0 => clock_rate as f64 * *num as f64 / *den as f64,
+            _ => clock_rate as f64,
+        }
+    }
+}
+
+impl FromStr for MediaClock {
+    type Err = ParseAttributeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut params = s.split_whitespace();
+        let clock = params.next().ok_or(ParseAttributeError::InvalidMediaClock)?;
+        let (name, value) = clock.split_once('=').unwrap_or((clock, ""));
+        let clock = match name.to_ascii_lowercase().as_str() {
+            "direct" => {
+                // The offset may exceed 32 bits, only its value modulo 2^32 matters for RTP
+                let offset: u64 = value.parse().map_err(|_| ParseAttributeError::InvalidMediaClock)?;
+                let rate = params
+                    .find_map(|p| p.strip_prefix("rate="))
+                    .map(|r| {
+                        let (num, den) = r.split_once('/').unwrap_or((r, "1"));
+                        Some((num.parse().ok()?, den.parse().ok()?))
+                    })
+                    .map(|r| r.ok_or(ParseAttributeError::InvalidMediaClock))
+                    .transpose()?;
+                MediaClock::Direct {
+                    offset: offset as u32,
+                    rate,
+                }
+            }
+            "sender" => MediaClock::Sender,
+            "ieee1722" if !value.is_empty() => MediaClock::Ieee1722(value.to_string()),
+            _ => MediaClock::Other(s.to_string()),
+        };
+        Ok(clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ref_clock() {
+        assert_eq!(
+            "ptp=IEEE1588-2008:39-A7-94-FF-FE-07-CB-D0:37"
+                .parse::<RefClock>()
+                .unwrap(),
+            RefClock::Ptp {
+                version: "IEEE1588-2008".to_string(),
+                grandmaster: Some("39-A7-94-FF-FE-07-CB-D0".to_string()),
+                domain: Some(37),
+                traceable: false,
+            }
+        );
+        assert_eq!(
+            "ptp=IEEE1588-2008:traceable".parse::<RefClock>().unwrap(),
+            RefClock::Ptp {
+                version: "IEEE1588-2008".to_string(),
+                grandmaster: None,
+                domain: None,
+                traceable: true,
+            }
+        );
+        assert_eq!(
+            "ntp=/traceable/".parse::<RefClock>().unwrap(),
+            RefClock::Ntp {
+                server: None,
+                traceable: true
+            }
+        );
+        assert_eq!("gps".parse::<RefClock>().unwrap(), RefClock::Gps);
+        assert_eq!(
+            "localmac=7C-E9-D3-1B-9A-AF".parse::<RefClock>().unwrap(),
+            RefClock::LocalMac("7C-E9-D3-1B-9A-AF".to_string())
+        );
+        assert!("ptp=IEEE1588-2008".parse::<RefClock>().is_err());
+    }
+
+    #[test]
+    fn test_parse_media_clock() {
+        assert_eq!(
+            "direct=963214424".parse::<MediaClock>().unwrap(),
+            MediaClock::Direct {
+                offset: 963214424,
+                rate: None
+            }
+        );
+        let clock: MediaClock = "direct=0 rate=1000/1001".parse().unwrap();
+        assert_eq!(
+            clock,
+            MediaClock::Direct {
+                offset: 0,
+                rate: Some((1000, 1001))
+            }
+        );
+        assert!((clock.effective_rate(90_000) - 89_910.09).abs() < 0.01);
+        assert_eq!("sender".parse::<MediaClock>().unwrap(), MediaClock::Sender);
+        assert!("direct=abc".parse::<MediaClock>().is_err());
+    }
+
+    #[test]
+    fn test_ref_clock_epoch() {
+        let ntp = RefClock::Ntp {
+            server: None,
+            traceable: true,
+        };
+        assert_eq!(
+            UNIX_EPOCH.duration_since(ntp.epoch().unwrap()).unwrap(),
+            Duration::from_secs(NTP_UNIX_OFFSET)
+        );
+        assert!(RefClock::Local.epoch().is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use super::{Bandwidth, Codec, ExtMap, Fmtp, MediaClock, ParseError, RefClock, RtpMap};
+use crate::rtsp::Profile;
+use std::fmt;
+use std::str::FromStr;
+
+/// A media description, starting with a `m=` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Media {
+    pub media_type: String,
+    pub port: u16,
+    pub protocol: String,
+    pub formats: Vec<u8>,
+    /// The `b=` lines of the media section
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bandwidths: Vec<Bandwidth>,
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl Media {
+    /// Returns the value of the first attribute with the given name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_deref().unwrap_or_default())
+    }
+
+    pub fn attributes<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.attributes
+            .iter()
+            .filter(move |(n, _)| n == name)
+            .map(|(_, v)| v.as_deref().unwrap_or_default())
+    }
+
+    /// The RTP profile of the `m=` line protocol, `None` if it isn't RTP,
+    /// e.g. `RTP/AVPF` offered by WebRTC derived servers
+    pub fn profile(&self) -> Option<Profile> {
+        let mut parts = self.protocol.split('/');
+        if !parts.next().is_some_and(|p| p.eq_ignore_ascii_case("RTP")) {
+            return None;
+        }
+        let profile = Profile::parse(parts.next()?)?;
+        parts.next().is_none().then_some(profile)
+    }
+
+    /// Whether the media is an ONVIF backchannel, which the server marks
+    /// `a=sendonly` although the client sends it
+    pub fn is_backchannel(&self) -> bool {
+        self.attributes.iter().any(|(n, _)| n == "sendonly")
+    }
+
+    pub fn control(&self) -> Option<&str> {
+        self.attribute("control")
+    }
+
+    /// The rtpmap of the given payload type, falling back to the static
+    /// payload type table if the SDP has none
+    pub fn rtpmap(&self, payload_type: u8) -> Option<RtpMap> {
+        self.attributes("rtpmap")
+            .filter_map(|v| v.parse::<RtpMap>().ok())
+            .find(|r| r.payload_type == payload_type)
+            .or_else(|| RtpMap::from_static(payload_type))
+    }
+
+    /// The header extensions negotiated for the media (RFC 8285)
+    pub fn extmaps(&self) -> impl Iterator<Item = ExtMap> + '_ {
+        self.attributes("extmap").filter_map(|v| v.parse().ok())
+    }
+
+    pub fn fmtp(&self, payload_type: u8) -> Option<Fmtp> {
+        self.attributes("fmtp")
+            .filter_map(|v| v.parse::<Fmtp>().ok())
+            .find(|f| f.payload_type == payload_type)
+    }
+
+    /// Retransmission payload types (RFC 4588) with the payload type each
+    /// one repairs, from the `rtx` rtpmaps and the `apt` fmtp parameter
+    pub fn rtx_payload_types(&self) -> Vec<(u8, u8)> {
+        self.formats
+            .iter()
+            .filter(|pt| self.rtpmap(**pt).is_some_and(|r| r.codec == Codec::RTX))
+            .filter_map(|pt| Some((*pt, self.fmtp(*pt)?.get("apt")?.parse().ok()?)))
+            .collect()
+    }
+
+    /// SSRCs of the first `a=ssrc-group` with `semantics`, e.g. `FID` for
+    /// a source followed by its retransmission stream
+    pub fn ssrc_group(&self, semantics: &str) -> Option<Vec<u32>> {
+        self.attributes("ssrc-group").find_map(|v| {
+            let mut parts = v.split_whitespace();
+            if !parts.next()?.eq_ignore_ascii_case(semantics) {
+                return None;
+            }
+            parts.map(|ssrc| ssrc.parse().ok()).collect()
+        })
+    }
+
+    /// Media level `a=ts-refclk` attributes, see [`Sdp::ref_clocks`](super::Sdp::ref_clocks)
+    pub fn ref_clocks(&self) -> Vec<RefClock> {
+        self.attributes("ts-refclk").filter_map(|v| v.parse().ok()).collect()
+    }
+
+    /// Advertised bitrate of the media in bits per second, from the `TIAS`
+    /// or else the `AS` bandwidth
+    pub fn bitrate(&self) -> Option<u64> {
+        let find = |modifier: &str| {
+            self.bandwidths
+                .iter()
+                .find(|b| b.modifier.eq_ignore_ascii_case(modifier))
+                .and_then(Bandwidth::bits_per_second)
+        };
+        find("TIAS").or_else(|| find("AS"))
+    }
+
+    /// Media level `a=mediaclk` attribute
+    pub fn media_clock(&self) -> Option<MediaClock> {
+        self.attribute("mediaclk").and_then(|v| v.parse().ok())
+    }
+}
+
+/// The media section in SDP syntax, the `m=` line followed by its attributes
+impl fmt::Display for Media {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m={} {} {}", self.media_type, self.port, self.protocol)?;
+        for format in &self.formats {
+            write!(f, " {}", format)?;
+        }
+        write!(f, "\r\n")?;
+        for bandwidth in &self.bandwidths {
+            write!(f, "b={}\r\n", bandwidth)?;
+        }
+        for (name, value) in &self.attributes {
+            match value {
+                Some(value) => write!(f, "a={}:{}\r\n", name, value)?,
+                None => write!(f, "a={}\r\n", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Media {
+    type Err = ParseError;
+
+    /// Parses the value of a `m=` line, e.g. `video 0 RTP/AVP 96`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut iter = s.split_whitespace();
+        let media_type = iter.next().ok_or(ParseError::InvalidFormat)?.to_string();
+        let port = iter
+            .next()
+            .and_then(|p| p.split('/').next())
+            .and_then(|p| p.parse().ok())
+            .ok_or(ParseError::InvalidFormat)?;
+        let protocol = iter.next().ok_or(ParseError::InvalidFormat)?.to_string();
+        let formats = iter.filter_map(|f| f.parse().ok()).collect();
+        Ok(Media {
+            media_type,
+            port,
+            protocol,
+            formats,
+            bandwidths: Vec::new(),
+            attributes: Vec::new(),
+        })
+    }
+}
@@ -1,4 +1,18 @@
+mod attribute;
+mod clock;
+mod media;
+mod payload_types;
 mod sdp;
 
-pub use sdp::Sdp;
+pub use attribute::Codec;
+pub use attribute::Fmtp;
+pub use attribute::ParseAttributeError;
+pub use attribute::RtpMap;
+pub use clock::MediaClock;
+pub use clock::RefClock;
+pub use media::Media;
 pub use sdp::ParseError;
+pub use sdp::Sdp;
+pub use payload_types::PayloadTypeMap;
+pub use attribute::ExtMap;
+pub use attribute::Bandwidth;
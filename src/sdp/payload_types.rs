@@ -0,0 +1,154 @@
+use super::{Media, RtpMap};
+
+/// Corrections of the payload types of a track, for cameras that describe
+/// one payload type in the SDP but send another, or describe the wrong
+/// codec. Applied to the [`Media`] before the depacketizer is created, so
+/// the payload type filter, the depacketizer selection and the clock rate
+/// all follow what is on the wire.
+///
+/// ```ignore
+/// // Described as 96, sent as 97
+/// let media = PayloadTypeMap::new().alias(97, 96).apply(&media);
+/// let depacketizer = new_depacketizer_for(&media, 97)?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayloadTypeMap {
+    // Payload type on the wire and the described one it stands for
+    aliases: Vec<(u8, u8)>,
+    // Payload type and the encoding that replaces its rtpmap
+    rtpmaps: Vec<(u8, String)>,
+}
+
+impl PayloadTypeMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packets of payload type `wire` carry the format described for `described`
+    pub fn alias(mut self, wire: u8, described: u8) -> Self {
+        self.aliases.push((wire, described));
+        self
+    }
+
+    /// Replaces the rtpmap of `payload_type` with `encoding`, e.g.
+    /// `"H264/90000"` to force the H.264 depacketizer
+    pub fn rtpmap(mut self, payload_type: u8, encoding: &str) -> Self {
+        self.rtpmaps.push((payload_type, encoding.to_string()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty() && self.rtpmaps.is_empty()
+    }
+
+    /// The payload type of the description a packet of `wire` is depacketized as
+    pub fn resolve(&self, wire: u8) -> u8 {
+        self.aliases
+            .iter()
+            .find(|(w, _)| *w == wire)
+            .map_or(wire, |(_, described)| *described)
+    }
+
+    /// Returns `media` with the rtpmaps of its formats replaced and the
+    /// aliases of its formats added as formats of their own, with the
+    /// rtpmap and fmtp of the described one
+    pub fn apply(&self, media: &Media) -> Media {
+        let mut media = media.clone();
+        // Overrides only apply to the media describing their payload type
+        for (payload_type, encoding) in &self.rtpmaps {
+            if media.formats.contains(payload_type) {
+                replace_attribute(&mut media, "rtpmap", *payload_type, Some(encoding.clone()));
+            }
+        }
+        // Aliases only apply to the media describing their payload type
+        let aliases: Vec<_> = self
+            .aliases
+            .iter()
+            .filter(|(_, d)| media.formats.contains(d))
+            .copied()
+            .collect();
+        for (wire, described) in aliases {
+            let rtpmap = media.rtpmap(described).map(|r| encoding(&r));
+            let fmtp = media.attributes("fmtp").find_map(|v| {
+                let (pt, params) = v.trim().split_once(' ').unwrap_or((v.trim(), ""));
+                (pt.parse() == Ok(described)).then(|| params.to_string())
+            });
+            replace_attribute(&mut media, "rtpmap", wire, rtpmap);
+            replace_attribute(&mut media, "fmtp", wire, fmtp);
+            if !media.formats.contains(&wire) {
+                media.formats.push(wire);
+            }
+        }
+        media
+    }
+}
+
+fn encoding(rtpmap: &RtpMap) -> String {
+    match rtpmap.channels {
+        Some(channels) => format!("{}/{}/{}", rtpmap.codec, rtpmap.timebase, channels),
+        None => format!("{}/{}", rtpmap.codec, rtpmap.timebase),
+    }
+}
+
+/// Replaces the `name` attributes of `payload_type` with one of `value`
+fn replace_attribute(media: &mut Media, name: &str, payload_type: u8, value: Option<String>) {
+    media.attributes.retain(|(n, v)| {
+        n != name
+            || v.as_deref()
+                .and_then(|v| v.split_whitespace().next())
+                .is_none_or(|pt| pt.parse() != Ok(payload_type))
+    });
+    if let Some(value) = value {
+        media
+            .attributes
+            .push((name.to_string(), Some(format!("{} {}", payload_type, value))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdp::Codec;
+
+    fn media() -> Media {
+        let mut media: Media = "video 0 RTP/AVP 96".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("96 H265/90000".to_string())));
+        media
+            .attributes
+            .push(("fmtp".to_string(), Some("96 packetization-mode=1".to_string())));
+        media
+    }
+
+    #[test]
+    fn test_payload_type_map() {
+        let map = PayloadTypeMap::new().rtpmap(96, "H264/90000").alias(97, 96);
+        assert_eq!(map.resolve(97), 96);
+        assert_eq!(map.resolve(8), 8);
+        let media = map.apply(&media());
+        assert_eq!(media.formats, [96, 97]);
+        assert_eq!(media.rtpmap(96).unwrap().codec, Codec::H264);
+        let alias = media.rtpmap(97).unwrap();
+        assert_eq!((alias.codec, alias.timebase), (Codec::H264, 90000));
+        assert_eq!(media.fmtp(97).unwrap().get("packetization-mode"), Some("1"));
+        assert_eq!(media.attributes("rtpmap").count(), 2);
+    }
+
+    #[test]
+    fn test_payload_type_map_static() {
+        let audio: Media = "audio 0 RTP/AVP 0".parse().unwrap();
+        let audio = PayloadTypeMap::new().alias(8, 0).apply(&audio);
+        assert_eq!(audio.rtpmap(8).unwrap().codec, Codec::PCMU);
+        // Other tracks don't claim the alias
+        let video = PayloadTypeMap::new().alias(8, 0).apply(&media());
+        assert_eq!(video.formats, [96]);
+        // Nor the rtpmap overrides of the formats of other tracks
+        let map = PayloadTypeMap::new().rtpmap(96, "H264/90000");
+        let audio = map.apply(&"audio 0 RTP/AVP 0".parse().unwrap());
+        assert_eq!(audio.formats, [0]);
+        assert_eq!(audio.attributes("rtpmap").count(), 0);
+        assert!(PayloadTypeMap::new().is_empty());
+    }
+}
@@ -1,9 +1,29 @@
-use std::{convert::TryFrom};
+use super::{Bandwidth, Media, MediaClock, RefClock};
+use std::convert::TryFrom;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+/// Attributes of ICE (RFC 8839) and DTLS-SRTP (RFC 8842) sessions
+const WEBRTC_ATTRIBUTES: [&str; 7] = [
+    "ice-lite",
+    "ice-ufrag",
+    "ice-pwd",
+    "ice-options",
+    "candidate",
+    "end-of-candidates",
+    "fingerprint",
+];
+
+/// A parsed session description. `Display` gives the description as it
+/// was received, `Debug` the session attributes and media sections.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sdp {
     description: String,
+    /// The session level `b=` lines
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bandwidths: Vec<Bandwidth>,
+    pub attributes: Vec<(String, Option<String>)>,
+    pub media: Vec<Media>,
 }
 
 #[derive(Error, Debug)]
@@ -12,18 +32,263 @@ pub enum ParseError {
     InvalidFormat,
 }
 
+impl Sdp {
+    /// Returns the value of the first session level attribute with the given name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_deref().unwrap_or_default())
+    }
+
+    /// Reference clocks of a media section, the session level ones apply
+    /// if the media section has none (RFC 7273)
+    pub fn ref_clocks(&self, media: &Media) -> Vec<RefClock> {
+        let clocks = media.ref_clocks();
+        if !clocks.is_empty() {
+            return clocks;
+        }
+        self.attributes
+            .iter()
+            .filter(|(n, _)| n == "ts-refclk")
+            .filter_map(|(_, v)| v.as_deref()?.parse().ok())
+            .collect()
+    }
+
+    /// Media clock of a media section, falling back to the session level one
+    pub fn media_clock(&self, media: &Media) -> Option<MediaClock> {
+        media
+            .media_clock()
+            .or_else(|| self.attribute("mediaclk").and_then(|v| v.parse().ok()))
+    }
+
+    /// Attributes and `m=` line protocols of a WebRTC style description
+    /// that need ICE or DTLS, which RTSP has no way to negotiate, e.g.
+    /// `ice-lite`, `fingerprint` or `UDP/TLS/RTP/SAVPF`. Empty for a
+    /// description that can be set up.
+    pub fn webrtc_attributes(&self) -> Vec<String> {
+        let attributes = self
+            .attributes
+            .iter()
+            .chain(self.media.iter().flat_map(|m| &m.attributes));
+        let mut found: Vec<String> = Vec::new();
+        let names = attributes
+            .map(|(n, _)| n.as_str())
+            .filter(|n| WEBRTC_ATTRIBUTES.contains(n));
+        let protocols = self.media.iter().map(|m| m.protocol.as_str()).filter(|p| {
+            p.split('/')
+                .any(|part| part.eq_ignore_ascii_case("TLS") || part.eq_ignore_ascii_case("DTLS"))
+        });
+        for name in names.chain(protocols) {
+            if !found.iter().any(|f| f == name) {
+                found.push(name.to_string());
+            }
+        }
+        found
+    }
+}
+
+fn parse_attribute(value: &str) -> (String, Option<String>) {
+    match value.split_once(':') {
+        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+        None => (value.to_string(), None),
+    }
+}
+
 impl TryFrom<&str> for Sdp {
     type Error = ParseError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut attributes = Vec::new();
+        let mut bandwidths = Vec::new();
+        let mut media: Vec<Media> = Vec::new();
+        for line in value.lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((kind, value)) = line.split_once('=') else {
+                log::debug!("Ignoring invalid SDP line {}", line);
+                continue;
+            };
+            match kind {
+                "m" => media.push(value.parse()?),
+                "a" => {
+                    let attribute = parse_attribute(value);
+                    match media.last_mut() {
+                        Some(m) => m.attributes.push(attribute),
+                        None => attributes.push(attribute),
+                    }
+                }
+                "b" => match value.parse() {
+                    Ok(bandwidth) => match media.last_mut() {
+                        Some(m) => m.bandwidths.push(bandwidth),
+                        None => bandwidths.push(bandwidth),
+                    },
+                    Err(_) => log::debug!("Ignoring invalid SDP line {}", line),
+                },
+                _ => {}
+            }
+        }
         Ok(Sdp {
             description: value.to_string(),
+            bandwidths,
+            attributes,
+            media,
         })
     }
 }
 
+impl std::fmt::Debug for Sdp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Sdp")
+            .field("attributes", &self.attributes)
+            .field("media", &self.media)
+            .finish()
+    }
+}
+
 impl std::fmt::Display for Sdp {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.description)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::Profile;
+    use crate::sdp::Codec;
+
+    const SDP: &str = "v=0\r\n\
+        o=- 1 1 IN IP4 192.168.0.8\r\n\
+        s=Session\r\n\
+        t=0 0\r\n\
+        a=control:*\r\n\
+        m=video 0 RTP/AVP 96\r\n\
+        b=AS:2048\r\n\
+        a=rtpmap:96 H264/90000\r\n\
+        a=fmtp:96 packetization-mode=1;profile-level-id=42e01f\r\n\
+        a=control:trackID=0\r\n\
+        m=audio 0 RTP/AVP 0\r\n\
+        a=control:trackID=1\r\n";
+
+    #[test]
+    fn test_parse_sdp() {
+        let sdp = Sdp::try_from(SDP).unwrap();
+        assert_eq!(sdp.attribute("control"), Some("*"));
+        assert_eq!(sdp.media.len(), 2);
+        let video = &sdp.media[0];
+        assert_eq!(video.media_type, "video");
+        assert_eq!(video.formats, vec![96]);
+        assert_eq!(video.control(), Some("trackID=0"));
+        assert_eq!(video.rtpmap(96).unwrap().codec, Codec::H264);
+        assert_eq!(video.fmtp(96).unwrap().get("packetization-mode"), Some("1"));
+        assert_eq!(video.bitrate(), Some(2_048_000));
+        assert!(video
+            .to_string()
+            .starts_with("m=video 0 RTP/AVP 96\r\nb=AS:2048\r\na=rtpmap"));
+        let audio = &sdp.media[1];
+        assert_eq!(audio.rtpmap(0).unwrap().codec, Codec::PCMU);
+        assert_eq!(sdp.to_string(), SDP);
+    }
+
+    #[test]
+    fn test_sdp_debug_display() {
+        let sdp = Sdp::try_from(SDP).unwrap();
+        assert_eq!(
+            sdp.media[1].to_string(),
+            "m=audio 0 RTP/AVP 0\r\na=control:trackID=1\r\n"
+        );
+        let debug = format!("{:?}", sdp);
+        assert!(debug.starts_with("Sdp { attributes: [(\"control\", Some(\"*\"))], media: [Media {"));
+        assert!(!debug.contains("\\r\\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sdp_serde() {
+        let sdp = Sdp::try_from(SDP).unwrap();
+        let json = serde_json::to_value(&sdp).unwrap();
+        assert_eq!(json["media"][0]["formats"], serde_json::json!([96]));
+        let parsed: Sdp = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.media, sdp.media);
+        assert_eq!(parsed.to_string(), SDP);
+    }
+
+    #[test]
+    fn test_parse_sdp_invalid_media() {
+        assert!(Sdp::try_from("v=0\r\ngarbage\r\nm=video\r\n").is_err());
+    }
+
+    #[test]
+    fn test_sdp_clocks() {
+        let sdp = Sdp::try_from(
+            "v=0\r\n\
+            a=ts-refclk:ptp=IEEE1588-2008:39-A7-94-FF-FE-07-CB-D0:37\r\n\
+            a=mediaclk:direct=0\r\n\
+            m=video 0 RTP/AVP 96\r\n\
+            m=audio 0 RTP/AVP 97\r\n\
+            a=ts-refclk:localmac=7C-E9-D3-1B-9A-AF\r\n\
+            a=mediaclk:sender\r\n",
+        )
+        .unwrap();
+        let video = &sdp.media[0];
+        assert!(matches!(sdp.ref_clocks(video)[..], [RefClock::Ptp { .. }]));
+        assert_eq!(
+            sdp.media_clock(video),
+            Some(MediaClock::Direct { offset: 0, rate: None })
+        );
+        let audio = &sdp.media[1];
+        assert!(matches!(sdp.ref_clocks(audio)[..], [RefClock::LocalMac(_)]));
+        assert_eq!(sdp.media_clock(audio), Some(MediaClock::Sender));
+    }
+
+    #[test]
+    fn test_sdp_media_profile() {
+        let sdp = Sdp::try_from(
+            "v=0\r\n\
+            m=video 0 RTP/AVPF 96\r\n\
+            m=audio 0 RTP/SAVPF 111\r\n\
+            m=application 0 UDP/DTLS/SCTP webrtc-datachannel\r\n",
+        )
+        .unwrap();
+        assert_eq!(sdp.media[0].profile(), Some(Profile::Avpf));
+        assert_eq!(sdp.media[1].profile(), Some(Profile::Savpf));
+        assert_eq!(sdp.media[2].profile(), None);
+    }
+
+    #[test]
+    fn test_sdp_webrtc_attributes() {
+        assert!(Sdp::try_from(SDP).unwrap().webrtc_attributes().is_empty());
+        let sdp = Sdp::try_from(
+            "v=0\r\n\
+            a=ice-lite\r\n\
+            a=fingerprint:sha-256 AB:CD\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 96\r\n\
+            a=candidate:1 1 udp 2130706431 192.168.0.8 50000 typ host\r\n\
+            a=candidate:2 1 udp 2130706431 10.0.0.8 50000 typ host\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            sdp.webrtc_attributes(),
+            ["ice-lite", "fingerprint", "candidate", "UDP/TLS/RTP/SAVPF"]
+        );
+    }
+
+    #[test]
+    fn test_sdp_backchannel() {
+        let sdp = Sdp::try_from(
+            "v=0\r\n\
+            m=audio 0 RTP/AVP 0\r\n\
+            a=recvonly\r\n\
+            m=audio 0 RTP/AVP 0\r\n\
+            a=control:audioback\r\n\
+            a=sendonly\r\n",
+        )
+        .unwrap();
+        assert!(!sdp.media[0].is_backchannel());
+        assert!(sdp.media[1].is_backchannel());
+    }
+}
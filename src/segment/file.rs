@@ -0,0 +1,78 @@
+use super::SegmentReader;
+use crate::rtp;
+use crate::rtsp::server::{describe_tracks, MediaReader, MediaSource};
+use crate::sdp::Sdp;
+use std::path::{Component, Path, PathBuf};
+use tokio::sync::broadcast;
+
+/// Serves the recordings below a directory, the request path is the path
+/// of the file, e.g. `rtsp://host/cam1/2024-05-01.mkv`.
+///
+/// Every session reads the file on its own, so clients can play from a
+/// `Range` and seek. Files are read whole on DESCRIBE and PLAY, which
+/// suits segments of minutes rather than days of footage.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    root: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The file of a request path, None if it leaves the root
+    fn file(&self, path: &str) -> Option<PathBuf> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+
+    fn reader(&self, path: &str) -> Option<SegmentReader> {
+        let file = self.file(path)?;
+        match SegmentReader::open(&file) {
+            Ok(reader) => Some(reader),
+            Err(e) => {
+                log::debug!("Failed to open {}: {}", file.display(), e);
+                None
+            }
+        }
+    }
+}
+
+impl MediaSource for FileSource {
+    fn describe(&self, path: &str) -> Option<Sdp> {
+        self.reader(path).map(|reader| describe_tracks(reader.tracks()))
+    }
+
+    /// Recordings are only opened
+    fn subscribe(&self, _path: &str, _track: usize) -> Option<broadcast::Receiver<rtp::Packet>> {
+        None
+    }
+
+    fn open(&self, path: &str) -> Option<Box<dyn MediaReader>> {
+        self.reader(path).map(|reader| Box::new(reader) as Box<dyn MediaReader>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_source_paths() {
+        let source = FileSource::new("/srv/recordings");
+        assert_eq!(
+            source.file("/cam1/today.mkv"),
+            Some(PathBuf::from("/srv/recordings/cam1/today.mkv"))
+        );
+        assert_eq!(source.file("/../etc/passwd"), None);
+        assert_eq!(
+            source.file("/cam1/./today.mkv"),
+            Some(PathBuf::from("/srv/recordings/cam1/today.mkv"))
+        );
+        assert!(source.describe("/missing.mkv").is_none());
+    }
+}
@@ -0,0 +1,528 @@
+use super::reader::{parse_avcc, Error, Result, Sample, Segment, Track};
+use crate::rtsp::server::SourceTrack;
+use crate::types::{FrameType, MediaType};
+use std::collections::HashMap;
+use std::ops::Range;
+
+const SEGMENT: u32 = 0x1853_8067;
+const SEEK_HEAD: u32 = 0x114D_9B74;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const DEFAULT_DURATION: u32 = 0x23_E383;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const BLOCK_GROUP: u32 = 0xA0;
+const BLOCK: u32 = 0xA1;
+const BLOCK_DURATION: u32 = 0x9B;
+const REFERENCE_BLOCK: u32 = 0xFB;
+const CUES: u32 = 0x1C53_BB6B;
+const ATTACHMENTS: u32 = 0x1941_A469;
+const CHAPTERS: u32 = 0x1043_A770;
+const TAGS: u32 = 0x1254_C367;
+
+/// Children of a Segment, they end a Cluster of unknown size
+const SEGMENT_CHILDREN: [u32; 8] = [SEEK_HEAD, INFO, TRACKS, CLUSTER, CUES, ATTACHMENTS, CHAPTERS, TAGS];
+
+/// Timestamps of Matroska tracks are converted to nanoseconds
+const TIMESCALE: u64 = 1_000_000_000;
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// A variable size integer at `pos`, its value without the length marker
+/// and its length
+fn read_vint(data: &[u8], pos: usize) -> Option<(u64, usize)> {
+    let first = *data.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    let value = data
+        .get(pos + 1..pos + len)?
+        .iter()
+        .fold(first as u64 & (0xff >> len), |value, b| (value << 8) | *b as u64);
+    Some((value, len))
+}
+
+struct Element {
+    id: u32,
+    payload: Range<usize>,
+}
+
+/// The element at `pos`, one of unknown size extends to `end`
+fn read_element(data: &[u8], pos: usize, end: usize) -> Result<Element> {
+    let invalid = || Error::Invalid("Matroska element");
+    let first = *data.get(pos).ok_or_else(invalid)?;
+    // Ids keep their length marker
+    let id_len = first.leading_zeros() as usize + 1;
+    if id_len > 4 {
+        return Err(invalid());
+    }
+    let id = data
+        .get(pos..pos + id_len)
+        .ok_or_else(invalid)?
+        .iter()
+        .fold(0u32, |id, b| (id << 8) | *b as u32);
+    let (size, size_len) = read_vint(data, pos + id_len).ok_or_else(invalid)?;
+    let start = pos + id_len + size_len;
+    // All value bits set is an unknown size
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    let payload_end = match unknown {
+        true => end,
+        false => start
+            .checked_add(size as usize)
+            .filter(|e| *e <= end)
+            .ok_or_else(invalid)?,
+    };
+    Ok(Element {
+        id,
+        payload: start.min(payload_end)..payload_end,
+    })
+}
+
+/// The elements in `range`
+fn elements(data: &[u8], range: Range<usize>) -> Result<Vec<Element>> {
+    let mut elements = Vec::new();
+    let mut pos = range.start;
+    while pos < range.end {
+        let element = read_element(data, pos, range.end)?;
+        pos = element.payload.end;
+        elements.push(element);
+    }
+    Ok(elements)
+}
+
+fn read_uint(data: &[u8]) -> u64 {
+    data.iter().fold(0, |value, b| (value << 8) | *b as u64)
+}
+
+fn read_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes(data.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    segment: Segment,
+    timestamp_scale: u64,
+    // Track indexes and default frame durations in ns by track number
+    tracks: HashMap<u64, (usize, u64)>,
+}
+
+impl Parser<'_> {
+    fn segment(&mut self, range: Range<usize>) -> Result<()> {
+        let mut pos = range.start;
+        while pos < range.end {
+            let element = read_element(self.data, pos, range.end)?;
+            pos = element.payload.end;
+            match element.id {
+                INFO => self.info(element.payload)?,
+                TRACKS => self.tracks(element.payload)?,
+                CLUSTER => pos = self.cluster(element.payload)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn info(&mut self, range: Range<usize>) -> Result<()> {
+        for element in elements(self.data, range)? {
+            if element.id == TIMESTAMP_SCALE {
+                self.timestamp_scale = read_uint(&self.data[element.payload]).max(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn tracks(&mut self, range: Range<usize>) -> Result<()> {
+        let data = self.data;
+        for entry in elements(data, range)?.into_iter().filter(|e| e.id == TRACK_ENTRY) {
+            let (mut number, mut kind, mut codec, mut private) = (0, 0, "", &[][..]);
+            let (mut default_duration, mut sample_rate, mut channels) = (0, 0.0, 1);
+            for element in elements(data, entry.payload)? {
+                let payload = &data[element.payload.clone()];
+                match element.id {
+                    TRACK_NUMBER => number = read_uint(payload),
+                    TRACK_TYPE => kind = read_uint(payload),
+                    CODEC_ID => codec = std::str::from_utf8(payload).unwrap_or_default().trim_end_matches('\0'),
+                    CODEC_PRIVATE => private = payload,
+                    DEFAULT_DURATION => default_duration = read_uint(payload),
+                    AUDIO => {
+                        for element in elements(data, element.payload)? {
+                            let payload = &data[element.payload];
+                            match element.id {
+                                SAMPLING_FREQUENCY => sample_rate = read_float(payload).unwrap_or_default(),
+                                CHANNELS => channels = read_uint(payload) as u16,
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let (media_type, frame_type, clock_rate, channels, parameter_sets, length_size) = match (kind, codec) {
+                (1, "V_MPEG4/ISO/AVC") => {
+                    let (length_size, sets) = parse_avcc(private).ok_or(Error::Invalid("AVC CodecPrivate"))?;
+                    (MediaType::Video, FrameType::H264, 90_000, None, sets, Some(length_size))
+                }
+                (2, codec) if codec.starts_with("A_AAC") => {
+                    let config = vec![private.to_vec()];
+                    (
+                        MediaType::Audio,
+                        FrameType::AAC,
+                        sample_rate as u32,
+                        Some(channels),
+                        config,
+                        None,
+                    )
+                }
+                // Opus is always described as stereo at 48 kHz (RFC 7587)
+                (2, "A_OPUS") => (MediaType::Audio, FrameType::Opus, 48_000, Some(2), Vec::new(), None),
+                _ => continue,
+            };
+            let track = Track {
+                source: SourceTrack {
+                    media_type,
+                    frame_type,
+                    clock_rate,
+                    channels,
+                    parameter_sets,
+                },
+                timescale: TIMESCALE,
+                length_size,
+            };
+            self.tracks
+                .insert(number, (self.segment.tracks.len(), default_duration));
+            self.segment.tracks.push(track);
+        }
+        Ok(())
+    }
+
+    /// Reads the blocks of a cluster and returns where it ends, a cluster
+    /// of unknown size ends with the next child of the segment
+    fn cluster(&mut self, range: Range<usize>) -> Result<usize> {
+        let data = self.data;
+        let mut time = 0;
+        let mut pos = range.start;
+        while pos < range.end {
+            let element = read_element(data, pos, range.end)?;
+            if SEGMENT_CHILDREN.contains(&element.id) {
+                return Ok(pos);
+            }
+            pos = element.payload.end;
+            match element.id {
+                TIMESTAMP => time = read_uint(&data[element.payload]),
+                SIMPLE_BLOCK => self.block(element.payload, time, None, None)?,
+                BLOCK_GROUP => {
+                    let (mut block, mut duration, mut keyframe) = (None, None, true);
+                    for element in elements(data, element.payload)? {
+                        match element.id {
+                            BLOCK => block = Some(element.payload),
+                            BLOCK_DURATION => duration = Some(read_uint(&data[element.payload])),
+                            REFERENCE_BLOCK => keyframe = false,
+                            _ => {}
+                        }
+                    }
+                    if let Some(block) = block {
+                        self.block(block, time, Some(keyframe), duration)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(range.end)
+    }
+
+    /// Adds the frames of a SimpleBlock or a Block of a BlockGroup, which
+    /// says whether it is a keyframe
+    fn block(
+        &mut self,
+        range: Range<usize>,
+        cluster_time: u64,
+        keyframe: Option<bool>,
+        duration: Option<u64>,
+    ) -> Result<()> {
+        let data = &self.data[range.clone()];
+        let invalid = || Error::Invalid("Matroska block");
+        let (number, len) = read_vint(data, 0).ok_or_else(invalid)?;
+        let Some(&(track, default_duration)) = self.tracks.get(&number) else {
+            return Ok(());
+        };
+        let header = data.get(len..len + 3).ok_or_else(invalid)?;
+        let relative = i16::from_be_bytes([header[0], header[1]]) as i64;
+        let flags = header[2];
+        let keyframe = keyframe.unwrap_or(flags & 0x80 != 0);
+        let frames = lace(data, len + 3, (flags >> 1) & 0x03).ok_or_else(invalid)?;
+
+        let scale = self.timestamp_scale;
+        let time = i64::try_from(cluster_time)
+            .ok()
+            .and_then(|time| time.checked_add(relative))
+            .and_then(|time| (time.max(0) as u64).checked_mul(scale))
+            .ok_or_else(invalid)?;
+        let duration = match duration {
+            Some(duration) => duration.checked_mul(scale).ok_or_else(invalid)? / frames.len() as u64,
+            None => default_duration,
+        };
+        for (i, frame) in frames.into_iter().enumerate() {
+            let offset = (i as u64).checked_mul(duration).ok_or_else(invalid)?;
+            self.segment.samples.push(Sample {
+                track,
+                time: time.checked_add(offset).ok_or_else(invalid)?,
+                composition_offset: 0,
+                duration,
+                range: range.start + frame.start..range.start + frame.end,
+                keyframe,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Ranges of the frames of a block from `pos` on, by the lacing bits of
+/// its flags
+fn lace(data: &[u8], pos: usize, lacing: u8) -> Option<Vec<Range<usize>>> {
+    if lacing == 0 {
+        return Some(std::iter::once(pos..data.len()).collect());
+    }
+    let count = *data.get(pos)? as usize + 1;
+    let mut pos = pos + 1;
+    let mut sizes = Vec::with_capacity(count);
+    match lacing {
+        // Xiph, sizes as sums of bytes up to 255
+        1 => {
+            for _ in 1..count {
+                let mut size = 0;
+                loop {
+                    let b = *data.get(pos)?;
+                    pos += 1;
+                    size += b as usize;
+                    if b != 255 {
+                        break;
+                    }
+                }
+                sizes.push(size);
+            }
+        }
+        // Fixed, frames of equal size
+        2 => sizes.resize(count - 1, (data.len() - pos) / count),
+        // EBML, the first size followed by signed differences
+        _ => {
+            let (first, len) = read_vint(data, pos)?;
+            pos += len;
+            let mut size = first as i64;
+            sizes.push(first as usize);
+            for _ in 2..count {
+                let (value, len) = read_vint(data, pos)?;
+                pos += len;
+                size += value as i64 - ((1i64 << (7 * len - 1)) - 1);
+                sizes.push(usize::try_from(size).ok()?);
+            }
+        }
+    }
+    let mut frames = Vec::with_capacity(count);
+    for size in sizes {
+        frames.push(pos..pos.checked_add(size).filter(|end| *end <= data.len())?);
+        pos += size;
+    }
+    frames.push(pos..data.len());
+    Some(frames)
+}
+
+/// Tracks and blocks of a Matroska or WebM file
+pub(super) fn parse(data: &[u8]) -> Result<Segment> {
+    let mut parser = Parser {
+        data,
+        segment: Segment::default(),
+        timestamp_scale: DEFAULT_TIMESTAMP_SCALE,
+        tracks: HashMap::new(),
+    };
+    for element in elements(data, 0..data.len())? {
+        if element.id == SEGMENT {
+            parser.segment(element.payload)?;
+        }
+    }
+    Ok(parser.segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut e: Vec<u8> = id.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        // 8 byte sizes
+        e.push(0x01);
+        e.extend_from_slice(&(payload.len() as u64).to_be_bytes()[1..]);
+        e.extend_from_slice(payload);
+        e
+    }
+
+    fn unknown_size(id: u32, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut e: Vec<u8> = id.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        e.push(0xff);
+        e.extend_from_slice(&children.concat());
+        e
+    }
+
+    fn block(track: u8, relative: i16, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut block = vec![0x80 | track];
+        block.extend_from_slice(&relative.to_be_bytes());
+        block.push(flags);
+        block.extend_from_slice(payload);
+        block
+    }
+
+    fn file() -> Vec<u8> {
+        let avcc = [1, 0x42, 0, 0x1e, 0xff, 0xe1, 0, 2, 0x67, 0x42, 1, 0, 2, 0x68, 0xce];
+        let video = element(
+            TRACK_ENTRY,
+            &[
+                element(TRACK_NUMBER, &[1]),
+                element(TRACK_TYPE, &[1]),
+                element(CODEC_ID, b"V_MPEG4/ISO/AVC"),
+                element(CODEC_PRIVATE, &avcc),
+            ]
+            .concat(),
+        );
+        let audio = element(
+            TRACK_ENTRY,
+            &[
+                element(TRACK_NUMBER, &[2]),
+                element(TRACK_TYPE, &[2]),
+                element(CODEC_ID, b"A_AAC"),
+                element(CODEC_PRIVATE, &[0x11, 0x90]),
+                element(DEFAULT_DURATION, &21_333_333u32.to_be_bytes()),
+                element(
+                    AUDIO,
+                    &[
+                        element(SAMPLING_FREQUENCY, &48_000f32.to_be_bytes()),
+                        element(CHANNELS, &[2]),
+                    ]
+                    .concat(),
+                ),
+            ]
+            .concat(),
+        );
+        let header = element(0x1A45_DFA3, &element(0x4282, b"matroska"));
+        // A live cluster of unknown size followed by one of known size
+        let live = unknown_size(
+            CLUSTER,
+            &[
+                element(TIMESTAMP, &[0x03, 0xe8]),
+                element(SIMPLE_BLOCK, &block(1, 0, 0x80, &[0, 0, 0, 2, 0x65, 1])),
+                // Two Xiph laced frames of 1 and 2 bytes
+                element(SIMPLE_BLOCK, &block(2, 10, 0x82, &[1, 1, 0xa1, 0xa2, 0xa3])),
+                element(
+                    BLOCK_GROUP,
+                    &[
+                        element(BLOCK, &block(1, 40, 0, &[0, 0, 0, 2, 0x41, 2])),
+                        element(REFERENCE_BLOCK, &[0xd8]),
+                    ]
+                    .concat(),
+                ),
+            ],
+        );
+        let cluster = element(
+            CLUSTER,
+            &[
+                element(TIMESTAMP, &[0x07, 0xd0]),
+                // Fixed lacing of two frames
+                element(SIMPLE_BLOCK, &block(2, 0, 0x84, &[1, 0xb1, 0xb2])),
+            ]
+            .concat(),
+        );
+        let segment = unknown_size(
+            SEGMENT,
+            &[
+                element(INFO, &element(TIMESTAMP_SCALE, &[0x0f, 0x42, 0x40])),
+                element(TRACKS, &[video, audio].concat()),
+                live,
+                cluster,
+            ],
+        );
+        [header, segment].concat()
+    }
+
+    #[test]
+    fn test_parse_mkv() {
+        let data = file();
+        let segment = parse(&data).unwrap();
+        assert_eq!(segment.tracks.len(), 2);
+        assert_eq!(segment.tracks[0].length_size, Some(4));
+        assert_eq!(segment.tracks[0].source.parameter_sets.len(), 2);
+        let audio = &segment.tracks[1].source;
+        assert_eq!(
+            (audio.frame_type, audio.clock_rate, audio.channels),
+            (FrameType::AAC, 48_000, Some(2))
+        );
+
+        let samples: Vec<_> = segment
+            .samples
+            .iter()
+            .map(|s| (s.track, s.time / 1_000_000, s.keyframe, &data[s.range.clone()]))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                (0, 1000, true, &[0, 0, 0, 2, 0x65, 1][..]),
+                (1, 1010, true, &[0xa1]),
+                (1, 1031, true, &[0xa2, 0xa3]),
+                (0, 1040, false, &[0, 0, 0, 2, 0x41, 2]),
+                (1, 2000, true, &[0xb1]),
+                (1, 2021, true, &[0xb2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mkv_overflow() {
+        let audio = element(
+            TRACK_ENTRY,
+            &[
+                element(TRACK_NUMBER, &[1]),
+                element(TRACK_TYPE, &[2]),
+                element(CODEC_ID, b"A_OPUS"),
+            ]
+            .concat(),
+        );
+        let cluster = element(
+            CLUSTER,
+            &[
+                element(TIMESTAMP, &[0x03, 0xe8]),
+                element(SIMPLE_BLOCK, &block(1, 0, 0x80, &[0xfc, 1])),
+            ]
+            .concat(),
+        );
+        // A timestamp scale that overflows the first timestamp in nanoseconds
+        let segment = unknown_size(
+            SEGMENT,
+            &[
+                element(INFO, &element(TIMESTAMP_SCALE, &[0x40, 0, 0, 0, 0, 0, 0, 0])),
+                element(TRACKS, &audio),
+                cluster,
+            ],
+        );
+        assert!(matches!(parse(&segment), Err(Error::Invalid("Matroska block"))));
+    }
+
+    #[test]
+    fn test_lace_ebml() {
+        // Three frames of 3, 2 and 1 bytes, the second size as difference of -1
+        let data = [2, 0x83, 0xbe, 1, 2, 3, 4, 5, 6];
+        let frames = lace(&data, 0, 3).unwrap();
+        assert_eq!(frames, [3..6, 6..8, 8..9]);
+        assert!(lace(&[2, 0x90], 0, 3).is_none());
+    }
+}
@@ -0,0 +1,8 @@
+mod file;
+mod mkv;
+mod mp4;
+mod reader;
+
+pub use reader::Error as SegmentError;
+pub use reader::SegmentReader;
+pub use file::FileSource;
@@ -0,0 +1,505 @@
+use super::reader::{parse_avcc, Error, Result, Sample, Segment, Track};
+use crate::io::bytes::ReadBytes;
+use crate::rtsp::server::SourceTrack;
+use crate::types::{FrameType, MediaType};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::ops::Range;
+
+/// sample_is_non_sync_sample of the sample flags
+const NON_SYNC_SAMPLE: u32 = 0x0001_0000;
+
+/// Size of the fields of a VisualSampleEntry before its boxes
+const VISUAL_SAMPLE_ENTRY_SIZE: usize = 78;
+/// Size of the fields of an AudioSampleEntry before its boxes
+const AUDIO_SAMPLE_ENTRY_SIZE: usize = 28;
+
+struct Mp4Box {
+    kind: [u8; 4],
+    /// Offset of the box header in the segment
+    start: usize,
+    payload: Range<usize>,
+}
+
+/// The boxes in `range` of the segment
+fn boxes(data: &[u8], range: Range<usize>) -> Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    let mut pos = range.start;
+    while pos + 8 <= range.end {
+        let mut c = Cursor::new(&data[pos..range.end]);
+        let size = c.read_u32()?;
+        let kind = data[pos + 4..pos + 8].try_into().unwrap();
+        let (header, size) = match size {
+            // Extends to the end, e.g. the mdat of a live recording
+            0 => (8, (range.end - pos) as u64),
+            1 => {
+                c.set_position(8);
+                (16, c.read_u64()?)
+            }
+            size => (8, size as u64),
+        };
+        if size < header as u64 || size > (range.end - pos) as u64 {
+            return Err(Error::Invalid("MP4 box size"));
+        }
+        let end = pos + size as usize;
+        boxes.push(Mp4Box {
+            kind,
+            start: pos,
+            payload: pos + header..end,
+        });
+        pos = end;
+    }
+    Ok(boxes)
+}
+
+/// Payload of the box at `path` below `range`
+fn find(data: &[u8], mut range: Range<usize>, path: &[&[u8; 4]]) -> Result<Option<Range<usize>>> {
+    for kind in path {
+        match boxes(data, range)?.into_iter().find(|b| &b.kind == *kind) {
+            Some(b) => range = b.payload,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(range))
+}
+
+fn skip(c: &mut Cursor<&[u8]>, n: u64) {
+    c.set_position(c.position() + n);
+}
+
+/// Tag, length and header size of the MPEG-4 descriptor at `pos`
+fn descriptor(data: &[u8], pos: usize) -> Option<(u8, usize, usize)> {
+    let tag = *data.get(pos)?;
+    let mut len = 0;
+    for i in 1..=4 {
+        let b = *data.get(pos + i)?;
+        len = (len << 7) | (b & 0x7f) as usize;
+        if b & 0x80 == 0 {
+            return Some((tag, len, 1 + i));
+        }
+    }
+    None
+}
+
+/// The DecoderSpecificInfo of the `esds` box, the AudioSpecificConfig of AAC
+fn parse_esds(data: &[u8]) -> Option<Vec<u8>> {
+    // Version and flags
+    let mut pos = 4;
+    let (3, _, header) = descriptor(data, pos)? else {
+        return None;
+    };
+    // ES_ID, then the flags of optional fields
+    pos += header;
+    let flags = *data.get(pos + 2)?;
+    pos += 3;
+    if flags & 0x80 != 0 {
+        pos += 2;
+    }
+    if flags & 0x40 != 0 {
+        pos += 1 + *data.get(pos)? as usize;
+    }
+    if flags & 0x20 != 0 {
+        pos += 2;
+    }
+    let (4, _, header) = descriptor(data, pos)? else {
+        return None;
+    };
+    // Object and stream type, buffer size and bitrates
+    pos += header + 13;
+    let (5, len, header) = descriptor(data, pos)? else {
+        return None;
+    };
+    data.get(pos + header..pos + header + len).map(<[u8]>::to_vec)
+}
+
+/// The id and track of a `trak` box, None for codecs that can't be read
+fn parse_trak(data: &[u8], trak: Range<usize>) -> Result<Option<(u32, Track)>> {
+    let invalid = || Error::Invalid("MP4 track");
+    let tkhd = find(data, trak.clone(), &[b"tkhd"])?.ok_or_else(invalid)?;
+    let mut c = Cursor::new(&data[tkhd]);
+    let version = c.read_u8()?;
+    // Flags and creation and modification times
+    skip(&mut c, if version == 1 { 19 } else { 11 });
+    let id = c.read_u32()?;
+
+    let mdhd = find(data, trak.clone(), &[b"mdia", b"mdhd"])?.ok_or_else(invalid)?;
+    let mut c = Cursor::new(&data[mdhd]);
+    let version = c.read_u8()?;
+    skip(&mut c, if version == 1 { 19 } else { 11 });
+    let timescale = c.read_u32()? as u64;
+
+    let stsd = find(data, trak, &[b"mdia", b"minf", b"stbl", b"stsd"])?.ok_or_else(invalid)?;
+    // Version, flags and entry count
+    let Some(entry) = boxes(data, stsd.start + 8..stsd.end)?.into_iter().next() else {
+        return Ok(None);
+    };
+    let track = match &entry.kind {
+        b"avc1" | b"avc3" => {
+            let start = entry.payload.start + VISUAL_SAMPLE_ENTRY_SIZE;
+            let Some(avcc) = find(data, start.min(entry.payload.end)..entry.payload.end, &[b"avcC"])? else {
+                return Ok(None);
+            };
+            let (length_size, parameter_sets) = parse_avcc(&data[avcc]).ok_or(Error::Invalid("avcC box"))?;
+            Track {
+                source: SourceTrack {
+                    media_type: MediaType::Video,
+                    frame_type: FrameType::H264,
+                    clock_rate: 90_000,
+                    channels: None,
+                    parameter_sets,
+                },
+                timescale,
+                length_size: Some(length_size),
+            }
+        }
+        b"mp4a" => {
+            let mut c = Cursor::new(&data[entry.payload.clone()]);
+            // Reserved, data reference index and version
+            skip(&mut c, 16);
+            let channels = c.read_u16()?;
+            skip(&mut c, 6);
+            let sample_rate = c.read_u32()? >> 16;
+            let start = entry.payload.start + AUDIO_SAMPLE_ENTRY_SIZE;
+            let Some(esds) = find(data, start.min(entry.payload.end)..entry.payload.end, &[b"esds"])? else {
+                return Ok(None);
+            };
+            let config = parse_esds(&data[esds]).ok_or(Error::Invalid("esds box"))?;
+            Track {
+                source: SourceTrack {
+                    media_type: MediaType::Audio,
+                    frame_type: FrameType::AAC,
+                    clock_rate: sample_rate,
+                    channels: Some(channels),
+                    parameter_sets: vec![config],
+                },
+                timescale,
+                length_size: None,
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some((id, track)))
+}
+
+/// Sample defaults of a track from `trex` and `tfhd`
+#[derive(Debug, Default, Clone, Copy)]
+struct Defaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    segment: Segment,
+    // Track indexes and sample defaults by track id
+    ids: HashMap<u32, usize>,
+    defaults: HashMap<u32, Defaults>,
+    // Decode time of the next sample of every track
+    next_time: Vec<u64>,
+}
+
+impl Parser<'_> {
+    fn moov(&mut self, moov: Range<usize>) -> Result<()> {
+        let data = self.data;
+        for b in boxes(data, moov.clone())? {
+            if &b.kind != b"trak" {
+                continue;
+            }
+            if let Some((id, track)) = parse_trak(data, b.payload)? {
+                self.ids.insert(id, self.segment.tracks.len());
+                self.segment.tracks.push(track);
+                self.next_time.push(0);
+            }
+        }
+        let Some(mvex) = find(data, moov, &[b"mvex"])? else {
+            return Ok(());
+        };
+        for trex in boxes(data, mvex)?.into_iter().filter(|b| &b.kind == b"trex") {
+            let mut c = Cursor::new(&data[trex.payload]);
+            // Version, flags, then the sample description index after the id
+            skip(&mut c, 4);
+            let id = c.read_u32()?;
+            skip(&mut c, 4);
+            let defaults = Defaults {
+                duration: c.read_u32()?,
+                size: c.read_u32()?,
+                flags: c.read_u32()?,
+            };
+            self.defaults.insert(id, defaults);
+        }
+        Ok(())
+    }
+
+    fn traf(&mut self, moof_start: usize, traf: Range<usize>) -> Result<()> {
+        let data = self.data;
+        let tfhd = find(data, traf.clone(), &[b"tfhd"])?.ok_or(Error::Invalid("traf box"))?;
+        let mut c = Cursor::new(&data[tfhd]);
+        let flags = c.read_u32()? & 0x00ff_ffff;
+        let id = c.read_u32()?;
+        let Some(&track) = self.ids.get(&id) else {
+            return Ok(());
+        };
+        let mut defaults = self.defaults.get(&id).copied().unwrap_or_default();
+        let mut base = moof_start;
+        if flags & 0x01 != 0 {
+            base = usize::try_from(c.read_u64()?).map_err(|_| Error::Invalid("tfhd box"))?;
+        }
+        if flags & 0x02 != 0 {
+            skip(&mut c, 4);
+        }
+        if flags & 0x08 != 0 {
+            defaults.duration = c.read_u32()?;
+        }
+        if flags & 0x10 != 0 {
+            defaults.size = c.read_u32()?;
+        }
+        if flags & 0x20 != 0 {
+            defaults.flags = c.read_u32()?;
+        }
+
+        let mut time = self.next_time[track];
+        if let Some(tfdt) = find(data, traf.clone(), &[b"tfdt"])? {
+            let mut c = Cursor::new(&data[tfdt]);
+            time = match c.read_u32()? >> 24 {
+                1 => c.read_u64()?,
+                _ => c.read_u32()? as u64,
+            };
+        }
+        let audio = self.segment.tracks[track].source.media_type == MediaType::Audio;
+        // Runs without data offset continue after the previous one
+        let mut pos = base;
+        for trun in boxes(data, traf)?.into_iter().filter(|b| &b.kind == b"trun") {
+            let mut c = Cursor::new(&data[trun.payload]);
+            let version_flags = c.read_u32()?;
+            let (version, flags) = (version_flags >> 24, version_flags & 0x00ff_ffff);
+            let count = c.read_u32()?;
+            if flags & 0x01 != 0 {
+                pos = base
+                    .checked_add_signed(c.read_u32()? as i32 as isize)
+                    .ok_or(Error::Invalid("trun box"))?;
+            }
+            let first_flags = match flags & 0x04 != 0 {
+                true => Some(c.read_u32()?),
+                false => None,
+            };
+            // Every sample needs its fields in the box, samples of the
+            // defaults only at least a byte of the file
+            let fields = [0x100, 0x200, 0x400, 0x800].iter().filter(|f| flags & **f != 0).count();
+            let remaining = c.get_ref().len() - c.position() as usize;
+            let max_count = match fields {
+                0 => data.len(),
+                fields => remaining / (4 * fields),
+            };
+            if count as usize > max_count {
+                return Err(Error::Invalid("trun sample count"));
+            }
+            for i in 0..count {
+                let duration = match flags & 0x100 != 0 {
+                    true => c.read_u32()?,
+                    false => defaults.duration,
+                };
+                let size = match flags & 0x200 != 0 {
+                    true => c.read_u32()?,
+                    false => defaults.size,
+                } as usize;
+                let sample_flags = match flags & 0x400 != 0 {
+                    true => c.read_u32()?,
+                    false => first_flags.filter(|_| i == 0).unwrap_or(defaults.flags),
+                };
+                let composition_offset = match (flags & 0x800 != 0, version) {
+                    (true, 0) => c.read_u32()? as i64,
+                    (true, _) => c.read_u32()? as i32 as i64,
+                    (false, _) => 0,
+                };
+                let end = pos.checked_add(size).ok_or(Error::Invalid("trun box"))?;
+                self.segment.samples.push(Sample {
+                    track,
+                    time,
+                    composition_offset,
+                    duration: duration as u64,
+                    range: pos..end,
+                    keyframe: audio || sample_flags & NON_SYNC_SAMPLE == 0,
+                });
+                time = time.checked_add(duration as u64).ok_or(Error::Invalid("trun box"))?;
+                pos = end;
+            }
+        }
+        self.next_time[track] = time;
+        Ok(())
+    }
+}
+
+/// Tracks and samples of an init segment followed by fragments
+pub(super) fn parse(data: &[u8]) -> Result<Segment> {
+    let mut parser = Parser {
+        data,
+        segment: Segment::default(),
+        ids: HashMap::new(),
+        defaults: HashMap::new(),
+        next_time: Vec::new(),
+    };
+    for b in boxes(data, 0..data.len())? {
+        match &b.kind {
+            b"moov" => parser.moov(b.payload)?,
+            b"moof" => {
+                for traf in boxes(data, b.payload)?.into_iter().filter(|b| &b.kind == b"traf") {
+                    parser.traf(b.start, traf.payload)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(parser.segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut b = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+        b.extend_from_slice(kind);
+        b.extend_from_slice(payload);
+        b
+    }
+
+    fn container(kind: &[u8; 4], children: &[Vec<u8>]) -> Vec<u8> {
+        mp4_box(kind, &children.concat())
+    }
+
+    fn trak(id: u32, handler: &[u8; 4], timescale: u32, entry: Vec<u8>) -> Vec<u8> {
+        let mut tkhd = vec![0; 12];
+        tkhd.extend_from_slice(&id.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 64]);
+        let mut mdhd = vec![0; 12];
+        mdhd.extend_from_slice(&timescale.to_be_bytes());
+        mdhd.extend_from_slice(&[0; 8]);
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend_from_slice(&entry);
+        let stbl = container(b"stbl", &[mp4_box(b"stsd", &stsd)]);
+        let minf = container(b"minf", &[stbl]);
+        let mdia = container(b"mdia", &[mp4_box(b"mdhd", &mdhd), mp4_box(b"hdlr", &hdlr), minf]);
+        container(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia])
+    }
+
+    fn trex(id: u32, duration: u32, flags: u32) -> Vec<u8> {
+        let mut trex = vec![0; 4];
+        for value in [id, 1, duration, 0, flags] {
+            trex.extend_from_slice(&value.to_be_bytes());
+        }
+        mp4_box(b"trex", &trex)
+    }
+
+    /// A traf with a trun of sizes, the data offset is patched in later
+    fn traf(id: u32, time: u32, first_flags: u32, sizes: &[u32]) -> Vec<u8> {
+        let mut tfhd = 0x0002_0000u32.to_be_bytes().to_vec();
+        tfhd.extend_from_slice(&id.to_be_bytes());
+        let mut tfdt = vec![0; 4];
+        tfdt.extend_from_slice(&time.to_be_bytes());
+        let mut trun = 0x0000_0205u32.to_be_bytes().to_vec();
+        trun.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        trun.extend_from_slice(&[0; 4]);
+        trun.extend_from_slice(&first_flags.to_be_bytes());
+        for size in sizes {
+            trun.extend_from_slice(&size.to_be_bytes());
+        }
+        container(
+            b"traf",
+            &[
+                mp4_box(b"tfhd", &tfhd),
+                mp4_box(b"tfdt", &tfdt),
+                mp4_box(b"trun", &trun),
+            ],
+        )
+    }
+
+    fn segment() -> Vec<u8> {
+        let avcc = [1, 0x42, 0, 0x1e, 0xff, 0xe1, 0, 2, 0x67, 0x42, 1, 0, 2, 0x68, 0xce];
+        let mut avc1 = vec![0; VISUAL_SAMPLE_ENTRY_SIZE];
+        avc1.extend_from_slice(&mp4_box(b"avcC", &avcc));
+        let mut mp4a = vec![0; 16];
+        mp4a.extend_from_slice(&[0, 2, 0, 16, 0, 0, 0, 0, 0xbb, 0x80, 0, 0]);
+        // ES_Descriptor with a DecoderConfigDescriptor and the AudioSpecificConfig
+        let mut esds = vec![0, 0, 0, 0, 3, 22, 0, 1, 0, 4, 17, 0x40, 0x15];
+        esds.extend_from_slice(&[0; 11]);
+        esds.extend_from_slice(&[5, 2, 0x11, 0x90]);
+        mp4a.extend_from_slice(&mp4_box(b"esds", &esds));
+        let moov = container(
+            b"moov",
+            &[
+                trak(1, b"vide", 90_000, mp4_box(b"avc1", &avc1)),
+                trak(2, b"soun", 48_000, mp4_box(b"mp4a", &mp4a)),
+                container(b"mvex", &[trex(1, 3000, NON_SYNC_SAMPLE), trex(2, 1024, 0)]),
+            ],
+        );
+        let mut moof = container(b"moof", &[traf(1, 90_000, 0, &[6, 6]), traf(2, 48_000, 0, &[1])]);
+        // Data offsets from the start of the moof to the samples in the mdat
+        let video_offset = (moof.len() + 8) as u32;
+        let trun_offsets: Vec<_> = moof
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| w == b"trun")
+            .map(|(i, _)| i + 12)
+            .collect();
+        moof[trun_offsets[0]..trun_offsets[0] + 4].copy_from_slice(&video_offset.to_be_bytes());
+        moof[trun_offsets[1]..trun_offsets[1] + 4].copy_from_slice(&(video_offset + 12).to_be_bytes());
+        let mdat = mp4_box(b"mdat", &[0, 0, 0, 2, 0x65, 1, 0, 0, 0, 2, 0x41, 2, 0xaa]);
+        [mp4_box(b"ftyp", b"iso6"), moov, moof, mdat].concat()
+    }
+
+    #[test]
+    fn test_parse_fmp4() {
+        let data = segment();
+        let segment = parse(&data).unwrap();
+        assert_eq!(segment.tracks.len(), 2);
+        let video = &segment.tracks[0];
+        assert_eq!(video.source.parameter_sets, [vec![0x67, 0x42], vec![0x68, 0xce]]);
+        assert_eq!((video.timescale, video.length_size), (90_000, Some(4)));
+        let audio = &segment.tracks[1].source;
+        assert_eq!((audio.clock_rate, audio.channels), (48_000, Some(2)));
+        assert_eq!(audio.parameter_sets, [vec![0x11, 0x90]]);
+
+        assert_eq!(segment.samples.len(), 3);
+        let samples = &segment.samples;
+        assert_eq!((samples[0].time, samples[0].keyframe), (90_000, true));
+        assert_eq!((samples[1].time, samples[1].keyframe), (93_000, false));
+        assert_eq!(&data[samples[1].range.clone()], [0, 0, 0, 2, 0x41, 2]);
+        assert_eq!((samples[2].track, samples[2].time), (1, 48_000));
+        assert_eq!(&data[samples[2].range.clone()], [0xaa]);
+    }
+
+    #[test]
+    fn test_parse_invalid_trun() {
+        // A base data offset at the end of the address space
+        let mut tfhd = 0x0000_0001u32.to_be_bytes().to_vec();
+        tfhd.extend_from_slice(&1u32.to_be_bytes());
+        tfhd.extend_from_slice(&(u64::MAX - 2).to_be_bytes());
+        let mut trun = 0x0000_0200u32.to_be_bytes().to_vec();
+        trun.extend_from_slice(&1u32.to_be_bytes());
+        trun.extend_from_slice(&6u32.to_be_bytes());
+        let traf = container(b"traf", &[mp4_box(b"tfhd", &tfhd), mp4_box(b"trun", &trun)]);
+        let data = [segment(), container(b"moof", &[traf])].concat();
+        assert!(matches!(parse(&data), Err(Error::Invalid("trun box"))));
+
+        // More samples than the box has sizes for
+        let mut tfhd = 0x0002_0000u32.to_be_bytes().to_vec();
+        tfhd.extend_from_slice(&1u32.to_be_bytes());
+        let mut trun = 0x0000_0200u32.to_be_bytes().to_vec();
+        trun.extend_from_slice(&u32::MAX.to_be_bytes());
+        trun.extend_from_slice(&6u32.to_be_bytes());
+        let traf = container(b"traf", &[mp4_box(b"tfhd", &tfhd), mp4_box(b"trun", &trun)]);
+        let data = [segment(), container(b"moof", &[traf])].concat();
+        assert!(matches!(parse(&data), Err(Error::Invalid("trun sample count"))));
+    }
+
+    #[test]
+    fn test_parse_invalid_box() {
+        let mut data = mp4_box(b"moov", &[0; 8]);
+        data[3] = 200;
+        assert!(matches!(parse(&data), Err(Error::Invalid(_))));
+    }
+}
@@ -0,0 +1,336 @@
+use super::{mkv, mp4};
+use crate::codec::{nal_type, to_annex_b};
+use crate::rtsp::server::{MediaReader, SourceFrame, SourceTrack};
+use crate::types::{Frame, FrameMetadata, FrameType, MediaType};
+use bytes::Bytes;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Invalid {0}")]
+    Invalid(&'static str),
+    #[error("No track of a supported codec")]
+    NoTracks,
+    #[error("Unsupported segment format {0}")]
+    UnsupportedFormat(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A track found by a container parser
+#[derive(Debug, Clone)]
+pub(super) struct Track {
+    pub source: SourceTrack,
+    /// Ticks per second of the sample times
+    pub timescale: u64,
+    /// Size of the NAL unit lengths of H.264 samples in AVCC format
+    pub length_size: Option<usize>,
+}
+
+/// A sample found by a container parser, in decode order per track
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Sample {
+    /// Index in the tracks of the parser
+    pub track: usize,
+    /// Decode time in the timescale of the track
+    pub time: u64,
+    /// Presentation minus decode time
+    pub composition_offset: i64,
+    pub duration: u64,
+    /// Bytes of the sample in the segment
+    pub range: Range<usize>,
+    pub keyframe: bool,
+}
+
+/// Tracks and samples of a segment
+#[derive(Debug, Default)]
+pub(super) struct Segment {
+    pub tracks: Vec<Track>,
+    pub samples: Vec<Sample>,
+}
+
+/// The NAL unit length size and the SPS and PPS of an
+/// AVCDecoderConfigurationRecord, as in the `avcC` box and the
+/// CodecPrivate of Matroska
+pub(super) fn parse_avcc(data: &[u8]) -> Option<(usize, Vec<Vec<u8>>)> {
+    let length_size = (*data.get(4)? & 0x03) as usize + 1;
+    let mut sets = Vec::new();
+    let mut pos = 5;
+    // SPS count in the low 5 bits, then the PPS count
+    for mask in [0x1f, 0xff] {
+        let count = *data.get(pos)? & mask;
+        pos += 1;
+        for _ in 0..count {
+            let len = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+            sets.push(data.get(pos + 2..pos + 2 + len)?.to_vec());
+            pos += 2 + len;
+        }
+    }
+    Some((length_size, sets))
+}
+
+/// Reads the samples of a fragmented MP4 or Matroska/WebM segment held in
+/// memory, e.g. a recording served by a [`Server`](crate::rtsp::server::Server).
+///
+/// H.264 and AAC tracks are read from both, Opus tracks from Matroska.
+/// Samples of all tracks are read in the order they are due, H.264
+/// samples are converted to Annex B format with the parameter sets in
+/// front of every keyframe.
+pub struct SegmentReader {
+    data: Bytes,
+    tracks: Vec<Track>,
+    sources: Vec<SourceTrack>,
+    // Samples of all tracks with their due times, ordered by them
+    samples: Vec<(Duration, Sample)>,
+    next: usize,
+}
+
+impl SegmentReader {
+    /// Reads a fragmented MP4 segment, an init segment followed by
+    /// fragments
+    pub fn mp4(data: impl Into<Bytes>) -> Result<Self> {
+        let data = data.into();
+        let segment = mp4::parse(&data)?;
+        Self::new(data, segment)
+    }
+
+    /// Reads a Matroska or WebM file, also a live one of unknown size
+    pub fn mkv(data: impl Into<Bytes>) -> Result<Self> {
+        let data = data.into();
+        let segment = mkv::parse(&data)?;
+        Self::new(data, segment)
+    }
+
+    /// Reads a whole file, the format is taken from the extension
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "mp4" | "m4s" | "m4v" | "m4a" | "fmp4" => Self::mp4(std::fs::read(path)?),
+            "mkv" | "mka" | "webm" => Self::mkv(std::fs::read(path)?),
+            _ => Err(Error::UnsupportedFormat(extension.to_string())),
+        }
+    }
+
+    fn new(data: Bytes, segment: Segment) -> Result<Self> {
+        if segment.tracks.is_empty() {
+            return Err(Error::NoTracks);
+        }
+        if segment.samples.iter().any(|s| s.range.end > data.len()) {
+            return Err(Error::Invalid("sample range"));
+        }
+        // Frames are due at their decode time, never before the previous
+        // frame of the track so decode order survives sorting
+        let mut last = vec![Duration::ZERO; segment.tracks.len()];
+        let mut samples: Vec<_> = segment
+            .samples
+            .into_iter()
+            .map(|sample| {
+                let time = ticks_to_duration(sample.time, segment.tracks[sample.track].timescale);
+                let due = time.max(last[sample.track]);
+                last[sample.track] = due;
+                (due, sample)
+            })
+            .collect();
+        samples.sort_by_key(|(due, _)| *due);
+        Ok(Self {
+            data,
+            sources: segment.tracks.iter().map(|t| t.source.clone()).collect(),
+            tracks: segment.tracks,
+            samples,
+            next: 0,
+        })
+    }
+
+    fn frame_data(&self, sample: &Sample) -> Vec<u8> {
+        let data = &self.data[sample.range.clone()];
+        let track = &self.tracks[sample.track];
+        let Some(length_size) = track.length_size else {
+            return data.to_vec();
+        };
+        let mut nals = Vec::new();
+        let mut pos = 0;
+        while pos + length_size <= data.len() {
+            let len = data[pos..pos + length_size]
+                .iter()
+                .fold(0usize, |len, b| (len << 8) | *b as usize);
+            pos += length_size;
+            let Some(nal) = data.get(pos..pos + len) else {
+                break;
+            };
+            nals.push(nal);
+            pos += len;
+        }
+        let has_sps = nals.iter().any(|nal| nal_type(FrameType::H264, nal) == Some(7));
+        if sample.keyframe && !has_sps {
+            let sets = track.source.parameter_sets.iter().map(Vec::as_slice);
+            return to_annex_b(sets.chain(nals));
+        }
+        to_annex_b(nals)
+    }
+}
+
+fn ticks_to_duration(ticks: u64, timescale: u64) -> Duration {
+    let nanos = ticks as u128 * 1_000_000_000 / timescale.max(1) as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+impl MediaReader for SegmentReader {
+    fn tracks(&self) -> &[SourceTrack] {
+        &self.sources
+    }
+
+    fn read_frame(&mut self) -> io::Result<Option<SourceFrame>> {
+        let Some((time, sample)) = self.samples.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        let track = &self.tracks[sample.track];
+        // RTP timestamps are presentation times at the clock rate of the track
+        let pts = sample.time as i128 + sample.composition_offset as i128;
+        let timestamp = pts * track.source.clock_rate as i128 / track.timescale.max(1) as i128;
+        let frame = Frame {
+            media_type: track.source.media_type,
+            frame_type: track.source.frame_type,
+            timestamp: timestamp as u32,
+            data: self.frame_data(sample),
+            metadata: FrameMetadata::default(),
+        };
+        Ok(Some(SourceFrame {
+            track: sample.track,
+            time: *time,
+            frame,
+        }))
+    }
+
+    /// Continues at the last keyframe of the first video track at or
+    /// before `position`, at the last sample before it without video
+    fn seek(&mut self, position: Duration) -> io::Result<Duration> {
+        let video = self.sources.iter().position(|t| t.media_type == MediaType::Video);
+        let start = self
+            .samples
+            .iter()
+            .enumerate()
+            .take_while(|(_, (time, _))| *time <= position)
+            .filter(|(_, (_, sample))| video.is_none_or(|video| sample.track == video && sample.keyframe))
+            .last()
+            .map_or(0, |(i, _)| i);
+        self.next = start;
+        Ok(self.samples.get(start).map_or(Duration::ZERO, |(time, _)| *time))
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.samples
+            .iter()
+            .map(|(time, sample)| *time + ticks_to_duration(sample.duration, self.tracks[sample.track].timescale))
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video_track() -> Track {
+        Track {
+            source: SourceTrack {
+                media_type: MediaType::Video,
+                frame_type: FrameType::H264,
+                clock_rate: 90_000,
+                channels: None,
+                parameter_sets: vec![vec![0x67, 0x42], vec![0x68, 0xce]],
+            },
+            timescale: 1000,
+            length_size: Some(4),
+        }
+    }
+
+    fn sample(track: usize, time: u64, range: Range<usize>, keyframe: bool) -> Sample {
+        Sample {
+            track,
+            time,
+            composition_offset: 0,
+            duration: 500,
+            range,
+            keyframe,
+        }
+    }
+
+    #[test]
+    fn test_parse_avcc() {
+        let avcc = [1, 0x42, 0, 0x1e, 0xff, 0xe1, 0, 2, 0x67, 0x42, 1, 0, 2, 0x68, 0xce];
+        let (length_size, sets) = parse_avcc(&avcc).unwrap();
+        assert_eq!(length_size, 4);
+        assert_eq!(sets, [vec![0x67, 0x42], vec![0x68, 0xce]]);
+        assert!(parse_avcc(&avcc[..10]).is_none());
+    }
+
+    #[test]
+    fn test_segment_reader() {
+        let data = Bytes::from_static(&[0, 0, 0, 2, 0x65, 1, 0, 0, 0, 2, 0x41, 2, 0xaa]);
+        let audio = Track {
+            source: SourceTrack {
+                media_type: MediaType::Audio,
+                frame_type: FrameType::AAC,
+                clock_rate: 48_000,
+                channels: Some(2),
+                parameter_sets: vec![vec![0x11, 0x90]],
+            },
+            timescale: 48_000,
+            length_size: None,
+        };
+        let segment = Segment {
+            tracks: vec![video_track(), audio],
+            samples: vec![
+                sample(0, 0, 0..6, true),
+                sample(0, 500, 6..12, false),
+                sample(1, 12_000, 12..13, true),
+            ],
+        };
+        let mut reader = SegmentReader::new(data, segment).unwrap();
+        assert_eq!(reader.duration(), Some(Duration::from_millis(1000)));
+
+        let first = reader.read_frame().unwrap().unwrap();
+        assert_eq!(
+            first.frame.data,
+            [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 1]
+        );
+        let audio = reader.read_frame().unwrap().unwrap();
+        assert_eq!((audio.track, audio.time), (1, Duration::from_millis(250)));
+        assert_eq!(audio.frame.timestamp, 12_000);
+        let second = reader.read_frame().unwrap().unwrap();
+        assert_eq!(second.frame.data, [0, 0, 0, 1, 0x41, 2]);
+        assert_eq!(second.frame.timestamp, 45_000);
+        assert!(reader.read_frame().unwrap().is_none());
+
+        // The only keyframe is at the start
+        assert_eq!(reader.seek(Duration::from_millis(600)).unwrap(), Duration::ZERO);
+        assert_eq!(reader.read_frame().unwrap().unwrap().time, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_segment_reader_errors() {
+        let segment = Segment {
+            tracks: vec![video_track()],
+            samples: vec![sample(0, 0, 0..10, true)],
+        };
+        assert!(matches!(
+            SegmentReader::new(Bytes::from_static(&[0; 4]), segment),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            SegmentReader::new(Bytes::new(), Segment::default()),
+            Err(Error::NoTracks)
+        ));
+        assert!(matches!(
+            SegmentReader::open("recording.avi"),
+            Err(Error::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,426 @@
+use super::key::{aes_cm, SessionKeys};
+use super::{CryptoAttribute, CryptoSuite, MasterKey, ParseCryptoError};
+use crate::sdp::Media;
+use aws_lc_rs::{constant_time, hmac};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// SRTCP always uses an 80 bit tag
+const SRTCP_TAG_LEN: usize = 10;
+const REPLAY_WINDOW: u64 = 64;
+/// Packets a master key may protect at most (RFC 3711 9.2)
+const SRTP_MAX_PACKETS: u64 = 1 << 48;
+const SRTCP_MAX_PACKETS: u32 = 1 << 31;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("SRTP packet too short")]
+    TooShort,
+    #[error("SRTP authentication failed")]
+    AuthenticationFailed,
+    #[error("Replayed SRTP packet with index {0}")]
+    Replayed(u64),
+    #[error(transparent)]
+    ParseCrypto(#[from] ParseCryptoError),
+    #[error("No supported crypto attribute")]
+    NoCrypto,
+    #[error("SRTP master key lifetime exceeded")]
+    KeyExpired,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Rejects indices that were already received or are older than the window
+#[derive(Debug, Default, Clone, Copy)]
+struct ReplayWindow {
+    highest: Option<u64>,
+    // Bit n is set if highest - n was received
+    received: u64,
+}
+
+impl ReplayWindow {
+    fn check(&self, index: u64) -> bool {
+        match self.highest {
+            Some(highest) if index <= highest => {
+                let age = highest - index;
+                age < REPLAY_WINDOW && self.received & (1 << age) == 0
+            }
+            _ => true,
+        }
+    }
+
+    fn update(&mut self, index: u64) {
+        match self.highest {
+            // Indices older than the window are neither tracked nor accepted
+            Some(highest) if index <= highest => {
+                if highest - index < REPLAY_WINDOW {
+                    self.received |= 1 << (highest - index);
+                }
+            }
+            Some(highest) => {
+                let shift = index - highest;
+                self.received = if shift < REPLAY_WINDOW {
+                    self.received << shift
+                } else {
+                    0
+                } | 1;
+                self.highest = Some(index);
+            }
+            None => {
+                self.received = 1;
+                self.highest = Some(index);
+            }
+        }
+    }
+}
+
+/// Rollover counter and replay state of one SSRC
+#[derive(Debug, Default, Clone, Copy)]
+struct Stream {
+    roc: u32,
+    // Highest sequence number received, None before the first packet
+    s_l: Option<u16>,
+    replay: ReplayWindow,
+}
+
+impl Stream {
+    /// Guesses the rollover counter of `seq` (RFC 3711 3.3.1)
+    fn estimate_roc(&self, seq: u16) -> u32 {
+        let Some(s_l) = self.s_l else {
+            return self.roc;
+        };
+        if s_l < 0x8000 {
+            if seq > s_l && seq - s_l > 0x8000 {
+                return self.roc.wrapping_sub(1);
+            }
+        } else if s_l - 0x8000 > seq {
+            return self.roc.wrapping_add(1);
+        }
+        self.roc
+    }
+
+    fn update(&mut self, roc: u32, seq: u16) {
+        let index = (roc as u64) << 16 | seq as u64;
+        if self.s_l.is_none() || index > (self.roc as u64) << 16 | self.s_l.unwrap_or_default() as u64 {
+            self.roc = roc;
+            self.s_l = Some(seq);
+        }
+        self.replay.update(index);
+    }
+}
+
+/// Length of the RTP header including CSRCs and the header extension
+fn rtp_header_len(packet: &[u8]) -> Result<usize> {
+    if packet.len() < 12 {
+        return Err(Error::TooShort);
+    }
+    let mut len = 12 + (packet[0] & 0x0f) as usize * 4;
+    if packet[0] & 0x10 != 0 {
+        let ext = packet.get(len + 2..len + 4).ok_or(Error::TooShort)?;
+        len += 4 + u16::from_be_bytes([ext[0], ext[1]]) as usize * 4;
+    }
+    Ok(len)
+}
+
+fn ssrc(packet: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        packet[offset],
+        packet[offset + 1],
+        packet[offset + 2],
+        packet[offset + 3],
+    ])
+}
+
+/// SRTP/SRTCP keys and state of one master key, e.g. of one media
+/// description. Packets are authenticated and decrypted with
+/// [`SrtpContext::unprotect_rtp`] before they are parsed, see
+/// [`Channel::srtp`](crate::rtsp::client::Channel::srtp).
+pub struct SrtpContext {
+    suite: CryptoSuite,
+    mki: Option<(u32, usize)>,
+    rtp: SessionKeys,
+    rtcp: SessionKeys,
+    streams: HashMap<u32, Stream>,
+    rtcp_replay: HashMap<u32, ReplayWindow>,
+    // Index of the next protected SRTCP packet
+    rtcp_index: u32,
+    // Packets processed with the master key and how many it may process
+    packets: u64,
+    lifetime: u64,
+}
+
+impl SrtpContext {
+    pub fn new(suite: CryptoSuite, key: &MasterKey) -> Self {
+        Self {
+            suite,
+            mki: None,
+            rtp: SessionKeys::derive(key, 0),
+            rtcp: SessionKeys::derive(key, 3),
+            streams: HashMap::new(),
+            rtcp_replay: HashMap::new(),
+            rtcp_index: 0,
+            packets: 0,
+            lifetime: SRTP_MAX_PACKETS,
+        }
+    }
+
+    pub fn from_crypto(crypto: &CryptoAttribute) -> Self {
+        Self {
+            mki: crypto.mki,
+            ..Self::new(crypto.suite, &crypto.key).lifetime(crypto.lifetime.unwrap_or(SRTP_MAX_PACKETS))
+        }
+    }
+
+    /// Number of SRTP and SRTCP packets the master key may protect, at
+    /// most 2^48. Once they were processed every packet fails with
+    /// [`Error::KeyExpired`] until the context is replaced by one of a new key.
+    pub fn lifetime(mut self, packets: u64) -> Self {
+        self.lifetime = packets.min(SRTP_MAX_PACKETS);
+        self
+    }
+
+    /// Counts a packet against the lifetime of the master key
+    fn use_key(&mut self) -> Result<()> {
+        if self.packets >= self.lifetime {
+            return Err(Error::KeyExpired);
+        }
+        self.packets += 1;
+        Ok(())
+    }
+
+    /// Keyed from the first supported `a=crypto` attribute of the media
+    pub fn from_media(media: &Media) -> Result<Self> {
+        media
+            .attributes("crypto")
+            .find_map(|v| v.parse::<CryptoAttribute>().ok())
+            .map(|crypto| Self::from_crypto(&crypto))
+            .ok_or(Error::NoCrypto)
+    }
+
+    pub fn suite(&self) -> CryptoSuite {
+        self.suite
+    }
+
+    fn mki_len(&self) -> usize {
+        self.mki.map_or(0, |(_, len)| len)
+    }
+
+    fn mki_bytes(&self) -> Vec<u8> {
+        self.mki
+            .map(|(value, len)| value.to_be_bytes()[4 - len..].to_vec())
+            .unwrap_or_default()
+    }
+
+    fn tag(key: &hmac::Key, data: &[u8], roc: Option<u32>) -> hmac::Tag {
+        let mut context = hmac::Context::with_key(key);
+        context.update(data);
+        if let Some(roc) = roc {
+            context.update(&roc.to_be_bytes());
+        }
+        context.sign()
+    }
+
+    /// Authenticates and decrypts a SRTP packet, returns the RTP packet
+    pub fn unprotect_rtp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let header_len = rtp_header_len(packet)?;
+        let tag_len = self.suite.tag_len();
+        let end = packet
+            .len()
+            .checked_sub(self.mki_len() + tag_len)
+            .filter(|end| *end >= header_len)
+            .ok_or(Error::TooShort)?;
+        let seq = u16::from_be_bytes([packet[2], packet[3]]);
+        let ssrc = ssrc(packet, 8);
+        let stream = self.streams.get(&ssrc).copied().unwrap_or_default();
+        let roc = stream.estimate_roc(seq);
+        let index = (roc as u64) << 16 | seq as u64;
+        if !stream.replay.check(index) {
+            return Err(Error::Replayed(index));
+        }
+        let tag = Self::tag(&self.rtp.auth, &packet[..end], Some(roc));
+        constant_time::verify_slices_are_equal(&tag.as_ref()[..tag_len], &packet[packet.len() - tag_len..])
+            .map_err(|_| Error::AuthenticationFailed)?;
+        self.use_key()?;
+        let mut rtp = packet[..end].to_vec();
+        aes_cm(&self.rtp.cipher, self.rtp.iv(ssrc, index), &mut rtp[header_len..]);
+        self.streams.entry(ssrc).or_default().update(roc, seq);
+        Ok(rtp)
+    }
+
+    /// Encrypts a RTP packet and appends the authentication tag
+    pub fn protect_rtp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let header_len = rtp_header_len(packet)?;
+        if packet.len() < header_len {
+            return Err(Error::TooShort);
+        }
+        self.use_key()?;
+        let seq = u16::from_be_bytes([packet[2], packet[3]]);
+        let ssrc = ssrc(packet, 8);
+        let stream = self.streams.entry(ssrc).or_default();
+        let roc = stream.estimate_roc(seq);
+        stream.update(roc, seq);
+        let index = (roc as u64) << 16 | seq as u64;
+        let mut srtp = packet.to_vec();
+        aes_cm(&self.rtp.cipher, self.rtp.iv(ssrc, index), &mut srtp[header_len..]);
+        let tag = Self::tag(&self.rtp.auth, &srtp, Some(roc));
+        srtp.extend_from_slice(&self.mki_bytes());
+        srtp.extend_from_slice(&tag.as_ref()[..self.suite.tag_len()]);
+        Ok(srtp)
+    }
+
+    /// Authenticates and decrypts a SRTCP compound packet
+    pub fn unprotect_rtcp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let end = packet
+            .len()
+            .checked_sub(self.mki_len() + SRTCP_TAG_LEN)
+            .filter(|end| *end >= 12)
+            .ok_or(Error::TooShort)?;
+        let e_index = ssrc(packet, end - 4);
+        let encrypted = e_index & 0x8000_0000 != 0;
+        let index = (e_index & 0x7fff_ffff) as u64;
+        let ssrc = ssrc(packet, 4);
+        let replay = self.rtcp_replay.get(&ssrc).copied().unwrap_or_default();
+        if !replay.check(index) {
+            return Err(Error::Replayed(index));
+        }
+        let tag = Self::tag(&self.rtcp.auth, &packet[..end], None);
+        constant_time::verify_slices_are_equal(&tag.as_ref()[..SRTCP_TAG_LEN], &packet[packet.len() - SRTCP_TAG_LEN..])
+            .map_err(|_| Error::AuthenticationFailed)?;
+        self.use_key()?;
+        let mut rtcp = packet[..end - 4].to_vec();
+        if encrypted {
+            aes_cm(&self.rtcp.cipher, self.rtcp.iv(ssrc, index), &mut rtcp[8..]);
+        }
+        self.rtcp_replay.entry(ssrc).or_default().update(index);
+        Ok(rtcp)
+    }
+
+    /// Encrypts a RTCP compound packet and appends index and authentication tag
+    pub fn protect_rtcp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        if packet.len() < 8 {
+            return Err(Error::TooShort);
+        }
+        // The 31 bit SRTCP index must not repeat with the same key
+        if self.rtcp_index >= SRTCP_MAX_PACKETS {
+            return Err(Error::KeyExpired);
+        }
+        self.use_key()?;
+        let index = self.rtcp_index;
+        self.rtcp_index += 1;
+        let mut srtcp = packet.to_vec();
+        aes_cm(
+            &self.rtcp.cipher,
+            self.rtcp.iv(ssrc(packet, 4), index as u64),
+            &mut srtcp[8..],
+        );
+        srtcp.extend_from_slice(&(index | 0x8000_0000).to_be_bytes());
+        let tag = Self::tag(&self.rtcp.auth, &srtcp, None);
+        srtcp.extend_from_slice(&self.mki_bytes());
+        srtcp.extend_from_slice(&tag.as_ref()[..SRTCP_TAG_LEN]);
+        Ok(srtcp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_key() -> MasterKey {
+        MasterKey::new([1; 16], [2; 14])
+    }
+
+    fn rtp(seq: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 1, 0xca, 0xfe, 0xba, 0xbe]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn test_srtp_round_trip() {
+        let mut sender = SrtpContext::new(CryptoSuite::AesCm128HmacSha1_80, &master_key());
+        let mut receiver = SrtpContext::new(CryptoSuite::AesCm128HmacSha1_80, &master_key());
+        for seq in [65534, 65535, 0, 1] {
+            let packet = rtp(seq, b"payload");
+            let srtp = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(srtp.len(), packet.len() + 10);
+            assert_ne!(&srtp[12..19], b"payload");
+            assert_eq!(receiver.unprotect_rtp(&srtp).unwrap(), packet);
+            assert!(matches!(receiver.unprotect_rtp(&srtp), Err(Error::Replayed(_))));
+        }
+        // The rollover counter was incremented after 65535
+        assert_eq!(receiver.streams[&0xcafebabe].roc, 1);
+
+        let mut srtp = sender.protect_rtp(&rtp(2, b"payload")).unwrap();
+        srtp[13] ^= 1;
+        assert!(matches!(
+            receiver.unprotect_rtp(&srtp),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn test_srtcp_round_trip() {
+        let crypto: CryptoAttribute =
+            "1 AES_CM_128_HMAC_SHA1_32 inline:4eF6Ok09ZJGxOy+TUSOQcqwlTy8MJQmVBpVs12Nz|2^20|1:4"
+                .parse()
+                .unwrap();
+        assert_eq!(crypto.mki, Some((1, 4)));
+        let mut sender = SrtpContext::from_crypto(&crypto);
+        let mut receiver = SrtpContext::from_crypto(&crypto);
+        // RR without report blocks
+        let rr = [0x80, 201, 0, 1, 0, 0, 0, 1];
+        let srtcp = sender.protect_rtcp(&rr).unwrap();
+        assert_eq!(srtcp.len(), rr.len() + 4 + 4 + SRTCP_TAG_LEN);
+        assert_eq!(receiver.unprotect_rtcp(&srtcp).unwrap(), rr);
+        assert!(matches!(receiver.unprotect_rtcp(&srtcp), Err(Error::Replayed(0))));
+        // The 32 bit tag only applies to SRTP
+        let srtp = sender.protect_rtp(&rtp(1, b"x")).unwrap();
+        assert_eq!(srtp.len(), 13 + 4 + 4);
+        assert_eq!(receiver.unprotect_rtp(&srtp).unwrap(), rtp(1, b"x"));
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+        window.update(100);
+        assert!(!window.check(100) && window.check(99));
+        // Far behind the highest index, e.g. a packet protected out of order
+        window.update(100 - REPLAY_WINDOW);
+        assert!(!window.check(100 - REPLAY_WINDOW));
+        window.update(1000);
+        assert!(window.check(999) && !window.check(1000));
+    }
+
+    #[test]
+    fn test_key_lifetime() {
+        let crypto: CryptoAttribute = "1 AES_CM_128_HMAC_SHA1_80 inline:4eF6Ok09ZJGxOy+TUSOQcqwlTy8MJQmVBpVs12Nz|2"
+            .parse()
+            .unwrap();
+        let mut sender = SrtpContext::from_crypto(&crypto);
+        let mut receiver = SrtpContext::new(crypto.suite, &crypto.key).lifetime(1);
+        let srtp = sender.protect_rtp(&rtp(1, b"x")).unwrap();
+        receiver.unprotect_rtp(&srtp).unwrap();
+        let srtp = sender.protect_rtp(&rtp(2, b"x")).unwrap();
+        assert!(matches!(receiver.unprotect_rtp(&srtp), Err(Error::KeyExpired)));
+        assert!(matches!(
+            sender.protect_rtcp(&[0x80, 201, 0, 1, 0, 0, 0, 1]),
+            Err(Error::KeyExpired)
+        ));
+    }
+
+    #[test]
+    fn test_context_from_media() {
+        let mut media: Media = "video 0 RTP/SAVP 96".parse().unwrap();
+        assert!(matches!(SrtpContext::from_media(&media), Err(Error::NoCrypto)));
+        media.attributes.push((
+            "crypto".to_string(),
+            Some("1 AES_CM_128_HMAC_SHA1_80 inline:4eF6Ok09ZJGxOy+TUSOQcqwlTy8MJQmVBpVs12Nz".to_string()),
+        ));
+        assert_eq!(
+            SrtpContext::from_media(&media).unwrap().suite(),
+            CryptoSuite::AesCm128HmacSha1_80
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use aws_lc_rs::cipher::{DecryptingKey, DecryptionContext, UnboundCipherKey, AES_128};
+use aws_lc_rs::hmac;
+use aws_lc_rs::iv::FixedLength;
+use base64::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub const MASTER_KEY_LEN: usize = 16;
+pub const MASTER_SALT_LEN: usize = 14;
+const AUTH_KEY_LEN: usize = 20;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseCryptoError {
+    #[error("Invalid crypto attribute {0}")]
+    InvalidFormat(String),
+    #[error("Unsupported crypto suite {0}")]
+    UnsupportedSuite(String),
+    #[error("Unsupported key method {0}")]
+    UnsupportedKeyMethod(String),
+    #[error("Invalid master key")]
+    InvalidKey,
+}
+
+/// SRTP crypto suites (RFC 4568 6.2), both use AES-CM with a 128 bit key
+/// and HMAC-SHA1, they differ in the length of the authentication tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoSuite {
+    AesCm128HmacSha1_80,
+    AesCm128HmacSha1_32,
+}
+
+impl CryptoSuite {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CryptoSuite::AesCm128HmacSha1_80 => "AES_CM_128_HMAC_SHA1_80",
+            CryptoSuite::AesCm128HmacSha1_32 => "AES_CM_128_HMAC_SHA1_32",
+        }
+    }
+
+    /// Length of the SRTP authentication tag in bytes, SRTCP always uses 80 bits
+    pub fn tag_len(&self) -> usize {
+        match self {
+            CryptoSuite::AesCm128HmacSha1_80 => 10,
+            CryptoSuite::AesCm128HmacSha1_32 => 4,
+        }
+    }
+}
+
+impl FromStr for CryptoSuite {
+    type Err = ParseCryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [CryptoSuite::AesCm128HmacSha1_80, CryptoSuite::AesCm128HmacSha1_32]
+            .into_iter()
+            .find(|suite| suite.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseCryptoError::UnsupportedSuite(s.to_string()))
+    }
+}
+
+/// Master key and salt the session keys are derived from
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey {
+    key: [u8; MASTER_KEY_LEN],
+    salt: [u8; MASTER_SALT_LEN],
+}
+
+impl MasterKey {
+    pub fn new(key: [u8; MASTER_KEY_LEN], salt: [u8; MASTER_SALT_LEN]) -> Self {
+        Self { key, salt }
+    }
+
+    /// Parses the base64 encoded concatenation of key and salt, as used by
+    /// the `inline:` key parameter of SDES
+    pub fn from_base64(s: &str) -> Result<Self, ParseCryptoError> {
+        let bytes = BASE64_STANDARD.decode(s).map_err(|_| ParseCryptoError::InvalidKey)?;
+        if bytes.len() != MASTER_KEY_LEN + MASTER_SALT_LEN {
+            return Err(ParseCryptoError::InvalidKey);
+        }
+        let (key, salt) = bytes.split_at(MASTER_KEY_LEN);
+        Ok(Self {
+            key: key.try_into().unwrap(),
+            salt: salt.try_into().unwrap(),
+        })
+    }
+
+    pub fn key(&self) -> &[u8; MASTER_KEY_LEN] {
+        &self.key
+    }
+
+    pub fn salt(&self) -> &[u8; MASTER_SALT_LEN] {
+        &self.salt
+    }
+}
+
+/// Doesn't print the key material
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// SDP `a=crypto` attribute (SDES, RFC 4568), e.g.
+/// `1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CryptoAttribute {
+    pub tag: u32,
+    pub suite: CryptoSuite,
+    pub key: MasterKey,
+    /// Value and length in bytes of the master key identifier carried in every packet
+    pub mki: Option<(u32, usize)>,
+    /// Packets the master key may protect, e.g. `2^20`
+    pub lifetime: Option<u64>,
+}
+
+impl FromStr for CryptoAttribute {
+    type Err = ParseCryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseCryptoError::InvalidFormat(s.to_string());
+        let mut iter = s.split_whitespace();
+        let tag = iter.next().and_then(|t| t.parse().ok()).ok_or_else(invalid)?;
+        let suite = iter.next().ok_or_else(invalid)?.parse()?;
+        // Only the first of several keys is used, session parameters are ignored
+        let key_params = iter.next().ok_or_else(invalid)?.split(';').next().unwrap_or_default();
+        let (method, info) = key_params.split_once(':').ok_or_else(invalid)?;
+        if !method.eq_ignore_ascii_case("inline") {
+            return Err(ParseCryptoError::UnsupportedKeyMethod(method.to_string()));
+        }
+        let mut parts = info.split('|');
+        let key = MasterKey::from_base64(parts.next().unwrap_or_default())?;
+        // The optional lifetime is followed by the optional MKI, which contains a colon
+        let parts: Vec<_> = parts.collect();
+        let lifetime = match parts.iter().find(|p| !p.contains(':')) {
+            Some(lifetime) => Some(parse_lifetime(lifetime).ok_or_else(invalid)?),
+            None => None,
+        };
+        let mki = match parts.iter().find(|p| p.contains(':')) {
+            Some(mki) => {
+                let (value, len) = mki.split_once(':').ok_or_else(invalid)?;
+                let value = value.parse().map_err(|_| invalid())?;
+                let len: usize = len.parse().map_err(|_| invalid())?;
+                if !(1..=4).contains(&len) {
+                    return Err(invalid());
+                }
+                Some((value, len))
+            }
+            None => None,
+        };
+        Ok(Self {
+            tag,
+            suite,
+            key,
+            mki,
+            lifetime,
+        })
+    }
+}
+
+/// A key lifetime, either decimal or a power of two like `2^20`
+fn parse_lifetime(s: &str) -> Option<u64> {
+    match s.strip_prefix("2^") {
+        Some(exponent) => exponent.parse().ok().and_then(|e: u32| 1u64.checked_shl(e)),
+        None => s.parse().ok(),
+    }
+}
+
+/// AES in counter mode, XORs the key stream starting at `iv` into `data`
+pub(crate) fn aes_cm(key: &DecryptingKey, iv: [u8; 16], data: &mut [u8]) {
+    if data.is_empty() {
+        return;
+    }
+    // Counter mode only fails for invalid contexts, which can't happen with a 128 bit IV
+    let _ = key.decrypt(data, DecryptionContext::Iv128(FixedLength::from(iv)));
+}
+
+fn cipher_key(key: &[u8]) -> DecryptingKey {
+    UnboundCipherKey::new(&AES_128, key)
+        .and_then(DecryptingKey::ctr)
+        .expect("AES-128 key of 16 bytes")
+}
+
+/// Fills `out` with the key of `label`, the key derivation rate is always 0
+fn derive_key(master_cipher: &DecryptingKey, master: &MasterKey, label: u8, out: &mut [u8]) {
+    let mut iv = [0u8; 16];
+    iv[..MASTER_SALT_LEN].copy_from_slice(&master.salt);
+    iv[7] ^= label;
+    out.fill(0);
+    aes_cm(master_cipher, iv, out);
+}
+
+/// Session keys of SRTP or SRTCP (RFC 3711 4.3)
+pub(crate) struct SessionKeys {
+    pub cipher: DecryptingKey,
+    pub salt: [u8; MASTER_SALT_LEN],
+    pub auth: hmac::Key,
+}
+
+impl SessionKeys {
+    /// `label` is the label of the encryption key, 0 for SRTP and 3 for
+    /// SRTCP, the authentication key and salt use the following ones
+    pub fn derive(master: &MasterKey, label: u8) -> Self {
+        let master_cipher = cipher_key(&master.key);
+        let mut key = [0u8; MASTER_KEY_LEN];
+        let mut auth = [0u8; AUTH_KEY_LEN];
+        let mut salt = [0u8; MASTER_SALT_LEN];
+        derive_key(&master_cipher, master, label, &mut key);
+        derive_key(&master_cipher, master, label + 1, &mut auth);
+        derive_key(&master_cipher, master, label + 2, &mut salt);
+        Self {
+            cipher: cipher_key(&key),
+            salt,
+            auth: hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &auth),
+        }
+    }
+
+    /// IV = (k_s * 2^16) XOR (SSRC * 2^64) XOR (index * 2^16)
+    pub fn iv(&self, ssrc: u32, index: u64) -> [u8; 16] {
+        let mut iv = [0u8; 16];
+        iv[..MASTER_SALT_LEN].copy_from_slice(&self.salt);
+        for (b, s) in iv[4..8].iter_mut().zip(ssrc.to_be_bytes()) {
+            *b ^= s;
+        }
+        for (b, i) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+            *b ^= i;
+        }
+        iv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_key_derivation() {
+        // RFC 3711 B.3
+        let master = MasterKey::new(
+            hex("E1F97A0D3E018BE0D64FA32C06DE4139").try_into().unwrap(),
+            hex("0EC675AD498AFEEBB6960B3AABE6").try_into().unwrap(),
+        );
+        let cipher = cipher_key(master.key());
+        let mut key = [0u8; MASTER_KEY_LEN];
+        let mut auth = [0u8; AUTH_KEY_LEN];
+        let mut salt = [0u8; MASTER_SALT_LEN];
+        derive_key(&cipher, &master, 0, &mut key);
+        derive_key(&cipher, &master, 1, &mut auth);
+        derive_key(&cipher, &master, 2, &mut salt);
+        assert_eq!(key.to_vec(), hex("C61E7A93744F39EE10734AFE3FF7A087"));
+        assert_eq!(auth.to_vec(), hex("CEBE321F6FF7716B6FD4AB49AF256A156D38BAA4"));
+        assert_eq!(salt.to_vec(), hex("30CBBC08863D8C85D49DB34A9AE1"));
+    }
+
+    #[test]
+    fn test_parse_crypto_attribute() {
+        let crypto: CryptoAttribute =
+            "1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4"
+                .parse()
+                .unwrap();
+        assert_eq!(crypto.tag, 1);
+        assert_eq!(crypto.suite, CryptoSuite::AesCm128HmacSha1_80);
+        assert_eq!(crypto.mki, Some((1, 4)));
+        assert_eq!(crypto.lifetime, Some(1 << 20));
+        assert_eq!(crypto.key.key()[0], 0x3d);
+        let crypto: CryptoAttribute = "1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|1000"
+            .parse()
+            .unwrap();
+        assert_eq!((crypto.lifetime, crypto.mki), (Some(1000), None));
+        assert!(
+            "1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^64"
+                .parse::<CryptoAttribute>()
+                .is_err()
+        );
+        assert!(matches!(
+            "1 F8_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR".parse::<CryptoAttribute>(),
+            Err(ParseCryptoError::UnsupportedSuite(_))
+        ));
+        assert!(matches!(
+            "1 AES_CM_128_HMAC_SHA1_32 inline:c2hvcnQ=".parse::<CryptoAttribute>(),
+            Err(ParseCryptoError::InvalidKey)
+        ));
+    }
+}
@@ -0,0 +1,12 @@
+mod context;
+mod key;
+
+pub use context::Error;
+pub use context::Result;
+pub use context::SrtpContext;
+pub use key::CryptoAttribute;
+pub use key::CryptoSuite;
+pub use key::MasterKey;
+pub use key::ParseCryptoError;
+pub use key::MASTER_KEY_LEN;
+pub use key::MASTER_SALT_LEN;
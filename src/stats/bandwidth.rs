@@ -0,0 +1,148 @@
+use super::Stats;
+use std::time::{Duration, Instant};
+
+/// Weight of the newest sample unless set with [`BandwidthEstimator::smoothing`]
+pub const DEFAULT_SMOOTHING: f64 = 0.3;
+
+/// Receive rate and loss of a stream, smoothed over the samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BandwidthEstimate {
+    /// Received payload and RTP headers in bits per second
+    pub bitrate: u64,
+    /// Fraction of the expected packets that were lost
+    pub loss: f64,
+    /// Highest interarrival jitter of the tracks
+    pub jitter: Duration,
+}
+
+/// Estimates the receive bandwidth and loss of a stream from periodic
+/// [`Stats`] snapshots, with an exponential moving average so a single
+/// burst doesn't decide.
+///
+/// ```ignore
+/// let mut estimator = BandwidthEstimator::new();
+/// loop {
+///     interval.tick().await;
+///     if let Some(estimate) = estimator.update(&stats.snapshot().await, Instant::now()) {
+///         log::info!("Receiving {} kbit/s", estimate.bitrate / 1000);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BandwidthEstimator {
+    smoothing: f64,
+    // Time, bytes, packets and losses of the previous snapshot
+    previous: Option<(Instant, u64, u64, u64)>,
+    estimate: Option<BandwidthEstimate>,
+}
+
+impl Default for BandwidthEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandwidthEstimator {
+    pub fn new() -> Self {
+        Self {
+            smoothing: DEFAULT_SMOOTHING,
+            previous: None,
+            estimate: None,
+        }
+    }
+
+    /// Weight of the newest sample from 0 to 1, 1 disables the smoothing
+    pub fn smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing.clamp(f64::EPSILON, 1.0);
+        self
+    }
+
+    pub fn estimate(&self) -> Option<BandwidthEstimate> {
+        self.estimate
+    }
+
+    /// Forgets the samples, e.g. after switching to another stream
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.estimate = None;
+    }
+
+    /// Adds the snapshot taken at `now` and returns the estimate, none
+    /// until two snapshots are known
+    pub fn update(&mut self, stats: &Stats, now: Instant) -> Option<BandwidthEstimate> {
+        let bytes = stats.tracks.values().map(|t| t.bytes).sum();
+        let packets = stats.tracks.values().map(|t| t.packets).sum();
+        let lost = stats.total_lost();
+        let jitter = stats.tracks.values().map(|t| t.jitter).max().unwrap_or_default();
+        let (at, previous_bytes, previous_packets, previous_lost) =
+            self.previous.replace((now, bytes, packets, lost))?;
+        let elapsed = now.saturating_duration_since(at).as_secs_f64();
+        // The counters start over when the tracks are registered again
+        if elapsed == 0.0 || bytes < previous_bytes || packets < previous_packets || lost < previous_lost {
+            return self.estimate;
+        }
+        let received = packets - previous_packets;
+        let lost = lost - previous_lost;
+        let sample = BandwidthEstimate {
+            bitrate: ((bytes - previous_bytes) as f64 * 8.0 / elapsed) as u64,
+            loss: match received + lost {
+                0 => 0.0,
+                expected => lost as f64 / expected as f64,
+            },
+            jitter,
+        };
+        let alpha = self.smoothing;
+        let estimate = match self.estimate {
+            Some(e) => BandwidthEstimate {
+                bitrate: (alpha * sample.bitrate as f64 + (1.0 - alpha) * e.bitrate as f64) as u64,
+                loss: alpha * sample.loss + (1.0 - alpha) * e.loss,
+                jitter,
+            },
+            None => sample,
+        };
+        self.estimate = Some(estimate);
+        self.estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::TrackStats;
+
+    fn stats(bytes: u64, packets: u64, lost: u64) -> Stats {
+        let track = TrackStats {
+            bytes,
+            packets,
+            lost,
+            ..Default::default()
+        };
+        Stats {
+            tracks: [(0, track)].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bandwidth_estimator() {
+        let mut estimator = BandwidthEstimator::new().smoothing(0.5);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert_eq!(estimator.update(&stats(0, 0, 0), at(0)), None);
+
+        // 250 kB and 200 packets in a second
+        let estimate = estimator.update(&stats(250_000, 200, 0), at(1)).unwrap();
+        assert_eq!((estimate.bitrate, estimate.loss), (2_000_000, 0.0));
+
+        // Half the rate and 10% loss, averaged with the first sample
+        let estimate = estimator.update(&stats(375_000, 290, 10), at(2)).unwrap();
+        assert_eq!(estimate.bitrate, 1_500_000);
+        assert!((estimate.loss - 0.05).abs() < 1e-9);
+
+        // Counters that started over keep the estimate
+        assert_eq!(estimator.update(&stats(1000, 10, 0), at(3)), Some(estimate));
+        estimator.reset();
+        assert_eq!(estimator.estimate(), None);
+    }
+}
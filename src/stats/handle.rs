@@ -0,0 +1,126 @@
+use super::track::{TrackCounters, TrackRecorder, TrackStats};
+use crate::sync::ClockSkew;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Metrics of all tracks of a stream at one point in time
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    pub tracks: BTreeMap<usize, TrackStats>,
+    /// Offset of the server's clock from the local one, estimated from the
+    /// Date headers of its responses
+    pub clock_skew: Option<ClockSkew>,
+}
+
+impl Stats {
+    pub fn track(&self, track: usize) -> Option<&TrackStats> {
+        self.tracks.get(&track)
+    }
+
+    pub fn total_lost(&self) -> u64 {
+        self.tracks.values().map(|t| t.lost).sum()
+    }
+}
+
+/// Shared handle to the per-track metrics of a stream, cheap to clone so
+/// monitoring code can poll it while the stream runs.
+#[derive(Debug, Default, Clone)]
+pub struct StatsHandle {
+    tracks: Arc<RwLock<BTreeMap<usize, Arc<TrackCounters>>>>,
+    clock_skew: Arc<Mutex<Option<ClockSkew>>>,
+}
+
+impl StatsHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a track and returns its recorder. Registering a track
+    /// again starts its metrics over. The jitter is only measured if
+    /// `clock_rate` is known, i.e. not 0.
+    pub fn track(&self, track: usize, clock_rate: u32) -> TrackRecorder {
+        let counters = Arc::new(TrackCounters::new());
+        self.tracks.write().unwrap().insert(track, counters.clone());
+        TrackRecorder::new(counters, clock_rate)
+    }
+
+    pub fn remove_track(&self, track: usize) {
+        self.tracks.write().unwrap().remove(&track);
+    }
+
+    /// Records the latest estimate of the server's clock skew, see
+    /// [`Channel::stats`](crate::rtsp::client::Channel::stats)
+    pub fn set_clock_skew(&self, skew: Option<ClockSkew>) {
+        *self.clock_skew.lock().unwrap() = skew;
+    }
+
+    /// Reads the current metrics. It is synchronous on purpose: the
+    /// counters are atomics and the lock is only held to copy them, so
+    /// it never waits on the packet path and can be polled from async
+    /// and blocking code alike.
+    pub fn snapshot(&self) -> Stats {
+        let tracks = self.tracks.read().unwrap();
+        Stats {
+            tracks: tracks.iter().map(|(id, c)| (*id, c.snapshot())).collect(),
+            clock_skew: *self.clock_skew.lock().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcp::ReportBlock;
+    use crate::rtp::Packet;
+    use crate::sync::NtpTimestamp;
+    use crate::testing::rtp_packet;
+    use std::time::{Duration, Instant, SystemTime};
+
+    fn packet(seq: u16, timestamp: u32) -> Packet {
+        rtp_packet(96, 1, seq, timestamp, false, &[0xab, 0xcd])
+    }
+
+    #[test]
+    fn test_stats_packets() {
+        let stats = StatsHandle::new();
+        let mut recorder = stats.track(0, 90_000);
+        let start = Instant::now();
+        // 3000 ticks are 33.3 ms at 90 kHz, the third packet is 10 ms late
+        recorder.record_packet(&packet(1, 0), start);
+        recorder.record_packet(&packet(2, 3000), start + Duration::from_nanos(33_333_334));
+        recorder.record_packet(&packet(4, 9000), start + Duration::from_millis(110));
+        recorder.set_reorder_depth(2);
+
+        let snapshot = stats.clone().snapshot();
+        let track = snapshot.track(0).unwrap();
+        assert_eq!(track.packets, 3);
+        assert_eq!(track.bytes, 42);
+        assert_eq!(track.lost, 1);
+        assert_eq!(snapshot.total_lost(), 1);
+        assert_eq!(track.reorder_depth, 2);
+        assert_eq!(track.last_timestamp, Some(9000));
+        assert!(track.last_packet.is_some());
+        // 900 ticks of transit difference / 16
+        assert_eq!(track.jitter, Duration::from_nanos(56 * 1_000_000_000 / 90_000));
+        assert_eq!(track.rtt, None);
+
+        stats.remove_track(0);
+        assert!(stats.snapshot().tracks.is_empty());
+    }
+
+    #[test]
+    fn test_stats_rtt() {
+        let stats = StatsHandle::new();
+        let mut recorder = stats.track(1, 8000);
+        let sent = SystemTime::now();
+        let lsr = NtpTimestamp::from_system_time(sent).compact();
+        // The peer held the report for 0.5 s, the report arrives 0.6 s after it was sent
+        let mut buf = [0u8; ReportBlock::SIZE];
+        buf[16..20].copy_from_slice(&lsr.to_be_bytes());
+        buf[20..24].copy_from_slice(&32_768u32.to_be_bytes());
+        recorder.record_report_block(&ReportBlock::new(&buf).unwrap(), sent + Duration::from_millis(600));
+        let rtt = stats.snapshot().track(1).unwrap().rtt.unwrap();
+        assert!(rtt.abs_diff(Duration::from_millis(100)) < Duration::from_millis(1));
+    }
+}
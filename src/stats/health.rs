@@ -0,0 +1,381 @@
+use super::Stats;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Frame intervals the frame rate stability is measured over
+const FRAME_INTERVALS: usize = 64;
+
+/// Status of a stream as shown to users, e.g. of a camera in an NVR
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HealthState {
+    Healthy,
+    Degraded,
+    Down,
+}
+
+/// Maps a metric to a factor score: 100 at or below `good`, 0 at or above
+/// `bad` and linear in between
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Threshold {
+    pub good: f64,
+    pub bad: f64,
+    /// Weight of the factor in the score, 0 ignores it
+    pub weight: f64,
+}
+
+impl Threshold {
+    pub fn new(good: f64, bad: f64, weight: f64) -> Self {
+        Self { good, bad, weight }
+    }
+
+    fn score(&self, value: f64) -> f64 {
+        if value <= self.good {
+            100.0
+        } else if value >= self.bad {
+            0.0
+        } else {
+            100.0 * (self.bad - value) / (self.bad - self.good)
+        }
+    }
+}
+
+/// Thresholds of the health score of a stream
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HealthConfig {
+    /// Fraction of the packets lost since the previous evaluation
+    pub loss: Threshold,
+    /// Highest interarrival jitter of the tracks, in milliseconds
+    pub jitter: Threshold,
+    /// Highest round-trip time of the tracks, in milliseconds
+    pub rtt: Threshold,
+    /// Reconnects within `reconnect_window`
+    pub reconnects: Threshold,
+    /// Coefficient of variation of the frame intervals
+    pub frame_rate: Threshold,
+    pub reconnect_window: Duration,
+    /// The stream is down when no packet arrived for this long
+    pub stall_timeout: Duration,
+    /// Scores below are degraded
+    pub degraded_below: u8,
+    /// Scores below are down
+    pub down_below: u8,
+    /// Points a score has to exceed a threshold by to recover to the
+    /// better state, so a score around a threshold doesn't flap
+    pub hysteresis: u8,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            loss: Threshold::new(0.005, 0.05, 3.0),
+            jitter: Threshold::new(30.0, 300.0, 1.0),
+            rtt: Threshold::new(150.0, 1500.0, 1.0),
+            reconnects: Threshold::new(0.0, 3.0, 2.0),
+            frame_rate: Threshold::new(0.15, 0.6, 2.0),
+            reconnect_window: Duration::from_secs(300),
+            stall_timeout: Duration::from_secs(5),
+            degraded_below: 70,
+            down_below: 30,
+            hysteresis: 10,
+        }
+    }
+}
+
+/// Scores from 0 to 100 of the metrics the health score combines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HealthFactors {
+    pub loss: u8,
+    pub jitter: u8,
+    pub rtt: u8,
+    pub reconnects: u8,
+    pub frame_rate: u8,
+}
+
+impl Default for HealthFactors {
+    fn default() -> Self {
+        Self {
+            loss: 100,
+            jitter: 100,
+            rtt: 100,
+            reconnects: 100,
+            frame_rate: 100,
+        }
+    }
+}
+
+/// Result of the last evaluation of a [`HealthMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Health {
+    pub state: HealthState,
+    /// Weighted score of the factors from 0 to 100
+    pub score: u8,
+    pub factors: HealthFactors,
+    /// No packet arrived within the stall timeout
+    pub stalled: bool,
+}
+
+/// Transition of the state of a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HealthEvent {
+    pub from: HealthState,
+    pub to: HealthState,
+    pub score: u8,
+}
+
+/// Scores the health of one session from its loss, jitter, RTT, reconnects
+/// and frame rate stability, and reports the transitions between
+/// [`HealthState`]s. The monitor is fed by its owner: [`Stats`] snapshots
+/// are evaluated periodically, reconnects and frames are recorded as they
+/// happen.
+///
+/// A session is down until its first packets arrive.
+///
+/// ```ignore
+/// let mut monitor = HealthMonitor::new(HealthConfig::default());
+/// loop {
+///     interval.tick().await;
+///     if let Some(event) = monitor.evaluate(&stats.snapshot().await, Instant::now()) {
+///         ui.set_status(camera, event.to);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct HealthMonitor {
+    config: HealthConfig,
+    health: Health,
+    // Packets and losses of the tracks at the previous evaluation
+    totals: Option<(u64, u64)>,
+    last_progress: Option<Instant>,
+    reconnects: VecDeque<Instant>,
+    last_frame: Option<Instant>,
+    frame_intervals: VecDeque<Duration>,
+}
+
+impl HealthMonitor {
+    pub fn new(config: HealthConfig) -> Self {
+        Self {
+            config,
+            health: Health {
+                state: HealthState::Down,
+                score: 0,
+                factors: HealthFactors::default(),
+                stalled: true,
+            },
+            totals: None,
+            last_progress: None,
+            reconnects: VecDeque::new(),
+            last_frame: None,
+            frame_intervals: VecDeque::with_capacity(FRAME_INTERVALS),
+        }
+    }
+
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    pub fn health(&self) -> Health {
+        self.health
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.health.state
+    }
+
+    pub fn record_reconnect(&mut self, at: Instant) {
+        self.reconnects.push_back(at);
+        // The frame intervals span the reconnect otherwise
+        self.last_frame = None;
+    }
+
+    /// Records the arrival of a frame of the track whose frame rate is
+    /// watched, usually the video track
+    pub fn record_frame(&mut self, at: Instant) {
+        if let Some(last) = self.last_frame.replace(at) {
+            if self.frame_intervals.len() == FRAME_INTERVALS {
+                self.frame_intervals.pop_front();
+            }
+            self.frame_intervals.push_back(at.saturating_duration_since(last));
+        }
+    }
+
+    /// Scores `stats` against the previous evaluation and returns the
+    /// transition of the state, if any
+    pub fn evaluate(&mut self, stats: &Stats, now: Instant) -> Option<HealthEvent> {
+        let packets: u64 = stats.tracks.values().map(|t| t.packets).sum();
+        let lost = stats.total_lost();
+        let (previous_packets, previous_lost) = self.totals.replace((packets, lost)).unwrap_or((0, 0));
+        // The counters start over when the tracks are registered again
+        let (received, lost) = match packets < previous_packets || lost < previous_lost {
+            true => (packets, lost),
+            false => (packets - previous_packets, lost - previous_lost),
+        };
+        if received > 0 {
+            self.last_progress = Some(now);
+        }
+        let stalled = self
+            .last_progress
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.config.stall_timeout);
+
+        let window = self.config.reconnect_window;
+        while self
+            .reconnects
+            .front()
+            .is_some_and(|at| now.saturating_duration_since(*at) > window)
+        {
+            self.reconnects.pop_front();
+        }
+
+        let loss = match received + lost {
+            0 => 0.0,
+            total => lost as f64 / total as f64,
+        };
+        let jitter = stats.tracks.values().map(|t| t.jitter).max().unwrap_or_default();
+        let rtt = stats.tracks.values().filter_map(|t| t.rtt).max().unwrap_or_default();
+        let config = &self.config;
+        let factors = [
+            (config.loss, loss),
+            (config.jitter, jitter.as_secs_f64() * 1000.0),
+            (config.rtt, rtt.as_secs_f64() * 1000.0),
+            (config.reconnects, self.reconnects.len() as f64),
+            (config.frame_rate, self.frame_variation()),
+        ]
+        .map(|(threshold, value)| (threshold.weight, threshold.score(value)));
+        let weights: f64 = factors.iter().map(|(weight, _)| weight).sum();
+        let score = match weights > 0.0 {
+            true => factors.iter().map(|(weight, score)| weight * score).sum::<f64>() / weights,
+            false => 100.0,
+        };
+        let [loss, jitter, rtt, reconnects, frame_rate] = factors.map(|(_, score)| score.round() as u8);
+
+        let score = score.round() as u8;
+        let from = self.health.state;
+        let to = self.next_state(score, stalled);
+        self.health = Health {
+            state: to,
+            score,
+            factors: HealthFactors {
+                loss,
+                jitter,
+                rtt,
+                reconnects,
+                frame_rate,
+            },
+            stalled,
+        };
+        (from != to).then_some(HealthEvent { from, to, score })
+    }
+
+    fn next_state(&self, score: u8, stalled: bool) -> HealthState {
+        let config = &self.config;
+        if stalled || score < config.down_below {
+            return HealthState::Down;
+        }
+        let recovers = |threshold: u8| score >= threshold.saturating_add(config.hysteresis);
+        match self.health.state {
+            HealthState::Healthy if score < config.degraded_below => HealthState::Degraded,
+            HealthState::Healthy => HealthState::Healthy,
+            _ if recovers(config.degraded_below) => HealthState::Healthy,
+            HealthState::Down if !recovers(config.down_below) => HealthState::Down,
+            _ => HealthState::Degraded,
+        }
+    }
+
+    /// Coefficient of variation of the frame intervals, 0 without enough
+    /// frames to tell
+    fn frame_variation(&self) -> f64 {
+        if self.frame_intervals.len() < 2 {
+            return 0.0;
+        }
+        let intervals = self.frame_intervals.iter().map(Duration::as_secs_f64);
+        let n = self.frame_intervals.len() as f64;
+        let mean = intervals.clone().sum::<f64>() / n;
+        if mean == 0.0 {
+            return 0.0;
+        }
+        let variance = intervals.map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+        variance.sqrt() / mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::TrackStats;
+
+    fn stats(packets: u64, lost: u64, jitter_ms: u64) -> Stats {
+        let track = TrackStats {
+            packets,
+            lost,
+            jitter: Duration::from_millis(jitter_ms),
+            ..Default::default()
+        };
+        Stats {
+            tracks: [(0, track)].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_health_transitions() {
+        let mut monitor = HealthMonitor::new(HealthConfig::default());
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        assert_eq!(monitor.state(), HealthState::Down);
+        assert_eq!(monitor.evaluate(&stats(0, 0, 0), at(0)), None);
+
+        let event = monitor.evaluate(&stats(100, 0, 5), at(1)).unwrap();
+        assert_eq!(
+            (event.from, event.to, event.score),
+            (HealthState::Down, HealthState::Healthy, 100)
+        );
+
+        // 10% loss costs the whole loss factor
+        let event = monitor.evaluate(&stats(190, 10, 5), at(2)).unwrap();
+        assert_eq!(event.to, HealthState::Degraded);
+        assert_eq!(monitor.health().factors.loss, 0);
+        assert_eq!(monitor.health().score, 67);
+
+        // Above the threshold but within the hysteresis
+        assert_eq!(monitor.evaluate(&stats(383, 17, 5), at(3)), None);
+        assert_eq!(monitor.health().score, 78);
+        let event = monitor.evaluate(&stats(483, 17, 5), at(4)).unwrap();
+        assert_eq!(event.to, HealthState::Healthy);
+
+        // No packets for the stall timeout
+        assert_eq!(monitor.evaluate(&stats(483, 17, 5), at(8)), None);
+        let event = monitor.evaluate(&stats(483, 17, 5), at(9)).unwrap();
+        assert_eq!(event.to, HealthState::Down);
+        assert!(monitor.health().stalled);
+
+        // Counters that start over after a reconnect
+        monitor.record_reconnect(at(10));
+        let event = monitor.evaluate(&stats(50, 0, 5), at(11)).unwrap();
+        assert_eq!(event.to, HealthState::Healthy);
+        assert_eq!(monitor.health().factors.reconnects, 67);
+    }
+
+    #[test]
+    fn test_health_frame_rate() {
+        let mut monitor = HealthMonitor::new(HealthConfig::default());
+        let start = Instant::now();
+        for i in 0..20 {
+            monitor.record_frame(start + Duration::from_millis(i * 40));
+        }
+        monitor.evaluate(&stats(100, 0, 0), start + Duration::from_secs(1));
+        assert_eq!(monitor.health().factors.frame_rate, 100);
+
+        // Frames in bursts
+        for i in 0..40 {
+            let offset = if i % 2 == 0 { 0 } else { 70 };
+            monitor.record_frame(start + Duration::from_millis(1000 + i / 2 * 80 + offset));
+        }
+        monitor.evaluate(&stats(200, 0, 0), start + Duration::from_secs(2));
+        assert!(monitor.health().factors.frame_rate < 50);
+    }
+}
@@ -0,0 +1,19 @@
+mod bandwidth;
+mod handle;
+mod health;
+mod track;
+
+pub use handle::Stats;
+pub use handle::StatsHandle;
+pub use track::TrackRecorder;
+pub use track::TrackStats;
+pub use health::Health;
+pub use health::HealthConfig;
+pub use health::HealthEvent;
+pub use health::HealthFactors;
+pub use health::HealthMonitor;
+pub use health::HealthState;
+pub use health::Threshold;
+pub use bandwidth::BandwidthEstimate;
+pub use bandwidth::BandwidthEstimator;
+pub use bandwidth::DEFAULT_SMOOTHING;
@@ -0,0 +1,132 @@
+use crate::rtcp::{ReceptionStats, ReportBlock};
+use crate::rtp::Packet;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Snapshot of the metrics of one track
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackStats {
+    pub packets: u64,
+    pub bytes: u64,
+    /// Packets missing in the sequence numbers received so far
+    pub lost: u64,
+    /// Interarrival jitter as defined in RFC 3550
+    pub jitter: Duration,
+    /// Round-trip time derived from the last reception report about us
+    pub rtt: Option<Duration>,
+    /// Packets held back by the reorder queue
+    pub reorder_depth: usize,
+    /// Arrival of the last packet on the wall clock
+    pub last_packet: Option<SystemTime>,
+    /// RTP timestamp of the last packet
+    pub last_timestamp: Option<u32>,
+}
+
+// Sentinel of the optional values
+const NONE: u64 = u64::MAX;
+
+/// Shared between the recorder of a track and the stats handle
+#[derive(Debug)]
+pub(super) struct TrackCounters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    lost: AtomicU64,
+    jitter_ns: AtomicU64,
+    rtt_us: AtomicU64,
+    reorder_depth: AtomicUsize,
+    last_packet_us: AtomicU64,
+    last_timestamp: AtomicU64,
+}
+
+impl TrackCounters {
+    pub(super) fn new() -> Self {
+        Self {
+            packets: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            lost: AtomicU64::new(0),
+            jitter_ns: AtomicU64::new(0),
+            rtt_us: AtomicU64::new(NONE),
+            reorder_depth: AtomicUsize::new(0),
+            last_packet_us: AtomicU64::new(NONE),
+            last_timestamp: AtomicU64::new(NONE),
+        }
+    }
+
+    pub(super) fn snapshot(&self) -> TrackStats {
+        let optional = |value: &AtomicU64| Some(value.load(Ordering::Relaxed)).filter(|v| *v != NONE);
+        TrackStats {
+            packets: self.packets.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            lost: self.lost.load(Ordering::Relaxed),
+            jitter: Duration::from_nanos(self.jitter_ns.load(Ordering::Relaxed)),
+            rtt: optional(&self.rtt_us).map(Duration::from_micros),
+            reorder_depth: self.reorder_depth.load(Ordering::Relaxed),
+            last_packet: optional(&self.last_packet_us).map(|us| UNIX_EPOCH + Duration::from_micros(us)),
+            last_timestamp: optional(&self.last_timestamp).map(|ts| ts as u32),
+        }
+    }
+}
+
+/// Records the metrics of one track, created by
+/// [`StatsHandle::track`](super::StatsHandle::track). Loss and jitter are
+/// the ones of the [`ReceptionStats`] the receiver reports are made of.
+///
+/// Recording only touches atomics, so it can run on the packet path of
+/// many sessions without contention with readers of the stats.
+pub struct TrackRecorder {
+    counters: Arc<TrackCounters>,
+    reception: ReceptionStats,
+}
+
+impl TrackRecorder {
+    pub(super) fn new(counters: Arc<TrackCounters>, clock_rate: u32) -> Self {
+        Self {
+            counters,
+            reception: ReceptionStats::new(clock_rate),
+        }
+    }
+
+    pub fn record_packet(&mut self, packet: &Packet, arrival: Instant) {
+        let counters = &self.counters;
+        counters.packets.fetch_add(1, Ordering::Relaxed);
+        counters.bytes.fetch_add(packet.len() as u64, Ordering::Relaxed);
+        counters
+            .last_packet_us
+            .store(wall_clock_us(SystemTime::now()), Ordering::Relaxed);
+        counters
+            .last_timestamp
+            .store(packet.timestamp() as u64, Ordering::Relaxed);
+
+        self.reception.record_packet(packet, arrival);
+        counters.lost.store(self.reception.lost(), Ordering::Relaxed);
+        counters
+            .jitter_ns
+            .store(self.reception.jitter().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Measures the round-trip time from a report block of a sender or
+    /// receiver report about one of our sources
+    pub fn record_report_block(&mut self, block: &ReportBlock, arrival: SystemTime) {
+        if let Some(rtt) = block.round_trip_time(arrival) {
+            self.record_rtt(rtt);
+        }
+    }
+
+    /// Records a round-trip time measured elsewhere, e.g. by the channel
+    /// that picked the report block about its own source
+    pub fn record_rtt(&mut self, rtt: Duration) {
+        self.counters.rtt_us.store(rtt.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Packets of the track held back for reordering, the channel sets it
+    /// from the queues of [`Channel::demux`](crate::rtsp::client::Channel::demux)
+    pub fn set_reorder_depth(&mut self, depth: usize) {
+        self.counters.reorder_depth.store(depth, Ordering::Relaxed);
+    }
+}
+
+fn wall_clock_us(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+}
@@ -0,0 +1,333 @@
+use super::Synchronizer;
+use crate::rtcp::SenderReport;
+use crate::rtp::FrameStream;
+use crate::task;
+use crate::types::Frame;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignedFrame {
+    pub source: usize,
+    pub ssrc: u32,
+    /// Capture time on the common wall clock timeline
+    pub time: SystemTime,
+    pub frame: Frame,
+}
+
+/// Frames of all sources captured within the same bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameGroup {
+    /// Start of the bucket, frames are captured in `start..start + bucket`
+    pub start: SystemTime,
+    /// Sorted by capture time
+    pub frames: Vec<AlignedFrame>,
+}
+
+impl FrameGroup {
+    /// The frames of one source
+    pub fn source(&self, source: usize) -> impl Iterator<Item = &AlignedFrame> {
+        self.frames.iter().filter(move |f| f.source == source)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AggregatorStats {
+    pub grouped: u64,
+    /// Frames of a bucket that was already released
+    pub late: u64,
+    /// Frames dropped because their source had no clock mapping yet
+    pub unsynced: u64,
+}
+
+/// Aligns the frames of several sessions, e.g. cameras of a multi-view
+/// setup, on a common wall clock timeline and groups them in buckets.
+///
+/// Every source has its own [`Synchronizer`] which is fed with its sender
+/// reports, SSRCs of different sessions may collide. [`Aggregator::run`]
+/// reads the frames of the sessions itself. A bucket is released
+/// once every source has delivered a frame past it, or once the newest
+/// frame of any source is `max_delay` past it, so a stalled source doesn't
+/// hold back the others.
+#[derive(Debug)]
+pub struct Aggregator {
+    bucket: Duration,
+    max_delay: Duration,
+    sources: HashMap<usize, Arc<Mutex<Synchronizer>>>,
+    // Capture time of the newest frame of every source
+    newest: HashMap<usize, SystemTime>,
+    buckets: BTreeMap<u128, Vec<AlignedFrame>>,
+    // Index of the next bucket to release, older frames are late
+    next: Option<u128>,
+    stats: AggregatorStats,
+}
+
+impl Aggregator {
+    pub fn new(bucket: Duration) -> Self {
+        Self {
+            bucket: bucket.max(Duration::from_micros(1)),
+            max_delay: Duration::from_millis(500),
+            sources: HashMap::new(),
+            newest: HashMap::new(),
+            buckets: BTreeMap::new(),
+            next: None,
+            stats: AggregatorStats::default(),
+        }
+    }
+
+    /// How long a bucket waits for sources that are behind
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Clock mapping of a source, created on first use. Passed to the
+    /// [`Channel`](crate::rtsp::client::Channel::synchronizer) of the
+    /// session it receives the sender reports of the source.
+    pub fn synchronizer(&mut self, source: usize) -> Arc<Mutex<Synchronizer>> {
+        self.sources.entry(source).or_default().clone()
+    }
+
+    pub fn handle_sender_report(&mut self, source: usize, report: &SenderReport, clock_rate: u32) {
+        let synchronizer = self.synchronizer(source);
+        synchronizer.lock().unwrap().handle_sender_report(report, clock_rate);
+    }
+
+    /// Stops waiting for a source, its queued frames are still released
+    pub fn remove_source(&mut self, source: usize) {
+        self.sources.remove(&source);
+        self.newest.remove(&source);
+    }
+
+    /// Queues a frame, returns false if it was dropped as late or unsynced
+    pub fn push(&mut self, source: usize, ssrc: u32, frame: Frame) -> bool {
+        let Some(time) = self
+            .sources
+            .get(&source)
+            .and_then(|s| s.lock().unwrap().capture_time(ssrc, frame.timestamp))
+        else {
+            self.stats.unsynced += 1;
+            return false;
+        };
+        let index = self.index(time);
+        if self.next.is_some_and(|next| index < next) {
+            self.stats.late += 1;
+            return false;
+        }
+        let newest = self.newest.entry(source).or_insert(time);
+        *newest = (*newest).max(time);
+        self.buckets.entry(index).or_default().push(AlignedFrame {
+            source,
+            ssrc,
+            time,
+            frame,
+        });
+        true
+    }
+
+    /// Next complete group, in timeline order
+    pub fn pop(&mut self) -> Option<FrameGroup> {
+        let (&index, _) = self.buckets.first_key_value()?;
+        let end = self.start(index + 1);
+        // Sources that haven't delivered a frame yet are waited for as well
+        let past = |source| self.newest.get(source).is_some_and(|&t| t >= end);
+        let complete = !self.sources.is_empty() && self.sources.keys().all(past);
+        let expired = self.newest.values().max().is_some_and(|&t| t >= end + self.max_delay);
+        if !complete && !expired {
+            return None;
+        }
+        self.release(index)
+    }
+
+    /// Releases the next group whether it is complete or not, e.g. at the end of the streams
+    pub fn flush(&mut self) -> Option<FrameGroup> {
+        let (&index, _) = self.buckets.first_key_value()?;
+        self.release(index)
+    }
+
+    pub fn stats(&self) -> AggregatorStats {
+        self.stats
+    }
+
+    /// Aligns the frames of `sessions`, the frame stream of every source
+    /// whose channel shares its [`Aggregator::synchronizer`], and sends
+    /// the groups to `tx`. A source is removed once its stream ends and
+    /// the remaining groups are flushed after the last one, or the
+    /// aggregation stops once `tx` is closed.
+    pub async fn run(mut self, sessions: Vec<(usize, FrameStream)>, tx: mpsc::Sender<FrameGroup>) -> AggregatorStats {
+        let (frame_tx, mut frame_rx) = mpsc::channel(8 * sessions.len().max(1));
+        let mut running = sessions.len();
+        for (source, mut stream) in sessions {
+            let frame_tx = frame_tx.clone();
+            task::spawn(task::AGGREGATOR, async move {
+                while let Some(frame) = stream.next().await {
+                    if frame_tx.send((source, Some(frame))).await.is_err() {
+                        return;
+                    }
+                }
+                let _ = frame_tx.send((source, None)).await;
+            });
+        }
+        drop(frame_tx);
+        while running > 0 {
+            let Some((source, frame)) = frame_rx.recv().await else {
+                break;
+            };
+            match frame {
+                Some(frame) => {
+                    let ssrc = frame.frame.metadata.origin.map_or(0, |origin| origin.ssrc);
+                    self.push(source, ssrc, frame.frame);
+                }
+                None => {
+                    self.remove_source(source);
+                    running -= 1;
+                }
+            }
+            while let Some(group) = self.pop() {
+                if tx.send(group).await.is_err() {
+                    return self.stats;
+                }
+            }
+        }
+        while let Some(group) = self.flush() {
+            if tx.send(group).await.is_err() {
+                break;
+            }
+        }
+        self.stats
+    }
+
+    fn release(&mut self, index: u128) -> Option<FrameGroup> {
+        let mut frames = self.buckets.remove(&index)?;
+        frames.sort_by_key(|f| f.time);
+        self.next = Some(index + 1);
+        self.stats.grouped += frames.len() as u64;
+        Some(FrameGroup {
+            start: self.start(index),
+            frames,
+        })
+    }
+
+    fn index(&self, time: SystemTime) -> u128 {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() / self.bucket.as_nanos()
+    }
+
+    fn start(&self, index: u128) -> SystemTime {
+        let nanos = index * self.bucket.as_nanos();
+        UNIX_EPOCH + Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtp::Packet;
+    use crate::sdp::Media;
+    use crate::sync::NtpTimestamp;
+    use crate::types::{FrameMetadata, FrameType, MediaType};
+
+    const BASE: u64 = 1_700_000_000;
+
+    fn frame(timestamp: u32) -> Frame {
+        Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp,
+            data: vec![0; 4],
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    /// Two cameras whose RTP clocks start at different values but are
+    /// captured at the same wall clock time
+    fn aggregator() -> Aggregator {
+        let mut aggregator = Aggregator::new(Duration::from_millis(40)).max_delay(Duration::from_millis(100));
+        let base = NtpTimestamp::from_system_time(UNIX_EPOCH + Duration::from_secs(BASE));
+        aggregator.synchronizer(0).lock().unwrap().update(1, base, 0, 90_000);
+        aggregator
+            .synchronizer(1)
+            .lock()
+            .unwrap()
+            .update(1, base, 1_000_000, 90_000);
+        aggregator
+    }
+
+    #[test]
+    fn test_aggregate_aligned() {
+        let mut aggregator = aggregator();
+        assert!(aggregator.push(0, 1, frame(0)));
+        assert!(aggregator.pop().is_none());
+        assert!(aggregator.push(1, 1, frame(1_000_000 + 900)));
+        assert!(aggregator.pop().is_none());
+        // 40 ms later both sources are past the first bucket
+        assert!(aggregator.push(0, 1, frame(3600)));
+        assert!(aggregator.push(1, 1, frame(1_000_000 + 3600)));
+        let group = aggregator.pop().unwrap();
+        assert_eq!(group.start, UNIX_EPOCH + Duration::from_secs(BASE));
+        assert_eq!(group.frames.len(), 2);
+        assert_eq!(group.source(1).next().unwrap().frame.timestamp, 1_000_900);
+        assert!(aggregator.pop().is_none());
+        assert_eq!(aggregator.flush().unwrap().frames.len(), 2);
+        assert_eq!(aggregator.stats().grouped, 4);
+    }
+
+    #[test]
+    fn test_aggregate_stalled_source() {
+        let mut aggregator = aggregator();
+        aggregator.push(1, 1, frame(1_000_000));
+        aggregator.push(0, 1, frame(0));
+        // Source 1 stalls, the bucket is released once source 0 is max_delay past it
+        aggregator.push(0, 1, frame(9000));
+        assert!(aggregator.pop().is_none());
+        aggregator.push(0, 1, frame(12_600));
+        assert_eq!(aggregator.pop().unwrap().frames.len(), 2);
+        // A frame of the released bucket is late
+        assert!(!aggregator.push(1, 1, frame(1_000_090)));
+        assert_eq!(aggregator.stats().late, 1);
+    }
+
+    #[test]
+    fn test_aggregate_unsynced() {
+        let mut aggregator = aggregator();
+        assert!(!aggregator.push(2, 1, frame(0)));
+        assert!(!aggregator.push(0, 2, frame(0)));
+        assert_eq!(aggregator.stats().unsynced, 2);
+        aggregator.push(0, 1, frame(0));
+        aggregator.push(1, 1, frame(1_000_000));
+        aggregator.remove_source(1);
+        aggregator.push(0, 1, frame(3600));
+        assert_eq!(aggregator.pop().unwrap().frames.len(), 2);
+    }
+
+    /// The H.264 frames of a session with the SSRC 1, one IDR picture per packet
+    fn session(timestamps: &[u32]) -> FrameStream {
+        let (packet_tx, packet_rx) = mpsc::channel(8);
+        for (seq, timestamp) in timestamps.iter().enumerate() {
+            let mut buf = vec![0x80, 0xe0, 0, seq as u8];
+            buf.extend_from_slice(&timestamp.to_be_bytes());
+            buf.extend_from_slice(&[0, 0, 0, 1, 0x65, 0]);
+            packet_tx.try_send(Packet::new(buf).unwrap()).unwrap();
+        }
+        let mut media: Media = "video 0 RTP/AVP 96".parse().unwrap();
+        media
+            .attributes
+            .push(("rtpmap".to_string(), Some("96 H264/90000".to_string())));
+        FrameStream::new(packet_rx, &media).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_aggregate_sessions() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let sessions = vec![(0, session(&[0, 3600])), (1, session(&[1_000_000, 1_003_600]))];
+        let stats = aggregator().run(sessions, tx).await;
+        assert_eq!(stats.grouped, 4);
+        let mut groups = Vec::new();
+        while let Some(group) = rx.recv().await {
+            groups.push((group.start, group.frames.len()));
+        }
+        let start = UNIX_EPOCH + Duration::from_secs(BASE);
+        assert_eq!(groups, [(start, 2), (start + Duration::from_millis(40), 2)]);
+    }
+}
@@ -0,0 +1,14 @@
+mod aggregator;
+mod ntp;
+mod skew;
+mod synchronizer;
+
+pub use aggregator::Aggregator;
+pub use aggregator::AggregatorStats;
+pub use aggregator::AlignedFrame;
+pub use aggregator::FrameGroup;
+pub use ntp::NtpTimestamp;
+pub use synchronizer::SourceClock;
+pub use synchronizer::Synchronizer;
+pub use skew::ClockSkew;
+pub use skew::SkewEstimator;
@@ -0,0 +1,58 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970)
+const UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// 64 bit NTP timestamp as carried in RTCP sender reports,
+/// 32 bits of seconds followed by 32 bits of fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NtpTimestamp(pub u64);
+
+impl NtpTimestamp {
+    pub fn seconds(&self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    pub fn fraction(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Middle 32 bits, as used in the LSR field of reception reports
+    pub fn compact(&self) -> u32 {
+        (self.0 >> 16) as u32
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        let nanos = (self.fraction() as u64 * 1_000_000_000 + (1 << 31)) >> 32;
+        let since_1900 = Duration::new(self.seconds() as u64, nanos as u32);
+        let offset = Duration::from_secs(UNIX_OFFSET);
+        if since_1900 >= offset {
+            UNIX_EPOCH + (since_1900 - offset)
+        } else {
+            UNIX_EPOCH - (offset - since_1900)
+        }
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_1900 = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d + Duration::from_secs(UNIX_OFFSET),
+            Err(e) => Duration::from_secs(UNIX_OFFSET).saturating_sub(e.duration()),
+        };
+        let fraction =
+            ((((since_1900.subsec_nanos() as u64) << 32) + 500_000_000) / 1_000_000_000).min(u32::MAX as u64);
+        NtpTimestamp((since_1900.as_secs() << 32) | fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntp_timestamp() {
+        let ntp = NtpTimestamp((UNIX_OFFSET + 10) << 32 | 0x8000_0000);
+        assert_eq!(ntp.to_system_time(), UNIX_EPOCH + Duration::from_millis(10_500));
+        assert_eq!(NtpTimestamp::from_system_time(ntp.to_system_time()), ntp);
+        assert_eq!(ntp.compact(), ((UNIX_OFFSET as u32 + 10) << 16) | 0x8000);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Offset of a server's wall clock from the local one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClockSkew {
+    /// Server clock minus local clock in milliseconds, positive if the
+    /// server is ahead
+    pub offset_ms: i64,
+    /// Maximum error of the offset
+    pub uncertainty: Duration,
+}
+
+impl ClockSkew {
+    /// A local time on the server's clock, e.g. for absolute `Range: clock=`
+    /// times of recordings on an NVR
+    pub fn to_server_time(&self, local: SystemTime) -> SystemTime {
+        shift(local, self.offset_ms)
+    }
+
+    /// A time of the server's clock on the local one
+    pub fn to_local_time(&self, server: SystemTime) -> SystemTime {
+        shift(server, -self.offset_ms)
+    }
+}
+
+fn shift(time: SystemTime, ms: i64) -> SystemTime {
+    let delta = Duration::from_millis(ms.unsigned_abs());
+    match ms >= 0 {
+        true => time + delta,
+        false => time - delta,
+    }
+}
+
+fn unix_ms(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// Estimates the [`ClockSkew`] of a server from the `Date` headers of its
+/// responses. The header only has a resolution of a second and the server
+/// wrote it at some time between sending the request and receiving the
+/// response, so every response bounds the offset to an interval. The
+/// estimate is the intersection of these intervals, it starts over if a
+/// response doesn't fit, e.g. after the server's clock was set.
+#[derive(Debug, Default, Clone)]
+pub struct SkewEstimator {
+    // Bounds of the offset in milliseconds
+    bounds: Option<(i64, i64)>,
+}
+
+impl SkewEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the `date` of a response to a request sent at `sent` and
+    /// answered at `received`, both local times
+    pub fn record(&mut self, date: SystemTime, sent: SystemTime, received: SystemTime) {
+        let date = unix_ms(date);
+        let low = date - unix_ms(received.max(sent));
+        let high = date + 1000 - unix_ms(sent);
+        self.bounds = match self.bounds {
+            Some((l, h)) if low.max(l) <= high.min(h) => Some((low.max(l), high.min(h))),
+            _ => Some((low, high)),
+        };
+    }
+
+    pub fn estimate(&self) -> Option<ClockSkew> {
+        let (low, high) = self.bounds?;
+        Some(ClockSkew {
+            offset_ms: low + (high - low) / 2,
+            uncertainty: Duration::from_millis(((high - low) / 2) as u64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skew_estimator() {
+        let mut estimator = SkewEstimator::new();
+        assert_eq!(estimator.estimate(), None);
+        let t0 = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        // The server is 10.3 s ahead, its dates are truncated to seconds
+        let date = |local: SystemTime| {
+            let server = local + Duration::from_millis(10_300);
+            UNIX_EPOCH + Duration::from_secs(server.duration_since(UNIX_EPOCH).unwrap().as_secs())
+        };
+        let rtt = Duration::from_millis(100);
+        estimator.record(date(t0), t0 - rtt, t0);
+        let first = estimator.estimate().unwrap();
+        assert!(first.uncertainty >= Duration::from_millis(500));
+
+        // Responses written at other fractions of a second narrow it down
+        for ms in [250, 690, 750] {
+            let sent = t0 + Duration::from_millis(ms);
+            estimator.record(date(sent), sent, sent);
+        }
+        let skew = estimator.estimate().unwrap();
+        assert!(skew.uncertainty < Duration::from_millis(100));
+        assert!((skew.offset_ms - 10_300).abs() < 100);
+        assert_eq!(skew.to_local_time(skew.to_server_time(t0)), t0);
+
+        // The server's clock was set back a minute
+        let sent = t0 + Duration::from_secs(5);
+        estimator.record(date(sent) - Duration::from_secs(60), sent, sent);
+        assert!((estimator.estimate().unwrap().offset_ms + 49_700).abs() < 1000);
+    }
+}
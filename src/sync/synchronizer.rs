@@ -0,0 +1,234 @@
+use super::NtpTimestamp;
+use crate::rtcp::SenderReport;
+use crate::sdp::{MediaClock, RefClock};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Relates the RTP clock of one source to wall clock time, using the
+/// NTP/RTP timestamp pair of its latest sender report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceClock {
+    pub clock_rate: u32,
+    /// Rate of the RTP timestamps relative to `clock_rate` as numerator and
+    /// denominator, e.g. 1000/1001 for NTSC video (RFC 7273 `rate=`)
+    pub scale: (u32, u32),
+    pub ntp: NtpTimestamp,
+    pub rtp_timestamp: u32,
+}
+
+impl SourceClock {
+    /// Estimated capture time of a RTP timestamp. Timestamps are taken as
+    /// the nearest one to the reference, which handles wraparound as long
+    /// as they are less than 2^31 ticks apart.
+    pub fn capture_time(&self, rtp_timestamp: u32) -> SystemTime {
+        let ticks = rtp_timestamp.wrapping_sub(self.rtp_timestamp) as i32 as i64;
+        let offset = Duration::from_nanos(self.ticks_to_nanos(ticks.unsigned_abs() as u128) as u64);
+        let reference = self.ntp.to_system_time();
+        if ticks >= 0 {
+            reference + offset
+        } else {
+            reference - offset
+        }
+    }
+
+    /// Clock of a source whose RTP timestamps are derived from a reference
+    /// clock (RFC 7273 `mediaclk:direct`), no sender report is needed. `now`
+    /// must be within 2^31 ticks of the timestamps that are mapped.
+    pub fn direct(clock_rate: u32, scale: (u32, u32), offset: u32, epoch: SystemTime, now: SystemTime) -> Self {
+        let mut clock = SourceClock {
+            clock_rate,
+            scale,
+            ntp: NtpTimestamp::from_system_time(epoch),
+            rtp_timestamp: offset,
+        };
+        // The last whole tick before `now`, its time is exact up to a nanosecond
+        let seconds = now.duration_since(epoch).unwrap_or_default().as_secs() as u128;
+        let ticks = seconds * clock_rate as u128 * scale.0 as u128 / scale.1.max(1) as u128;
+        let elapsed = Duration::from_nanos(clock.ticks_to_nanos(ticks) as u64);
+        clock.ntp = NtpTimestamp::from_system_time(epoch + elapsed);
+        clock.rtp_timestamp = (ticks + offset as u128) as u32;
+        clock
+    }
+
+    fn ticks_to_nanos(&self, ticks: u128) -> u128 {
+        let rate = self.clock_rate as u128 * self.scale.0 as u128;
+        ticks * 1_000_000_000 * self.scale.1 as u128 / rate.max(1)
+    }
+}
+
+/// Keeps the clock mapping of every source, so timestamps of different
+/// tracks can be put on the same timeline for lip-sync.
+#[derive(Debug, Default)]
+pub struct Synchronizer {
+    sources: HashMap<u32, SourceClock>,
+}
+
+impl Synchronizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the mapping of the report's source, `clock_rate` is the
+    /// RTP clock rate of the track the report belongs to.
+    pub fn handle_sender_report(&mut self, report: &SenderReport, clock_rate: u32) {
+        self.update(
+            report.ssrc(),
+            NtpTimestamp(report.ntp_timestamp()),
+            report.rtp_ts(),
+            clock_rate,
+        );
+    }
+
+    pub fn update(&mut self, ssrc: u32, ntp: NtpTimestamp, rtp_timestamp: u32, clock_rate: u32) {
+        if clock_rate == 0 {
+            log::warn!("Ignoring sender report of {:08x} without clock rate", ssrc);
+            return;
+        }
+        self.sources.insert(
+            ssrc,
+            SourceClock {
+                clock_rate,
+                scale: (1, 1),
+                ntp,
+                rtp_timestamp,
+            },
+        );
+    }
+
+    /// Maps the source with its SDP clock signalling instead of sender
+    /// reports. Returns false unless the media clock is direct and the
+    /// epoch of the reference clock is known, e.g. for PTP or NTP.
+    pub fn handle_media_clock(
+        &mut self,
+        ssrc: u32,
+        media_clock: &MediaClock,
+        ref_clock: &RefClock,
+        clock_rate: u32,
+    ) -> bool {
+        let (MediaClock::Direct { offset, rate }, Some(epoch)) = (media_clock, ref_clock.epoch()) else {
+            return false;
+        };
+        let scale = rate.filter(|(_, den)| *den != 0).unwrap_or((1, 1));
+        if clock_rate == 0 || scale.0 == 0 {
+            return false;
+        }
+        let clock = SourceClock::direct(clock_rate, scale, *offset, epoch, SystemTime::now());
+        self.sources.insert(ssrc, clock);
+        true
+    }
+
+    pub fn source(&self, ssrc: u32) -> Option<&SourceClock> {
+        self.sources.get(&ssrc)
+    }
+
+    /// Estimated capture time, `None` until a sender report of the source arrived
+    pub fn capture_time(&self, ssrc: u32, rtp_timestamp: u32) -> Option<SystemTime> {
+        self.sources.get(&ssrc).map(|s| s.capture_time(rtp_timestamp))
+    }
+
+    pub fn remove(&mut self, ssrc: u32) {
+        self.sources.remove(&ssrc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcp::CompoundPacket;
+    use crate::rtcp::RtcpPacket;
+
+    fn ntp(time: SystemTime) -> NtpTimestamp {
+        NtpTimestamp::from_system_time(time)
+    }
+
+    /// Current time with NTP precision, so durations compare exactly
+    fn now() -> SystemTime {
+        ntp(SystemTime::now()).to_system_time()
+    }
+
+    #[test]
+    fn test_capture_time() {
+        let now = now();
+        let mut sync = Synchronizer::new();
+        assert!(sync.capture_time(1, 0).is_none());
+        sync.update(1, ntp(now), 90_000, 90_000);
+        let later = sync.capture_time(1, 180_000).unwrap();
+        assert_eq!(later.duration_since(now).unwrap().as_millis(), 1000);
+        let earlier = sync.capture_time(1, 45_000).unwrap();
+        assert_eq!(now.duration_since(earlier).unwrap().as_millis(), 500);
+    }
+
+    #[test]
+    fn test_capture_time_wraparound() {
+        let now = now();
+        let mut sync = Synchronizer::new();
+        sync.update(1, ntp(now), u32::MAX - 8_999, 90_000);
+        let after_wrap = sync.capture_time(1, 9_000).unwrap();
+        assert_eq!(after_wrap.duration_since(now).unwrap().as_millis(), 200);
+    }
+
+    #[test]
+    fn test_lip_sync() {
+        let now = now();
+        let mut sync = Synchronizer::new();
+        // Video and audio with unrelated RTP timestamps but the same wall clock
+        sync.update(1, ntp(now), 1_000, 90_000);
+        sync.update(2, ntp(now), 500_000, 48_000);
+        let video = sync.capture_time(1, 1_000 + 9_000).unwrap();
+        let audio = sync.capture_time(2, 500_000 + 4_800).unwrap();
+        let diff = video.duration_since(audio).unwrap_or_else(|e| e.duration());
+        assert!(diff < Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_handle_sender_report() {
+        let mut buf = vec![0x80, 200, 0, 6, 0, 0, 0, 7];
+        buf.extend_from_slice(&((2_208_988_800u64 + 100) << 32).to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0x03, 0xe8, 0, 0, 0, 1, 0, 0, 0, 1]);
+        let compound = CompoundPacket::new(buf);
+        let Some(Ok(RtcpPacket::SenderReport(sr))) = compound.iter().next() else {
+            panic!("expected sender report");
+        };
+        let mut sync = Synchronizer::new();
+        sync.handle_sender_report(&sr, 1_000);
+        let time = sync.capture_time(7, 2_000).unwrap();
+        assert_eq!(time, std::time::UNIX_EPOCH + Duration::from_secs(101));
+    }
+
+    #[test]
+    fn test_direct_media_clock() {
+        let epoch = std::time::UNIX_EPOCH;
+        let now = epoch + Duration::from_secs(1_000_000);
+        let clock = SourceClock::direct(90_000, (1, 1), 1_000, epoch, now);
+        // 1 s after `now` the timestamp is 1_000_001 s * 90 kHz + offset, modulo 2^32
+        let ts = ((1_000_001u64 * 90_000 + 1_000) % (1 << 32)) as u32;
+        assert_eq!(clock.capture_time(ts), now + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_handle_media_clock() {
+        let mut sync = Synchronizer::new();
+        let ptp: RefClock = "ptp=IEEE1588-2008:traceable".parse().unwrap();
+        let direct: MediaClock = "direct=0".parse().unwrap();
+        assert!(!sync.handle_media_clock(1, &MediaClock::Sender, &ptp, 90_000));
+        assert!(!sync.handle_media_clock(1, &direct, &RefClock::Local, 90_000));
+        assert!(sync.handle_media_clock(1, &direct, &ptp, 90_000));
+        assert_eq!(sync.source(1).unwrap().clock_rate, 90_000);
+        let ntsc: MediaClock = "direct=0 rate=1000/1001".parse().unwrap();
+        assert!(sync.handle_media_clock(1, &ntsc, &ptp, 90_000));
+        assert_eq!(sync.source(1).unwrap().scale, (1000, 1001));
+    }
+
+    #[test]
+    fn test_direct_media_clock_ntsc() {
+        let epoch = std::time::UNIX_EPOCH;
+        let now = epoch + Duration::from_secs(1_000_000);
+        let clock = SourceClock::direct(90_000, (1000, 1001), 0, epoch, now);
+        // 1001 s of the 89.91 kHz clock are exactly 90_000_000 ticks
+        let time = epoch + Duration::from_secs(1_001_000);
+        let ts = ((1_000_000u64 * 90_000) % (1 << 32)) as u32;
+        let capture_time = clock.capture_time(ts);
+        let diff = capture_time.duration_since(time).unwrap_or_else(|e| e.duration());
+        assert!(diff < Duration::from_micros(1), "off by {:?}", diff);
+    }
+}
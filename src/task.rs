@@ -0,0 +1,73 @@
+//! Spawning of the crate's background tasks.
+//!
+//! All tasks are spawned through [`spawn`] so they carry a name. When the
+//! crate is built with the `console` feature and
+//! `RUSTFLAGS="--cfg tokio_unstable"`, the name is handed to
+//! `tokio::task::Builder`, which makes the tasks identifiable in
+//! tokio-console. Otherwise the tasks are spawned normally and the name is
+//! only used for logging.
+
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+/// Well-known task names used by the crate.
+///
+/// The RTCP receiver reports have no task of their own, their timer runs in
+/// the poll loop of the channel and shows up as [`CHANNEL`].
+pub const CHANNEL: &str = "rtsp-channel";
+pub const UDP_RX: &str = "udp-rx";
+pub const KEEPALIVE: &str = "keepalive";
+pub const SUPERVISOR: &str = "rtsp-supervisor";
+pub const SERVER: &str = "rtsp-server";
+pub const SERVER_CONNECTION: &str = "rtsp-server-conn";
+pub const SERVER_STREAM: &str = "rtsp-server-stream";
+pub const TS_BRIDGE: &str = "ts-bridge";
+pub const PROBE: &str = "rtsp-probe";
+pub const AGGREGATOR: &str = "aggregator";
+#[cfg(any(test, feature = "testing"))]
+pub const MOCK_SERVER: &str = "mock-server";
+#[cfg(any(test, feature = "testing"))]
+pub const REPLAY_SERVER: &str = "replay-server";
+#[cfg(any(test, feature = "testing"))]
+pub const REPLAY_EVENTS: &str = "replay-events";
+#[cfg(any(test, feature = "testing"))]
+pub const REPLAY_PACKETS: &str = "replay-packets";
+#[cfg(any(test, feature = "testing"))]
+pub const LOSSY_RELAY: &str = "lossy-relay";
+
+pub fn spawn<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = instrument(name.to_string(), future);
+    #[cfg(all(tokio_unstable, feature = "console"))]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("failed to spawn task")
+    }
+    #[cfg(not(all(tokio_unstable, feature = "console")))]
+    {
+        tokio::task::spawn(future)
+    }
+}
+
+async fn instrument<F: Future>(name: String, future: F) -> F::Output {
+    log::debug!("Task {} started", name);
+    let output = future.await;
+    log::debug!("Task {} finished", name);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spawn() {
+        let handle = spawn("test", async { 42 });
+        assert_eq!(handle.await.unwrap(), 42);
+    }
+}
@@ -0,0 +1,449 @@
+//! Impaired transports to exercise loss and jitter handling in tests.
+//!
+//! All decisions are drawn from a seeded rng, so a given seed always
+//! produces the same drops, duplicates, reorders and delays for the
+//! same input.
+
+use crate::task;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UdpSocket;
+use tokio::time::{Instant, Sleep};
+
+/// Probabilities and seed used to impair a flow of packets.
+#[derive(Debug, Clone)]
+pub struct Impairment {
+    seed: u64,
+    drop: f64,
+    duplicate: f64,
+    reorder: f64,
+    max_delay: Duration,
+}
+
+impl Impairment {
+    /// Creates an impairment that doesn't touch any packet yet.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            drop: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            max_delay: Duration::ZERO,
+        }
+    }
+
+    /// Probability of a packet being dropped.
+    pub fn drop(mut self, p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "drop probability out of range");
+        self.drop = p;
+        self
+    }
+
+    /// Probability of a packet being delivered twice.
+    pub fn duplicate(mut self, p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "duplicate probability out of range");
+        self.duplicate = p;
+        self
+    }
+
+    /// Probability of a packet being held back and delivered after the next one.
+    pub fn reorder(mut self, p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "reorder probability out of range");
+        self.reorder = p;
+        self
+    }
+
+    /// Each delivered packet is delayed by a random duration up to `max`.
+    pub fn delay(mut self, max: Duration) -> Self {
+        self.max_delay = max;
+        self
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImpairmentStats {
+    pub delivered: usize,
+    pub dropped: usize,
+    pub duplicated: usize,
+    pub reordered: usize,
+}
+
+/// Applies an [`Impairment`] to a sequence of packets.
+///
+/// Packets are pushed in their original order and popped together with
+/// the delay they should be delivered after.
+pub struct LossyQueue<T> {
+    impairment: Impairment,
+    rng: StdRng,
+    held: Option<T>,
+    out: VecDeque<(Duration, T)>,
+    stats: ImpairmentStats,
+}
+
+impl<T: Clone> LossyQueue<T> {
+    pub fn new(impairment: Impairment) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(impairment.seed),
+            impairment,
+            held: None,
+            out: VecDeque::new(),
+            stats: ImpairmentStats::default(),
+        }
+    }
+
+    pub fn push(&mut self, packet: T) {
+        if self.rng.random_bool(self.impairment.drop) {
+            self.stats.dropped += 1;
+            return;
+        }
+        if self.held.is_none() && self.rng.random_bool(self.impairment.reorder) {
+            self.stats.reordered += 1;
+            self.held = Some(packet);
+            return;
+        }
+        if self.rng.random_bool(self.impairment.duplicate) {
+            self.stats.duplicated += 1;
+            self.deliver(packet.clone());
+        }
+        self.deliver(packet);
+        if let Some(held) = self.held.take() {
+            self.deliver(held);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<(Duration, T)> {
+        self.out.pop_front()
+    }
+
+    /// Releases a packet that is held back for reordering.
+    pub fn flush(&mut self) {
+        if let Some(held) = self.held.take() {
+            self.deliver(held);
+        }
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.stats
+    }
+
+    fn deliver(&mut self, packet: T) {
+        let max = self.impairment.max_delay.as_micros() as u64;
+        let delay = if max == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_micros(self.rng.random_range(0..=max))
+        };
+        self.stats.delivered += 1;
+        self.out.push_back((delay, packet));
+    }
+}
+
+/// Splits off the next complete unit of an interleaved stream, returns its
+/// length and whether it is a `$` frame rather than a RTSP message.
+fn next_unit(buf: &[u8]) -> Option<(usize, bool)> {
+    if buf.first() == Some(&b'$') {
+        let len = 4 + u16::from_be_bytes([*buf.get(2)?, *buf.get(3)?]) as usize;
+        return (buf.len() >= len).then_some((len, true));
+    }
+    let end = buf.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let content_length = std::str::from_utf8(&buf[..end])
+        .ok()?
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    (buf.len() >= end + content_length).then_some((end + content_length, false))
+}
+
+/// Wraps the server side of an interleaved RTSP connection and impairs the
+/// `$` frames read from it. RTSP messages pass through untouched.
+///
+/// Since the stream keeps its order, a delayed frame also delays everything
+/// read after it, like a congested TCP connection would.
+pub struct LossyStream<S> {
+    inner: S,
+    queue: LossyQueue<Vec<u8>>,
+    input: Vec<u8>,
+    ready: VecDeque<(Instant, Vec<u8>)>,
+    offset: usize,
+    last_release: Option<Instant>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    eof: bool,
+}
+
+impl<S> LossyStream<S> {
+    pub fn new(inner: S, impairment: Impairment) -> Self {
+        Self {
+            inner,
+            queue: LossyQueue::new(impairment),
+            input: Vec::new(),
+            ready: VecDeque::new(),
+            offset: 0,
+            last_release: None,
+            sleep: None,
+            eof: false,
+        }
+    }
+
+    pub fn stats(&self) -> ImpairmentStats {
+        self.queue.stats()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn enqueue(&mut self, delay: Duration, data: Vec<u8>) {
+        let mut at = Instant::now() + delay;
+        if let Some(last) = self.last_release {
+            at = at.max(last);
+        }
+        self.last_release = Some(at);
+        self.ready.push_back((at, data));
+    }
+
+    fn drain_queue(&mut self) {
+        while let Some((delay, frame)) = self.queue.pop() {
+            self.enqueue(delay, frame);
+        }
+    }
+
+    fn split_input(&mut self) {
+        while let Some((len, is_frame)) = next_unit(&self.input) {
+            let unit: Vec<u8> = self.input.drain(..len).collect();
+            if is_frame {
+                self.queue.push(unit);
+                self.drain_queue();
+            } else {
+                self.enqueue(Duration::ZERO, unit);
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for LossyStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((at, data)) = this.ready.front() {
+                if *at > Instant::now() {
+                    let sleep = this
+                        .sleep
+                        .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(*at)));
+                    if sleep.deadline() != *at {
+                        sleep.as_mut().reset(*at);
+                    }
+                    ready!(sleep.as_mut().poll(cx));
+                    this.sleep = None;
+                    continue;
+                }
+                let n = (data.len() - this.offset).min(buf.remaining());
+                buf.put_slice(&data[this.offset..this.offset + n]);
+                this.offset += n;
+                if this.offset == data.len() {
+                    this.ready.pop_front();
+                    this.offset = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.eof {
+                return Poll::Ready(Ok(()));
+            }
+            let mut tmp = [0u8; 4096];
+            let mut read_buf = ReadBuf::new(&mut tmp);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+            if read_buf.filled().is_empty() {
+                this.eof = true;
+                this.queue.flush();
+                this.drain_queue();
+                if !this.input.is_empty() {
+                    let rest = std::mem::take(&mut this.input);
+                    this.enqueue(Duration::ZERO, rest);
+                }
+            } else {
+                this.input.extend_from_slice(read_buf.filled());
+                this.split_input();
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for LossyStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Forwards every datagram received on `socket` to `target`, impaired by
+/// `impairment`. Runs until receiving or sending fails.
+pub async fn relay_datagrams(socket: UdpSocket, target: SocketAddr, impairment: Impairment) -> io::Result<()> {
+    let socket = Arc::new(socket);
+    let mut queue = LossyQueue::new(impairment);
+    let mut buf = vec![0u8; u16::MAX as usize];
+    loop {
+        let (n, _) = socket.recv_from(&mut buf).await?;
+        queue.push(buf[..n].to_vec());
+        while let Some((delay, datagram)) = queue.pop() {
+            if delay.is_zero() {
+                socket.send_to(&datagram, target).await?;
+            } else {
+                let socket = socket.clone();
+                task::spawn(task::LOSSY_RELAY, async move {
+                    tokio::time::sleep(delay).await;
+                    let _ = socket.send_to(&datagram, target).await;
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn frame(channel: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![b'$', channel];
+        buf.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn run(impairment: Impairment, count: u8) -> (Vec<u8>, ImpairmentStats) {
+        let mut queue = LossyQueue::new(impairment);
+        for i in 0..count {
+            queue.push(i);
+        }
+        queue.flush();
+        let mut out = Vec::new();
+        while let Some((_, i)) = queue.pop() {
+            out.push(i);
+        }
+        (out, queue.stats())
+    }
+
+    #[test]
+    fn test_lossy_queue_passthrough() {
+        let (out, stats) = run(Impairment::new(1), 10);
+        assert_eq!(out, (0..10).collect::<Vec<_>>());
+        assert_eq!(stats.delivered, 10);
+    }
+
+    #[test]
+    fn test_lossy_queue_is_deterministic() {
+        let impairment = Impairment::new(42).drop(0.2).duplicate(0.1).reorder(0.2);
+        let (first, _) = run(impairment.clone(), 100);
+        let (second, _) = run(impairment.clone(), 100);
+        assert_eq!(first, second);
+        // The exact pattern of the seed, a change of the rng use shows up here
+        let (out, stats) = run(impairment, 20);
+        assert_eq!(
+            out,
+            [0, 2, 1, 5, 3, 7, 6, 10, 9, 11, 11, 12, 13, 14, 15, 16, 16, 17, 18]
+        );
+        assert_eq!(
+            stats,
+            ImpairmentStats {
+                delivered: 19,
+                dropped: 3,
+                duplicated: 2,
+                reordered: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_lossy_queue_certain_impairments() {
+        assert!(run(Impairment::new(1).drop(1.0), 5).0.is_empty());
+        assert_eq!(run(Impairment::new(1).duplicate(1.0), 2).0, vec![0, 0, 1, 1]);
+        assert_eq!(run(Impairment::new(1).reorder(1.0), 4).0, vec![1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn test_lossy_queue_delay() {
+        let mut queue = LossyQueue::new(Impairment::new(7).delay(Duration::from_millis(50)));
+        for i in 0..20 {
+            queue.push(i);
+        }
+        while let Some((delay, _)) = queue.pop() {
+            assert!(delay <= Duration::from_millis(50));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lossy_stream_keeps_rtsp_messages() {
+        let (mut server, client) = tokio::io::duplex(1024);
+        let mut stream = LossyStream::new(client, Impairment::new(3).drop(1.0));
+        let response = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\nContent-Length: 4\r\n\r\n$abc";
+        let mut input = frame(0, &[1, 2, 3]);
+        input.extend_from_slice(response);
+        input.extend_from_slice(&frame(1, &[4, 5]));
+        server.write_all(&input).await.unwrap();
+        drop(server);
+
+        let mut output = Vec::new();
+        stream.read_to_end(&mut output).await.unwrap();
+        assert_eq!(output, response);
+        assert_eq!(stream.stats().dropped, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lossy_stream_delay() {
+        let (mut server, client) = tokio::io::duplex(1024);
+        let impairment = Impairment::new(3).drop(0.3).delay(Duration::from_millis(100));
+        let mut stream = LossyStream::new(client, impairment);
+        let input: Vec<u8> = (0..8).flat_map(|i| frame(0, &[i])).collect();
+        server.write_all(&input).await.unwrap();
+        drop(server);
+
+        // The frames that survive and the time they are read at, a delayed
+        // frame holds back the ones behind it
+        let start = Instant::now();
+        let mut received = Vec::new();
+        let mut buf = [0u8; 5];
+        while stream.read_exact(&mut buf).await.is_ok() {
+            received.push((buf[4], start.elapsed().as_millis()));
+        }
+        assert_eq!(
+            received,
+            [(1, 25), (2, 49), (3, 94), (4, 94), (5, 94), (6, 94), (7, 94)]
+        );
+        assert_eq!(stream.stats().dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn test_relay_datagrams() {
+        let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let relay_addr = relay.local_addr().unwrap();
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let impairment = Impairment::new(5).duplicate(1.0);
+        let handle = tokio::spawn(relay_datagrams(relay, receiver.local_addr().unwrap(), impairment));
+
+        sender.send_to(b"rtp", relay_addr).await.unwrap();
+        let mut buf = [0u8; 16];
+        for _ in 0..2 {
+            let n = receiver.recv(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"rtp");
+        }
+        handle.abort();
+    }
+}
@@ -0,0 +1,302 @@
+//! Scriptable RTSP server for tests that need a peer but no camera.
+//!
+//! A [`MockServer`] plays a script on one connection: it waits for the
+//! expected requests and answers them with canned responses, sends
+//! interleaved data and closes the connection where the script says so.
+//! The requests it received are returned for further assertions.
+
+use crate::rtsp::{IncomingRequest, Method, Status};
+use crate::task;
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Error)]
+pub enum MockError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Expected {expected} request, got {got:?}")]
+    UnexpectedRequest { expected: Method, got: IncomingRequest },
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Connection closed while expecting {0}")]
+    Closed(Method),
+}
+
+pub type MockResult = Result<Vec<IncomingRequest>, MockError>;
+
+/// Canned response of a [`MockServer`], the CSeq of the request is mirrored
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: Status,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl MockResponse {
+    pub fn new(status: Status) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::new(Status::OK)
+    }
+
+    /// 401 with a Basic challenge of `realm`
+    pub fn unauthorized(realm: &str) -> Self {
+        Self::new(Status::Unauthorized).header("WWW-Authenticate", &format!("Basic realm=\"{}\"", realm))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Answers DESCRIBE with the SDP
+    pub fn sdp(self, sdp: &str) -> Self {
+        self.header("Content-Type", "application/sdp").body(sdp)
+    }
+
+    /// The Content-Length is added when the response is sent
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    fn serialize(&self, cseq: Option<u32>) -> String {
+        let mut response = format!("RTSP/1.0 {}\r\n", self.status);
+        if let Some(cseq) = cseq {
+            let _ = write!(response, "CSeq: {}\r\n", cseq);
+        }
+        for (name, value) in &self.headers {
+            let _ = write!(response, "{}: {}\r\n", name, value);
+        }
+        if let Some(body) = &self.body {
+            let _ = write!(response, "Content-Length: {}\r\n", body.len());
+        }
+        response.push_str("\r\n");
+        response.push_str(self.body.as_deref().unwrap_or_default());
+        response
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Expect(Method, MockResponse),
+    Interleaved(u8, Vec<u8>),
+    Sleep(Duration),
+    Close,
+}
+
+/// Plays a script of expected requests and server actions on a connection,
+/// e.g.
+///
+/// ```ignore
+/// let server = MockServer::new()
+///     .expect(Method::Describe, MockResponse::unauthorized("cam"))
+///     .expect(Method::Describe, MockResponse::ok().sdp(SDP))
+///     .expect(Method::Play, MockResponse::ok())
+///     .interleaved(0, rtp_packet)
+///     .close();
+/// ```
+///
+/// A request of another method than expected fails the script. Interleaved
+/// data sent by the client is skipped.
+#[derive(Debug, Clone, Default)]
+pub struct MockServer {
+    steps: Vec<Step>,
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for a request of `method` and answers it with `response`
+    pub fn expect(mut self, method: Method, response: MockResponse) -> Self {
+        self.steps.push(Step::Expect(method, response));
+        self
+    }
+
+    /// Sends `data` interleaved on `channel`
+    pub fn interleaved(mut self, channel: u8, data: &[u8]) -> Self {
+        self.steps.push(Step::Interleaved(channel, data.to_vec()));
+        self
+    }
+
+    pub fn sleep(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Sleep(duration));
+        self
+    }
+
+    /// Drops the connection, the remaining steps are skipped
+    pub fn close(mut self) -> Self {
+        self.steps.push(Step::Close);
+        self
+    }
+
+    /// Plays the script on `stream`, e.g. the server side of a
+    /// `tokio::io::duplex`. Without [`MockServer::close`] the connection
+    /// stays open until the client closes it.
+    pub fn serve<S>(self, stream: S) -> JoinHandle<MockResult>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        task::spawn(task::MOCK_SERVER, self.run(stream))
+    }
+
+    /// Accepts one TCP connection and plays the script on it
+    pub async fn listen(self) -> io::Result<(SocketAddr, JoinHandle<MockResult>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let handle = task::spawn(task::MOCK_SERVER, async move {
+            let (stream, _) = listener.accept().await?;
+            self.run(stream).await
+        });
+        Ok((addr, handle))
+    }
+
+    async fn run<S: AsyncRead + AsyncWrite + Unpin>(self, mut stream: S) -> MockResult {
+        let mut requests = Vec::new();
+        let mut buf = Vec::new();
+        for step in self.steps {
+            match step {
+                Step::Expect(method, response) => {
+                    let request = read_request(&mut stream, &mut buf)
+                        .await?
+                        .ok_or(MockError::Closed(method))?;
+                    if request.method != Some(method) {
+                        return Err(MockError::UnexpectedRequest {
+                            expected: method,
+                            got: request,
+                        });
+                    }
+                    stream.write_all(response.serialize(request.cseq).as_bytes()).await?;
+                    requests.push(request);
+                }
+                Step::Interleaved(channel, data) => {
+                    let mut frame = vec![b'$', channel];
+                    frame.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                    frame.extend_from_slice(&data);
+                    stream.write_all(&frame).await?;
+                }
+                Step::Sleep(duration) => tokio::time::sleep(duration).await,
+                Step::Close => {
+                    stream.shutdown().await?;
+                    return Ok(requests);
+                }
+            }
+        }
+        // Keep the connection open until the client is done
+        while read_request(&mut stream, &mut buf).await?.is_some() {}
+        Ok(requests)
+    }
+}
+
+/// Reads the next request, `None` if the connection was closed
+pub(super) async fn read_request<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut Vec<u8>,
+) -> Result<Option<IncomingRequest>, MockError> {
+    loop {
+        // Skip interleaved frames of the client, e.g. RTCP receiver reports
+        while buf.first() == Some(&b'$') && buf.len() >= 4 {
+            let len = 4 + u16::from_be_bytes([buf[2], buf[3]]) as usize;
+            if buf.len() < len {
+                break;
+            }
+            buf.drain(..len);
+        }
+        if buf.first() != Some(&b'$') {
+            match IncomingRequest::parse(buf) {
+                Ok(Some((request, n))) => {
+                    buf.drain(..n);
+                    return Ok(Some(request));
+                }
+                Ok(None) => {}
+                Err(e) => return Err(MockError::InvalidRequest(e.to_string())),
+            }
+        }
+        let mut chunk = [0u8; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::client::{Channel, Command, CommandError, Options, Request};
+    use tokio::sync::{mpsc, oneshot};
+    use url::Url;
+
+    async fn options(cmd_tx: &mpsc::Sender<Command>) -> Result<Vec<Method>, CommandError> {
+        let (tx, rx) = oneshot::channel();
+        let url = Url::parse("rtsp://cam/live").unwrap();
+        cmd_tx
+            .send(Command::Request(Request::Options(Options::new(url, tx))))
+            .await
+            .unwrap();
+        rx.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mock_server() {
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        let server = MockServer::new()
+            .expect(Method::Options, MockResponse::unauthorized("cam"))
+            .expect(Method::Options, MockResponse::ok().header("Public", "OPTIONS, PLAY"))
+            .interleaved(0, &[0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1])
+            .close()
+            .serve(sstream);
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, mut packet_rx) = mpsc::channel(8);
+        let handle = Channel::new(cstream, cmd_rx, packet_tx)
+            .user("user")
+            .pass("pass")
+            .start();
+
+        assert_eq!(options(&cmd_tx).await.unwrap(), [Method::Options, Method::Play]);
+        assert_eq!(packet_rx.recv().await.unwrap().sequence_number(), 1);
+        let requests = server.await.unwrap().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("Authorization"), Some("Basic dXNlcjpwYXNz"));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_unexpected_request() {
+        let (cstream, sstream) = tokio::io::duplex(4096);
+        let server = MockServer::new()
+            .expect(Method::Describe, MockResponse::ok())
+            .serve(sstream);
+        let (cmd_tx, cmd_rx) = mpsc::channel(8);
+        let (packet_tx, _) = mpsc::channel(8);
+        let _handle = Channel::new(cstream, cmd_rx, packet_tx).start();
+        let (tx, _rx) = oneshot::channel();
+        let url = Url::parse("rtsp://cam/live").unwrap();
+        cmd_tx
+            .send(Command::Request(Request::Options(Options::new(url, tx))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(MockError::UnexpectedRequest {
+                expected: Method::Describe,
+                ..
+            })
+        ));
+    }
+}
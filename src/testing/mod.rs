@@ -0,0 +1,23 @@
+mod lossy;
+mod mock;
+mod replay;
+mod source;
+
+pub use lossy::relay_datagrams;
+pub use lossy::Impairment;
+pub use lossy::ImpairmentStats;
+pub use lossy::LossyQueue;
+pub use lossy::LossyStream;
+pub use mock::MockError;
+pub use mock::MockResponse;
+pub use mock::MockResult;
+pub use mock::MockServer;
+pub use replay::Replay;
+pub use replay::ReplayError;
+pub use replay::ReplayOutcome;
+pub use replay::ReplayResult;
+pub use replay::Transcript;
+pub use source::rtp_packet;
+pub use source::test_packet;
+pub use source::TestSource;
+pub use source::TEST_SDP;
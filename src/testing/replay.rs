@@ -0,0 +1,447 @@
+//! Replays recorded RTSP sessions against a [`Channel`], e.g. to turn a
+//! capture of a misbehaving camera into a regression test.
+//!
+//! A [`Transcript`] is the byte stream of both directions of a session in a
+//! line based text format:
+//!
+//! ```text
+//! # Comments and empty lines are skipped
+//! C: DESCRIBE rtsp://cam/live RTSP/1.0
+//! C: CSeq: 2
+//! C:
+//! S: RTSP/1.0 200 OK
+//! S: CSeq: 2
+//! S:
+//! $0: 80 60 00 01 00 00 00 00 00 00 00 05 65
+//! ! sleep 100
+//! ! close
+//! ```
+//!
+//! Consecutive `C:` or `S:` lines form one chunk of client or server bytes,
+//! every line ends with CRLF. `$<channel>:` is interleaved data of the
+//! server in hex, `! sleep <ms>` pauses and `! close` drops the connection.
+//! Requests of the client are matched by method and url, the CSeq headers
+//! of the server are rewritten to the CSeq the channel actually used, so a
+//! capture taken with any client replays. Everything else is sent verbatim,
+//! broken responses included.
+
+use super::mock::read_request;
+use super::MockError;
+use crate::rtp;
+use crate::rtsp::client::{Channel, Command, Ctrl, Event};
+use crate::rtsp::IncomingRequest;
+use crate::task;
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Line {line}: expected {expected}, got {got:?}")]
+    Mismatch {
+        line: usize,
+        expected: String,
+        got: Box<IncomingRequest>,
+    },
+    #[error("Line {line}: connection closed while expecting {expected}")]
+    Closed { line: usize, expected: String },
+}
+
+impl From<MockError> for ReplayError {
+    fn from(e: MockError) -> Self {
+        match e {
+            MockError::Io(e) => ReplayError::Io(e),
+            e => ReplayError::InvalidRequest(e.to_string()),
+        }
+    }
+}
+
+pub type ReplayResult = Result<Vec<IncomingRequest>, ReplayError>;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Client(IncomingRequest),
+    Server(String),
+    Interleaved(u8, Vec<u8>),
+    Sleep(Duration),
+    Close,
+}
+
+/// Both directions of a recorded session, see the [module docs](self)
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    // Entries with the line they start at
+    entries: Vec<(usize, Entry)>,
+}
+
+impl FromStr for Transcript {
+    type Err = ReplayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Vec::new();
+        // Direction, first line and bytes of the chunk being collected
+        let mut chunk: Option<(char, usize, String)> = None;
+        for (i, line) in s.lines().enumerate() {
+            let line_number = i + 1;
+            let error = |reason: &str| ReplayError::Parse {
+                line: line_number,
+                reason: reason.to_string(),
+            };
+            let direction = match line.as_bytes().first() {
+                Some(b'C') if line.starts_with("C:") => Some('C'),
+                Some(b'S') if line.starts_with("S:") => Some('S'),
+                _ => None,
+            };
+            if let Some(direction) = direction {
+                let text = line[2..].strip_prefix(' ').unwrap_or(&line[2..]);
+                match &mut chunk {
+                    Some((d, _, bytes)) if *d == direction => bytes.push_str(text),
+                    _ => {
+                        if let Some(done) = chunk.take() {
+                            entries.push(finish_chunk(done)?);
+                        }
+                        chunk = Some((direction, line_number, text.to_string()));
+                    }
+                }
+                chunk.as_mut().unwrap().2.push_str("\r\n");
+                continue;
+            }
+            if let Some(done) = chunk.take() {
+                entries.push(finish_chunk(done)?);
+            }
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = if let Some(rest) = line.strip_prefix('$') {
+                let (channel, data) = rest.split_once(':').ok_or_else(|| error("missing ':'"))?;
+                let channel = channel.trim().parse().map_err(|_| error("invalid channel"))?;
+                Entry::Interleaved(channel, parse_hex(data).ok_or_else(|| error("invalid hex data"))?)
+            } else if let Some(directive) = line.strip_prefix('!') {
+                match directive.split_whitespace().collect::<Vec<_>>()[..] {
+                    ["close"] => Entry::Close,
+                    ["sleep", ms] => {
+                        let ms = ms.parse().map_err(|_| error("invalid sleep duration"))?;
+                        Entry::Sleep(Duration::from_millis(ms))
+                    }
+                    _ => return Err(error("unknown directive")),
+                }
+            } else {
+                return Err(error("unknown line"));
+            };
+            entries.push((line_number, entry));
+        }
+        if let Some(done) = chunk.take() {
+            entries.push(finish_chunk(done)?);
+        }
+        Ok(Self { entries })
+    }
+}
+
+fn finish_chunk((direction, line, bytes): (char, usize, String)) -> Result<(usize, Entry), ReplayError> {
+    if direction == 'S' {
+        return Ok((line, Entry::Server(bytes)));
+    }
+    let error = |reason: &str| ReplayError::Parse {
+        line,
+        reason: reason.to_string(),
+    };
+    match IncomingRequest::parse(bytes.as_bytes()) {
+        Ok(Some((request, n))) if n == bytes.len() => Ok((line, Entry::Client(request))),
+        Ok(Some(_)) => Err(error("more than one request")),
+        Ok(None) => Err(error("incomplete request")),
+        Err(e) => Err(error(&e.to_string())),
+    }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Replaces the values of CSeq headers found in `cseqs`
+fn rewrite_cseq(chunk: &str, cseqs: &HashMap<u32, u32>) -> String {
+    let mut rewritten = String::with_capacity(chunk.len());
+    for line in chunk.split_inclusive("\r\n") {
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        match value.trim().parse().ok().and_then(|cseq| cseqs.get(&cseq)) {
+            Some(cseq) if name.eq_ignore_ascii_case("cseq") => {
+                rewritten.push_str(&format!("{}: {}\r\n", name, cseq));
+            }
+            _ => rewritten.push_str(line),
+        }
+    }
+    rewritten
+}
+
+impl Transcript {
+    /// Plays the server side of the transcript on `stream`. `played` is
+    /// notified once all entries are played, without a `! close` the
+    /// connection stays open until the client closes it.
+    pub fn serve<S>(self, stream: S, played: Option<oneshot::Sender<()>>) -> JoinHandle<ReplayResult>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        task::spawn(task::REPLAY_SERVER, self.run(stream, played))
+    }
+
+    async fn run<S: AsyncRead + AsyncWrite + Unpin>(
+        self,
+        mut stream: S,
+        played: Option<oneshot::Sender<()>>,
+    ) -> ReplayResult {
+        let mut requests = Vec::new();
+        let mut buf = Vec::new();
+        // CSeq of the recorded requests to the one the client used
+        let mut cseqs = HashMap::new();
+        for (line, entry) in self.entries {
+            match entry {
+                Entry::Client(expected) => {
+                    let describe = || {
+                        format!(
+                            "{} {}",
+                            expected.method.as_ref().map_or("?", |m| m.as_str()),
+                            expected.uri
+                        )
+                    };
+                    let request = read_request(&mut stream, &mut buf)
+                        .await?
+                        .ok_or_else(|| ReplayError::Closed {
+                            line,
+                            expected: describe(),
+                        })?;
+                    if request.method != expected.method || request.uri != expected.uri {
+                        return Err(ReplayError::Mismatch {
+                            line,
+                            expected: describe(),
+                            got: Box::new(request),
+                        });
+                    }
+                    if let (Some(recorded), Some(used)) = (expected.cseq, request.cseq) {
+                        cseqs.insert(recorded, used);
+                    }
+                    requests.push(request);
+                }
+                Entry::Server(chunk) => stream.write_all(rewrite_cseq(&chunk, &cseqs).as_bytes()).await?,
+                Entry::Interleaved(channel, data) => {
+                    let mut frame = vec![b'$', channel];
+                    frame.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                    frame.extend_from_slice(&data);
+                    stream.write_all(&frame).await?;
+                }
+                Entry::Sleep(duration) => tokio::time::sleep(duration).await,
+                Entry::Close => {
+                    stream.shutdown().await?;
+                    if let Some(played) = played {
+                        let _ = played.send(());
+                    }
+                    return Ok(requests);
+                }
+            }
+        }
+        if let Some(played) = played {
+            let _ = played.send(());
+        }
+        while read_request(&mut stream, &mut buf).await?.is_some() {}
+        Ok(requests)
+    }
+}
+
+/// What a [`Channel`] did during a [`Replay`]
+#[derive(Debug)]
+pub struct ReplayOutcome {
+    /// Requests the channel sent, as received by the server
+    pub requests: Vec<IncomingRequest>,
+    pub events: Vec<Event>,
+    pub packets: Vec<rtp::Packet>,
+}
+
+/// Runs a [`Channel`] against the server side of a [`Transcript`], e.g.
+///
+/// ```ignore
+/// let replay = Replay::start(capture.parse()?, |channel| channel.user("admin"));
+/// let sdp = describe(&replay.cmd_tx).await?;
+/// let outcome = replay.finish().await?;
+/// assert_eq!(outcome.packets.len(), 3);
+/// ```
+///
+/// Events and packets are collected in the background, so the transcript
+/// plays at its own pace while commands are sent on `cmd_tx`.
+pub struct Replay {
+    pub cmd_tx: mpsc::Sender<Command>,
+    played: oneshot::Receiver<()>,
+    server: JoinHandle<ReplayResult>,
+    channel: JoinHandle<()>,
+    events: JoinHandle<Vec<Event>>,
+    packets: JoinHandle<Vec<rtp::Packet>>,
+}
+
+impl Replay {
+    /// Starts the channel returned by `configure`, the event and packet
+    /// senders are set by the replay
+    pub fn start<F>(transcript: Transcript, configure: F) -> Self
+    where
+        F: FnOnce(Channel<DuplexStream>) -> Channel<DuplexStream>,
+    {
+        let (cstream, sstream) = tokio::io::duplex(64 * 1024);
+        let (played_tx, played) = oneshot::channel();
+        let server = transcript.serve(sstream, Some(played_tx));
+        let (cmd_tx, cmd_rx) = mpsc::channel(16);
+        let (packet_tx, packet_rx) = mpsc::channel(256);
+        let (event_tx, event_rx) = mpsc::channel(256);
+        let channel = configure(Channel::new(cstream, cmd_rx, packet_tx))
+            .events(event_tx)
+            .start();
+        Self {
+            cmd_tx,
+            played,
+            server,
+            channel,
+            events: task::spawn(task::REPLAY_EVENTS, collect(event_rx)),
+            packets: task::spawn(task::REPLAY_PACKETS, collect(packet_rx)),
+        }
+    }
+
+    /// Waits for the transcript to be played, shuts the channel down and
+    /// returns what it did. The requests the transcript expects have to be
+    /// sent before, otherwise this waits for them forever.
+    pub async fn finish(self) -> Result<ReplayOutcome, ReplayError> {
+        // The server task ends early on a mismatch
+        let _ = self.played.await;
+        let cmd = Command::Ctrl(Ctrl::Shutdown);
+        let _ = self.cmd_tx.send(cmd).await;
+        drop(self.cmd_tx);
+        let _ = self.channel.await;
+        let requests = self.server.await.map_err(io::Error::other)??;
+        Ok(ReplayOutcome {
+            requests,
+            events: self.events.await.map_err(io::Error::other)?,
+            packets: self.packets.await.map_err(io::Error::other)?,
+        })
+    }
+}
+
+async fn collect<T>(mut rx: mpsc::Receiver<T>) -> Vec<T> {
+    let mut items = Vec::new();
+    while let Some(item) = rx.recv().await {
+        items.push(item);
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtsp::client::{CommandHandle, Describe, DisconnectReason, Play, Request};
+    use url::Url;
+
+    // A camera that sends RTP right after the PLAY response and drops the
+    // connection
+    const CAPTURE: &str = "\
+# Recorded with another client, CSeqs start at 5
+C: DESCRIBE rtsp://cam/live RTSP/1.0
+C: CSeq: 5
+C: Accept: application/sdp
+C:
+S: RTSP/1.0 200 OK
+S: CSeq: 5
+S: Content-Type: application/sdp
+S: Content-Length: 40
+S:
+S: v=0
+S: m=video 0 RTP/AVP 96
+S: a=control:*
+C: PLAY rtsp://cam/live RTSP/1.0
+C: CSeq: 6
+C:
+S: RTSP/1.0 200 OK
+S: CSeq: 6
+S:
+$0: 80 60 00 07 00 00 00 00 00 00 00 05 41
+! sleep 10
+$0: 80 60 00 08 00 00 00 00 00 00 00 05 41
+! close
+";
+
+    #[tokio::test]
+    async fn test_replay() {
+        let transcript: Transcript = CAPTURE.parse().unwrap();
+        let replay = Replay::start(transcript, |channel| channel.user_agent("replay"));
+        let url = Url::parse("rtsp://cam/live").unwrap();
+        let describe = CommandHandle::send(&replay.cmd_tx, |tx| Request::Describe(Describe::new(url.clone(), tx)))
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(describe.into_sdp().unwrap().media.len(), 1);
+        CommandHandle::send(&replay.cmd_tx, |tx| Request::Play(Play::new(url, tx)))
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        let outcome = replay.finish().await.unwrap();
+        assert_eq!(outcome.requests.len(), 2);
+        assert_eq!(outcome.requests[1].cseq, Some(2));
+        assert_eq!(
+            outcome.packets.iter().map(|p| p.sequence_number()).collect::<Vec<_>>(),
+            [7, 8]
+        );
+        assert_eq!(outcome.events.first(), Some(&Event::Connected));
+        assert_eq!(
+            outcome.events.last(),
+            Some(&Event::Disconnected {
+                reason: DisconnectReason::Closed
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_mismatch() {
+        let transcript: Transcript = "C: OPTIONS rtsp://cam/live RTSP/1.0\nC: CSeq: 1\nC:\n".parse().unwrap();
+        let replay = Replay::start(transcript, |channel| channel);
+        let url = Url::parse("rtsp://cam/other").unwrap();
+        // Dropping the handle would cancel the request before it is sent
+        let _describe = CommandHandle::send(&replay.cmd_tx, |tx| Request::Describe(Describe::new(url, tx))).await;
+        match replay.finish().await {
+            Err(ReplayError::Mismatch { line: 1, expected, got }) => {
+                assert_eq!(expected, "OPTIONS rtsp://cam/live");
+                assert_eq!(got.uri, "rtsp://cam/other");
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_transcript_parse_error() {
+        assert!(matches!(
+            "S: RTSP/1.0 200 OK\n$x: 00".parse::<Transcript>(),
+            Err(ReplayError::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            "$0: 0".parse::<Transcript>(),
+            Err(ReplayError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            "C: PLAY rtsp://cam RTSP/1.0".parse::<Transcript>(),
+            Err(ReplayError::Parse { line: 1, .. })
+        ));
+    }
+}
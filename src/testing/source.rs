@@ -0,0 +1,46 @@
+//! A live source for tests that run the server, or a client against it.
+
+use crate::rtp::{Packet, PacketBuilder};
+use crate::rtsp::server::MediaSource;
+use crate::sdp::Sdp;
+use tokio::sync::broadcast;
+
+/// Description of the single H.264 track of a [`TestSource`]
+pub const TEST_SDP: &str = "v=0\r\ns=Test\r\nm=video 0 RTP/AVP 96\r\na=rtpmap:96 H264/90000\r\na=control:trackID=0\r\n";
+
+/// Serves the packets sent on its channel as the track `trackID=0` of the
+/// path `/live`, described by [`TEST_SDP`]
+pub struct TestSource {
+    tx: broadcast::Sender<Packet>,
+}
+
+impl TestSource {
+    pub fn new(tx: broadcast::Sender<Packet>) -> Self {
+        Self { tx }
+    }
+}
+
+impl MediaSource for TestSource {
+    fn describe(&self, path: &str) -> Option<Sdp> {
+        (path == "/live").then(|| Sdp::try_from(TEST_SDP).unwrap())
+    }
+
+    fn subscribe(&self, path: &str, track: usize) -> Option<broadcast::Receiver<Packet>> {
+        (path == "/live" && track == 0).then(|| self.tx.subscribe())
+    }
+}
+
+/// An RTP packet of the payload type of [`TEST_SDP`] with `seq` and a
+/// payload of one byte
+pub fn test_packet(seq: u16) -> Packet {
+    rtp_packet(96, 1, seq, 0, false, &[0xab])
+}
+
+/// A single RTP packet with the given header fields, the fixture of the
+/// unit tests that need packets with exact sequence numbers and timestamps
+pub fn rtp_packet(payload_type: u8, ssrc: u32, seq: u16, timestamp: u32, marker: bool, payload: &[u8]) -> Packet {
+    PacketBuilder::new(payload_type, ssrc)
+        .sequence_number(seq)
+        .timestamp_offset(timestamp)
+        .build(payload, 0, marker)
+}
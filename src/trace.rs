@@ -0,0 +1,55 @@
+//! Instrumentation for diagnosing interop issues, e.g. which request a
+//! camera rejected, without a packet capture. Events go to `tracing` with
+//! the `tracing` feature and compile to nothing without it.
+
+/// Bytes shown by [`hex_dump`], the rest is counted
+#[cfg(feature = "tracing")]
+const HEX_DUMP_LIMIT: usize = 128;
+
+/// A `tracing` event with the `tracing` feature, the level followed by the
+/// arguments of the `tracing` macros, e.g.
+/// `trace::event!(debug, cseq, status = %status, "Received response")`.
+/// Arguments are not evaluated without the feature. A `log` line that
+/// reports the same event is compiled only without the feature, so the
+/// event is emitted once.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        {
+            tracing::$level!($($arg)+);
+        }
+    };
+}
+
+pub(crate) use event;
+
+/// The first bytes of data that could not be parsed, e.g.
+/// `52 54 53 50 2f 31 2e 30 ... (2048 bytes)`
+#[cfg(feature = "tracing")]
+pub(crate) fn hex_dump(data: &[u8]) -> String {
+    let mut dump = data
+        .iter()
+        .take(HEX_DUMP_LIMIT)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if data.len() > HEX_DUMP_LIMIT {
+        dump.push_str(&format!(" ... ({} bytes)", data.len()));
+    }
+    dump
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(hex_dump(b"RTSP"), "52 54 53 50");
+        assert_eq!(hex_dump(&[]), "");
+        let dump = hex_dump(&[0xab; 200]);
+        assert!(dump.starts_with("ab ab "));
+        assert!(dump.ends_with("ab ... (200 bytes)"));
+        assert_eq!(dump.matches("ab").count(), HEX_DUMP_LIMIT);
+    }
+}
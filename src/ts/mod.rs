@@ -0,0 +1,8 @@
+mod muxer;
+mod sink;
+
+pub use muxer::Error as TsError;
+pub use muxer::TsMuxer;
+pub use muxer::TS_PACKET_SIZE;
+pub use sink::bridge;
+pub use sink::TsWriter;
@@ -0,0 +1,567 @@
+use crate::codec::{nal_type, NalUnits};
+use crate::rtcp::SenderReport;
+use crate::sync::{NtpTimestamp, SourceClock};
+use crate::types::{Frame, FrameType, MediaType};
+use std::time::SystemTime;
+use thiserror::Error;
+
+pub const TS_PACKET_SIZE: usize = 188;
+const PAT_PID: u16 = 0x0000;
+const PMT_PID: u16 = 0x1000;
+const FIRST_ES_PID: u16 = 0x0100;
+const PROGRAM_NUMBER: u16 = 1;
+/// Added to all timestamps, so tracks starting slightly before the first
+/// frame don't get negative ones
+const PTS_OFFSET: i64 = 90_000;
+/// How far the PCR runs ahead of the presentation time
+const PCR_DELAY: i64 = 45_000;
+/// Longest time without PAT and PMT, in 90 kHz ticks
+const PSI_INTERVAL: i64 = 45_000;
+const TIMESTAMP_MASK: i64 = (1 << 33) - 1;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Codec {0:?} can't be carried in MPEG-TS")]
+    UnsupportedCodec(FrameType),
+    #[error("AAC tracks require a valid AudioSpecificConfig")]
+    InvalidAacConfig,
+    #[error("Track {0} was already added")]
+    DuplicateTrack(usize),
+    #[error("Unknown track {0}")]
+    UnknownTrack(usize),
+    #[error("Track {0} has no clock rate")]
+    InvalidClockRate(usize),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Fields of the ADTS header taken from the AudioSpecificConfig
+#[derive(Debug, Clone, Copy)]
+struct AdtsConfig {
+    profile: u8,
+    frequency_index: u8,
+    channels: u8,
+}
+
+impl AdtsConfig {
+    fn parse(config: &[u8]) -> Result<Self> {
+        let [first, second, ..] = config else {
+            return Err(Error::InvalidAacConfig);
+        };
+        let object_type = first >> 3;
+        let frequency_index = ((first & 0x07) << 1) | (second >> 7);
+        let channels = (second >> 3) & 0x0f;
+        // ADTS can only signal the object types 1 to 4 and explicit frequencies
+        if !(1..=4).contains(&object_type) || frequency_index > 12 {
+            return Err(Error::InvalidAacConfig);
+        }
+        Ok(Self {
+            profile: object_type - 1,
+            frequency_index,
+            channels,
+        })
+    }
+
+    fn header(&self, payload_len: usize) -> [u8; 7] {
+        let len = payload_len + 7;
+        [
+            0xff,
+            0xf1,
+            (self.profile << 6) | (self.frequency_index << 2) | (self.channels >> 2),
+            ((self.channels & 0x03) << 6) | ((len >> 11) as u8 & 0x03),
+            (len >> 3) as u8,
+            ((len as u8 & 0x07) << 5) | 0x1f,
+            0xfc,
+        ]
+    }
+}
+
+#[derive(Debug)]
+struct Track {
+    id: usize,
+    pid: u16,
+    stream_type: u8,
+    stream_id: u8,
+    frame_type: FrameType,
+    clock_rate: u32,
+    adts: Option<AdtsConfig>,
+    clock: Option<SourceClock>,
+    first_timestamp: Option<u32>,
+    last_timestamp: u32,
+    // Unwrapped RTP timestamp relative to the first one
+    ticks: i64,
+    continuity: u8,
+}
+
+impl Track {
+    fn is_video(&self) -> bool {
+        matches!(self.frame_type, FrameType::H264 | FrameType::H265)
+    }
+
+    fn relative_ticks(&mut self, timestamp: u32) -> i64 {
+        match self.first_timestamp {
+            None => self.first_timestamp = Some(timestamp),
+            Some(_) => self.ticks += timestamp.wrapping_sub(self.last_timestamp) as i32 as i64,
+        }
+        self.last_timestamp = timestamp;
+        self.ticks
+    }
+}
+
+/// Muxes depacketized frames into a MPEG transport stream with a single
+/// program, e.g. for HLS segmenters or `udp://` re-streamers.
+///
+/// Every track gets its own PID. Timestamps are taken from the RTP
+/// timestamps of the frames, tracks with a [`SourceClock`] are put on a
+/// common timeline via their sender reports, so they stay in sync. The PCR
+/// is carried by the first video track, or the first track without video.
+#[derive(Debug, Default)]
+pub struct TsMuxer {
+    tracks: Vec<Track>,
+    // Capture time the timeline of tracks with a clock starts at
+    epoch: Option<SystemTime>,
+    pat_continuity: u8,
+    pmt_continuity: u8,
+    pmt_version: u8,
+    psi_pending: bool,
+    last_psi: Option<i64>,
+}
+
+impl TsMuxer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a H.264 or H.265 track and returns its PID. The frames must be
+    /// in Annex B format. AAC tracks are added with [`TsMuxer::add_aac_track`].
+    pub fn add_track(&mut self, track: usize, frame_type: FrameType, clock_rate: u32) -> Result<u16> {
+        let stream_type = match frame_type {
+            FrameType::H264 => 0x1b,
+            FrameType::H265 => 0x24,
+            FrameType::AAC => return Err(Error::InvalidAacConfig),
+            _ => return Err(Error::UnsupportedCodec(frame_type)),
+        };
+        self.insert_track(track, frame_type, stream_type, clock_rate, None)
+    }
+
+    /// Adds an AAC track and returns its PID, the raw access units are
+    /// framed with ADTS headers built from the AudioSpecificConfig, e.g. of
+    /// [`AacDepacketizer::config`](crate::rtp::AacDepacketizer::config)
+    pub fn add_aac_track(&mut self, track: usize, clock_rate: u32, config: &[u8]) -> Result<u16> {
+        let adts = AdtsConfig::parse(config)?;
+        self.insert_track(track, FrameType::AAC, 0x0f, clock_rate, Some(adts))
+    }
+
+    fn insert_track(
+        &mut self,
+        id: usize,
+        frame_type: FrameType,
+        stream_type: u8,
+        clock_rate: u32,
+        adts: Option<AdtsConfig>,
+    ) -> Result<u16> {
+        if clock_rate == 0 {
+            return Err(Error::InvalidClockRate(id));
+        }
+        if self.tracks.iter().any(|t| t.id == id) {
+            return Err(Error::DuplicateTrack(id));
+        }
+        let pid = FIRST_ES_PID + self.tracks.len() as u16;
+        let same_kind = |t: &&Track| t.is_video() == adts.is_none();
+        let index = self.tracks.iter().filter(same_kind).count() as u8;
+        let stream_id = if adts.is_none() { 0xe0 } else { 0xc0 } + index;
+        self.tracks.push(Track {
+            id,
+            pid,
+            stream_type,
+            stream_id,
+            frame_type,
+            clock_rate,
+            adts,
+            clock: None,
+            first_timestamp: None,
+            last_timestamp: 0,
+            ticks: 0,
+            continuity: 0,
+        });
+        self.pmt_version = (self.pmt_version + 1) & 0x1f;
+        self.psi_pending = true;
+        Ok(pid)
+    }
+
+    /// Maps the RTP timestamps of the track to wall clock time from now on
+    pub fn set_clock(&mut self, track: usize, clock: SourceClock) -> Result<()> {
+        self.track_mut(track)?.clock = Some(clock);
+        Ok(())
+    }
+
+    /// Updates the clock of the track from its sender report
+    pub fn handle_sender_report(&mut self, track: usize, report: &SenderReport) -> Result<()> {
+        let track = self.track_mut(track)?;
+        track.clock = Some(SourceClock {
+            clock_rate: track.clock_rate,
+            scale: (1, 1),
+            ntp: NtpTimestamp(report.ntp_timestamp()),
+            rtp_timestamp: report.rtp_ts(),
+        });
+        Ok(())
+    }
+
+    fn track_mut(&mut self, track: usize) -> Result<&mut Track> {
+        self.tracks
+            .iter_mut()
+            .find(|t| t.id == track)
+            .ok_or(Error::UnknownTrack(track))
+    }
+
+    /// PID of the track carrying the PCR
+    fn pcr_pid(&self) -> u16 {
+        self.tracks
+            .iter()
+            .find(|t| t.is_video())
+            .or(self.tracks.first())
+            .map_or(0x1fff, |t| t.pid)
+    }
+
+    /// Appends the TS packets of the frame to `out`, preceded by PAT and
+    /// PMT when they are due
+    pub fn write_frame(&mut self, track: usize, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        let pcr_pid = self.pcr_pid();
+        let index = self
+            .tracks
+            .iter()
+            .position(|t| t.id == track)
+            .ok_or(Error::UnknownTrack(track))?;
+        let pts = self.presentation_time(index, frame.timestamp);
+        let track = &self.tracks[index];
+        let is_pcr = track.pid == pcr_pid;
+        let keyframe = frame.is_keyframe();
+
+        let psi_due = self.last_psi.is_none_or(|last| pts - last >= PSI_INTERVAL);
+        if self.psi_pending || (is_pcr && track.is_video() && keyframe) || psi_due {
+            self.write_psi(pcr_pid, out);
+            self.psi_pending = false;
+            self.last_psi = Some(pts);
+        }
+
+        let track = &mut self.tracks[index];
+        let payload = elementary_stream(track, frame);
+        let pes = pes_packet(track.stream_id, pts & TIMESTAMP_MASK, &payload, track.is_video());
+        let pcr = is_pcr.then(|| (pts - PCR_DELAY).max(0) & TIMESTAMP_MASK);
+        let random_access = keyframe && frame.media_type == MediaType::Video;
+        let mut pos = write_packet(out, track.pid, &mut track.continuity, true, random_access, pcr, &pes);
+        while pos < pes.len() {
+            pos += write_packet(out, track.pid, &mut track.continuity, false, false, None, &pes[pos..]);
+        }
+        Ok(())
+    }
+
+    /// Presentation time of the timestamp in 90 kHz ticks
+    fn presentation_time(&mut self, index: usize, timestamp: u32) -> i64 {
+        let track = &mut self.tracks[index];
+        let ticks = track.relative_ticks(timestamp);
+        let pts = match track.clock {
+            Some(clock) => {
+                let capture = clock.capture_time(timestamp);
+                let epoch = *self.epoch.get_or_insert(capture);
+                match capture.duration_since(epoch) {
+                    Ok(d) => (d.as_micros() * 9 / 100) as i64,
+                    Err(e) => -((e.duration().as_micros() * 9 / 100) as i64),
+                }
+            }
+            None => ticks * 90_000 / track.clock_rate as i64,
+        };
+        (pts + PTS_OFFSET).max(0)
+    }
+
+    fn write_psi(&mut self, pcr_pid: u16, out: &mut Vec<u8>) {
+        let mut pat = vec![0x00, 0xb0, 0x00];
+        pat.extend_from_slice(&[0x00, 0x01, 0xc1, 0x00, 0x00]);
+        pat.extend_from_slice(&PROGRAM_NUMBER.to_be_bytes());
+        pat.extend_from_slice(&(0xe000 | PMT_PID).to_be_bytes());
+        write_section(out, PAT_PID, &mut self.pat_continuity, pat);
+
+        let mut pmt = vec![0x02, 0xb0, 0x00];
+        pmt.extend_from_slice(&PROGRAM_NUMBER.to_be_bytes());
+        pmt.extend_from_slice(&[0xc1 | (self.pmt_version << 1), 0x00, 0x00]);
+        pmt.extend_from_slice(&(0xe000 | pcr_pid).to_be_bytes());
+        pmt.extend_from_slice(&[0xf0, 0x00]);
+        for track in &self.tracks {
+            pmt.push(track.stream_type);
+            pmt.extend_from_slice(&(0xe000 | track.pid).to_be_bytes());
+            pmt.extend_from_slice(&[0xf0, 0x00]);
+        }
+        write_section(out, PMT_PID, &mut self.pmt_continuity, pmt);
+    }
+}
+
+/// The frame data as carried in the PES packet: Annex B with an access unit
+/// delimiter for video, ADTS framed for AAC
+fn elementary_stream(track: &Track, frame: &Frame) -> Vec<u8> {
+    let aud: &[u8] = match track.frame_type {
+        FrameType::H264 => &[0, 0, 0, 1, 0x09, 0xf0],
+        FrameType::H265 => &[0, 0, 0, 1, 0x46, 0x01, 0x50],
+        _ => &[],
+    };
+    let has_aud = NalUnits::new(&frame.data)
+        .next()
+        .and_then(|nal| nal_type(track.frame_type, nal))
+        .is_some_and(|t| (track.frame_type == FrameType::H264 && t == 9) || t == 35);
+    let mut data = Vec::with_capacity(frame.data.len() + 7);
+    match track.adts {
+        Some(adts) => data.extend_from_slice(&adts.header(frame.data.len())),
+        None if !has_aud => data.extend_from_slice(aud),
+        None => {}
+    }
+    data.extend_from_slice(&frame.data);
+    data
+}
+
+fn pes_packet(stream_id: u8, pts: i64, payload: &[u8], unbounded: bool) -> Vec<u8> {
+    let length = 8 + payload.len();
+    // Video PES packets may exceed the 16 bit length, 0 means unspecified
+    let length = if length > u16::MAX as usize || unbounded {
+        0
+    } else {
+        length as u16
+    };
+    let mut pes = Vec::with_capacity(14 + payload.len());
+    pes.extend_from_slice(&[0x00, 0x00, 0x01, stream_id]);
+    pes.extend_from_slice(&length.to_be_bytes());
+    pes.extend_from_slice(&[0x80, 0x80, 0x05]);
+    let pts = pts as u64;
+    pes.extend_from_slice(&[
+        0x21 | ((pts >> 29) as u8 & 0x0e),
+        (pts >> 22) as u8,
+        ((pts >> 14) as u8 & 0xfe) | 0x01,
+        (pts >> 7) as u8,
+        ((pts << 1) as u8 & 0xfe) | 0x01,
+    ]);
+    pes.extend_from_slice(payload);
+    pes
+}
+
+/// Writes one TS packet with as much of `payload` as fits, the rest of a
+/// short payload is filled with adaptation field stuffing. Returns the
+/// payload bytes written.
+fn write_packet(
+    out: &mut Vec<u8>,
+    pid: u16,
+    continuity: &mut u8,
+    start: bool,
+    random_access: bool,
+    pcr: Option<i64>,
+    payload: &[u8],
+) -> usize {
+    // Adaptation field without its length byte
+    let mut adaptation = Vec::new();
+    if random_access || pcr.is_some() {
+        adaptation.push(((random_access as u8) << 6) | ((pcr.is_some() as u8) << 4));
+        if let Some(pcr) = pcr {
+            let base = pcr as u64;
+            adaptation.extend_from_slice(&[
+                (base >> 25) as u8,
+                (base >> 17) as u8,
+                (base >> 9) as u8,
+                (base >> 1) as u8,
+                ((base as u8 & 0x01) << 7) | 0x7e,
+                0x00,
+            ]);
+        }
+    }
+    let mut has_adaptation = !adaptation.is_empty();
+    let space = TS_PACKET_SIZE - 4 - if has_adaptation { 1 + adaptation.len() } else { 0 };
+    let n = payload.len().min(space);
+    let stuffing = space - n;
+    if stuffing > 0 {
+        if !has_adaptation && stuffing > 1 {
+            adaptation.push(0x00);
+            adaptation.resize(stuffing - 1, 0xff);
+        } else if has_adaptation {
+            adaptation.resize(adaptation.len() + stuffing, 0xff);
+        }
+        // A single byte of stuffing is the length byte of an empty field
+        has_adaptation = true;
+    }
+    out.extend_from_slice(&[
+        0x47,
+        ((start as u8) << 6) | ((pid >> 8) as u8 & 0x1f),
+        pid as u8,
+        if has_adaptation { 0x30 } else { 0x10 } | *continuity,
+    ]);
+    if has_adaptation {
+        out.push(adaptation.len() as u8);
+        out.extend_from_slice(&adaptation);
+    }
+    out.extend_from_slice(&payload[..n]);
+    *continuity = (*continuity + 1) & 0x0f;
+    n
+}
+
+/// Writes a PSI section, `section` starts with the table id and has room
+/// for the 12 bit length which is filled in along with the CRC
+fn write_section(out: &mut Vec<u8>, pid: u16, continuity: &mut u8, mut section: Vec<u8>) {
+    let length = (section.len() - 3 + 4) as u16;
+    section[1] = (section[1] & 0xf0) | (length >> 8) as u8;
+    section[2] = length as u8;
+    let crc = crc32(&section);
+    section.extend_from_slice(&crc.to_be_bytes());
+    out.extend_from_slice(&[0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10 | *continuity]);
+    out.push(0x00);
+    out.extend_from_slice(&section);
+    out.resize(out.len() + TS_PACKET_SIZE - 5 - section.len(), 0xff);
+    *continuity = (*continuity + 1) & 0x0f;
+}
+
+/// CRC-32/MPEG-2 of PSI sections
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FrameMetadata;
+    use std::time::Duration;
+
+    fn frame(media_type: MediaType, frame_type: FrameType, timestamp: u32, data: Vec<u8>) -> Frame {
+        Frame {
+            media_type,
+            frame_type,
+            timestamp,
+            data,
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    fn split_packets(out: &[u8]) -> Vec<&[u8]> {
+        assert_eq!(out.len() % TS_PACKET_SIZE, 0);
+        out.chunks(TS_PACKET_SIZE).collect()
+    }
+
+    fn pid(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[1] & 0x1f, packet[2]])
+    }
+
+    fn pes_pts(pes: &[u8]) -> u64 {
+        let p = &pes[9..14];
+        ((p[0] as u64 & 0x0e) << 29)
+            | ((p[1] as u64) << 22)
+            | ((p[2] as u64 & 0xfe) << 14)
+            | ((p[3] as u64) << 7)
+            | ((p[4] as u64) >> 1)
+    }
+
+    /// Payload of the packet after the adaptation field
+    fn payload(packet: &[u8]) -> &[u8] {
+        match packet[3] & 0x20 {
+            0 => &packet[4..],
+            _ => &packet[5 + packet[4] as usize..],
+        }
+    }
+
+    #[test]
+    fn test_crc32() {
+        // PAT of a single program with the PMT on PID 0x1000
+        let pat = [0x00, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x00, 0x01, 0xf0, 0x00];
+        assert_eq!(crc32(&pat), 0x2ab104b2);
+    }
+
+    #[test]
+    fn test_mux_video_and_audio() {
+        let mut muxer = TsMuxer::new();
+        assert_eq!(muxer.add_aac_track(1, 48000, &[0x11, 0x90]).unwrap(), 0x100);
+        assert_eq!(muxer.add_track(0, FrameType::H264, 90000).unwrap(), 0x101);
+        assert!(matches!(
+            muxer.add_track(2, FrameType::Opus, 48000),
+            Err(Error::UnsupportedCodec(FrameType::Opus))
+        ));
+
+        let idr = [0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00];
+        let mut out = Vec::new();
+        muxer
+            .write_frame(
+                0,
+                &frame(MediaType::Video, FrameType::H264, 1000, idr.repeat(50)),
+                &mut out,
+            )
+            .unwrap();
+        let packets = split_packets(&out);
+        assert!(packets.iter().all(|p| p[0] == 0x47));
+        assert_eq!(pid(packets[0]), PAT_PID);
+        assert_eq!(pid(packets[1]), PMT_PID);
+        // The PCR PID is the video track, followed by both streams
+        let pmt = &packets[1][5..];
+        assert_eq!(u16::from_be_bytes([pmt[8] & 0x1f, pmt[9]]), 0x101);
+        assert_eq!(&pmt[12..17], &[0x0f, 0xe1, 0x00, 0xf0, 0x00]);
+        assert_eq!(&pmt[17..22], &[0x1b, 0xe1, 0x01, 0xf0, 0x00]);
+
+        // Random access and PCR in the adaptation field of the first packet
+        let first = packets[2];
+        assert_eq!(pid(first), 0x101);
+        assert_eq!(first[1] & 0x40, 0x40);
+        assert_eq!(first[5], 0x50);
+        let pes = payload(first);
+        assert_eq!(&pes[..4], &[0, 0, 1, 0xe0]);
+        assert_eq!(pes_pts(pes), PTS_OFFSET as u64);
+        assert_eq!(&pes[14..20], &[0, 0, 0, 1, 0x09, 0xf0]);
+        let video: Vec<u8> = packets[2..].iter().flat_map(|p| payload(p).to_vec()).collect();
+        assert_eq!(video.len(), 14 + 6 + 400);
+        assert_eq!(packets[3][3] & 0x0f, 1);
+
+        // Audio is framed with ADTS, its PTS follows the RTP timestamps
+        out.clear();
+        let aac = frame(MediaType::Audio, FrameType::AAC, 5000, vec![0x21; 10]);
+        muxer.write_frame(1, &aac, &mut out).unwrap();
+        out.clear();
+        let aac = frame(MediaType::Audio, FrameType::AAC, 5000 + 4800, vec![0x21; 10]);
+        muxer.write_frame(1, &aac, &mut out).unwrap();
+        let packets = split_packets(&out);
+        assert_eq!(packets.len(), 1);
+        let pes = payload(packets[0]);
+        assert_eq!(&pes[..6], &[0, 0, 1, 0xc0, 0, 25]);
+        assert_eq!(pes_pts(pes), PTS_OFFSET as u64 + 9000);
+        assert_eq!(&pes[14..21], &[0xff, 0xf1, 0x4c, 0x80, 0x02, 0x3f, 0xfc]);
+    }
+
+    #[test]
+    fn test_mux_with_clocks() {
+        let mut muxer = TsMuxer::new();
+        muxer.add_track(0, FrameType::H265, 90000).unwrap();
+        muxer.add_aac_track(1, 8000, &[0x15, 0x88]).unwrap();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let ntp = NtpTimestamp::from_system_time(now);
+        let clock = |clock_rate, rtp_timestamp| SourceClock {
+            clock_rate,
+            scale: (1, 1),
+            ntp,
+            rtp_timestamp,
+        };
+        muxer.set_clock(0, clock(90000, 10_000)).unwrap();
+        muxer.set_clock(1, clock(8000, 700)).unwrap();
+
+        let mut out = Vec::new();
+        let data = vec![0, 0, 0, 1, 0x26, 0x01, 0xaf];
+        muxer
+            .write_frame(0, &frame(MediaType::Video, FrameType::H265, 10_000, data), &mut out)
+            .unwrap();
+        out.clear();
+        // Captured 100 ms after the video frame
+        let aac = frame(MediaType::Audio, FrameType::AAC, 1500, vec![0; 4]);
+        muxer.write_frame(1, &aac, &mut out).unwrap();
+        let packets = split_packets(&out);
+        assert_eq!(pes_pts(payload(packets[packets.len() - 1])), PTS_OFFSET as u64 + 9000);
+        assert!(matches!(muxer.set_clock(5, clock(1, 0)), Err(Error::UnknownTrack(5))));
+    }
+}
@@ -0,0 +1,128 @@
+use super::{TsError, TsMuxer};
+use crate::task;
+use crate::types::Frame;
+use bytes::Bytes;
+use std::io;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Writes the transport stream of the frames to `W`, e.g. a file or the
+/// stdin of a HLS segmenter.
+#[derive(Debug)]
+pub struct TsWriter<W> {
+    muxer: TsMuxer,
+    writer: W,
+    buf: Vec<u8>,
+}
+
+impl<W: AsyncWrite + Unpin> TsWriter<W> {
+    /// `muxer` must already have the tracks of the frames
+    pub fn new(muxer: TsMuxer, writer: W) -> Self {
+        Self {
+            muxer,
+            writer,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn muxer_mut(&mut self) -> &mut TsMuxer {
+        &mut self.muxer
+    }
+
+    pub async fn write_frame(&mut self, track: usize, frame: &Frame) -> io::Result<()> {
+        self.buf.clear();
+        self.muxer.write_frame(track, frame, &mut self.buf)?;
+        self.writer.write_all(&self.buf).await
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush().await
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Flushes and shuts the writer down, e.g. so a file is complete on
+    /// disk before the process exits, and returns it
+    pub async fn finish(mut self) -> io::Result<W> {
+        self.writer.flush().await?;
+        self.writer.shutdown().await?;
+        Ok(self.writer)
+    }
+}
+
+/// Turns the frames of `frames` into transport stream chunks of whole
+/// packets, one chunk per frame, which makes the crate a RTSP to TS
+/// bridge. Frames of unknown tracks are dropped. The task ends when either
+/// side is closed.
+pub fn bridge(
+    mut muxer: TsMuxer,
+    mut frames: mpsc::Receiver<(usize, Frame)>,
+    capacity: usize,
+) -> mpsc::Receiver<Bytes> {
+    let (tx, rx) = mpsc::channel(capacity);
+    task::spawn(task::TS_BRIDGE, async move {
+        while let Some((track, frame)) = frames.recv().await {
+            let mut buf = Vec::new();
+            if let Err(e) = muxer.write_frame(track, &frame, &mut buf) {
+                log::warn!("Dropping frame: {}", e);
+                continue;
+            }
+            if tx.send(Bytes::from(buf)).await.is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Errors of the muxer as seen by the writer
+impl From<TsError> for io::Error {
+    fn from(e: TsError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ts::TS_PACKET_SIZE;
+    use crate::types::{FrameMetadata, FrameType, MediaType};
+
+    fn frame(timestamp: u32) -> Frame {
+        Frame {
+            media_type: MediaType::Video,
+            frame_type: FrameType::H264,
+            timestamp,
+            data: vec![0, 0, 0, 1, 0x41, 0x9a, 0x02],
+            metadata: FrameMetadata::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ts_writer() {
+        let mut muxer = TsMuxer::new();
+        muxer.add_track(0, FrameType::H264, 90000).unwrap();
+        let mut writer = TsWriter::new(muxer, Vec::new());
+        writer.write_frame(0, &frame(0)).await.unwrap();
+        writer.write_frame(0, &frame(3000)).await.unwrap();
+        assert!(writer.write_frame(1, &frame(3000)).await.is_err());
+        // PAT, PMT and one packet per frame
+        let out = writer.finish().await.unwrap();
+        assert_eq!(out.len(), 4 * TS_PACKET_SIZE);
+    }
+
+    #[tokio::test]
+    async fn test_ts_bridge() {
+        let mut muxer = TsMuxer::new();
+        muxer.add_track(0, FrameType::H264, 90000).unwrap();
+        let (tx, rx) = mpsc::channel(4);
+        let mut chunks = bridge(muxer, rx, 4);
+        tx.send((1, frame(0))).await.unwrap();
+        tx.send((0, frame(0))).await.unwrap();
+        drop(tx);
+        assert_eq!(chunks.recv().await.unwrap().len(), 3 * TS_PACKET_SIZE);
+        assert!(chunks.recv().await.is_none());
+    }
+}
@@ -1,8 +1,15 @@
+use crate::codec::{is_random_access, NalUnits, SeiMessage};
+use crate::rtp::{AudioLevel, VideoOrientation};
+use std::io::Result;
+use tokio::io::AsyncReadExt;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MediaType {
     Video,
     Audio,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FrameType {
     H264,
     H265,
@@ -18,15 +25,71 @@ pub enum FrameType {
     AV1,
     JPEG,
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Frame {
     pub media_type: MediaType,
     pub frame_type: FrameType,
+    /// RTP timestamp of the first sample of the frame
+    pub timestamp: u32,
     pub data: Vec<u8>,
+    pub metadata: FrameMetadata,
+}
+
+/// Information about a frame besides its data, filled by pipeline stages
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FrameMetadata {
+    /// SEI messages of H.264/H.265 frames, see [`ParseSei`](crate::filter::ParseSei)
+    pub sei: Vec<SeiMessage>,
+    /// The RTP packets the frame was assembled from, set by the depacketizer
+    pub origin: Option<FrameOrigin>,
+    /// Level of the loudest packet of an audio frame, from the RTP header
+    /// extension, see [`FrameStream`](crate::rtp::FrameStream)
+    pub audio_level: Option<AudioLevel>,
+    /// Orientation of the video as last signaled by the RTP header extension
+    pub orientation: Option<VideoOrientation>,
+}
+
+/// Source and sequence numbers of the RTP packets of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameOrigin {
+    pub ssrc: u32,
+    pub first_sequence: u16,
+    pub last_sequence: u16,
+}
+
+impl FrameOrigin {
+    pub fn new(ssrc: u32, sequence: u16) -> Self {
+        Self {
+            ssrc,
+            first_sequence: sequence,
+            last_sequence: sequence,
+        }
+    }
+
+    /// Number of packets from the first to the last sequence number
+    pub fn packets(&self) -> u16 {
+        self.last_sequence.wrapping_sub(self.first_sequence).wrapping_add(1)
+    }
+}
+
+impl Frame {
+    /// Whether decoding can start at this frame. H.264 and H.265 data is
+    /// expected in Annex B format, frames of codecs without inter
+    /// prediction are always keyframes.
+    pub fn is_keyframe(&self) -> bool {
+        match self.frame_type {
+            FrameType::H264 | FrameType::H265 => {
+                NalUnits::new(&self.data).any(|nal| is_random_access(self.frame_type, nal))
+            }
+            // Inverted key frame flag of the frame tag (RFC 6386)
+            FrameType::VP8 => self.data.first().is_some_and(|b| b & 1 == 0),
+            _ => true,
+        }
+    }
 }
 
+#[allow(async_fn_in_trait)]
 pub trait AsyncReadFrame {
-    async fn read_frame<Stream: AsyncReadExt + Unpin>(
-        &mut self,
-        stream: &mut Stream,
-    ) -> Result<Frame>;
+    async fn read_frame<Stream: AsyncReadExt + Unpin>(&mut self, stream: &mut Stream) -> Result<Frame>;
 }
@@ -0,0 +1,72 @@
+use mm_streamer::rtsp::{IncomingRequest, ResponseBuilder, Status};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt::Write;
+
+/// Counts the allocations of the current thread, tests run on threads of
+/// their own so they don't count each other's
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations of `f` per run, averaged over many runs
+fn allocations<T>(mut f: impl FnMut() -> T) -> f64 {
+    const RUNS: usize = 1000;
+    let before = ALLOCATIONS.with(Cell::get);
+    for _ in 0..RUNS {
+        std::hint::black_box(f());
+    }
+    (ALLOCATIONS.with(Cell::get) - before) as f64 / RUNS as f64
+}
+
+const REQUEST: &[u8] = b"SETUP rtsp://camera/live/trackID=0 RTSP/1.0\r\n\
+                         CSeq: 3\r\n\
+                         User-Agent: mm_streamer\r\n\
+                         Authorization: Digest username=\"admin\", realm=\"camera\", nonce=\"0a1b2c\"\r\n\
+                         Session: 0123456789ABCDEF\r\n\
+                         Transport: RTP/AVP/TCP;unicast;interleaved=0-1\r\n\
+                         Accept: application/sdp\r\n\r\n";
+
+#[test]
+fn test_request_allocations() {
+    // The uri, the header names and values and their ranges
+    let n = allocations(|| IncomingRequest::parse(REQUEST).unwrap().unwrap());
+    assert!(n <= 3.0, "{} allocations per parsed request", n);
+}
+
+#[test]
+fn test_response_allocations() {
+    // The header lines, the reused buffer of a connection doesn't allocate
+    let mut buffer = String::with_capacity(1024);
+    let n = allocations(|| {
+        let response = ResponseBuilder::new(Status::OK, Some(3))
+            .header("Session", "0123456789ABCDEF;timeout=60")
+            .header("Transport", "RTP/AVP/TCP;unicast;interleaved=0-1")
+            .header("Range", "npt=0.000-");
+        buffer.clear();
+        write!(buffer, "{}", response).unwrap();
+        buffer.len()
+    });
+    assert!(n <= 1.0, "{} allocations per serialized response", n);
+}
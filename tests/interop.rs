@@ -0,0 +1,144 @@
+mod common;
+
+use common::*;
+use mm_streamer::rtsp::client::CommandError;
+use mm_streamer::rtsp::{LowerTransport, Method, Transport};
+use tokio::net::UdpSocket;
+
+#[tokio::test]
+#[ignore]
+async fn test_options() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX") else {
+        return;
+    };
+    let client = Client::connect(&url).await;
+    let methods = client.options().await.unwrap();
+    for method in [Method::Describe, Method::Setup, Method::Play, Method::Teardown] {
+        assert!(methods.contains(&method), "{} not supported", method);
+    }
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_describe() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX") else {
+        return;
+    };
+    let client = Client::connect(&url).await;
+    let sdp = client.describe().await.unwrap();
+    assert!(sdp.to_string().contains("m=video"));
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_describe_with_credentials() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX_AUTH") else {
+        return;
+    };
+    let client = Client::connect(&url).await;
+    client.describe().await.unwrap();
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_describe_with_wrong_password() {
+    let Some(mut url) = endpoint("RTSP_IT_MEDIAMTX_AUTH") else {
+        return;
+    };
+    url.set_password(Some("wrong")).unwrap();
+    let client = Client::connect(&url).await;
+    let result = client.describe().await;
+    assert!(
+        matches!(result, Err(CommandError::Unauthorized)),
+        "unexpected result {:?}",
+        result.err()
+    );
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_describe_without_credentials() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX_AUTH") else {
+        return;
+    };
+    let (url, _) = split_credentials(&url);
+    let client = Client::connect(&url).await;
+    assert!(matches!(client.describe().await, Err(CommandError::Unauthorized)));
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_setup_play_tcp() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX") else {
+        return;
+    };
+    let client = Client::connect(&url).await;
+    let sdp = client.describe().await.unwrap();
+    let track = first_track_url(&client.url, &sdp).expect("the stream has no track with a control");
+    let response = client.setup(&track, Transport::tcp((0, 1))).await.unwrap();
+    assert_eq!(response.transport.lower, LowerTransport::Tcp);
+    assert_eq!(response.transport.interleaved, Some((0, 1)));
+    client.play().await.unwrap();
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_setup_play_teardown_udp() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX") else {
+        return;
+    };
+    let rtp = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    let rtcp = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    let ports = (rtp.local_addr().unwrap().port(), rtcp.local_addr().unwrap().port());
+    let client = Client::connect(&url).await;
+    let sdp = client.describe().await.unwrap();
+    let track = first_track_url(&client.url, &sdp).expect("the stream has no track with a control");
+    let response = client.setup(&track, Transport::udp(ports)).await.unwrap();
+    assert_eq!(response.transport.lower, LowerTransport::Udp);
+    assert_eq!(response.transport.client_port, Some(ports));
+    client.play().await.unwrap();
+    let mut buf = vec![0u8; 2048];
+    let n = timeout(rtp.recv(&mut buf)).await.unwrap();
+    assert!(mm_streamer::rtp::Packet::new(buf[..n].to_vec()).is_ok());
+    client.teardown().await.unwrap();
+    let result = client.play().await;
+    assert!(result.is_err(), "play after teardown succeeded");
+    client.shutdown().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_reconnect() {
+    let Some(url) = endpoint("RTSP_IT_MEDIAMTX") else {
+        return;
+    };
+    for _ in 0..3 {
+        let client = Client::connect(&url).await;
+        client.describe().await.unwrap();
+        client.shutdown().await;
+    }
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_live555_setup_play_teardown() {
+    let Some(url) = endpoint("RTSP_IT_LIVE555") else { return };
+    let rtp = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    let rtcp = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    let ports = (rtp.local_addr().unwrap().port(), rtcp.local_addr().unwrap().port());
+    let client = Client::connect(&url).await;
+    let sdp = client.describe().await.unwrap();
+    let track = first_track_url(&client.url, &sdp).expect("the stream has no track with a control");
+    client.setup(&track, Transport::udp(ports)).await.unwrap();
+    client.play().await.unwrap();
+    let mut buf = vec![0u8; 2048];
+    timeout(rtp.recv(&mut buf)).await.unwrap();
+    client.teardown().await.unwrap();
+    client.shutdown().await;
+}